@@ -0,0 +1,78 @@
+//! Compares adding and removing components that use table and sparse storage.
+use bencher::{benchmark_group, benchmark_main, Bencher};
+use kecs::*;
+
+#[derive(Clone, Component)]
+struct Position(f32);
+
+#[derive(Clone, Component)]
+struct Velocity(f32);
+
+#[derive(Clone, Component)]
+struct TableMarker;
+
+#[derive(Clone, Component)]
+#[component(storage = "sparse")]
+struct SparseMarker;
+
+const ENTITY_COUNT: usize = 1000;
+
+fn world_with_entities() -> (World, Vec<Entity>) {
+    let mut world = World::new();
+    let entities = (0..ENTITY_COUNT)
+        .map(|i| world.spawn((Position(i as f32), Velocity(1.0))))
+        .collect();
+    (world, entities)
+}
+
+fn add_remove<T: ComponentTrait>(bench: &mut Bencher, component: impl Fn() -> T) {
+    let (mut world, entities) = world_with_entities();
+    bench.iter(|| {
+        for entity in &entities {
+            world.add_component(*entity, component()).unwrap();
+        }
+        for entity in &entities {
+            world.remove_component::<T>(*entity).unwrap();
+        }
+    });
+}
+
+fn add_remove_table(bench: &mut Bencher) {
+    add_remove(bench, || TableMarker);
+}
+
+fn add_remove_sparse(bench: &mut Bencher) {
+    add_remove(bench, || SparseMarker);
+}
+
+fn query<T: ComponentTrait>(bench: &mut Bencher, component: impl Fn() -> T) {
+    let (mut world, entities) = world_with_entities();
+    for entity in entities.iter().step_by(2) {
+        world.add_component(*entity, component()).unwrap();
+    }
+    bench.iter(|| {
+        (|mut query: Query<(&mut Position, &Velocity, &T)>| {
+            for (position, velocity, _) in &mut query {
+                position.0 += velocity.0;
+            }
+        })
+        .run(&world)
+    });
+}
+
+fn query_table(bench: &mut Bencher) {
+    query(bench, || TableMarker);
+}
+
+fn query_sparse(bench: &mut Bencher) {
+    query(bench, || SparseMarker);
+}
+
+benchmark_group!(
+    benches,
+    add_remove_table,
+    add_remove_sparse,
+    query_table,
+    query_sparse
+);
+benchmark_main!(benches);
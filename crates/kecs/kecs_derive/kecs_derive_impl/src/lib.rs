@@ -1,15 +1,16 @@
 use kreflect_common::*;
 
 pub fn kecs_component_impl(value: &Value) -> String {
-    let (name, generic_parameters) = match value {
-        Value::Struct(s) => (&s.name, &s.generic_parameters),
-        Value::Enum(e) => (&e.name, &e.generic_parameters),
+    let (name, generic_parameters, attributes) = match value {
+        Value::Struct(s) => (&s.name, &s.generic_parameters, &s.attributes),
+        Value::Enum(e) => (&e.name, &e.generic_parameters, &e.attributes),
         _ => panic!(),
     };
 
     format!(
         r#"
         impl{} ComponentTrait for {}{} {{
+            {}
             fn clone_components(
                 _entity_migrator: &mut EntityMigrator,
                 items: &[Self],
@@ -21,19 +22,21 @@ pub fn kecs_component_impl(value: &Value) -> String {
         &generic_parameters.as_impl_args(),
         &name,
         &generic_parameters.as_args(),
+        storage_impl(attributes),
     )
 }
 
 pub fn kecs_non_clone_component_impl(value: &Value) -> String {
-    let (name, generic_parameters) = match value {
-        Value::Struct(s) => (&s.name, &s.generic_parameters),
-        Value::Enum(e) => (&e.name, &e.generic_parameters),
+    let (name, generic_parameters, attributes) = match value {
+        Value::Struct(s) => (&s.name, &s.generic_parameters, &s.attributes),
+        Value::Enum(e) => (&e.name, &e.generic_parameters, &e.attributes),
         _ => panic!(),
     };
 
     format!(
         r#"
         impl{} ComponentTrait for {}{} {{
+            {}
             fn clone_components(
                 _entity_migrator: &mut EntityMigrator,
                 _items: &[Self],
@@ -45,5 +48,28 @@ pub fn kecs_non_clone_component_impl(value: &Value) -> String {
         &generic_parameters.as_impl_args(),
         &name,
         &generic_parameters.as_args(),
+        storage_impl(attributes),
     )
 }
+
+/// Reads `#[component(storage = "sparse")]` and declares the component's storage.
+fn storage_impl(attributes: &[Attribute]) -> &'static str {
+    for attribute in attributes {
+        if attribute.path.as_string() != "component" {
+            continue;
+        }
+        match attribute.tokens.as_slice() {
+            [Token::OpenParentheses, Token::Identifier(key), Token::Equal, Token::StringLiteral(storage), Token::CloseParentheses]
+                if key == "storage" =>
+            {
+                return match storage.trim_matches('"') {
+                    "table" => "",
+                    "sparse" => "const STORAGE: ComponentStorage = ComponentStorage::Sparse;",
+                    storage => panic!("Unknown component storage: {:?}", storage),
+                };
+            }
+            _ => panic!("Expected #[component(storage = \"sparse\")]"),
+        }
+    }
+    ""
+}
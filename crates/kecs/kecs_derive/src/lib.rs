@@ -1,6 +1,6 @@
 use kreflect_common::*;
 
-#[proc_macro_derive(Component, attributes(skip))]
+#[proc_macro_derive(Component, attributes(skip, component))]
 pub fn derive_component(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let mut rust_tokens = Vec::new();
     token_stream_to_rust_tokens(item, &mut rust_tokens);
@@ -16,7 +16,7 @@ pub fn derive_component(item: proc_macro::TokenStream) -> proc_macro::TokenStrea
     output_string.parse().unwrap()
 }
 
-#[proc_macro_derive(ManualSerdeComponent, attributes(skip))]
+#[proc_macro_derive(ManualSerdeComponent, attributes(skip, component))]
 pub fn manual_serde_component(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let mut rust_tokens = Vec::new();
     token_stream_to_rust_tokens(item, &mut rust_tokens);
@@ -27,7 +27,7 @@ pub fn manual_serde_component(item: proc_macro::TokenStream) -> proc_macro::Toke
     output_string.parse().unwrap()
 }
 
-#[proc_macro_derive(NotCloneComponent)]
+#[proc_macro_derive(NotCloneComponent, attributes(component))]
 pub fn derive_non_clone_component(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let mut rust_tokens = Vec::new();
     token_stream_to_rust_tokens(item, &mut rust_tokens);
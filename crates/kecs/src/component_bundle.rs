@@ -14,6 +14,8 @@ impl<C: ComponentTrait> ComponentBundleTrait for C {
 pub(crate) trait AnyComponentTrait: Any {
     fn new_archetype_channel(&self) -> ArchetypeChannel;
     fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn storage(&self) -> ComponentStorage;
+    fn insert_sparse(&mut self, world: &mut World, entity: Entity, change_tick: u64);
 }
 
 impl<Component: ComponentTrait> AnyComponentTrait for Option<Component> {
@@ -23,6 +25,14 @@ impl<Component: ComponentTrait> AnyComponentTrait for Option<Component> {
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
+    fn storage(&self) -> ComponentStorage {
+        Component::STORAGE
+    }
+    fn insert_sparse(&mut self, world: &mut World, entity: Entity, change_tick: u64) {
+        world
+            .sparse_storage_mut::<Component>()
+            .insert(entity, self.take().unwrap(), change_tick);
+    }
 }
 
 pub(crate) fn add_components_to_entity_inner(
@@ -34,8 +44,26 @@ pub(crate) fn add_components_to_entity_inner(
         .entities
         .get_entity_location(entity)
         .ok_or(KecsError::EntityMissing)?;
+    let change_tick = world.next_change_tick();
+
+    // Sort sparse components to the end and insert them, they don't affect the [Archetype].
+    components_and_component_ids.sort_unstable_by_key(|(component, component_id)| {
+        (
+            component.storage() == ComponentStorage::Sparse,
+            *component_id,
+        )
+    });
+    let table_components_count = components_and_component_ids
+        .partition_point(|(component, _)| component.storage() == ComponentStorage::Table);
+    let (components_and_component_ids, sparse_components) =
+        components_and_component_ids.split_at_mut(table_components_count);
+    for (component, _) in sparse_components {
+        component.insert_sparse(world, entity, change_tick);
+    }
+    if components_and_component_ids.is_empty() {
+        return Ok(());
+    }
 
-    components_and_component_ids.sort_unstable_by_key(|(_, component_id)| *component_id);
     let old_archetype = &world.archetypes[entity_location.archetype_index];
     let new_component_ids = merge_sorted_iter(
         old_archetype.channels.len() + components_and_component_ids.len(),
@@ -109,7 +137,7 @@ pub(crate) fn add_components_to_entity_inner(
         for channel in new_archetype.channels.iter_mut() {
             let component_and_component_id = &mut components_and_component_ids[component_index];
             if channel.component_id == component_and_component_id.1 {
-                channel.data.push(&mut *component_and_component_id.0);
+                channel.push(&mut *component_and_component_id.0, change_tick);
                 component_index += 1;
                 if component_index >= components_and_component_ids.len() {
                     break;
@@ -123,9 +151,10 @@ pub(crate) fn add_components_to_entity_inner(
         for channel in archetype.channels.iter_mut() {
             let component_and_component_id = &mut components_and_component_ids[component_index];
             if channel.component_id == component_and_component_id.1 {
-                channel.data.assign(
+                channel.assign(
                     entity_location.index_within_archetype,
                     &mut *component_and_component_id.0,
+                    change_tick,
                 );
                 component_index += 1;
                 if component_index >= components_and_component_ids.len() {
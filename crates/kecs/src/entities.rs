@@ -92,7 +92,10 @@ impl Entities {
     }
 
     pub fn get_entity_location(&self, entity: Entity) -> Option<EntityLocation> {
-        let (generation, location) = &self.generation_and_entity_location[entity.index as usize];
+        // Reserved [Entity]s may not have an entry yet.
+        let (generation, location) = self
+            .generation_and_entity_location
+            .get(entity.index as usize)?;
         if *generation != entity.generation {
             None
         } else {
@@ -177,21 +177,33 @@ impl HierarchyNode {
     /// Remove an [Entity], all its components, and all of its descendent [Entity]s, from the [World].
     /// A [KecsError] is returned if the entity does not exist.
     pub fn despawn_hierarchy(world: &mut World, entity: Entity) -> Result<(), KecsError> {
-        world.despawn(entity).unwrap();
-        if let Ok(hierarchy_node) = world
+        // Detach the [Entity] from its parent so the parent's children stay valid.
+        if let Some(parent) = world
             .get_component_mut::<HierarchyNode>(entity)
-            .map(|h| h.clone_hierarchy_node())
+            .ok()
+            .and_then(|h| h.parent)
         {
-            // Despawn all children and their siblings
-            let mut current_child = hierarchy_node.last_child;
-            while let Some(child) = current_child {
-                Self::despawn_hierarchy(world, child).unwrap();
-                current_child = world
-                    .get_component_mut::<HierarchyNode>(child)
-                    .map(|n| n.previous_sibling)
-                    .ok()
-                    .flatten();
-            }
+            Self::remove_child(world, parent, entity)?;
+        }
+        Self::despawn_descendents_and_self(world, entity)
+    }
+
+    fn despawn_descendents_and_self(world: &mut World, entity: Entity) -> Result<(), KecsError> {
+        // The [HierarchyNode] must be read before the [Entity] is despawned.
+        let last_child = world
+            .get_component_mut::<HierarchyNode>(entity)
+            .ok()
+            .and_then(|h| h.last_child);
+        world.despawn(entity)?;
+
+        // Despawn all children and their siblings
+        let mut current_child = last_child;
+        while let Some(child) = current_child {
+            current_child = world
+                .get_component_mut::<HierarchyNode>(child)
+                .ok()
+                .and_then(|n| n.previous_sibling);
+            Self::despawn_descendents_and_self(world, child)?;
         }
 
         Ok(())
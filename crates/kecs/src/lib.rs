@@ -4,6 +4,8 @@ mod world;
 pub use world::*;
 
 mod sparse_set;
+mod sparse_storage;
+pub use sparse_storage::*;
 mod storage_lookup;
 
 mod chained_iterator;
@@ -44,6 +46,7 @@ mod entities;
 pub use entities::*;
 
 pub mod hierarchy;
+pub mod relationship;
 
 #[cfg(test)]
 mod tests;
@@ -53,6 +56,7 @@ pub enum KecsError {
     NoMatchingComponent(&'static str),
     EntityMissing,
     ChannelExclusivelyLocked,
+    MultipleMatchingEntities,
 }
 
 impl KecsError {
@@ -68,7 +72,28 @@ pub(crate) fn get_component_id<T: 'static + ComponentTrait>() -> ComponentId {
     ComponentId(TypeId::of::<T>())
 }
 
+/// How a component type is stored in the [World].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentStorage {
+    /// Components are stored in [Archetype] tables, which are fast to iterate.
+    /// Adding or removing the component moves the [Entity] to a different [Archetype].
+    Table,
+    /// Components are stored in a sparse set outside of the [Archetype] tables.
+    /// Adding or removing the component is cheap, but iterating is slower.
+    /// Use this for components that are frequently added and removed, like marker components.
+    ///
+    /// Sparse components cannot be accessed as singletons.
+    Sparse,
+}
+
 pub trait ComponentTrait: 'static + Send + Sync + Sized {
+    /// Set with `#[component(storage = "sparse")]` when deriving [ComponentTrait].
+    const STORAGE: ComponentStorage = ComponentStorage::Table;
+
+    /// Called before an [Entity] with this component is despawned.
+    /// [relationship::Relation]s use this to keep related [Entity]s up to date.
+    const ON_DESPAWN: Option<fn(&mut World, Entity)> = None;
+
     fn get_component_id(&self) -> ComponentId {
         ComponentId(TypeId::of::<Self>())
     }
@@ -116,3 +141,18 @@ tuple_impls! { 9, (0, A), (1, B), (2, C), (3, D), (4, E), (5, F), (6, G), (7, H)
 tuple_impls! { 10, (0, A), (1, B), (2, C), (3, D), (4, E), (5, F), (6, G), (7, H), (8, I), (9, J)}
 tuple_impls! { 11, (0, A), (1, B), (2, C), (3, D), (4, E), (5, F), (6, G), (7, H), (8, I), (9, J), (10, K)}
 tuple_impls! { 12, (0, A), (1, B), (2, C), (3, D), (4, E), (5, F), (6, G), (7, H), (8, I), (9, J), (10, K), (11, L)}
+
+// Queries support more parameters than systems and bundles.
+// Implementing systems for more parameters makes compile times far worse.
+macro_rules! query_tuple_impls {
+    ( $count: tt, $( ($index: tt, $tuple:ident) ),*) => {
+        multi_iterator_impl! { $count, $( ($index, $tuple) ),*}
+        query_impls! { $count, $( ($index, $tuple) ),*}
+        query_iterator_impls! { $count, $( ($index, $tuple) ),*}
+    };
+}
+
+query_tuple_impls! { 13, (0, A), (1, B), (2, C), (3, D), (4, E), (5, F), (6, G), (7, H), (8, I), (9, J), (10, K), (11, L), (12, M)}
+query_tuple_impls! { 14, (0, A), (1, B), (2, C), (3, D), (4, E), (5, F), (6, G), (7, H), (8, I), (9, J), (10, K), (11, L), (12, M), (13, N)}
+query_tuple_impls! { 15, (0, A), (1, B), (2, C), (3, D), (4, E), (5, F), (6, G), (7, H), (8, I), (9, J), (10, K), (11, L), (12, M), (13, N), (14, O)}
+query_tuple_impls! { 16, (0, A), (1, B), (2, C), (3, D), (4, E), (5, F), (6, G), (7, H), (8, I), (9, J), (10, K), (11, L), (12, M), (13, N), (14, O), (15, P)}
@@ -1,17 +1,71 @@
+/// An iterator over the rows of an [crate::Archetype].
+/// A row is `None` if the [crate::Entity] in that row doesn't match the [crate::Query],
+/// which happens when the [crate::Entity] is missing a sparse component.
+#[doc(hidden)]
+pub trait RowIterator: Iterator {
+    type Row;
+    fn next_row(&mut self) -> Option<Option<Self::Row>>;
+}
+
+impl<R, I: Iterator<Item = Option<R>>> RowIterator for I {
+    type Row = R;
+    fn next_row(&mut self) -> Option<Option<Self::Row>> {
+        self.next()
+    }
+}
+
+/// A [RowIterator] that can be split into two iterators over different rows,
+/// which is used to iterate an [crate::Archetype] from multiple threads.
+#[doc(hidden)]
+pub trait SplitRowIterator: RowIterator + Sized {
+    /// Returns an iterator over the first `row` rows and an iterator over the rest.
+    fn split_at(self, row: usize) -> (Self, Self);
+}
+
 pub struct MultiIterator<ITERATOR>(pub(crate) ITERATOR);
 
 macro_rules! multi_iterator_impl {
     ( $count: tt, $( ($index: tt, $tuple:ident) ),* ) => {
-        impl<$( $tuple: Iterator,)*> MultiIterator<($( $tuple,)*)> {
+        impl<$( $tuple: RowIterator,)*> MultiIterator<($( $tuple,)*)> {
             pub fn new(iterators: ($( $tuple,)*)) -> Self {
                 Self(iterators)
             }
         }
 
-        impl<$( $tuple: Iterator,)*> Iterator for MultiIterator<($( $tuple,)*)> {
-            type Item = ($( $tuple::Item,)*);
+        impl<$( $tuple: RowIterator,)*> Iterator for MultiIterator<($( $tuple,)*)> {
+            type Item = Option<($( $tuple::Row,)*)>;
+
+            #[allow(non_snake_case, unreachable_patterns, clippy::unused_unit)]
             fn next(&mut self) -> Option<Self::Item> {
-                Some(($( self.0.$index.next()?,)*))
+                // Every iterator is advanced so they stay on the same row.
+                match ($( self.0.$index.next_row()?,)*) {
+                    ($( Some($tuple),)*) => Some(Some(($( $tuple,)*))),
+                    _ => Some(None),
+                }
+            }
+
+            #[allow(unused_mut)]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                // The shortest iterator determines the length.
+                let mut min = usize::MAX;
+                let mut max: Option<usize> = None;
+                $(
+                    let (i_min, i_max) = self.0.$index.size_hint();
+                    min = min.min(i_min);
+                    max = match (max, i_max) {
+                        (Some(max), Some(i_max)) => Some(max.min(i_max)),
+                        (max, i_max) => max.or(i_max),
+                    };
+                )*
+                (min, max)
+            }
+        }
+
+        impl<$( $tuple: SplitRowIterator,)*> SplitRowIterator for MultiIterator<($( $tuple,)*)> {
+            #[allow(unused_variables, clippy::unused_unit, clippy::let_unit_value)]
+            fn split_at(self, row: usize) -> (Self, Self) {
+                let halves = ($( self.0.$index.split_at(row),)*);
+                (Self(($( halves.$index.0,)*)), Self(($( halves.$index.1,)*)))
             }
         }
     }
@@ -1,3 +1,5 @@
+use crate::{RowIterator, SplitRowIterator};
+
 pub struct OptionIterator<T: Iterator> {
     len_remaining: usize,
     iter: Option<T>,
@@ -12,8 +14,9 @@ impl<T: Iterator> OptionIterator<T> {
     }
 }
 
-impl<T: Iterator> Iterator for OptionIterator<T> {
-    type Item = Option<T::Item>;
+impl<T: RowIterator> Iterator for OptionIterator<T> {
+    // Every row matches, the inner row is `None` if the [crate::Entity] doesn't have the components.
+    type Item = Option<Option<T::Row>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.len_remaining == 0 {
@@ -21,10 +24,32 @@ impl<T: Iterator> Iterator for OptionIterator<T> {
         }
 
         self.len_remaining -= 1;
-        if let Some(iter) = &mut self.iter {
-            Some(iter.next())
-        } else {
-            Some(None)
-        }
+        Some(Some(
+            self.iter
+                .as_mut()
+                .and_then(|iter| iter.next_row())
+                .flatten(),
+        ))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len_remaining, Some(self.len_remaining))
+    }
+}
+
+impl<T: SplitRowIterator> SplitRowIterator for OptionIterator<T> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        let row = row.min(self.len_remaining);
+        let (first, second) = match self.iter {
+            Some(iter) => {
+                let (first, second) = iter.split_at(row);
+                (Some(first), Some(second))
+            }
+            None => (None, None),
+        };
+        (
+            Self::new(row, first),
+            Self::new(self.len_remaining - row, second),
+        )
     }
 }
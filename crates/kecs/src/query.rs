@@ -1,10 +1,31 @@
-use std::{iter::Zip, sync::RwLockWriteGuard};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    RwLockWriteGuard,
+};
 
 // ------ This section is for [Query]s which can accept multiple parameters.
 use crate::*;
 
 pub trait FilterTrait {
+    /// True if this filter must be checked per [Entity],
+    /// which is needed for sparse components and [Changed] filters.
+    const PER_ENTITY: bool = false;
     fn append_filters(filters: &mut Vec<(Option<usize>, Filter)>);
+    /// Returns true if the [Archetype] passes this filter.
+    /// This is used for filters like [Or] that can't be expressed with [Filter]s.
+    fn matches_archetype(archetype: &Archetype) -> bool;
+    /// Appends the sparse components this filter checks.
+    fn append_sparse(_sparse: &mut Vec<ComponentId>) {}
+    /// Returns true if the [Entity] in the [Archetype]'s `row` passes this filter.
+    /// This is only called if [FilterTrait::PER_ENTITY] is true.
+    fn matches_entity(
+        _archetype: &Archetype,
+        _row: usize,
+        _sparse: &SparseBorrows,
+        _change_ticks: ChangeTicks,
+    ) -> bool {
+        true
+    }
 }
 
 pub struct With<T: ComponentTrait> {
@@ -12,14 +33,41 @@ pub struct With<T: ComponentTrait> {
 }
 
 impl<T: ComponentTrait> FilterTrait for With<T> {
+    const PER_ENTITY: bool = matches!(T::STORAGE, ComponentStorage::Sparse);
+
     fn append_filters(filters: &mut Vec<(Option<usize>, Filter)>) {
-        filters.push((
-            None,
-            Filter {
-                component_id: get_component_id::<T>(),
-                filter_type: FilterType::With,
-            },
-        ))
+        if !Self::PER_ENTITY {
+            filters.push((
+                None,
+                Filter {
+                    component_id: get_component_id::<T>(),
+                    filter_type: FilterType::With,
+                },
+            ))
+        }
+    }
+
+    fn matches_archetype(archetype: &Archetype) -> bool {
+        Self::PER_ENTITY || archetype.has_component::<T>()
+    }
+
+    fn append_sparse(sparse: &mut Vec<ComponentId>) {
+        if Self::PER_ENTITY {
+            sparse.push(get_component_id::<T>());
+        }
+    }
+
+    fn matches_entity(
+        archetype: &Archetype,
+        row: usize,
+        sparse: &SparseBorrows,
+        _change_ticks: ChangeTicks,
+    ) -> bool {
+        if Self::PER_ENTITY {
+            sparse.contains::<T>(archetype.entities[row])
+        } else {
+            archetype.has_component::<T>()
+        }
     }
 }
 
@@ -28,27 +76,112 @@ pub struct Without<T: ComponentTrait> {
 }
 
 impl<T: ComponentTrait> FilterTrait for Without<T> {
+    const PER_ENTITY: bool = matches!(T::STORAGE, ComponentStorage::Sparse);
+
     fn append_filters(filters: &mut Vec<(Option<usize>, Filter)>) {
-        filters.push((
-            None,
-            Filter {
-                component_id: get_component_id::<T>(),
-                filter_type: FilterType::Without,
-            },
-        ))
+        if !Self::PER_ENTITY {
+            filters.push((
+                None,
+                Filter {
+                    component_id: get_component_id::<T>(),
+                    filter_type: FilterType::Without,
+                },
+            ))
+        }
+    }
+
+    fn matches_archetype(archetype: &Archetype) -> bool {
+        Self::PER_ENTITY || !archetype.has_component::<T>()
+    }
+
+    fn append_sparse(sparse: &mut Vec<ComponentId>) {
+        if Self::PER_ENTITY {
+            sparse.push(get_component_id::<T>());
+        }
+    }
+
+    fn matches_entity(
+        archetype: &Archetype,
+        row: usize,
+        sparse: &SparseBorrows,
+        _change_ticks: ChangeTicks,
+    ) -> bool {
+        if Self::PER_ENTITY {
+            !sparse.contains::<T>(archetype.entities[row])
+        } else {
+            !archetype.has_component::<T>()
+        }
+    }
+}
+
+/// Matches [Entity]s whose component was added or mutably borrowed since the system last ran.
+///
+/// Mutably iterating a [Query] or calling [Query::get_entity_components_mut] marks the
+/// components as changed even if they aren't written to.
+/// A system that hasn't run before sees every component as changed.
+///
+/// `Query<&A, Changed<A>>`
+pub struct Changed<T: ComponentTrait> {
+    phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T: ComponentTrait> FilterTrait for Changed<T> {
+    const PER_ENTITY: bool = true;
+
+    fn append_filters(filters: &mut Vec<(Option<usize>, Filter)>) {
+        With::<T>::append_filters(filters)
     }
+
+    fn matches_archetype(archetype: &Archetype) -> bool {
+        With::<T>::matches_archetype(archetype)
+    }
+
+    fn append_sparse(sparse: &mut Vec<ComponentId>) {
+        With::<T>::append_sparse(sparse)
+    }
+
+    fn matches_entity(
+        archetype: &Archetype,
+        row: usize,
+        sparse: &SparseBorrows,
+        change_ticks: ChangeTicks,
+    ) -> bool {
+        match T::STORAGE {
+            ComponentStorage::Table => archetype
+                .channels
+                .binary_search_by_key(&get_component_id::<T>(), |channel| channel.component_id)
+                .is_ok_and(|channel_index| {
+                    archetype.get_change_ticks(channel_index)[row].load(Ordering::Relaxed)
+                        > change_ticks.last_run
+                }),
+            ComponentStorage::Sparse => {
+                sparse.changed_since::<T>(archetype.entities[row], change_ticks.last_run)
+            }
+        }
+    }
+}
+
+/// Matches if any of the filters in the tuple match.
+///
+/// `Query<&A, Or<(With<B>, With<C>)>>`
+pub struct Or<T> {
+    phantom: std::marker::PhantomData<fn() -> T>,
 }
 
 pub struct Query<'a, PARAMETERS: QueryParametersTrait, FILTERS: FilterTrait = ()> {
     pub(crate) fetch:
         Vec<ArchetypeBorrow<'a, <PARAMETERS as QueryParametersFetchTrait<'a>>::FetchResult>>,
     pub(crate) entities: &'a Entities,
+    /// Keeps the sparse component storages locked while the [Query] exists.
+    pub(crate) _sparse: SparseBorrows<'a>,
     pub(crate) phantom: std::marker::PhantomData<fn(FILTERS)>,
 }
 
 pub(crate) struct ArchetypeBorrow<'a, T> {
     pub(crate) archetype: &'a Archetype,
     pub(crate) borrow: T,
+    /// Which rows pass [FilterTrait::matches_entity] if the filters are checked per [Entity].
+    pub(crate) row_mask: Option<Vec<bool>>,
 }
 
 impl<'a, PARAMETERS: QueryParametersTrait, FILTERS: FilterTrait> Query<'a, PARAMETERS, FILTERS> {
@@ -66,7 +199,8 @@ impl<'a, PARAMETERS: QueryParametersTrait, FILTERS: FilterTrait> Query<'a, PARAM
         self.into_iter()
     }
 
-    pub fn get_entity_components<'b>(&'b self, entity: Entity) -> Option<<<<PARAMETERS as QueryParametersFetchTrait<'a>>::FetchResult as GetIteratorsTrait<'b>>::Iterator as Iterator>::Item>{
+    /// Finds the [ArchetypeBorrow] and row of an [Entity] that passes this [Query]'s filters.
+    fn entity_row(&self, entity: Entity) -> Option<(usize, usize)> {
         let entity_location = self.entities.get_entity_location(entity)?;
         let archetype_borrow_index = self
             .fetch
@@ -74,79 +208,149 @@ impl<'a, PARAMETERS: QueryParametersTrait, FILTERS: FilterTrait> Query<'a, PARAM
                 archetype_borrow.archetype.index_in_world
             })
             .ok()?;
-        Some(
-            self.fetch[archetype_borrow_index]
-                .borrow
-                .get_components(entity_location.index_within_archetype),
-        )
+        let row = entity_location.index_within_archetype;
+        if let Some(row_mask) = &self.fetch[archetype_borrow_index].row_mask {
+            if !row_mask[row] {
+                return None;
+            }
+        }
+        Some((archetype_borrow_index, row))
     }
 
-    pub fn get_entity_components_mut<'b>(&'b mut self, entity: Entity) -> Option<<<<PARAMETERS as QueryParametersFetchTrait<'a>>::FetchResult as GetIteratorsTrait<'b>>::IteratorMut as Iterator>::Item>{
-        let entity_location = self.entities.get_entity_location(entity)?;
-        let archetype_borrow_index = self
-            .fetch
-            .binary_search_by_key(&entity_location.archetype_index, |archetype_borrow| {
-                archetype_borrow.archetype.index_in_world
-            })
-            .ok()?;
-        Some(
-            self.fetch[archetype_borrow_index]
-                .borrow
-                .get_components_mut(entity_location.index_within_archetype),
-        )
+    pub fn get_entity_components<'b>(
+        &'b self,
+        entity: Entity,
+    ) -> Option<
+        <<PARAMETERS as QueryParametersFetchTrait<'a>>::FetchResult as GetIteratorsTrait<'b>>::Item,
+    > {
+        let (archetype_borrow_index, row) = self.entity_row(entity)?;
+        self.fetch[archetype_borrow_index]
+            .borrow
+            .get_components(row)
     }
 
-    /// Produces an [Iterator] that returns the [Entity] and references to its associated components.
-    pub fn entities_and_components<'b>(
-        &'b self,
-    ) -> ChainedIterator<
-        Zip<
-            std::slice::Iter<'b, Entity>,
-            <<PARAMETERS as QueryParametersFetchTrait<'a>>::FetchResult as GetIteratorsTrait<'b>>::Iterator,
-        >,
+    pub fn get_entity_components_mut<'b>(
+        &'b mut self,
+        entity: Entity,
+    ) -> Option<
+        <<PARAMETERS as QueryParametersFetchTrait<'a>>::FetchResult as GetIteratorsTrait<'b>>::ItemMut,
     >{
-        ChainedIterator::new(
-            self.fetch
-                .iter()
-                .map(|i| i.archetype.entities.iter().zip(i.borrow.get_iterator()))
-                .collect(),
-        )
+        let (archetype_borrow_index, row) = self.entity_row(entity)?;
+        self.fetch[archetype_borrow_index]
+            .borrow
+            .get_components_mut(row)
+    }
+
+    fn no_matching_entities() -> KecsError {
+        KecsError::NoMatchingComponent(std::any::type_name::<PARAMETERS>())
     }
 
-    /// Produces an [Iterator] that returns the [Entity] and mutable references to its associated components.
-    pub fn entities_and_components_mut<'b>(
+    /// Gets the components of the only [Entity] that matches this [Query].
+    /// Returns an error if there are no matching [Entity]s or multiple matching [Entity]s.
+    pub fn get_single<'b>(
+        &'b self,
+    ) -> Result<<<&'b Self as IntoIterator>::IntoIter as Iterator>::Item, KecsError>
+    where
+        &'b Self: IntoIterator,
+    {
+        let mut iter = self.into_iter();
+        let item = iter.next().ok_or_else(Self::no_matching_entities)?;
+        if iter.next().is_some() {
+            return Err(KecsError::MultipleMatchingEntities);
+        }
+        Ok(item)
+    }
+
+    /// Gets the components of the only [Entity] that matches this [Query].
+    /// Returns an error if there are no matching [Entity]s or multiple matching [Entity]s.
+    pub fn get_single_mut<'b>(
         &'b mut self,
-    ) -> ChainedIterator<
-        Zip<
-            std::slice::Iter<'b, Entity>,
-            <<PARAMETERS as QueryParametersFetchTrait<'a>>::FetchResult as GetIteratorsTrait<'b>>::IteratorMut,
-        >,
-    >{
-        ChainedIterator::new(
-            self.fetch
-                .iter_mut()
-                .map(|i| i.archetype.entities.iter().zip(i.borrow.get_iterator_mut()))
-                .collect(),
-        )
+    ) -> Result<<<&'b mut Self as IntoIterator>::IntoIter as Iterator>::Item, KecsError>
+    where
+        &'b mut Self: IntoIterator,
+    {
+        let mut iter = self.into_iter();
+        let item = iter.next().ok_or_else(Self::no_matching_entities)?;
+        if iter.next().is_some() {
+            return Err(KecsError::MultipleMatchingEntities);
+        }
+        Ok(item)
+    }
+
+    /// Calls `f` with the components of each matching [Entity].
+    /// Each [Archetype]'s components are split into batches of `batch_size` that run on `ktasks` workers.
+    ///
+    /// The [Query] keeps its component channels locked until every batch is complete.
+    /// If there are no other workers each [Archetype] is one batch that runs on this thread.
+    #[cfg(feature = "scheduler")]
+    pub fn par_iter_mut<'b>(
+        &'b mut self,
+        batch_size: usize,
+        f: impl Fn(
+                <<<PARAMETERS as QueryParametersFetchTrait<'a>>::FetchResult as GetIteratorsTrait<
+                    'b,
+                >>::IteratorMut as RowIterator>::Row,
+            ) + Send
+            + Sync,
+    ) where
+        <<PARAMETERS as QueryParametersFetchTrait<'a>>::FetchResult as GetIteratorsTrait<'b>>::IteratorMut:
+            SplitRowIterator + Send,
+    {
+        let batch_size = if ktasks::has_other_workers() {
+            batch_size.max(1)
+        } else {
+            usize::MAX
+        };
+        let f = &f;
+        ktasks::scope(|scope| {
+            for archetype_borrow in self.fetch.iter_mut() {
+                let mut rows = archetype_borrow.archetype.len();
+                let mut row_mask = archetype_borrow.row_mask.as_deref();
+                let mut iter = archetype_borrow.borrow.get_iterator_mut();
+                while rows > 0 {
+                    let batch_rows = rows.min(batch_size);
+                    let (batch, rest) = iter.split_at(batch_rows);
+                    iter = rest;
+                    rows -= batch_rows;
+
+                    let batch_row_mask = match row_mask {
+                        Some(mask) => {
+                            let (batch_mask, rest) = mask.split_at(batch_rows);
+                            row_mask = Some(rest);
+                            Some(batch_mask.iter())
+                        }
+                        None => None,
+                    };
+                    let batch = ArchetypeIterator::<_, FILTERS> {
+                        iter: batch,
+                        row_mask: batch_row_mask,
+                        phantom: std::marker::PhantomData,
+                    };
+                    scope.spawn(move || batch.flatten().for_each(f));
+                }
+            }
+        });
     }
 }
 
-pub(crate) fn get_meta_data<const CHANNEL_COUNT: usize>(
+pub(crate) fn get_meta_data(
     world: &World,
     filters: &[(Option<usize>, Filter)],
-    mutable: [bool; CHANNEL_COUNT],
+    mutable: &[bool],
+    sparse: Vec<(ComponentId, bool)>,
+    archetype_filter: impl Fn(&Archetype, &[Option<usize>]) -> bool,
 ) -> Result<SystemParameterMetaData, KecsError> {
     let mut archetypes = Vec::new();
     let mut channels = Vec::new();
 
-    for matching_archetype in world
-        .storage_lookup
-        .matching_archetype_iterator::<CHANNEL_COUNT>(filters)
-    {
-        archetypes.push(matching_archetype.archetype_index);
-        for (matching_archetype_channel, mutable) in
-            matching_archetype.channels.iter().zip(mutable.iter())
-        {
+    let mut matching_channels = vec![None; mutable.len()];
+    let mut matching_archetypes = world.storage_lookup.matching_archetypes(filters);
+    while let Some(archetype_index) = matching_archetypes.next_into(&mut matching_channels) {
+        if !archetype_filter(&world.archetypes[archetype_index], &matching_channels) {
+            continue;
+        }
+        archetypes.push(archetype_index);
+        for (matching_archetype_channel, mutable) in matching_channels.iter().zip(mutable.iter()) {
             channels.push(matching_archetype_channel.map(|c| (c, *mutable)));
         }
     }
@@ -154,105 +358,296 @@ pub(crate) fn get_meta_data<const CHANNEL_COUNT: usize>(
     Ok(SystemParameterMetaData {
         archetypes,
         channels,
+        sparse,
     })
 }
 
-// Manual implementations for Query<'_, A> because it's easier to type Query<'a, A> instead of
-// Query<'a, (A,)> to get all entities with a single component.
-impl<A: QueryParameterTrait, FILTERS: FilterTrait> SystemParameterTrait for Query<'_, A, FILTERS> {
+impl<PARAMETERS: QueryParametersTrait, FILTERS: FilterTrait> SystemParameterTrait
+    for Query<'_, PARAMETERS, FILTERS>
+{
     fn get_meta_data(world: &World) -> Result<SystemParameterMetaData, KecsError> {
-        let mut filters = vec![(Some(0), A::filter())];
+        let mut filters = Vec::new();
+        let mut mutable = Vec::with_capacity(PARAMETERS::CHANNEL_COUNT);
+        PARAMETERS::append_filters(&mut filters, &mut mutable, 0);
         FILTERS::append_filters(&mut filters);
-        let mutable = [A::mutable()];
-        get_meta_data(world, &filters, mutable)
+
+        // Filters only read sparse components, and only need listing if not already borrowed.
+        let mut sparse = Vec::new();
+        let mut sparse_filters = Vec::new();
+        PARAMETERS::append_sparse(&mut sparse);
+        FILTERS::append_sparse(&mut sparse_filters);
+        for component_id in sparse_filters {
+            if !sparse.iter().any(|(id, _)| *id == component_id) {
+                sparse.push((component_id, false));
+            }
+        }
+
+        get_meta_data(world, &filters, &mutable, sparse, |archetype, channels| {
+            FILTERS::matches_archetype(archetype) && PARAMETERS::matches_channels(channels)
+        })
     }
 }
 
-impl<'a, A: QueryParameterTrait, FILTERS: FilterTrait> SystemParameterFetchTrait<'a>
-    for Query<'_, A, FILTERS>
+impl<'a, PARAMETERS: QueryParametersTrait, FILTERS: FilterTrait> SystemParameterFetchTrait<'a>
+    for Query<'_, PARAMETERS, FILTERS>
 {
-    type FetchResult = Option<Query<'a, A, FILTERS>>;
+    type FetchResult = Option<Query<'a, PARAMETERS, FILTERS>>;
 
     fn fetch(
         world: &'a World,
         meta_data: &SystemParameterMetaData,
+        change_ticks: ChangeTicks,
     ) -> Result<Self::FetchResult, KecsError> {
-        let mut fetch = Vec::new();
-        for (archetype_index, channel) in meta_data.archetypes.iter().zip(meta_data.channels.iter())
-        {
+        let mut sparse_parameters = Vec::new();
+        let mut sparse_filters = Vec::new();
+        PARAMETERS::append_sparse(&mut sparse_parameters);
+        FILTERS::append_sparse(&mut sparse_filters);
+        let sparse = SparseBorrows::new(world, &sparse_parameters, &sparse_filters)?;
+
+        let channel_count = PARAMETERS::CHANNEL_COUNT;
+        let mut fetch = Vec::with_capacity(meta_data.archetypes.len());
+        let mut channels = Vec::with_capacity(channel_count);
+        for (i, archetype_index) in meta_data.archetypes.iter().enumerate() {
             let archetype = &world.archetypes[*archetype_index];
+            channels.clear();
+            channels.extend(
+                meta_data.channels[i * channel_count..(i + 1) * channel_count]
+                    .iter()
+                    .map(|c| c.map(|c| c.0)),
+            );
+            let row_mask = FILTERS::PER_ENTITY.then(|| {
+                (0..archetype.entities.len())
+                    .map(|row| FILTERS::matches_entity(archetype, row, &sparse, change_ticks))
+                    .collect()
+            });
             fetch.push(ArchetypeBorrow {
                 archetype,
-                borrow: A::fetch(archetype, channel.map(|c| c.0))?,
+                borrow: PARAMETERS::fetch(archetype, &channels, &sparse, change_ticks)?,
+                row_mask,
             });
         }
         Ok(Some(Query {
             fetch,
             entities: &world.entities,
+            _sparse: sparse,
             phantom: std::marker::PhantomData,
         }))
     }
 }
 
-impl<A: QueryParameterTrait> QueryParametersTrait for A {}
+impl<A: QueryParameterTrait> QueryParametersTrait for A {
+    const CHANNEL_COUNT: usize = A::CHANNEL_COUNT;
+    fn append_filters(
+        filters: &mut Vec<(Option<usize>, Filter)>,
+        mutable: &mut Vec<bool>,
+        first_channel: usize,
+    ) {
+        A::append_filters(filters, mutable, first_channel)
+    }
+    fn matches_channels(channels: &[Option<usize>]) -> bool {
+        A::matches_channels(channels)
+    }
+    fn append_sparse(sparse: &mut Vec<(ComponentId, bool)>) {
+        A::append_sparse(sparse)
+    }
+}
 
-impl<'a, A: QueryParameterFetchTrait<'a>> QueryParametersFetchTrait<'a> for A {
-    type FetchResult = A::FetchResult;
+impl<'a, A: QueryParameterTrait> QueryParametersFetchTrait<'a> for A {
+    type FetchResult = <A as QueryParameterFetchTrait<'a>>::FetchResult;
+    fn fetch(
+        archetype: &'a Archetype,
+        channels: &[Option<usize>],
+        sparse: &SparseBorrows<'a>,
+        change_ticks: ChangeTicks,
+    ) -> Result<Self::FetchResult, KecsError> {
+        <A as QueryParameterFetchTrait<'a>>::fetch(archetype, channels, sparse, change_ticks)
+    }
 }
 
 macro_rules! query_impls {
+    // `Query<()>` iterates one `()` per [Entity], so its fetch needs to know the [Archetype]'s length.
+    ( $count: tt, ) => {
+        impl FilterTrait for () {
+            fn append_filters(_filters: &mut Vec<(Option<usize>, Filter)>) {}
+
+            fn matches_archetype(_archetype: &Archetype) -> bool {
+                true
+            }
+        }
+
+        impl QueryParametersTrait for () {
+            const CHANNEL_COUNT: usize = 0;
+
+            fn append_filters(_filters: &mut Vec<(Option<usize>, Filter)>, _mutable: &mut Vec<bool>, _first_channel: usize) {}
+
+            fn matches_channels(_channels: &[Option<usize>]) -> bool {
+                true
+            }
+        }
+
+        impl<'a> QueryParametersFetchTrait<'a> for () {
+            type FetchResult = EmptyFetch;
+
+            fn fetch(archetype: &'a Archetype, _channels: &[Option<usize>], _sparse: &SparseBorrows<'a>, _change_ticks: ChangeTicks) -> Result<Self::FetchResult, KecsError> {
+                Ok(EmptyFetch(archetype.entities.len()))
+            }
+        }
+    };
     ( $count: tt, $( ($index: tt, $tuple:ident) ),* ) => {
         impl<$( $tuple: FilterTrait,)*> FilterTrait for ($( $tuple,)*) {
+            const PER_ENTITY: bool = false $( || $tuple::PER_ENTITY)*;
+
             #[allow(unused)]
             fn append_filters(filters: &mut Vec<(Option<usize>, Filter)>) {
                 $(
                     $tuple::append_filters(filters);
                  )*
             }
+
+            #[allow(unused)]
+            fn matches_archetype(archetype: &Archetype) -> bool {
+                true $( && $tuple::matches_archetype(archetype))*
+            }
+
+            #[allow(unused)]
+            fn append_sparse(sparse: &mut Vec<ComponentId>) {
+                $(
+                    $tuple::append_sparse(sparse);
+                 )*
+            }
+
+            #[allow(unused)]
+            fn matches_entity(archetype: &Archetype, row: usize, sparse: &SparseBorrows, change_ticks: ChangeTicks) -> bool {
+                true $( && $tuple::matches_entity(archetype, row, sparse, change_ticks))*
+            }
+        }
+
+        impl<$( $tuple: FilterTrait,)*> FilterTrait for Or<($( $tuple,)*)> {
+            const PER_ENTITY: bool = false $( || $tuple::PER_ENTITY)*;
+
+            // Nothing is appended here because [Filter]s must all match.
+            fn append_filters(_filters: &mut Vec<(Option<usize>, Filter)>) {}
+
+            #[allow(unused)]
+            fn matches_archetype(archetype: &Archetype) -> bool {
+                false $( || $tuple::matches_archetype(archetype))*
+            }
+
+            #[allow(unused)]
+            fn append_sparse(sparse: &mut Vec<ComponentId>) {
+                $(
+                    $tuple::append_sparse(sparse);
+                 )*
+            }
+
+            #[allow(unused)]
+            fn matches_entity(archetype: &Archetype, row: usize, sparse: &SparseBorrows, change_ticks: ChangeTicks) -> bool {
+                false $( || $tuple::matches_entity(archetype, row, sparse, change_ticks))*
+            }
         }
 
-        #[allow(unused_mut, unused)]
-        impl<FILTERS: FilterTrait, $( $tuple: QueryParameterTrait,)*> SystemParameterTrait for Query<'_, ($( $tuple,)*), FILTERS> {
-            fn get_meta_data(world: &World) -> Result<SystemParameterMetaData, KecsError> {
-                let mut filters = vec![$( (Some($index), $tuple::filter()),)*];
-                FILTERS::append_filters(&mut filters);
-                let mutable = [$( $tuple::mutable(),)*];
-                get_meta_data(world, &filters, mutable)
+        impl<$( $tuple,)*> AnyIsSome for ($( Option<$tuple>,)*) {
+            #[allow(non_snake_case)]
+            fn any_is_some(&self) -> bool {
+                let ($( $tuple,)*) = self;
+                false $( || $tuple.is_some())*
             }
         }
 
-        #[allow( unused)]
-        impl<'a, FILTERS: FilterTrait, $( $tuple: QueryParameterTrait,)*> SystemParameterFetchTrait<'a> for Query<'_, ($( $tuple,)*), FILTERS> {
-            type FetchResult = Option<Query<'a, ($( $tuple,)*), FILTERS>,>;
+        impl<$($tuple: QueryParameterTrait,)*> QueryParametersTrait for ($( $tuple,)*) {
+            const CHANNEL_COUNT: usize = 0 $( + $tuple::CHANNEL_COUNT)*;
 
-            fn fetch(world: &'a World, meta_data: &SystemParameterMetaData) -> Result<Self::FetchResult, KecsError> {
-                let mut fetch = Vec::new();
-                for (archetype_index, channels) in meta_data.archetypes.iter().zip( meta_data.channels.chunks_exact($count)) {
-                    let archetype = &world.archetypes[*archetype_index];
-                    fetch.push(ArchetypeBorrow {
+            #[allow(unused_mut, unused)]
+            fn append_filters(filters: &mut Vec<(Option<usize>, Filter)>, mutable: &mut Vec<bool>, first_channel: usize) {
+                let mut channel = first_channel;
+                $(
+                    $tuple::append_filters(filters, mutable, channel);
+                    channel += $tuple::CHANNEL_COUNT;
+                )*
+            }
+
+            #[allow(unused_mut, unused)]
+            fn matches_channels(channels: &[Option<usize>]) -> bool {
+                let mut channel = 0;
+                $(
+                    if !$tuple::matches_channels(&channels[channel..channel + $tuple::CHANNEL_COUNT]) {
+                        return false;
+                    }
+                    channel += $tuple::CHANNEL_COUNT;
+                )*
+                true
+            }
+
+            #[allow(unused)]
+            fn append_sparse(sparse: &mut Vec<(ComponentId, bool)>) {
+                $(
+                    $tuple::append_sparse(sparse);
+                )*
+            }
+        }
+
+        impl<'a, $($tuple: QueryParameterTrait,)*> QueryParametersFetchTrait<'a> for ($( $tuple,)*) {
+            type FetchResult = ($( <$tuple as QueryParameterFetchTrait<'a>>::FetchResult,)*);
+
+            #[allow(unused_mut, unused, clippy::unused_unit)]
+            fn fetch(archetype: &'a Archetype, channels: &[Option<usize>], sparse: &SparseBorrows<'a>, change_ticks: ChangeTicks) -> Result<Self::FetchResult, KecsError> {
+                let mut channel = 0;
+                Ok(($({
+                    let fetch = <$tuple as QueryParameterFetchTrait<'a>>::fetch(
                         archetype,
-                        borrow: ($( $tuple::fetch(archetype, channels[$index].map(|c| c.0))?,)*)
-                    });
-                }
-                Ok(Some(Query { fetch, entities: &world.entities, phantom: std::marker::PhantomData }))
+                        &channels[channel..channel + $tuple::CHANNEL_COUNT],
+                        sparse,
+                        change_ticks,
+                    )?;
+                    channel += $tuple::CHANNEL_COUNT;
+                    fetch
+                },)*))
             }
         }
 
-        impl<$($tuple: QueryParameterTrait,)*> QueryParametersTrait for ($( $tuple,)*) {}
+        impl<$($tuple: QueryParameterTrait,)*> QueryParameterTrait for AnyOf<($( $tuple,)*)> {
+            const CHANNEL_COUNT: usize = <($( Option<$tuple>,)*) as QueryParametersTrait>::CHANNEL_COUNT;
+
+            fn append_filters(filters: &mut Vec<(Option<usize>, Filter)>, mutable: &mut Vec<bool>, first_channel: usize) {
+                <($( Option<$tuple>,)*) as QueryParametersTrait>::append_filters(filters, mutable, first_channel)
+            }
 
-        impl<'a, $($tuple: QueryParameterFetchTrait<'a>,)*> QueryParametersFetchTrait<'a> for ($( $tuple,)*) {
-            type FetchResult = ($( $tuple::FetchResult,)*);
+            #[allow(unused_mut, unused)]
+            fn matches_channels(channels: &[Option<usize>]) -> bool {
+                let mut channel = 0;
+                $(
+                    if $tuple::matches_channels(&channels[channel..channel + $tuple::CHANNEL_COUNT]) {
+                        return true;
+                    }
+                    channel += $tuple::CHANNEL_COUNT;
+                )*
+                false
+            }
+
+            fn append_sparse(sparse: &mut Vec<(ComponentId, bool)>) {
+                <($( $tuple,)*) as QueryParametersTrait>::append_sparse(sparse)
+            }
+        }
+
+        impl<'a, $($tuple: QueryParameterTrait,)*> QueryParameterFetchTrait<'a> for AnyOf<($( $tuple,)*)> {
+            type FetchResult = AnyOfFetch<<($( Option<$tuple>,)*) as QueryParametersFetchTrait<'a>>::FetchResult>;
+
+            fn fetch(archetype: &'a Archetype, channels: &[Option<usize>], sparse: &SparseBorrows<'a>, change_ticks: ChangeTicks) -> Result<Self::FetchResult, KecsError> {
+                Ok(AnyOfFetch(<($( Option<$tuple>,)*) as QueryParametersFetchTrait<'a>>::fetch(archetype, channels, sparse, change_ticks)?))
+            }
         }
     }
 }
 
 macro_rules! query_iterator_impls {
     // These first two cases are implemented manually so skip them in this macro.
+    ($count: tt, ) => {};
     ($count: tt, ($index0: tt, $tuple0:ident)) => {};
-    ($count: tt, ($index0: tt, $tuple0:ident), ($index1: tt, $tuple1:ident)) => {};
     ($count: tt, $( ($index: tt, $tuple:ident) ),* ) => {
         #[allow(unused)]
         impl<'a, $( $tuple: GetIteratorsTrait<'a>,)*> GetIteratorsTrait<'a> for ($( $tuple,)*) {
+            type Item = ($( $tuple::Item,)*);
+            type ItemMut = ($( $tuple::ItemMut,)*);
             type Iterator = MultiIterator<($( $tuple::Iterator,)*)>;
             type IteratorMut = MultiIterator<($( $tuple::IteratorMut,)*)>;
             fn get_iterator(&'a self) -> Self::Iterator {
@@ -261,31 +656,88 @@ macro_rules! query_iterator_impls {
             fn get_iterator_mut(&'a mut self) -> Self::IteratorMut {
                 MultiIterator::<($( $tuple::IteratorMut,)*)>::new(($( self.$index.get_iterator_mut(),)*))
             }
-            #[allow(clippy::unused_unit)]
-            fn get_components(&'a self, index: usize) -> <Self::Iterator as Iterator>::Item {
-                ($( self.$index.get_components(index),)*)
+            fn get_components(&'a self, index: usize) -> Option<Self::Item> {
+                Some(($( self.$index.get_components(index)?,)*))
             }
-            #[allow(clippy::unused_unit)]
-            fn get_components_mut(&'a mut self, index: usize) -> <Self::IteratorMut as Iterator>::Item {
-                ($( self.$index.get_components_mut(index),)*)
+            fn get_components_mut(&'a mut self, index: usize) -> Option<Self::ItemMut> {
+                Some(($( self.$index.get_components_mut(index)?,)*))
             }
         }
     };
 }
 
+/// Iterates the rows of one [Archetype], skipping rows that fail the [Query]'s filters.
+#[doc(hidden)]
+pub struct ArchetypeIterator<'b, I, FILTERS> {
+    iter: I,
+    /// Only used if [FilterTrait::PER_ENTITY] is true.
+    row_mask: Option<std::slice::Iter<'b, bool>>,
+    phantom: std::marker::PhantomData<fn(FILTERS)>,
+}
+
+impl<'b, I: RowIterator, FILTERS: FilterTrait> Iterator for ArchetypeIterator<'b, I, FILTERS> {
+    type Item = Option<I::Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // This is a constant so filters that only check [Archetype]s don't pay for a row mask.
+        if !FILTERS::PER_ENTITY {
+            return self.iter.next_row();
+        }
+        let row = self.iter.next_row()?;
+        let passes_filters = match &mut self.row_mask {
+            Some(row_mask) => *row_mask.next()?,
+            None => true,
+        };
+        Some(row.filter(|_| passes_filters))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Iterates the components of each [Entity] that matches a [Query].
+pub struct QueryIterator<'b, I: RowIterator, FILTERS: FilterTrait>(
+    ChainedIterator<ArchetypeIterator<'b, I, FILTERS>>,
+);
+
+impl<'b, I: RowIterator, FILTERS: FilterTrait> Iterator for QueryIterator<'b, I, FILTERS> {
+    type Item = I::Row;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Rows of [Entity]s without sparse components are skipped.
+        loop {
+            if let Some(row) = self.0.next()? {
+                return Some(row);
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.0.size_hint().1)
+    }
+}
+
 impl<'a, 'b, FILTERS: FilterTrait, PARAMETERS: QueryParametersTrait> IntoIterator
     for &'b Query<'a, PARAMETERS, FILTERS>
 {
     type Item = <Self::IntoIter as IntoIterator>::Item;
-    type IntoIter =
-        ChainedIterator<<<PARAMETERS as QueryParametersFetchTrait<'a>>::FetchResult as GetIteratorsTrait<'b>>::Iterator>;
+    type IntoIter = QueryIterator<
+        'b,
+        <<PARAMETERS as QueryParametersFetchTrait<'a>>::FetchResult as GetIteratorsTrait<'b>>::Iterator,
+        FILTERS,
+    >;
     fn into_iter(self) -> Self::IntoIter {
-        ChainedIterator::new(
+        QueryIterator(ChainedIterator::new(
             self.fetch
                 .iter()
-                .map(|archetype_borrow| archetype_borrow.borrow.get_iterator())
+                .map(|archetype_borrow| ArchetypeIterator {
+                    iter: archetype_borrow.borrow.get_iterator(),
+                    row_mask: archetype_borrow.row_mask.as_ref().map(|m| m.iter()),
+                    phantom: std::marker::PhantomData,
+                })
                 .collect(),
-        )
+        ))
     }
 }
 
@@ -293,52 +745,73 @@ impl<'a, 'b, FILTERS: FilterTrait, PARAMETERS: QueryParametersTrait> IntoIterato
     for &'b mut Query<'a, PARAMETERS, FILTERS>
 {
     type Item = <Self::IntoIter as IntoIterator>::Item;
-    type IntoIter =
-        ChainedIterator<<<PARAMETERS as QueryParametersFetchTrait<'a>>::FetchResult as GetIteratorsTrait<'b>>::IteratorMut>;
+    type IntoIter = QueryIterator<
+        'b,
+        <<PARAMETERS as QueryParametersFetchTrait<'a>>::FetchResult as GetIteratorsTrait<'b>>::IteratorMut,
+        FILTERS,
+    >;
     fn into_iter(self) -> Self::IntoIter {
-        ChainedIterator::new(
+        QueryIterator(ChainedIterator::new(
             self.fetch
                 .iter_mut()
-                .map(|archetype_borrow| archetype_borrow.borrow.get_iterator_mut())
+                .map(|archetype_borrow| ArchetypeIterator {
+                    iter: archetype_borrow.borrow.get_iterator_mut(),
+                    row_mask: archetype_borrow.row_mask.as_ref().map(|m| m.iter()),
+                    phantom: std::marker::PhantomData,
+                })
                 .collect(),
+        ))
+    }
+}
+
+#[doc(hidden)]
+pub struct EmptyFetch(pub(crate) usize);
+
+impl SplitRowIterator for std::iter::RepeatN<Option<()>> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        let row = row.min(self.len());
+        (
+            std::iter::repeat_n(Some(()), row),
+            std::iter::repeat_n(Some(()), self.len() - row),
         )
     }
 }
 
-impl<'a, A: GetIteratorsTrait<'a>> GetIteratorsTrait<'a> for (A,) {
-    type Iterator = A::Iterator;
-    type IteratorMut = A::IteratorMut;
+impl<'a> GetIteratorsTrait<'a> for EmptyFetch {
+    type Item = ();
+    type ItemMut = ();
+    type Iterator = std::iter::RepeatN<Option<()>>;
+    type IteratorMut = std::iter::RepeatN<Option<()>>;
     fn get_iterator(&'a self) -> Self::Iterator {
-        self.0.get_iterator()
+        std::iter::repeat_n(Some(()), self.0)
     }
     fn get_iterator_mut(&'a mut self) -> Self::IteratorMut {
-        self.0.get_iterator_mut()
+        std::iter::repeat_n(Some(()), self.0)
     }
-    fn get_components(&'a self, index: usize) -> <Self::Iterator as Iterator>::Item {
-        self.0.get_components(index)
+    fn get_components(&'a self, _index: usize) -> Option<Self::Item> {
+        Some(())
     }
-    fn get_components_mut(&'a mut self, index: usize) -> <Self::IteratorMut as Iterator>::Item {
-        self.0.get_components_mut(index)
+    fn get_components_mut(&'a mut self, _index: usize) -> Option<Self::ItemMut> {
+        Some(())
     }
 }
 
-impl<'a, A: GetIteratorsTrait<'a>, B: GetIteratorsTrait<'a>> GetIteratorsTrait<'a> for (A, B) {
-    type Iterator = Zip<A::Iterator, B::Iterator>;
-    type IteratorMut = Zip<A::IteratorMut, B::IteratorMut>;
+impl<'a, A: GetIteratorsTrait<'a>> GetIteratorsTrait<'a> for (A,) {
+    type Item = A::Item;
+    type ItemMut = A::ItemMut;
+    type Iterator = A::Iterator;
+    type IteratorMut = A::IteratorMut;
     fn get_iterator(&'a self) -> Self::Iterator {
-        self.0.get_iterator().zip(self.1.get_iterator())
+        self.0.get_iterator()
     }
     fn get_iterator_mut(&'a mut self) -> Self::IteratorMut {
-        self.0.get_iterator_mut().zip(self.1.get_iterator_mut())
+        self.0.get_iterator_mut()
     }
-    fn get_components(&'a self, index: usize) -> <Self::Iterator as Iterator>::Item {
-        (self.0.get_components(index), self.1.get_components(index))
+    fn get_components(&'a self, index: usize) -> Option<Self::Item> {
+        self.0.get_components(index)
     }
-    fn get_components_mut(&'a mut self, index: usize) -> <Self::IteratorMut as Iterator>::Item {
-        (
-            self.0.get_components_mut(index),
-            self.1.get_components_mut(index),
-        )
+    fn get_components_mut(&'a mut self, index: usize) -> Option<Self::ItemMut> {
+        self.0.get_components_mut(index)
     }
 }
 
@@ -350,134 +823,578 @@ impl<'a, 'b, PARAMETERS: QueryParametersTrait, FILTERS: FilterTrait> AsSystemArg
         self.take().unwrap()
     }
 }
-pub trait QueryParametersTrait: for<'a> QueryParametersFetchTrait<'a> {}
+pub trait QueryParametersTrait: for<'a> QueryParametersFetchTrait<'a> {
+    /// The total number of [Archetype] channels used by these parameters.
+    const CHANNEL_COUNT: usize;
+    fn append_filters(
+        filters: &mut Vec<(Option<usize>, Filter)>,
+        mutable: &mut Vec<bool>,
+        first_channel: usize,
+    );
+    fn matches_channels(channels: &[Option<usize>]) -> bool;
+    /// Appends the sparse components used by these parameters and whether they're mutable.
+    fn append_sparse(_sparse: &mut Vec<(ComponentId, bool)>) {}
+}
 pub trait QueryParametersFetchTrait<'a> {
     type FetchResult: for<'b> GetIteratorsTrait<'b>;
+    fn fetch(
+        archetype: &'a Archetype,
+        channels: &[Option<usize>],
+        sparse: &SparseBorrows<'a>,
+        change_ticks: ChangeTicks,
+    ) -> Result<Self::FetchResult, KecsError>;
 }
 
-trait IntoIteratorTrait {}
 pub trait QueryParameterTrait: for<'a> QueryParameterFetchTrait<'a> {
-    fn filter() -> Filter;
-    fn mutable() -> bool;
+    /// The number of [Archetype] channels this parameter uses.
+    const CHANNEL_COUNT: usize = 1;
+    /// Appends this parameter's [Filter]s and whether each of its channels is mutable.
+    /// `first_channel` is the index of the first channel used by this parameter.
+    fn append_filters(
+        filters: &mut Vec<(Option<usize>, Filter)>,
+        mutable: &mut Vec<bool>,
+        first_channel: usize,
+    );
+    /// Returns false if an [Archetype] with these channels should not be part of the [Query].
+    fn matches_channels(_channels: &[Option<usize>]) -> bool {
+        true
+    }
+    /// Appends the sparse components used by this parameter and whether they're mutable.
+    fn append_sparse(_sparse: &mut Vec<(ComponentId, bool)>) {}
 }
 pub trait QueryParameterFetchTrait<'a> {
     type FetchResult: for<'b> GetIteratorsTrait<'b>;
     fn fetch(
         archetype: &'a Archetype,
-        channel_index: Option<usize>,
+        channels: &[Option<usize>],
+        sparse: &SparseBorrows<'a>,
+        change_ticks: ChangeTicks,
     ) -> Result<Self::FetchResult, KecsError>;
 }
 
+/// Gets iterators over the rows of an [Archetype].
+/// Rows are `None` if the [Entity] in that row doesn't match, which happens with sparse components.
 pub trait GetIteratorsTrait<'a> {
-    type Iterator: Iterator;
-    type IteratorMut: Iterator;
+    type Item;
+    type ItemMut;
+    type Iterator: Iterator<Item = Option<Self::Item>>;
+    type IteratorMut: Iterator<Item = Option<Self::ItemMut>>;
     fn get_iterator(&'a self) -> Self::Iterator;
     fn get_iterator_mut(&'a mut self) -> Self::IteratorMut;
-    fn get_components(&'a self, index: usize) -> <Self::Iterator as Iterator>::Item;
-    fn get_components_mut(&'a mut self, index: usize) -> <Self::IteratorMut as Iterator>::Item;
+    fn get_components(&'a self, index: usize) -> Option<Self::Item>;
+    fn get_components_mut(&'a mut self, index: usize) -> Option<Self::ItemMut>;
+}
+
+/// The components of a sparse component type for the [Entity]s of an [Archetype].
+#[doc(hidden)]
+pub struct SparseColumn<'a, T> {
+    entities: &'a [Entity],
+    /// Where each [Entity]'s component is in `data`, indexed by [Entity] index.
+    indices: &'a [Option<usize>],
+    data: *mut T,
+    /// The change tick of each [Entity]'s component, indexed by [Entity] index.
+    change_ticks: Option<&'a crate::sparse_set::SparseSet<AtomicU64>>,
+}
+
+impl<T> Clone for SparseColumn<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SparseColumn<'_, T> {}
+
+// Safety: Components are only borrowed as allowed by the locks the [Query] holds.
+// Components are only borrowed mutably by a [SparseIterMut] or with `&mut` access to the fetch,
+// and split [SparseIterMut]s cover different rows, so mutable borrows never overlap.
+unsafe impl<T: Send + Sync> Send for SparseColumn<'_, T> {}
+unsafe impl<T: Sync> Sync for SparseColumn<'_, T> {}
+
+impl<'a, T: ComponentTrait> SparseColumn<'a, T> {
+    fn new(archetype: &'a Archetype, sparse: &SparseBorrows<'a>, mutable: bool) -> Self {
+        let (indices, data, change_ticks) = match sparse.get::<T>() {
+            // Safety: The [SparseSet] is locked by the [Query] for as long as this exists.
+            // `data_ptr` is only called if the [Query] holds a write lock.
+            Some((set, change_ticks)) => unsafe {
+                let data = if mutable {
+                    (*set).data_ptr()
+                } else {
+                    (*set).data().as_ptr() as *mut T
+                };
+                ((*set).indices(), data, Some(&*change_ticks))
+            },
+            None => (&[][..], std::ptr::null_mut(), None),
+        };
+        Self {
+            entities: &archetype.entities,
+            indices,
+            data,
+            change_ticks,
+        }
+    }
+}
+
+impl<T> SparseColumn<'_, T> {
+    fn get(&self, row: usize) -> Option<*mut T> {
+        let data_index = (*self.indices.get(self.entities[row].index as usize)?)?;
+        // Safety: `indices` only contains valid indices of `data`.
+        Some(unsafe { self.data.add(data_index) })
+    }
+
+    fn set_changed(&self, row: usize, change_tick: u64) {
+        if let Some(tick) = self
+            .change_ticks
+            .and_then(|change_ticks| change_ticks.get(self.entities[row].index as usize))
+        {
+            tick.store(change_tick, Ordering::Relaxed);
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct SparseIter<'a, T> {
+    column: SparseColumn<'a, T>,
+    rows: std::ops::Range<usize>,
+}
+
+impl<T> SparseIter<'_, T> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        let mid = (self.rows.start + row).min(self.rows.end);
+        (
+            Self {
+                column: self.column,
+                rows: self.rows.start..mid,
+            },
+            Self {
+                column: self.column,
+                rows: mid..self.rows.end,
+            },
+        )
+    }
+}
+
+impl<'a, T: 'a> Iterator for SparseIter<'a, T> {
+    type Item = Option<&'a T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = self.rows.next()?;
+        // Safety: The [Query] holds at least a read lock on the [SparseSet].
+        Some(self.column.get(row).map(|component| unsafe { &*component }))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rows.size_hint()
+    }
+}
+
+#[doc(hidden)]
+pub struct SparseIterMut<'a, T> {
+    column: SparseColumn<'a, T>,
+    rows: std::ops::Range<usize>,
+    change_tick: u64,
+}
+
+impl<T> SparseIterMut<'_, T> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        let mid = (self.rows.start + row).min(self.rows.end);
+        (
+            Self {
+                column: self.column,
+                rows: self.rows.start..mid,
+                change_tick: self.change_tick,
+            },
+            Self {
+                column: self.column,
+                rows: mid..self.rows.end,
+                change_tick: self.change_tick,
+            },
+        )
+    }
+}
+
+impl<'a, T: 'a> Iterator for SparseIterMut<'a, T> {
+    type Item = Option<&'a mut T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = self.rows.next()?;
+        // Safety: The [Query] holds a write lock on the [SparseSet].
+        // Each [Entity] is in exactly one row of one [Archetype] so the borrows never overlap.
+        Some(self.column.get(row).map(|component| {
+            self.column.set_changed(row, self.change_tick);
+            unsafe { &mut *component }
+        }))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rows.size_hint()
+    }
+}
+
+#[doc(hidden)]
+pub enum ReadFetch<'a, T> {
+    Table(RwLockReadGuard<'a, Vec<T>>),
+    Sparse(SparseColumn<'a, T>),
+}
+
+/// Only one of the iterators is used, depending on the component's [ComponentStorage].
+#[doc(hidden)]
+pub struct ReadIterator<'a, T> {
+    table: std::slice::Iter<'a, T>,
+    sparse: Option<SparseIter<'a, T>>,
+}
+
+impl<'a, T> ReadIterator<'a, T> {
+    fn table(channel: &'a [T]) -> Self {
+        Self {
+            table: channel.iter(),
+            sparse: None,
+        }
+    }
+
+    fn sparse(column: SparseColumn<'a, T>) -> Self {
+        Self {
+            table: Default::default(),
+            sparse: Some(SparseIter {
+                column,
+                rows: 0..column.entities.len(),
+            }),
+        }
+    }
+}
+
+impl<T: ComponentTrait> SplitRowIterator for ReadIterator<'_, T> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        match self.sparse {
+            Some(sparse) => {
+                let (first, second) = sparse.split_at(row);
+                (
+                    Self {
+                        table: Default::default(),
+                        sparse: Some(first),
+                    },
+                    Self {
+                        table: Default::default(),
+                        sparse: Some(second),
+                    },
+                )
+            }
+            None => {
+                let (first, second) = self.table.as_slice().split_at(row);
+                (Self::table(first), Self::table(second))
+            }
+        }
+    }
+}
+
+impl<'a, T: ComponentTrait> Iterator for ReadIterator<'a, T> {
+    type Item = Option<&'a T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        // `T::STORAGE` is a constant so table components don't check for sparse rows.
+        match T::STORAGE {
+            ComponentStorage::Table => self.table.next().map(Some),
+            ComponentStorage::Sparse => self.sparse.as_mut()?.next(),
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.sparse {
+            Some(iter) => iter.size_hint(),
+            None => self.table.size_hint(),
+        }
+    }
 }
 
 impl<T: ComponentTrait> QueryParameterTrait for &T {
-    fn filter() -> Filter {
-        Filter {
-            component_id: get_component_id::<T>(),
-            filter_type: FilterType::With,
+    fn append_filters(
+        filters: &mut Vec<(Option<usize>, Filter)>,
+        mutable: &mut Vec<bool>,
+        first_channel: usize,
+    ) {
+        if T::STORAGE == ComponentStorage::Table {
+            filters.push((
+                Some(first_channel),
+                Filter {
+                    component_id: get_component_id::<T>(),
+                    filter_type: FilterType::With,
+                },
+            ));
         }
+        mutable.push(false);
     }
-    fn mutable() -> bool {
-        false
+    fn matches_channels(channels: &[Option<usize>]) -> bool {
+        T::STORAGE == ComponentStorage::Sparse || channels[0].is_some()
+    }
+    fn append_sparse(sparse: &mut Vec<(ComponentId, bool)>) {
+        if T::STORAGE == ComponentStorage::Sparse {
+            sparse.push((get_component_id::<T>(), false));
+        }
     }
 }
 
 impl<'a, T: ComponentTrait> QueryParameterFetchTrait<'a> for &T {
-    type FetchResult = RwLockReadGuard<'a, Vec<T>>;
+    type FetchResult = ReadFetch<'a, T>;
     fn fetch(
         archetype: &'a Archetype,
-        channel_index: Option<usize>,
+        channels: &[Option<usize>],
+        sparse: &SparseBorrows<'a>,
+        _change_ticks: ChangeTicks,
     ) -> Result<Self::FetchResult, KecsError> {
-        archetype.get_read_channel(channel_index.unwrap())
+        Ok(match T::STORAGE {
+            ComponentStorage::Table => {
+                ReadFetch::Table(archetype.get_read_channel(channels[0].unwrap())?)
+            }
+            ComponentStorage::Sparse => {
+                ReadFetch::Sparse(SparseColumn::new(archetype, sparse, false))
+            }
+        })
     }
 }
 
-impl<'a, T: 'static> GetIteratorsTrait<'a> for RwLockReadGuard<'_, Vec<T>> {
-    type Iterator = std::slice::Iter<'a, T>;
-    type IteratorMut = std::slice::Iter<'a, T>;
+impl<'a, T: ComponentTrait> GetIteratorsTrait<'a> for ReadFetch<'_, T> {
+    type Item = &'a T;
+    type ItemMut = &'a T;
+    type Iterator = ReadIterator<'a, T>;
+    type IteratorMut = ReadIterator<'a, T>;
     fn get_iterator(&'a self) -> Self::Iterator {
-        self.iter()
+        match self {
+            Self::Table(channel) => ReadIterator::table(channel),
+            Self::Sparse(column) => ReadIterator::sparse(*column),
+        }
     }
     fn get_iterator_mut(&'a mut self) -> Self::IteratorMut {
-        self.iter()
+        self.get_iterator()
     }
-    fn get_components(&'a self, index: usize) -> <Self::Iterator as Iterator>::Item {
-        &self[index]
+    fn get_components(&'a self, index: usize) -> Option<Self::Item> {
+        match self {
+            Self::Table(channel) => Some(&channel[index]),
+            // Safety: The [Query] holds at least a read lock on the [SparseSet].
+            Self::Sparse(column) => column.get(index).map(|component| unsafe { &*component }),
+        }
     }
-    fn get_components_mut(&'a mut self, index: usize) -> <Self::IteratorMut as Iterator>::Item {
-        &self[index]
+    fn get_components_mut(&'a mut self, index: usize) -> Option<Self::ItemMut> {
+        self.get_components(index)
+    }
+}
+
+/// Mutable components are marked as changed at `change_tick` when they're borrowed mutably.
+#[doc(hidden)]
+pub struct WriteFetch<'a, T> {
+    storage: WriteStorage<'a, T>,
+    change_tick: u64,
+}
+
+enum WriteStorage<'a, T> {
+    Table(RwLockWriteGuard<'a, Vec<T>>, &'a [AtomicU64]),
+    Sparse(SparseColumn<'a, T>),
+}
+
+/// Only one of the iterators is used, depending on the component's [ComponentStorage].
+#[doc(hidden)]
+pub struct WriteIterator<'a, T> {
+    table: std::slice::IterMut<'a, T>,
+    table_change_ticks: std::slice::Iter<'a, AtomicU64>,
+    sparse: Option<SparseIterMut<'a, T>>,
+    change_tick: u64,
+}
+
+impl<'a, T> WriteIterator<'a, T> {
+    fn table(channel: &'a mut [T], change_ticks: &'a [AtomicU64], change_tick: u64) -> Self {
+        Self {
+            table: channel.iter_mut(),
+            table_change_ticks: change_ticks.iter(),
+            sparse: None,
+            change_tick,
+        }
+    }
+
+    fn sparse(sparse: SparseIterMut<'a, T>) -> Self {
+        Self {
+            table: Default::default(),
+            table_change_ticks: Default::default(),
+            change_tick: sparse.change_tick,
+            sparse: Some(sparse),
+        }
+    }
+}
+
+impl<T: ComponentTrait> SplitRowIterator for WriteIterator<'_, T> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        match self.sparse {
+            Some(sparse) => {
+                let (first, second) = sparse.split_at(row);
+                (Self::sparse(first), Self::sparse(second))
+            }
+            None => {
+                let (first, second) = self.table.into_slice().split_at_mut(row);
+                let (first_ticks, second_ticks) = self.table_change_ticks.as_slice().split_at(row);
+                (
+                    Self::table(first, first_ticks, self.change_tick),
+                    Self::table(second, second_ticks, self.change_tick),
+                )
+            }
+        }
+    }
+}
+
+impl<'a, T: ComponentTrait> Iterator for WriteIterator<'a, T> {
+    type Item = Option<&'a mut T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        // `T::STORAGE` is a constant so table components don't check for sparse rows.
+        match T::STORAGE {
+            ComponentStorage::Table => {
+                let component = self.table.next()?;
+                self.table_change_ticks
+                    .next()?
+                    .store(self.change_tick, Ordering::Relaxed);
+                Some(Some(component))
+            }
+            ComponentStorage::Sparse => self.sparse.as_mut()?.next(),
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.sparse {
+            Some(iter) => iter.size_hint(),
+            None => self.table.size_hint(),
+        }
     }
 }
 
 impl<T: ComponentTrait> QueryParameterTrait for &mut T {
-    fn filter() -> Filter {
-        Filter {
-            component_id: get_component_id::<T>(),
-            filter_type: FilterType::With,
+    fn append_filters(
+        filters: &mut Vec<(Option<usize>, Filter)>,
+        mutable: &mut Vec<bool>,
+        first_channel: usize,
+    ) {
+        if T::STORAGE == ComponentStorage::Table {
+            filters.push((
+                Some(first_channel),
+                Filter {
+                    component_id: get_component_id::<T>(),
+                    filter_type: FilterType::With,
+                },
+            ));
         }
+        mutable.push(true);
     }
-    fn mutable() -> bool {
-        true
+    fn matches_channels(channels: &[Option<usize>]) -> bool {
+        T::STORAGE == ComponentStorage::Sparse || channels[0].is_some()
+    }
+    fn append_sparse(sparse: &mut Vec<(ComponentId, bool)>) {
+        if T::STORAGE == ComponentStorage::Sparse {
+            sparse.push((get_component_id::<T>(), true));
+        }
     }
 }
 
 impl<'a, T: ComponentTrait> QueryParameterFetchTrait<'a> for &mut T {
-    type FetchResult = RwLockWriteGuard<'a, Vec<T>>;
+    type FetchResult = WriteFetch<'a, T>;
     fn fetch(
         archetype: &'a Archetype,
-        channel_index: Option<usize>,
+        channels: &[Option<usize>],
+        sparse: &SparseBorrows<'a>,
+        change_ticks: ChangeTicks,
     ) -> Result<Self::FetchResult, KecsError> {
-        archetype.get_write_channel(channel_index.unwrap())
+        let storage = match T::STORAGE {
+            ComponentStorage::Table => {
+                let channel_index = channels[0].unwrap();
+                WriteStorage::Table(
+                    archetype.get_write_channel(channel_index)?,
+                    archetype.get_change_ticks(channel_index),
+                )
+            }
+            ComponentStorage::Sparse => {
+                WriteStorage::Sparse(SparseColumn::new(archetype, sparse, true))
+            }
+        };
+        Ok(WriteFetch {
+            storage,
+            change_tick: change_ticks.this_run,
+        })
     }
 }
 
-impl<'a, T: 'static> GetIteratorsTrait<'a> for RwLockWriteGuard<'_, Vec<T>> {
-    type Iterator = std::slice::Iter<'a, T>;
-    type IteratorMut = std::slice::IterMut<'a, T>;
+impl<'a, T: ComponentTrait> GetIteratorsTrait<'a> for WriteFetch<'_, T> {
+    type Item = &'a T;
+    type ItemMut = &'a mut T;
+    type Iterator = ReadIterator<'a, T>;
+    type IteratorMut = WriteIterator<'a, T>;
     fn get_iterator(&'a self) -> Self::Iterator {
-        self.iter()
+        match &self.storage {
+            WriteStorage::Table(channel, _) => ReadIterator::table(channel),
+            WriteStorage::Sparse(column) => ReadIterator::sparse(*column),
+        }
     }
     fn get_iterator_mut(&'a mut self) -> Self::IteratorMut {
-        self.iter_mut()
+        match &mut self.storage {
+            WriteStorage::Table(channel, change_ticks) => {
+                WriteIterator::table(channel, change_ticks, self.change_tick)
+            }
+            WriteStorage::Sparse(column) => WriteIterator::sparse(SparseIterMut {
+                column: *column,
+                rows: 0..column.entities.len(),
+                change_tick: self.change_tick,
+            }),
+        }
     }
-    fn get_components(&'a self, index: usize) -> <Self::Iterator as Iterator>::Item {
-        &self[index]
+    fn get_components(&'a self, index: usize) -> Option<Self::Item> {
+        match &self.storage {
+            WriteStorage::Table(channel, _) => Some(&channel[index]),
+            // Safety: The [Query] holds a write lock on the [SparseSet].
+            WriteStorage::Sparse(column) => {
+                column.get(index).map(|component| unsafe { &*component })
+            }
+        }
     }
-    fn get_components_mut(&'a mut self, index: usize) -> <Self::IteratorMut as Iterator>::Item {
-        &mut self[index]
+    fn get_components_mut(&'a mut self, index: usize) -> Option<Self::ItemMut> {
+        match &mut self.storage {
+            WriteStorage::Table(channel, change_ticks) => {
+                change_ticks[index].store(self.change_tick, Ordering::Relaxed);
+                Some(&mut channel[index])
+            }
+            // Safety: The [Query] holds a write lock on the [SparseSet] and `self` is borrowed mutably.
+            WriteStorage::Sparse(column) => column.get(index).map(|component| {
+                column.set_changed(index, self.change_tick);
+                unsafe { &mut *component }
+            }),
+        }
     }
 }
 
 impl<Q: QueryParameterTrait> QueryParameterTrait for Option<Q> {
-    fn filter() -> Filter {
-        let inner_filter = Q::filter();
-        Filter {
-            filter_type: FilterType::Optional,
-            component_id: inner_filter.component_id,
+    const CHANNEL_COUNT: usize = Q::CHANNEL_COUNT;
+    fn append_filters(
+        filters: &mut Vec<(Option<usize>, Filter)>,
+        mutable: &mut Vec<bool>,
+        first_channel: usize,
+    ) {
+        let first_filter = filters.len();
+        Q::append_filters(filters, mutable, first_channel);
+        for (_, filter) in &mut filters[first_filter..] {
+            if let FilterType::With = filter.filter_type {
+                filter.filter_type = FilterType::Optional;
+            }
         }
     }
-    fn mutable() -> bool {
-        Q::mutable()
+    fn append_sparse(sparse: &mut Vec<(ComponentId, bool)>) {
+        Q::append_sparse(sparse)
     }
 }
 
-impl<'a, Q: QueryParameterFetchTrait<'a>> QueryParameterFetchTrait<'a> for Option<Q> {
-    type FetchResult = (usize, Option<Q::FetchResult>);
+impl<'a, Q: QueryParameterTrait> QueryParameterFetchTrait<'a> for Option<Q> {
+    type FetchResult = (
+        usize,
+        Option<<Q as QueryParameterFetchTrait<'a>>::FetchResult>,
+    );
     fn fetch(
         archetype: &'a Archetype,
-        channel_index: Option<usize>,
+        channels: &[Option<usize>],
+        sparse: &SparseBorrows<'a>,
+        change_ticks: ChangeTicks,
     ) -> Result<Self::FetchResult, KecsError> {
         Ok((
             archetype.entities.len(),
-            if let Some(channel_index) = channel_index {
-                Some(Q::fetch(archetype, Some(channel_index))?)
+            if Q::matches_channels(channels) {
+                Some(<Q as QueryParameterFetchTrait<'a>>::fetch(
+                    archetype,
+                    channels,
+                    sparse,
+                    change_ticks,
+                )?)
             } else {
                 None
             },
@@ -486,6 +1403,8 @@ impl<'a, Q: QueryParameterFetchTrait<'a>> QueryParameterFetchTrait<'a> for Optio
 }
 
 impl<'a, T: GetIteratorsTrait<'a>> GetIteratorsTrait<'a> for (usize, Option<T>) {
+    type Item = Option<T::Item>;
+    type ItemMut = Option<T::ItemMut>;
     type Iterator = OptionIterator<T::Iterator>;
     type IteratorMut = OptionIterator<T::IteratorMut>;
     fn get_iterator(&'a self) -> Self::Iterator {
@@ -494,10 +1413,273 @@ impl<'a, T: GetIteratorsTrait<'a>> GetIteratorsTrait<'a> for (usize, Option<T>)
     fn get_iterator_mut(&'a mut self) -> Self::IteratorMut {
         OptionIterator::new(self.0, self.1.as_mut().map(|s| s.get_iterator_mut()))
     }
-    fn get_components(&'a self, index: usize) -> <Self::Iterator as Iterator>::Item {
-        self.1.as_ref().map(|i| i.get_components(index))
+    fn get_components(&'a self, index: usize) -> Option<Self::Item> {
+        Some(self.1.as_ref().and_then(|i| i.get_components(index)))
+    }
+    fn get_components_mut(&'a mut self, index: usize) -> Option<Self::ItemMut> {
+        Some(self.1.as_mut().and_then(|i| i.get_components_mut(index)))
+    }
+}
+
+/// Fetches the [Entity] that owns the components.
+///
+/// `Query<(Entity, &A)>`
+impl QueryParameterTrait for Entity {
+    const CHANNEL_COUNT: usize = 0;
+    fn append_filters(
+        _filters: &mut Vec<(Option<usize>, Filter)>,
+        _mutable: &mut Vec<bool>,
+        _first_channel: usize,
+    ) {
+    }
+}
+
+impl<'a> QueryParameterFetchTrait<'a> for Entity {
+    type FetchResult = &'a [Entity];
+    fn fetch(
+        archetype: &'a Archetype,
+        _channels: &[Option<usize>],
+        _sparse: &SparseBorrows<'a>,
+        _change_ticks: ChangeTicks,
+    ) -> Result<Self::FetchResult, KecsError> {
+        Ok(&archetype.entities)
+    }
+}
+
+#[doc(hidden)]
+pub struct EntityIterator<'a>(std::slice::Iter<'a, Entity>);
+
+impl Iterator for EntityIterator<'_> {
+    type Item = Option<Entity>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|entity| Some(*entity))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl SplitRowIterator for EntityIterator<'_> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        let (first, second) = self.0.as_slice().split_at(row);
+        (Self(first.iter()), Self(second.iter()))
+    }
+}
+
+impl<'a> GetIteratorsTrait<'a> for &[Entity] {
+    type Item = Entity;
+    type ItemMut = Entity;
+    type Iterator = EntityIterator<'a>;
+    type IteratorMut = Self::Iterator;
+    fn get_iterator(&'a self) -> Self::Iterator {
+        EntityIterator(self.iter())
+    }
+    fn get_iterator_mut(&'a mut self) -> Self::IteratorMut {
+        EntityIterator(self.iter())
+    }
+    fn get_components(&'a self, index: usize) -> Option<Self::Item> {
+        Some(self[index])
+    }
+    fn get_components_mut(&'a mut self, index: usize) -> Option<Self::ItemMut> {
+        Some(self[index])
+    }
+}
+
+/// Fetches `true` if the [Entity] has the component, without borrowing the component.
+///
+/// `Query<(&A, Has<B>)>`
+pub struct Has<T: ComponentTrait> {
+    phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T: ComponentTrait> QueryParameterTrait for Has<T> {
+    fn append_filters(
+        filters: &mut Vec<(Option<usize>, Filter)>,
+        mutable: &mut Vec<bool>,
+        first_channel: usize,
+    ) {
+        if T::STORAGE == ComponentStorage::Table {
+            filters.push((
+                Some(first_channel),
+                Filter {
+                    component_id: get_component_id::<T>(),
+                    filter_type: FilterType::Optional,
+                },
+            ));
+        }
+        mutable.push(false);
+    }
+    fn append_sparse(sparse: &mut Vec<(ComponentId, bool)>) {
+        if T::STORAGE == ComponentStorage::Sparse {
+            sparse.push((get_component_id::<T>(), false));
+        }
+    }
+}
+
+impl<'a, T: ComponentTrait> QueryParameterFetchTrait<'a> for Has<T> {
+    type FetchResult = HasFetch<'a>;
+    fn fetch(
+        archetype: &'a Archetype,
+        channels: &[Option<usize>],
+        sparse: &SparseBorrows<'a>,
+        _change_ticks: ChangeTicks,
+    ) -> Result<Self::FetchResult, KecsError> {
+        let indices = match T::STORAGE {
+            ComponentStorage::Table => None,
+            ComponentStorage::Sparse => {
+                Some(SparseColumn::<T>::new(archetype, sparse, false).indices)
+            }
+        };
+        Ok(HasFetch {
+            entities: &archetype.entities,
+            has: channels[0].is_some(),
+            indices,
+        })
+    }
+}
+
+#[doc(hidden)]
+pub struct HasFetch<'a> {
+    entities: &'a [Entity],
+    has: bool,
+    /// The indices of a sparse component's [SparseSet].
+    indices: Option<&'a [Option<usize>]>,
+}
+
+impl HasFetch<'_> {
+    fn has(&self, entity: &Entity) -> bool {
+        match self.indices {
+            Some(indices) => matches!(indices.get(entity.index as usize), Some(Some(_))),
+            None => self.has,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct HasIterator<'a> {
+    fetch: &'a HasFetch<'a>,
+    entities: std::slice::Iter<'a, Entity>,
+}
+
+impl SplitRowIterator for HasIterator<'_> {
+    fn split_at(self, row: usize) -> (Self, Self) {
+        let (first, second) = self.entities.as_slice().split_at(row);
+        (
+            Self {
+                fetch: self.fetch,
+                entities: first.iter(),
+            },
+            Self {
+                fetch: self.fetch,
+                entities: second.iter(),
+            },
+        )
+    }
+}
+
+impl Iterator for HasIterator<'_> {
+    type Item = Option<bool>;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(Some(self.fetch.has(self.entities.next()?)))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entities.size_hint()
+    }
+}
+
+impl<'a> GetIteratorsTrait<'a> for HasFetch<'_> {
+    type Item = bool;
+    type ItemMut = bool;
+    type Iterator = HasIterator<'a>;
+    type IteratorMut = HasIterator<'a>;
+    fn get_iterator(&'a self) -> Self::Iterator {
+        HasIterator {
+            fetch: self,
+            entities: self.entities.iter(),
+        }
+    }
+    fn get_iterator_mut(&'a mut self) -> Self::IteratorMut {
+        self.get_iterator()
+    }
+    fn get_components(&'a self, index: usize) -> Option<Self::Item> {
+        Some(self.has(&self.entities[index]))
+    }
+    fn get_components_mut(&'a mut self, index: usize) -> Option<Self::ItemMut> {
+        self.get_components(index)
+    }
+}
+
+/// Fetches the components of the [Entity]s that have at least one of the components.
+/// Each component is returned as an [Option].
+///
+/// `Query<AnyOf<(&A, &mut B)>>`
+pub struct AnyOf<T> {
+    phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+/// Implemented for tuples of [Option]s to check if an [AnyOf] row matches.
+#[doc(hidden)]
+pub trait AnyIsSome {
+    fn any_is_some(&self) -> bool;
+}
+
+impl<T> AnyIsSome for Option<T> {
+    fn any_is_some(&self) -> bool {
+        self.is_some()
+    }
+}
+
+#[doc(hidden)]
+pub struct AnyOfFetch<T>(pub(crate) T);
+
+#[doc(hidden)]
+pub struct AnyOfIterator<I>(I);
+
+impl<I: SplitRowIterator> SplitRowIterator for AnyOfIterator<I>
+where
+    I::Row: AnyIsSome,
+{
+    fn split_at(self, row: usize) -> (Self, Self) {
+        let (first, second) = self.0.split_at(row);
+        (Self(first), Self(second))
+    }
+}
+
+impl<I: RowIterator> Iterator for AnyOfIterator<I>
+where
+    I::Row: AnyIsSome,
+{
+    type Item = Option<I::Row>;
+    fn next(&mut self) -> Option<Self::Item> {
+        // An [Entity] may be missing all of the components if some of them are sparse.
+        Some(self.0.next_row()?.filter(|row| row.any_is_some()))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T: GetIteratorsTrait<'a>> GetIteratorsTrait<'a> for AnyOfFetch<T>
+where
+    T::Item: AnyIsSome,
+    T::ItemMut: AnyIsSome,
+{
+    type Item = T::Item;
+    type ItemMut = T::ItemMut;
+    type Iterator = AnyOfIterator<T::Iterator>;
+    type IteratorMut = AnyOfIterator<T::IteratorMut>;
+    fn get_iterator(&'a self) -> Self::Iterator {
+        AnyOfIterator(self.0.get_iterator())
+    }
+    fn get_iterator_mut(&'a mut self) -> Self::IteratorMut {
+        AnyOfIterator(self.0.get_iterator_mut())
+    }
+    fn get_components(&'a self, index: usize) -> Option<Self::Item> {
+        self.0.get_components(index).filter(|row| row.any_is_some())
     }
-    fn get_components_mut(&'a mut self, index: usize) -> <Self::IteratorMut as Iterator>::Item {
-        self.1.as_mut().map(|i| i.get_components_mut(index))
+    fn get_components_mut(&'a mut self, index: usize) -> Option<Self::ItemMut> {
+        self.0
+            .get_components_mut(index)
+            .filter(|row| row.any_is_some())
     }
 }
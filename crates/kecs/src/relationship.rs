@@ -0,0 +1,189 @@
+//! Relationships between [Entity]s other than the parent and child tree of [crate::hierarchy::HierarchyNode].
+//!
+//! Each kind of relationship is a marker type that implements [RelationshipTrait].
+//! An [Entity] has at most one target per kind of relationship, but a target can
+//! have any number of [Entity]s related to it.
+//!
+//! ```ignore
+//! struct OwnedBy;
+//! impl RelationshipTrait for OwnedBy {
+//!     const DESPAWN_POLICY: DespawnPolicy = DespawnPolicy::Cascade;
+//! }
+//!
+//! Relation::<OwnedBy>::relate(world, sword, player)?;
+//! for owned in RelatedFrom::<OwnedBy>::sources_of(&query, player) { }
+//! ```
+use crate::*;
+
+/// What happens to the [Entity]s related to a target when the target is despawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DespawnPolicy {
+    /// Despawn the related [Entity]s as well.
+    Cascade,
+    /// Keep the [Relation] component but set its target to `None`.
+    Orphan,
+    /// Remove the [Relation] component.
+    RemoveRelation,
+}
+
+/// Implemented by marker types to declare a kind of relationship.
+pub trait RelationshipTrait: 'static + Send + Sync {
+    const DESPAWN_POLICY: DespawnPolicy = DespawnPolicy::RemoveRelation;
+}
+
+/// Added to an [Entity] that is related to a target [Entity].
+/// Use [Relation::relate] and [Relation::unrelate] to edit relationships
+/// so that the target's [RelatedFrom] stays up to date.
+pub struct Relation<R: RelationshipTrait> {
+    target: Option<Entity>,
+    phantom: std::marker::PhantomData<fn() -> R>,
+}
+
+/// Added to an [Entity] that other [Entity]s have a [Relation] with.
+/// This is maintained automatically.
+pub struct RelatedFrom<R: RelationshipTrait> {
+    sources: Vec<Entity>,
+    phantom: std::marker::PhantomData<fn() -> R>,
+}
+
+impl<R: RelationshipTrait> Relation<R> {
+    /// The target of this [Relation].
+    /// This is `None` if the target was despawned with [DespawnPolicy::Orphan].
+    pub fn target(&self) -> Option<Entity> {
+        self.target
+    }
+
+    /// Relate `source` to `target`, replacing any previous target of this kind of relationship.
+    pub fn relate(world: &mut World, source: Entity, target: Entity) -> Result<(), KecsError> {
+        if let Ok(relation) = world.get_component_mut::<Self>(source) {
+            if let Some(old_target) = relation.target.take() {
+                RelatedFrom::<R>::remove_source(world, old_target, source);
+            }
+            world.get_component_mut::<Self>(source)?.target = Some(target);
+        } else {
+            world.add_component(
+                source,
+                Self {
+                    target: Some(target),
+                    phantom: std::marker::PhantomData,
+                },
+            )?;
+        }
+
+        if let Ok(related_from) = world.get_component_mut::<RelatedFrom<R>>(target) {
+            related_from.sources.push(source);
+            Ok(())
+        } else {
+            world.add_component(
+                target,
+                RelatedFrom::<R> {
+                    sources: vec![source],
+                    phantom: std::marker::PhantomData,
+                },
+            )
+        }
+    }
+
+    /// Removes the [Relation] from `source`.
+    pub fn unrelate(world: &mut World, source: Entity) -> Result<(), KecsError> {
+        let relation = world.remove_component::<Self>(source)?;
+        if let Some(target) = relation.target {
+            RelatedFrom::<R>::remove_source(world, target, source);
+        }
+        Ok(())
+    }
+
+    fn on_despawn(world: &mut World, entity: Entity) {
+        if let Some(target) = world
+            .get_component_mut::<Self>(entity)
+            .ok()
+            .and_then(|relation| relation.target)
+        {
+            RelatedFrom::<R>::remove_source(world, target, entity);
+        }
+    }
+}
+
+impl<R: RelationshipTrait> RelatedFrom<R> {
+    /// The [Entity]s that have a [Relation] with this [Entity].
+    pub fn sources(&self) -> &[Entity] {
+        &self.sources
+    }
+
+    /// Gets the [Entity]s that have a [Relation] with `target`.
+    /// Returns an empty slice if there are none.
+    pub fn sources_of<'b>(query: &'b Query<&RelatedFrom<R>>, target: Entity) -> &'b [Entity] {
+        query
+            .get_entity_components(target)
+            .map_or(&[], |related_from| related_from.sources())
+    }
+
+    fn remove_source(world: &mut World, target: Entity, source: Entity) {
+        if let Ok(related_from) = world.get_component_mut::<Self>(target) {
+            related_from.sources.retain(|s| *s != source);
+            if related_from.sources.is_empty() {
+                let _ = world.remove_component::<Self>(target);
+            }
+        }
+    }
+
+    fn on_despawn(world: &mut World, entity: Entity) {
+        // Removing the component first means hooks on the sources won't try to edit it.
+        let sources = match world.remove_component::<Self>(entity) {
+            Ok(related_from) => related_from.sources,
+            Err(_) => return,
+        };
+        for source in sources {
+            match R::DESPAWN_POLICY {
+                DespawnPolicy::Cascade => {
+                    // The source may have already been despawned by another relationship.
+                    let _ = world.despawn(source);
+                }
+                DespawnPolicy::Orphan => {
+                    if let Ok(relation) = world.get_component_mut::<Relation<R>>(source) {
+                        relation.target = None;
+                    }
+                }
+                DespawnPolicy::RemoveRelation => {
+                    let _ = world.remove_component::<Relation<R>>(source);
+                }
+            }
+        }
+    }
+}
+
+impl<R: RelationshipTrait> ComponentTrait for Relation<R> {
+    const ON_DESPAWN: Option<fn(&mut World, Entity)> = Some(Self::on_despawn);
+
+    fn clone_components(entity_migrator: &mut EntityMigrator, items: &[Self]) -> Option<Vec<Self>> {
+        Some(
+            items
+                .iter()
+                .map(|relation| Self {
+                    target: relation.target.map(|e| entity_migrator.migrate(e)),
+                    phantom: std::marker::PhantomData,
+                })
+                .collect(),
+        )
+    }
+}
+
+impl<R: RelationshipTrait> ComponentTrait for RelatedFrom<R> {
+    const ON_DESPAWN: Option<fn(&mut World, Entity)> = Some(Self::on_despawn);
+
+    fn clone_components(entity_migrator: &mut EntityMigrator, items: &[Self]) -> Option<Vec<Self>> {
+        Some(
+            items
+                .iter()
+                .map(|related_from| Self {
+                    sources: related_from
+                        .sources
+                        .iter()
+                        .map(|e| entity_migrator.migrate(*e))
+                        .collect(),
+                    phantom: std::marker::PhantomData,
+                })
+                .collect(),
+        )
+    }
+}
@@ -23,9 +23,9 @@ struct ResourceAccessGroup {
 struct SubSchedule {
     exclusive_system: Option<usize>,
     systems_being_scheduled: Vec<SystemBeingScheduled>,
-    /// Key is archetype index and channel
+    /// Key is an archetype channel or sparse component storage
     /// Value is an index into systems
-    resources: HashMap<(usize, usize), ResourceAccessGroup>,
+    resources: HashMap<WorldResource, ResourceAccessGroup>,
     last_exclusive_system_index: usize,
     systems_to_schedule: Vec<usize>,
 }
@@ -82,17 +82,14 @@ impl SubSchedule {
                         /*
                         println!(
                             "ARCHETYPE ACCESS: {:?}",
-                            archetype_access.archetype_index_and_channel
+                            archetype_access.resource
                         );
                         println!("MUTABLE: {:?}", archetype_access.mutable);
                         */
                         let last_exclusive_system_index = self.last_exclusive_system_index;
                         let current_systems = self
                             .resources
-                            .entry((
-                                archetype_access.archetype_index,
-                                archetype_access.channel_index,
-                            ))
+                            .entry(archetype_access.resource)
                             .or_insert_with(|| ResourceAccessGroup {
                                 waiting_on_index: 0,
                                 mutable: true,
@@ -21,6 +21,8 @@ impl<T: ComponentTrait> SystemParameterTrait for &T {
         Ok(SystemParameterMetaData {
             archetypes,
             channels,
+            // Sparse components can't be singletons.
+            sparse: Vec::new(),
         })
     }
 }
@@ -31,6 +33,7 @@ impl<'a, T: ComponentTrait> SystemParameterFetchTrait<'a> for &T {
     fn fetch(
         world: &'a World,
         meta_data: &SystemParameterMetaData,
+        _change_ticks: ChangeTicks,
     ) -> Result<Self::FetchResult, KecsError> {
         for (&archetype_index, channel_index) in
             meta_data.archetypes.iter().zip(meta_data.channels.iter())
@@ -71,6 +74,8 @@ impl<T: ComponentTrait> SystemParameterTrait for &mut T {
         Ok(SystemParameterMetaData {
             archetypes,
             channels,
+            // Sparse components can't be singletons.
+            sparse: Vec::new(),
         })
     }
 }
@@ -81,14 +86,16 @@ impl<'a, T: ComponentTrait> SystemParameterFetchTrait<'a> for &mut T {
     fn fetch(
         world: &'a World,
         meta_data: &SystemParameterMetaData,
+        change_ticks: ChangeTicks,
     ) -> Result<Self::FetchResult, KecsError> {
         for (&archetype_index, channel_index) in
             meta_data.archetypes.iter().zip(meta_data.channels.iter())
         {
             let (channel_index, _) = channel_index.unwrap();
-            let channel =
-                world.archetypes[archetype_index].get_write_channel::<T>(channel_index)?;
+            let archetype = &world.archetypes[archetype_index];
+            let channel = archetype.get_write_channel::<T>(channel_index)?;
             if !channel.is_empty() {
+                archetype.channels[channel_index].set_changed(0, change_ticks.this_run);
                 return Ok(channel);
             }
         }
@@ -112,6 +119,7 @@ pub trait SingletonQuery: SystemParameterTrait {
     fn get_from_archetype<'a>(
         archetype: &'a Archetype,
         channel_index: usize,
+        change_ticks: ChangeTicks,
     ) -> Result<<Self as SystemParameterFetchTrait<'a>>::FetchResult, KecsError>;
 }
 
@@ -122,6 +130,7 @@ impl<A: ComponentTrait> SingletonQuery for &A {
     fn get_from_archetype<'a>(
         archetype: &'a Archetype,
         channel_index: usize,
+        _change_ticks: ChangeTicks,
     ) -> Result<<Self as SystemParameterFetchTrait<'a>>::FetchResult, KecsError> {
         let channel = archetype.get_read_channel::<A>(channel_index)?;
         if channel.is_empty() {
@@ -139,11 +148,13 @@ impl<A: ComponentTrait> SingletonQuery for &mut A {
     fn get_from_archetype<'a>(
         archetype: &'a Archetype,
         channel_index: usize,
+        change_ticks: ChangeTicks,
     ) -> Result<<Self as SystemParameterFetchTrait<'a>>::FetchResult, KecsError> {
         let channel = archetype.get_write_channel::<A>(channel_index)?;
         if channel.is_empty() {
             Err(KecsError::no_matching_component::<A>())
         } else {
+            archetype.channels[channel_index].set_changed(0, change_ticks.this_run);
             Ok(channel)
         }
     }
@@ -179,6 +190,8 @@ impl<A: SingletonQuery, B: SingletonQuery> SystemParameterTrait for (A, B) {
         Ok(SystemParameterMetaData {
             archetypes,
             channels,
+            // Sparse components can't be singletons.
+            sparse: Vec::new(),
         })
     }
 }
@@ -249,6 +262,7 @@ macro_rules! singleton_impls {
                 Ok(SystemParameterMetaData {
                     archetypes,
                     channels,
+                    sparse: Vec::new(),
                 })
             }
         }
@@ -262,6 +276,7 @@ macro_rules! singleton_impls {
             fn fetch(
                 world: &'a World,
                 meta_data: &SystemParameterMetaData,
+                change_ticks: ChangeTicks,
             ) -> Result<Self::FetchResult, KecsError> {
                 for (&archetype_index, channel_indices) in meta_data
                     .archetypes
@@ -270,7 +285,7 @@ macro_rules! singleton_impls {
                 {
                     let archetype = &world.archetypes[archetype_index];
                     let channels: [(usize, bool); $count] =  [$( channel_indices[$index].unwrap(),)*];
-                    $(let $tuple = $tuple::get_from_archetype(archetype, channels[$index].0);
+                    $(let $tuple = $tuple::get_from_archetype(archetype, channels[$index].0, change_ticks);
                     if !$tuple.is_ok() {
                         continue;
                     })*
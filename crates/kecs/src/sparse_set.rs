@@ -5,6 +5,12 @@ pub struct SparseSet<T> {
     data_index_to_item_index: Vec<usize>,
 }
 
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> SparseSet<T> {
     pub fn new() -> Self {
         Self {
@@ -14,27 +20,49 @@ impl<T> SparseSet<T> {
         }
     }
 
-    pub fn insert(&mut self, index: usize, data: T) {
+    /// Inserts `data` at `index`, replacing and returning the previous value.
+    pub fn insert(&mut self, index: usize, data: T) -> Option<T> {
         // This line highlights the weakness of sparse sets.
         // They use a bunch of memory!
         if self.indices.len() <= index {
             self.indices.resize(index + 1, None);
         }
 
+        if let Some(data_index) = self.indices[index] {
+            return Some(std::mem::replace(&mut self.data[data_index], data));
+        }
+
         let new_index = self.data.len();
         self.indices[index] = Some(new_index);
+        self.data.push(data);
+        self.data_index_to_item_index.push(index);
+        None
+    }
 
-        // Is this a memory leak if an index is reused?
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        let data_index = self.indices.get_mut(index)?.take()?;
+        let data = self.data.swap_remove(data_index);
+        self.data_index_to_item_index.swap_remove(data_index);
 
-        self.data.push(data);
+        // Update the index of the item that was swapped into the removed item's place.
+        if let Some(moved_index) = self.data_index_to_item_index.get(data_index) {
+            self.indices[*moved_index] = Some(data_index);
+        }
+        Some(data)
+    }
 
-        self.data_index_to_item_index.push(index);
+    pub fn contains(&self, index: usize) -> bool {
+        matches!(self.indices.get(index), Some(Some(_)))
     }
 
     pub fn get(&self, index: usize) -> Option<&T> {
         Some(&self.data[(*self.indices.get(index)?)?])
     }
 
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        Some(&mut self.data[(*self.indices.get(index)?)?])
+    }
+
     pub fn len(&self) -> usize {
         self.data.len()
     }
@@ -46,4 +74,22 @@ impl<T> SparseSet<T> {
     pub fn data_index_to_item_index(&self) -> &Vec<usize> {
         &self.data_index_to_item_index
     }
+
+    /// Removes all items and returns them with their index.
+    pub fn drain(&mut self) -> impl Iterator<Item = (usize, T)> + '_ {
+        self.indices.clear();
+        self.data_index_to_item_index
+            .drain(..)
+            .zip(self.data.drain(..))
+    }
+
+    /// Where each index's item is stored in [SparseSet::data].
+    pub(crate) fn indices(&self) -> &[Option<usize>] {
+        &self.indices
+    }
+
+    /// A pointer to the start of [SparseSet::data] for handing out disjoint mutable borrows.
+    pub(crate) fn data_ptr(&mut self) -> *mut T {
+        self.data.as_mut_ptr()
+    }
 }
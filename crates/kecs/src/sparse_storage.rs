@@ -0,0 +1,249 @@
+use crate::sparse_set::SparseSet;
+use crate::*;
+use std::{
+    any::Any,
+    cell::UnsafeCell,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+/// Storage for a component that uses [ComponentStorage::Sparse].
+/// One of these is stored in the [World] per sparse component type.
+pub(crate) trait SparseStorageTrait: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn contains(&self, entity: Entity) -> bool;
+    /// The change tick of the [Entity]'s component, if it has the component.
+    fn change_tick(&self, entity: Entity) -> Option<u64>;
+    fn remove_entity(&mut self, entity: Entity);
+    fn on_despawn(&self) -> Option<fn(&mut World, Entity)>;
+    /// Cloned components count as changed at `change_tick`.
+    fn clone_storage(
+        &mut self,
+        entity_migrator: &mut EntityMigrator,
+        change_tick: u64,
+    ) -> Option<Box<dyn SparseStorageTrait>>;
+    fn append_storage(&mut self, other: &mut dyn SparseStorageTrait);
+}
+
+pub(crate) struct SparseStorage<T> {
+    set: UnsafeCell<SparseSet<T>>,
+    /// The [World::change_tick] each component was last added or mutably borrowed at.
+    /// Indexed the same way as `set`. These are atomic so that [Query]s can update them.
+    change_ticks: SparseSet<AtomicU64>,
+}
+
+// Safety: The [SparseStorage] is only accessed through the [RwLock] it is stored in.
+// [Query]s use the [UnsafeCell] to hand out borrows of different [Entity]s' components
+// while they hold a write lock.
+unsafe impl<T: Send + Sync> Sync for SparseStorage<T> {}
+
+impl<T: ComponentTrait> SparseStorage<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            set: UnsafeCell::new(SparseSet::new()),
+            change_ticks: SparseSet::new(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, entity: Entity, component: T, change_tick: u64) {
+        self.set.get_mut().insert(entity.index as usize, component);
+        self.change_ticks
+            .insert(entity.index as usize, AtomicU64::new(change_tick));
+    }
+
+    pub(crate) fn remove(&mut self, entity: Entity) -> Option<T> {
+        self.change_ticks.remove(entity.index as usize);
+        self.set.get_mut().remove(entity.index as usize)
+    }
+
+    /// Gets the [Entity]'s component and marks it as changed.
+    pub(crate) fn get_mut(&mut self, entity: Entity, change_tick: u64) -> Option<&mut T> {
+        *self.change_ticks.get_mut(entity.index as usize)?.get_mut() = change_tick;
+        self.set.get_mut().get_mut(entity.index as usize)
+    }
+}
+
+impl<T: ComponentTrait> SparseStorageTrait for SparseStorage<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn contains(&self, entity: Entity) -> bool {
+        // Safety: Only the indices are read, which [Query]s never hand out mutable borrows of.
+        unsafe { &*self.set.get() }.contains(entity.index as usize)
+    }
+    fn change_tick(&self, entity: Entity) -> Option<u64> {
+        self.change_ticks
+            .get(entity.index as usize)
+            .map(|change_tick| change_tick.load(Ordering::Relaxed))
+    }
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+    fn on_despawn(&self) -> Option<fn(&mut World, Entity)> {
+        T::ON_DESPAWN
+    }
+    fn clone_storage(
+        &mut self,
+        entity_migrator: &mut EntityMigrator,
+        change_tick: u64,
+    ) -> Option<Box<dyn SparseStorageTrait>> {
+        let set = self.set.get_mut();
+        let cloned_components = T::clone_components(entity_migrator, set.data())?;
+        let mut new_storage = SparseStorage::<T>::new();
+        for (component, index) in cloned_components
+            .into_iter()
+            .zip(set.data_index_to_item_index())
+        {
+            // Only the index is used to migrate an [Entity].
+            let new_entity = entity_migrator.migrate(Entity {
+                index: *index as u32,
+                generation: 0,
+            });
+            new_storage.insert(new_entity, component, change_tick);
+        }
+        Some(Box::new(new_storage))
+    }
+    fn append_storage(&mut self, other: &mut dyn SparseStorageTrait) {
+        let other = other
+            .as_any_mut()
+            .downcast_mut::<SparseStorage<T>>()
+            .unwrap();
+        let set = self.set.get_mut();
+        for (index, component) in other.set.get_mut().drain() {
+            set.insert(index, component);
+        }
+        for (index, change_tick) in other.change_ticks.drain() {
+            self.change_ticks.insert(index, change_tick);
+        }
+    }
+}
+
+impl World {
+    /// Gets the [SparseStorage] for a component, creating it if it doesn't exist.
+    pub(crate) fn sparse_storage_mut<T: ComponentTrait>(&mut self) -> &mut SparseStorage<T> {
+        self.sparse_storages
+            .entry(get_component_id::<T>())
+            .or_insert_with(|| RwLock::new(Box::new(SparseStorage::<T>::new())))
+            .get_mut()
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<SparseStorage<T>>()
+            .unwrap()
+    }
+
+    pub(crate) fn get_sparse_storage_mut<T: ComponentTrait>(
+        &mut self,
+    ) -> Option<&mut SparseStorage<T>> {
+        Some(
+            self.sparse_storages
+                .get_mut(&get_component_id::<T>())?
+                .get_mut()
+                .unwrap()
+                .as_any_mut()
+                .downcast_mut::<SparseStorage<T>>()
+                .unwrap(),
+        )
+    }
+}
+
+enum SparseGuard<'a> {
+    Read(RwLockReadGuard<'a, Box<dyn SparseStorageTrait>>),
+    Write(RwLockWriteGuard<'a, Box<dyn SparseStorageTrait>>),
+}
+
+/// The sparse component storages locked by a [Query].
+#[doc(hidden)]
+pub struct SparseBorrows<'a> {
+    borrows: Vec<(ComponentId, SparseGuard<'a>)>,
+}
+
+impl<'a> SparseBorrows<'a> {
+    /// `parameters` are the sparse components a [Query] fetches and if they're mutable.
+    /// `filters` are only checked for whether an [Entity] has them.
+    pub(crate) fn new(
+        world: &'a World,
+        parameters: &[(ComponentId, bool)],
+        filters: &[ComponentId],
+    ) -> Result<Self, KecsError> {
+        let mut borrows: Vec<(ComponentId, SparseGuard<'a>)> = Vec::new();
+        let parameters = parameters
+            .iter()
+            .map(|(component_id, mutable)| (*component_id, *mutable, false));
+        let filters = filters
+            .iter()
+            .map(|component_id| (*component_id, false, true));
+        for (component_id, mutable, is_filter) in parameters.chain(filters) {
+            if let Some((_, guard)) = borrows.iter().find(|(id, _)| *id == component_id) {
+                // Filters only check which [Entity]s have a component so they can share any lock.
+                // Otherwise parameters can only share a read lock.
+                if is_filter || (!mutable && matches!(guard, SparseGuard::Read(_))) {
+                    continue;
+                }
+                return Err(KecsError::ChannelExclusivelyLocked);
+            }
+            let storage = match world.sparse_storages.get(&component_id) {
+                Some(storage) => storage,
+                // No [Entity] has had this component yet.
+                None => continue,
+            };
+            let guard = if mutable {
+                SparseGuard::Write(
+                    storage
+                        .try_write()
+                        .map_err(|_| KecsError::ChannelExclusivelyLocked)?,
+                )
+            } else {
+                SparseGuard::Read(
+                    storage
+                        .try_read()
+                        .map_err(|_| KecsError::ChannelExclusivelyLocked)?,
+                )
+            };
+            borrows.push((component_id, guard));
+        }
+        Ok(Self { borrows })
+    }
+
+    fn get_storage(&self, component_id: ComponentId) -> Option<&dyn SparseStorageTrait> {
+        self.borrows
+            .iter()
+            .find(|(id, _)| *id == component_id)
+            .map(|(_, guard)| match guard {
+                SparseGuard::Read(guard) => &***guard,
+                SparseGuard::Write(guard) => &***guard,
+            })
+    }
+
+    /// Returns `None` if no [Entity] has had the component.
+    /// The [SparseSet] may only be mutated if a mutable parameter requested it.
+    /// The second [SparseSet] contains the change tick of each component.
+    pub(crate) fn get<T: ComponentTrait>(
+        &self,
+    ) -> Option<(*mut SparseSet<T>, *const SparseSet<AtomicU64>)> {
+        self.get_storage(get_component_id::<T>()).map(|storage| {
+            let storage = storage.as_any().downcast_ref::<SparseStorage<T>>().unwrap();
+            (
+                storage.set.get(),
+                &storage.change_ticks as *const SparseSet<AtomicU64>,
+            )
+        })
+    }
+
+    pub(crate) fn contains<T: ComponentTrait>(&self, entity: Entity) -> bool {
+        self.get_storage(get_component_id::<T>())
+            .is_some_and(|storage| storage.contains(entity))
+    }
+
+    /// Returns true if the [Entity] has the component and it changed after `tick`.
+    pub(crate) fn changed_since<T: ComponentTrait>(&self, entity: Entity, tick: u64) -> bool {
+        self.get_storage(get_component_id::<T>())
+            .and_then(|storage| storage.change_tick(entity))
+            .is_some_and(|change_tick| change_tick > tick)
+    }
+}
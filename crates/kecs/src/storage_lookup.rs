@@ -31,8 +31,8 @@ impl StorageLookup {
         for (i, component) in component_ids.iter().enumerate() {
             self.component_archetypes
                 .entry(*component)
-                .or_insert_with(SparseSet::new)
-                .insert(archetype_index, i)
+                .or_default()
+                .insert(archetype_index, i);
         }
         self.all_archetypes.push(archetype_index);
     }
@@ -42,6 +42,16 @@ impl StorageLookup {
         // Filter and an optional channel associated with the filter.
         filters: &[(Option<usize>, Filter)],
     ) -> MatchingArchetypeIterator<CHANNEL_COUNT> {
+        MatchingArchetypeIterator(self.matching_archetypes(filters))
+    }
+
+    /// Like [StorageLookup::matching_archetype_iterator] but for when the number
+    /// of channels is only known at runtime.
+    pub(crate) fn matching_archetypes(
+        &self,
+        // Filter and an optional channel associated with the filter.
+        filters: &[(Option<usize>, Filter)],
+    ) -> MatchingArchetypes<'_> {
         let mut filter_info = Vec::with_capacity(filters.len());
         for (output_index, filter) in filters.iter() {
             let archetypes = self.component_archetypes.get(&filter.component_id);
@@ -62,7 +72,7 @@ impl StorageLookup {
         // Find the smallest requirement.
         filter_info.sort_by_key(|filter_info| filter_info.matching_archetypes_len);
 
-        MatchingArchetypeIterator {
+        MatchingArchetypes {
             offset: 0,
             filter_info,
             storage_info: self,
@@ -85,7 +95,9 @@ pub(crate) struct ArchetypeMatch<const CHANNEL_COUNT: usize> {
     pub channels: [Option<usize>; CHANNEL_COUNT],
 }
 
-pub(crate) struct MatchingArchetypeIterator<'a, const CHANNEL_COUNT: usize> {
+pub(crate) struct MatchingArchetypeIterator<'a, const CHANNEL_COUNT: usize>(MatchingArchetypes<'a>);
+
+pub(crate) struct MatchingArchetypes<'a> {
     offset: usize,
     filter_info: Vec<FilterInfo<'a>>,
     storage_info: &'a StorageLookup,
@@ -94,6 +106,19 @@ pub(crate) struct MatchingArchetypeIterator<'a, const CHANNEL_COUNT: usize> {
 impl<'a, const CHANNEL_COUNT: usize> Iterator for MatchingArchetypeIterator<'a, CHANNEL_COUNT> {
     type Item = ArchetypeMatch<CHANNEL_COUNT>;
     fn next(&mut self) -> Option<Self::Item> {
+        let mut channels = [None; CHANNEL_COUNT];
+        let archetype_index = self.0.next_into(&mut channels)?;
+        Some(ArchetypeMatch {
+            archetype_index,
+            channels,
+        })
+    }
+}
+
+impl<'a> MatchingArchetypes<'a> {
+    /// Finds the next matching [Archetype] and writes its channels into `channels`.
+    /// This is used when the number of channels is only known at runtime.
+    pub(crate) fn next_into(&mut self, channels: &mut [Option<usize>]) -> Option<usize> {
         fn match_tail_filters(
             filters: &[FilterInfo],
             archetype_index: usize,
@@ -138,11 +163,11 @@ impl<'a, const CHANNEL_COUNT: usize> Iterator for MatchingArchetypeIterator<'a,
             true
         }
 
-        let (first_filter_info, tail_filter_info) = self.filter_info.split_first()?;
-
-        match first_filter_info.filter_type {
+        match self.filter_info.split_first() {
             // This will almost always be the case chosen, but the other cases are handled just-in-case.
-            FilterType::With => {
+            Some((first_filter_info, tail_filter_info))
+                if matches!(first_filter_info.filter_type, FilterType::With) =>
+            {
                 let archetypes = first_filter_info.archetypes?;
                 for (channel, &archetype_index) in archetypes.data()[self.offset..]
                     .iter()
@@ -151,30 +176,25 @@ impl<'a, const CHANNEL_COUNT: usize> Iterator for MatchingArchetypeIterator<'a,
                     // Increment offset so that the iterator can resume where it left off.
                     self.offset += 1;
 
-                    let mut channels = [None; CHANNEL_COUNT];
+                    channels.fill(None);
                     if let Some(output_index) = first_filter_info.output_index {
                         channels[output_index] = Some(*channel);
                     }
 
-                    if match_tail_filters(tail_filter_info, archetype_index, &mut channels) {
-                        return Some(ArchetypeMatch {
-                            archetype_index,
-                            channels,
-                        });
+                    if match_tail_filters(tail_filter_info, archetype_index, channels) {
+                        return Some(archetype_index);
                     }
                 }
             }
-            FilterType::Optional | FilterType::Without => {
-                // These cases need to check *all* Archetypes.
+            // These cases need to check *all* Archetypes.
+            // This includes queries without any filters, like a query for just [Entity]s.
+            _ => {
                 for archetype_index in &self.storage_info.all_archetypes[self.offset..] {
                     self.offset += 1;
 
-                    let mut channels = [None; CHANNEL_COUNT];
-                    if match_tail_filters(&self.filter_info, *archetype_index, &mut channels) {
-                        return Some(ArchetypeMatch {
-                            archetype_index: *archetype_index,
-                            channels,
-                        });
+                    channels.fill(None);
+                    if match_tail_filters(&self.filter_info, *archetype_index, channels) {
+                        return Some(*archetype_index);
                     }
                 }
             }
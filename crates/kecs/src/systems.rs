@@ -1,19 +1,71 @@
 use crate::*;
 
+/// Storage in the [World] that a system parameter borrows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WorldResource {
+    /// A component channel of an [Archetype].
+    Channel {
+        archetype_index: usize,
+        channel_index: usize,
+    },
+    /// The storage of a component that uses [ComponentStorage::Sparse].
+    /// The whole storage is locked at once, regardless of which [Archetype]s are accessed.
+    Sparse(ComponentId),
+}
+
 pub struct ArchetypeAccess {
-    pub archetype_index: usize,
-    pub channel_index: usize,
+    pub resource: WorldResource,
     pub mutable: bool,
 }
 
 pub struct SystemParameterMetaData {
     pub archetypes: Vec<usize>,
     pub channels: Vec<Option<(usize, bool)>>,
+    /// The sparse components the parameter borrows and if they're borrowed mutably.
+    pub sparse: Vec<(ComponentId, bool)>,
 }
 
 impl SystemParameterMetaData {
+    pub fn empty() -> Self {
+        Self {
+            archetypes: Vec::new(),
+            channels: Vec::new(),
+            sparse: Vec::new(),
+        }
+    }
+
+    /// Adds the accesses of another parameter's [SystemParameterMetaData].
+    /// Used by parameters that are made up of other parameters.
+    ///
+    /// Each channel is stored alongside its own archetype, so the result
+    /// should only be used for scheduling and not passed to `other`'s `fetch`.
+    pub fn extend(&mut self, other: SystemParameterMetaData) {
+        if !other.archetypes.is_empty() && !other.channels.is_empty() {
+            let channel_count = other.channels.len() / other.archetypes.len();
+            for (archetype_index, channels) in other
+                .archetypes
+                .iter()
+                .zip(other.channels.chunks_exact(channel_count))
+            {
+                for channel in channels.iter().flatten() {
+                    self.archetypes.push(*archetype_index);
+                    self.channels.push(Some(*channel));
+                }
+            }
+        }
+        self.sparse.extend(other.sparse);
+    }
+
     pub fn append_meta_data(&self, archetype_access: &mut Vec<ArchetypeAccess>) {
-        if !self.archetypes.is_empty() {
+        for (component_id, mutable) in &self.sparse {
+            archetype_access.push(ArchetypeAccess {
+                resource: WorldResource::Sparse(*component_id),
+                mutable: *mutable,
+            })
+        }
+
+        // Parameters like `Query<Entity>` don't access any channels.
+        if !self.archetypes.is_empty() && !self.channels.is_empty() {
             let channel_count = self.channels.len() / self.archetypes.len();
             for (archetype_index, channels) in self
                 .archetypes
@@ -22,8 +74,10 @@ impl SystemParameterMetaData {
             {
                 for (channel_index, mutable) in channels.iter().flatten() {
                     archetype_access.push(ArchetypeAccess {
-                        archetype_index: *archetype_index,
-                        channel_index: *channel_index,
+                        resource: WorldResource::Channel {
+                            archetype_index: *archetype_index,
+                            channel_index: *channel_index,
+                        },
                         mutable: *mutable,
                     })
                 }
@@ -86,8 +140,10 @@ macro_rules! system_tuple_impls {
                     f($($tuple,)*)
                 }
 
+                // This system hasn't run before so every component counts as changed.
+                let change_ticks = world.change_ticks(0);
                 $(let $tuple = $tuple::get_meta_data(world)?;)*
-                $(let mut $tuple = <$tuple as SystemParameterFetchTrait<'return_lifetime>>::fetch(world, &$tuple)?;)*
+                $(let mut $tuple = <$tuple as SystemParameterFetchTrait<'return_lifetime>>::fetch(world, &$tuple, change_ticks)?;)*
                 $(let $tuple = $tuple.as_system_arg();)*
                 let result = call_inner(&mut self, $( $tuple ),*);
                 Ok(result)
@@ -113,16 +169,19 @@ macro_rules! system_tuple_impls {
                     f($($tuple,)*)
                 }
 
+                let mut last_run = 0;
                 System {
                     system_inner: SystemInner::NonExclusive{
                         system: Box::new(
                             move |world: &World| {
+                                let change_ticks = world.change_ticks(last_run);
                                 let mut archetype_access = Vec::new();
                                 $(let $tuple = $tuple::get_meta_data(world)?;)*
                                 $($tuple.append_meta_data(&mut archetype_access);)*
-                                $(let mut $tuple = <$tuple as SystemParameterFetchTrait>::fetch(world, &$tuple)?;)*
+                                $(let mut $tuple = <$tuple as SystemParameterFetchTrait>::fetch(world, &$tuple, change_ticks)?;)*
                                 $(let $tuple = $tuple.as_system_arg();)*
                                 call_inner(&mut self, $( $tuple ),*);
+                                last_run = change_ticks.this_run;
                                 Ok(())
                         }),
                         //meta_data: Box::new( |world: &World| {
@@ -180,6 +239,7 @@ pub trait SystemParameterFetchTrait<'a> {
     fn fetch(
         world: &'a World,
         meta_data: &SystemParameterMetaData,
+        change_ticks: ChangeTicks,
     ) -> Result<Self::FetchResult, KecsError>;
 }
 
@@ -423,7 +423,7 @@ fn despawn_and_add_world() {
 #[test]
 fn despawn_and_add_world1() {
     let mut world = World::new();
-    let entity_a = world.spawn(A);
+    world.spawn(A);
 
     let mut world_b = world.clone_world();
     let e = world_b.spawn(A);
@@ -432,6 +432,463 @@ fn despawn_and_add_world1() {
     world.add_world(&mut world_b);
 }
 
+#[test]
+fn add_components_to_reserved_entity() {
+    let mut world = World::new();
+    let entity = world.reserve_entity();
+    world.add_components(entity, (A, B)).unwrap();
+    (|query: Query<(&A, &B)>| {
+        assert_eq!(query.iter().count(), 1);
+    })
+    .run(&world);
+}
+
+#[test]
+fn despawn_hierarchy() {
+    use crate::hierarchy::HierarchyNode;
+
+    let mut world = World::new();
+    let root = world.spawn(A);
+    let parent = world.spawn(A);
+    let child0 = world.spawn(A);
+    let child1 = world.spawn(A);
+    HierarchyNode::set_parent(&mut world, Some(root), parent).unwrap();
+    HierarchyNode::set_parent(&mut world, Some(parent), child0).unwrap();
+    HierarchyNode::set_parent(&mut world, Some(parent), child1).unwrap();
+
+    HierarchyNode::despawn_hierarchy(&mut world, parent).unwrap();
+    assert_eq!(world.len(), 1);
+    assert!(world
+        .get_component_mut::<HierarchyNode>(root)
+        .unwrap()
+        .last_child()
+        .is_none());
+}
+
+#[test]
+fn entity_query() {
+    let mut world = World::new();
+    let entity_a = world.spawn(A);
+    let entity_b = world.spawn((A, B));
+    (|query: Query<(Entity, &A)>| {
+        let mut entities: Vec<Entity> = query.iter().map(|(e, _)| e).collect();
+        entities.sort();
+        assert_eq!(entities, [entity_a, entity_b]);
+    })
+    .run(&world);
+    (|query: Query<Entity, With<B>>| {
+        assert_eq!(query.get_single().unwrap(), entity_b);
+    })
+    .run(&world);
+}
+
+#[test]
+fn or_filter() {
+    let mut world = World::new();
+    world.spawn(A);
+    world.spawn((A, B));
+    world.spawn((A, C));
+    world.spawn(C);
+    type WithBOrC<'a> = Query<'a, &'static A, Or<(With<B>, With<C>)>>;
+    (|query: WithBOrC| {
+        assert_eq!(query.iter().count(), 2);
+    })
+    .run(&world);
+}
+
+#[test]
+fn any_of_query() {
+    let mut world = World::new();
+    world.spawn(A);
+    world.spawn((A, B));
+    world.spawn(B);
+    world.spawn(C);
+    (|query: Query<AnyOf<(&A, &B)>>| {
+        assert_eq!(query.iter().count(), 3);
+        assert_eq!(
+            query
+                .iter()
+                .filter(|(a, b)| a.is_some() && b.is_some())
+                .count(),
+            1
+        );
+    })
+    .run(&world);
+}
+
+#[test]
+fn has_query() {
+    let mut world = World::new();
+    world.spawn(A);
+    world.spawn((A, B));
+    (|query: Query<(&A, Has<B>)>| {
+        assert_eq!(query.iter().filter(|(_, has_b)| *has_b).count(), 1);
+    })
+    .run(&world);
+}
+
+#[test]
+fn single_query_helpers() {
+    let mut world = World::new();
+    world.spawn(A);
+    (|mut query: Query<&mut A>| {
+        assert!(query.get_single_mut().is_ok());
+    })
+    .run(&world);
+    world.spawn(A);
+    (|query: Query<&A>| {
+        assert_eq!(
+            query.get_single().err(),
+            Some(KecsError::MultipleMatchingEntities)
+        );
+    })
+    .run(&world);
+    (|query: Query<&B>| {
+        assert!(query.get_single().is_err());
+    })
+    .run(&world);
+}
+
+#[cfg(feature = "scheduler")]
+#[test]
+fn par_iter_mut() {
+    #[derive(Clone, Component)]
+    struct Count(usize);
+
+    ktasks::create_workers_with_count(4);
+
+    let mut world = World::new();
+    let mut entities = Vec::new();
+    for i in 0..1000 {
+        if i % 2 == 0 {
+            entities.push(world.spawn(Count(i)));
+        } else {
+            entities.push(world.spawn((Count(i), A)));
+        }
+    }
+    (|mut query: Query<(Entity, &mut Count)>| {
+        query.par_iter_mut(64, |(_, count)| count.0 += 1);
+    })
+    .run(&world);
+    (|query: Query<&Count>| {
+        let mut counts: Vec<usize> = query.iter().map(|count| count.0).collect();
+        counts.sort_unstable();
+        assert!(counts.into_iter().eq(1..1001));
+    })
+    .run(&world);
+
+    // Batches of sparse components skip the [Entity]s without them.
+    for (i, entity) in entities.into_iter().enumerate() {
+        if i % 3 == 0 {
+            world.add_component(entity, Selected(i)).unwrap();
+        }
+    }
+    (|mut query: Query<(&mut Count, &Selected)>| {
+        query.par_iter_mut(10, |(count, _)| count.0 = 0);
+    })
+    .run(&world);
+    (|query: Query<&Count>| {
+        assert_eq!(query.iter().filter(|count| count.0 == 0).count(), 334);
+    })
+    .run(&world);
+}
+
+#[derive(Clone, Component)]
+#[component(storage = "sparse")]
+struct Selected(usize);
+
+#[test]
+fn sparse_components() {
+    let mut world = World::new();
+    let entity_a = world.spawn(A);
+    let entity_b = world.spawn((A, B));
+    let archetype_count = world.archetypes.len();
+
+    world.add_component(entity_a, Selected(1)).unwrap();
+    world.add_component(entity_b, Selected(2)).unwrap();
+    assert_eq!(world.archetypes.len(), archetype_count);
+
+    world.get_component_mut::<Selected>(entity_b).unwrap().0 = 3;
+    assert_eq!(
+        world
+            .remove_component::<Selected>(entity_a)
+            .ok()
+            .map(|s| s.0),
+        Some(1)
+    );
+    assert!(world.remove_component::<Selected>(entity_a).is_err());
+    assert_eq!(world.archetypes.len(), archetype_count);
+
+    (|query: Query<(Entity, &A, &Selected)>| {
+        assert_eq!(query.get_single().unwrap().0, entity_b);
+        assert_eq!(query.get_single().unwrap().2 .0, 3);
+        assert!(query.get_entity_components(entity_a).is_none());
+    })
+    .run(&world);
+    (|mut query: Query<(&mut Selected, Option<&B>)>| {
+        for (selected, _) in &mut query {
+            selected.0 += 1;
+        }
+        assert_eq!(query.get_single().unwrap().0 .0, 4);
+    })
+    .run(&world);
+    (|query: Query<(&A, Option<&Selected>, Has<Selected>)>| {
+        assert_eq!(query.iter().filter(|(_, s, _)| s.is_some()).count(), 1);
+        assert_eq!(query.iter().filter(|(_, _, has)| *has).count(), 1);
+    })
+    .run(&world);
+    (|query: Query<AnyOf<(&B, &Selected)>>| {
+        assert_eq!(query.iter().count(), 1);
+    })
+    .run(&world);
+}
+
+#[test]
+fn sparse_filters() {
+    let mut world = World::new();
+    let entity_a = world.spawn(A);
+    let entity_b = world.spawn((A, Selected(0)));
+    world.spawn((B, Selected(1)));
+
+    (|query: Query<Entity, (With<A>, With<Selected>)>| {
+        assert_eq!(query.get_single().unwrap(), entity_b);
+    })
+    .run(&world);
+    (|query: Query<Entity, (With<A>, Without<Selected>)>| {
+        assert_eq!(query.get_single().unwrap(), entity_a);
+        assert!(query.get_entity_components(entity_b).is_none());
+    })
+    .run(&world);
+    type BOrSelected = Or<(With<B>, With<Selected>)>;
+    (|query: Query<Entity, BOrSelected>| {
+        assert_eq!(query.iter().count(), 2);
+    })
+    .run(&world);
+    (|query: Query<&Selected, With<B>>| {
+        assert_eq!(query.get_single().unwrap().0, 1);
+    })
+    .run(&world);
+    assert!(world.try_query::<(&Selected, &mut Selected)>().is_err());
+}
+
+#[test]
+fn changed_filter() {
+    use std::sync::{Arc, Mutex};
+
+    let mut world = World::new();
+    let entity_a = world.spawn((A, Selected(0)));
+    let entity_b = world.spawn((A, B));
+
+    let changed = Arc::new(Mutex::new(Vec::new()));
+    let mut changed_system = {
+        let changed = changed.clone();
+        (move |a: Query<Entity, Changed<A>>, selected: Query<Entity, Changed<Selected>>| {
+            let mut changed = changed.lock().unwrap();
+            changed.clear();
+            changed.extend(a.iter().map(|e| (e, "A")));
+            changed.extend(selected.iter().map(|e| (e, "Selected")));
+        })
+        .system()
+    };
+    let mut take_changed = |world: &mut World| {
+        changed_system.run(world);
+        let mut changed = changed.lock().unwrap().clone();
+        changed.sort();
+        changed
+    };
+
+    // Everything counts as changed the first time a system runs.
+    assert_eq!(
+        take_changed(&mut world),
+        [(entity_a, "A"), (entity_a, "Selected"), (entity_b, "A")]
+    );
+    assert!(take_changed(&mut world).is_empty());
+
+    world.get_component_mut::<A>(entity_b).unwrap();
+    assert_eq!(take_changed(&mut world), [(entity_b, "A")]);
+
+    // Mutably iterating a [Query] marks components as changed, reading doesn't.
+    (|mut query: Query<&mut Selected>, _b: Query<&B>| {
+        for selected in &mut query {
+            selected.0 += 1;
+        }
+    })
+    .system()
+    .run(&mut world);
+    (|query: Query<&A>| assert_eq!(query.iter().count(), 2))
+        .system()
+        .run(&mut world);
+    assert_eq!(take_changed(&mut world), [(entity_a, "Selected")]);
+
+    // Moving to a different [Archetype] keeps the change ticks of existing components.
+    world.add_component(entity_a, C).unwrap();
+    assert!(take_changed(&mut world).is_empty());
+    world.add_component(entity_a, Selected(5)).unwrap();
+    world.add_component(entity_b, A).unwrap();
+    assert_eq!(
+        take_changed(&mut world),
+        [(entity_a, "Selected"), (entity_b, "A")]
+    );
+
+    type ChangedBOrWithC<'a> = Query<'a, &'static A, Or<(Changed<B>, With<C>)>>;
+    (|query: ChangedBOrWithC| {
+        // A [Query] outside of a scheduled system sees every component as changed.
+        assert_eq!(query.iter().count(), 2);
+    })
+    .run(&world);
+}
+
+#[test]
+fn sparse_despawn_and_clone() {
+    let mut world = World::new();
+    let entity_a = world.spawn((A, Selected(0)));
+    world.spawn((A, Selected(1)));
+    world.despawn(entity_a).unwrap();
+
+    let entity_c = world.spawn(A);
+    (|query: Query<&Selected>| {
+        assert_eq!(query.get_single().unwrap().0, 1);
+    })
+    .run(&world);
+    assert!(world.get_component_mut::<Selected>(entity_c).is_err());
+
+    let mut other_world = World::new();
+    other_world.spawn((B, Selected(2)));
+    other_world.add_world(&mut world);
+    (|query: Query<&Selected>| {
+        let mut selected: Vec<usize> = query.iter().map(|s| s.0).collect();
+        selected.sort_unstable();
+        assert_eq!(selected, [1, 2]);
+    })
+    .run(&other_world);
+}
+
+#[test]
+fn sparse_meta_data() {
+    let mut world = World::new();
+    world.spawn((A, Selected(0)));
+
+    let selected = get_component_id::<Selected>();
+    let meta_data = <Query<(&A, &mut Selected)>>::get_meta_data(&world).unwrap();
+    assert_eq!(meta_data.sparse, [(selected, true)]);
+
+    let meta_data = <Query<&A, With<Selected>>>::get_meta_data(&world).unwrap();
+    assert_eq!(meta_data.sparse, [(selected, false)]);
+}
+
+#[test]
+fn extend_meta_data() {
+    let mut world = World::new();
+    world.spawn((A, B, Selected(0)));
+    world.spawn(A);
+
+    let mut meta_data = SystemParameterMetaData::empty();
+    meta_data.extend(<Query<(&A, &mut B)>>::get_meta_data(&world).unwrap());
+    meta_data.extend(<Query<(&A, Option<&Selected>)>>::get_meta_data(&world).unwrap());
+
+    // One channel per archetype entry. `Selected` is sparse so it has no channels.
+    assert_eq!(meta_data.archetypes.len(), meta_data.channels.len());
+    assert_eq!(meta_data.channels.len(), 2 + 2);
+    assert_eq!(
+        meta_data.channels.iter().filter(|c| c.unwrap().1).count(),
+        1
+    );
+    assert_eq!(meta_data.sparse, [(get_component_id::<Selected>(), false)]);
+
+    let mut archetype_access = Vec::new();
+    meta_data.append_meta_data(&mut archetype_access);
+    assert_eq!(archetype_access.len(), 5);
+}
+
+#[test]
+fn relationships() {
+    use relationship::*;
+
+    struct Targets;
+    impl RelationshipTrait for Targets {}
+
+    let mut world = World::new();
+    let target = world.spawn(A);
+    let source_a = world.spawn(B);
+    let source_b = world.spawn(B);
+    Relation::<Targets>::relate(&mut world, source_a, target).unwrap();
+    Relation::<Targets>::relate(&mut world, source_b, target).unwrap();
+    (|query: Query<&RelatedFrom<Targets>>| {
+        assert_eq!(
+            RelatedFrom::sources_of(&query, target),
+            &[source_a, source_b]
+        );
+        assert!(RelatedFrom::sources_of(&query, source_a).is_empty());
+    })
+    .run(&world);
+
+    // Despawning a source removes it from the target.
+    world.despawn(source_a).unwrap();
+    assert_eq!(
+        world
+            .get_component_mut::<RelatedFrom<Targets>>(target)
+            .unwrap()
+            .sources(),
+        &[source_b]
+    );
+
+    Relation::<Targets>::unrelate(&mut world, source_b).unwrap();
+    assert!(world
+        .get_component_mut::<RelatedFrom<Targets>>(target)
+        .is_err());
+}
+
+#[test]
+fn relationship_despawn_policies() {
+    use relationship::*;
+
+    struct OwnedBy;
+    impl RelationshipTrait for OwnedBy {
+        const DESPAWN_POLICY: DespawnPolicy = DespawnPolicy::Cascade;
+    }
+    struct AttachedTo;
+    impl RelationshipTrait for AttachedTo {
+        const DESPAWN_POLICY: DespawnPolicy = DespawnPolicy::Orphan;
+    }
+    struct Targets;
+    impl RelationshipTrait for Targets {}
+
+    let mut world = World::new();
+    let owner = world.spawn(A);
+    let owned = world.spawn(B);
+    let owned_owned = world.spawn(B);
+    let attached = world.spawn(C);
+    let targeting = world.spawn(C);
+    Relation::<OwnedBy>::relate(&mut world, owned, owner).unwrap();
+    Relation::<OwnedBy>::relate(&mut world, owned_owned, owned).unwrap();
+    Relation::<AttachedTo>::relate(&mut world, attached, owner).unwrap();
+    Relation::<Targets>::relate(&mut world, targeting, owner).unwrap();
+
+    world.despawn(owner).unwrap();
+    assert!(world.get_component_mut::<B>(owned).is_err());
+    assert!(world.get_component_mut::<B>(owned_owned).is_err());
+    assert_eq!(
+        world
+            .get_component_mut::<Relation<AttachedTo>>(attached)
+            .unwrap()
+            .target(),
+        None
+    );
+    assert!(world
+        .get_component_mut::<Relation<Targets>>(targeting)
+        .is_err());
+    assert_eq!(world.len(), 2);
+
+    // Cyclic cascading relationships despawn both [Entity]s.
+    let a = world.spawn(A);
+    let b = world.spawn(A);
+    Relation::<OwnedBy>::relate(&mut world, a, b).unwrap();
+    Relation::<OwnedBy>::relate(&mut world, b, a).unwrap();
+    world.despawn(a).unwrap();
+    assert!(world.get_component_mut::<A>(b).is_err());
+    assert_eq!(world.len(), 2);
+}
+
 /*
 #[test]
 fn componentless_query() {
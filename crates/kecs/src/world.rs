@@ -2,7 +2,10 @@ use crate::storage_lookup::*;
 use crate::*;
 use std::{
     any::Any,
-    sync::{RwLock, RwLockWriteGuard},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock, RwLockWriteGuard,
+    },
 };
 
 pub(crate) trait ComponentChannelVecTrait: Send + Sync {
@@ -18,7 +21,6 @@ pub(crate) trait ComponentChannelVecTrait: Send + Sync {
         &mut self,
         entity_migrator: &mut EntityMigrator,
     ) -> Option<Box<dyn ComponentChannelVecTrait>>;
-    fn len(&mut self) -> usize;
 }
 
 impl<T: ComponentTrait> ComponentChannelVecTrait for RwLock<Vec<T>> {
@@ -83,14 +85,15 @@ impl<T: ComponentTrait> ComponentChannelVecTrait for RwLock<Vec<T>> {
             self.get_mut().unwrap(),
         )?)))
     }
-    fn len(&mut self) -> usize {
-        self.get_mut().unwrap().len()
-    }
 }
 
 pub(crate) struct ArchetypeChannel {
     pub(crate) component_id: ComponentId,
-    pub(crate) data: Box<dyn ComponentChannelVecTrait>,
+    data: Box<dyn ComponentChannelVecTrait>,
+    /// The [World::change_tick] each component was last added or mutably borrowed at.
+    /// These are atomic so that [Query]s holding a write lock on `data` can update them.
+    pub(crate) change_ticks: Vec<AtomicU64>,
+    pub(crate) on_despawn: Option<fn(&mut World, Entity)>,
 }
 
 impl ArchetypeChannel {
@@ -99,6 +102,8 @@ impl ArchetypeChannel {
             component_id: ComponentId(TypeId::of::<Component>()),
             data: Box::new(RwLock::new(Vec::<Component>::with_capacity(1)))
                 as Box<dyn ComponentChannelVecTrait>,
+            change_ticks: Vec::with_capacity(1),
+            on_despawn: Component::ON_DESPAWN,
         }
     }
 
@@ -106,16 +111,65 @@ impl ArchetypeChannel {
         Self {
             component_id: self.component_id,
             data: self.data.new_same_type(),
+            change_ticks: Vec::new(),
+            on_despawn: self.on_despawn,
         }
     }
 
-    pub(crate) fn clone_channel(&mut self, entity_migrator: &mut EntityMigrator) -> Option<Self> {
+    /// Cloned components count as changed at `change_tick`.
+    pub(crate) fn clone_channel(
+        &mut self,
+        entity_migrator: &mut EntityMigrator,
+        change_tick: u64,
+    ) -> Option<Self> {
+        let data = self.data.clone_channel(entity_migrator)?;
         Some(Self {
             component_id: self.component_id,
-            data: self.data.clone_channel(entity_migrator)?,
+            data,
+            change_ticks: (0..self.change_ticks.len())
+                .map(|_| AtomicU64::new(change_tick))
+                .collect(),
+            on_despawn: self.on_despawn,
         })
     }
 
+    pub(crate) fn push(&mut self, component: &mut dyn AnyComponentTrait, change_tick: u64) {
+        self.data.push(component);
+        self.change_ticks.push(AtomicU64::new(change_tick));
+    }
+
+    pub(crate) fn assign(
+        &mut self,
+        index: usize,
+        component: &mut dyn AnyComponentTrait,
+        change_tick: u64,
+    ) {
+        self.data.assign(index, component);
+        *self.change_ticks[index].get_mut() = change_tick;
+    }
+
+    pub(crate) fn swap_remove(&mut self, index: usize) {
+        self.data.swap_remove(index);
+        self.change_ticks.swap_remove(index);
+    }
+
+    pub(crate) fn migrate_component(&mut self, index: usize, other: &mut ArchetypeChannel) {
+        self.data.migrate_component(index, &mut *other.data);
+        other
+            .change_ticks
+            .push(self.change_ticks.swap_remove(index));
+    }
+
+    pub(crate) fn append_channel(&mut self, other: &mut ArchetypeChannel) {
+        self.data.append_channel(&mut *other.data);
+        self.change_ticks.append(&mut other.change_ticks);
+    }
+
+    /// Marks a component as changed.
+    pub(crate) fn set_changed(&self, index: usize, change_tick: u64) {
+        self.change_ticks[index].store(change_tick, Ordering::Relaxed);
+    }
+
     pub(crate) fn as_mut_vec<T: 'static>(&mut self) -> &mut Vec<T> {
         self.data
             .as_any_mut()
@@ -143,6 +197,27 @@ impl Archetype {
         }
     }
 
+    /// Returns true if the [Archetype] stores the component.
+    pub fn has_component<T: ComponentTrait>(&self) -> bool {
+        self.has_component_id(get_component_id::<T>())
+    }
+
+    pub(crate) fn has_component_id(&self, component_id: ComponentId) -> bool {
+        // Channels are sorted by [ComponentId]
+        self.channels
+            .binary_search_by_key(&component_id, |channel| channel.component_id)
+            .is_ok()
+    }
+
+    /// The number of [Entity]s in this [Archetype].
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
     pub(crate) fn get_read_channel<T: 'static>(
         &self,
         channel_index: usize,
@@ -168,11 +243,17 @@ impl Archetype {
             .try_write()
             .map_err(|_| KecsError::ChannelExclusivelyLocked)
     }
+
+    /// The change ticks of a channel's components, see [ArchetypeChannel::change_ticks].
+    pub(crate) fn get_change_ticks(&self, channel_index: usize) -> &[AtomicU64] {
+        &self.channels[channel_index].change_ticks
+    }
 }
 
 pub struct EntityRef<'a> {
     archetype: &'a mut Archetype,
     index_within_archetype: usize,
+    change_tick: u64,
 }
 
 impl<'a> EntityRef<'a> {
@@ -180,6 +261,7 @@ impl<'a> EntityRef<'a> {
         let component_id = get_component_id::<Component>();
         for channel in &mut self.archetype.channels {
             if channel.component_id == component_id {
+                channel.set_changed(self.index_within_archetype, self.change_tick);
                 let component = &mut channel.as_mut_vec()[self.index_within_archetype];
                 return Ok(component);
             }
@@ -194,6 +276,22 @@ pub struct World {
     pub(crate) components_ids_to_archetype_index: HashMap<Vec<ComponentId>, usize>,
     pub(crate) storage_lookup: StorageLookup,
     pub(crate) entities: Entities,
+    /// Components that use [ComponentStorage::Sparse] are stored here instead of in [Archetype]s.
+    pub(crate) sparse_storages: HashMap<ComponentId, RwLock<Box<dyn SparseStorageTrait>>>,
+    /// Incremented each time a system runs or components are changed outside of a system.
+    /// Used to find the components matched by [Changed] filters.
+    pub(crate) change_tick: AtomicU64,
+}
+
+/// The ticks a system uses to mark and detect changed components.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChangeTicks {
+    /// The [World]'s change tick the last time the system ran.
+    /// Components changed after this tick pass [Changed] filters.
+    /// This is 0 for systems that haven't run before, so every component counts as changed.
+    pub last_run: u64,
+    /// Components mutably borrowed by the system are marked as changed at this tick.
+    pub this_run: u64,
 }
 
 struct RemoveInfo {
@@ -215,6 +313,8 @@ impl World {
             components_ids_to_archetype_index: HashMap::new(),
             storage_lookup: StorageLookup::new(),
             entities: Entities::new(),
+            sparse_storages: HashMap::new(),
+            change_tick: AtomicU64::new(0),
         };
 
         // Insert the empty [Archetype]
@@ -235,6 +335,20 @@ impl World {
         self.entities.len()
     }
 
+    /// Advances the [World]'s change tick and returns the new tick.
+    pub(crate) fn next_change_tick(&self) -> u64 {
+        self.change_tick.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Returns the [ChangeTicks] for a system that last ran at `last_run`.
+    /// [Query]s fetched without a system use a `last_run` of 0.
+    pub(crate) fn change_ticks(&self, last_run: u64) -> ChangeTicks {
+        ChangeTicks {
+            last_run,
+            this_run: self.next_change_tick(),
+        }
+    }
+
     pub(crate) fn spawn_reserved_entities(&mut self) {
         let empty_archetype = &mut self.archetypes[0];
         while let Some(entity) = self
@@ -268,14 +382,41 @@ impl World {
     pub fn despawn(&mut self, entity: Entity) -> Result<(), KecsError> {
         self.spawn_reserved_entities();
 
+        // Run despawn hooks first because they may need to read the [Entity]'s components.
+        let entity_location = self
+            .entities
+            .get_entity_location(entity)
+            .ok_or(KecsError::EntityMissing)?;
+        let on_despawn_hooks: Vec<_> = self.archetypes[entity_location.archetype_index]
+            .channels
+            .iter()
+            .filter_map(|channel| channel.on_despawn)
+            .chain(self.sparse_storages.values_mut().filter_map(|storage| {
+                let storage = storage.get_mut().unwrap();
+                storage.on_despawn().filter(|_| storage.contains(entity))
+            }))
+            .collect();
+        if !on_despawn_hooks.is_empty() {
+            for on_despawn in on_despawn_hooks {
+                on_despawn(self, entity);
+            }
+            // A hook may have despawned this [Entity] already, for example with cyclic relationships.
+            if self.entities.get_entity_location(entity).is_none() {
+                return Ok(());
+            }
+        }
+
+        // Hooks may have moved the [Entity] so its location is looked up again.
         let entity_location = self.entities.free(entity)?;
 
+        for storage in self.sparse_storages.values_mut() {
+            storage.get_mut().unwrap().remove_entity(entity);
+        }
+
         // Remove the [Entity]'s components from the [Archetype]
         let archetype = &mut self.archetypes[entity_location.archetype_index];
         for channel in &mut archetype.channels {
-            channel
-                .data
-                .swap_remove(entity_location.index_within_archetype);
+            channel.swap_remove(entity_location.index_within_archetype);
         }
         archetype
             .entities
@@ -335,6 +476,17 @@ impl World {
         &mut self,
         entity: Entity,
     ) -> Result<Component, KecsError> {
+        if Component::STORAGE == ComponentStorage::Sparse {
+            self.spawn_reserved_entities();
+            self.entities
+                .get_entity_location(entity)
+                .ok_or(KecsError::EntityMissing)?;
+            return self
+                .get_sparse_storage_mut::<Component>()
+                .and_then(|storage| storage.remove(entity))
+                .ok_or_else(KecsError::no_matching_component::<Component>);
+        }
+
         let removing_component_id = get_component_id::<Component>();
         let RemoveInfo {
             archetype_index,
@@ -347,9 +499,9 @@ impl World {
         )?;
 
         // Is this swap-removing the wrong entity?
-        let removed_component = self.archetypes[archetype_index].channels[archetype_channel]
-            .as_mut_vec()
-            .swap_remove(entity_index_in_archetype);
+        let channel = &mut self.archetypes[archetype_index].channels[archetype_channel];
+        channel.change_ticks.swap_remove(entity_index_in_archetype);
+        let removed_component = channel.as_mut_vec().swap_remove(entity_index_in_archetype);
         Ok(removed_component)
     }
 
@@ -462,10 +614,8 @@ impl World {
                     .cmp(&destination_channel.component_id)
                 {
                     std::cmp::Ordering::Equal => {
-                        source_channel.data.migrate_component(
-                            index_within_archetype,
-                            &mut *destination_channel.data,
-                        );
+                        source_channel
+                            .migrate_component(index_within_archetype, destination_channel);
                         source_channel_index += 1;
                         destination_channel_index += 1;
                     }
@@ -511,11 +661,21 @@ impl World {
             .get_entity_location(entity)
             .ok_or(KecsError::EntityMissing)?;
 
+        let change_tick = self.next_change_tick();
+
+        if Component::STORAGE == ComponentStorage::Sparse {
+            return self
+                .get_sparse_storage_mut::<Component>()
+                .and_then(|storage| storage.get_mut(entity, change_tick))
+                .ok_or_else(KecsError::no_matching_component::<Component>);
+        }
+
         let component_id = get_component_id::<Component>();
 
         let archetype = &mut self.archetypes[entity_location.archetype_index as usize];
         for channel in &mut archetype.channels {
             if channel.component_id == component_id {
+                channel.set_changed(entity_location.index_within_archetype, change_tick);
                 let component = &mut channel.as_mut_vec()[entity_location.index_within_archetype];
                 return Ok(component);
             }
@@ -528,9 +688,11 @@ impl World {
             .entities
             .get_entity_location(entity)
             .ok_or(KecsError::EntityMissing)?;
+        let change_tick = self.next_change_tick();
         Ok(EntityRef {
             archetype: &mut self.archetypes[entity_location.archetype_index as usize],
             index_within_archetype: entity_location.index_within_archetype,
+            change_tick,
         })
     }
 
@@ -559,11 +721,14 @@ impl World {
             .matching_archetype_iterator::<1>(&filters)
             .next()
             .ok_or_else(KecsError::no_matching_component::<Component>)?;
-        self.archetypes[matching_archetype.archetype_index].channels
-            [matching_archetype.channels[0].unwrap()]
-        .as_mut_vec()
-        .get_mut(0)
-        .ok_or_else(KecsError::no_matching_component::<Component>)
+        let change_tick = self.next_change_tick();
+        let channel = &mut self.archetypes[matching_archetype.archetype_index].channels
+            [matching_archetype.channels[0].unwrap()];
+        if channel.change_ticks.is_empty() {
+            return Err(KecsError::no_matching_component::<Component>());
+        }
+        channel.set_changed(0, change_tick);
+        Ok(&mut channel.as_mut_vec()[0])
     }
 
     pub fn remove_singleton<Component: ComponentTrait>(&mut self) -> Result<Component, KecsError> {
@@ -607,10 +772,13 @@ impl World {
     /// An internal helper used by [clone_world] and [add_world]
     fn clone_world_into_world(source: &mut World, destination: &mut World) -> EntityMigrator {
         destination.spawn_reserved_entities();
+        // Components added to the destination count as changed.
+        let change_tick = destination.next_change_tick();
 
         let World {
             archetypes: old_archetypes,
             entities: old_entities,
+            sparse_storages: old_sparse_storages,
             ..
         } = source;
 
@@ -619,7 +787,7 @@ impl World {
             .entities
             .reserve_space_for_entity_cloning(old_entities);
 
-        let entity_migrator = {
+        let mut entity_migrator = {
             let World {
                 archetypes: new_archetypes,
                 components_ids_to_archetype_index: new_components_ids_to_archetype_index,
@@ -634,7 +802,8 @@ impl World {
             for old_archetype in old_archetypes {
                 let mut new_channels = Vec::new();
                 for channel in &mut old_archetype.channels {
-                    if let Some(channel) = channel.clone_channel(&mut entity_migrator) {
+                    if let Some(channel) = channel.clone_channel(&mut entity_migrator, change_tick)
+                    {
                         new_channels.push(channel);
                     }
                 }
@@ -663,9 +832,7 @@ impl World {
                             .iter_mut()
                             .zip(new_channels.iter_mut())
                         {
-                            desination_channel
-                                .data
-                                .append_channel(&mut *source_channel.data)
+                            desination_channel.append_channel(source_channel)
                         }
 
                         // Append entities to this [Archetype] and update the [Entity] location
@@ -706,6 +873,26 @@ impl World {
             }
             entity_migrator
         };
+
+        for (component_id, old_storage) in old_sparse_storages {
+            if let Some(mut new_storage) = old_storage
+                .get_mut()
+                .unwrap()
+                .clone_storage(&mut entity_migrator, change_tick)
+            {
+                match destination.sparse_storages.entry(*component_id) {
+                    std::collections::hash_map::Entry::Occupied(mut entry) => entry
+                        .get_mut()
+                        .get_mut()
+                        .unwrap()
+                        .append_storage(&mut *new_storage),
+                    std::collections::hash_map::Entry::Vacant(entry) => {
+                        entry.insert(RwLock::new(new_storage));
+                    }
+                }
+            }
+        }
+
         destination
             .entities
             .truncate_free_entities_after_cloning(old_entities);
@@ -720,7 +907,7 @@ impl World {
         Query<'a, PARAMS>: SystemParameterTrait,
     {
         let meta_data = <Query<PARAMS> as SystemParameterTrait>::get_meta_data(self)?;
-        <Query<PARAMS> as SystemParameterFetchTrait>::fetch(self, &meta_data)
+        <Query<PARAMS> as SystemParameterFetchTrait>::fetch(self, &meta_data, self.change_ticks(0))
     }
     /// Get a [Query] from the [World] without running a system
     pub fn query<'a, PARAMS: QueryParametersTrait>(
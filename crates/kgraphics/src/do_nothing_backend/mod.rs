@@ -1,21 +1,36 @@
+//! A backend that accepts every call and draws nothing.
+//! Used to run `koi` without a window or GPU, like in tests.
+#![allow(unused)]
+
 use crate::*;
 use raw_window_handle::HasRawWindowHandle;
 
 pub struct GraphicsContext;
 
-#[derive(Clone)]
 pub struct RenderTarget;
-#[derive(Clone)]
+
+impl RenderTargetTrait for RenderTarget {
+    fn pixel_format(&self) -> PixelFormat {
+        PixelFormat::RGBA8Unorm
+    }
+
+    fn current_frame(&self) -> Texture {
+        Texture
+    }
+}
+
 pub struct FragmentFunction;
-#[derive(Clone)]
 pub struct VertexFunction;
+
 #[derive(Clone)]
 pub struct DataBuffer<T> {
     phantom: std::marker::PhantomData<T>,
 }
+
 #[derive(Clone)]
 pub struct IndexBuffer;
-#[derive(Clone)]
+
+#[derive(Debug, Clone)]
 pub struct Texture;
 
 impl Texture {
@@ -24,45 +39,69 @@ impl Texture {
     }
 }
 
-#[derive(Clone)]
 pub struct CommandBuffer;
-#[derive(Clone)]
+
 pub struct RenderPass<'a> {
-    phantom: std::marker::PhantomData<&'a ()>,
+    phantom: std::marker::PhantomData<&'a mut CommandBuffer>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct IntProperty;
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct FloatProperty;
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Vec2Property;
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Vec3Property;
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Vec4Property;
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Mat4Property;
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct TextureProperty;
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct CubeMapProperty;
 
+macro_rules! properties_exist {
+    ($($property: ident),*) => {
+        $(
+            impl $property {
+                pub fn exists(&self) -> bool {
+                    true
+                }
+            }
+        )*
+    };
+}
+
+properties_exist!(
+    IntProperty,
+    FloatProperty,
+    Vec2Property,
+    Vec3Property,
+    Vec4Property,
+    Mat4Property,
+    TextureProperty,
+    CubeMapProperty
+);
+
 #[derive(Clone)]
-pub struct Pipeline;
+pub struct Pipeline {
+    blending: Option<(BlendFactor, BlendFactor)>,
+}
 
 impl Pipeline {
     pub fn blending(&self) -> Option<(BlendFactor, BlendFactor)> {
-        None
+        self.blending
     }
 }
 
-// Presently this isn't dropped appropriately.
 #[derive(Debug, Clone)]
 pub struct CubeMap;
 
 impl CubeMap {
     pub fn get_face_texture(&self, face: usize) -> Texture {
+        assert!(face < 6);
         Texture
     }
 }
@@ -70,70 +109,108 @@ impl CubeMap {
 #[derive(Clone, PartialEq, Debug, Copy, Default)]
 pub struct Framebuffer;
 
+#[derive(Clone)]
+pub struct UniformBlock<T> {
+    phantom: std::marker::PhantomData<T>,
+}
+
+impl<T> UniformBlock<T> {
+    pub const fn from_location(location: u32) -> Self {
+        Self {
+            phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct VertexAttribute<T> {
     phantom: std::marker::PhantomData<T>,
 }
 
+impl<T> VertexAttribute<T> {
+    pub fn exists(&self) -> bool {
+        true
+    }
+}
+
 impl PipelineTrait for Pipeline {
-    fn get_int_property(&self, name: &str) -> Result<IntProperty, ()> {
+    fn get_int_property(&self, name: &str) -> Result<IntProperty, PropertyError> {
         Ok(IntProperty)
     }
-    fn get_float_property(&self, name: &str) -> Result<FloatProperty, ()> {
+    fn get_float_property(&self, name: &str) -> Result<FloatProperty, PropertyError> {
         Ok(FloatProperty)
     }
-    fn get_vec2_property(&self, name: &str) -> Result<Vec2Property, ()> {
+    fn get_vec2_property(&self, name: &str) -> Result<Vec2Property, PropertyError> {
         Ok(Vec2Property)
     }
-    fn get_vec3_property(&self, name: &str) -> Result<Vec3Property, ()> {
+    fn get_vec3_property(&self, name: &str) -> Result<Vec3Property, PropertyError> {
         Ok(Vec3Property)
     }
-    fn get_vec4_property(&self, name: &str) -> Result<Vec4Property, ()> {
+    fn get_vec4_property(&self, name: &str) -> Result<Vec4Property, PropertyError> {
         Ok(Vec4Property)
     }
-    fn get_mat4_property(&self, name: &str) -> Result<Mat4Property, ()> {
+    fn get_mat4_property(&self, name: &str) -> Result<Mat4Property, PropertyError> {
         Ok(Mat4Property)
     }
-    fn get_texture_property(&self, name: &str) -> Result<TextureProperty, ()> {
+    fn get_texture_property(&self, name: &str) -> Result<TextureProperty, PropertyError> {
         Ok(TextureProperty)
     }
-    fn get_cube_map_property(&self, name: &str) -> Result<CubeMapProperty, ()> {
+    fn get_cube_map_property(&self, name: &str) -> Result<CubeMapProperty, PropertyError> {
         Ok(CubeMapProperty)
     }
+    fn get_uniform_block<T>(&self, name: &str) -> Result<UniformBlock<T>, String> {
+        Ok(UniformBlock {
+            phantom: std::marker::PhantomData,
+        })
+    }
     fn get_vertex_attribute<T>(&self, name: &str) -> Result<VertexAttribute<T>, String> {
-        Ok(VertexAttribute::<T> {
+        Ok(VertexAttribute {
             phantom: std::marker::PhantomData,
         })
     }
 }
 
+impl<'a> PipelineBuilderTrait for PipelineBuilder<'a> {
+    fn build(self) -> Result<Pipeline, String> {
+        Ok(Pipeline {
+            blending: self.blending,
+        })
+    }
+}
+
 impl RenderPassTrait for RenderPass<'_> {
     fn set_pipeline(&mut self, pipeline: &Pipeline) {}
     fn set_vertex_attribute<T>(
+        &mut self,
+        attribute: &VertexAttribute<T>,
+        buffer: Option<&DataBuffer<T>>,
+    ) {
+    }
+    fn set_instance_attribute<T>(
         &mut self,
         vertex_attribute: &VertexAttribute<T>,
         buffer: Option<&DataBuffer<T>>,
     ) {
     }
-
     fn set_vertex_attribute_to_constant<T>(
         &mut self,
         vertex_attribute: &VertexAttribute<T>,
         value: &[f32],
     ) {
     }
-
+    fn set_uniform_block<T>(
+        &mut self,
+        uniform_block: &UniformBlock<T>,
+        buffer: Option<&DataBuffer<T>>,
+    ) {
+    }
     fn set_float_property(&mut self, property: &FloatProperty, value: f32) {}
-
     fn set_int_property(&mut self, property: &IntProperty, value: i32) {}
-
     fn set_vec2_property(&mut self, property: &Vec2Property, value: (f32, f32)) {}
-
     fn set_vec3_property(&mut self, property: &Vec3Property, value: (f32, f32, f32)) {}
     fn set_vec4_property(&mut self, property: &Vec4Property, value: (f32, f32, f32, f32)) {}
     fn set_mat4_property(&mut self, property: &Mat4Property, value: &[f32; 16]) {}
-
     fn set_viewport(&mut self, x: u32, y: u32, width: u32, height: u32) {}
-
     fn set_texture_property(
         &mut self,
         property: &TextureProperty,
@@ -141,7 +218,6 @@ impl RenderPassTrait for RenderPass<'_> {
         texture_unit: u8,
     ) {
     }
-
     fn set_cube_map_property(
         &mut self,
         property: &CubeMapProperty,
@@ -149,8 +225,17 @@ impl RenderPassTrait for RenderPass<'_> {
         texture_unit: u8,
     ) {
     }
-
+    fn draw_triangles(&mut self, vertex_count: u32, index_buffer: &IndexBuffer) {}
+    fn draw_triangles_without_buffer(&mut self, vertex_count: u32) {}
+    fn draw_triangles_instanced(
+        &mut self,
+        vertex_count: u32,
+        index_buffer: &IndexBuffer,
+        instances: u32,
+    ) {
+    }
     fn set_depth_mask(&mut self, depth_mask: bool) {}
+    fn set_stencil_reference(&mut self, reference: u8) {}
     fn blit_framebuffer(
         self,
         target: &Framebuffer,
@@ -164,13 +249,9 @@ impl RenderPassTrait for RenderPass<'_> {
         dest_height: u32,
     ) {
     }
-
-    fn draw_triangles(&mut self, count: u32, buffer: &IndexBuffer) {}
-    fn draw_triangles_without_buffer(&mut self, count: u32) {}
 }
 
 impl CommandBufferTrait for CommandBuffer {
-    /// Gets the number of actions encoded in the `CommandBuffer`
     fn len(&self) -> usize {
         0
     }
@@ -189,8 +270,6 @@ impl CommandBufferTrait for CommandBuffer {
         }
     }
 
-    /// If the color_texture binds to the DefaultFramebuffer then
-    /// all textures will bind to the default framebuffer.
     fn begin_render_pass<'a>(
         &'a mut self,
         color_texture: Option<&Texture>,
@@ -202,8 +281,10 @@ impl CommandBufferTrait for CommandBuffer {
             phantom: std::marker::PhantomData,
         }
     }
+
     fn present(&mut self) {}
 }
+
 impl GraphicsContextTrait for GraphicsContext {
     fn new() -> Self {
         Self
@@ -212,7 +293,6 @@ impl GraphicsContextTrait for GraphicsContext {
         Self
     }
 
-    /// This must only be called once per window.
     fn get_render_target_for_window(
         &mut self,
         window: &impl HasRawWindowHandle,
@@ -222,6 +302,16 @@ impl GraphicsContextTrait for GraphicsContext {
         RenderTarget
     }
 
+    #[cfg(feature = "SDL")]
+    unsafe fn get_render_target_for_window_sdl(
+        &mut self,
+        window: kapp::WindowId,
+        _width: u32,
+        _height: u32,
+    ) -> Result<RenderTarget, ()> {
+        Ok(RenderTarget)
+    }
+
     fn resize(&mut self, window: &impl HasRawWindowHandle, width: u32, height: u32) {}
     fn new_fragment_function(&mut self, source: &str) -> Result<FragmentFunction, String> {
         Ok(FragmentFunction)
@@ -230,14 +320,15 @@ impl GraphicsContextTrait for GraphicsContext {
         Ok(VertexFunction)
     }
 
-    fn new_data_buffer<T>(&mut self, data: &[T]) -> Result<DataBuffer<T>, ()> {
+    fn new_data_buffer<T>(&mut self, data: &[T]) -> Result<DataBuffer<T>, GraphicsError> {
         Ok(DataBuffer {
             phantom: std::marker::PhantomData,
         })
     }
+    fn update_data_buffer<T>(&mut self, data_buffer: &mut DataBuffer<T>, data: &[T]) {}
     fn delete_data_buffer<T>(&mut self, data_buffer: DataBuffer<T>) {}
 
-    fn new_index_buffer(&mut self, data: &[u32]) -> Result<IndexBuffer, ()> {
+    fn new_index_buffer(&mut self, data: &[u32]) -> Result<IndexBuffer, GraphicsError> {
         Ok(IndexBuffer)
     }
     fn delete_index_buffer(&mut self, index_buffer: IndexBuffer) {}
@@ -246,18 +337,23 @@ impl GraphicsContextTrait for GraphicsContext {
         &mut self,
         width: u32,
         height: u32,
+        depth: u32,
         data: Option<&[u8]>,
         pixel_format: PixelFormat,
         texture_settings: TextureSettings,
-    ) -> Result<Texture, ()> {
+    ) -> Result<Texture, GraphicsError> {
         Ok(Texture)
     }
 
     fn update_texture(
         &mut self,
         texture: &Texture,
+        x: u32,
+        y: u32,
+        z: u32,
         width: u32,
         height: u32,
+        depth: u32,
         data: Option<&[u8]>,
         pixel_format_in: PixelFormat,
         texture_settings: TextureSettings,
@@ -266,19 +362,18 @@ impl GraphicsContextTrait for GraphicsContext {
 
     fn delete_texture(&mut self, texture: Texture) {}
 
-    fn new_pipeline(
+    fn read_texture(
         &mut self,
-        vertex_function: VertexFunction,
-        fragment_function: FragmentFunction,
-        output_pixel_format: PixelFormat,
-    ) -> PipelineBuilder {
-        PipelineBuilder::new(self)
-    }
-
-    fn new_command_buffer(&mut self) -> CommandBuffer {
-        CommandBuffer
+        texture: &Texture,
+        format: PixelFormat,
+        width: u32,
+        height: u32,
+    ) -> Vec<u8> {
+        let bytes_per_pixel = format
+            .bytes_per_pixel()
+            .expect("Compressed textures cannot be read");
+        vec![0; width as usize * height as usize * bytes_per_pixel]
     }
-    fn commit_command_buffer(&mut self, command_buffer: CommandBuffer) {}
 
     fn generate_mip_map_for_texture(&mut self, texture: &Texture) {}
 
@@ -289,7 +384,7 @@ impl GraphicsContextTrait for GraphicsContext {
         data: Option<[&[u8]; 6]>,
         pixel_format: PixelFormat,
         texture_settings: TextureSettings,
-    ) -> Result<CubeMap, ()> {
+    ) -> Result<CubeMap, GraphicsError> {
         Ok(CubeMap)
     }
 
@@ -308,6 +403,20 @@ impl GraphicsContextTrait for GraphicsContext {
 
     fn generate_mip_map_for_cube_map(&mut self, cube_map: &CubeMap) {}
 
+    fn new_pipeline(
+        &mut self,
+        vertex_function: VertexFunction,
+        fragment_function: FragmentFunction,
+        output_pixel_format: PixelFormat,
+    ) -> PipelineBuilder {
+        PipelineBuilder::new(self)
+    }
+
+    fn new_command_buffer(&mut self) -> CommandBuffer {
+        CommandBuffer
+    }
+    fn commit_command_buffer(&mut self, command_buffer: CommandBuffer) {}
+
     fn new_framebuffer(
         &mut self,
         color_texture: Option<&Texture>,
@@ -318,10 +427,9 @@ impl GraphicsContextTrait for GraphicsContext {
     }
 
     fn delete_framebuffer(&mut self, framebuffer: Framebuffer) {}
-}
 
-impl<'a> PipelineBuilderTrait for PipelineBuilder<'a> {
-    fn build(self) -> Result<Pipeline, String> {
-        Ok(Pipeline)
+    /// Every format is accepted because nothing is ever sampled.
+    fn supports_pixel_format(&self, pixel_format: PixelFormat) -> bool {
+        true
     }
 }
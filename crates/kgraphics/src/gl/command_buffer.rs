@@ -39,6 +39,7 @@ pub(super) enum CommandBufferAction {
     DrawTriangleArrays(u32),
     DrawTrianglesInstanced(u32, u32),
     SetDepthMask(bool),
+    SetStencilReference(u8),
     BlitFramebuffer {
         target: Framebuffer,
         dest_x: u32,
@@ -413,6 +414,11 @@ impl<'a> RenderPassTrait for RenderPass<'a> {
             .actions
             .push(CommandBufferAction::SetDepthMask(depth_mask))
     }
+    fn set_stencil_reference(&mut self, reference: u8) {
+        self.command_buffer
+            .actions
+            .push(CommandBufferAction::SetStencilReference(reference))
+    }
     fn blit_framebuffer(
         self,
         target: &Framebuffer,
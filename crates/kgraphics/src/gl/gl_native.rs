@@ -329,6 +329,48 @@ impl GL {
         );
     }
 
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn compressed_tex_image_2d(
+        &self,
+        target: GLenum,
+        level: i32,
+        internal_format: GLenum,
+        width: i32,
+        height: i32,
+        data: &[u8],
+    ) {
+        self.gl.CompressedTexImage2D(
+            target,
+            level,
+            internal_format,
+            width,
+            height,
+            0, /* border: must be 0 */
+            data.len() as i32,
+            data.as_ptr() as *const std::ffi::c_void,
+        );
+    }
+
+    /// Lists the extensions supported by this context.
+    pub unsafe fn get_extensions(&self) -> Vec<String> {
+        let mut count = 0;
+        self.gl.GetIntegerv(GL_NUM_EXTENSIONS, &mut count);
+        (0..count as u32)
+            .filter_map(|i| {
+                let name = self.gl.GetStringi(GL_EXTENSIONS, i);
+                if name.is_null() {
+                    None
+                } else {
+                    Some(
+                        std::ffi::CStr::from_ptr(name as *const std::os::raw::c_char)
+                            .to_string_lossy()
+                            .into_owned(),
+                    )
+                }
+            })
+            .collect()
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub unsafe fn tex_sub_image_2d(
         &self,
@@ -439,13 +481,22 @@ impl GL {
         self.gl.BindFramebuffer(target, framebuffer.0);
     }
 
-    pub unsafe fn read_pixels(&self, format: GLenum, type_: GLenum, size: usize) -> Vec<u8> {
-        let mut pixel_data: Vec<u8> = vec![0; size * size * 4];
+    pub unsafe fn read_pixels(
+        &self,
+        format: GLenum,
+        type_: GLenum,
+        width: u32,
+        height: u32,
+        bytes_per_pixel: usize,
+    ) -> Vec<u8> {
+        let mut pixel_data: Vec<u8> = vec![0; width as usize * height as usize * bytes_per_pixel];
+        // Rows are tightly packed.
+        self.gl.PixelStorei(GL_PACK_ALIGNMENT, 1);
         self.gl.ReadPixels(
             0,
             0,
-            size as i32,
-            size as i32,
+            width as i32,
+            height as i32,
             format,
             type_,
             pixel_data.as_mut_ptr() as *mut std::ffi::c_void,
@@ -477,6 +528,20 @@ impl GL {
             GL_COLOR_BUFFER_BIT,
             GL_LINEAR,
         );
+        // Depth must be blitted with nearest filtering.
+        // This does nothing if either framebuffer lacks a depth attachment.
+        self.gl.BlitFramebuffer(
+            source_x as i32,
+            source_y as i32,
+            source_width as i32,
+            source_height as i32,
+            dest_x as i32,
+            dest_y as i32,
+            dest_width as i32,
+            dest_height as i32,
+            GL_DEPTH_BUFFER_BIT,
+            GL_NEAREST,
+        );
     }
 
     pub unsafe fn create_framebuffer(&self) -> Result<Framebuffer, String> {
@@ -537,6 +602,22 @@ impl GL {
         self.gl.BlendFunc(source, destination);
     }
 
+    pub unsafe fn stencil_func(&self, function: GLenum, reference: i32, mask: u32) {
+        self.gl.StencilFunc(function, reference, mask);
+    }
+
+    pub unsafe fn stencil_op(&self, stencil_fail: GLenum, depth_fail: GLenum, pass: GLenum) {
+        self.gl.StencilOp(stencil_fail, depth_fail, pass);
+    }
+
+    pub unsafe fn stencil_mask(&self, mask: u32) {
+        self.gl.StencilMask(mask);
+    }
+
+    pub unsafe fn clear_stencil(&self, value: i32) {
+        self.gl.ClearStencil(value);
+    }
+
     pub unsafe fn cull_face(&self, parameter: GLenum) {
         self.gl.CullFace(parameter);
     }
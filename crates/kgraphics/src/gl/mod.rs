@@ -7,6 +7,7 @@ mod gl_native;
 use gl_native::*;
 
 // Included for GLContext
+use crate::gl_shared::{compare_function_to_gl_enum, stencil_operation_to_gl_enum};
 use crate::{BlendFactor, DepthTest, FacesToRender, PixelFormat};
 use kapp::*;
 use raw_window_handle::*;
@@ -20,6 +21,8 @@ pub struct GraphicsContext {
     old_command_buffers: Vec<CommandBuffer>,
     gl_context: GLContext,
     gl: gl_native::GL,
+    /// Block compressed formats enabled by extensions on this context.
+    compressed_pixel_formats: Vec<PixelFormat>,
 }
 pub struct VertexFunction {
     shader: gl_native::Shader,
@@ -38,6 +41,7 @@ pub struct Pipeline {
     depth_test: DepthTest,
     faces_to_render: FacesToRender,
     blending: Option<(BlendFactor, BlendFactor)>,
+    stencil: Option<StencilState>,
     //depth_clear_value: f32,
 }
 
@@ -171,6 +175,12 @@ pub struct VertexAttribute<T> {
     phantom: std::marker::PhantomData<T>,
 }
 
+impl<T> VertexAttribute<T> {
+    pub fn exists(&self) -> bool {
+        self.info.is_some()
+    }
+}
+
 #[derive(Clone, PartialEq)]
 pub struct FloatProperty {
     location: Option<gl_native::UniformLocation>,
@@ -478,6 +488,7 @@ impl<'a> PipelineBuilderTrait for PipelineBuilder<'a> {
             depth_test: self.depth_test,
             faces_to_render: self.faces_to_render,
             blending: self.blending,
+            stencil: self.stencil,
             // depth_clear_value: self.depth_clear_value,
         })
     }
@@ -521,10 +532,13 @@ impl GraphicsContextTrait for GraphicsContext {
 
             gl.enable(GL_TEXTURE_CUBE_MAP_SEAMLESS);
 
+            let compressed_pixel_formats = compressed_pixel_formats(&gl.get_extensions());
+
             GraphicsContext {
                 gl_context,
                 gl,
                 old_command_buffers: Vec::new(),
+                compressed_pixel_formats,
             }
         }
     }
@@ -600,6 +614,20 @@ impl GraphicsContextTrait for GraphicsContext {
         }
     }
 
+    fn update_data_buffer<T>(&mut self, data_buffer: &mut DataBuffer<T>, data: &[T]) {
+        unsafe {
+            self.gl.bind_buffer(GL_ARRAY_BUFFER, Some(data_buffer.buffer));
+            // Respecifying the whole buffer lets the driver give it new storage
+            // instead of waiting for draws that use the previous contents.
+            self.gl.buffer_data_u8_slice(
+                GL_ARRAY_BUFFER.0,
+                slice_to_bytes(data),
+                GL_DYNAMIC_DRAW.0,
+            );
+        }
+        data_buffer.len = std::mem::size_of::<T>() * data.len();
+    }
+
     fn delete_data_buffer<T>(&mut self, data_buffer: DataBuffer<T>) {
         unsafe { self.gl.delete_buffer(data_buffer.buffer) }
     }
@@ -848,21 +876,42 @@ impl GraphicsContextTrait for GraphicsContext {
         }
     }
 
-    fn read_texture(&mut self, texture: &Texture, format: PixelFormat, size: usize) -> Vec<u8> {
-        let framebuffer = self.new_framebuffer(Some(texture), None, None);
+    fn read_texture(
+        &mut self,
+        texture: &Texture,
+        format: PixelFormat,
+        width: u32,
+        height: u32,
+    ) -> Vec<u8> {
+        // The default framebuffer is read directly.
+        let framebuffer = match texture.texture_type {
+            TextureType::DefaultFramebuffer => None,
+            _ => Some(self.new_framebuffer(Some(texture), None, None)),
+        };
         let (pixel_format, _inner_pixel_format, type_) =
             crate::gl_shared::pixel_format_to_gl_format_and_inner_format_and_type(format, false);
+        let bytes_per_pixel = format
+            .bytes_per_pixel()
+            .expect("Compressed textures cannot be read");
 
         let result = unsafe {
-            self.gl.bind_framebuffer(GL_FRAMEBUFFER, framebuffer);
             self.gl
-                .read_pixels(GLenum(pixel_format), GLenum(type_), size)
+                .bind_framebuffer(GL_FRAMEBUFFER, framebuffer.unwrap_or_default());
+            self.gl.read_pixels(
+                GLenum(pixel_format),
+                GLenum(type_),
+                width,
+                height,
+                bytes_per_pixel,
+            )
         };
         unsafe {
             self.gl
                 .bind_framebuffer(GL_FRAMEBUFFER, Framebuffer::default());
         }
-        self.delete_framebuffer(framebuffer);
+        if let Some(framebuffer) = framebuffer {
+            self.delete_framebuffer(framebuffer);
+        }
         result
     }
 
@@ -1002,6 +1051,8 @@ impl GraphicsContextTrait for GraphicsContext {
 
     fn commit_command_buffer(&mut self, mut command_buffer: CommandBuffer) {
         let mut current_program = None;
+        // Tracked so `SetStencilReference` can reuse the rest of the pipeline's stencil state.
+        let mut current_stencil: Option<StencilState> = None;
 
         unsafe {
             use CommandBufferAction::*;
@@ -1011,7 +1062,16 @@ impl GraphicsContextTrait for GraphicsContext {
                 match command {
                     Clear((r, g, b, a)) => {
                         self.gl.clear_color(r, g, b, a);
-                        self.gl.clear(GL_COLOR_BUFFER_BIT.0 | GL_DEPTH_BUFFER_BIT.0);
+
+                        // The stencil write mask also applies to clears.
+                        self.gl.stencil_mask(0xFF);
+                        self.gl.clear_stencil(0);
+                        self.gl.clear(
+                            GL_COLOR_BUFFER_BIT.0 | GL_DEPTH_BUFFER_BIT.0 | GL_STENCIL_BUFFER_BIT.0,
+                        );
+                        if let Some(stencil) = current_stencil {
+                            self.gl.stencil_mask(stencil.write_mask as u32);
+                        }
                     }
                     BindFramebuffer(framebuffer) => {
                         self.gl.bind_framebuffer(GL_FRAMEBUFFER, framebuffer);
@@ -1084,6 +1144,24 @@ impl GraphicsContextTrait for GraphicsContext {
                             self.gl.disable(GL_BLEND);
                         }
 
+                        if let Some(stencil) = pipeline.stencil {
+                            self.gl.enable(GL_STENCIL_TEST);
+                            self.gl.stencil_func(
+                                GLenum(compare_function_to_gl_enum(stencil.compare)),
+                                stencil.reference as i32,
+                                stencil.read_mask as u32,
+                            );
+                            self.gl.stencil_op(
+                                GLenum(stencil_operation_to_gl_enum(stencil.fail)),
+                                GLenum(stencil_operation_to_gl_enum(stencil.depth_fail)),
+                                GLenum(stencil_operation_to_gl_enum(stencil.pass)),
+                            );
+                            self.gl.stencil_mask(stencil.write_mask as u32);
+                        } else {
+                            self.gl.disable(GL_STENCIL_TEST);
+                        }
+                        current_stencil = pipeline.stencil;
+
                         self.gl.gl.ClearDepth(1.0);
                     }
                     SetUniformBlock((block, buffer, offset, len)) => {
@@ -1097,7 +1175,10 @@ impl GraphicsContextTrait for GraphicsContext {
                     }
                     SetVertexAttribute((attribute, buffer, per_instance)) => {
                         if buffer.is_none() {
-                            self.gl.disable_vertex_attrib_array(attribute.index);
+                            // Matrices span multiple attribute slots which all need to be disabled.
+                            for i in 0..(attribute.byte_size / 16).max(1) {
+                                self.gl.disable_vertex_attrib_array(attribute.index + i);
+                            }
                         } else {
                             self.gl.bind_buffer(GL_ARRAY_BUFFER, buffer);
 
@@ -1198,6 +1279,15 @@ impl GraphicsContextTrait for GraphicsContext {
                         self.gl.draw_arrays(GL_TRIANGLES, 0, (count * 3) as i32);
                     }
                     SetDepthMask(value) => self.gl.set_depth_mask(value),
+                    SetStencilReference(reference) => {
+                        if let Some(stencil) = current_stencil {
+                            self.gl.stencil_func(
+                                GLenum(compare_function_to_gl_enum(stencil.compare)),
+                                reference as i32,
+                                stencil.read_mask as u32,
+                            );
+                        }
+                    }
                     BlitFramebuffer {
                         target,
                         source_x,
@@ -1318,9 +1408,188 @@ impl GraphicsContextTrait for GraphicsContext {
             self.gl.delete_framebuffer(framebuffer);
         }
     }
+
+    fn supports_pixel_format(&self, pixel_format: PixelFormat) -> bool {
+        !pixel_format.is_compressed() || self.compressed_pixel_formats.contains(&pixel_format)
+    }
+
+    fn new_texture_with_mipmaps(
+        &mut self,
+        width: u32,
+        height: u32,
+        levels: &[&[u8]],
+        pixel_format: PixelFormat,
+        texture_settings: TextureSettings,
+    ) -> Result<Texture, GraphicsError> {
+        unsafe {
+            let texture = self.gl.create_texture().unwrap();
+            self.gl.bind_texture(GL_TEXTURE_2D, Some(texture));
+            for (level, data) in levels.iter().enumerate() {
+                self.tex_image_level(
+                    GL_TEXTURE_2D,
+                    level,
+                    width,
+                    height,
+                    data,
+                    pixel_format,
+                    texture_settings.srgb,
+                );
+            }
+            self.set_sampling_for_mip_levels(
+                GL_TEXTURE_2D,
+                levels.len(),
+                pixel_format,
+                texture_settings,
+            );
+            Ok(Texture {
+                texture_type: TextureType::Texture(texture),
+                mip: 0,
+                is_3d: false,
+            })
+        }
+    }
+
+    fn new_cube_map_with_mipmaps(
+        &mut self,
+        size: u32,
+        levels: &[[&[u8]; 6]],
+        pixel_format: PixelFormat,
+        texture_settings: TextureSettings,
+    ) -> Result<CubeMap, GraphicsError> {
+        unsafe {
+            let texture = self.gl.create_texture().unwrap();
+            self.gl.bind_texture(GL_TEXTURE_CUBE_MAP, Some(texture));
+            for (level, faces) in levels.iter().enumerate() {
+                for (i, data) in faces.iter().enumerate() {
+                    self.tex_image_level(
+                        GLenum(GL_TEXTURE_CUBE_MAP_POSITIVE_X.0 + i as u32),
+                        level,
+                        size,
+                        size,
+                        data,
+                        pixel_format,
+                        texture_settings.srgb,
+                    );
+                }
+            }
+            self.set_sampling_for_mip_levels(
+                GL_TEXTURE_CUBE_MAP,
+                levels.len(),
+                pixel_format,
+                texture_settings,
+            );
+            Ok(CubeMap { texture })
+        }
+    }
+}
+
+/// Finds which block compressed formats the context's extensions enable.
+fn compressed_pixel_formats(extensions: &[String]) -> Vec<PixelFormat> {
+    let has = |name: &str| extensions.iter().any(|e| e == name);
+    // RGTC (BC4 and BC5) is core in OpenGL 3.0
+    let mut formats = vec![PixelFormat::BC4RUnorm, PixelFormat::BC5RGUnorm];
+    if has("GL_EXT_texture_compression_s3tc") {
+        formats.extend([
+            PixelFormat::BC1RGBAUnorm,
+            PixelFormat::BC2RGBAUnorm,
+            PixelFormat::BC3RGBAUnorm,
+        ]);
+    }
+    if has("GL_ARB_texture_compression_bptc") {
+        formats.extend([PixelFormat::BC6HRGBUfloat, PixelFormat::BC7RGBAUnorm]);
+    }
+    if has("GL_ARB_ES3_compatibility") {
+        formats.extend([PixelFormat::ETC2RGB8Unorm, PixelFormat::ETC2RGBA8Unorm]);
+    }
+    formats
 }
 
 impl GraphicsContext {
+    /// Uploads a single mip level of a texture that's already bound.
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn tex_image_level(
+        &self,
+        target: GLenum,
+        level: usize,
+        width: u32,
+        height: u32,
+        data: &[u8],
+        pixel_format: PixelFormat,
+        srgb: bool,
+    ) {
+        let width = (width >> level).max(1) as i32;
+        let height = (height >> level).max(1) as i32;
+        if let Some(inner_pixel_format) =
+            crate::gl_shared::compressed_pixel_format_to_gl_inner_format(pixel_format, srgb)
+        {
+            self.gl.compressed_tex_image_2d(
+                target,
+                level as i32,
+                GLenum(inner_pixel_format),
+                width,
+                height,
+                data,
+            );
+        } else {
+            let (format, inner_pixel_format, type_) =
+                crate::gl_shared::pixel_format_to_gl_format_and_inner_format_and_type(
+                    pixel_format,
+                    srgb,
+                );
+            self.gl.tex_image_2d(
+                target,
+                level as i32,
+                inner_pixel_format as i32,
+                width,
+                height,
+                0, /* border: must be 0 */
+                GLenum(format),
+                GLenum(type_),
+                Some(data),
+            );
+        }
+    }
+
+    /// Sets filtering and wrapping for a bound texture with `mip_levels` uploaded levels.
+    unsafe fn set_sampling_for_mip_levels(
+        &self,
+        target: GLenum,
+        mip_levels: usize,
+        pixel_format: PixelFormat,
+        texture_settings: TextureSettings,
+    ) {
+        // Mipmaps can't be generated for compressed formats.
+        let generate_mipmaps =
+            texture_settings.generate_mipmaps && mip_levels == 1 && !pixel_format.is_compressed();
+
+        let minification_filter = minification_filter_to_gl_enum(
+            texture_settings.minification_filter,
+            texture_settings.mipmap_filter,
+            generate_mipmaps || mip_levels > 1,
+        );
+        let magnification_filter =
+            magnification_filter_to_gl_enum(texture_settings.magnification_filter);
+        self.gl
+            .tex_parameter_i32(target, GL_TEXTURE_MIN_FILTER, minification_filter as i32);
+        self.gl
+            .tex_parameter_i32(target, GL_TEXTURE_MAG_FILTER, magnification_filter as i32);
+
+        let wrapping_horizontal = wrapping_to_gl_enum(texture_settings.wrapping_horizontal);
+        let wrapping_vertical = wrapping_to_gl_enum(texture_settings.wrapping_vertical);
+        self.gl
+            .tex_parameter_i32(target, GL_TEXTURE_WRAP_S, wrapping_horizontal as i32);
+        self.gl
+            .tex_parameter_i32(target, GL_TEXTURE_WRAP_T, wrapping_vertical as i32);
+
+        if generate_mipmaps {
+            self.gl.generate_mipmap(target);
+        } else {
+            // Files may not include a full mip chain, so only sample the levels that exist.
+            self.gl
+                .tex_parameter_i32(target, GL_TEXTURE_MAX_LEVEL, mip_levels as i32 - 1);
+        }
+    }
+
     fn new_program(
         &self,
         vertex_function: &VertexFunction,
@@ -1,4 +1,4 @@
-pub use crate::{FilterMode, PixelFormat, WrappingMode};
+pub use crate::{CompareFunction, FilterMode, PixelFormat, StencilOperation, WrappingMode};
 pub use std::os::raw::c_uint;
 
 pub const ACTIVE_UNIFORMS: c_uint = 0x8B86;
@@ -10,6 +10,7 @@ pub const FLOAT: c_uint = 0x1406;
 pub const UNSIGNED_SHORT: c_uint = 0x1403;
 pub const UNSIGNED_INT: c_uint = 0x1405;
 pub const UNSIGNED_BYTE: c_uint = 0x1401;
+pub const UNSIGNED_INT_24_8: c_uint = 0x84FA;
 
 pub const FLOAT_VEC2: c_uint = 0x8B50;
 pub const FLOAT_VEC3: c_uint = 0x8B51;
@@ -18,6 +19,7 @@ pub const FLOAT_MAT4: c_uint = 0x8B5C;
 pub const SAMPLER_2D: c_uint = 0x8B5E;
 pub const SAMPLER_CUBE: c_uint = 0x8B60;
 
+pub const NEVER: c_uint = 0x0200;
 pub const LESS: c_uint = 0x0201;
 pub const EQUAL: c_uint = 0x0202;
 pub const LEQUAL: c_uint = 0x0203;
@@ -26,6 +28,15 @@ pub const NOTEQUAL: c_uint = 0x0205;
 pub const GEQUAL: c_uint = 0x0206;
 pub const ALWAYS: c_uint = 0x0207;
 
+pub const ZERO: c_uint = 0x0;
+pub const KEEP: c_uint = 0x1E00;
+pub const REPLACE: c_uint = 0x1E01;
+pub const INCR: c_uint = 0x1E02;
+pub const DECR: c_uint = 0x1E03;
+pub const INVERT: c_uint = 0x150A;
+pub const INCR_WRAP: c_uint = 0x8507;
+pub const DECR_WRAP: c_uint = 0x8508;
+
 pub const FRONT: c_uint = 0x0404;
 pub const BACK: c_uint = 0x0405;
 pub const FRONT_AND_BACK: c_uint = 0x0408;
@@ -37,6 +48,7 @@ pub const SRC_ALPHA: c_uint = 0x0302;
 pub const DEPTH_COMPONENT16: c_uint = 0x81A5;
 pub const DEPTH_COMPONENT24: c_uint = 0x81A6;
 pub const DEPTH_COMPONENT32F: c_uint = 0x8CAC;
+pub const DEPTH24_STENCIL8: c_uint = 0x88F0;
 
 pub const NEAREST: c_uint = 0x2600;
 pub const LINEAR: c_uint = 0x2601;
@@ -50,6 +62,7 @@ pub const MIRRORED_REPEAT: c_uint = 0x8370;
 pub const REPEAT: c_uint = 0x2901;
 
 pub const DEPTH_COMPONENT: c_uint = 0x1902;
+pub const DEPTH_STENCIL: c_uint = 0x84F9;
 pub const RED: c_uint = 0x1903;
 pub const RG: c_uint = 0x8227;
 pub const RGB: c_uint = 0x1907;
@@ -67,6 +80,22 @@ pub const RGB32F: c_uint = 0x8815;
 pub const RGBA16F: c_uint = 0x881A;
 pub const RGBA32F: c_uint = 0x8814;
 
+pub const COMPRESSED_RGBA_S3TC_DXT1_EXT: c_uint = 0x83F1;
+pub const COMPRESSED_RGBA_S3TC_DXT3_EXT: c_uint = 0x83F2;
+pub const COMPRESSED_RGBA_S3TC_DXT5_EXT: c_uint = 0x83F3;
+pub const COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT: c_uint = 0x8C4D;
+pub const COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT: c_uint = 0x8C4E;
+pub const COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: c_uint = 0x8C4F;
+pub const COMPRESSED_RED_RGTC1: c_uint = 0x8DBB;
+pub const COMPRESSED_RG_RGTC2: c_uint = 0x8DBD;
+pub const COMPRESSED_RGBA_BPTC_UNORM: c_uint = 0x8E8C;
+pub const COMPRESSED_SRGB_ALPHA_BPTC_UNORM: c_uint = 0x8E8D;
+pub const COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT: c_uint = 0x8E8F;
+pub const COMPRESSED_RGB8_ETC2: c_uint = 0x9274;
+pub const COMPRESSED_SRGB8_ETC2: c_uint = 0x9275;
+pub const COMPRESSED_RGBA8_ETC2_EAC: c_uint = 0x9278;
+pub const COMPRESSED_SRGB8_ALPHA8_ETC2_EAC: c_uint = 0x9279;
+
 pub const TEXTURE0: c_uint = 0x84C0;
 
 pub const TEXTURE_2D: c_uint = 0x0DE1;
@@ -128,14 +157,51 @@ pub unsafe fn flip_image(pixel_format: PixelFormat, width: usize, height: usize,
         PixelFormat::RG8Unorm => flip_image_inner::<u8, 2>(data, width, height),
         PixelFormat::RGB8Unorm => flip_image_inner::<u8, 3>(data, width, height),
         PixelFormat::RGBA8Unorm => flip_image_inner::<u8, 4>(data, width, height),
-        PixelFormat::Depth16 | PixelFormat::Depth24 | PixelFormat::Depth32F => {
-            flip_image_inner::<f32, 1>(data, width, height)
-        }
+        PixelFormat::Depth16
+        | PixelFormat::Depth24
+        | PixelFormat::Depth32F
+        | PixelFormat::Depth24Stencil8 => flip_image_inner::<f32, 1>(data, width, height),
         PixelFormat::RGBA16F => flip_image_inner::<[u8; 2], 4>(data, width, height),
         PixelFormat::RGBA32F => flip_image_inner::<f32, 4>(data, width, height),
+        // Compressed blocks can't be flipped without decoding them.
+        PixelFormat::BC1RGBAUnorm
+        | PixelFormat::BC2RGBAUnorm
+        | PixelFormat::BC3RGBAUnorm
+        | PixelFormat::BC4RUnorm
+        | PixelFormat::BC5RGUnorm
+        | PixelFormat::BC6HRGBUfloat
+        | PixelFormat::BC7RGBAUnorm
+        | PixelFormat::ETC2RGB8Unorm
+        | PixelFormat::ETC2RGBA8Unorm => {}
     }
 }
 
+/// The internal format used to upload a block compressed [PixelFormat].
+/// Returns `None` for uncompressed formats.
+pub fn compressed_pixel_format_to_gl_inner_format(
+    pixel_format: PixelFormat,
+    srgb: bool,
+) -> Option<c_uint> {
+    Some(match (pixel_format, srgb) {
+        (PixelFormat::BC1RGBAUnorm, false) => COMPRESSED_RGBA_S3TC_DXT1_EXT,
+        (PixelFormat::BC1RGBAUnorm, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT,
+        (PixelFormat::BC2RGBAUnorm, false) => COMPRESSED_RGBA_S3TC_DXT3_EXT,
+        (PixelFormat::BC2RGBAUnorm, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT,
+        (PixelFormat::BC3RGBAUnorm, false) => COMPRESSED_RGBA_S3TC_DXT5_EXT,
+        (PixelFormat::BC3RGBAUnorm, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT,
+        (PixelFormat::BC4RUnorm, _) => COMPRESSED_RED_RGTC1,
+        (PixelFormat::BC5RGUnorm, _) => COMPRESSED_RG_RGTC2,
+        (PixelFormat::BC6HRGBUfloat, _) => COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+        (PixelFormat::BC7RGBAUnorm, false) => COMPRESSED_RGBA_BPTC_UNORM,
+        (PixelFormat::BC7RGBAUnorm, true) => COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+        (PixelFormat::ETC2RGB8Unorm, false) => COMPRESSED_RGB8_ETC2,
+        (PixelFormat::ETC2RGB8Unorm, true) => COMPRESSED_SRGB8_ETC2,
+        (PixelFormat::ETC2RGBA8Unorm, false) => COMPRESSED_RGBA8_ETC2_EAC,
+        (PixelFormat::ETC2RGBA8Unorm, true) => COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+        _ => return None,
+    })
+}
+
 /*
 pub unsafe fn prepare_image(
     pixel_format: PixelFormat,
@@ -185,25 +251,31 @@ pub fn pixel_format_to_gl_format_and_inner_format_and_type(
         PixelFormat::RGB8Unorm /*| PixelFormat::RGB32F | PixelFormat::RGB16F*/ => RGB,
         PixelFormat::RGBA8Unorm  | PixelFormat::RGBA16F | PixelFormat::RGBA32F => RGBA,
         PixelFormat::Depth16 | PixelFormat::Depth24 | PixelFormat::Depth32F => DEPTH_COMPONENT,
+        PixelFormat::Depth24Stencil8 => DEPTH_STENCIL,
+        // Compressed formats are uploaded with `compressed_pixel_format_to_gl_inner_format`
+        _ => RGBA,
     };
 
     let mut inner_format = match pixel_format {
         PixelFormat::Depth16 => DEPTH_COMPONENT16,
         PixelFormat::Depth24 => DEPTH_COMPONENT24,
         PixelFormat::Depth32F => DEPTH_COMPONENT32F,
+        PixelFormat::Depth24Stencil8 => DEPTH24_STENCIL8,
         PixelFormat::R8Unorm => R8,
         PixelFormat::RG8Unorm => RG8,
         PixelFormat::RGB8Unorm => RGB8,
         PixelFormat::RGBA8Unorm => RGBA8,
         PixelFormat::RGBA16F => RGBA16F,
-        PixelFormat::RGBA32F => RGBA32F
+        PixelFormat::RGBA32F => RGBA32F,
         // PixelFormat::RGB16F => RGB16F,
         // PixelFormat::RGB32F => RGB32F,
+        _ => compressed_pixel_format_to_gl_inner_format(pixel_format, false).unwrap(),
     };
 
     let type_ = match pixel_format {
         PixelFormat::Depth16 => UNSIGNED_SHORT,
         PixelFormat::Depth24 => UNSIGNED_INT,
+        PixelFormat::Depth24Stencil8 => UNSIGNED_INT_24_8,
         // PixelFormat::RGB16F => HALF_FLOAT,
         PixelFormat::RGBA16F => HALF_FLOAT,
         PixelFormat::Depth32F | PixelFormat::RGBA32F => FLOAT,
@@ -247,3 +319,29 @@ pub fn wrapping_to_gl_enum(wrapping_mode: WrappingMode) -> c_uint {
         WrappingMode::Repeat => REPEAT,
     }
 }
+
+pub fn compare_function_to_gl_enum(compare_function: CompareFunction) -> c_uint {
+    match compare_function {
+        CompareFunction::Never => NEVER,
+        CompareFunction::Less => LESS,
+        CompareFunction::Equal => EQUAL,
+        CompareFunction::LessOrEqual => LEQUAL,
+        CompareFunction::Greater => GREATER,
+        CompareFunction::NotEqual => NOTEQUAL,
+        CompareFunction::GreaterOrEqual => GEQUAL,
+        CompareFunction::Always => ALWAYS,
+    }
+}
+
+pub fn stencil_operation_to_gl_enum(stencil_operation: StencilOperation) -> c_uint {
+    match stencil_operation {
+        StencilOperation::Keep => KEEP,
+        StencilOperation::Zero => ZERO,
+        StencilOperation::Replace => REPLACE,
+        StencilOperation::IncrementClamp => INCR,
+        StencilOperation::DecrementClamp => DECR,
+        StencilOperation::Invert => INVERT,
+        StencilOperation::IncrementWrap => INCR_WRAP,
+        StencilOperation::DecrementWrap => DECR_WRAP,
+    }
+}
@@ -94,6 +94,10 @@ pub trait RenderPassTrait {
 
     fn set_depth_mask(&mut self, depth_mask: bool);
 
+    /// Overrides the stencil reference value of the current pipeline until the pipeline is changed.
+    /// Has no effect if the current pipeline has no stencil state.
+    fn set_stencil_reference(&mut self, reference: u8);
+
     #[allow(clippy::too_many_arguments)]
     fn blit_framebuffer(
         self,
@@ -158,6 +162,9 @@ pub trait GraphicsContextTrait: Sized {
     fn new_vertex_function(&mut self, source: &str) -> Result<VertexFunction, String>;
 
     fn new_data_buffer<T>(&mut self, data: &[T]) -> Result<DataBuffer<T>, GraphicsError>;
+    /// Replaces the contents of a [DataBuffer] without creating a new buffer.
+    /// Commands that use the buffer see the new contents when the command buffer is committed.
+    fn update_data_buffer<T>(&mut self, data_buffer: &mut DataBuffer<T>, data: &[T]);
     fn delete_data_buffer<T>(&mut self, data_buffer: DataBuffer<T>);
 
     fn new_index_buffer(&mut self, data: &[u32]) -> Result<IndexBuffer, GraphicsError>;
@@ -190,7 +197,16 @@ pub trait GraphicsContextTrait: Sized {
 
     fn delete_texture(&mut self, texture: Texture);
 
-    fn read_texture(&mut self, texture: &Texture, format: PixelFormat, size: usize) -> Vec<u8>;
+    /// Reads back the bottom-left `width` x `height` pixels of a texture in `format`.
+    /// Rows are returned bottom to top.
+    /// A [RenderTarget]'s current frame can be read before it's presented.
+    fn read_texture(
+        &mut self,
+        texture: &Texture,
+        format: PixelFormat,
+        width: u32,
+        height: u32,
+    ) -> Vec<u8>;
 
     fn generate_mip_map_for_texture(&mut self, texture: &Texture);
 
@@ -236,4 +252,47 @@ pub trait GraphicsContextTrait: Sized {
     fn get_multiview_supported(&self) -> MultiviewSupport {
         MultiviewSupport::None
     }
+
+    /// Can textures of this [PixelFormat] be sampled on this device?
+    /// Uncompressed formats are always supported.
+    fn supports_pixel_format(&self, pixel_format: PixelFormat) -> bool {
+        !pixel_format.is_compressed()
+    }
+
+    /// Creates a texture from pre-baked mip levels, starting with the full size level.
+    /// This is the only way to upload block compressed [PixelFormat]s.
+    fn new_texture_with_mipmaps(
+        &mut self,
+        width: u32,
+        height: u32,
+        levels: &[&[u8]],
+        pixel_format: PixelFormat,
+        texture_settings: TextureSettings,
+    ) -> Result<Texture, GraphicsError> {
+        self.new_texture(
+            width,
+            height,
+            1,
+            levels.first().copied(),
+            pixel_format,
+            texture_settings,
+        )
+    }
+
+    /// Creates a cube map from pre-baked mip levels, each with 6 faces.
+    fn new_cube_map_with_mipmaps(
+        &mut self,
+        size: u32,
+        levels: &[[&[u8]; 6]],
+        pixel_format: PixelFormat,
+        texture_settings: TextureSettings,
+    ) -> Result<CubeMap, GraphicsError> {
+        self.new_cube_map(
+            size,
+            size,
+            levels.first().copied(),
+            pixel_format,
+            texture_settings,
+        )
+    }
 }
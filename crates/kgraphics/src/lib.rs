@@ -35,13 +35,74 @@ pub enum PixelFormat {
     Depth16,
     Depth24,
     Depth32F,
+    /// 24-bit depth and an 8-bit stencil.
+    /// Pass the same texture as both the depth and stencil attachments of a framebuffer.
+    Depth24Stencil8,
     RGBA16F,
     RGBA32F,
     // RGB16F,
     // RGB32F,
+    /// BC1 / DXT1 block compressed RGB with 1-bit alpha.
+    BC1RGBAUnorm,
+    /// BC2 / DXT3 block compressed RGBA with explicit alpha.
+    BC2RGBAUnorm,
+    /// BC3 / DXT5 block compressed RGBA with interpolated alpha.
+    BC3RGBAUnorm,
+    /// BC4 block compressed single channel.
+    BC4RUnorm,
+    /// BC5 block compressed two channels.
+    BC5RGUnorm,
+    /// BC6H block compressed unsigned half-float RGB.
+    BC6HRGBUfloat,
+    /// BC7 block compressed RGBA.
+    BC7RGBAUnorm,
+    /// ETC2 block compressed RGB. ETC1 data is also valid ETC2 data.
+    ETC2RGB8Unorm,
+    /// ETC2 block compressed RGBA with EAC alpha.
+    ETC2RGBA8Unorm,
 }
 
-#[derive(Clone, Copy, Debug)]
+impl PixelFormat {
+    /// Is this a block compressed format?
+    /// Compressed formats are stored as 4x4 pixel blocks and can't be rendered to.
+    pub fn is_compressed(&self) -> bool {
+        self.block_bytes().is_some()
+    }
+
+    /// The number of bytes used to store a pixel of uncompressed formats.
+    pub fn bytes_per_pixel(&self) -> Option<usize> {
+        match self {
+            PixelFormat::R8Unorm => Some(1),
+            PixelFormat::RG8Unorm | PixelFormat::Depth16 => Some(2),
+            PixelFormat::RGB8Unorm => Some(3),
+            PixelFormat::RGBA8Unorm
+            | PixelFormat::Depth24
+            | PixelFormat::Depth32F
+            | PixelFormat::Depth24Stencil8 => Some(4),
+            PixelFormat::RGBA16F => Some(8),
+            PixelFormat::RGBA32F => Some(16),
+            _ => None,
+        }
+    }
+
+    /// The number of bytes used to store a 4x4 block of pixels for compressed formats.
+    pub fn block_bytes(&self) -> Option<usize> {
+        match self {
+            PixelFormat::BC1RGBAUnorm | PixelFormat::BC4RUnorm | PixelFormat::ETC2RGB8Unorm => {
+                Some(8)
+            }
+            PixelFormat::BC2RGBAUnorm
+            | PixelFormat::BC3RGBAUnorm
+            | PixelFormat::BC5RGUnorm
+            | PixelFormat::BC6HRGBUfloat
+            | PixelFormat::BC7RGBAUnorm
+            | PixelFormat::ETC2RGBA8Unorm => Some(16),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum FacesToRender {
     Front,
     Back,
@@ -49,7 +110,7 @@ pub enum FacesToRender {
     None,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 /// Specifies if a pixel will be rendered based on the z-buffer value.
 pub enum DepthTest {
     /// Effectively disables depth testing.
@@ -64,8 +125,72 @@ pub enum DepthTest {
     GreaterOrEqual,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Compares a reference value against a value already in a buffer.
+pub enum CompareFunction {
+    Never,
+    Less,
+    Equal,
+    LessOrEqual,
+    Greater,
+    NotEqual,
+    GreaterOrEqual,
+    Always,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// What happens to a stencil buffer value after the stencil and depth tests.
+pub enum StencilOperation {
+    Keep,
+    Zero,
+    /// Replace the value with the reference value.
+    Replace,
+    IncrementClamp,
+    DecrementClamp,
+    /// Flip the value's bits.
+    Invert,
+    IncrementWrap,
+    DecrementWrap,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Specifies if a pixel will be rendered based on the stencil buffer value
+/// and how the stencil buffer is updated.
+/// The same state is used for front and back faces.
+pub struct StencilState {
+    /// The value compared against and written by [StencilOperation::Replace].
+    /// Can be changed without a new pipeline with `RenderPassTrait::set_stencil_reference`.
+    pub reference: u8,
+    /// Masks both the reference and the stencil buffer value before they're compared.
+    pub read_mask: u8,
+    /// Which bits of the stencil buffer can be written.
+    pub write_mask: u8,
+    /// The pixel passes if `reference <compare> stencil_value`.
+    pub compare: CompareFunction,
+    /// Applied if the stencil test fails.
+    pub fail: StencilOperation,
+    /// Applied if the stencil test passes but the depth test fails.
+    pub depth_fail: StencilOperation,
+    /// Applied if both the stencil and depth tests pass.
+    pub pass: StencilOperation,
+}
+
+impl Default for StencilState {
+    fn default() -> Self {
+        Self {
+            reference: 0,
+            read_mask: 0xFF,
+            write_mask: 0xFF,
+            compare: CompareFunction::Always,
+            fail: StencilOperation::Keep,
+            depth_fail: StencilOperation::Keep,
+            pass: StencilOperation::Keep,
+        }
+    }
+}
+
 /// This should be expanded
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum BlendFactor {
     /// source_pixel
     One,
@@ -8,6 +8,8 @@ pub struct PipelineBuilder<'a> {
     pub(crate) faces_to_render: FacesToRender,
     /// Source and destination blend factors.
     pub(crate) blending: Option<(BlendFactor, BlendFactor)>,
+    /// `None` disables the stencil test.
+    pub(crate) stencil: Option<StencilState>,
     #[allow(unused)]
     pub(crate) output_pixel_format: PixelFormat,
     pub(crate) depth_clear_value: f32,
@@ -21,6 +23,7 @@ impl<'a> PipelineBuilder<'a> {
             fragment: None,
             depth_test: crate::DepthTest::LessOrEqual,
             blending: None,
+            stencil: None,
             output_pixel_format: PixelFormat::RGB8Unorm,
             faces_to_render: FacesToRender::Front,
             depth_clear_value: 1.0,
@@ -41,6 +44,13 @@ impl<'a> PipelineBuilder<'a> {
         self
     }
 
+    /// Defaults to 'None' which disables the stencil test.
+    /// The framebuffer needs a stencil attachment for this to have an effect.
+    pub fn stencil(mut self, stencil: Option<StencilState>) -> Self {
+        self.stencil = stencil;
+        self
+    }
+
     /// Defaults to '1.0'
     pub fn depth_clear_value(mut self, depth_clear_value: f32) -> Self {
         self.depth_clear_value = depth_clear_value;
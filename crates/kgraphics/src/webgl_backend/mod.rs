@@ -6,6 +6,8 @@ use std::collections::HashMap;
 pub struct GraphicsContext {
     old_command_buffers: Vec<CommandBuffer>,
     js: WebGLJS,
+    /// Block compressed formats enabled by extensions on this context.
+    compressed_pixel_formats: Vec<PixelFormat>,
 }
 
 pub struct RenderTarget {
@@ -130,6 +132,7 @@ enum Command {
     SetDepthMask = 16,
     BlitFramebuffer = 17,
     SetUniformBlock = 18,
+    SetStencilReference = 19,
 }
 
 pub struct CommandBuffer {
@@ -165,6 +168,7 @@ pub struct Pipeline {
     depth_test: DepthTest,
     faces_to_render: FacesToRender,
     blending: Option<(BlendFactor, BlendFactor)>,
+    stencil: Option<StencilState>,
     depth_clear_value: f32,
 }
 
@@ -180,6 +184,12 @@ pub struct VertexAttribute<T> {
     phantom: std::marker::PhantomData<T>,
 }
 
+impl<T> VertexAttribute<T> {
+    pub fn exists(&self) -> bool {
+        self.info.is_some()
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct UniformBlockInfo {
     location: u32,
@@ -337,6 +347,22 @@ impl RenderPassTrait for RenderPass<'_> {
             source_blend_factor,
             destination_blend_factor,
         ]);
+
+        // A leading 0 disables the stencil test.
+        let stencil = pipeline.stencil.map_or([0; 8], |stencil| {
+            [
+                1,
+                compare_function_to_gl_enum(stencil.compare),
+                stencil.reference as u32,
+                stencil.read_mask as u32,
+                stencil.write_mask as u32,
+                stencil_operation_to_gl_enum(stencil.fail),
+                stencil_operation_to_gl_enum(stencil.depth_fail),
+                stencil_operation_to_gl_enum(stencil.pass),
+            ]
+        });
+        self.command_buffer.u32_data.extend_from_slice(&stencil);
+
         self.command_buffer
             .f32_data
             .push(pipeline.depth_clear_value);
@@ -559,6 +585,13 @@ impl RenderPassTrait for RenderPass<'_> {
             .extend_from_slice(&[if value { 1 } else { 0 }]);
     }
 
+    fn set_stencil_reference(&mut self, reference: u8) {
+        self.command_buffer
+            .commands
+            .push(Command::SetStencilReference);
+        self.command_buffer.u32_data.push(reference as u32);
+    }
+
     fn blit_framebuffer(
         self,
         target: &Framebuffer,
@@ -703,6 +736,7 @@ struct WebGLJS {
     new_vertex_function: JSObject,
     new_fragment_function: JSObject,
     new_data_buffer: JSObject,
+    update_data_buffer: JSObject,
     new_index_buffer: JSObject,
     delete_buffer: JSObject,
     new_texture: JSObject,
@@ -718,10 +752,14 @@ struct WebGLJS {
     run_command_buffer: JSObject,
     get_attribute_location: JSObject,
     get_multiview_supported: JSObject,
+    get_compressed_texture_support: JSObject,
+    tex_image_level: JSObject,
+    set_texture_sampling: JSObject,
     generate_mip_map: JSObject,
     framebuffer_texture_2d: JSObject,
     framebuffer_renderbuffer: JSObject,
     bind_framebuffer: JSObject,
+    read_pixels: JSObject,
     create_framebuffer: JSObject,
     delete_framebuffer: JSObject,
 }
@@ -735,6 +773,7 @@ impl WebGLJS {
             new_vertex_function: o.get_property("new_vertex_function"),
             new_fragment_function: o.get_property("new_fragment_function"),
             new_data_buffer: o.get_property("new_data_buffer"),
+            update_data_buffer: o.get_property("update_data_buffer"),
             new_index_buffer: o.get_property("new_index_buffer"),
             delete_buffer: o.get_property("delete_buffer"),
             new_texture: o.get_property("new_texture"),
@@ -750,10 +789,14 @@ impl WebGLJS {
             run_command_buffer: o.get_property("run_command_buffer"),
             get_attribute_location: o.get_property("get_attribute_location"),
             get_multiview_supported: o.get_property("get_multiview_supported"),
+            get_compressed_texture_support: o.get_property("get_compressed_texture_support"),
+            tex_image_level: o.get_property("tex_image_level"),
+            set_texture_sampling: o.get_property("set_texture_sampling"),
             generate_mip_map: o.get_property("generate_mip_map"),
             framebuffer_texture_2d: o.get_property("framebuffer_texture_2d"),
             framebuffer_renderbuffer: o.get_property("framebuffer_renderbuffer"),
             bind_framebuffer: o.get_property("bind_framebuffer"),
+            read_pixels: o.get_property("read_pixels"),
             create_framebuffer: o.get_property("create_framebuffer"),
             delete_framebuffer: o.get_property("delete_framebuffer"),
         }
@@ -788,6 +831,95 @@ impl GraphicsContext {
         Ok(texture)
     }
 
+    /// Uploads a single mip level of a texture.
+    #[allow(clippy::too_many_arguments)]
+    fn tex_image_level(
+        &self,
+        texture: &JSObjectDynamic,
+        target: u32,
+        image_target: u32,
+        level: usize,
+        width: u32,
+        height: u32,
+        data: &[u8],
+        pixel_format: PixelFormat,
+        srgb: bool,
+    ) {
+        let width = (width >> level).max(1);
+        let height = (height >> level).max(1);
+        let (pixel_format, inner_pixel_format, type_, compressed) =
+            match crate::gl_shared::compressed_pixel_format_to_gl_inner_format(pixel_format, srgb) {
+                Some(inner_pixel_format) => (0, inner_pixel_format, 0, 1),
+                None => {
+                    let (pixel_format, inner_pixel_format, type_) =
+                        crate::gl_shared::pixel_format_to_gl_format_and_inner_format_and_type(
+                            pixel_format,
+                            srgb,
+                        );
+                    (pixel_format, inner_pixel_format, type_, 0)
+                }
+            };
+        self.js.tex_image_level.call_raw(&[
+            texture.index(),
+            target,
+            image_target,
+            level as u32,
+            inner_pixel_format,
+            width,
+            height,
+            pixel_format,
+            type_,
+            compressed,
+            data.as_ptr() as u32,
+            data.len() as u32,
+        ]);
+    }
+
+    /// Sets filtering and wrapping for a texture with `mip_levels` uploaded levels.
+    fn set_sampling_for_mip_levels(
+        &self,
+        texture: &JSObjectDynamic,
+        target: u32,
+        mip_levels: usize,
+        pixel_format: PixelFormat,
+        texture_settings: TextureSettings,
+    ) {
+        // Mipmaps can't be generated for compressed formats.
+        let generate_mipmaps =
+            texture_settings.generate_mipmaps && mip_levels == 1 && !pixel_format.is_compressed();
+
+        let minification_filter = minification_filter_to_gl_enum(
+            texture_settings.minification_filter,
+            texture_settings.mipmap_filter,
+            generate_mipmaps || mip_levels > 1,
+        );
+        let magnification_filter =
+            magnification_filter_to_gl_enum(texture_settings.magnification_filter);
+        let wrapping_horizontal = wrapping_to_gl_enum(texture_settings.wrapping_horizontal);
+        let wrapping_vertical = wrapping_to_gl_enum(texture_settings.wrapping_vertical);
+
+        // Files may not include a full mip chain, so only sample the levels that exist.
+        let max_level = if generate_mipmaps {
+            1000
+        } else {
+            mip_levels as u32 - 1
+        };
+        self.js.set_texture_sampling.call_raw(&[
+            texture.index(),
+            target,
+            minification_filter,
+            magnification_filter,
+            wrapping_horizontal,
+            wrapping_vertical,
+            max_level,
+        ]);
+        if generate_mipmaps {
+            self.js
+                .generate_mip_map
+                .call_raw(&[texture.index(), target]);
+        }
+    }
+
     fn update_texture_internal(
         &mut self,
         texture: &Texture,
@@ -872,9 +1004,36 @@ impl GraphicsContextTrait for GraphicsContext {
 
         // Initialize context
         js.new.call_raw(&[msaa_enabled, display_p3]);
+
+        let support = js
+            .get_compressed_texture_support
+            .call()
+            .unwrap()
+            .get_value_u32();
+        let mut compressed_pixel_formats = Vec::new();
+        if support & 1 != 0 {
+            compressed_pixel_formats.extend([
+                PixelFormat::BC1RGBAUnorm,
+                PixelFormat::BC2RGBAUnorm,
+                PixelFormat::BC3RGBAUnorm,
+            ]);
+        }
+        if support & 2 != 0 {
+            compressed_pixel_formats.extend([PixelFormat::BC4RUnorm, PixelFormat::BC5RGUnorm]);
+        }
+        if support & 4 != 0 {
+            compressed_pixel_formats
+                .extend([PixelFormat::BC6HRGBUfloat, PixelFormat::BC7RGBAUnorm]);
+        }
+        if support & 8 != 0 {
+            compressed_pixel_formats
+                .extend([PixelFormat::ETC2RGB8Unorm, PixelFormat::ETC2RGBA8Unorm]);
+        }
+
         Self {
             js,
             old_command_buffers: Vec::new(),
+            compressed_pixel_formats,
         }
     }
 
@@ -924,6 +1083,16 @@ impl GraphicsContextTrait for GraphicsContext {
         })
     }
 
+    fn update_data_buffer<T>(&mut self, data_buffer: &mut DataBuffer<T>, data: &[T]) {
+        let len = std::mem::size_of::<T>() * data.len();
+        self.js.update_data_buffer.call_raw(&[
+            data_buffer.js_object.index(),
+            data.as_ptr() as u32,
+            len as u32,
+        ]);
+        data_buffer.len = len;
+    }
+
     fn delete_data_buffer<T>(&mut self, data_buffer: DataBuffer<T>) {
         self.js.delete_buffer.call_1_arg(&data_buffer.js_object);
     }
@@ -1035,8 +1204,42 @@ impl GraphicsContextTrait for GraphicsContext {
         }
     }
 
-    fn read_texture(&mut self, _texture: &Texture, _format: PixelFormat, _size: usize) -> Vec<u8> {
-        todo!()
+    fn read_texture(
+        &mut self,
+        texture: &Texture,
+        format: PixelFormat,
+        width: u32,
+        height: u32,
+    ) -> Vec<u8> {
+        // The default framebuffer is read directly.
+        let framebuffer = match texture.texture_type {
+            TextureType::DefaultFramebuffer => None,
+            _ => Some(self.new_framebuffer(Some(texture), None, None)),
+        };
+        let (pixel_format, _inner_pixel_format, type_) =
+            crate::gl_shared::pixel_format_to_gl_format_and_inner_format_and_type(format, false);
+        let bytes_per_pixel = format
+            .bytes_per_pixel()
+            .expect("Compressed textures cannot be read");
+
+        let mut data = vec![0u8; width as usize * height as usize * bytes_per_pixel];
+        self.js.read_pixels.call_raw(&[
+            framebuffer
+                .as_ref()
+                .and_then(|f| f.0.as_ref())
+                .map_or(0, |f| f.index()),
+            width,
+            height,
+            pixel_format,
+            type_,
+            data.as_mut_ptr() as u32,
+            data.len() as u32,
+        ]);
+
+        if let Some(framebuffer) = framebuffer {
+            self.delete_framebuffer(framebuffer);
+        }
+        data
     }
 
     fn new_pipeline(
@@ -1249,6 +1452,78 @@ impl GraphicsContextTrait for GraphicsContext {
         }
     }
 
+    fn supports_pixel_format(&self, pixel_format: PixelFormat) -> bool {
+        !pixel_format.is_compressed() || self.compressed_pixel_formats.contains(&pixel_format)
+    }
+
+    fn new_texture_with_mipmaps(
+        &mut self,
+        width: u32,
+        height: u32,
+        levels: &[&[u8]],
+        pixel_format: PixelFormat,
+        texture_settings: TextureSettings,
+    ) -> Result<Texture, GraphicsError> {
+        let js_object = self.js.new_texture.call().unwrap().to_dynamic();
+        for (level, data) in levels.iter().enumerate() {
+            self.tex_image_level(
+                &js_object,
+                TEXTURE_2D,
+                TEXTURE_2D,
+                level,
+                width,
+                height,
+                data,
+                pixel_format,
+                texture_settings.srgb,
+            );
+        }
+        self.set_sampling_for_mip_levels(
+            &js_object,
+            TEXTURE_2D,
+            levels.len(),
+            pixel_format,
+            texture_settings,
+        );
+        Ok(Texture {
+            texture_type: TextureType::Texture(js_object),
+            mip: 0,
+        })
+    }
+
+    fn new_cube_map_with_mipmaps(
+        &mut self,
+        size: u32,
+        levels: &[[&[u8]; 6]],
+        pixel_format: PixelFormat,
+        texture_settings: TextureSettings,
+    ) -> Result<CubeMap, GraphicsError> {
+        let texture = self.js.new_texture.call().unwrap().to_dynamic();
+        for (level, faces) in levels.iter().enumerate() {
+            for (i, data) in faces.iter().enumerate() {
+                self.tex_image_level(
+                    &texture,
+                    TEXTURE_CUBE_MAP,
+                    TEXTURE_CUBE_MAP_POSITIVE_X + i as u32,
+                    level,
+                    size,
+                    size,
+                    data,
+                    pixel_format,
+                    texture_settings.srgb,
+                );
+            }
+        }
+        self.set_sampling_for_mip_levels(
+            &texture,
+            TEXTURE_CUBE_MAP,
+            levels.len(),
+            pixel_format,
+            texture_settings,
+        );
+        Ok(CubeMap { texture })
+    }
+
     fn get_multiview_supported(&self) -> MultiviewSupport {
         match self
             .js
@@ -1371,6 +1646,7 @@ impl<'a> PipelineBuilderTrait for PipelineBuilder<'a> {
             depth_test: self.depth_test,
             faces_to_render: self.faces_to_render,
             blending: self.blending,
+            stencil: self.stencil,
             depth_clear_value: self.depth_clear_value,
         })
     }
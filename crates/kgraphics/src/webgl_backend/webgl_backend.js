@@ -87,6 +87,11 @@ var gl_web_object = {
     gl.bufferData(gl.ARRAY_BUFFER, data, gl.STATIC_DRAW);
     return buffer;
   },
+  update_data_buffer(data_buffer_index, data_ptr, data_length) {
+    const data = new Uint8Array(self.kwasm_memory.buffer, data_ptr, data_length);
+    gl.bindBuffer(gl.ARRAY_BUFFER, self.kwasm_get_object(data_buffer_index));
+    gl.bufferData(gl.ARRAY_BUFFER, data, gl.DYNAMIC_DRAW);
+  },
   new_index_buffer(data_ptr, data_length) {
     const data = new Uint32Array(self.kwasm_memory.buffer, data_ptr, data_length);
     let buffer = gl.createBuffer();
@@ -244,6 +249,46 @@ var gl_web_object = {
       }
     }
   },
+  get_compressed_texture_support() {
+    // Each bit corresponds to a family of block compressed formats.
+    let support = 0;
+    if (gl.getExtension('WEBGL_compressed_texture_s3tc') && gl.getExtension('WEBGL_compressed_texture_s3tc_srgb')) {
+      support |= 1;
+    }
+    if (gl.getExtension('EXT_texture_compression_rgtc')) {
+      support |= 2;
+    }
+    if (gl.getExtension('EXT_texture_compression_bptc')) {
+      support |= 4;
+    }
+    if (gl.getExtension('WEBGL_compressed_texture_etc')) {
+      support |= 8;
+    }
+    return support;
+  },
+  tex_image_level(texture_index, target, image_target, level, inner_pixel_format, width, height, pixel_format, type_, compressed, data_ptr, data_length) {
+    let texture = self.kwasm_get_object(texture_index);
+    gl.bindTexture(target, texture);
+
+    if (compressed !== 0) {
+      let data = new Uint8Array(self.kwasm_memory.buffer, data_ptr, data_length);
+      gl.compressedTexImage2D(image_target, level, inner_pixel_format, width, height, 0, data);
+    } else {
+      let data = type_ == gl.FLOAT ?
+        new Float32Array(self.kwasm_memory.buffer, data_ptr, data_length / 4) :
+        new Uint8Array(self.kwasm_memory.buffer, data_ptr, data_length);
+      gl.texImage2D(image_target, level, inner_pixel_format, width, height, 0, pixel_format, type_, data);
+    }
+  },
+  set_texture_sampling(texture_index, target, min, mag, wrapping_horizontal, wrapping_vertical, max_level) {
+    let texture = self.kwasm_get_object(texture_index);
+    gl.bindTexture(target, texture);
+    gl.texParameteri(target, gl.TEXTURE_MIN_FILTER, min);
+    gl.texParameteri(target, gl.TEXTURE_MAG_FILTER, mag);
+    gl.texParameteri(target, gl.TEXTURE_WRAP_S, wrapping_horizontal);
+    gl.texParameteri(target, gl.TEXTURE_WRAP_T, wrapping_vertical);
+    gl.texParameteri(target, gl.TEXTURE_MAX_LEVEL, max_level);
+  },
   generate_mip_map(texture_index, texture_type) {
     let texture = self.kwasm_get_object(texture_index);
     gl.bindTexture(texture_type, texture);
@@ -273,6 +318,16 @@ var gl_web_object = {
       renderbuffer,
     );
   },
+  read_pixels(framebuffer_index, width, height, pixel_format, type_, data_ptr, data_length) {
+    let framebuffer = self.kwasm_get_object(framebuffer_index);
+    gl.bindFramebuffer(gl.FRAMEBUFFER, framebuffer);
+    let data = type_ == gl.FLOAT ?
+      new Float32Array(self.kwasm_memory.buffer, data_ptr, data_length / 4) :
+      new Uint8Array(self.kwasm_memory.buffer, data_ptr, data_length);
+    gl.pixelStorei(gl.PACK_ALIGNMENT, 1);
+    gl.readPixels(0, 0, width, height, pixel_format, type_, data);
+    gl.bindFramebuffer(gl.FRAMEBUFFER, null);
+  },
   create_framebuffer() {
     return gl.createFramebuffer();
   },
@@ -290,6 +345,12 @@ var gl_web_object = {
     let u32_offset = 0;
     //let temp_framebuffer = null;
 
+    // Tracked so SetStencilReference can reuse the rest of the pipeline's stencil state.
+    let stencil_enabled = 0;
+    let stencil_func = gl.ALWAYS;
+    let stencil_read_mask = 0xFF;
+    let stencil_write_mask = 0xFF;
+
     for (i = 0; i < length; i++) {
       //console.log("COMMAND " + commands[i]);
       switch (commands[i]) {
@@ -300,7 +361,14 @@ var gl_web_object = {
           let b = f32_data[f32_offset++];
           let a = f32_data[f32_offset++];
           gl.clearColor(r, g, b, a);
-          gl.clear(gl.COLOR_BUFFER_BIT | gl.DEPTH_BUFFER_BIT);
+
+          // The stencil write mask also applies to clears.
+          gl.stencilMask(0xFF);
+          gl.clearStencil(0);
+          gl.clear(gl.COLOR_BUFFER_BIT | gl.DEPTH_BUFFER_BIT | gl.STENCIL_BUFFER_BIT);
+          if (stencil_enabled !== 0) {
+            gl.stencilMask(stencil_write_mask);
+          }
           break;
         }
         case 1: {
@@ -363,6 +431,14 @@ var gl_web_object = {
           let culling = u32_data[u32_offset++];
           let source_blend_factor = u32_data[u32_offset++];
           let destination_blend_factor = u32_data[u32_offset++];
+          stencil_enabled = u32_data[u32_offset++];
+          stencil_func = u32_data[u32_offset++];
+          let stencil_reference = u32_data[u32_offset++];
+          stencil_read_mask = u32_data[u32_offset++];
+          stencil_write_mask = u32_data[u32_offset++];
+          let stencil_fail = u32_data[u32_offset++];
+          let stencil_depth_fail = u32_data[u32_offset++];
+          let stencil_pass = u32_data[u32_offset++];
           let depth_clear_value = f32_data[f32_offset++];
 
           let program = kwasm_get_object(program_index);
@@ -384,6 +460,15 @@ var gl_web_object = {
             gl.blendFunc(source_blend_factor, destination_blend_factor);
           }
 
+          if (stencil_enabled === 0) {
+            gl.disable(gl.STENCIL_TEST);
+          } else {
+            gl.enable(gl.STENCIL_TEST);
+            gl.stencilFunc(stencil_func, stencil_reference, stencil_read_mask);
+            gl.stencilOp(stencil_fail, stencil_depth_fail, stencil_pass);
+            gl.stencilMask(stencil_write_mask);
+          }
+
           gl.clearDepth(depth_clear_value);
           break;
         }
@@ -397,7 +482,11 @@ var gl_web_object = {
           let buffer = kwasm_get_object(buffer_index);
 
           if (buffer === null) {
-            gl.disableVertexAttribArray(attribute_index);
+            // Matrices span multiple attribute slots which all need to be disabled.
+            let len = Math.max(number_of_components / 4, 1);
+            for (let i = 0; i < len; i++) {
+              gl.disableVertexAttribArray(attribute_index + i);
+            }
           } else {
             gl.bindBuffer(gl.ARRAY_BUFFER, buffer);
 
@@ -604,6 +693,8 @@ var gl_web_object = {
           let framebuffer = kwasm_get_object(framebuffer_index);
           gl.bindFramebuffer(gl.DRAW_FRAMEBUFFER, framebuffer)
           gl.blitFramebuffer(source_x, source_y, source_w, source_h, dest_x, dest_y, dest_w, dest_h, gl.COLOR_BUFFER_BIT, gl.LINEAR);
+          // Depth must be blitted with nearest filtering.
+          gl.blitFramebuffer(source_x, source_y, source_w, source_h, dest_x, dest_y, dest_w, dest_h, gl.DEPTH_BUFFER_BIT, gl.NEAREST);
           gl.invalidateFramebuffer(gl.READ_FRAMEBUFFER, [gl.COLOR_ATTACHMENT0, gl.DEPTH_ATTACHMENT]);
           break;
         }
@@ -623,7 +714,15 @@ var gl_web_object = {
             offset,
             len,
           );
-
+          break;
+        }
+        case 19: {
+          // SetStencilReference
+          let stencil_reference = u32_data[u32_offset++];
+          if (stencil_enabled !== 0) {
+            gl.stencilFunc(stencil_func, stencil_reference, stencil_read_mask);
+          }
+          break;
         }
       }
     }
@@ -0,0 +1,185 @@
+use kmath::numeric_traits::NumericFloat;
+use kmath::*;
+
+/// Rounds down to a grid index. Negative values become 0.
+#[doc(hidden)]
+pub trait FloorToIndex {
+    fn floor_to_index(self) -> usize;
+}
+
+impl FloorToIndex for f32 {
+    fn floor_to_index(self) -> usize {
+        self.floor() as usize
+    }
+}
+
+impl FloorToIndex for f64 {
+    fn floor_to_index(self) -> usize {
+        self.floor() as usize
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct HeightfieldHandle(pub(crate) usize);
+
+/// A grid of heights, like terrain, that `RigidBody`s rest on.
+/// Heightfields never move and can't be rotated.
+///
+/// Each cell of the grid is split into two triangles along the diagonal
+/// from its lowest column and row to its highest.
+#[derive(Clone, Debug)]
+pub struct Heightfield<F: NumericFloat> {
+    columns: usize,
+    rows: usize,
+    /// Row-major heights with `columns` heights per row.
+    /// Heights are relative to `origin` and rows run along +Z.
+    pub heights: Vec<F>,
+    /// Where the height at the first column and row would be if it were 0.
+    pub origin: Vector<F, 3>,
+    /// The distance between neighboring columns along X and neighboring rows along Z.
+    pub cell_size: Vector<F, 2>,
+}
+
+impl<F: NumericFloat + FloorToIndex> Heightfield<F> {
+    pub fn new(
+        columns: usize,
+        rows: usize,
+        heights: Vec<F>,
+        origin: Vector<F, 3>,
+        cell_size: Vector<F, 2>,
+    ) -> Self {
+        assert!(
+            columns >= 2 && rows >= 2,
+            "A Heightfield needs at least 2 columns and 2 rows"
+        );
+        assert_eq!(heights.len(), columns * rows);
+        Self {
+            columns,
+            rows,
+            heights,
+            origin,
+            cell_size,
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    fn get(&self, column: usize, row: usize) -> F {
+        self.heights[row * self.columns + column]
+    }
+
+    /// Finds the cell containing a world position and how far across the cell it is.
+    fn cell(&self, x: F, z: F) -> Option<(usize, usize, F, F)> {
+        let column = (x - self.origin.x) / self.cell_size.x;
+        let row = (z - self.origin.z) / self.cell_size.y;
+        let last_column = F::from_f32((self.columns - 1) as f32);
+        let last_row = F::from_f32((self.rows - 1) as f32);
+        // Written so that NaNs are outside the heightfield.
+        if !(column >= F::ZERO && column <= last_column && row >= F::ZERO && row <= last_row) {
+            return None;
+        }
+
+        // The last column and row belong to the cells before them.
+        let cell_column = column.floor_to_index().min(self.columns - 2);
+        let cell_row = row.floor_to_index().min(self.rows - 2);
+        Some((
+            cell_column,
+            cell_row,
+            column - F::from_f32(cell_column as f32),
+            row - F::from_f32(cell_row as f32),
+        ))
+    }
+
+    /// The height of the surface in world space at a world position's X and Z.
+    /// Returns `None` outside the heightfield.
+    pub fn height_at(&self, x: F, z: F) -> Option<F> {
+        let (column, row, x, z) = self.cell(x, z)?;
+        let h00 = self.get(column, row);
+        let h10 = self.get(column + 1, row);
+        let h01 = self.get(column, row + 1);
+        let h11 = self.get(column + 1, row + 1);
+
+        let height = if z >= x {
+            h00 + (h01 - h00) * z + (h11 - h01) * x
+        } else {
+            h00 + (h10 - h00) * x + (h11 - h10) * z
+        };
+        Some(self.origin.y + height)
+    }
+
+    /// The normal of the triangle at a world position's X and Z.
+    /// Returns `None` outside the heightfield.
+    pub fn normal_at(&self, x: F, z: F) -> Option<Vector<F, 3>> {
+        let (column, row, x, z) = self.cell(x, z)?;
+        let h00 = self.get(column, row);
+        let h10 = self.get(column + 1, row);
+        let h01 = self.get(column, row + 1);
+        let h11 = self.get(column + 1, row + 1);
+
+        // How much the height changes across one cell of the triangle.
+        let (x_change, z_change) = if z >= x {
+            (h11 - h01, h01 - h00)
+        } else {
+            (h10 - h00, h11 - h10)
+        };
+        Some(
+            Vector::<F, 3>::new(
+                -x_change * self.cell_size.y,
+                self.cell_size.x * self.cell_size.y,
+                -z_change * self.cell_size.x,
+            )
+            .normalized(),
+        )
+    }
+}
+
+#[cfg(test)]
+fn slope() -> Heightfield<f32> {
+    // Rises by 1.0 per column and by 2.0 per row.
+    Heightfield::new(
+        3,
+        3,
+        vec![0., 1., 2., 2., 3., 4., 4., 5., 6.],
+        Vec3::new(-1., 10., -1.),
+        Vec2::new(1., 1.),
+    )
+}
+
+#[test]
+fn heightfield_height_at() {
+    let heightfield = slope();
+    assert_eq!(heightfield.height_at(-1., -1.), Some(10.));
+    assert_eq!(heightfield.height_at(1., 1.), Some(16.));
+    assert_eq!(heightfield.height_at(0.5, -0.5), Some(12.5));
+    assert_eq!(heightfield.height_at(1.5, 0.), None);
+    assert_eq!(heightfield.height_at(0., f32::NAN), None);
+}
+
+#[test]
+fn heightfield_triangles() {
+    // A single cell with one raised corner is only raised in the triangle containing that corner.
+    let heightfield = Heightfield::new(2, 2, vec![0., 1., 0., 0.], Vec3::ZERO, Vec2::new(2., 2.));
+    assert_eq!(heightfield.height_at(1.5, 0.5), Some(0.5));
+    assert_eq!(heightfield.height_at(0.5, 1.5), Some(0.));
+    assert_eq!(heightfield.normal_at(0.5, 1.5), Some(Vec3::Y));
+
+    let normal = heightfield.normal_at(1.5, 0.5).unwrap();
+    assert!(normal.y > 0.0 && normal.x < 0.0 && normal.z > 0.0);
+    assert!((normal.length() - 1.0).abs() < 0.0001);
+}
+
+#[test]
+fn heightfield_normal_at() {
+    let heightfield = slope();
+    let expected = Vec3::new(-1., 1., -2.).normalized();
+    for (x, z) in [(-0.5, -0.25), (-0.25, -0.5), (0.9, 0.1)] {
+        let normal = heightfield.normal_at(x, z).unwrap();
+        assert!((normal - expected).length() < 0.0001);
+    }
+}
@@ -2,6 +2,9 @@ pub mod collision;
 
 mod convex_mesh_collider;
 
+mod heightfield;
+pub use heightfield::*;
+
 use std::fmt::Debug;
 
 use collision::{GJKEpsilon, VeryLargeNumber};
@@ -32,6 +35,7 @@ pub struct PhysicsWorld<F: NumericFloat> {
     rigid_bodies: Vec<RigidBodyData<F>>,
     colliders: Vec<ColliderData<F>>,
     pub collider_meshes: Vec<MeshData<F>>,
+    heightfields: Vec<Heightfield<F>>,
     /// For debug purposes, a collision occurred in the last frame.
     pub collision_occurred: bool,
     pub contact_points: Vec<Vector<F, 3>>,
@@ -88,8 +92,15 @@ impl PhysicsDefaults for f64 {
     const TIME_STEP: Self = 1.0 / 60.0;
 }
 
-impl<F: NumericFloat + PhysicsDefaults + Debug + GJKEpsilon + VeryLargeNumber + OneDividedBy12>
-    PhysicsWorld<F>
+impl<
+        F: NumericFloat
+            + PhysicsDefaults
+            + Debug
+            + GJKEpsilon
+            + VeryLargeNumber
+            + OneDividedBy12
+            + FloorToIndex,
+    > PhysicsWorld<F>
 {
     pub fn new() -> Self {
         let mut gravity = Vector::<F, 3>::ZERO;
@@ -101,6 +112,7 @@ impl<F: NumericFloat + PhysicsDefaults + Debug + GJKEpsilon + VeryLargeNumber +
             rigid_bodies: Vec::new(),
             colliders: Vec::new(),
             collider_meshes: Vec::new(),
+            heightfields: Vec::new(),
             collision_occurred: false,
             contact_points: Vec::new(),
         }
@@ -442,6 +454,57 @@ impl<F: NumericFloat + PhysicsDefaults + Debug + GJKEpsilon + VeryLargeNumber +
                 }
             }
         }
+
+        self.collide_with_heightfields();
+    }
+
+    /// Pushes `RigidBody`s out of heightfields and stops them moving into the surface.
+    /// Only the vertices of a `Collider`'s mesh are checked,
+    /// so a heightfield's peaks can poke through large faces.
+    fn collide_with_heightfields(&mut self) {
+        for collider in &self.colliders {
+            let rigid_body = match collider.attached_rigid_body {
+                Some(rigid_body_handle) => &mut self.rigid_bodies[rigid_body_handle.0],
+                None => continue,
+            };
+            if rigid_body.mass == F::INFINITY {
+                continue;
+            }
+
+            let mesh = &self.collider_meshes[collider.mesh_index.0];
+            for heightfield in &self.heightfields {
+                // Ignore scale for now, like collisions between `Collider`s do.
+                let to_world = Matrix::<F, 4, 4>::from_translation_rotation_scale(
+                    rigid_body.position,
+                    rigid_body.rotation,
+                    Vector::<F, 3>::ONE,
+                );
+
+                // Find the vertex that's deepest below the surface.
+                let mut deepest: Option<(F, Vector<F, 3>)> = None;
+                for &position in &mesh.positions {
+                    let position = to_world.transform_point(position);
+                    if let Some(height) = heightfield.height_at(position.x, position.z) {
+                        let depth = height - position.y;
+                        if depth > F::ZERO && deepest.is_none_or(|(deepest, _)| depth > deepest) {
+                            let normal = heightfield.normal_at(position.x, position.z).unwrap();
+                            deepest = Some((depth, normal));
+                        }
+                    }
+                }
+
+                if let Some((depth, normal)) = deepest {
+                    self.collision_occurred = true;
+                    rigid_body.position.y = rigid_body.position.y + depth;
+
+                    let velocity_into_surface = rigid_body.velocity.dot(normal);
+                    if velocity_into_surface < F::ZERO {
+                        rigid_body.velocity -=
+                            normal * velocity_into_surface * (F::ONE + rigid_body.bounciness);
+                    }
+                }
+            }
+        }
     }
 
     /// Position is relative to the `RigidBody`
@@ -567,6 +630,23 @@ impl<F: NumericFloat + PhysicsDefaults + Debug + GJKEpsilon + VeryLargeNumber +
     pub fn get_mesh_data(&self, mesh_data_handle: &MeshDataHandle) -> &MeshData<F> {
         self.collider_meshes.get(mesh_data_handle.0).unwrap()
     }
+
+    /// Adds a [Heightfield] that every `RigidBody` with a `Collider` collides with.
+    pub fn add_heightfield(&mut self, heightfield: Heightfield<F>) -> HeightfieldHandle {
+        self.heightfields.push(heightfield);
+        HeightfieldHandle(self.heightfields.len() - 1)
+    }
+
+    pub fn get_heightfield(&self, heightfield_handle: HeightfieldHandle) -> &Heightfield<F> {
+        &self.heightfields[heightfield_handle.0]
+    }
+
+    pub fn get_heightfield_mut(
+        &mut self,
+        heightfield_handle: HeightfieldHandle,
+    ) -> &mut Heightfield<F> {
+        &mut self.heightfields[heightfield_handle.0]
+    }
 }
 
 pub trait OneDividedBy12 {
@@ -645,3 +725,55 @@ fn index_twice<T>(slice: &mut [T], first: usize, second: usize) -> (&mut T, &mut
         (&mut b[0], &mut a[second])
     }
 }
+
+#[test]
+fn rest_on_heightfield() {
+    let mut physics_world = PhysicsWorld::<f32>::new();
+    physics_world.add_heightfield(Heightfield::new(
+        2,
+        2,
+        vec![1., 1., 1., 1.],
+        Vec3::new(-5., 0., -5.),
+        Vec2::new(10., 10.),
+    ));
+
+    let cube = [
+        Vec3::new(-0.5, -0.5, -0.5),
+        Vec3::new(0.5, -0.5, -0.5),
+        Vec3::new(0.5, -0.5, 0.5),
+        Vec3::new(-0.5, -0.5, 0.5),
+        Vec3::new(-0.5, 0.5, -0.5),
+        Vec3::new(0.5, 0.5, -0.5),
+        Vec3::new(0.5, 0.5, 0.5),
+        Vec3::new(-0.5, 0.5, 0.5),
+    ];
+    let mesh_index = physics_world.add_mesh_data(&cube, &[], &[]);
+    let associated_entity = AssociatedEntity {
+        index: 0,
+        generation: 0,
+    };
+    let rigid_body = physics_world.new_rigid_body(RigidBodyData {
+        mass: 1.0,
+        position: Vec3::new(0., 3., 0.),
+        rotation: Quaternion::IDENTITY,
+        velocity: Vec3::ZERO,
+        angular_velocity: Vec3::ZERO,
+        bounciness: 0.0,
+        gravity_multiplier: 1.0,
+        associated_entity: associated_entity.clone(),
+    });
+    physics_world.new_collider(ColliderData {
+        offset_from_rigid_body: Vec3::ZERO,
+        attached_rigid_body: Some(rigid_body),
+        mesh_index,
+        associated_entity,
+    });
+
+    for _ in 0..300 {
+        physics_world.update();
+    }
+
+    let rigid_body = physics_world.get_rigid_body_data(rigid_body);
+    assert!((rigid_body.position.y - 1.5).abs() < 0.01);
+    assert!(rigid_body.velocity.y <= 0.0 && rigid_body.velocity.y > -0.2);
+}
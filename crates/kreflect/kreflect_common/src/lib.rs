@@ -372,6 +372,7 @@ impl<'a> Fields<'a> {
 // A `Struct` declaration
 #[derive(Debug)]
 pub struct Struct<'a> {
+    pub attributes: Vec<Attribute<'a>>,
     pub name: Cow<'a, str>,
     pub visibility: Visibility,
     pub fields: Fields<'a>,
@@ -402,6 +403,7 @@ pub struct EnumVariant<'a> {
 
 #[derive(Debug)]
 pub struct Enum<'a> {
+    pub attributes: Vec<Attribute<'a>>,
     pub name: Cow<'a, str>,
     pub visibility: Visibility,
     pub variants: Vec<EnumVariant<'a>>,
@@ -1023,6 +1025,7 @@ impl<'a> Parser<'a> {
 
         Some(match self.peek()? {
             Token::SemiColon => Struct {
+                attributes: Vec::new(),
                 name,
                 visibility,
                 fields: Fields::Unit,
@@ -1031,6 +1034,7 @@ impl<'a> Parser<'a> {
             _ => {
                 let fields = self.fields().expect("Could not parse fields");
                 Struct {
+                    attributes: Vec::new(),
                     name,
                     visibility,
                     fields,
@@ -1051,6 +1055,7 @@ impl<'a> Parser<'a> {
         let variants = self.enum_variants()?;
 
         Some(Enum {
+            attributes: Vec::new(),
             name,
             variants,
             visibility,
@@ -1100,12 +1105,20 @@ impl<'a> Parser<'a> {
 
     // Parses a top-level item declaration
     pub fn parse(&mut self) -> Option<Value<'a>> {
-        // Skip other attributes for now.
-        while self.attribute().is_some() {}
+        let mut attributes = Vec::new();
+        while let Some(attribute) = self.attribute() {
+            attributes.push(attribute)
+        }
         let visibility = self.visibility().unwrap();
         Some(match self.peek()? {
-            Token::Struct => Value::Struct(self._struct(visibility)?),
-            Token::Enum => Value::Enum(self._enum(visibility)?),
+            Token::Struct => Value::Struct(Struct {
+                attributes,
+                ..self._struct(visibility)?
+            }),
+            Token::Enum => Value::Enum(Enum {
+                attributes,
+                ..self._enum(visibility)?
+            }),
             Token::Fn => Value::Function(self.function(visibility)?),
             token => todo!(
                 "Kreflect cannot parse non-struct non-enums yet: {:?}",
@@ -687,6 +687,106 @@ pub fn run_tasks_unless_there_are_workers() {
     })
 }
 
+/// Returns true if there are other worker threads that tasks can be sent to.
+pub fn has_other_workers() -> bool {
+    WORKER.with(|w| !w.borrow().other_task_queues.is_empty())
+}
+
+/// Spawns tasks that can borrow from the surrounding scope.
+/// All tasks spawned with the [Scope] are complete when this returns.
+///
+/// This thread runs tasks while waiting, so this works even if there are no other workers.
+/// If a task panics the panic is resumed on this thread once all tasks have completed.
+pub fn scope<'a, R>(f: impl FnOnce(&mut Scope<'a>) -> R) -> R {
+    let mut scope = Scope {
+        state: Arc::new(ScopeState {
+            remaining: Mutex::new(0),
+            completed: Condvar::new(),
+            panic: Mutex::new(None),
+        }),
+        phantom: std::marker::PhantomData,
+    };
+    let result = f(&mut scope);
+    scope.wait();
+    if let Some(panic) = scope.state.panic.lock().unwrap().take() {
+        std::panic::resume_unwind(panic);
+    }
+    result
+}
+
+/// Shared by a [Scope] and its tasks so the [Scope] can wait for them to complete.
+struct ScopeState {
+    remaining: Mutex<usize>,
+    completed: Condvar,
+    panic: Mutex<Option<Box<dyn std::any::Any + Send>>>,
+}
+
+/// Created by [scope].
+pub struct Scope<'a> {
+    state: Arc<ScopeState>,
+    // Invariant over 'a so that tasks can't borrow anything that lives shorter than the scope.
+    phantom: std::marker::PhantomData<&'a mut &'a ()>,
+}
+
+impl<'a> Scope<'a> {
+    /// Spawn a task that can run on any thread.
+    pub fn spawn(&mut self, task: impl FnOnce() + Send + 'a) {
+        let task: Box<dyn FnOnce() + Send + 'a> = Box::new(task);
+
+        // Safety: Tasks are only ever run as `'static` so the lifetime is erased here,
+        // which is sound as long as no task outlives the borrows it holds:
+        // - A [Scope] is only created by [scope], which waits for every task before returning.
+        // - A [Scope] also waits when dropped, in case `f` panics and [scope] unwinds.
+        // - `f` only gets `&mut Scope`, so it can't move or leak the [Scope] to skip waiting.
+        // - `remaining` is only decremented after `task` has been called, which consumes it,
+        //   so nothing borrowed by the task is used after the [Scope] stops waiting.
+        let task: Box<dyn FnOnce() + Send + 'static> = unsafe { std::mem::transmute(task) };
+
+        *self.state.remaining.lock().unwrap() += 1;
+        let state = self.state.clone();
+
+        // Panics are caught so that a panicking task doesn't take down a worker
+        // and leave the [Scope] waiting forever.
+        spawn(async move {
+            if let Err(panic) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(task)) {
+                state.panic.lock().unwrap().get_or_insert(panic);
+            }
+            *state.remaining.lock().unwrap() -= 1;
+            state.completed.notify_all();
+        })
+        .run();
+    }
+
+    fn wait(&mut self) {
+        loop {
+            // Tasks are enqueued on this thread so run them here in case no other worker takes them.
+            run_current_thread_tasks();
+
+            let remaining = self.state.remaining.lock().unwrap();
+            if *remaining == 0 {
+                break;
+            }
+
+            // Any remaining tasks are running on other workers, so sleep until one completes.
+            #[cfg(not(target_arch = "wasm32"))]
+            drop(self.state.completed.wait(remaining).unwrap());
+
+            // The main thread isn't allowed to block on Wasm.
+            #[cfg(target_arch = "wasm32")]
+            {
+                drop(remaining);
+                std::hint::spin_loop();
+            }
+        }
+    }
+}
+
+impl<'a> Drop for Scope<'a> {
+    fn drop(&mut self) {
+        self.wait();
+    }
+}
+
 // This needs to store the TaskQueue to push to and the parent task to wake.
 // In what scenarios would a waker be cloned?
 // Does the parent task need to be stored in an option that's taken when waking the parent?
@@ -41,7 +41,7 @@ fn main() {
         move |event: Event, world: &mut World| {
             match event {
                 Event::FixedUpdate => {
-                    let mut commands = Commands::new();
+                    let mut commands = CommandQueue::new();
 
                     (|input: &Input, cameras: Query<(&Camera, &GlobalTransform)>| {
                         if input.pointer_button_down(PointerButton::Primary) {
@@ -0,0 +1,66 @@
+use koi::*;
+
+fn main() {
+    App::new().setup_and_run(|world: &mut World| {
+        // Spawn a camera and make it look towards the origin.
+        world.spawn((
+            Transform::new()
+                .with_position(Vec3::new(0.0, 3.0, 6.0))
+                .looking_at(Vec3::Y, Vec3::Y),
+            Camera::new(),
+            CameraControls::new(),
+        ));
+
+        // A floor for the soft particles to fade into.
+        world.spawn((
+            Transform::new().with_scale(Vec3::fill(10.0)),
+            Mesh::PLANE,
+            Material::UNLIT,
+            Color::new(0.2, 0.2, 0.2, 1.0),
+        ));
+
+        // A fire-like fountain of additive particles.
+        let mut fire = ParticleEmitter::new();
+        fire.rate = 200.0;
+        fire.shape = EmitterShape::Cone {
+            angle: 0.3,
+            radius: 0.3,
+        };
+        fire.size = Curve::linear(0.4, 0.0);
+        fire.color = Curve::new(vec![
+            (0.0, Color::new(1.0, 0.8, 0.2, 1.0)),
+            (0.5, Color::new(1.0, 0.2, 0.0, 0.8)),
+            (1.0, Color::new(0.2, 0.0, 0.0, 0.0)),
+        ]);
+        fire.noise_strength = 4.0;
+        fire.additive = true;
+        world.spawn((Transform::new().with_position(-Vec3::X), fire));
+
+        // Sparks that burst out and fall.
+        let mut sparks = ParticleEmitter::new();
+        sparks.rate = 0.0;
+        sparks.shape = EmitterShape::Sphere { radius: 0.1 };
+        sparks.speed = 3.0..5.0;
+        sparks.gravity = Vec3::new(0.0, -9.8, 0.0);
+        sparks.drag = 1.0;
+        sparks.size = Curve::constant(0.05);
+        sparks.color = Curve::linear(Color::WHITE, Color::new(1.0, 0.5, 0.0, 0.0));
+        let sparks = world.spawn((
+            Transform::new().with_position(Vec3::new(1.0, 1.0, 0.0)),
+            sparks,
+        ));
+
+        move |event: Event, world: &mut World| {
+            if let Event::FixedUpdate = event {
+                // Press space to burst sparks.
+                if world.get_singleton::<Input>().key_down(Key::Space) {
+                    world
+                        .get_component_mut::<ParticleEmitter>(sparks)
+                        .unwrap()
+                        .burst(100);
+                }
+            }
+            false
+        }
+    });
+}
@@ -1,27 +1,8 @@
 use koi::*;
 
-#[derive(Component, Clone)]
-struct Character {
-    running: bool,
-    current_sprite: usize,
-    sprites: [Sprite; 2],
-    sprite_timer: f32,
-}
-
 #[derive(Component, Clone)]
 struct Controlled;
 
-impl Character {
-    pub fn new(sprites: [Sprite; 2]) -> Self {
-        Self {
-            running: false,
-            current_sprite: 0,
-            sprites,
-            sprite_timer: 0.,
-        }
-    }
-}
-
 fn main() {
     App::new().setup_and_run(|world: &mut World| {
         // Spawn a camera
@@ -63,10 +44,9 @@ fn main() {
         let _snow_man_sprite = tiles_sprite_map.get_sprite(5, 7);
         let middle_platform_sprite = tiles_sprite_map.get_sprite(2, 0);
 
-        let character_sprites = [
-            character_sprite_map.get_sprite(0, 0),
-            character_sprite_map.get_sprite(1, 0),
-        ];
+        // The character steps between its first two frames while running.
+        let run_animation =
+            SpriteAnimation::from_tiles(character_sprite_map.clone(), &[(0, 0), (1, 0)], 1.0 / 0.3);
 
         for i in 0..5 {
             world.spawn(sprite_bundle(
@@ -77,63 +57,45 @@ fn main() {
 
         world.spawn((
             Transform::new().with_position(Vec3::X * 4.0 as f32),
-            Mesh::VERTICAL_QUAD,
-            Material::UNLIT_TRANSPARENT,
-            character_sprites[0].clone(),
-            Character::new(character_sprites),
+            // Draw the character in front of the tiles.
+            character_sprite_map.get_sprite(0, 0).with_sorting(1, 0),
+            run_animation,
             Controlled,
         ));
 
         |event, world| {
             match event {
                 Event::FixedUpdate => {
-                    (|time: &Time, mut characters: Query<(&mut Character, &mut Sprite)>| {
-                        // Animate sprites
-                        for (character, sprite) in characters.iter_mut() {
-                            if character.running {
-                                let animate_speed = 0.3;
-                                character.sprite_timer += time.fixed_time_step as f32;
-                                if character.sprite_timer > animate_speed {
-                                    character.current_sprite += 1;
-                                    if character.current_sprite >= character.sprites.len() {
-                                        character.current_sprite = 0;
-                                    }
-                                    *sprite = character.sprites[character.current_sprite].clone();
-                                    character.sprite_timer -= animate_speed;
-                                }
-                            } else {
-                                character.sprite_timer = 0.5;
-                                *sprite = character.sprites[1].clone();
-                            }
-                        }
-                    })
-                    .run(world);
-
                     // Move controlled characters
                     (|time: &Time,
                       input: &mut Input,
                       mut characters: Query<(
                         &mut Transform,
-                        Option<&mut Character>,
+                        &mut Sprite,
+                        Option<&mut SpriteAnimation>,
                         &Controlled,
                     )>| {
                         let speed = 2.0;
-                        for (transform, character, _) in characters.iter_mut() {
+                        for (transform, sprite, animation, _) in characters.iter_mut() {
                             let mut input_pressed = false;
                             if input.key(Key::Left) {
                                 transform.position.x -= speed * time.fixed_time_step as f32;
-                                transform.rotation =
-                                    Quat::from_angle_axis(0.0 * std::f32::consts::TAU, Vec3::Y);
+                                sprite.flip_x = false;
                                 input_pressed = true;
                             }
                             if input.key(Key::Right) {
                                 transform.position.x += speed * time.fixed_time_step as f32;
-                                transform.rotation =
-                                    Quat::from_angle_axis(0.5 * std::f32::consts::TAU, Vec3::Y);
+                                sprite.flip_x = true;
                                 input_pressed = true;
                             }
-                            if let Some(character) = character {
-                                character.running = input_pressed;
+                            if let Some(animation) = animation {
+                                // Stand on the second frame when not running.
+                                if input_pressed && !animation.playing {
+                                    animation.restart();
+                                } else if !input_pressed {
+                                    animation.playing = false;
+                                    animation.set_frame(1);
+                                }
                             }
                         }
                     })
@@ -146,14 +108,6 @@ fn main() {
     });
 }
 
-fn sprite_bundle(
-    transform: Transform,
-    sprite: Sprite,
-) -> (Transform, Handle<Mesh>, Handle<Material>, Sprite) {
-    (
-        transform,
-        Mesh::VERTICAL_QUAD,
-        Material::UNLIT_TRANSPARENT,
-        sprite,
-    )
+fn sprite_bundle(transform: Transform, sprite: Sprite) -> (Transform, Sprite) {
+    (transform, sprite)
 }
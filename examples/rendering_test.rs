@@ -37,7 +37,7 @@ fn main() {
         // Up is more metallic
         // Right is more more rough
         let spacing = 2.0;
-        let mut commands = Commands::new();
+        let mut commands = CommandQueue::new();
 
         (|materials: &mut Assets<Material>| {
             let rows = 6;
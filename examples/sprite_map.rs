@@ -25,15 +25,35 @@ fn main() {
         // Enter the tile of the sprite.
         let snow_man_sprite = sprite_map.get_sprite(5, 7);
 
+        // Sprites are batched into one draw per texture, so all of these are drawn together.
         for i in 0..5 {
             world.spawn((
-                Transform::new().with_position(Vec3::X * i as f32),
-                Mesh::VERTICAL_QUAD,
-                Material::UNLIT,
-                snow_man_sprite.clone(),
+                Transform::new().with_position(Vec3::X * i as f32 * 0.6),
+                // Overlapping snow men are drawn from left to right.
+                snow_man_sprite.clone().with_sorting(0, i),
             ));
         }
 
+        // A stretched platform behind the snow men.
+        // Nine-slicing keeps the edges of the tile from stretching.
+        let tile_border = Vec2::new(4.0 / 398.0, 4.0 / 178.0);
+        world.spawn((
+            Transform::new().with_position(Vec3::new(1.2, -0.5, 0.0)),
+            sprite_map
+                .get_sprite(2, 0)
+                .with_size(Vec2::new(4.0, 1.0))
+                .with_nine_slice(NineSlice::uniform(tile_border, 0.25))
+                .with_sorting(-1, 0),
+        ));
+
+        // A flipped and tinted snow man in front of the others.
+        let mut blue_snow_man = snow_man_sprite.with_tint(Color::BLUE.with_lightness(0.8));
+        blue_snow_man.flip_x = true;
+        world.spawn((
+            Transform::new().with_position(Vec3::new(1.2, 1.0, 0.0)),
+            blue_snow_man.with_sorting(1, 0),
+        ));
+
         |_, _| false
     });
 }
@@ -0,0 +1,85 @@
+use koi::*;
+
+#[derive(Component, Clone)]
+struct Controlled;
+
+fn main() {
+    App::new().setup_and_run(|world: &mut World| {
+        spawn_skybox(world, "assets/venice_sunset.hdr");
+
+        world.spawn((
+            Light::new(LightMode::Directional, Color::WHITE, 1.0),
+            Transform::new()
+                .with_position(Vec3::new(0.0, 20.0, 10.0))
+                .looking_at(Vec3::ZERO, Vec3::Y),
+            ShadowCaster::new(),
+        ));
+
+        world.spawn((
+            Transform::new()
+                .with_position(Vec3::new(0.0, 30.0, 60.0))
+                .looking_at(Vec3::ZERO, Vec3::Y),
+            Camera::new(),
+            CameraControls::new(),
+        ));
+
+        // Rolling hills that rise towards the back.
+        let heightmap = Heightmap::from_fn(257, 257, |x, z| {
+            let hills = (x * 12.0).sin() * (z * 9.0).cos() * 0.15 + 0.15;
+            hills + (1.0 - z) * (1.0 - z) * 0.7
+        });
+        world.spawn((
+            Transform::new(),
+            Terrain::new(
+                heightmap,
+                Vec3::new(200.0, 30.0, 200.0),
+                Material::PHYSICALLY_BASED,
+            ),
+        ));
+
+        // A sphere that the arrow keys move across the terrain.
+        world.spawn((
+            Transform::new(),
+            Mesh::SPHERE,
+            Material::PHYSICALLY_BASED,
+            Color::ORANGE,
+            Controlled,
+        ));
+
+        move |event: Event, world: &mut World| {
+            if let Event::FixedUpdate = event {
+                (|input: &Input,
+                  terrains: Query<(&Terrain, &GlobalTransform)>,
+                  mut controlled: Query<(&mut Transform, &Controlled)>| {
+                    for (transform, _) in &mut controlled {
+                        if input.key(Key::Left) {
+                            transform.position -= Vec3::X * 0.5;
+                        }
+                        if input.key(Key::Right) {
+                            transform.position += Vec3::X * 0.5;
+                        }
+                        if input.key(Key::Up) {
+                            transform.position -= Vec3::Z * 0.5;
+                        }
+                        if input.key(Key::Down) {
+                            transform.position += Vec3::Z * 0.5;
+                        }
+
+                        // Rest the sphere on the ground.
+                        for (terrain, global_transform) in &terrains {
+                            if let Some((surface, normal)) =
+                                terrain.surface_at(global_transform, transform.position)
+                            {
+                                transform.position = surface + normal * 0.5;
+                            }
+                        }
+                    }
+                })
+                .run(world)
+            }
+
+            // Do not consume the event and allow other systems to respond to it.
+            false
+        }
+    });
+}
@@ -9,6 +9,10 @@ pub trait AssetTrait: 'static + Send + Sync {
 pub trait AssetLoaderTrait<T: AssetTrait> {
     type Options;
     fn load_with_options(&mut self, path: &str, handle: Handle<T>, options: Self::Options);
+    /// Loads `path` again with the options it was first loaded with.
+    /// The reloaded asset should replace the current one with [Assets::replace].
+    /// Loaders that don't support reloading do nothing.
+    fn reload(&mut self, _path: &str, _handle: Handle<T>) {}
     fn load_with_data_and_options_and_extension(
         &mut self,
         _data: Vec<u8>,
@@ -85,6 +89,10 @@ impl<T> IndirectionStorage<T> {
         self.indirect_indices[indirect_index] = self.items.len() - 1;
     }
 
+    fn replace(&mut self, indirect_index: usize, item: T) -> T {
+        std::mem::replace(self.get_mut(indirect_index), item)
+    }
+
     fn get(&self, indirect_index: usize) -> &T {
         &self.items[self.indirect_indices[indirect_index]].item
     }
@@ -342,6 +350,30 @@ impl<T: AssetTrait> Assets<T> {
             .replace_placeholder(handle.indirection_index, asset)
     }
 
+    /// Points a `Handle` towards a new asset, even if it was already loaded.
+    /// Returns the previous asset, or `None` if the `Handle` pointed at the placeholder.
+    /// Everything holding the `Handle` will use the new asset.
+    pub fn replace(&mut self, handle: &Handle<T>, asset: T) -> Option<T> {
+        if self.is_placeholder(handle) {
+            self.replace_placeholder(handle, asset);
+            None
+        } else {
+            Some(
+                self.indirection_storage
+                    .replace(handle.indirection_index, asset),
+            )
+        }
+    }
+
+    /// Each path assets have been loaded from along with a [Handle] to that asset.
+    /// Paths whose assets have been dropped are skipped.
+    pub fn loaded_paths(&self) -> Vec<(String, Handle<T>)> {
+        self.path_to_handle
+            .iter()
+            .filter_map(|(path, weak_handle)| Some((path.clone(), weak_handle.upgrade()?)))
+            .collect()
+    }
+
     /// Pass in a closure that will properly clean-up the items that need to be dropped.
     /// This is needed to clean up things like GPU resources.
     pub fn drop_items(&mut self, mut drop_function: impl FnMut(T)) {
@@ -401,6 +433,14 @@ where
         new_handle
     }
 
+    /// Loads an asset's path again and replaces the asset once it has loaded.
+    /// Does nothing if the asset wasn't loaded from a path.
+    pub fn reload(&mut self, handle: &Handle<T>) {
+        if let Some(path) = self.handle_to_path.get(&handle.indirection_index) {
+            self.asset_loader.reload(path, handle.clone());
+        }
+    }
+
     pub fn load_with_data_and_options_and_extension(
         &mut self,
         data: Vec<u8>,
@@ -121,27 +121,18 @@ pub fn move_sources(
         listener.last_position = Some(listener_transform.position);
         let listener_velocity = (listener_transform.position - last_position) * 60.;
 
+        // Queued sounds are played first so that the motion update below also applies to them.
         for (source, source_transform) in &mut sources {
-            let last_position = source.last_position.unwrap_or(source_transform.position);
-            let velocity = if source.teleported {
-                // If the source begins moving immediately this will be slightly incorrect.
-                Vec3::ZERO
-            } else {
-                (source_transform.position - last_position) * 60.
-            };
-
-            source.last_position = Some(source_transform.position);
-
-            let relative_position = source_transform.position - listener_transform.position;
-            let relative_velocity = -(listener_velocity - velocity);
+            if source.to_play.is_empty() {
+                continue;
+            }
 
-            source.set_position_and_velocity(
-                relative_position,
-                relative_velocity,
-                source.teleported,
+            let (relative_position, relative_velocity) = relative_motion(
+                source,
+                source_transform.position,
+                listener_transform.position,
+                listener_velocity,
             );
-            source.teleported = false;
-
             let velocity: [f32; 3] = relative_velocity.into();
             let position: [f32; 3] = relative_position.into();
 
@@ -184,9 +175,47 @@ pub fn move_sources(
                 source.to_play.swap_remove(i);
             }
         }
+
+        // Scenes can have many sources, so their motion is updated in parallel.
+        let listener_position = listener_transform.position;
+        sources.par_iter_mut(64, |(source, source_transform)| {
+            let (relative_position, relative_velocity) = relative_motion(
+                source,
+                source_transform.position,
+                listener_position,
+                listener_velocity,
+            );
+            source.last_position = Some(source_transform.position);
+            source.set_position_and_velocity(
+                relative_position,
+                relative_velocity,
+                source.teleported,
+            );
+            source.teleported = false;
+        });
     }
 }
 
+/// Returns the position and velocity of an [AudioSource] relative to the listener.
+fn relative_motion(
+    source: &AudioSource,
+    source_position: Vec3,
+    listener_position: Vec3,
+    listener_velocity: Vec3,
+) -> (Vec3, Vec3) {
+    let last_position = source.last_position.unwrap_or(source_position);
+    let velocity = if source.teleported {
+        // If the source begins moving immediately this will be slightly incorrect.
+        Vec3::ZERO
+    } else {
+        (source_position - last_position) * 60.
+    };
+    (
+        source_position - listener_position,
+        -(listener_velocity - velocity),
+    )
+}
+
 pub(super) trait SpatialHandle: Send + Sync {
     fn set_motion(&mut self, position: Vec3, velocity: Vec3, discontinuity: bool);
     // fn set_volume(&mut self, volume: f32);
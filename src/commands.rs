@@ -1,116 +1,399 @@
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
+use std::sync::RwLockWriteGuard;
+
 use kecs::hierarchy::*;
 use kecs::*;
 
-enum Command {
-    DespawnEntity(Entity),
-    RunSystem(System),
-    SetParent {
-        parent: Option<Entity>,
-        child: Entity,
-    },
+/// Where a command is stored in a [CommandQueue] and how to use it.
+struct CommandMeta {
+    /// The command's first byte in [CommandQueue]'s `bytes`.
+    offset: usize,
+    /// Reads the command out of its bytes, then runs it if there's a [World] or drops it otherwise.
+    consume: unsafe fn(*const MaybeUninit<u8>, Option<&mut World>),
 }
 
-/// [Commands] is a way to enque edits of the [World] for later.
+/// [CommandQueue] stores edits of the [World] to be applied later.
+/// A [CommandQueue] singleton lives in the [World] and is used by [Commands].
+///
+/// Commands are stored back-to-back in one buffer that's reused after the queue is applied,
+/// so queueing a command doesn't allocate once the buffer is large enough.
 #[derive(NotCloneComponent)]
-pub struct Commands(Vec<Command>);
+pub struct CommandQueue {
+    bytes: Vec<MaybeUninit<u8>>,
+    commands: Vec<CommandMeta>,
+}
 
-impl Default for Commands {
+impl Default for CommandQueue {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl Commands {
+impl CommandQueue {
     pub fn new() -> Self {
-        Self(Vec::new())
+        Self {
+            bytes: Vec::new(),
+            commands: Vec::new(),
+        }
     }
 
+    /// Enqueue a command to run with exclusive access to the [World].
+    pub fn push<C: FnOnce(&mut World) + Send + Sync + 'static>(&mut self, command: C) {
+        /// # Safety
+        /// `bytes` must point to a `C` written by [CommandQueue::push] that hasn't been consumed.
+        unsafe fn consume<C: FnOnce(&mut World)>(
+            bytes: *const MaybeUninit<u8>,
+            world: Option<&mut World>,
+        ) {
+            let command = std::ptr::read_unaligned(bytes as *const C);
+            if let Some(world) = world {
+                command(world);
+            }
+        }
+
+        let offset = self.bytes.len();
+        let size = std::mem::size_of::<C>();
+        self.bytes.reserve(size);
+        // Safety: There's room for `size` more bytes and unaligned writes don't need padding.
+        // The command is forgotten here and consumed exactly once by `apply` or `clear`.
+        unsafe {
+            std::ptr::write_unaligned(self.bytes.as_mut_ptr().add(offset) as *mut C, command);
+            self.bytes.set_len(offset + size);
+        }
+        self.commands.push(CommandMeta {
+            offset,
+            consume: consume::<C>,
+        });
+    }
+
+    /// Spawns a new [Entity] when the queue is applied.
+    /// Use [Commands::spawn] if the [Entity] is needed immediately.
     pub fn spawn(&mut self, component_bundle: impl ComponentBundleTrait) {
-        let mut component_bundle = Some(component_bundle);
-        self.0.push(Command::RunSystem(
-            (move |world: &mut World| {
-                // kecs doesn't support FnOnce systems yet, so use an Option here
-                // to make this closure FnMut.
-                world.spawn(component_bundle.take().unwrap());
-            })
-            .system(),
-        ))
+        self.push(move |world: &mut World| {
+            world.spawn(component_bundle);
+        })
     }
 
+    /// Despawns the [Entity] and all of its descendents.
     pub fn despawn(&mut self, entity: Entity) {
-        self.0.push(Command::DespawnEntity(entity));
+        self.push(move |world: &mut World| {
+            if let Err(error) = HierarchyNode::despawn_hierarchy(world, entity) {
+                klog::log!("COMMAND ERROR: Could not despawn {:?}: {:?}", entity, error);
+            }
+        })
     }
 
-    pub fn add_world(&mut self, world: World) {
-        let mut new_world = Some(world);
-        self.0.push(Command::RunSystem(
-            (move |world: &mut World| {
-                // kecs doesn't support FnOnce systems yet, so use an Option here
-                // to make this closure FnMut.
-                let mut new_world = new_world.take().unwrap();
-                world.add_world(&mut new_world);
-            })
-            .system(),
-        ))
+    pub fn add_world(&mut self, mut world: World) {
+        self.push(move |main_world: &mut World| {
+            main_world.add_world(&mut world);
+        })
     }
 
     /// Preserves the child global Transform if it a has a Transform component.
     pub fn set_parent(&mut self, parent: Option<Entity>, child: Entity) {
-        self.0.push(Command::SetParent { parent, child });
+        self.push(move |world: &mut World| crate::set_parent(world, parent, child))
     }
 
     pub fn add_component(&mut self, entity: Entity, component: impl ComponentTrait) {
-        let mut component = Some(component);
-        self.0.push(Command::RunSystem(
-            (move |world: &mut World| {
-                // kecs doesn't support FnOnce systems yet, so use an Option here
-                // to make this closure FnMut.
-                let _ = world.add_component(entity, component.take().unwrap());
-            })
-            .system(),
-        ))
+        self.push(move |world: &mut World| {
+            if let Err(error) = world.add_component(entity, component) {
+                klog::log!(
+                    "COMMAND ERROR: Could not add a component to {:?}: {:?}",
+                    entity,
+                    error
+                );
+            }
+        })
+    }
+
+    pub fn add_components(&mut self, entity: Entity, components: impl ComponentBundleTrait) {
+        self.push(move |world: &mut World| {
+            if let Err(error) = world.add_components(entity, components) {
+                klog::log!(
+                    "COMMAND ERROR: Could not add components to {:?}: {:?}",
+                    entity,
+                    error
+                );
+            }
+        })
     }
 
     pub fn remove_component<Component: ComponentTrait>(&mut self, entity: Entity) {
-        self.0.push(Command::RunSystem(
-            (move |world: &mut World| {
-                // kecs doesn't support FnOnce systems yet, so use an Option here
-                // to make this closure FnMut.
-                let _ = world.remove_component::<Component>(entity);
-            })
-            .system(),
-        ))
+        self.push(move |world: &mut World| {
+            if let Err(error) = world.remove_component::<Component>(entity) {
+                klog::log!(
+                    "COMMAND ERROR: Could not remove a component from {:?}: {:?}",
+                    entity,
+                    error
+                );
+            }
+        })
     }
 
+    /// Runs the commands in the order they were queued and empties the queue.
     pub fn apply(&mut self, world: &mut World) {
-        for command in &mut self.0 {
-            match command {
-                Command::DespawnEntity(entity) => {
-                    HierarchyNode::despawn_hierarchy(world, *entity).unwrap();
-                }
-                Command::SetParent { parent, child } => crate::set_parent(world, *parent, *child),
-                Command::RunSystem(system) => system.run(world),
+        for command in self.commands.drain(..) {
+            // Safety: Each command is consumed once because it's drained from `commands`.
+            // If a command panics the rest are leaked rather than dropped.
+            unsafe {
+                (command.consume)(self.bytes.as_ptr().add(command.offset), Some(&mut *world));
             }
         }
-        self.clear();
+        self.bytes.clear();
     }
 
+    /// Drops the commands without running them.
     pub fn clear(&mut self) {
-        self.0.clear();
+        for command in self.commands.drain(..) {
+            // Safety: Each command is consumed once because it's drained from `commands`.
+            unsafe {
+                (command.consume)(self.bytes.as_ptr().add(command.offset), None);
+            }
+        }
+        self.bytes.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+}
+
+impl Drop for CommandQueue {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// [Commands] is a way to enque edits of the [World] for later.
+///
+/// [Commands] is a system parameter that writes to the [World]'s [CommandQueue].
+/// Unlike a [CommandQueue] it can reserve [Entity]s so that spawned [Entity]s can be
+/// referenced before the commands are applied.
+pub struct Commands<'a> {
+    queue: &'a mut CommandQueue,
+    world: &'a World,
+}
+
+impl<'a> Commands<'a> {
+    /// Construct [Commands] that reserves [Entity]s from `world` and enqueues edits to `queue`.
+    /// The `queue` should later be applied to the same [World].
+    pub fn new(world: &'a World, queue: &'a mut CommandQueue) -> Self {
+        Self { queue, world }
+    }
+
+    /// Reserves an [Entity] that will be spawned with `component_bundle`
+    /// when the commands are applied.
+    pub fn spawn<'b>(
+        &'b mut self,
+        component_bundle: impl ComponentBundleTrait,
+    ) -> EntityCommands<'a, 'b> {
+        let entity = self.world.reserve_entity();
+        self.queue.add_components(entity, component_bundle);
+        EntityCommands {
+            entity,
+            commands: self,
+        }
+    }
+
+    /// Get an [EntityCommands] to enqueue edits for an existing [Entity].
+    pub fn entity<'b>(&'b mut self, entity: Entity) -> EntityCommands<'a, 'b> {
+        EntityCommands {
+            entity,
+            commands: self,
+        }
+    }
+}
+
+impl<'a> Deref for Commands<'a> {
+    type Target = CommandQueue;
+    fn deref(&self) -> &Self::Target {
+        self.queue
+    }
+}
+
+impl<'a> DerefMut for Commands<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.queue
+    }
+}
+
+/// Enqueues edits for a single [Entity].
+pub struct EntityCommands<'a, 'b> {
+    entity: Entity,
+    commands: &'b mut Commands<'a>,
+}
+
+impl<'a, 'b> EntityCommands<'a, 'b> {
+    /// The [Entity] these commands apply to.
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    /// Adds components to the [Entity], replacing components of the same type.
+    pub fn insert(&mut self, component_bundle: impl ComponentBundleTrait) -> &mut Self {
+        self.commands
+            .queue
+            .add_components(self.entity, component_bundle);
+        self
+    }
+
+    pub fn remove<Component: ComponentTrait>(&mut self) -> &mut Self {
+        self.commands
+            .queue
+            .remove_component::<Component>(self.entity);
+        self
+    }
+
+    /// Spawn children of this [Entity].
+    pub fn with_children(&mut self, f: impl FnOnce(&mut ChildBuilder)) -> &mut Self {
+        f(&mut ChildBuilder {
+            parent: self.entity,
+            commands: self.commands,
+        });
+        self
+    }
+
+    /// Despawns the [Entity] and all of its descendents.
+    pub fn despawn_recursive(self) {
+        self.commands.queue.despawn(self.entity);
+    }
+}
+
+/// Passed to the closure of [EntityCommands::with_children] to spawn children.
+pub struct ChildBuilder<'a, 'b> {
+    parent: Entity,
+    commands: &'b mut Commands<'a>,
+}
+
+impl<'a, 'b> ChildBuilder<'a, 'b> {
+    pub fn parent_entity(&self) -> Entity {
+        self.parent
+    }
+
+    pub fn spawn<'c>(
+        &'c mut self,
+        component_bundle: impl ComponentBundleTrait,
+    ) -> EntityCommands<'a, 'c> {
+        let parent = self.parent;
+        let entity_commands = self.commands.spawn(component_bundle);
+        let child = entity_commands.id();
+        entity_commands
+            .commands
+            .queue
+            .set_parent(Some(parent), child);
+        entity_commands
+    }
+}
+
+pub struct CommandsFetch<'a> {
+    queue: RwLockWriteGuard<'a, Vec<CommandQueue>>,
+    world: &'a World,
+}
+
+impl SystemParameterTrait for Commands<'_> {
+    fn get_meta_data(world: &World) -> Result<SystemParameterMetaData, KecsError> {
+        <&mut CommandQueue as SystemParameterTrait>::get_meta_data(world)
+    }
+}
+
+impl<'a> SystemParameterFetchTrait<'a> for Commands<'_> {
+    type FetchResult = CommandsFetch<'a>;
+
+    fn fetch(
+        world: &'a World,
+        meta_data: &SystemParameterMetaData,
+        change_ticks: ChangeTicks,
+    ) -> Result<Self::FetchResult, KecsError> {
+        Ok(CommandsFetch {
+            queue: <&mut CommandQueue as SystemParameterFetchTrait<'a>>::fetch(
+                world,
+                meta_data,
+                change_ticks,
+            )?,
+            world,
+        })
+    }
+}
+
+impl<'a, 'b> AsSystemArg<'b> for CommandsFetch<'a> {
+    type Arg = Commands<'b>;
+    fn as_system_arg(&'b mut self) -> Self::Arg {
+        Commands {
+            queue: &mut self.queue[0],
+            world: self.world,
+        }
     }
 }
 
 pub fn apply_commands(world: &mut World) {
-    let mut commands = Commands::new();
+    let mut commands = CommandQueue::new();
     std::mem::swap(
         &mut commands,
-        world.get_single_component_mut::<Commands>().unwrap(),
+        world.get_single_component_mut::<CommandQueue>().unwrap(),
     );
     commands.apply(world);
-    commands.clear();
 
     std::mem::swap(
         &mut commands,
-        world.get_single_component_mut::<Commands>().unwrap(),
+        world.get_single_component_mut::<CommandQueue>().unwrap(),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Clone, Component)]
+    struct Counter(usize);
+
+    #[test]
+    fn commands_run_in_order() {
+        let mut world = World::new();
+        let entity = world.spawn(Counter(0));
+        let mut queue = CommandQueue::new();
+        for i in 1..=3u8 {
+            // Differently sized commands are packed together.
+            let padding = [i; 7];
+            queue.push(move |world: &mut World| {
+                let counter = world.get_component_mut::<Counter>(entity).unwrap();
+                counter.0 = counter.0 * 10 + padding[0] as usize;
+            });
+        }
+        queue.despawn(entity);
+        assert_eq!(queue.len(), 4);
+
+        queue.push(move |world: &mut World| {
+            assert!(world.get_component_mut::<Counter>(entity).is_err());
+        });
+        let counter = Arc::new(AtomicUsize::new(0));
+        let counter_clone = counter.clone();
+        queue.push(move |_: &mut World| {
+            counter_clone.fetch_add(123, Ordering::Relaxed);
+        });
+        queue.apply(&mut world);
+        assert!(queue.is_empty());
+        assert_eq!(counter.load(Ordering::Relaxed), 123);
+    }
+
+    #[test]
+    fn unapplied_commands_are_dropped() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let mut queue = CommandQueue::new();
+        let counter_clone = counter.clone();
+        queue.push(move |_: &mut World| drop(counter_clone));
+        queue.clear();
+        assert_eq!(Arc::strong_count(&counter), 1);
+
+        let counter_clone = counter.clone();
+        queue.push(move |_: &mut World| drop(counter_clone));
+        drop(queue);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+}
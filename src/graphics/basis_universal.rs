@@ -0,0 +1,832 @@
+//! A transcoder for Basis Universal ETC1S data, as stored in BasisLZ supercompressed `.ktx2` files.
+//!
+//! ETC1S blocks are ETC1 blocks where both halves share one color and intensity table,
+//! so they map directly to ETC1 / ETC2 and are quick to re-encode as BC1 / BC3.
+//! UASTC data is not supported.
+
+use crate::*;
+use kgraphics::*;
+
+/// Reads bits least significant bit first.
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    bit: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, bit: 0 }
+    }
+
+    pub fn read_bits(&mut self, count: u32) -> Result<u32, CompressedTextureError> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self
+                .data
+                .get(self.bit / 8)
+                .ok_or(CompressedTextureError::InvalidBasisData)?;
+            value |= (((byte >> (self.bit % 8)) & 1) as u32) << i;
+            self.bit += 1;
+        }
+        Ok(value)
+    }
+
+    /// Reads a variable length integer made of `chunk_bits` sized chunks,
+    /// each followed by a bit that's set if more chunks follow.
+    pub fn read_vlc(&mut self, chunk_bits: u32) -> Result<u32, CompressedTextureError> {
+        let mut value = 0;
+        let mut offset = 0;
+        loop {
+            let chunk = self.read_bits(chunk_bits + 1)?;
+            value |= (chunk & ((1 << chunk_bits) - 1)) << offset;
+            offset += chunk_bits;
+            if chunk & (1 << chunk_bits) == 0 {
+                return Ok(value);
+            }
+            if offset >= 32 {
+                return Err(CompressedTextureError::InvalidBasisData);
+            }
+        }
+    }
+
+    pub fn read_huffman_table(&mut self) -> Result<HuffmanTable, CompressedTextureError> {
+        let symbol_count = self.read_bits(14)? as usize;
+        if symbol_count == 0 {
+            return HuffmanTable::new(&[]);
+        }
+
+        let mut code_length_code_sizes = [0; 21];
+        let code_length_code_count = self.read_bits(5)? as usize;
+        for code in CODE_LENGTH_CODE_ORDER
+            .iter()
+            .take(code_length_code_count.min(21))
+        {
+            code_length_code_sizes[*code] = self.read_bits(3)? as u8;
+        }
+        let code_length_table = HuffmanTable::new(&code_length_code_sizes)?;
+
+        let mut code_sizes = Vec::with_capacity(symbol_count);
+        while code_sizes.len() < symbol_count {
+            let code = self.decode(&code_length_table)?;
+            match code {
+                0..=16 => code_sizes.push(code as u8),
+                17 => {
+                    let count = self.read_bits(3)? + 3;
+                    code_sizes.extend((0..count).map(|_| 0));
+                }
+                18 => {
+                    let count = self.read_bits(7)? + 11;
+                    code_sizes.extend((0..count).map(|_| 0));
+                }
+                _ => {
+                    let count = if code == 19 {
+                        self.read_bits(2)? + 3
+                    } else {
+                        self.read_bits(7)? + 7
+                    };
+                    let previous = match code_sizes.last() {
+                        Some(previous) if *previous != 0 => *previous,
+                        _ => return Err(CompressedTextureError::InvalidBasisData),
+                    };
+                    code_sizes.extend((0..count).map(|_| previous));
+                }
+            }
+        }
+        if code_sizes.len() != symbol_count {
+            return Err(CompressedTextureError::InvalidBasisData);
+        }
+        HuffmanTable::new(&code_sizes)
+    }
+
+    /// Huffman codes are stored starting with their most significant bit.
+    pub fn decode(&mut self, table: &HuffmanTable) -> Result<u32, CompressedTextureError> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for count in &table.counts[1..] {
+            code |= self.read_bits(1)? as i32;
+            let count = *count as i32;
+            if code - first < count {
+                return Ok(table.symbols[(index + code - first) as usize] as u32);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(CompressedTextureError::InvalidBasisData)
+    }
+}
+
+/// The order code length code sizes are stored in.
+const CODE_LENGTH_CODE_ORDER: [usize; 21] = [
+    17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16,
+];
+
+/// A canonical Huffman table.
+pub(crate) struct HuffmanTable {
+    /// The number of codes of each length.
+    counts: [u16; 17],
+    /// Symbols ordered by code length then value.
+    symbols: Vec<u16>,
+}
+
+impl HuffmanTable {
+    pub fn new(code_sizes: &[u8]) -> Result<Self, CompressedTextureError> {
+        let mut counts = [0; 17];
+        for size in code_sizes {
+            *counts
+                .get_mut(*size as usize)
+                .ok_or(CompressedTextureError::InvalidBasisData)? += 1;
+        }
+        counts[0] = 0;
+
+        // Check that the code isn't over-subscribed.
+        let mut left = 1i32;
+        for count in &counts[1..] {
+            left = (left << 1) - *count as i32;
+            if left < 0 {
+                return Err(CompressedTextureError::InvalidBasisData);
+            }
+        }
+
+        let mut symbols: Vec<u16> = (0..code_sizes.len() as u16)
+            .filter(|s| code_sizes[*s as usize] != 0)
+            .collect();
+        symbols.sort_by_key(|s| code_sizes[*s as usize]);
+        Ok(Self { counts, symbols })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+struct Endpoint {
+    color5: [u8; 3],
+    intensity: u8,
+}
+
+type Selector = [[u8; 4]; 4];
+
+/// The codebooks and Huffman tables shared by every slice in a file.
+pub(crate) struct Etc1sCodebooks {
+    endpoints: Vec<Endpoint>,
+    selectors: Vec<Selector>,
+    endpoint_prediction_model: HuffmanTable,
+    delta_endpoint_model: HuffmanTable,
+    selector_model: HuffmanTable,
+    selector_history_rle_model: HuffmanTable,
+    selector_history_size: usize,
+}
+
+impl Etc1sCodebooks {
+    pub fn new(
+        endpoint_count: usize,
+        selector_count: usize,
+        endpoints_data: &[u8],
+        selectors_data: &[u8],
+        tables_data: &[u8],
+    ) -> Result<Self, CompressedTextureError> {
+        let endpoints = decode_endpoints(endpoint_count, endpoints_data)?;
+        let selectors = decode_selectors(selector_count, selectors_data)?;
+
+        let mut reader = BitReader::new(tables_data);
+        let endpoint_prediction_model = reader.read_huffman_table()?;
+        if endpoint_prediction_model.is_empty() {
+            return Err(CompressedTextureError::InvalidBasisData);
+        }
+        let delta_endpoint_model = reader.read_huffman_table()?;
+        let selector_model = reader.read_huffman_table()?;
+        let selector_history_rle_model = reader.read_huffman_table()?;
+        let selector_history_size = reader.read_bits(13)? as usize;
+
+        Ok(Self {
+            endpoints,
+            selectors,
+            endpoint_prediction_model,
+            delta_endpoint_model,
+            selector_model,
+            selector_history_rle_model,
+            selector_history_size,
+        })
+    }
+
+    /// Transcodes an ETC1S slice to the `target` format.
+    /// If `alpha` is provided its green channel becomes the alpha of the output.
+    pub fn transcode(
+        &self,
+        width: u32,
+        height: u32,
+        rgb: &[u8],
+        alpha: Option<&[u8]>,
+        target: PixelFormat,
+    ) -> Result<Vec<u8>, CompressedTextureError> {
+        let (width, height) = (width as usize, height as usize);
+        let blocks_x = width.div_ceil(4);
+        let color_blocks = self.decode_slice(blocks_x, height.div_ceil(4), rgb)?;
+        let alpha_blocks = alpha
+            .map(|alpha| self.decode_slice(blocks_x, height.div_ceil(4), alpha))
+            .transpose()?;
+
+        let block_alpha = |i: usize| -> [u8; 16] {
+            let mut values = [255; 16];
+            if let Some(alpha_blocks) = &alpha_blocks {
+                let (endpoint, selector) = &alpha_blocks[i];
+                for (j, pixel) in block_pixels(endpoint, selector).iter().enumerate() {
+                    values[j] = pixel[1];
+                }
+            }
+            values
+        };
+
+        let mut output = Vec::new();
+        match target {
+            PixelFormat::ETC2RGB8Unorm => {
+                for (endpoint, selector) in &color_blocks {
+                    output.extend(etc1_block(endpoint, selector));
+                }
+            }
+            PixelFormat::BC1RGBAUnorm => {
+                for (endpoint, selector) in &color_blocks {
+                    output.extend(encode_bc1_block(&block_pixels(endpoint, selector)));
+                }
+            }
+            PixelFormat::BC3RGBAUnorm => {
+                for (i, (endpoint, selector)) in color_blocks.iter().enumerate() {
+                    output.extend(encode_bc4_block(&block_alpha(i)));
+                    output.extend(encode_bc1_block(&block_pixels(endpoint, selector)));
+                }
+            }
+            PixelFormat::RGBA8Unorm => {
+                output.resize(width * height * 4, 0);
+                for (i, (endpoint, selector)) in color_blocks.iter().enumerate() {
+                    let alpha = block_alpha(i);
+                    let (block_x, block_y) = (i % blocks_x, i / blocks_x);
+                    for (j, pixel) in block_pixels(endpoint, selector).iter().enumerate() {
+                        let (x, y) = (block_x * 4 + j % 4, block_y * 4 + j / 4);
+                        if x < width && y < height {
+                            let index = (y * width + x) * 4;
+                            output[index..index + 3].copy_from_slice(&pixel[..3]);
+                            output[index + 3] = alpha[j];
+                        }
+                    }
+                }
+            }
+            _ => return Err(CompressedTextureError::CannotDecode(target)),
+        }
+        Ok(output)
+    }
+
+    fn decode_slice(
+        &self,
+        blocks_x: usize,
+        blocks_y: usize,
+        data: &[u8],
+    ) -> Result<Vec<(Endpoint, Selector)>, CompressedTextureError> {
+        const ENDPOINT_PREDICTION_REPEAT: u32 = 256;
+        const SELECTOR_HISTORY_RLE_COUNT_TOTAL: u32 = 64;
+        const SELECTOR_HISTORY_RLE_THRESHOLD: u32 = 3;
+
+        let invalid = || CompressedTextureError::InvalidBasisData;
+        let mut reader = BitReader::new(data);
+        let mut history = SelectorHistory::new(self.selector_history_size);
+        let history_first_symbol = self.selectors.len();
+        let history_rle_symbol = (self.selector_history_size + history_first_symbol) as u32;
+
+        // The endpoint index and prediction bits of the previous and current rows.
+        let mut rows = [
+            vec![(0usize, 0u32); blocks_x],
+            vec![(0usize, 0u32); blocks_x],
+        ];
+
+        let mut blocks = Vec::with_capacity(blocks_x * blocks_y);
+        let mut prediction_repeat_count = 0;
+        let mut previous_prediction_symbol = 0;
+        let mut prediction_bits = 0;
+        let mut previous_endpoint = 0;
+        let mut selector_rle_count = 0;
+
+        for block_y in 0..blocks_y {
+            let current = block_y & 1;
+            for block_x in 0..blocks_x {
+                // Each prediction symbol covers a 2x2 group of blocks.
+                if block_x & 1 == 0 {
+                    if block_y & 1 == 0 {
+                        if prediction_repeat_count > 0 {
+                            prediction_repeat_count -= 1;
+                            prediction_bits = previous_prediction_symbol;
+                        } else {
+                            prediction_bits = reader.decode(&self.endpoint_prediction_model)?;
+                            if prediction_bits == ENDPOINT_PREDICTION_REPEAT {
+                                prediction_repeat_count = reader.read_vlc(4)? + 2;
+                                prediction_bits = previous_prediction_symbol;
+                            } else {
+                                previous_prediction_symbol = prediction_bits;
+                            }
+                        }
+                        rows[current ^ 1][block_x].1 = prediction_bits >> 4;
+                    } else {
+                        prediction_bits = rows[current][block_x].1;
+                    }
+                }
+
+                let prediction = prediction_bits & 3;
+                prediction_bits >>= 2;
+
+                let endpoint = match prediction {
+                    // Left
+                    0 if block_x > 0 => previous_endpoint,
+                    // Above
+                    1 if block_y > 0 => rows[current ^ 1][block_x].0,
+                    // Above and left
+                    2 if block_x > 0 && block_y > 0 => rows[current ^ 1][block_x - 1].0,
+                    3 => {
+                        let delta = reader.decode(&self.delta_endpoint_model)? as usize;
+                        let mut endpoint = delta + previous_endpoint;
+                        if endpoint >= self.endpoints.len() {
+                            endpoint -= self.endpoints.len();
+                        }
+                        endpoint
+                    }
+                    _ => return Err(invalid()),
+                };
+                rows[current][block_x].0 = endpoint;
+                previous_endpoint = endpoint;
+
+                let mut symbol = if selector_rle_count > 0 {
+                    selector_rle_count -= 1;
+                    history_first_symbol as u32
+                } else {
+                    reader.decode(&self.selector_model)?
+                };
+                if symbol == history_rle_symbol {
+                    let run = reader.decode(&self.selector_history_rle_model)?;
+                    selector_rle_count = if run == SELECTOR_HISTORY_RLE_COUNT_TOTAL - 1 {
+                        reader.read_vlc(7)? + SELECTOR_HISTORY_RLE_THRESHOLD
+                    } else {
+                        run + SELECTOR_HISTORY_RLE_THRESHOLD
+                    };
+                    if selector_rle_count as usize > blocks_x * blocks_y {
+                        return Err(invalid());
+                    }
+                    selector_rle_count -= 1;
+                    symbol = history_first_symbol as u32;
+                }
+
+                let symbol = symbol as usize;
+                let selector = if symbol >= history_first_symbol {
+                    let index = symbol - history_first_symbol;
+                    let selector = *history.values.get(index).ok_or_else(invalid)?;
+                    history.use_index(index);
+                    selector
+                } else {
+                    if self.selector_history_size > 0 {
+                        history.add(symbol);
+                    }
+                    symbol
+                };
+
+                blocks.push((
+                    *self.endpoints.get(endpoint).ok_or_else(invalid)?,
+                    *self.selectors.get(selector).ok_or_else(invalid)?,
+                ));
+            }
+        }
+        Ok(blocks)
+    }
+}
+
+/// Recently used selectors, kept roughly in most recently used order.
+struct SelectorHistory {
+    values: Vec<usize>,
+    rover: usize,
+}
+
+impl SelectorHistory {
+    fn new(size: usize) -> Self {
+        Self {
+            values: vec![0; size],
+            rover: size / 2,
+        }
+    }
+
+    fn add(&mut self, value: usize) {
+        self.values[self.rover] = value;
+        self.rover += 1;
+        if self.rover == self.values.len() {
+            self.rover = self.values.len() / 2;
+        }
+    }
+
+    fn use_index(&mut self, index: usize) {
+        if index != 0 {
+            self.values.swap(index / 2, index);
+        }
+    }
+}
+
+fn decode_endpoints(count: usize, data: &[u8]) -> Result<Vec<Endpoint>, CompressedTextureError> {
+    let mut reader = BitReader::new(data);
+    let color5_delta_models = [
+        reader.read_huffman_table()?,
+        reader.read_huffman_table()?,
+        reader.read_huffman_table()?,
+    ];
+    let intensity_delta_model = reader.read_huffman_table()?;
+    let grayscale = reader.read_bits(1)? != 0;
+
+    let mut previous_intensity = 0;
+    let mut previous_color5 = [16u32; 3];
+    let mut endpoints = Vec::with_capacity(count);
+    for _ in 0..count {
+        let intensity = (reader.decode(&intensity_delta_model)? + previous_intensity) & 7;
+        previous_intensity = intensity;
+
+        let mut color5 = [0; 3];
+        for c in 0..if grayscale { 1 } else { 3 } {
+            let model = match previous_color5[c] {
+                0..=9 => &color5_delta_models[0],
+                10..=21 => &color5_delta_models[1],
+                _ => &color5_delta_models[2],
+            };
+            let value = (previous_color5[c] + reader.decode(model)?) & 31;
+            color5[c] = value as u8;
+            previous_color5[c] = value;
+        }
+        if grayscale {
+            color5 = [color5[0]; 3];
+        }
+        endpoints.push(Endpoint {
+            color5,
+            intensity: intensity as u8,
+        });
+    }
+    Ok(endpoints)
+}
+
+fn decode_selectors(count: usize, data: &[u8]) -> Result<Vec<Selector>, CompressedTextureError> {
+    let mut reader = BitReader::new(data);
+    let global_codebook = reader.read_bits(1)? != 0;
+    let hybrid = reader.read_bits(1)? != 0;
+    if global_codebook || hybrid {
+        return Err(CompressedTextureError::UnsupportedFormat(
+            "Basis Universal global selector codebooks".into(),
+        ));
+    }
+    let raw = reader.read_bits(1)? != 0;
+    let delta_model = if raw {
+        None
+    } else {
+        Some(reader.read_huffman_table()?)
+    };
+
+    let mut previous_rows = [0u32; 4];
+    let mut selectors = Vec::with_capacity(count);
+    for i in 0..count {
+        let mut selector = [[0; 4]; 4];
+        for (y, row) in selector.iter_mut().enumerate() {
+            let byte = match &delta_model {
+                Some(delta_model) if i > 0 => reader.decode(delta_model)? ^ previous_rows[y],
+                _ => reader.read_bits(8)?,
+            };
+            previous_rows[y] = byte;
+            for (x, value) in row.iter_mut().enumerate() {
+                *value = ((byte >> (x * 2)) & 3) as u8;
+            }
+        }
+        selectors.push(selector);
+    }
+    Ok(selectors)
+}
+
+/// Selectors index the intensity table from most negative to most positive.
+fn block_pixels(endpoint: &Endpoint, selector: &Selector) -> [[u8; 4]; 16] {
+    let table = ETC1_INTENSITY_TABLES[endpoint.intensity as usize];
+    let mut pixels = [[0; 4]; 16];
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let modifier = table[selector[i / 4][i % 4] as usize];
+        for (channel, base) in pixel.iter_mut().zip(endpoint.color5) {
+            let base = base as i32;
+            *channel = (((base << 3) | (base >> 2)) + modifier).clamp(0, 255) as u8;
+        }
+        pixel[3] = 255;
+    }
+    pixels
+}
+
+/// An ETC1S block is a differential ETC1 block with no delta and matching intensity tables.
+fn etc1_block(endpoint: &Endpoint, selector: &Selector) -> [u8; 8] {
+    // Maps selectors to ETC1's modifier indices.
+    const SELECTOR_TO_ETC1: [u16; 4] = [3, 2, 0, 1];
+
+    let mut block = [0; 8];
+    for (channel, base) in block.iter_mut().zip(endpoint.color5) {
+        *channel = base << 3;
+    }
+    block[3] = (endpoint.intensity << 5) | (endpoint.intensity << 2) | 2;
+
+    let (mut msb, mut lsb) = (0u16, 0u16);
+    for (y, row) in selector.iter().enumerate() {
+        for (x, value) in row.iter().enumerate() {
+            let index = SELECTOR_TO_ETC1[*value as usize];
+            let bit = x * 4 + y;
+            msb |= (index >> 1) << bit;
+            lsb |= (index & 1) << bit;
+        }
+    }
+    block[4..6].copy_from_slice(&msb.to_be_bytes());
+    block[6..8].copy_from_slice(&lsb.to_be_bytes());
+    block
+}
+
+/// Picks the format Basis Universal data is transcoded to.
+pub(crate) fn basis_transcode_target(
+    has_alpha: bool,
+    supported_pixel_formats: &[PixelFormat],
+) -> PixelFormat {
+    let supported = |f| supported_pixel_formats.contains(&f);
+    if !has_alpha && supported(PixelFormat::ETC2RGB8Unorm) {
+        PixelFormat::ETC2RGB8Unorm
+    } else if !has_alpha && supported(PixelFormat::BC1RGBAUnorm) {
+        PixelFormat::BC1RGBAUnorm
+    } else if has_alpha && supported(PixelFormat::BC3RGBAUnorm) {
+        PixelFormat::BC3RGBAUnorm
+    } else {
+        PixelFormat::RGBA8Unorm
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Writes bits least significant bit first.
+    #[derive(Default)]
+    pub struct BitWriter {
+        pub bytes: Vec<u8>,
+        bit: usize,
+    }
+
+    impl BitWriter {
+        pub fn write_bits(&mut self, value: u32, count: u32) {
+            for i in 0..count {
+                if self.bit.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                *self.bytes.last_mut().unwrap() |= (((value >> i) & 1) as u8) << (self.bit % 8);
+                self.bit += 1;
+            }
+        }
+
+        /// Writes a canonical Huffman code, most significant bit first.
+        pub fn write_symbol(&mut self, code_sizes: &[u8], symbol: usize) {
+            let (code, size) = canonical_code(code_sizes, symbol);
+            for i in (0..size).rev() {
+                self.write_bits((code >> i) & 1, 1);
+            }
+        }
+
+        /// Writes a table with every code length stored as a literal.
+        pub fn write_huffman_table(&mut self, code_sizes: &[u8]) {
+            self.write_bits(code_sizes.len() as u32, 14);
+            if code_sizes.is_empty() {
+                return;
+            }
+            let mut code_length_code_sizes = [0u8; 21];
+            code_length_code_sizes[..17].fill(5);
+            self.write_bits(21, 5);
+            for code in CODE_LENGTH_CODE_ORDER {
+                self.write_bits(code_length_code_sizes[code] as u32, 3);
+            }
+            for size in code_sizes {
+                self.write_symbol(&code_length_code_sizes, *size as usize);
+            }
+        }
+    }
+
+    fn canonical_code(code_sizes: &[u8], symbol: usize) -> (u32, u32) {
+        let mut code = 0;
+        for size in 1..=16 {
+            for (s, code_size) in code_sizes.iter().enumerate() {
+                if *code_size == size {
+                    if s == symbol {
+                        return (code, size as u32);
+                    }
+                    code += 1;
+                }
+            }
+            code <<= 1;
+        }
+        panic!("Symbol has no code")
+    }
+
+    #[test]
+    fn huffman_round_trip() {
+        let code_sizes = [2, 0, 3, 3, 1, 0, 0];
+        let mut writer = BitWriter::default();
+        writer.write_huffman_table(&code_sizes);
+        for symbol in [4, 0, 3, 2, 4, 4] {
+            writer.write_symbol(&code_sizes, symbol);
+        }
+        writer.write_bits(0b101_1110, 8);
+
+        let mut reader = BitReader::new(&writer.bytes);
+        let table = reader.read_huffman_table().unwrap();
+        for symbol in [4, 0, 3, 2, 4, 4] {
+            assert_eq!(reader.decode(&table).unwrap(), symbol);
+        }
+        assert_eq!(reader.read_bits(8).unwrap(), 0b101_1110);
+    }
+
+    #[test]
+    fn huffman_table_runs() {
+        // Code length codes 3, 17 and 19 each get a 2 bit code.
+        let mut writer = BitWriter::default();
+        writer.write_bits(12, 14);
+        writer.write_bits(19, 5);
+        let mut sizes = [0u8; 21];
+        sizes[3] = 2;
+        sizes[17] = 2;
+        sizes[19] = 2;
+        for code in CODE_LENGTH_CODE_ORDER.iter().take(19) {
+            writer.write_bits(sizes[*code] as u32, 3);
+        }
+        // 4 zeros, a single 3 then 7 more.
+        writer.write_symbol(&sizes, 17);
+        writer.write_bits(1, 3);
+        writer.write_symbol(&sizes, 3);
+        writer.write_symbol(&sizes, 19);
+        writer.write_bits(0, 2);
+        writer.write_symbol(&sizes, 19);
+        writer.write_bits(1, 2);
+
+        let mut reader = BitReader::new(&writer.bytes);
+        let table = reader.read_huffman_table().unwrap();
+        assert_eq!(table.counts[3], 8);
+        assert_eq!(table.symbols, (4..12).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn vlc() {
+        let mut writer = BitWriter::default();
+        // 0b10_0110 in 4 bit chunks: 0110 with a continue bit, then 0010.
+        writer.write_bits(0b1_0110, 5);
+        writer.write_bits(0b0_0010, 5);
+        assert_eq!(
+            BitReader::new(&writer.bytes).read_vlc(4).unwrap(),
+            0b10_0110
+        );
+    }
+
+    pub const TEST_ENDPOINTS: [([u8; 3], u8); 2] = [([20, 13, 16], 2), ([31, 0, 8], 5)];
+    pub const TEST_SELECTORS: [Selector; 2] = [
+        [[0, 1, 2, 3], [3, 2, 1, 0], [0, 0, 3, 3], [1, 1, 2, 2]],
+        [[3, 3, 3, 3], [0, 0, 0, 0], [1, 2, 1, 2], [2, 2, 1, 1]],
+    ];
+
+    /// Encodes the codebooks and a slice for an 8x4 image that uses both test endpoints and selectors.
+    pub fn test_etc1s_data() -> (Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>) {
+        let mut endpoints = BitWriter::default();
+        let color5_sizes = [5u8; 32];
+        let intensity_sizes = [3u8; 8];
+        for _ in 0..3 {
+            endpoints.write_huffman_table(&color5_sizes);
+        }
+        endpoints.write_huffman_table(&intensity_sizes);
+        endpoints.write_bits(0, 1);
+        let mut previous_color5 = [16; 3];
+        let mut previous_intensity = 0;
+        for (color5, intensity) in TEST_ENDPOINTS {
+            endpoints.write_symbol(
+                &intensity_sizes,
+                ((intensity + 8 - previous_intensity) & 7) as usize,
+            );
+            previous_intensity = intensity;
+            for c in 0..3 {
+                let delta = (color5[c] + 32 - previous_color5[c]) & 31;
+                endpoints.write_symbol(&color5_sizes, delta as usize);
+                previous_color5[c] = color5[c];
+            }
+        }
+
+        let mut selectors = BitWriter::default();
+        selectors.write_bits(0, 1);
+        selectors.write_bits(0, 1);
+        selectors.write_bits(1, 1);
+        for selector in TEST_SELECTORS {
+            for row in selector {
+                let byte = row
+                    .iter()
+                    .enumerate()
+                    .fold(0, |byte, (x, v)| byte | (*v as u32) << (x * 2));
+                selectors.write_bits(byte, 8);
+            }
+        }
+
+        // Both blocks use a delta from the previous endpoint.
+        let mut prediction_sizes = vec![0u8; 257];
+        prediction_sizes[0b1111] = 1;
+        let delta_sizes = [1u8, 1];
+        let selector_sizes = [1u8, 1];
+
+        let mut tables = BitWriter::default();
+        tables.write_huffman_table(&prediction_sizes);
+        tables.write_huffman_table(&delta_sizes);
+        tables.write_huffman_table(&selector_sizes);
+        tables.write_huffman_table(&[]);
+        tables.write_bits(0, 13);
+
+        let mut slice = BitWriter::default();
+        slice.write_symbol(&prediction_sizes, 0b1111);
+        slice.write_symbol(&delta_sizes, 0);
+        slice.write_symbol(&selector_sizes, 0);
+        slice.write_symbol(&delta_sizes, 1);
+        slice.write_symbol(&selector_sizes, 1);
+
+        (endpoints.bytes, selectors.bytes, tables.bytes, slice.bytes)
+    }
+
+    pub fn expected_pixels() -> Vec<u8> {
+        let mut pixels = vec![0; 8 * 4 * 4];
+        for (block, ((color5, intensity), selector)) in
+            TEST_ENDPOINTS.iter().zip(TEST_SELECTORS).enumerate()
+        {
+            for y in 0..4 {
+                for x in 0..4 {
+                    let modifier =
+                        ETC1_INTENSITY_TABLES[*intensity as usize][selector[y][x] as usize];
+                    let index = (y * 8 + block * 4 + x) * 4;
+                    for c in 0..3 {
+                        let base = ((color5[c] << 3) | (color5[c] >> 2)) as i32;
+                        pixels[index + c] = (base + modifier).clamp(0, 255) as u8;
+                    }
+                    pixels[index + 3] = 255;
+                }
+            }
+        }
+        pixels
+    }
+
+    fn test_codebooks() -> (Etc1sCodebooks, Vec<u8>) {
+        let (endpoints, selectors, tables, slice) = test_etc1s_data();
+        let codebooks = Etc1sCodebooks::new(2, 2, &endpoints, &selectors, &tables).unwrap();
+        (codebooks, slice)
+    }
+
+    #[test]
+    fn transcode_etc1s_to_rgba8() {
+        let (codebooks, slice) = test_codebooks();
+        let pixels = codebooks
+            .transcode(8, 4, &slice, None, PixelFormat::RGBA8Unorm)
+            .unwrap();
+        assert_eq!(pixels, expected_pixels());
+    }
+
+    #[test]
+    fn transcode_etc1s_to_etc1() {
+        let (codebooks, slice) = test_codebooks();
+        let blocks = codebooks
+            .transcode(8, 4, &slice, None, PixelFormat::ETC2RGB8Unorm)
+            .unwrap();
+        let pixels = decode_blocks(PixelFormat::ETC2RGB8Unorm, 8, 4, &blocks).unwrap();
+        assert_eq!(pixels, expected_pixels());
+    }
+
+    #[test]
+    fn transcode_etc1s_to_bc1() {
+        let (codebooks, slice) = test_codebooks();
+        let blocks = codebooks
+            .transcode(8, 4, &slice, None, PixelFormat::BC1RGBAUnorm)
+            .unwrap();
+        let pixels = decode_blocks(PixelFormat::BC1RGBAUnorm, 8, 4, &blocks).unwrap();
+        for (a, b) in pixels.iter().zip(expected_pixels()) {
+            assert!((*a as i32 - b as i32).abs() < 48);
+        }
+    }
+
+    #[test]
+    fn transcode_etc1s_with_alpha() {
+        let (codebooks, slice) = test_codebooks();
+        let pixels = codebooks
+            .transcode(8, 4, &slice, Some(&slice), PixelFormat::RGBA8Unorm)
+            .unwrap();
+        for pixel in pixels.chunks(4) {
+            assert_eq!(pixel[3], pixel[1]);
+        }
+    }
+
+    #[test]
+    fn picks_transcode_target() {
+        let formats = [PixelFormat::BC1RGBAUnorm, PixelFormat::BC3RGBAUnorm];
+        assert_eq!(
+            basis_transcode_target(false, &formats),
+            PixelFormat::BC1RGBAUnorm
+        );
+        assert_eq!(
+            basis_transcode_target(true, &formats),
+            PixelFormat::BC3RGBAUnorm
+        );
+        assert_eq!(basis_transcode_target(true, &[]), PixelFormat::RGBA8Unorm);
+    }
+}
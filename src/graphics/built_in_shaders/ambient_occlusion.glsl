@@ -0,0 +1,84 @@
+#VERTEX 
+
+#INCLUDE fullscreen_vertex
+
+#FRAGMENT
+
+in vec2 TexCoords;
+
+uniform sampler2D p_depth_texture;
+// The fraction of p_depth_texture that's in use.
+uniform vec2 p_depth_texture_scale;
+uniform vec2 p_view_size;
+
+uniform mat4 p_projection;
+uniform mat4 p_inverse_projection;
+
+uniform float p_radius;
+uniform float p_intensity;
+uniform float p_bias;
+
+out vec4 color_out;
+
+const int SAMPLE_COUNT = 16;
+const float GOLDEN_ANGLE = 2.39996323;
+const float TAU = 6.28318530718;
+
+// Reconstructs a view-space position from the depth at screen coordinates from 0.0 to 1.0.
+vec3 view_position(vec2 coordinates)
+{
+    coordinates = clamp(coordinates, 0.0, 1.0);
+    float depth = texture(p_depth_texture, coordinates * p_depth_texture_scale).r;
+    vec4 position = p_inverse_projection * vec4(vec3(coordinates, depth) * 2.0 - 1.0, 1.0);
+    return position.xyz / position.w;
+}
+
+void main()
+{
+    float depth = texture(p_depth_texture, TexCoords * p_depth_texture_scale).r;
+    if (depth >= 1.0) {
+        // Nothing was drawn here so there's nothing to occlude.
+        color_out = vec4(1.0, 65000.0, 0.0, 1.0);
+        return;
+    }
+    vec3 center = view_position(TexCoords);
+
+    // Reconstruct the normal from neighboring depths.
+    // The neighbor closest in depth is used so normals don't bend across edges.
+    vec2 texel = 1.0 / p_view_size;
+    vec3 left = view_position(TexCoords - vec2(texel.x, 0.0));
+    vec3 right = view_position(TexCoords + vec2(texel.x, 0.0));
+    vec3 down = view_position(TexCoords - vec2(0.0, texel.y));
+    vec3 up = view_position(TexCoords + vec2(0.0, texel.y));
+    vec3 dx = abs(right.z - center.z) < abs(center.z - left.z) ? right - center : center - left;
+    vec3 dy = abs(up.z - center.z) < abs(center.z - down.z) ? up - center : center - down;
+    vec3 normal = normalize(cross(dx, dy));
+
+    // Rotate the samples per-pixel with interleaved gradient noise. The blur smooths out the noise.
+    float angle = TAU * fract(52.9829189 * fract(dot(gl_FragCoord.xy, vec2(0.06711056, 0.00583715))));
+    vec3 random_direction = vec3(cos(angle), sin(angle), 0.0);
+    vec3 tangent = normalize(random_direction - normal * dot(random_direction, normal));
+    mat3 tangent_to_view = mat3(tangent, cross(normal, tangent), normal);
+
+    float occlusion = 0.0;
+    for (int i = 0; i < SAMPLE_COUNT; i++) {
+        // Spiral points over the hemisphere, with more samples close to the center.
+        float t = (float(i) + 0.5) / float(SAMPLE_COUNT);
+        float spiral_angle = float(i) * GOLDEN_ANGLE;
+        vec3 direction = vec3(vec2(cos(spiral_angle), sin(spiral_angle)) * sqrt(t), sqrt(1.0 - t));
+        vec3 sample_position = center + tangent_to_view * direction * p_radius * mix(0.1, 1.0, t * t);
+
+        vec4 projected = p_projection * vec4(sample_position, 1.0);
+        vec2 sample_coordinates = projected.xy / projected.w * 0.5 + 0.5;
+        float scene_depth = view_position(sample_coordinates).z;
+
+        // Ignore occluders far in front of this pixel so objects don't darken what's far behind them.
+        float range_check = smoothstep(0.0, 1.0, p_radius / abs(center.z - scene_depth));
+        occlusion += (scene_depth >= sample_position.z + p_bias ? 1.0 : 0.0) * range_check;
+    }
+
+    float visibility = pow(1.0 - occlusion / float(SAMPLE_COUNT), p_intensity);
+
+    // The view depth is stored for the blur.
+    color_out = vec4(visibility, -center.z, 0.0, 1.0);
+}
@@ -0,0 +1,43 @@
+#VERTEX 
+
+#INCLUDE fullscreen_vertex
+
+#FRAGMENT
+
+in vec2 TexCoords;
+
+// Visibility is in the red channel and view depth is in the green channel.
+uniform sampler2D p_texture;
+// The fraction of p_texture that's in use.
+uniform vec2 p_used_texture_scale;
+// The distance between samples in texture coordinates.
+uniform vec2 p_direction;
+
+out vec4 color_out;
+
+const int RADIUS = 4;
+// How quickly samples stop contributing as their depth differs from the center's.
+const float DEPTH_SENSITIVITY = 20.0;
+
+void main()
+{
+    vec2 center_coordinates = TexCoords * p_used_texture_scale;
+    vec2 center = texture(p_texture, center_coordinates).rg;
+
+    float sum = 0.0;
+    float total_weight = 0.0;
+    for (int i = -RADIUS; i <= RADIUS; i++) {
+        vec2 coordinates = clamp(center_coordinates + p_direction * float(i), vec2(0.0), p_used_texture_scale);
+        vec2 value = texture(p_texture, coordinates).rg;
+
+        // Bilateral weights keep occlusion from bleeding across depth edges.
+        float spatial_weight = exp(-float(i * i) / (2.0 * 2.5 * 2.5));
+        float depth_weight = exp(-abs(value.g - center.g) / max(center.g, 0.0001) * DEPTH_SENSITIVITY);
+        float weight = spatial_weight * depth_weight;
+
+        sum += value.r * weight;
+        total_weight += weight;
+    }
+
+    color_out = vec4(sum / total_weight, center.g, 0.0, 1.0);
+}
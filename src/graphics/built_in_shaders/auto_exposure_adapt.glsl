@@ -0,0 +1,24 @@
+#VERTEX 
+
+#INCLUDE fullscreen_vertex
+
+#FRAGMENT
+
+// The scene's average log luminance this frame.
+uniform sampler2D p_average_luminance;
+// The log luminance adapted to last frame.
+uniform sampler2D p_previous_luminance;
+
+uniform float p_min_luminance_log2;
+uniform float p_max_luminance_log2;
+// How far to move towards the average this frame. 1.0 adapts immediately.
+uniform float p_adaptation;
+
+out vec4 color_out;
+
+void main()
+{
+    float target = clamp(texelFetch(p_average_luminance, ivec2(0, 0), 0).r, p_min_luminance_log2, p_max_luminance_log2);
+    float previous = texelFetch(p_previous_luminance, ivec2(0, 0), 0).r;
+    color_out = vec4(mix(previous, target, p_adaptation), 0.0, 0.0, 1.0);
+}
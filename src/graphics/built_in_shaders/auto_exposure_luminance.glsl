@@ -0,0 +1,30 @@
+#VERTEX 
+
+#INCLUDE fullscreen_vertex
+
+#FRAGMENT
+
+in vec2 TexCoords;
+
+uniform sampler2D p_texture;
+// The size of the area of p_texture covered by each output pixel.
+uniform vec2 p_pixel_footprint;
+
+out vec4 color_out;
+
+void main()
+{
+    // Average a grid of samples so that small bright areas aren't missed.
+    float sum = 0.0;
+    for (int x = 0; x < 4; x++) {
+        for (int y = 0; y < 4; y++) {
+            vec2 offset = (vec2(x, y) + 0.5) / 4.0 - 0.5;
+            vec3 color = texture(p_texture, TexCoords + offset * p_pixel_footprint).rgb;
+            float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+
+            // Averaging log luminance keeps a few very bright pixels from dominating.
+            sum += log2(max(luminance, 0.0001));
+        }
+    }
+    color_out = vec4(sum / 16.0, 0.0, 0.0, 1.0);
+}
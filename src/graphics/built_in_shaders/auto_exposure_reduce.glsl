@@ -0,0 +1,24 @@
+#VERTEX 
+
+#INCLUDE fullscreen_vertex
+
+#FRAGMENT
+
+uniform sampler2D p_texture;
+
+out vec4 color_out;
+
+// How many texels along each axis are averaged into one.
+const int REDUCTION = 8;
+
+void main()
+{
+    ivec2 start = ivec2(gl_FragCoord.xy) * REDUCTION;
+    float sum = 0.0;
+    for (int x = 0; x < REDUCTION; x++) {
+        for (int y = 0; y < REDUCTION; y++) {
+            sum += texelFetch(p_texture, start + ivec2(x, y), 0).r;
+        }
+    }
+    color_out = vec4(sum / float(REDUCTION * REDUCTION), 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,24 @@
+#VERTEX
+
+in vec3 a_position;
+
+uniform mat4 p_model;
+uniform mat4 p_views[1];
+uniform mat4 p_projections[1];
+
+void main()
+{
+    gl_Position = p_projections[0] * p_views[0] * p_model * vec4(a_position, 1.0);
+}
+
+#FRAGMENT
+
+// The entity's RenderFlags packed into 8-bit channels, lowest bits first.
+uniform vec4 p_render_flags;
+
+out vec4 color_out;
+
+void main()
+{
+    color_out = p_render_flags;
+}
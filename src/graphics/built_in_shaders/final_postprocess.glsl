@@ -1,4 +1,4 @@
-#VERTEX 
+#VERTEX
 
 #INCLUDE fullscreen_vertex
 
@@ -6,10 +6,37 @@
 
 in vec2 TexCoords;
 
+// The scene, which may have been anti-aliased into a texture of a different size than p_blurred_texture.
+// It's sampled at gl_FragCoord instead of TexCoords.
 uniform sampler2D p_texture;
+// The fraction of p_texture that's in use.
+uniform vec2 p_scene_texture_scale;
 uniform sampler2D p_blurred_texture;
 
 uniform float p_bloom_strength;
+
+// Scene colors are multiplied by this.
+uniform float p_exposure;
+// If 1 p_exposure is also scaled by the adapted log luminance in p_exposure_texture.
+uniform int p_automatic_exposure;
+uniform sampler2D p_exposure_texture;
+
+uniform vec3 p_white_balance;
+
+// 0 is none, 1 is Reinhard, 2 is ACES fitted, 3 is AgX, and 4 is Uncharted 2.
+uniform int p_tonemapping;
+
+// A color-grading lookup table laid out as a horizontal strip of square slices, one per blue value.
+uniform sampler2D p_lut;
+uniform int p_use_lut;
+
+uniform vec2 p_viewport_size;
+uniform float p_vignette_intensity;
+uniform float p_vignette_smoothness;
+
+uniform float p_film_grain;
+uniform int p_frame;
+
 out vec4 color_out;
 
 // Portal 2 Screenspace dithering (modified for VR):
@@ -23,21 +50,149 @@ vec3 ScreenSpaceDither( vec2 vScreenPos )
 
 const float DITHER_SCALE = 4.0;
 
+// The scene luminance that automatic exposure maps to middle gray.
+const float MIDDLE_GRAY = 0.18;
+
+// https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+// Stephen Hill's fit, from: https://github.com/TheRealMJP/BakingLab/blob/master/BakingLab/ACES.hlsl
+const mat3 ACES_INPUT = mat3(
+    0.59719, 0.07600, 0.02840,
+    0.35458, 0.90834, 0.13383,
+    0.04823, 0.01566, 0.83777
+);
+const mat3 ACES_OUTPUT = mat3(
+     1.60475, -0.10208, -0.00327,
+    -0.53108,  1.10813, -0.07276,
+    -0.07367, -0.00605,  1.07602
+);
+
+vec3 aces_fitted(vec3 color)
+{
+    color = ACES_INPUT * color;
+    vec3 a = color * (color + 0.0245786) - 0.000090537;
+    vec3 b = color * (0.983729 * color + 0.4329510) + 0.238081;
+    return clamp(ACES_OUTPUT * (a / b), 0.0, 1.0);
+}
+
+// Benjamin Wrensch's minimal AgX: https://iolite-engine.com/blog_posts/minimal_agx_implementation
+vec3 agx(vec3 color)
+{
+    const mat3 AGX_INPUT = mat3(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104
+    );
+    const mat3 AGX_OUTPUT = mat3(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116
+    );
+    const float MIN_EV = -12.47393;
+    const float MAX_EV = 4.026069;
+
+    color = AGX_INPUT * color;
+    color = clamp(log2(max(color, vec3(1e-10))), MIN_EV, MAX_EV);
+    color = (color - MIN_EV) / (MAX_EV - MIN_EV);
+
+    // A polynomial fit of AgX's default contrast curve.
+    vec3 x2 = color * color;
+    vec3 x4 = x2 * x2;
+    color = 15.5 * x4 * x2 - 40.14 * x4 * color + 31.96 * x4 - 6.868 * x2 * color + 0.4298 * x2 + 0.1191 * color - 0.00232;
+
+    // AgX outputs display-encoded colors so convert back to linear.
+    color = AGX_OUTPUT * color;
+    return pow(max(color, vec3(0.0)), vec3(2.2));
+}
+
+// http://filmicworlds.com/blog/filmic-tonemapping-operators/
+vec3 uncharted2_curve(vec3 x)
+{
+    const float A = 0.15;
+    const float B = 0.50;
+    const float C = 0.10;
+    const float D = 0.20;
+    const float E = 0.02;
+    const float F = 0.30;
+    return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+vec3 uncharted2(vec3 color)
+{
+    const float WHITE_POINT = 11.2;
+    const float EXPOSURE_BIAS = 2.0;
+    return uncharted2_curve(color * EXPOSURE_BIAS) / uncharted2_curve(vec3(WHITE_POINT));
+}
+
+vec3 tonemap(vec3 color)
+{
+    if (p_tonemapping == 1) {
+        return color / (color + vec3(1.0));
+    } else if (p_tonemapping == 2) {
+        return aces_fitted(color);
+    } else if (p_tonemapping == 3) {
+        return agx(color);
+    } else if (p_tonemapping == 4) {
+        return uncharted2(color);
+    }
+    return color;
+}
+
+// Looks up a color in the color-grading table, interpolating between its slices.
+vec3 apply_lut(vec3 color)
+{
+    float size = float(textureSize(p_lut, 0).y);
+    color = clamp(color, 0.0, 1.0);
+
+    float blue = color.b * (size - 1.0);
+    float slice = floor(blue);
+    float next_slice = min(slice + 1.0, size - 1.0);
+
+    // Sample texel centers so that slices don't bleed into each-other.
+    vec2 coordinates = (color.rg * (size - 1.0) + 0.5) / vec2(size * size, size);
+    vec3 a = texture(p_lut, coordinates + vec2(slice / size, 0.0)).rgb;
+    vec3 b = texture(p_lut, coordinates + vec2(next_slice / size, 0.0)).rgb;
+    return mix(a, b, blue - slice);
+}
+
+// Noise that changes every frame, from 0.0 to 1.0.
+// http://www.iryoku.com/next-generation-post-processing-in-call-of-duty-advanced-warfare
+float interleaved_gradient_noise(vec2 position)
+{
+    position += float(p_frame % 64) * vec2(47.0, 17.0);
+    return fract(52.9829189 * fract(dot(position, vec2(0.06711056, 0.00583715))));
+}
+
 void main()
 {
-    color_out = texture(p_texture, TexCoords);
+    vec2 screen_position = gl_FragCoord.xy / p_viewport_size;
+    color_out = texture(p_texture, screen_position * p_scene_texture_scale);
     float alpha = color_out.a;
 
-    // Bloom 
+    // Bloom
     color_out = mix(color_out, texture(p_blurred_texture, TexCoords), p_bloom_strength);
 
-    // Reinhard tonemap
-   // color_out.rgb = color_out.rgb / (color_out.rgb + vec3(1.0));
+    float exposure = p_exposure;
+    if (p_automatic_exposure == 1) {
+        exposure *= MIDDLE_GRAY / exp2(texelFetch(p_exposure_texture, ivec2(0, 0), 0).r);
+    }
+    color_out.rgb *= exposure * p_white_balance;
+
+    color_out.rgb = tonemap(color_out.rgb);
+
+    // Color-grading tables expect sRGB encoded colors.
+    if (p_use_lut == 1) {
+        color_out.rgb = pow(apply_lut(pow(color_out.rgb, vec3(1.0/2.2))), vec3(2.2));
+    }
+
+    // Vignette
+    float distance_from_center = length(screen_position - 0.5) * sqrt(2.0);
+    color_out.rgb *= 1.0 - p_vignette_intensity * smoothstep(1.0 - p_vignette_smoothness, 1.0, distance_from_center);
 
     // Premultiply alpha
     color_out.rgb *= alpha;
 
-    color_out.rgb = pow(color_out.rgb, vec3(1.0/2.2)); 
+    color_out.rgb = pow(color_out.rgb, vec3(1.0/2.2));
+    color_out.rgb += (interleaved_gradient_noise(gl_FragCoord.xy) - 0.5) * p_film_grain;
     color_out.rgb += ScreenSpaceDither(gl_FragCoord.xy) * DITHER_SCALE;
     color_out.a = alpha;
-}
\ No newline at end of file
+}
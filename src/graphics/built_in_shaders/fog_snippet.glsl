@@ -0,0 +1,45 @@
+
+// Set from the camera's `Fog` component. 0 means no fog, then 1 is linear, 2 is exponential
+// and 3 is exponential-height.
+uniform int p_fog_mode;
+uniform vec4 p_fog_color;
+uniform float p_fog_start;
+uniform float p_fog_end;
+uniform float p_fog_density;
+uniform float p_fog_height_falloff;
+uniform float p_fog_base_height;
+uniform int p_fog_use_environment_color;
+// The reflection probe's specular irradiance map.
+uniform samplerCube p_fog_environment_map;
+
+// How much of a surface at `world_position` is hidden by fog.
+float FogAmount(vec3 world_position, vec3 camera_position)
+{
+    float distance_to_camera = length(world_position - camera_position);
+    if (p_fog_mode == 1) {
+        return clamp((distance_to_camera - p_fog_start) / (p_fog_end - p_fog_start), 0.0, 1.0);
+    } else if (p_fog_mode == 2) {
+        return 1.0 - exp(-p_fog_density * distance_to_camera);
+    } else if (p_fog_mode == 3) {
+        // The fog's density integrated along the view ray.
+        float falloff = p_fog_height_falloff * (world_position.y - camera_position.y);
+        float ray_factor = abs(falloff) > 0.0001 ? (1.0 - exp(-falloff)) / falloff : 1.0;
+        float density_at_camera = p_fog_density * exp(-p_fog_height_falloff * (camera_position.y - p_fog_base_height));
+        return clamp(1.0 - exp(-density_at_camera * distance_to_camera * ray_factor), 0.0, 1.0);
+    }
+    return 0.0;
+}
+
+vec3 ApplyFog(vec3 color, vec3 world_position, vec3 camera_position)
+{
+    if (p_fog_mode == 0) {
+        return color;
+    }
+
+    vec3 fog_color = p_fog_color.rgb;
+    if (p_fog_use_environment_color == 1) {
+        // The blurriest level keeps the fog from showing sharp details of the sky.
+        fog_color *= textureLod(p_fog_environment_map, normalize(world_position - camera_position), 4.0).rgb;
+    }
+    return mix(color, fog_color, FogAmount(world_position, camera_position));
+}
@@ -0,0 +1,70 @@
+#VERTEX
+
+#INCLUDE fullscreen_vertex
+
+#FRAGMENT
+
+// The scene, which is sampled at gl_FragCoord so p_texture_coordinate_scale is unused.
+uniform sampler2D p_texture;
+// The fraction of p_texture that's in use.
+uniform vec2 p_used_texture_scale;
+uniform vec2 p_view_size;
+
+out vec4 color_out;
+
+// Based on Timothy Lottes' FXAA 3.11, simplified to a single search along the edge.
+const float FXAA_SPAN_MAX = 8.0;
+const float FXAA_REDUCE_MUL = 1.0 / 8.0;
+const float FXAA_REDUCE_MIN = 1.0 / 128.0;
+
+vec4 sample_scene(vec2 coordinates)
+{
+    return texture(p_texture, clamp(coordinates, vec2(0.0), vec2(1.0)) * p_used_texture_scale);
+}
+
+// The scene is HDR so edges are detected on a compressed, perceptual luminance.
+float luma(vec3 color)
+{
+    return sqrt(dot(color / (color + vec3(1.0)), vec3(0.299, 0.587, 0.114)));
+}
+
+void main()
+{
+    vec2 texel = 1.0 / p_view_size;
+    vec2 coordinates = gl_FragCoord.xy * texel;
+
+    vec4 center = sample_scene(coordinates);
+    float luma_center = luma(center.rgb);
+    float luma_north_west = luma(sample_scene(coordinates + vec2(-1.0, -1.0) * texel).rgb);
+    float luma_north_east = luma(sample_scene(coordinates + vec2(1.0, -1.0) * texel).rgb);
+    float luma_south_west = luma(sample_scene(coordinates + vec2(-1.0, 1.0) * texel).rgb);
+    float luma_south_east = luma(sample_scene(coordinates + vec2(1.0, 1.0) * texel).rgb);
+
+    float luma_min = min(luma_center, min(min(luma_north_west, luma_north_east), min(luma_south_west, luma_south_east)));
+    float luma_max = max(luma_center, max(max(luma_north_west, luma_north_east), max(luma_south_west, luma_south_east)));
+
+    // The direction along the edge.
+    vec2 direction = vec2(
+        -((luma_north_west + luma_north_east) - (luma_south_west + luma_south_east)),
+        (luma_north_west + luma_south_west) - (luma_north_east + luma_south_east)
+    );
+    float direction_reduce = max(
+        (luma_north_west + luma_north_east + luma_south_west + luma_south_east) * 0.25 * FXAA_REDUCE_MUL,
+        FXAA_REDUCE_MIN
+    );
+    float inverse_direction_min = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+    direction = clamp(direction * inverse_direction_min, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * texel;
+
+    vec4 a = 0.5 * (
+        sample_scene(coordinates + direction * (1.0 / 3.0 - 0.5)) +
+        sample_scene(coordinates + direction * (2.0 / 3.0 - 0.5))
+    );
+    vec4 b = a * 0.5 + 0.25 * (
+        sample_scene(coordinates + direction * -0.5) +
+        sample_scene(coordinates + direction * 0.5)
+    );
+
+    // If the wider search crossed another edge fall back to the narrower one.
+    float luma_b = luma(b.rgb);
+    color_out = (luma_b < luma_min || luma_b > luma_max) ? a : b;
+}
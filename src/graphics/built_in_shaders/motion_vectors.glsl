@@ -0,0 +1,34 @@
+#VERTEX
+
+in vec3 a_position;
+
+uniform mat4 p_model;
+uniform mat4 p_previous_model;
+uniform mat4 p_view_projection;
+uniform mat4 p_previous_view_projection;
+
+out vec4 current_position;
+out vec4 previous_position;
+
+void main()
+{
+    current_position = p_view_projection * p_model * vec4(a_position, 1.0);
+    previous_position = p_previous_view_projection * p_previous_model * vec4(a_position, 1.0);
+    gl_Position = current_position;
+}
+
+#FRAGMENT
+
+in vec4 current_position;
+in vec4 previous_position;
+
+out vec4 color_out;
+
+void main()
+{
+    // How far this surface moved across the screen since last frame, in texture coordinates.
+    vec2 motion = (current_position.xy / current_position.w - previous_position.xy / previous_position.w) * 0.5;
+
+    // Alpha marks pixels with a surface. Other pixels are reprojected from depth.
+    color_out = vec4(motion, 0.0, 1.0);
+}
@@ -0,0 +1,38 @@
+#VERTEX
+
+in vec3 a_position;
+in vec3 a_normal;
+
+uniform mat4 p_model;
+uniform mat4 p_views[1];
+uniform mat4 p_projections[1];
+
+uniform vec2 p_view_size;
+// How far, in pixels, to push the surface outwards. 0.0 when drawing the mask.
+uniform float p_width;
+
+void main()
+{
+    mat4 view_model = p_views[0] * p_model;
+    gl_Position = p_projections[0] * view_model * vec4(a_position, 1.0);
+
+    // Push the vertex outwards along its normal as seen on screen so the width is the same at any distance.
+    vec3 view_normal = transpose(inverse(mat3(view_model))) * a_normal;
+    vec2 screen_normal = (p_projections[0] * vec4(view_normal, 0.0)).xy * p_view_size;
+    float screen_normal_length = length(screen_normal);
+    if (screen_normal_length > 0.0001) {
+        gl_Position.xy += screen_normal / screen_normal_length * p_width * 2.0 / p_view_size * gl_Position.w;
+    }
+}
+
+#FRAGMENT
+
+uniform vec4 p_color;
+
+out vec4 color_out;
+
+void main()
+{
+    // Premultiplied so the outlines can be blended over the scene.
+    color_out = vec4(p_color.rgb * p_color.a, p_color.a);
+}
@@ -0,0 +1,73 @@
+#VERTEX
+
+uniform mat4 p_views[NUM_VIEWS];
+uniform mat4 p_projections[NUM_VIEWS];
+
+in vec3 a_position;
+in vec2 a_texture_coordinate;
+
+// Each particle's columns are: position and size, color, texture coordinate offset and scale.
+in mat4 a_instance_model;
+
+out vec2 TexCoords;
+out vec4 ParticleColor;
+out float ViewDepth;
+
+void main()
+{
+    vec4 position_and_size = a_instance_model[0];
+    vec4 view_position = p_views[0] * vec4(position_and_size.xyz, 1.0);
+
+    // Offset the corners in view space so the quad always faces the camera.
+    view_position.xy += a_position.xy * position_and_size.w;
+    ViewDepth = -view_position.z;
+
+    ParticleColor = a_instance_model[1];
+    TexCoords = a_texture_coordinate * a_instance_model[2].zw + a_instance_model[2].xy;
+    gl_Position = p_projections[0] * view_position;
+}
+
+#FRAGMENT
+
+in vec2 TexCoords;
+in vec4 ParticleColor;
+in float ViewDepth;
+
+uniform sampler2D p_texture;
+
+// The scene's depth. Only used if p_use_depth_texture is 1.
+uniform sampler2D p_depth_texture;
+uniform int p_use_depth_texture;
+uniform mat4 p_inverse_projection;
+
+// How far particles fade out in front of opaque surfaces. 0.0 disables soft particles.
+uniform float p_soft_distance;
+// If 1 the particle adds to what's behind it instead of covering it.
+uniform int p_additive;
+
+out vec4 color_out;
+
+void main()
+{
+    vec4 color = ParticleColor * texture(p_texture, TexCoords);
+
+    if (p_use_depth_texture == 1) {
+        vec2 screen_coordinates = gl_FragCoord.xy / vec2(textureSize(p_depth_texture, 0));
+        float depth = texture(p_depth_texture, screen_coordinates).r;
+        vec4 scene_position = p_inverse_projection * vec4(0.0, 0.0, depth * 2.0 - 1.0, 1.0);
+        float distance_to_scene = -scene_position.z / scene_position.w - ViewDepth;
+
+        if (p_soft_distance > 0.0) {
+            color.a *= clamp(distance_to_scene / p_soft_distance, 0.0, 1.0);
+        } else if (distance_to_scene < 0.0) {
+            discard;
+        }
+    }
+
+    // Output premultiplied alpha.
+    color.rgb *= color.a;
+    if (p_additive == 1) {
+        color.a = 0.0;
+    }
+    color_out = color;
+}
@@ -4,10 +4,16 @@
 
 #FRAGMENT
 
+#ifdef PROJECTED_DECAL
+#INCLUDE projected_decal
+#else
 in vec2 TexCoords;
 in vec3 WorldPosition;  
 in vec3 Normal;
+in vec4 Tangent;
 in vec4 VertexColor;
+in float ViewDepth;
+#endif
 
 out vec4 color_out;
 
@@ -27,18 +33,45 @@ uniform sampler2D p_base_color_texture;
 uniform sampler2D p_metallic_roughness_texture;
 
 uniform sampler2D p_normal_texture;
+uniform float p_alpha_cutoff;
 uniform sampler2D p_ambient_texture;
 uniform sampler2D p_emissive_texture;
 
-uniform vec3 p_camera_positions[1];
+#ifdef SPLAT_MAP
+// The splat map's red, green and blue channels weight the first three layers
+// and the fourth layer fills in the rest. The splat map's alpha isn't used.
+// The layers take the texture units of the textures they replace.
+uniform sampler2D p_splat_map;
+uniform sampler2D p_layer_0_texture;
+uniform sampler2D p_layer_1_texture;
+uniform sampler2D p_layer_2_texture;
+uniform sampler2D p_layer_3_texture;
+// How many times each layer's texture repeats across the splat map.
+uniform vec4 p_layer_tiling;
+uniform vec4 p_layer_roughness;
+uniform vec4 p_layer_metallic;
+#endif
 
-uniform int p_light_count;
+uniform vec3 p_camera_positions[1];
 
 uniform float p_dither_scale;
 
-uniform vec4 p_fog_color;
-uniform float p_fog_start;
-uniform float p_fog_end;
+// Cross-fades between levels of detail. 0.0 means no cross-fade.
+// A positive value draws that fraction of pixels and a negative value draws the remaining pixels.
+uniform float p_lod_fade;
+
+void LevelOfDetailFade()
+{
+    if (p_lod_fade != 0.0) {
+        // Interleaved gradient noise
+        highp float noise = fract(52.9829189 * fract(dot(gl_FragCoord.xy, vec2(0.06711056, 0.00583715))));
+        if ((p_lod_fade > 0.0) == (noise >= abs(p_lod_fade))) {
+            discard;
+        }
+    }
+}
+
+#INCLUDE fog
 
 struct Light {
     vec3 position;
@@ -46,14 +79,32 @@ struct Light {
     int mode;
     vec3 color_and_intensity;
     float radius;
-    int shadows_enabled;
     float ambient;
-    float ibl_shadowing;
+    // Past this distance the light is faded out.
+    float range;
+    // Spot light properties.
+    float inner_cos;
+    float outer_cos;
     // Ambient light added to shadows only
     // vec3 shadow_color;
 };  
 
-uniform Light p_lights[4];
+// Lights are stored in a data texture with 4 texels per light.
+uniform highp sampler2D p_light_data;
+
+// Lights are assigned to clusters of the view so each pixel only loops over nearby lights.
+// The first texels store (offset, count) for each cluster followed by light indices packed 4 per texel.
+// See `light_clusters.rs`.
+uniform highp sampler2D p_light_clusters;
+uniform vec3 p_cluster_grid;
+uniform float p_cluster_z_near;
+uniform float p_cluster_z_far;
+uniform vec2 p_cluster_screen_size;
+
+// Only one light casts shadows. This is -1 if no light does.
+uniform int p_shadow_light_index;
+uniform float p_shadow_bias;
+uniform float p_shadow_ibl_shadowing;
 
 // sampler2Ds can't be in structs (some drivers support it, but not all)
 // so store them separately.
@@ -68,12 +119,22 @@ uniform mat4 p_world_to_light_space_1;
 uniform mat4 p_world_to_light_space_2;
 uniform mat4 p_world_to_light_space_3;
 
+// Point lights store the distance to the closest surface in a cube map.
+uniform samplerCube p_light_point_shadow_map;
+uniform float p_light_point_shadow_range;
+
 uniform samplerCube p_irradiance_map;
 uniform samplerCube p_prefilter_map;
 uniform sampler2D p_brdf_lookup_table;
 
+// Screen-space ambient occlusion. This is white if it's disabled.
+uniform sampler2D p_ambient_occlusion_texture;
+// Converts gl_FragCoord to p_ambient_occlusion_texture's texture coordinates.
+uniform vec2 p_ambient_occlusion_scale;
+
 uniform vec4 p_cascade_depths;
-const float biases[4] = float[4](0.0001, 0.0001, 0.0002, 0.0004);
+// Further cascades cover more of the world per texel so they need more bias.
+const float cascade_bias_scales[4] = float[4](0.5, 0.5, 1.0, 2.0);
 
 
 // Up to 4 cascades are supported.
@@ -84,28 +145,43 @@ const float biases[4] = float[4](0.0001, 0.0001, 0.0002, 0.0004);
 const float PI = 3.14159265359;
 
 // ----------------------------------------------------------------------------
-// Easy trick to get tangent-normals to world-space to keep PBR code simplified.
-// Don't worry if you don't get what's going on; you generally want to do normal 
-// mapping the usual way for performance anways; I do plan make a note of this 
-// technique somewhere later in the normal mapping tutorial.
-vec3 getNormalFromMap()
+// Transforms the normal map's tangent-space normal to world-space.
+// `N` must be normalized.
+vec3 getNormalFromMap(vec3 N)
 {
     vec3 tangentNormal = texture(p_normal_texture, TexCoords).xyz * 2.0 - 1.0;
 
-    vec3 Q1  = dFdx(WorldPosition);
-    vec3 Q2  = dFdy(WorldPosition);
-    vec2 st1 = dFdx(TexCoords);
-    vec2 st2 = dFdy(TexCoords);
-
-    vec3 N   = normalize(Normal);
-    
-    // This line means that meshes without UVs will not be able to calculate their normal.
-    vec3 T  = normalize(Q1*st2.t - Q2*st1.t);
-    vec3 B  = -normalize(cross(N, T));
+    vec3 T;
+    vec3 B;
+    if (dot(Tangent.xyz, Tangent.xyz) > 0.0) {
+        // Use the mesh's MikkTSpace tangents, which match the space normal maps are baked in.
+        // The bitangent is rebuilt per-pixel as MikkTSpace expects.
+        #ifdef PROJECTED_DECAL
+        // Decals draw the back of their box so gl_FrontFacing doesn't describe the surface.
+        float handedness = Tangent.w;
+        #else
+        float handedness = gl_FrontFacing ? Tangent.w : -Tangent.w;
+        #endif
+        T = normalize(Tangent.xyz - N * dot(N, Tangent.xyz));
+        B = cross(N, T) * handedness;
+    } else {
+        // Without tangents derive them from screen-space derivatives.
+        vec3 Q1  = dFdx(WorldPosition);
+        vec3 Q2  = dFdy(WorldPosition);
+        vec2 st1 = dFdx(TexCoords);
+        vec2 st2 = dFdy(TexCoords);
+
+        // Meshes without UVs can't be normal mapped.
+        vec3 derived_tangent = Q1*st2.t - Q2*st1.t;
+        if (dot(derived_tangent, derived_tangent) <= 0.0) {
+            return N;
+        }
+        T = normalize(derived_tangent);
+        B = -normalize(cross(N, T));
+    }
     mat3 TBN = mat3(T, B, N);
 
     return normalize(TBN * tangentNormal);
-  // return N;
 }
 
 const float MEDIUMP_FLOAT_MAX = 65504.0;
@@ -210,39 +286,137 @@ float ShadowCalculation(in sampler2D shadowMap, vec4 fragPosLightSpace, vec3 lig
     return shadow;
 }
 
+// This must match `ClusterGrid::depth_slice` in `light_clusters.rs`.
+int LightClusterIndex()
+{
+    vec2 tile = floor(gl_FragCoord.xy / p_cluster_screen_size * p_cluster_grid.xy);
+    tile = clamp(tile, vec2(0.0), p_cluster_grid.xy - 1.0);
+
+    float slice = 0.0;
+    if (ViewDepth > p_cluster_z_near) {
+        slice = floor(log(ViewDepth / p_cluster_z_near) / log(p_cluster_z_far / p_cluster_z_near) * p_cluster_grid.z);
+    }
+    slice = clamp(slice, 0.0, p_cluster_grid.z - 1.0);
+
+    return int(tile.x + tile.y * p_cluster_grid.x + slice * p_cluster_grid.x * p_cluster_grid.y);
+}
+
+vec4 LightClusterTexel(int index)
+{
+    int width = textureSize(p_light_clusters, 0).x;
+    return texelFetch(p_light_clusters, ivec2(index % width, index / width), 0);
+}
+
+Light GetLight(int index)
+{
+    vec4 position_and_mode = texelFetch(p_light_data, ivec2(0, index), 0);
+    vec4 direction_and_range = texelFetch(p_light_data, ivec2(1, index), 0);
+    vec4 color_and_ambient = texelFetch(p_light_data, ivec2(2, index), 0);
+    vec4 spot_and_radius = texelFetch(p_light_data, ivec2(3, index), 0);
+
+    Light light;
+    light.position = position_and_mode.xyz;
+    light.mode = int(position_and_mode.w);
+    light.direction = direction_and_range.xyz;
+    light.range = direction_and_range.w;
+    light.color_and_intensity = color_and_ambient.rgb;
+    light.ambient = color_and_ambient.a;
+    light.inner_cos = spot_and_radius.x;
+    light.outer_cos = spot_and_radius.y;
+    light.radius = spot_and_radius.z;
+    return light;
+}
+
+float PointShadowCalculation(vec3 light_to_fragment, float bias)
+{
+    float current_distance = length(light_to_fragment);
+    if (current_distance > p_light_point_shadow_range) {
+        return 0.0;
+    }
+    float closest_distance = texture(p_light_point_shadow_map, light_to_fragment).r * p_light_point_shadow_range;
+    // The bias is scaled because distances are in world units instead of depth buffer units.
+    return current_distance - bias * p_light_point_shadow_range > closest_distance ? 1.0 : 0.0;
+}
+
 
 void main()
 {
+    LevelOfDetailFade();
+    #ifdef PROJECTED_DECAL
+    ProjectDecal();
+    vec3 normal = Normal;
+    // Used to pick a shadow cascade.
+    float z = ViewDepth;
+    #else
     vec3 normal = gl_FrontFacing ? Normal : Normal * -1.0;
-   
+    // Used to pick a shadow cascade.
     float z = gl_FragCoord.z / gl_FragCoord.w;
-    float fog_factor = (z - p_fog_start) / (p_fog_end - p_fog_start);
-    fog_factor = clamp(fog_factor, 0.0, 1.0 );
-    
+    #endif
+
     float alpha = 1.0;
 
     // reflectance equation
     vec3 Lo = vec3(0.0);
     
+    #ifdef PROJECTED_DECAL
+    // Projected decals leave the emissive and ambient textures' units free for the scene's depth.
+    vec3 emissive = p_emissive;
+    #else
+    #ifdef SPLAT_MAP
+    vec3 emissive = p_emissive;
+    #else
     vec3 emissive = p_emissive * texture(p_emissive_texture, TexCoords).rgb;
+    #endif
+    #endif
     vec3 debug_color = vec3(0.0);
 
+    #ifdef SPLAT_MAP
+    vec3 splat = texture(p_splat_map, TexCoords).rgb;
+    vec4 splat_weights = vec4(splat, max(1.0 - splat.r - splat.g - splat.b, 0.0));
+    splat_weights /= dot(splat_weights, vec4(1.0));
+    vec4 layer_color =
+        texture(p_layer_0_texture, TexCoords * p_layer_tiling.x) * splat_weights.x +
+        texture(p_layer_1_texture, TexCoords * p_layer_tiling.y) * splat_weights.y +
+        texture(p_layer_2_texture, TexCoords * p_layer_tiling.z) * splat_weights.z +
+        texture(p_layer_3_texture, TexCoords * p_layer_tiling.w) * splat_weights.w;
+    // Laid out like a metallic roughness texture: roughness in green and metallic in blue.
+    vec4 metallic_roughness = vec4(0.0, dot(splat_weights, p_layer_roughness), dot(splat_weights, p_layer_metallic), 1.0);
+    vec4 base_color_rgba = p_base_color * layer_color * VertexColor;
+    #else
     vec4 metallic_roughness = texture(p_metallic_roughness_texture, TexCoords);
     vec4 base_color_rgba = (p_base_color * texture(p_base_color_texture, TexCoords) * VertexColor);
+    #endif
     vec3 base_color = base_color_rgba.rgb;
     alpha = base_color_rgba.a;
+    #ifdef ALPHA_CLIP
+    if (alpha < p_alpha_cutoff) {
+        discard;
+    }
+    alpha = 1.0;
+    #endif
 
     float metallic  = p_metallic * metallic_roughness.b;
     float roughness = p_roughness * metallic_roughness.g;
+    #ifdef PROJECTED_DECAL
+    float ambient_amount = p_ambient;
+    #else
+    #ifdef SPLAT_MAP
+    float ambient_amount = p_ambient;
+    #else
     float ambient_amount = p_ambient * texture(p_ambient_texture, TexCoords).r;
+    #endif
+    #endif
 
     // vec3 base_color = (p_base_color).rgb;
     //  float metallic  = 1.0 - p_metallic;
     //  float roughness = p_roughness;
 
     // When interpolating between face normals the normal can get shorted, so renormalize here.
+    #ifdef NORMAL_MAP
+    vec3 N = getNormalFromMap(normalize(normal));
+    #else
     vec3 N = normalize(normal);
-    // vec3 N = getNormalFromMap();
+    #endif
     vec3 V = normalize(p_camera_positions[0] - WorldPosition);
 
     // calculate reflectance at normal incidence; if dia-electric (like plastic) use F0 
@@ -257,12 +431,15 @@ void main()
 
     float ibl_scale = 1.0;
 
-        // For loop commented out because Safari performs poorly with it.
-         //for(int i = 0; i < p_light_count; ++i) 
-        if (p_light_count >= 1)
+        vec4 cluster = LightClusterTexel(LightClusterIndex());
+        int light_offset = int(cluster.r);
+        int cluster_light_count = int(cluster.g);
+
+        for(int k = 0; k < cluster_light_count; ++k)
         {
-            int i = 0;
-            Light light = p_lights[i];        
+            int light_index = light_offset + k;
+            int i = int(LightClusterTexel(light_index / 4)[light_index % 4]);
+            Light light = GetLight(i);
 
             // calculate per-light radiance
             vec3 L;// = light.position - WorldPosition;
@@ -272,7 +449,7 @@ void main()
 
             if (light.mode == 0) {
                 L = normalize(-light.direction);
-            } else if (light.mode == 1) {
+            } else {
                 L = normalize(light.position - WorldPosition);
             }
 
@@ -289,7 +466,15 @@ void main()
             if (light.mode == 0) {
                 attenuation = 1.0;
             } else if (light.mode == 1) {
-                attenuation = 1.0 / (distance * distance);
+                // Fade out towards the end of the range so the light can be clustered.
+                float range_falloff = clamp(1.0 - pow(distance / light.range, 4.0), 0.0, 1.0);
+                attenuation = range_falloff * range_falloff / (distance * distance);
+            } else {
+                // Fade out towards the edge of the cone and the end of the range.
+                float cos_angle = dot(-L, normalize(light.direction));
+                float cone = clamp((cos_angle - light.outer_cos) / max(light.inner_cos - light.outer_cos, 0.0001), 0.0, 1.0);
+                float range_falloff = clamp(1.0 - pow(distance / light.range, 4.0), 0.0, 1.0);
+                attenuation = cone * range_falloff * range_falloff / (distance * distance);
             }
 
             // debugColor = vec3(distance) / 30.;
@@ -323,7 +508,13 @@ void main()
 
             // Todo: This should *not* be hard-coded.
             float near_plane_depth = 0.3;
-            if (p_lights[i].shadows_enabled == 1) {
+            if (i == p_shadow_light_index && light.mode == 1) {
+                shadow = PointShadowCalculation(WorldPosition + N * 0.02 - light.position, p_shadow_bias);
+            } else if (i == p_shadow_light_index && light.mode == 2) {
+                // Spot lights have a single perspective shadow map.
+                vec4 light_space_position = p_world_to_light_space_0 * vec4(WorldPosition + N * 0.02, 1.0);
+                shadow = ShadowCalculation(p_light_shadow_maps_0, light_space_position, L, 0.0, p_shadow_bias);
+            } else if (i == p_shadow_light_index) {
                 // Todo: his offset needs to be scaled with cascade otherwise acne is introduced at far distances.
                 vec4 offset_world_position = vec4(WorldPosition + N * 0.1, 1.0);
                 
@@ -332,19 +523,19 @@ void main()
                 }
                 else if (z > p_cascade_depths[2]) {
                     vec4 light_space_position = p_world_to_light_space_3 * offset_world_position;
-                    shadow = ShadowCalculation(p_light_shadow_maps_3, light_space_position, L, p_cascade_depths[3] - p_cascade_depths[2], biases[3]);
+                    shadow = ShadowCalculation(p_light_shadow_maps_3, light_space_position, L, p_cascade_depths[3] - p_cascade_depths[2], p_shadow_bias * cascade_bias_scales[3]);
                     //debug_color = vec3(1.0, 0.0, 0.0);
                 } else if (z > p_cascade_depths[1]) {
                     vec4 light_space_position = p_world_to_light_space_2 * offset_world_position;
-                    shadow = ShadowCalculation(p_light_shadow_maps_2, light_space_position, L, p_cascade_depths[2] - p_cascade_depths[1], biases[2]);
+                    shadow = ShadowCalculation(p_light_shadow_maps_2, light_space_position, L, p_cascade_depths[2] - p_cascade_depths[1], p_shadow_bias * cascade_bias_scales[2]);
                     //debug_color = vec3(0.0, 1.0, 0.0);
                 } else if (z > p_cascade_depths[0]) {
                     vec4 light_space_position = p_world_to_light_space_1 * offset_world_position;
-                    shadow = ShadowCalculation(p_light_shadow_maps_1, light_space_position, L, p_cascade_depths[1] - p_cascade_depths[0], biases[1]);
+                    shadow = ShadowCalculation(p_light_shadow_maps_1, light_space_position, L, p_cascade_depths[1] - p_cascade_depths[0], p_shadow_bias * cascade_bias_scales[1]);
                     //debug_color = vec3(0.0, 0.0, 1.0);
                 } else {
                     vec4 light_space_position = p_world_to_light_space_0 * offset_world_position;
-                    shadow = ShadowCalculation(p_light_shadow_maps_0, light_space_position, L, p_cascade_depths[0] - near_plane_depth, biases[0]);
+                    shadow = ShadowCalculation(p_light_shadow_maps_0, light_space_position, L, p_cascade_depths[0] - near_plane_depth, p_shadow_bias * cascade_bias_scales[0]);
                 }
             }
 
@@ -353,7 +544,7 @@ void main()
             // add to outgoing radiance Lo
             Lo += (kD * base_color / PI + specular) * radiance * NdotL * (1.0 - shadow);  // note that we already multiplied the BRDF by the Fresnel (kS) so we won't multiply by kS again
             
-            ibl_scale = min(ibl_scale, 1.0 - (light.ibl_shadowing * shadow * attenuation));
+            ibl_scale = min(ibl_scale, 1.0 - (p_shadow_ibl_shadowing * shadow * attenuation));
 
             // Add ambient light only to the shadow
             // Lo += shadow * light.shadow_color;
@@ -380,14 +571,14 @@ void main()
         if (ibl_scale < 1.0) {
             specular = vec3(0.0);
         }
-        vec3 ambient = (kD * diffuse + specular) * ambient_amount * ibl_scale; 
+        float screen_space_ambient_occlusion = texture(p_ambient_occlusion_texture, gl_FragCoord.xy * p_ambient_occlusion_scale).r;
+        vec3 ambient = (kD * diffuse + specular) * ambient_amount * ibl_scale * screen_space_ambient_occlusion; 
 
         vec3 color = ambient + Lo;
     
-    
-    // This should be applied before the shader instead.
-    //color = mix(color, p_fog_color.rgb, fog_factor );
+
     color += emissive;
+    color = ApplyFog(color, WorldPosition, p_camera_positions[0]);
 
     // HDR tonemapping
     // color = color / (color + vec3(1.0));
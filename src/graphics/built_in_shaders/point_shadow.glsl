@@ -0,0 +1,31 @@
+#VERTEX 
+
+in vec3 a_position;
+
+uniform mat4 p_model;
+uniform mat4 p_views[1];
+uniform mat4 p_projections[1];
+
+out vec3 WorldPosition;
+
+void main()
+{
+    WorldPosition = vec3(p_model * vec4(a_position, 1.0));
+    gl_Position = p_projections[0] * p_views[0] * vec4(WorldPosition, 1.0);
+}
+
+#FRAGMENT
+
+in vec3 WorldPosition;
+
+out vec4 color_out;
+
+uniform vec3 p_light_position;
+uniform float p_range;
+
+void main()
+{
+    // Store the linear distance to the light so the cube map can be sampled in any direction.
+    float distance = length(WorldPosition - p_light_position) / p_range;
+    color_out = vec4(distance, distance, distance, 1.0);
+}
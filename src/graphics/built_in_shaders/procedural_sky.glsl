@@ -0,0 +1,116 @@
+#VERTEX
+
+uniform mat4 p_views[NUM_VIEWS];
+uniform mat4 p_projections[NUM_VIEWS];
+
+in vec3 a_position;
+
+out vec3 local_position;
+
+void main()
+{
+    local_position = a_position;
+    gl_Position = p_projections[0] * p_views[0] * vec4(a_position, 1.0);
+}
+
+#FRAGMENT
+
+out vec4 color_out;
+in vec3 local_position;
+
+// Points towards the sun.
+uniform vec3 p_sun_direction;
+// Haziness of the atmosphere. 2.0 is a clear day and 10.0 is very hazy.
+uniform float p_turbidity;
+uniform float p_intensity;
+// The sun's angular radius in radians.
+uniform float p_sun_size;
+uniform vec3 p_ground_color;
+
+// The Preetham sky model:
+// "A Practical Analytic Model for Daylight" by Preetham, Shirley and Smits.
+
+// The Perez sky luminance distribution.
+// theta is the angle from the zenith and gamma is the angle from the sun.
+float Perez(float cos_theta, float gamma, float cos_gamma, float A, float B, float C, float D, float E)
+{
+    return (1.0 + A * exp(B / cos_theta)) * (1.0 + C * exp(D * gamma) + E * cos_gamma * cos_gamma);
+}
+
+// Returns the sky's color in CIE xyY with Y in kilocandelas per square meter.
+vec3 SkyxyY(float cos_theta, float gamma, float cos_gamma, float theta_sun)
+{
+    float T = p_turbidity;
+    float T2 = T * T;
+    float s = theta_sun;
+    float s2 = s * s;
+    float s3 = s2 * s;
+
+    float chi = (4.0 / 9.0 - T / 120.0) * (3.14159265 - 2.0 * s);
+    vec3 zenith = vec3(
+        T2 * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s)
+            + T * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s + 0.00394)
+            + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s + 0.25886),
+        T2 * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s)
+            + T * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s + 0.00516)
+            + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s + 0.26688),
+        (4.0453 * T - 4.9710) * tan(chi) - 0.2155 * T + 2.4192
+    );
+
+    float cos_sun = cos(s);
+    float x = Perez(cos_theta, gamma, cos_gamma,
+        -0.0193 * T - 0.2592, -0.0665 * T + 0.0008, -0.0004 * T + 0.2125, -0.0641 * T - 0.8989, -0.0033 * T + 0.0452)
+        / Perez(1.0, s, cos_sun,
+        -0.0193 * T - 0.2592, -0.0665 * T + 0.0008, -0.0004 * T + 0.2125, -0.0641 * T - 0.8989, -0.0033 * T + 0.0452);
+    float y = Perez(cos_theta, gamma, cos_gamma,
+        -0.0167 * T - 0.2608, -0.0950 * T + 0.0092, -0.0079 * T + 0.2102, -0.0441 * T - 1.6537, -0.0109 * T + 0.0529)
+        / Perez(1.0, s, cos_sun,
+        -0.0167 * T - 0.2608, -0.0950 * T + 0.0092, -0.0079 * T + 0.2102, -0.0441 * T - 1.6537, -0.0109 * T + 0.0529);
+    float Y = Perez(cos_theta, gamma, cos_gamma,
+        0.1787 * T - 1.4630, -0.3554 * T + 0.4275, -0.0227 * T + 5.3251, 0.1206 * T - 2.5771, -0.0670 * T + 0.3703)
+        / Perez(1.0, s, cos_sun,
+        0.1787 * T - 1.4630, -0.3554 * T + 0.4275, -0.0227 * T + 5.3251, 0.1206 * T - 2.5771, -0.0670 * T + 0.3703);
+
+    return zenith * vec3(x, y, Y);
+}
+
+vec3 xyYToLinearSRGB(vec3 xyY)
+{
+    float Y = xyY.z;
+    float X = xyY.x / xyY.y * Y;
+    float Z = (1.0 - xyY.x - xyY.y) / xyY.y * Y;
+    return max(vec3(
+        3.2406 * X - 1.5372 * Y - 0.4986 * Z,
+        -0.9689 * X + 1.8758 * Y + 0.0415 * Z,
+        0.0557 * X - 0.2040 * Y + 1.0570 * Z
+    ), vec3(0.0));
+}
+
+void main()
+{
+    vec3 direction = normalize(local_position);
+    vec3 sun_direction = normalize(p_sun_direction);
+
+    // The model breaks down when the sun or view is below the horizon so both are clamped to it.
+    float theta_sun = acos(clamp(sun_direction.y, 0.01, 1.0));
+    float cos_theta = max(direction.y, 0.01);
+    vec3 sky_direction = normalize(vec3(direction.x, cos_theta, direction.z));
+    float cos_gamma = clamp(dot(sky_direction, normalize(vec3(sun_direction.x, cos(theta_sun), sun_direction.z))), -1.0, 1.0);
+    float gamma = acos(cos_gamma);
+
+    // Scale kilocandelas to values close to 1.0 for a clear daytime sky.
+    vec3 sky = xyYToLinearSRGB(SkyxyY(cos_theta, gamma, cos_gamma, theta_sun)) * 0.1;
+
+    // Add the sun's disk, tinted by the sky around it.
+    float sun_disk = smoothstep(cos(p_sun_size * 1.2), cos(p_sun_size), dot(direction, sun_direction));
+    sky += sky * sun_disk * 50.0;
+
+    // Fade into the ground below the horizon.
+    vec3 ground = p_ground_color * (max(sun_direction.y, 0.0) + 0.05) * 0.5;
+    sky = mix(sky, ground, 1.0 - smoothstep(-0.05, 0.0, direction.y));
+
+    // Dim the sky as the sun sets below the horizon.
+    sky *= mix(0.01, 1.0, smoothstep(-0.1, 0.05, sun_direction.y));
+
+    color_out = vec4(sky * p_intensity, 1.0);
+}
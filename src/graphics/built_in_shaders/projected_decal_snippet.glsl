@@ -0,0 +1,71 @@
+// Projected decals shade the surface in the scene's depth texture instead of their own box.
+// These replace the inputs from the vertex shader and are filled in by `ProjectDecal`.
+vec2 TexCoords;
+vec3 WorldPosition;
+vec3 Normal;
+vec4 Tangent;
+vec4 VertexColor;
+float ViewDepth;
+
+uniform sampler2D p_decal_depth_texture;
+uniform vec2 p_decal_screen_size;
+uniform mat4 p_decal_inverse_projection;
+uniform mat4 p_decal_inverse_view;
+
+// Transforms from world space into the decal's box, which spans -0.5 to 0.5 on each axis.
+uniform mat4 p_decal_world_to_local;
+// The decal's +Z and +X axes in world space. The decal projects along -Z.
+uniform vec3 p_decal_back;
+uniform vec3 p_decal_right;
+// The cosines of the angles between a surface and the decal where the decal starts and finishes fading out.
+uniform vec2 p_decal_angle_fade;
+uniform float p_decal_opacity;
+
+// Each pixel's RenderFlags packed into 8-bit channels, lowest bits first.
+// Only used if p_decal_use_layers is 1.
+uniform sampler2D p_decal_layers_texture;
+uniform int p_decal_use_layers;
+// Surfaces receive the decal if they're on any of p_decal_layers and none of p_decal_excluded_layers.
+uniform int p_decal_layers;
+uniform int p_decal_excluded_layers;
+
+void ProjectDecal()
+{
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    float depth = texelFetch(p_decal_depth_texture, pixel, 0).r;
+
+    vec4 clip_position = vec4(gl_FragCoord.xy / p_decal_screen_size * 2.0 - 1.0, depth * 2.0 - 1.0, 1.0);
+    vec4 view_position = p_decal_inverse_projection * clip_position;
+    view_position /= view_position.w;
+    ViewDepth = -view_position.z;
+    WorldPosition = (p_decal_inverse_view * view_position).xyz;
+
+    // The surface's normal is rebuilt from its neighboring pixels.
+    // Derivatives must be taken before any pixel is discarded.
+    vec3 surface_normal = normalize(cross(dFdx(WorldPosition), dFdy(WorldPosition)));
+
+    vec3 local_position = (p_decal_world_to_local * vec4(WorldPosition, 1.0)).xyz;
+    if (any(greaterThan(abs(local_position), vec3(0.5)))) {
+        discard;
+    }
+
+    if (p_decal_use_layers == 1) {
+        uvec4 bytes = uvec4(round(texelFetch(p_decal_layers_texture, pixel, 0) * 255.0));
+        int layers = int(bytes.r | (bytes.g << 8u) | (bytes.b << 16u) | (bytes.a << 24u));
+        if ((layers & p_decal_layers) == 0 || (layers & p_decal_excluded_layers) != 0) {
+            discard;
+        }
+    }
+
+    float facing = dot(surface_normal, p_decal_back);
+    float fade = clamp((facing - p_decal_angle_fade.y) / max(p_decal_angle_fade.x - p_decal_angle_fade.y, 0.0001), 0.0, 1.0);
+    if (fade <= 0.0) {
+        discard;
+    }
+
+    Normal = surface_normal;
+    // The texture is laid out like a Mesh::VERTICAL_QUAD facing +Z so its bitangent points down.
+    TexCoords = vec2(local_position.x + 0.5, 0.5 - local_position.y);
+    Tangent = vec4(p_decal_right, -1.0);
+    VertexColor = vec4(1.0, 1.0, 1.0, fade * p_decal_opacity);
+}
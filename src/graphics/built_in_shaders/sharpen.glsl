@@ -0,0 +1,40 @@
+#VERTEX
+
+#INCLUDE fullscreen_vertex
+
+#FRAGMENT
+
+// Sampled at gl_FragCoord so p_texture_coordinate_scale is unused.
+uniform sampler2D p_texture;
+// The fraction of p_texture that's in use.
+uniform vec2 p_used_texture_scale;
+uniform vec2 p_view_size;
+// 0.0 leaves the image unchanged.
+uniform float p_sharpness;
+
+out vec4 color_out;
+
+vec4 sample_texture(vec2 coordinates)
+{
+    return texture(p_texture, clamp(coordinates, vec2(0.0), vec2(1.0)) * p_used_texture_scale);
+}
+
+// Restores detail softened by temporal anti-aliasing with an unsharp mask of the four closest neighbors.
+void main()
+{
+    vec2 texel = 1.0 / p_view_size;
+    vec2 coordinates = gl_FragCoord.xy * texel;
+
+    vec4 center = sample_texture(coordinates);
+    vec3 north = sample_texture(coordinates + vec2(0.0, texel.y)).rgb;
+    vec3 south = sample_texture(coordinates - vec2(0.0, texel.y)).rgb;
+    vec3 east = sample_texture(coordinates + vec2(texel.x, 0.0)).rgb;
+    vec3 west = sample_texture(coordinates - vec2(texel.x, 0.0)).rgb;
+
+    vec3 sharpened = center.rgb + (4.0 * center.rgb - north - south - east - west) * p_sharpness;
+
+    // Clamping to the neighborhood prevents halos around edges.
+    vec3 neighborhood_min = min(center.rgb, min(min(north, south), min(east, west)));
+    vec3 neighborhood_max = max(center.rgb, max(max(north, south), max(east, west)));
+    color_out = vec4(clamp(sharpened, neighborhood_min, neighborhood_max), center.a);
+}
@@ -0,0 +1,44 @@
+#VERTEX
+
+#ifdef MULTIVIEW
+#extension GL_OVR_multiview2 : require
+layout (num_views = 2) in;
+#endif
+
+uniform mat4 p_views[NUM_VIEWS];
+uniform mat4 p_projections[NUM_VIEWS];
+
+// Sprites are positioned on the CPU so that sprites with different transforms can share a draw.
+in vec3 a_position;
+in vec2 a_texture_coordinate;
+in vec4 a_color;
+
+out vec2 TexCoords;
+out vec4 SpriteColor;
+
+void main()
+{
+    TexCoords = a_texture_coordinate;
+    SpriteColor = a_color;
+#ifdef MULTIVIEW
+    gl_Position = p_projections[gl_ViewID_OVR] * p_views[gl_ViewID_OVR] * vec4(a_position, 1.0);
+#else
+    gl_Position = p_projections[0] * p_views[0] * vec4(a_position, 1.0);
+#endif
+}
+
+#FRAGMENT
+
+in vec2 TexCoords;
+in vec4 SpriteColor;
+
+uniform sampler2D p_texture;
+
+out vec4 color_out;
+
+void main()
+{
+    // Textures are premultiplied by their alpha, so the tint is premultiplied to match.
+    vec4 tint = vec4(SpriteColor.rgb * SpriteColor.a, SpriteColor.a);
+    color_out = tint * texture(p_texture, TexCoords);
+}
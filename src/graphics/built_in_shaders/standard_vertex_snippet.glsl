@@ -8,22 +8,63 @@ uniform mat4 p_projections[NUM_VIEWS];
 
 in vec3 a_position;
 in vec2 a_texture_coordinate;
+in vec2 a_texture_coordinate_1;
 in vec3 a_normal;
+// The w component is the handedness of the tangent space. A zero tangent means the mesh has none.
+in vec4 a_tangent;
 in vec4 a_color;
 
+#ifdef SKINNING
+#ifndef MAX_JOINTS
+#define MAX_JOINTS 64
+#endif
+// The indices of the joints that move this vertex, and how much each moves it.
+in vec4 a_joints;
+in vec4 a_weights;
+uniform mat4 p_joint_matrices[MAX_JOINTS];
+#endif
+
+// Per-instance data used when the renderer batches identical draws.
+in mat4 a_instance_model;
+in vec4 a_instance_color;
+uniform int p_instanced;
+
 uniform mat4 p_model;
 
 out vec2 TexCoords;
+out vec2 TexCoords1;
 out vec3 WorldPosition;
 out vec3 Normal;
+out vec4 Tangent;
 out vec4 VertexColor;
+// Used to find which cluster of lights a pixel is in.
+out float ViewDepth;
 
 void main()
 {
-    WorldPosition = vec3(p_model * vec4(a_position, 1.0));
-    Normal = mat3(p_model) * a_normal;
-    TexCoords = a_texture_coordinate;
+    mat4 model = p_model;
+#ifdef VERTEX_COLORS
     VertexColor = a_color;
+#else
+    VertexColor = vec4(1.0);
+#endif
+    if (p_instanced == 1) {
+        model = a_instance_model;
+        VertexColor *= a_instance_color;
+    }
+#ifdef SKINNING
+    model = model * (
+        a_weights.x * p_joint_matrices[int(a_joints.x)] +
+        a_weights.y * p_joint_matrices[int(a_joints.y)] +
+        a_weights.z * p_joint_matrices[int(a_joints.z)] +
+        a_weights.w * p_joint_matrices[int(a_joints.w)]);
+#endif
+
+    WorldPosition = vec3(model * vec4(a_position, 1.0));
+    Normal = mat3(model) * a_normal;
+    Tangent = vec4(mat3(model) * a_tangent.xyz, a_tangent.w);
+    TexCoords = a_texture_coordinate;
+    TexCoords1 = a_texture_coordinate_1;
     
     #ifdef MULTVIEW
         mat4 view = p_views[gl_ViewID_OVR];
@@ -33,6 +74,8 @@ void main()
         mat4 projection = p_projections[0];
     #endif
     
+    ViewDepth = -(view * vec4(WorldPosition, 1.0)).z;
+
     // For now share the same projection matrix between views.
-    gl_Position = projection * view * p_model * vec4(a_position, 1.0);
+    gl_Position = projection * view * model * vec4(a_position, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,119 @@
+#VERTEX
+
+#INCLUDE fullscreen_vertex
+
+#FRAGMENT
+
+// Textures are sampled at gl_FragCoord so p_texture_coordinate_scale is unused.
+
+// This frame's scene, rendered with a jittered projection.
+uniform sampler2D p_texture;
+uniform sampler2D p_depth_texture;
+// The fraction of p_texture and p_depth_texture that's in use.
+uniform vec2 p_scene_texture_scale;
+
+// Per-object motion in texture coordinates. Alpha is 0.0 where no object was drawn.
+uniform sampler2D p_motion_texture;
+uniform vec2 p_motion_texture_scale;
+
+// Last frame's anti-aliased scene.
+uniform sampler2D p_history_texture;
+uniform vec2 p_history_texture_scale;
+
+// Converts this frame's clip space to last frame's, for pixels without per-object motion.
+uniform mat4 p_reprojection;
+// How much of the history is kept. 0.0 if there's no history.
+uniform float p_history_weight;
+uniform vec2 p_view_size;
+
+out vec4 color_out;
+
+// Colors are clamped in YCoCg because its bounding boxes fit colors more tightly than RGB's.
+vec3 rgb_to_ycocg(vec3 color)
+{
+    return vec3(
+        dot(color, vec3(0.25, 0.5, 0.25)),
+        dot(color, vec3(0.5, 0.0, -0.5)),
+        dot(color, vec3(-0.25, 0.5, -0.25))
+    );
+}
+
+vec3 ycocg_to_rgb(vec3 color)
+{
+    return vec3(
+        color.x + color.y - color.z,
+        color.x + color.z,
+        color.x - color.y - color.z
+    );
+}
+
+vec4 sample_scene(vec2 coordinates)
+{
+    return texture(p_texture, clamp(coordinates, vec2(0.0), vec2(1.0)) * p_scene_texture_scale);
+}
+
+// Clips the history towards the center of the neighborhood's bounding box instead of clamping each channel,
+// which keeps its hue from shifting.
+vec3 clip_to_box(vec3 history, vec3 box_min, vec3 box_max)
+{
+    vec3 center = 0.5 * (box_max + box_min);
+    vec3 extents = 0.5 * (box_max - box_min) + 0.0001;
+    vec3 offset = history - center;
+    vec3 units = abs(offset / extents);
+    float max_unit = max(units.x, max(units.y, units.z));
+    return max_unit > 1.0 ? center + offset / max_unit : history;
+}
+
+void main()
+{
+    vec2 texel = 1.0 / p_view_size;
+    vec2 coordinates = gl_FragCoord.xy * texel;
+
+    // Gather the 3x3 neighborhood's color bounds.
+    // The closest depth is used for reprojection so that edges of moving objects aren't left behind.
+    vec4 current = sample_scene(coordinates);
+    vec3 box_min = vec3(65000.0);
+    vec3 box_max = vec3(-65000.0);
+    float closest_depth = 1.0;
+    vec2 closest_coordinates = coordinates;
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            vec2 neighbor_coordinates = coordinates + vec2(float(x), float(y)) * texel;
+            vec3 neighbor = rgb_to_ycocg(sample_scene(neighbor_coordinates).rgb);
+            box_min = min(box_min, neighbor);
+            box_max = max(box_max, neighbor);
+
+            float depth = texture(p_depth_texture, clamp(neighbor_coordinates, vec2(0.0), vec2(1.0)) * p_scene_texture_scale).r;
+            if (depth < closest_depth) {
+                closest_depth = depth;
+                closest_coordinates = neighbor_coordinates;
+            }
+        }
+    }
+
+    vec2 previous_coordinates;
+    vec4 motion = texture(p_motion_texture, clamp(closest_coordinates, vec2(0.0), vec2(1.0)) * p_motion_texture_scale);
+    if (motion.a > 0.5) {
+        previous_coordinates = coordinates - motion.xy;
+    } else {
+        // Only the camera moved here.
+        vec4 previous = p_reprojection * vec4(vec3(coordinates, closest_depth) * 2.0 - 1.0, 1.0);
+        previous_coordinates = previous.xy / previous.w * 0.5 + 0.5;
+    }
+
+    float history_weight = p_history_weight;
+    if (any(lessThan(previous_coordinates, vec2(0.0))) || any(greaterThan(previous_coordinates, vec2(1.0)))) {
+        // The history has nothing for areas that just came into view.
+        history_weight = 0.0;
+    }
+
+    vec3 history = texture(p_history_texture, previous_coordinates * p_history_texture_scale).rgb;
+    history = ycocg_to_rgb(clip_to_box(rgb_to_ycocg(history), box_min, box_max));
+
+    // Weigh by inverse luminance so that bright, flickering pixels don't dominate the blend.
+    float current_weight = (1.0 - history_weight) / (1.0 + rgb_to_ycocg(current.rgb).x);
+    history_weight = history_weight / (1.0 + rgb_to_ycocg(history).x);
+    vec3 color = (current.rgb * current_weight + history * history_weight) / max(current_weight + history_weight, 0.0001);
+
+    color_out = vec4(color, current.a);
+}
@@ -21,9 +21,36 @@ uniform vec4 p_base_color;
 // Physically based rendering textures
 // These are multipled by the corresponding properties.
 uniform sampler2D p_base_color_texture;
+uniform float p_alpha_cutoff;
+
+uniform vec3 p_camera_positions[1];
+
+#INCLUDE fog
+
+// Cross-fades between levels of detail. 0.0 means no cross-fade.
+// A positive value draws that fraction of pixels and a negative value draws the remaining pixels.
+uniform float p_lod_fade;
+
+void LevelOfDetailFade()
+{
+    if (p_lod_fade != 0.0) {
+        // Interleaved gradient noise
+        highp float noise = fract(52.9829189 * fract(dot(gl_FragCoord.xy, vec2(0.06711056, 0.00583715))));
+        if ((p_lod_fade > 0.0) == (noise >= abs(p_lod_fade))) {
+            discard;
+        }
+    }
+}
 
 void main()
 {
+  LevelOfDetailFade();
   vec4 base_color = (VertexColor * p_base_color * texture(p_base_color_texture, TexCoords * p_texture_coordinate_scale + p_texture_coordinate_offset));
-  color_out = base_color;
+  #ifdef ALPHA_CLIP
+  if (base_color.a < p_alpha_cutoff) {
+    discard;
+  }
+  base_color.a = 1.0;
+  #endif
+  color_out = vec4(ApplyFog(base_color.rgb, WorldPosition, p_camera_positions[0]), base_color.a);
 }
\ No newline at end of file
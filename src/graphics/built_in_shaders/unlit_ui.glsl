@@ -1,5 +1,7 @@
 #VERTEX 
 
+// UI meshes are colored per-vertex.
+#define VERTEX_COLORS
 #INCLUDE standard_vertex
 
 #FRAGMENT
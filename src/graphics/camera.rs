@@ -33,6 +33,32 @@ pub struct Camera {
     #[skip]
     pub camera_target: Option<CameraTarget>,
     pub post_processing_enabled: bool,
+    /// How edges are smoothed. Only used if `post_processing_enabled` is `true`.
+    pub anti_aliasing: AntiAliasing,
+}
+
+/// How a [Camera] smooths jagged edges.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AntiAliasing {
+    None,
+    /// Multisample anti-aliasing. Smooths the edges of geometry but not aliasing from shading,
+    /// and is expensive on WebGL.
+    Msaa,
+    /// Fast approximate anti-aliasing. A cheap pass that blurs along edges it detects in the image.
+    Fxaa,
+    /// Accumulates jittered frames over time, which smooths both geometry and shading.
+    /// Moving objects may leave faint trails.
+    Temporal {
+        /// How much detail softened by accumulation is restored. 0.0 is no sharpening.
+        sharpness: f32,
+    },
+}
+
+impl AntiAliasing {
+    /// [AntiAliasing::Temporal] with reasonable defaults.
+    pub fn temporal() -> Self {
+        AntiAliasing::Temporal { sharpness: 0.25 }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -73,6 +99,7 @@ impl Camera {
             camera_target: Some(CameraTarget::Primary),
             resolution_scale: 1.0,
             post_processing_enabled: true,
+            anti_aliasing: AntiAliasing::Msaa,
         };
         camera.update_projection_matrix();
         camera
@@ -165,11 +192,25 @@ impl Camera {
         self.update_projection_matrix();
     }
 
+    pub fn get_far_plane(&self) -> f32 {
+        self.z_far
+    }
+
     // Returns projection matrix
     pub fn projection_matrix(&self) -> Mat4 {
         self.projection_matrix
     }
 
+    /// The projection matrix offset by a fraction of a pixel.
+    /// Used by [AntiAliasing::Temporal] to sample a different part of each pixel every frame.
+    pub fn jittered_projection_matrix(&self, offset_in_pixels: Vec2) -> Mat4 {
+        // Pixels are larger than the view's when `resolution_scale` is more than 1.0.
+        let offset = (offset_in_pixels * 2.0 * self.resolution_scale)
+            .div_by_component(Vec2::new(self.view_width as f32, self.view_height as f32));
+        // Translating in clip space shifts everything on screen by the same amount, whatever its depth.
+        Mat4::from_translation(offset.extend(0.0)) * self.projection_matrix
+    }
+
     // Useful for shadow maps
     pub fn projection_matrix_with_z_near_and_z_far(&self, z_near: f32, z_far: f32) -> Mat4 {
         let aspect_ratio = self.view_width as f32 / self.view_height as f32;
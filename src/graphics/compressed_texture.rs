@@ -0,0 +1,486 @@
+//! Shared support for GPU block compressed textures loaded from `.ktx2` and `.dds` files.
+//!
+//! Compressed data is uploaded as is when the backend can sample its [PixelFormat],
+//! otherwise the blocks are decoded to [PixelFormat::RGBA8Unorm] on the CPU.
+
+use crate::*;
+use kgraphics::*;
+
+#[derive(Debug, Clone)]
+pub enum CompressedTextureError {
+    /// The file's header is invalid or the file is truncated.
+    InvalidFile,
+    /// The file stores pixels in a format that can't be loaded.
+    UnsupportedFormat(String),
+    /// The file uses a supercompression scheme that can't be decoded.
+    UnsupportedSupercompression(u32),
+    /// Basis Universal data could not be transcoded.
+    InvalidBasisData,
+    /// The backend can't sample this format and it can't be decoded on the CPU.
+    CannotDecode(PixelFormat),
+}
+
+/// Every block compressed [PixelFormat].
+pub const COMPRESSED_PIXEL_FORMATS: [PixelFormat; 9] = [
+    PixelFormat::BC1RGBAUnorm,
+    PixelFormat::BC2RGBAUnorm,
+    PixelFormat::BC3RGBAUnorm,
+    PixelFormat::BC4RUnorm,
+    PixelFormat::BC5RGUnorm,
+    PixelFormat::BC6HRGBUfloat,
+    PixelFormat::BC7RGBAUnorm,
+    PixelFormat::ETC2RGB8Unorm,
+    PixelFormat::ETC2RGBA8Unorm,
+];
+
+/// Lists the block compressed [PixelFormat]s the backend can sample.
+pub fn supported_compressed_pixel_formats(graphics: &Graphics) -> Vec<PixelFormat> {
+    COMPRESSED_PIXEL_FORMATS
+        .iter()
+        .copied()
+        .filter(|f| graphics.context.supports_pixel_format(*f))
+        .collect()
+}
+
+/// The contents of a texture container: pre-baked mip levels of one or six faces.
+pub(crate) struct CompressedImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: PixelFormat,
+    /// `None` if the file doesn't specify a color space.
+    pub srgb: Option<bool>,
+    pub faces: u32,
+    /// Each level stores its faces one after another, starting with the full size level.
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    pub fn into_texture_load_data(
+        mut self,
+        options: &mut TextureSettings,
+        supported_pixel_formats: &[PixelFormat],
+    ) -> Result<TextureLoadData, CompressedTextureError> {
+        // Only formats that store color can be sRGB.
+        let can_be_srgb = matches!(
+            self.pixel_format,
+            PixelFormat::RGBA8Unorm
+                | PixelFormat::BC1RGBAUnorm
+                | PixelFormat::BC2RGBAUnorm
+                | PixelFormat::BC3RGBAUnorm
+                | PixelFormat::BC7RGBAUnorm
+                | PixelFormat::ETC2RGB8Unorm
+                | PixelFormat::ETC2RGBA8Unorm
+        );
+
+        if self.pixel_format.is_compressed()
+            && !supported_pixel_formats.contains(&self.pixel_format)
+        {
+            self.decode_to_rgba8()?;
+        }
+
+        if let Some(srgb) = self.srgb {
+            options.srgb = srgb;
+        }
+        options.srgb &= can_be_srgb;
+
+        // Mipmaps can't be generated for compressed data and pre-baked mipmaps are used as is.
+        if self.levels.len() > 1 || self.pixel_format.is_compressed() {
+            options.generate_mipmaps = false;
+        }
+
+        Ok(TextureLoadData {
+            data: TextureData::MipLevels {
+                levels: self.levels,
+                faces: self.faces,
+            },
+            pixel_format: self.pixel_format,
+            width: self.width,
+            height: self.height,
+        })
+    }
+
+    fn decode_to_rgba8(&mut self) -> Result<(), CompressedTextureError> {
+        for (level, data) in self.levels.iter_mut().enumerate() {
+            let width = (self.width >> level).max(1);
+            let height = (self.height >> level).max(1);
+            let face_length = data.len() / self.faces as usize;
+
+            let mut decoded = Vec::with_capacity((width * height * 4 * self.faces) as usize);
+            for face in data.chunks(face_length.max(1)) {
+                decoded.extend(decode_blocks(self.pixel_format, width, height, face)?);
+            }
+            *data = decoded;
+        }
+        self.pixel_format = PixelFormat::RGBA8Unorm;
+        Ok(())
+    }
+}
+
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, CompressedTextureError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(CompressedTextureError::InvalidFile)
+}
+
+pub(crate) fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, CompressedTextureError> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(CompressedTextureError::InvalidFile)
+}
+
+pub(crate) fn read_bytes(
+    bytes: &[u8],
+    offset: usize,
+    length: usize,
+) -> Result<&[u8], CompressedTextureError> {
+    bytes
+        .get(offset..offset.saturating_add(length))
+        .ok_or(CompressedTextureError::InvalidFile)
+}
+
+/// The number of bytes a compressed image of this size takes.
+pub(crate) fn compressed_size(pixel_format: PixelFormat, width: u32, height: u32) -> usize {
+    let blocks = (width as usize).div_ceil(4) * (height as usize).div_ceil(4);
+    blocks * pixel_format.block_bytes().unwrap_or(0)
+}
+
+/// Decodes block compressed data to [PixelFormat::RGBA8Unorm] pixels.
+pub(crate) fn decode_blocks(
+    pixel_format: PixelFormat,
+    width: u32,
+    height: u32,
+    data: &[u8],
+) -> Result<Vec<u8>, CompressedTextureError> {
+    let block_bytes = pixel_format
+        .block_bytes()
+        .ok_or(CompressedTextureError::CannotDecode(pixel_format))?;
+    if data.len() < compressed_size(pixel_format, width, height) {
+        return Err(CompressedTextureError::InvalidFile);
+    }
+
+    let (width, height) = (width as usize, height as usize);
+    let blocks_x = width.div_ceil(4);
+    let mut pixels = vec![0; width * height * 4];
+
+    for (i, block) in data
+        .chunks_exact(block_bytes)
+        .take(blocks_x * height.div_ceil(4))
+        .enumerate()
+    {
+        let decoded = match pixel_format {
+            PixelFormat::BC1RGBAUnorm => decode_bc1_block(block, true),
+            PixelFormat::BC2RGBAUnorm => {
+                let mut decoded = decode_bc1_block(&block[8..], false);
+                for (j, pixel) in decoded.iter_mut().enumerate() {
+                    pixel[3] = ((block[j / 2] >> ((j % 2) * 4)) & 0xF) * 17;
+                }
+                decoded
+            }
+            PixelFormat::BC3RGBAUnorm => {
+                let mut decoded = decode_bc1_block(&block[8..], false);
+                for (pixel, alpha) in decoded.iter_mut().zip(decode_bc4_block(block)) {
+                    pixel[3] = alpha;
+                }
+                decoded
+            }
+            PixelFormat::BC4RUnorm => decode_bc4_block(block).map(|r| [r, 0, 0, 255]),
+            PixelFormat::BC5RGUnorm => {
+                let red = decode_bc4_block(block);
+                let green = decode_bc4_block(&block[8..]);
+                let mut decoded = [[0, 0, 0, 255]; 16];
+                for j in 0..16 {
+                    decoded[j][0] = red[j];
+                    decoded[j][1] = green[j];
+                }
+                decoded
+            }
+            PixelFormat::ETC2RGB8Unorm => decode_etc1_block(block)
+                .ok_or(CompressedTextureError::CannotDecode(pixel_format))?,
+            _ => return Err(CompressedTextureError::CannotDecode(pixel_format)),
+        };
+
+        let (block_x, block_y) = (i % blocks_x, i / blocks_x);
+        for (j, pixel) in decoded.iter().enumerate() {
+            let (x, y) = (block_x * 4 + j % 4, block_y * 4 + j / 4);
+            if x < width && y < height {
+                let index = (y * width + x) * 4;
+                pixels[index..index + 4].copy_from_slice(pixel);
+            }
+        }
+    }
+    Ok(pixels)
+}
+
+fn color_565_to_rgb(color: u16) -> [u8; 3] {
+    let r = (color >> 11) as u8 & 31;
+    let g = (color >> 5) as u8 & 63;
+    let b = color as u8 & 31;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+fn rgb_to_color_565(color: [u8; 3]) -> u16 {
+    let r = (color[0] as u16 * 31 + 127) / 255;
+    let g = (color[1] as u16 * 63 + 127) / 255;
+    let b = (color[2] as u16 * 31 + 127) / 255;
+    (r << 11) | (g << 5) | b
+}
+
+fn bc1_palette(color0: u16, color1: u16, allow_transparency: bool) -> [[u8; 4]; 4] {
+    let c0 = color_565_to_rgb(color0);
+    let c1 = color_565_to_rgb(color1);
+    let mix = |a: u8, b: u8, wa: u16, wb: u16| ((a as u16 * wa + b as u16 * wb) / (wa + wb)) as u8;
+
+    let mut palette = [[0; 4]; 4];
+    palette[0] = [c0[0], c0[1], c0[2], 255];
+    palette[1] = [c1[0], c1[1], c1[2], 255];
+    if color0 > color1 || !allow_transparency {
+        for i in 0..3 {
+            palette[2][i] = mix(c0[i], c1[i], 2, 1);
+            palette[3][i] = mix(c0[i], c1[i], 1, 2);
+        }
+        palette[2][3] = 255;
+        palette[3][3] = 255;
+    } else {
+        for i in 0..3 {
+            palette[2][i] = mix(c0[i], c1[i], 1, 1);
+        }
+        palette[2][3] = 255;
+        palette[3] = [0, 0, 0, 0];
+    }
+    palette
+}
+
+/// BC2 and BC3 color blocks always use four colors.
+fn decode_bc1_block(block: &[u8], allow_transparency: bool) -> [[u8; 4]; 16] {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let palette = bc1_palette(color0, color1, allow_transparency);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    let mut pixels = [[0; 4]; 16];
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        *pixel = palette[((indices >> (i * 2)) & 3) as usize];
+    }
+    pixels
+}
+
+fn bc4_palette(value0: u8, value1: u8) -> [u8; 8] {
+    let (a, b) = (value0 as u32, value1 as u32);
+    let mut palette = [value0, value1, 0, 0, 0, 0, 0, 255];
+    if value0 > value1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * a + i as u32 * b) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * a + i as u32 * b) / 5) as u8;
+        }
+    }
+    palette
+}
+
+fn decode_bc4_block(block: &[u8]) -> [u8; 16] {
+    let palette = bc4_palette(block[0], block[1]);
+    let mut index_bytes = [0; 8];
+    index_bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(index_bytes);
+
+    let mut values = [0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[((indices >> (i * 3)) & 7) as usize];
+    }
+    values
+}
+
+/// A quick BC1 encoder that fits the block's bounding box.
+/// It's used to transcode Basis Universal data, which has already been quantized.
+pub(crate) fn encode_bc1_block(pixels: &[[u8; 4]; 16]) -> [u8; 8] {
+    let mut min = [255u8; 3];
+    let mut max = [0u8; 3];
+    for pixel in pixels {
+        for i in 0..3 {
+            min[i] = min[i].min(pixel[i]);
+            max[i] = max[i].max(pixel[i]);
+        }
+    }
+
+    let (mut color0, mut color1) = (rgb_to_color_565(max), rgb_to_color_565(min));
+    // `color0 > color1` selects four color mode.
+    if color0 < color1 {
+        std::mem::swap(&mut color0, &mut color1);
+    }
+
+    let mut indices = 0u32;
+    if color0 != color1 {
+        let palette = bc1_palette(color0, color1, false);
+        for (i, pixel) in pixels.iter().enumerate() {
+            indices |= (nearest(&palette, |p| color_distance(p, pixel)) as u32) << (i * 2);
+        }
+    }
+
+    let mut block = [0; 8];
+    block[0..2].copy_from_slice(&color0.to_le_bytes());
+    block[2..4].copy_from_slice(&color1.to_le_bytes());
+    block[4..8].copy_from_slice(&indices.to_le_bytes());
+    block
+}
+
+/// A quick BC4 encoder that fits the block's range.
+/// BC3 stores its alpha as a BC4 block.
+pub(crate) fn encode_bc4_block(values: &[u8; 16]) -> [u8; 8] {
+    let min = *values.iter().min().unwrap();
+    let max = *values.iter().max().unwrap();
+
+    let mut indices = 0u64;
+    if max != min {
+        // `max > min` selects the mode with 6 interpolated values.
+        let palette = bc4_palette(max, min);
+        for (i, value) in values.iter().enumerate() {
+            let index = nearest(&palette, |p| (*p as i32 - *value as i32).abs());
+            indices |= (index as u64) << (i * 3);
+        }
+    }
+
+    let mut block = [0; 8];
+    block[0] = max;
+    block[1] = min;
+    block[2..8].copy_from_slice(&indices.to_le_bytes()[..6]);
+    block
+}
+
+fn color_distance(a: &[u8; 4], b: &[u8; 4]) -> i32 {
+    (0..3)
+        .map(|i| (a[i] as i32 - b[i] as i32).pow(2))
+        .sum::<i32>()
+}
+
+fn nearest<T>(palette: &[T], distance: impl Fn(&T) -> i32) -> usize {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, p)| distance(p))
+        .map(|(i, _)| i)
+        .unwrap()
+}
+
+/// The ETC1 intensity modifier tables.
+pub(crate) const ETC1_INTENSITY_TABLES: [[i32; 4]; 8] = [
+    [-8, -2, 2, 8],
+    [-17, -5, 5, 17],
+    [-29, -9, 9, 29],
+    [-42, -13, 13, 42],
+    [-60, -18, 18, 60],
+    [-80, -24, 24, 80],
+    [-106, -33, 33, 106],
+    [-183, -47, 47, 183],
+];
+
+/// ETC1 stores modifier indices in the order: +small, +large, -small, -large.
+const ETC1_INDEX_TO_MODIFIER: [usize; 4] = [2, 3, 1, 0];
+
+/// Decodes an ETC1 block. ETC1 is a subset of ETC2 RGB.
+/// Returns `None` for blocks that use the ETC2-only modes.
+pub(crate) fn decode_etc1_block(block: &[u8]) -> Option<[[u8; 4]; 16]> {
+    let differential = block[3] & 2 != 0;
+    let flip = block[3] & 1 != 0;
+    let tables = [(block[3] >> 5) as usize, ((block[3] >> 2) & 7) as usize];
+
+    let mut base_colors = [[0i32; 3]; 2];
+    for c in 0..3 {
+        if differential {
+            let base = (block[c] >> 3) as i32;
+            // Sign extend the 3 bit delta.
+            let delta = (((block[c] & 7) << 5) as i8 >> 5) as i32;
+            let second = base + delta;
+            if !(0..32).contains(&second) {
+                return None;
+            }
+            base_colors[0][c] = (base << 3) | (base >> 2);
+            base_colors[1][c] = (second << 3) | (second >> 2);
+        } else {
+            base_colors[0][c] = (block[c] >> 4) as i32 * 17;
+            base_colors[1][c] = (block[c] & 15) as i32 * 17;
+        }
+    }
+
+    let msb = u16::from_be_bytes([block[4], block[5]]);
+    let lsb = u16::from_be_bytes([block[6], block[7]]);
+
+    let mut pixels = [[0; 4]; 16];
+    for x in 0..4 {
+        for y in 0..4 {
+            let subblock = if flip { y / 2 } else { x / 2 };
+            // Pixel indices are stored column by column.
+            let bit = x * 4 + y;
+            let index = ((((msb >> bit) & 1) << 1) | ((lsb >> bit) & 1)) as usize;
+            let modifier = ETC1_INTENSITY_TABLES[tables[subblock]][ETC1_INDEX_TO_MODIFIER[index]];
+
+            let pixel = &mut pixels[y * 4 + x];
+            for c in 0..3 {
+                pixel[c] = (base_colors[subblock][c] + modifier).clamp(0, 255) as u8;
+            }
+            pixel[3] = 255;
+        }
+    }
+    Some(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bc1_round_trip() {
+        let mut pixels = [[0, 0, 0, 255]; 16];
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let v = if i % 2 == 0 { 255 } else { 0 };
+            *pixel = [v, v, 0, 255];
+        }
+        let block = encode_bc1_block(&pixels);
+        assert_eq!(decode_bc1_block(&block, true), pixels);
+    }
+
+    #[test]
+    fn bc4_round_trip() {
+        let mut values = [0; 16];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = if i < 8 { 7 } else { 200 };
+        }
+        assert_eq!(decode_bc4_block(&encode_bc4_block(&values)), values);
+    }
+
+    #[test]
+    fn bc1_three_color_mode() {
+        // color0 <= color1 makes index 3 transparent black.
+        let block = [0, 0, 0xFF, 0xFF, 0b1111_0110, 0, 0, 0];
+        let pixels = decode_bc1_block(&block, true);
+        assert_eq!(pixels[0], [127, 127, 127, 255]);
+        assert_eq!(pixels[1], [255, 255, 255, 255]);
+        assert_eq!(pixels[2], [0, 0, 0, 0]);
+        assert_eq!(pixels[4], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn decodes_partial_blocks() {
+        let block = encode_bc1_block(&[[255, 0, 0, 255]; 16]);
+        let pixels = decode_blocks(PixelFormat::BC1RGBAUnorm, 2, 3, &block).unwrap();
+        assert_eq!(pixels.len(), 2 * 3 * 4);
+        assert!(pixels.chunks(4).all(|p| p == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn etc1_individual_mode() {
+        // Left half uses color 0x8 and table 0, right half 0x2 and table 1.
+        // Every pixel uses index 0: the small positive modifier.
+        let block = [0x82, 0x82, 0x82, 0b0000_0100, 0, 0, 0, 0];
+        let pixels = decode_etc1_block(&block).unwrap();
+        assert_eq!(pixels[0], [136 + 2, 136 + 2, 136 + 2, 255]);
+        assert_eq!(pixels[3], [34 + 5, 34 + 5, 34 + 5, 255]);
+    }
+}
@@ -26,6 +26,18 @@ struct SpecularIrradianceProperties {
     a_position: VertexAttribute<Vec3>,
 }
 
+struct ProceduralSkyProperties {
+    shader: Shader,
+    projection_property: Mat4Property,
+    view_property: Mat4Property,
+    p_sun_direction: Vec3Property,
+    p_turbidity: FloatProperty,
+    p_intensity: FloatProperty,
+    p_sun_size: FloatProperty,
+    p_ground_color: Vec3Property,
+    a_position: VertexAttribute<Vec3>,
+}
+
 fn get_shader_and_properties(
     graphics: &mut Graphics,
     source: &str,
@@ -70,6 +82,7 @@ pub struct CubeMapRenderer {
     equirectangular_to_cubemap_shader: ShaderAndProperties,
     diffuse_irradiance_convolution_shader: ShaderAndProperties,
     specular_irradiance_shader: SpecularIrradianceProperties,
+    procedural_sky_shader: ProceduralSkyProperties,
 }
 
 impl CubeMapRenderer {
@@ -116,12 +129,183 @@ impl CubeMapRenderer {
                 .unwrap(),
             shader: specular_irradiance_convolution_shader,
         };
+        let procedural_sky_shader = graphics
+            .new_shader(
+                include_str!("built_in_shaders/procedural_sky.glsl"),
+                PipelineSettings {
+                    depth_test: DepthTest::LessOrEqual,
+                    ..PipelineSettings::default()
+                },
+            )
+            .unwrap();
+        let procedural_sky_shader = ProceduralSkyProperties {
+            projection_property: procedural_sky_shader
+                .pipeline
+                .get_mat4_property("p_projections[0]")
+                .unwrap(),
+            view_property: procedural_sky_shader
+                .pipeline
+                .get_mat4_property("p_views[0]")
+                .unwrap(),
+            p_sun_direction: procedural_sky_shader
+                .pipeline
+                .get_vec3_property("p_sun_direction")
+                .unwrap(),
+            p_turbidity: procedural_sky_shader
+                .pipeline
+                .get_float_property("p_turbidity")
+                .unwrap(),
+            p_intensity: procedural_sky_shader
+                .pipeline
+                .get_float_property("p_intensity")
+                .unwrap(),
+            p_sun_size: procedural_sky_shader
+                .pipeline
+                .get_float_property("p_sun_size")
+                .unwrap(),
+            p_ground_color: procedural_sky_shader
+                .pipeline
+                .get_vec3_property("p_ground_color")
+                .unwrap(),
+            a_position: procedural_sky_shader
+                .pipeline
+                .get_vertex_attribute("a_position")
+                .unwrap(),
+            shader: procedural_sky_shader,
+        };
         CubeMapRenderer {
             equirectangular_to_cubemap_shader,
             diffuse_irradiance_convolution_shader,
             specular_irradiance_shader,
+            procedural_sky_shader,
+        }
+    }
+
+    /// Renders a [ProceduralSky] lit by a sun in `sun_direction` into each face of `cube_map`.
+    pub fn render_procedural_sky(
+        &self,
+        graphics: &mut Graphics,
+        meshes: &Assets<Mesh>,
+        procedural_sky: &ProceduralSky,
+        sun_direction: Vec3,
+        cube_map: &CubeMap,
+        size: usize,
+    ) {
+        let shader = &self.procedural_sky_shader;
+        let cube_mesh = meshes.get(&Mesh::CUBE_MAP_CUBE).gpu_mesh.as_ref().unwrap();
+        let projection: Mat4 =
+            kmath::projection_matrices::perspective_gl(90.0_f32.to_radians(), 1.0, 0.1, 10.);
+        let ground_color = procedural_sky
+            .ground_color
+            .to_rgb_color(color_spaces::LINEAR_SRGB)
+            .xyz();
+
+        for (i, view) in cube_map_views().iter().enumerate() {
+            let mut command_buffer = graphics.context.new_command_buffer();
+
+            let face_texture = cube_map.get_face_texture(i);
+            let framebuffer = graphics
+                .context
+                .new_framebuffer(Some(&face_texture), None, None);
+            {
+                let mut render_pass = command_buffer
+                    .begin_render_pass_with_framebuffer(&framebuffer, Some((0.0, 0.0, 0.0, 1.0)));
+                render_pass.set_viewport(0, 0, size as u32, size as u32);
+
+                render_pass.set_pipeline(&shader.shader.pipeline);
+                render_pass.set_mat4_property(&shader.projection_property, projection.as_array());
+                render_pass.set_mat4_property(&shader.view_property, view.as_array());
+                render_pass.set_vec3_property(&shader.p_sun_direction, sun_direction.into());
+                render_pass.set_float_property(&shader.p_turbidity, procedural_sky.turbidity);
+                render_pass.set_float_property(&shader.p_intensity, procedural_sky.intensity);
+                render_pass.set_float_property(&shader.p_sun_size, procedural_sky.sun_size);
+                render_pass.set_vec3_property(&shader.p_ground_color, ground_color.into());
+                render_pass.set_vertex_attribute(&shader.a_position, Some(&cube_mesh.positions));
+
+                render_pass.draw_triangles(cube_mesh.triangle_count, &cube_mesh.index_buffer);
+            }
+            graphics.context.commit_command_buffer(command_buffer);
+            graphics.context.delete_framebuffer(framebuffer);
         }
+        graphics.context.generate_mip_map_for_cube_map(cube_map);
     }
+
+    /// Convolves `environment` into the diffuse and specular irradiance maps used for image based lighting.
+    /// The irradiance maps should be created with [new_irradiance_cube_maps].
+    pub fn render_irradiance_cube_maps(
+        &self,
+        graphics: &mut Graphics,
+        meshes: &Assets<Mesh>,
+        environment: &CubeMap,
+        diffuse_irradiance_cube_map: &CubeMap,
+        specular_irradiance_cube_map: &CubeMap,
+    ) {
+        render_cube_map(
+            graphics,
+            meshes,
+            &self.diffuse_irradiance_convolution_shader,
+            TextureIn::CubeMap(environment),
+            diffuse_irradiance_cube_map,
+            DIFFUSE_IRRADIANCE_SIZE as usize,
+        );
+        render_specular_irradiance_cube_map(
+            graphics,
+            meshes,
+            &self.specular_irradiance_shader,
+            environment,
+            specular_irradiance_cube_map,
+        );
+    }
+}
+
+const DIFFUSE_IRRADIANCE_SIZE: u32 = 32;
+const SPECULAR_IRRADIANCE_SIZE: u32 = 128;
+
+/// Creates empty diffuse and specular irradiance maps for [CubeMapRenderer::render_irradiance_cube_maps].
+pub fn new_irradiance_cube_maps(graphics: &mut Graphics) -> (CubeMap, CubeMap) {
+    let texture_settings = TextureSettings {
+        srgb: false,
+        minification_filter: FilterMode::Linear,
+        magnification_filter: FilterMode::Linear,
+        wrapping_horizontal: WrappingMode::ClampToEdge,
+        wrapping_vertical: WrappingMode::ClampToEdge,
+        generate_mipmaps: false,
+        ..Default::default()
+    };
+    let diffuse_irradiance_cube_map = graphics
+        .new_cube_map(
+            None,
+            DIFFUSE_IRRADIANCE_SIZE,
+            DIFFUSE_IRRADIANCE_SIZE,
+            PixelFormat::RGBA16F,
+            texture_settings,
+        )
+        .unwrap();
+    let specular_irradiance_cube_map = graphics
+        .new_cube_map(
+            None,
+            SPECULAR_IRRADIANCE_SIZE,
+            SPECULAR_IRRADIANCE_SIZE,
+            PixelFormat::RGBA16F,
+            TextureSettings {
+                generate_mipmaps: true,
+                ..texture_settings
+            },
+        )
+        .unwrap();
+    (diffuse_irradiance_cube_map, specular_irradiance_cube_map)
+}
+
+// I assume the -Y here is to flip the image as well.
+fn cube_map_views() -> [Mat4; 6] {
+    [
+        Mat4::looking_at(Vec3::ZERO, Vec3::X, -Vec3::Y),
+        Mat4::looking_at(Vec3::ZERO, -Vec3::X, -Vec3::Y),
+        Mat4::looking_at(Vec3::ZERO, Vec3::Y, Vec3::Z),
+        Mat4::looking_at(Vec3::ZERO, -Vec3::Y, -Vec3::Z),
+        Mat4::looking_at(Vec3::ZERO, Vec3::Z, -Vec3::Y),
+        Mat4::looking_at(Vec3::ZERO, -Vec3::Z, -Vec3::Y),
+    ]
 }
 
 enum TextureIn<'a> {
@@ -141,15 +325,7 @@ fn render_cube_map(
     let projection: Mat4 =
         kmath::projection_matrices::perspective_gl(90.0_f32.to_radians(), 1.0, 0.1, 10.);
 
-    // I assume the -Y here is to flip the image as well.
-    let views = [
-        Mat4::looking_at(Vec3::ZERO, Vec3::X, -Vec3::Y),
-        Mat4::looking_at(Vec3::ZERO, -Vec3::X, -Vec3::Y),
-        Mat4::looking_at(Vec3::ZERO, Vec3::Y, Vec3::Z),
-        Mat4::looking_at(Vec3::ZERO, -Vec3::Y, -Vec3::Z),
-        Mat4::looking_at(Vec3::ZERO, Vec3::Z, -Vec3::Y),
-        Mat4::looking_at(Vec3::ZERO, -Vec3::Z, -Vec3::Y),
-    ];
+    let views = cube_map_views();
 
     for (i, view) in views.iter().enumerate() {
         let mut command_buffer = graphics.context.new_command_buffer();
@@ -203,22 +379,14 @@ fn render_specular_irradiance_cube_map(
     environment_texture: &CubeMap,
     cube_map: &CubeMap,
 ) {
-    let size = 128.0;
+    let size = SPECULAR_IRRADIANCE_SIZE as f32;
 
     let cube_mesh = meshes.get(&Mesh::CUBE_MAP_CUBE).gpu_mesh.as_ref().unwrap();
 
     let projection: Mat4 =
         kmath::projection_matrices::perspective_gl(90.0_f32.to_radians(), 1.0, 0.1, 10.);
 
-    // I assume the -Y here is to flip the image as well.
-    let views = [
-        Mat4::looking_at(Vec3::ZERO, Vec3::X, -Vec3::Y),
-        Mat4::looking_at(Vec3::ZERO, -Vec3::X, -Vec3::Y),
-        Mat4::looking_at(Vec3::ZERO, Vec3::Y, Vec3::Z),
-        Mat4::looking_at(Vec3::ZERO, -Vec3::Y, -Vec3::Z),
-        Mat4::looking_at(Vec3::ZERO, Vec3::Z, -Vec3::Y),
-        Mat4::looking_at(Vec3::ZERO, -Vec3::Z, -Vec3::Y),
-    ];
+    let views = cube_map_views();
 
     let mip_map_levels = 5;
     for mip in 0..mip_map_levels {
@@ -309,19 +477,27 @@ pub fn load_reflection_probe_immediate(
         ..Default::default()
     };
 
-    let texture_load_data =
-        texture_load_data_from_bytes("hdr", data, &mut options.texture_settings);
-
     let new_handle = cube_maps.new_handle();
-    let cube_map_load_message = CubeMapLoadMessage {
-        handle: new_handle.clone(),
-        texture_load_data,
-        texture_settings: options.texture_settings,
-        diffuse_and_specular_irradiance_cubemaps: options.diffuse_and_specular_irradiance_cubemaps,
-        spawn_light: true,
-    };
-
-    load_cube_map_immediate(cube_maps, graphics, meshes, cube_map_load_message, commands);
+    // A probe that fails to load keeps its placeholder cube maps.
+    match texture_load_data_from_bytes("hdr", data, &mut options.texture_settings, &[]) {
+        Ok(texture_load_data) => {
+            let cube_map_load_message = CubeMapLoadMessage {
+                handle: new_handle.clone(),
+                texture_load_data,
+                texture_settings: options.texture_settings,
+                diffuse_and_specular_irradiance_cubemaps: options
+                    .diffuse_and_specular_irradiance_cubemaps,
+                spawn_light: true,
+            };
+            load_cube_map_immediate(cube_maps, graphics, meshes, cube_map_load_message, commands);
+        }
+        Err(error) => {
+            klog::log!(
+                "TEXTURE ERROR: Could not load reflection probe: {:?}",
+                error
+            );
+        }
+    }
     ReflectionProbe {
         source: new_handle,
         diffuse_irradiance_map,
@@ -348,112 +524,100 @@ fn load_cube_map_immediate(
     };
 
     if message.spawn_light {
-        let direction = find_brightest_direction(&mut message.texture_load_data);
-        commands.spawn((
-            Transform::new()
-                .with_position(direction)
-                .looking_at(Vec3::ZERO, Vec3::Y),
-            Light::new(LightMode::Directional, Color::WHITE, 0.0),
-            ShadowCaster::new().with_ibl_shadowing(0.5),
-        ))
+        if let Some(direction) = find_brightest_direction(&mut message.texture_load_data) {
+            commands.spawn((
+                Transform::new()
+                    .with_position(direction)
+                    .looking_at(Vec3::ZERO, Vec3::Y),
+                Light::new(LightMode::Directional, Color::WHITE, 0.0),
+                ShadowCaster::new().with_ibl_shadowing(0.5),
+            ));
+        }
     }
-    let pixel_format = message.texture_load_data.pixel_format;
-    // Create a GPU texture to process into the CubeMap
-    let texture =
-        new_texture_from_texture_load_data(graphics, message.texture_load_data, texture_settings);
-
-    // This needs to be true otherwise artifacts are introduced into the CubeMap.
-    // Why?
-    texture_settings.generate_mipmaps = true;
-    let face_size = 512;
-    // Hardcode the cube map's size for now.
-    let cube_map = graphics
-        .new_cube_map(None, face_size, face_size, pixel_format, texture_settings)
-        .unwrap();
-
-    render_cube_map(
-        graphics,
-        meshes,
-        &cube_maps
-            .asset_loader
-            .cube_map_renderer
-            .equirectangular_to_cubemap_shader,
-        TextureIn::Texture(&texture),
-        &cube_map,
-        face_size as usize,
-    );
-    graphics.context.generate_mip_map_for_cube_map(&cube_map);
 
-    // Manually free the texture here.
-    graphics.context.delete_texture(texture.0);
+    let cube_map = match message.texture_load_data.data {
+        // Files that already store 6 faces are uploaded directly.
+        TextureData::MipLevels { levels, faces: 6 } => {
+            let face_levels: Vec<[&[u8]; 6]> = levels
+                .iter()
+                .map(|level| {
+                    let face_length = level.len() / 6;
+                    std::array::from_fn(|face| &level[face * face_length..(face + 1) * face_length])
+                })
+                .collect();
+            texture_settings.generate_mipmaps =
+                face_levels.len() == 1 && !message.texture_load_data.pixel_format.is_compressed();
+            graphics
+                .context
+                .new_cube_map_with_mipmaps(
+                    message.texture_load_data.width,
+                    &face_levels,
+                    message.texture_load_data.pixel_format,
+                    texture_settings,
+                )
+                .unwrap()
+        }
+        data => {
+            let texture_load_data = TextureLoadData {
+                data,
+                ..message.texture_load_data
+            };
+            // The CubeMap is rendered to, so it can't use a compressed format.
+            let pixel_format = match texture_load_data.pixel_format {
+                PixelFormat::BC6HRGBUfloat => PixelFormat::RGBA16F,
+                pixel_format if pixel_format.is_compressed() => PixelFormat::RGBA8Unorm,
+                pixel_format => pixel_format,
+            };
+            // Create a GPU texture to process into the CubeMap
+            let texture =
+                new_texture_from_texture_load_data(graphics, texture_load_data, texture_settings);
+
+            // This needs to be true otherwise artifacts are introduced into the CubeMap.
+            // Why?
+            texture_settings.generate_mipmaps = true;
+            let face_size = 512;
+            // Hardcode the cube map's size for now.
+            let cube_map = graphics
+                .new_cube_map(None, face_size, face_size, pixel_format, texture_settings)
+                .unwrap();
+
+            render_cube_map(
+                graphics,
+                meshes,
+                &cube_maps
+                    .asset_loader
+                    .cube_map_renderer
+                    .equirectangular_to_cubemap_shader,
+                TextureIn::Texture(&texture),
+                &cube_map,
+                face_size as usize,
+            );
+            graphics.context.generate_mip_map_for_cube_map(&cube_map);
+
+            // Manually free the texture here.
+            graphics.context.delete_texture(texture.0);
+            cube_map
+        }
+    };
 
     // If we also want to convolute the CubeMap do so here.
     if let Some((diffuse_handle, specular_handle)) =
         message.diffuse_and_specular_irradiance_cubemaps
     {
-        let size = 32;
-        let diffuse_irradiance_cubemap = graphics
-            .new_cube_map(
-                None,
-                size,
-                size,
-                PixelFormat::RGBA16F,
-                TextureSettings {
-                    srgb: false,
-                    minification_filter: FilterMode::Linear,
-                    magnification_filter: FilterMode::Linear,
-                    wrapping_horizontal: WrappingMode::ClampToEdge,
-                    wrapping_vertical: WrappingMode::ClampToEdge,
-                    generate_mipmaps: false,
-                    ..Default::default()
-                },
-            )
-            .unwrap();
-
-        // Convolute here.
-        render_cube_map(
-            graphics,
-            meshes,
-            &cube_maps
-                .asset_loader
-                .cube_map_renderer
-                .diffuse_irradiance_convolution_shader,
-            TextureIn::CubeMap(&cube_map),
-            &diffuse_irradiance_cubemap,
-            size as usize,
-        );
+        let (diffuse_irradiance_cubemap, specular_irradiance_cubemap) =
+            new_irradiance_cube_maps(graphics);
+        cube_maps
+            .asset_loader
+            .cube_map_renderer
+            .render_irradiance_cube_maps(
+                graphics,
+                meshes,
+                &cube_map,
+                &diffuse_irradiance_cubemap,
+                &specular_irradiance_cubemap,
+            );
 
         cube_maps.replace_placeholder(&diffuse_handle, diffuse_irradiance_cubemap);
-
-        let specular_irradiance_cubemap = graphics
-            .new_cube_map(
-                None,
-                128,
-                128,
-                PixelFormat::RGBA16F,
-                TextureSettings {
-                    srgb: false,
-                    minification_filter: FilterMode::Linear,
-                    magnification_filter: FilterMode::Linear,
-                    wrapping_horizontal: WrappingMode::ClampToEdge,
-                    wrapping_vertical: WrappingMode::ClampToEdge,
-                    generate_mipmaps: true,
-                    ..Default::default()
-                },
-            )
-            .unwrap();
-
-        render_specular_irradiance_cube_map(
-            graphics,
-            meshes,
-            &cube_maps
-                .asset_loader
-                .cube_map_renderer
-                .specular_irradiance_shader,
-            &cube_map,
-            &specular_irradiance_cubemap,
-        );
-
         cube_maps.replace_placeholder(&specular_handle, specular_irradiance_cubemap);
     }
     cube_maps.replace_placeholder(&message.handle, cube_map);
@@ -464,10 +628,10 @@ pub fn load_cube_maps(
     cube_maps: &mut Assets<CubeMap>,
     graphics: &mut Graphics,
     meshes: &Assets<Mesh>,
-    commands: &mut Commands,
+    mut commands: Commands,
 ) {
     while let Ok(message) = cube_maps.asset_loader.receiver.inner().try_recv() {
-        load_cube_map_immediate(cube_maps, graphics, meshes, message, commands)
+        load_cube_map_immediate(cube_maps, graphics, meshes, message, &mut commands)
     }
 }
 
@@ -485,6 +649,7 @@ pub struct CubeMapAssetLoader {
     sender: SyncGuard<mpsc::Sender<CubeMapLoadMessage>>,
     receiver: SyncGuard<mpsc::Receiver<CubeMapLoadMessage>>,
     cube_map_renderer: CubeMapRenderer,
+    supported_pixel_formats: Vec<PixelFormat>,
 }
 
 impl CubeMapAssetLoader {
@@ -494,12 +659,20 @@ impl CubeMapAssetLoader {
             sender: SyncGuard::new(sender),
             receiver: SyncGuard::new(receiver),
             cube_map_renderer: CubeMapRenderer::new(graphics),
+            supported_pixel_formats: supported_compressed_pixel_formats(graphics),
         }
     }
+
+    pub fn cube_map_renderer(&self) -> &CubeMapRenderer {
+        &self.cube_map_renderer
+    }
 }
 
-fn find_brightest_direction(texture_load_data: &mut TextureLoadData) -> Vec3 {
-    #[allow(irrefutable_let_patterns)]
+/// Finds the brightest direction of an HDR equirectangular image and removes that pixel.
+fn find_brightest_direction(texture_load_data: &mut TextureLoadData) -> Option<Vec3> {
+    if texture_load_data.pixel_format != PixelFormat::RGBA32F {
+        return None;
+    }
     if let TextureData::Bytes(bytes) = &mut texture_load_data.data {
         let mut brightest_pixel_index = 0;
         let mut brightes_pixel = f32::MIN;
@@ -522,9 +695,9 @@ fn find_brightest_direction(texture_load_data: &mut TextureLoadData) -> Vec3 {
         let y = (pixel_y / texture_load_data.height as f32 * std::f32::consts::PI).sin();
 
         let dir = Vec3::new(x, -y, z);
-        -dir
+        Some(-dir)
     } else {
-        unreachable!()
+        None
     }
 }
 
@@ -538,10 +711,26 @@ impl AssetLoaderTrait<CubeMap> for NotSendSync<CubeMapAssetLoader> {
     ) {
         let path = path.to_owned();
         let sender = self.sender.inner().clone();
+        let supported_pixel_formats = self.supported_pixel_formats.clone();
 
         ktasks::spawn(async move {
-            let texture_load_data =
-                texture_data_from_path(&path, &mut options.texture_settings).await;
+            let texture_load_data = match texture_data_from_path(
+                &path,
+                &mut options.texture_settings,
+                &supported_pixel_formats,
+            )
+            .await
+            {
+                Ok(texture_load_data) => texture_load_data,
+                Err(error) => {
+                    klog::log!(
+                        "TEXTURE ERROR: Could not load cube map {:?}: {:?}",
+                        path,
+                        error
+                    );
+                    return;
+                }
+            };
 
             let _ = sender.send(CubeMapLoadMessage {
                 handle,
@@ -660,9 +849,9 @@ pub fn spawn_reflection_probe_immediate(world: &mut World, data: &[u8]) {
                                   graphics: &mut Graphics,
                                   meshes: &mut Assets<Mesh>,
                                   materials: &mut Assets<Material>,
-                                  commands: &mut Commands| {
+                                  mut commands: Commands| {
         let reflection_probe =
-            load_reflection_probe_immediate(cube_maps, graphics, meshes, commands, data);
+            load_reflection_probe_immediate(cube_maps, graphics, meshes, &mut commands, data);
 
         let mut material = Material::new(Shader::SKY_BOX);
         material.set_cube_map("p_environment_map", reflection_probe.source.clone());
@@ -0,0 +1,269 @@
+//! Parsing for `.dds` texture containers.
+//!
+//! Supports BC1 through BC7 and 8-bit RGBA data, with legacy FourCC or DX10 headers.
+//! Cube maps are loaded if the file contains all 6 faces. Array and 3D textures are not supported.
+
+use crate::*;
+use kgraphics::*;
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_LENGTH: usize = 128;
+const DX10_HEADER_LENGTH: usize = 20;
+
+const PIXEL_FORMAT_FOURCC: u32 = 0x4;
+const PIXEL_FORMAT_RGB: u32 = 0x40;
+const CAPS2_CUBE_MAP: u32 = 0x200;
+const DX10_MISC_TEXTURE_CUBE: u32 = 0x4;
+
+/// Maps a DXGI format to a [PixelFormat] and whether it's sRGB.
+fn dxgi_format_to_pixel_format(dxgi_format: u32) -> Option<(PixelFormat, bool)> {
+    Some(match dxgi_format {
+        28 => (PixelFormat::RGBA8Unorm, false),
+        29 => (PixelFormat::RGBA8Unorm, true),
+        71 => (PixelFormat::BC1RGBAUnorm, false),
+        72 => (PixelFormat::BC1RGBAUnorm, true),
+        74 => (PixelFormat::BC2RGBAUnorm, false),
+        75 => (PixelFormat::BC2RGBAUnorm, true),
+        77 => (PixelFormat::BC3RGBAUnorm, false),
+        78 => (PixelFormat::BC3RGBAUnorm, true),
+        80 => (PixelFormat::BC4RUnorm, false),
+        83 => (PixelFormat::BC5RGUnorm, false),
+        95 => (PixelFormat::BC6HRGBUfloat, false),
+        98 => (PixelFormat::BC7RGBAUnorm, false),
+        99 => (PixelFormat::BC7RGBAUnorm, true),
+        _ => return None,
+    })
+}
+
+/// Legacy headers identify compressed formats with a FourCC code.
+fn four_cc_to_pixel_format(four_cc: &[u8]) -> Option<PixelFormat> {
+    Some(match four_cc {
+        b"DXT1" => PixelFormat::BC1RGBAUnorm,
+        b"DXT2" | b"DXT3" => PixelFormat::BC2RGBAUnorm,
+        b"DXT4" | b"DXT5" => PixelFormat::BC3RGBAUnorm,
+        b"ATI1" | b"BC4U" => PixelFormat::BC4RUnorm,
+        b"ATI2" | b"BC5U" => PixelFormat::BC5RGUnorm,
+        _ => return None,
+    })
+}
+
+pub(crate) fn dds_image_from_bytes(
+    bytes: &[u8],
+) -> Result<CompressedImage, CompressedTextureError> {
+    if bytes.len() < HEADER_LENGTH || &bytes[..4] != MAGIC {
+        return Err(CompressedTextureError::InvalidFile);
+    }
+
+    let height = read_u32(bytes, 12)?.max(1);
+    let width = read_u32(bytes, 16)?.max(1);
+    let depth = read_u32(bytes, 24)?;
+    let level_count = read_u32(bytes, 28)?.max(1) as usize;
+    let pixel_format_flags = read_u32(bytes, 80)?;
+    let four_cc = read_bytes(bytes, 84, 4)?;
+    let caps2 = read_u32(bytes, 112)?;
+
+    let mut data_offset = HEADER_LENGTH;
+    let mut cube_map = caps2 & CAPS2_CUBE_MAP != 0;
+    let mut swap_red_and_blue = false;
+
+    let (pixel_format, srgb) = if pixel_format_flags & PIXEL_FORMAT_FOURCC != 0 {
+        if four_cc == b"DX10" {
+            let dxgi_format = read_u32(bytes, HEADER_LENGTH)?;
+            let misc_flags = read_u32(bytes, HEADER_LENGTH + 8)?;
+            let array_size = read_u32(bytes, HEADER_LENGTH + 12)?;
+            if array_size > 1 {
+                return Err(CompressedTextureError::UnsupportedFormat(
+                    "Array textures".into(),
+                ));
+            }
+            cube_map |= misc_flags & DX10_MISC_TEXTURE_CUBE != 0;
+            data_offset += DX10_HEADER_LENGTH;
+
+            let (pixel_format, srgb) =
+                dxgi_format_to_pixel_format(dxgi_format).ok_or_else(|| {
+                    CompressedTextureError::UnsupportedFormat(format!(
+                        "DXGI format {}",
+                        dxgi_format
+                    ))
+                })?;
+            (pixel_format, Some(srgb))
+        } else {
+            let pixel_format = four_cc_to_pixel_format(four_cc).ok_or_else(|| {
+                CompressedTextureError::UnsupportedFormat(format!(
+                    "FourCC {}",
+                    String::from_utf8_lossy(four_cc)
+                ))
+            })?;
+            (pixel_format, None)
+        }
+    } else if pixel_format_flags & PIXEL_FORMAT_RGB != 0 && read_u32(bytes, 88)? == 32 {
+        // Uncompressed data is either RGBA or BGRA.
+        swap_red_and_blue = match read_u32(bytes, 92)? {
+            0x0000_00FF => false,
+            0x00FF_0000 => true,
+            _ => {
+                return Err(CompressedTextureError::UnsupportedFormat(
+                    "Uncompressed channel layout".into(),
+                ))
+            }
+        };
+        (PixelFormat::RGBA8Unorm, None)
+    } else {
+        return Err(CompressedTextureError::UnsupportedFormat(
+            "Uncompressed pixel format".into(),
+        ));
+    };
+
+    if depth > 1 && !cube_map {
+        return Err(CompressedTextureError::UnsupportedFormat(
+            "3D textures".into(),
+        ));
+    }
+
+    let level_size = |level: usize| {
+        let width = (width >> level).max(1);
+        let height = (height >> level).max(1);
+        if pixel_format.is_compressed() {
+            compressed_size(pixel_format, width, height)
+        } else {
+            (width * height * 4) as usize
+        }
+    };
+
+    // DDS stores every level of a face before the next face,
+    // but levels are uploaded with their faces together.
+    let faces = if cube_map { 6 } else { 1 };
+    let mut levels = vec![Vec::new(); level_count];
+    let mut offset = data_offset;
+    for _ in 0..faces {
+        for (level, data) in levels.iter_mut().enumerate() {
+            let size = level_size(level);
+            data.extend_from_slice(read_bytes(bytes, offset, size)?);
+            offset += size;
+        }
+    }
+
+    if swap_red_and_blue {
+        for level in &mut levels {
+            for pixel in level.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+    }
+
+    Ok(CompressedImage {
+        width,
+        height,
+        pixel_format,
+        srgb,
+        faces,
+        levels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dds_file(
+        width: u32,
+        height: u32,
+        level_count: u32,
+        four_cc: &[u8; 4],
+        dx10: Option<(u32, u32)>,
+        caps2: u32,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut bytes = vec![0; HEADER_LENGTH];
+        bytes[..4].copy_from_slice(MAGIC);
+        bytes[4..8].copy_from_slice(&124u32.to_le_bytes());
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes[28..32].copy_from_slice(&level_count.to_le_bytes());
+        bytes[76..80].copy_from_slice(&32u32.to_le_bytes());
+        bytes[80..84].copy_from_slice(&PIXEL_FORMAT_FOURCC.to_le_bytes());
+        bytes[84..88].copy_from_slice(four_cc);
+        bytes[112..116].copy_from_slice(&caps2.to_le_bytes());
+        if let Some((dxgi_format, misc_flags)) = dx10 {
+            for value in [dxgi_format, 3, misc_flags, 1, 0] {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn dxt1_with_mipmaps() {
+        let level0: Vec<u8> = (0..4).flat_map(|_| [1u8; 8]).collect();
+        let level1 = [2u8; 8];
+        let level2 = [3u8; 8];
+        let data = [&level0[..], &level1, &level2].concat();
+
+        let image = dds_image_from_bytes(&dds_file(8, 8, 3, b"DXT1", None, 0, &data)).unwrap();
+        assert_eq!(image.pixel_format, PixelFormat::BC1RGBAUnorm);
+        assert_eq!(image.srgb, None);
+        assert_eq!(image.levels, vec![level0, level1.to_vec(), level2.to_vec()]);
+    }
+
+    #[test]
+    fn dx10_cube_map() {
+        // Each face has two levels, filled with a value from their face and level.
+        // Level 0 of an 8x8 BC7 image is 4 blocks.
+        let mut data = Vec::new();
+        for face in 0..6u8 {
+            data.extend([face * 2; 16 * 4]);
+            data.extend([face * 2 + 1; 16]);
+        }
+        let bytes = dds_file(
+            8,
+            8,
+            2,
+            b"DX10",
+            Some((99, DX10_MISC_TEXTURE_CUBE)),
+            0,
+            &data,
+        );
+        let image = dds_image_from_bytes(&bytes).unwrap();
+        assert_eq!(image.pixel_format, PixelFormat::BC7RGBAUnorm);
+        assert_eq!(image.srgb, Some(true));
+        assert_eq!(image.faces, 6);
+        for face in 0..6 {
+            assert!(image.levels[0][face * 64..(face + 1) * 64]
+                .iter()
+                .all(|v| *v == face as u8 * 2));
+            assert!(image.levels[1][face * 16..(face + 1) * 16]
+                .iter()
+                .all(|v| *v == face as u8 * 2 + 1));
+        }
+
+        // BC7 can't be decoded on the CPU.
+        assert!(matches!(
+            image.into_texture_load_data(&mut TextureSettings::default(), &[]),
+            Err(CompressedTextureError::CannotDecode(
+                PixelFormat::BC7RGBAUnorm
+            ))
+        ));
+    }
+
+    #[test]
+    fn bgra8() {
+        let mut bytes = dds_file(1, 1, 1, &[0; 4], None, 0, &[1, 2, 3, 4]);
+        bytes[80..84].copy_from_slice(&PIXEL_FORMAT_RGB.to_le_bytes());
+        bytes[88..92].copy_from_slice(&32u32.to_le_bytes());
+        bytes[92..96].copy_from_slice(&0x00FF_0000u32.to_le_bytes());
+
+        let image = dds_image_from_bytes(&bytes).unwrap();
+        assert_eq!(image.pixel_format, PixelFormat::RGBA8Unorm);
+        assert_eq!(image.levels[0], [3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn truncated_file() {
+        let bytes = dds_file(8, 8, 1, b"DXT5", None, 0, &[0; 32]);
+        assert!(matches!(
+            dds_image_from_bytes(&bytes),
+            Err(CompressedTextureError::InvalidFile)
+        ));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::*;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// Saves a sequence of frames as numbered PNG files in a directory.
+///
+/// While recording every frame advances [Time] by the same amount, regardless of how long it took to render,
+/// so a recording plays back at a steady rate and runs the same fixed updates each time.
+/// The files can be combined into a video with a tool like `ffmpeg`.
+///
+/// Spawn a [FrameRecorder] to start recording. Requires the `png` feature.
+#[derive(NotCloneComponent)]
+pub struct FrameRecorder {
+    directory: String,
+    frame_count: u32,
+    recorded_frames: u32,
+    /// Frames that have been captured but are still being saved on another thread.
+    saving_frames: Arc<AtomicU32>,
+    /// How much time passes between recorded frames.
+    /// The default is 1/60th of a second.
+    pub frame_seconds: f64,
+    /// Records an [OffscreenRenderTarget] instead of the window.
+    pub target: Option<Handle<OffscreenRenderTarget>>,
+    /// Quits the app once every frame has been recorded.
+    /// The default is `false`.
+    pub quit_when_finished: bool,
+}
+
+impl FrameRecorder {
+    /// Records `frame_count` frames to `directory`, which is created if it doesn't exist.
+    pub fn new(directory: &str, frame_count: u32) -> Self {
+        Self {
+            directory: directory.to_owned(),
+            frame_count,
+            recorded_frames: 0,
+            saving_frames: Arc::new(AtomicU32::new(0)),
+            frame_seconds: 1.0 / 60.0,
+            target: None,
+            quit_when_finished: false,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.recorded_frames >= self.frame_count
+    }
+
+    /// The path of a recorded frame.
+    pub fn frame_path(&self, frame: u32) -> String {
+        format!("{}/frame_{:05}.png", self.directory, frame)
+    }
+}
+
+pub(super) fn record_frames(
+    graphics: &mut Graphics,
+    time: &mut Time,
+    mut frame_recorders: Query<&mut FrameRecorder>,
+    #[cfg(not(feature = "headless"))] kapp_application: &mut KappApplication,
+) {
+    for frame_recorder in &mut frame_recorders {
+        if frame_recorder.is_finished() {
+            // Quitting is immediate so it waits until the last frame has been rendered and saved.
+            #[cfg(not(feature = "headless"))]
+            if frame_recorder.quit_when_finished {
+                if frame_recorder.saving_frames.load(Ordering::Acquire) == 0 {
+                    kapp_application.quit();
+                } else {
+                    graphics.request_redraw();
+                }
+            }
+            continue;
+        }
+
+        // The first frame's time has already been measured, so recording begins with the next frame.
+        if time.simulated_frame_seconds.is_none() {
+            if let Err(error) = std::fs::create_dir_all(&frame_recorder.directory) {
+                klog::log!(
+                    "FRAME RECORDER ERROR: Could not create {}: {:?}",
+                    frame_recorder.directory,
+                    error
+                );
+                frame_recorder.frame_count = 0;
+                continue;
+            }
+            time.simulated_frame_seconds = Some(frame_recorder.frame_seconds);
+            graphics.request_redraw();
+            continue;
+        }
+
+        // Encoding is slow so frames are saved off the main thread.
+        let path = frame_recorder.frame_path(frame_recorder.recorded_frames);
+        let saving_frames = frame_recorder.saving_frames.clone();
+        saving_frames.fetch_add(1, Ordering::AcqRel);
+        let destination = ScreenshotDestination::callback(move |screenshot| {
+            ktasks::spawn(async move {
+                if let Err(error) = screenshot.save_png(&path) {
+                    klog::log!("FRAME RECORDER ERROR: Could not save {}: {:?}", path, error);
+                }
+                saving_frames.fetch_sub(1, Ordering::AcqRel);
+            })
+            .run();
+        });
+        match &frame_recorder.target {
+            Some(target) => graphics.request_screenshot_of_target(target, destination),
+            None => graphics.request_screenshot(destination),
+        }
+        frame_recorder.recorded_frames += 1;
+
+        if frame_recorder.is_finished() {
+            time.simulated_frame_seconds = None;
+            klog::log!(
+                "Recorded {} frames to {}",
+                frame_recorder.frame_count,
+                frame_recorder.directory
+            );
+        }
+        // Keep rendering even if automatic redraws are disabled.
+        graphics.request_redraw();
+    }
+}
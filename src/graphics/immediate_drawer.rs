@@ -14,7 +14,7 @@ fn setup_systems(world: &mut World) {
 
 fn draw_system(world: &mut World) {
     let immediate_drawer = world.get_singleton::<ImmediateDrawer>();
-    let mut commands = Commands::new();
+    let mut commands = CommandQueue::new();
     std::mem::swap(&mut commands, &mut immediate_drawer.commands);
     commands.apply(world);
 }
@@ -22,7 +22,7 @@ fn draw_system(world: &mut World) {
 /// [ImmediateDrawer] draws things for a single frame. Useful for debug visualizations.
 #[derive(NotCloneComponent)]
 pub struct ImmediateDrawer {
-    commands: Commands,
+    commands: CommandQueue,
     color: Color,
     material: Handle<Material>,
 }
@@ -36,7 +36,7 @@ impl Default for ImmediateDrawer {
 impl ImmediateDrawer {
     pub fn new() -> Self {
         Self {
-            commands: Commands::new(),
+            commands: CommandQueue::new(),
             color: Color::WHITE,
             material: Material::DEFAULT,
         }
@@ -0,0 +1,404 @@
+//! Parsing for `.ktx2` texture containers.
+//!
+//! Supports uncompressed and block compressed data, zstd supercompression (with the
+//! `ktx2_zstd` feature), and BasisLZ / ETC1S data which is transcoded to a format the backend supports.
+//! Cube maps are loaded if the file has 6 faces. Array and 3D textures are not supported.
+
+use super::basis_universal::*;
+use crate::*;
+use kgraphics::*;
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const HEADER_LENGTH: usize = 80;
+
+const SUPERCOMPRESSION_NONE: u32 = 0;
+const SUPERCOMPRESSION_BASIS_LZ: u32 = 1;
+const SUPERCOMPRESSION_ZSTD: u32 = 2;
+
+const COLOR_MODEL_ETC1S: u8 = 163;
+const COLOR_MODEL_UASTC: u8 = 166;
+const TRANSFER_FUNCTION_SRGB: u8 = 2;
+
+/// Maps a Vulkan format to a [PixelFormat] and whether it's sRGB.
+fn vk_format_to_pixel_format(vk_format: u32) -> Option<(PixelFormat, bool)> {
+    Some(match vk_format {
+        9 => (PixelFormat::R8Unorm, false),
+        16 => (PixelFormat::RG8Unorm, false),
+        23 => (PixelFormat::RGB8Unorm, false),
+        37 => (PixelFormat::RGBA8Unorm, false),
+        43 => (PixelFormat::RGBA8Unorm, true),
+        97 => (PixelFormat::RGBA16F, false),
+        109 => (PixelFormat::RGBA32F, false),
+        131 | 133 => (PixelFormat::BC1RGBAUnorm, false),
+        132 | 134 => (PixelFormat::BC1RGBAUnorm, true),
+        135 => (PixelFormat::BC2RGBAUnorm, false),
+        136 => (PixelFormat::BC2RGBAUnorm, true),
+        137 => (PixelFormat::BC3RGBAUnorm, false),
+        138 => (PixelFormat::BC3RGBAUnorm, true),
+        139 => (PixelFormat::BC4RUnorm, false),
+        141 => (PixelFormat::BC5RGUnorm, false),
+        143 => (PixelFormat::BC6HRGBUfloat, false),
+        145 => (PixelFormat::BC7RGBAUnorm, false),
+        146 => (PixelFormat::BC7RGBAUnorm, true),
+        147 => (PixelFormat::ETC2RGB8Unorm, false),
+        148 => (PixelFormat::ETC2RGB8Unorm, true),
+        151 => (PixelFormat::ETC2RGBA8Unorm, false),
+        152 => (PixelFormat::ETC2RGBA8Unorm, true),
+        _ => return None,
+    })
+}
+
+pub(crate) fn ktx2_image_from_bytes(
+    bytes: &[u8],
+    supported_pixel_formats: &[PixelFormat],
+) -> Result<CompressedImage, CompressedTextureError> {
+    if bytes.len() < HEADER_LENGTH || bytes[..12] != IDENTIFIER {
+        return Err(CompressedTextureError::InvalidFile);
+    }
+
+    let vk_format = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?.max(1);
+    let depth = read_u32(bytes, 28)?;
+    let layers = read_u32(bytes, 32)?;
+    let faces = read_u32(bytes, 36)?;
+    let level_count = read_u32(bytes, 40)?.max(1) as usize;
+    let supercompression = read_u32(bytes, 44)?;
+
+    if depth > 1 || layers > 1 {
+        return Err(CompressedTextureError::UnsupportedFormat(
+            "3D and array textures".into(),
+        ));
+    }
+    if faces != 1 && faces != 6 {
+        return Err(CompressedTextureError::InvalidFile);
+    }
+
+    // The data format descriptor describes the color model and transfer function.
+    let dfd_offset = read_u32(bytes, 48)? as usize;
+    let color_model = *bytes
+        .get(dfd_offset + 12)
+        .ok_or(CompressedTextureError::InvalidFile)?;
+    let transfer_function = *bytes
+        .get(dfd_offset + 14)
+        .ok_or(CompressedTextureError::InvalidFile)?;
+
+    let mut levels = Vec::with_capacity(level_count);
+    for level in 0..level_count {
+        let index = HEADER_LENGTH + level * 24;
+        levels.push(read_bytes(
+            bytes,
+            read_u64(bytes, index)? as usize,
+            read_u64(bytes, index + 8)? as usize,
+        )?);
+    }
+
+    if supercompression == SUPERCOMPRESSION_BASIS_LZ {
+        if color_model != COLOR_MODEL_ETC1S {
+            return Err(CompressedTextureError::InvalidFile);
+        }
+        let global_data = read_bytes(
+            bytes,
+            read_u64(bytes, 64)? as usize,
+            read_u64(bytes, 72)? as usize,
+        )?;
+        return transcode_basis_lz(
+            global_data,
+            &levels,
+            width,
+            height,
+            faces,
+            transfer_function == TRANSFER_FUNCTION_SRGB,
+            supported_pixel_formats,
+        );
+    }
+
+    if vk_format == 0 && color_model == COLOR_MODEL_UASTC {
+        return Err(CompressedTextureError::UnsupportedFormat(
+            "Basis Universal UASTC".into(),
+        ));
+    }
+    let (pixel_format, srgb) = vk_format_to_pixel_format(vk_format).ok_or_else(|| {
+        CompressedTextureError::UnsupportedFormat(format!("Vulkan format {}", vk_format))
+    })?;
+
+    let levels = levels
+        .iter()
+        .map(|data| match supercompression {
+            SUPERCOMPRESSION_NONE => Ok(data.to_vec()),
+            SUPERCOMPRESSION_ZSTD => zstd_decompress(data),
+            _ => Err(CompressedTextureError::UnsupportedSupercompression(
+                supercompression,
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(CompressedImage {
+        width,
+        height,
+        pixel_format,
+        srgb: Some(srgb),
+        faces,
+        levels,
+    })
+}
+
+#[cfg(feature = "ktx2_zstd")]
+fn zstd_decompress(data: &[u8]) -> Result<Vec<u8>, CompressedTextureError> {
+    use std::io::Read;
+    let mut decoder =
+        ruzstd::StreamingDecoder::new(data).map_err(|_| CompressedTextureError::InvalidFile)?;
+    let mut decompressed = Vec::new();
+    decoder
+        .read_to_end(&mut decompressed)
+        .map_err(|_| CompressedTextureError::InvalidFile)?;
+    Ok(decompressed)
+}
+
+#[cfg(not(feature = "ktx2_zstd"))]
+fn zstd_decompress(_data: &[u8]) -> Result<Vec<u8>, CompressedTextureError> {
+    klog::log!("KTX2 ERROR: Enable the `ktx2_zstd` feature to load zstd supercompressed files");
+    Err(CompressedTextureError::UnsupportedSupercompression(
+        SUPERCOMPRESSION_ZSTD,
+    ))
+}
+
+/// The global data stores the ETC1S codebooks followed by a description of each image's slices.
+fn transcode_basis_lz(
+    global_data: &[u8],
+    levels: &[&[u8]],
+    width: u32,
+    height: u32,
+    faces: u32,
+    srgb: bool,
+    supported_pixel_formats: &[PixelFormat],
+) -> Result<CompressedImage, CompressedTextureError> {
+    let counts = read_bytes(global_data, 0, 4)?;
+    let endpoint_count = u16::from_le_bytes([counts[0], counts[1]]) as usize;
+    let selector_count = u16::from_le_bytes([counts[2], counts[3]]) as usize;
+    let endpoints_length = read_u32(global_data, 4)? as usize;
+    let selectors_length = read_u32(global_data, 8)? as usize;
+    let tables_length = read_u32(global_data, 12)? as usize;
+
+    let image_count = levels.len() * faces as usize;
+    let image_descriptions = 20;
+    let endpoints_offset = image_descriptions + image_count * 20;
+    let selectors_offset = endpoints_offset + endpoints_length;
+    let tables_offset = selectors_offset + selectors_length;
+
+    let codebooks = Etc1sCodebooks::new(
+        endpoint_count,
+        selector_count,
+        read_bytes(global_data, endpoints_offset, endpoints_length)?,
+        read_bytes(global_data, selectors_offset, selectors_length)?,
+        read_bytes(global_data, tables_offset, tables_length)?,
+    )?;
+
+    // Each image description is: flags, RGB offset, RGB length, alpha offset, alpha length.
+    let image_description = |image: usize| -> Result<[usize; 5], CompressedTextureError> {
+        let mut description = [0; 5];
+        for (i, value) in description.iter_mut().enumerate() {
+            *value = read_u32(global_data, image_descriptions + image * 20 + i * 4)? as usize;
+        }
+        Ok(description)
+    };
+
+    let mut has_alpha = false;
+    for image in 0..image_count {
+        has_alpha |= image_description(image)?[4] > 0;
+    }
+    let target = basis_transcode_target(has_alpha, supported_pixel_formats);
+
+    let mut transcoded_levels = Vec::with_capacity(levels.len());
+    for (level, data) in levels.iter().enumerate() {
+        let level_width = (width >> level).max(1);
+        let level_height = (height >> level).max(1);
+        let mut transcoded = Vec::new();
+        for face in 0..faces as usize {
+            let [_, rgb_offset, rgb_length, alpha_offset, alpha_length] =
+                image_description(level * faces as usize + face)?;
+            let alpha = if alpha_length > 0 {
+                Some(read_bytes(data, alpha_offset, alpha_length)?)
+            } else {
+                None
+            };
+            transcoded.extend(codebooks.transcode(
+                level_width,
+                level_height,
+                read_bytes(data, rgb_offset, rgb_length)?,
+                alpha,
+                target,
+            )?);
+        }
+        transcoded_levels.push(transcoded);
+    }
+
+    Ok(CompressedImage {
+        width,
+        height,
+        pixel_format: target,
+        srgb: Some(srgb),
+        faces,
+        levels: transcoded_levels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a `.ktx2` file with a data format descriptor that only sets the color model and transfer function.
+    #[allow(clippy::too_many_arguments)]
+    fn ktx2_file(
+        vk_format: u32,
+        width: u32,
+        height: u32,
+        faces: u32,
+        supercompression: u32,
+        color_model: u8,
+        global_data: &[u8],
+        levels: &[Vec<u8>],
+    ) -> Vec<u8> {
+        let mut dfd = vec![0; 44];
+        dfd[0..4].copy_from_slice(&44u32.to_le_bytes());
+        dfd[12] = color_model;
+        dfd[14] = TRANSFER_FUNCTION_SRGB;
+
+        let dfd_offset = HEADER_LENGTH + levels.len() * 24;
+        let global_data_offset = dfd_offset + dfd.len();
+        let mut level_offset = global_data_offset + global_data.len();
+
+        let mut bytes = IDENTIFIER.to_vec();
+        for value in [
+            vk_format,
+            1,
+            width,
+            height,
+            0,
+            0,
+            faces,
+            levels.len() as u32,
+            supercompression,
+            dfd_offset as u32,
+            dfd.len() as u32,
+            0,
+            0,
+        ] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend((global_data_offset as u64).to_le_bytes());
+        bytes.extend((global_data.len() as u64).to_le_bytes());
+        for level in levels {
+            bytes.extend((level_offset as u64).to_le_bytes());
+            bytes.extend((level.len() as u64).to_le_bytes());
+            bytes.extend(0u64.to_le_bytes());
+            level_offset += level.len();
+        }
+        bytes.extend(dfd);
+        bytes.extend(global_data);
+        for level in levels {
+            bytes.extend(level);
+        }
+        bytes
+    }
+
+    #[test]
+    fn rgba8_with_mipmaps() {
+        let levels = vec![vec![1; 2 * 2 * 4], vec![2; 4]];
+        let bytes = ktx2_file(43, 2, 2, 1, 0, 0, &[], &levels);
+        let image = ktx2_image_from_bytes(&bytes, &[]).unwrap();
+        assert_eq!(image.pixel_format, PixelFormat::RGBA8Unorm);
+        assert_eq!(image.srgb, Some(true));
+        assert_eq!(image.levels, levels);
+    }
+
+    #[test]
+    fn bc1_cube_map() {
+        let block = encode_bc1_block(&[[0, 255, 0, 255]; 16]);
+        let level: Vec<u8> = (0..6).flat_map(|_| block).collect();
+        let bytes = ktx2_file(132, 4, 4, 6, 0, 0, &[], std::slice::from_ref(&level));
+
+        let image = ktx2_image_from_bytes(&bytes, &[PixelFormat::BC1RGBAUnorm]).unwrap();
+        assert_eq!(image.faces, 6);
+        assert_eq!(image.levels, vec![level]);
+
+        // Without support for BC1 the blocks are decoded.
+        let mut settings = TextureSettings::default();
+        let texture_load_data = image.into_texture_load_data(&mut settings, &[]).unwrap();
+        assert_eq!(texture_load_data.pixel_format, PixelFormat::RGBA8Unorm);
+        assert!(settings.srgb);
+        match texture_load_data.data {
+            TextureData::MipLevels { levels, faces } => {
+                assert_eq!(faces, 6);
+                assert_eq!(levels[0].len(), 6 * 4 * 4 * 4);
+                assert!(levels[0].chunks(4).all(|p| p == [0, 255, 0, 255]));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn basis_lz() {
+        let (endpoints, selectors, tables, slice) =
+            super::super::basis_universal::tests::test_etc1s_data();
+        let mut global_data = Vec::new();
+        global_data.extend(2u16.to_le_bytes());
+        global_data.extend(2u16.to_le_bytes());
+        for length in [endpoints.len(), selectors.len(), tables.len(), 0] {
+            global_data.extend((length as u32).to_le_bytes());
+        }
+        for value in [0, 0, slice.len() as u32, 0, 0] {
+            global_data.extend(value.to_le_bytes());
+        }
+        global_data.extend(&endpoints);
+        global_data.extend(&selectors);
+        global_data.extend(&tables);
+
+        let bytes = ktx2_file(0, 8, 4, 1, 1, COLOR_MODEL_ETC1S, &global_data, &[slice]);
+        let image = ktx2_image_from_bytes(&bytes, &[]).unwrap();
+        assert_eq!(image.pixel_format, PixelFormat::RGBA8Unorm);
+        assert_eq!(
+            image.levels[0],
+            super::super::basis_universal::tests::expected_pixels()
+        );
+
+        let image = ktx2_image_from_bytes(&bytes, &[PixelFormat::ETC2RGB8Unorm]).unwrap();
+        assert_eq!(image.pixel_format, PixelFormat::ETC2RGB8Unorm);
+        assert_eq!(image.levels[0].len(), 16);
+    }
+
+    #[test]
+    fn uastc_is_unsupported() {
+        let bytes = ktx2_file(0, 4, 4, 1, 0, COLOR_MODEL_UASTC, &[], &[vec![0; 16]]);
+        assert!(matches!(
+            ktx2_image_from_bytes(&bytes, &[]),
+            Err(CompressedTextureError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_identifier() {
+        let mut bytes = ktx2_file(37, 1, 1, 1, 0, 0, &[], &[vec![0; 4]]);
+        bytes[1] = 0;
+        assert!(matches!(
+            ktx2_image_from_bytes(&bytes, &[]),
+            Err(CompressedTextureError::InvalidFile)
+        ));
+    }
+
+    #[cfg(feature = "ktx2_zstd")]
+    #[test]
+    fn zstd() {
+        // A zstd frame with a single raw block.
+        let pixels = [10u8, 20, 30, 255];
+        let mut frame = vec![0x28, 0xB5, 0x2F, 0xFD, 0x20, pixels.len() as u8];
+        frame.extend(&((pixels.len() as u32) << 3 | 1).to_le_bytes()[..3]);
+        frame.extend(pixels);
+
+        let bytes = ktx2_file(37, 1, 1, 1, SUPERCOMPRESSION_ZSTD, 0, &[], &[frame]);
+        let image = ktx2_image_from_bytes(&bytes, &[]).unwrap();
+        assert_eq!(image.levels[0], pixels);
+    }
+}
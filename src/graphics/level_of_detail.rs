@@ -0,0 +1,95 @@
+use crate::*;
+
+/// One [Mesh] in a [LevelOfDetail] chain.
+#[derive(Clone, Debug)]
+pub struct DetailLevel {
+    pub mesh: Handle<Mesh>,
+    /// The smallest screen size this level is used at.
+    /// Screen size is the diameter of the [Mesh]'s bounding sphere relative to the viewport height.
+    pub min_screen_size: f32,
+}
+
+/// Swaps an [Entity]'s [Mesh] for simpler versions as it gets smaller on screen.
+/// The [Entity]'s own [Handle<Mesh>] is still used for culling and shadows.
+#[derive(Component, Clone, Debug)]
+pub struct LevelOfDetail {
+    /// Sorted from most to least detailed.
+    /// If the [Entity] is smaller than every level's `min_screen_size` it isn't rendered.
+    pub levels: Vec<DetailLevel>,
+    /// How far below a level's threshold it fades out with a dithered cross-fade,
+    /// as a fraction of the threshold.
+    /// 0.0 disables cross-fading. The default value is 0.1.
+    pub cross_fade: f32,
+}
+
+/// The levels of a [LevelOfDetail] to render.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LevelOfDetailSelection {
+    pub level: usize,
+    /// A more detailed level that's fading out and how much of it is still visible.
+    pub fading_level: Option<(usize, f32)>,
+}
+
+impl LevelOfDetail {
+    pub fn new(levels: Vec<DetailLevel>) -> Self {
+        Self {
+            levels,
+            cross_fade: 0.1,
+        }
+    }
+
+    /// Each [Mesh] is used down to half the screen size of the previous one.
+    /// The first [Mesh] is used when its bounds cover at least half the viewport height
+    /// and the last [Mesh] is used at any size.
+    pub fn from_meshes(meshes: impl IntoIterator<Item = Handle<Mesh>>) -> Self {
+        let mut levels: Vec<DetailLevel> = meshes
+            .into_iter()
+            .enumerate()
+            .map(|(i, mesh)| DetailLevel {
+                mesh,
+                min_screen_size: 0.5f32.powi(i as i32 + 1),
+            })
+            .collect();
+        if let Some(last) = levels.last_mut() {
+            last.min_screen_size = 0.0;
+        }
+        Self::new(levels)
+    }
+
+    pub fn with_cross_fade(mut self, cross_fade: f32) -> Self {
+        self.cross_fade = cross_fade;
+        self
+    }
+
+    /// Returns `None` if no level should render at this screen size.
+    pub fn select(&self, screen_size: f32) -> Option<LevelOfDetailSelection> {
+        let level = self
+            .levels
+            .iter()
+            .position(|l| screen_size >= l.min_screen_size)?;
+
+        let mut fading_level = None;
+        if level > 0 && self.cross_fade > 0.0 {
+            let threshold = self.levels[level - 1].min_screen_size;
+            let fade_start = threshold * (1.0 - self.cross_fade);
+            if screen_size > fade_start && threshold > fade_start {
+                fading_level = Some((
+                    level - 1,
+                    (screen_size - fade_start) / (threshold - fade_start),
+                ));
+            }
+        }
+        Some(LevelOfDetailSelection {
+            level,
+            fading_level,
+        })
+    }
+
+    /// How large a sphere appears as a fraction of the viewport height.
+    pub fn screen_size(view: &Mat4, projection: &Mat4, center: Vec3, radius: f32) -> f32 {
+        let view_position = view.transform_point(center);
+        // `w` is the distance for perspective projections and 1.0 for orthographic projections.
+        let w = projection.row(3).dot(view_position.extend(1.0));
+        radius * projection[(1, 1)] / w.max(f32::EPSILON)
+    }
+}
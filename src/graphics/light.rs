@@ -6,6 +6,14 @@ pub enum LightMode {
     Directional,
     /// For light sources that emit from a point, like a lamp.
     Point { radius: f32 },
+    /// For light sources that emit a cone of light, like a flashlight.
+    /// Angles are in radians and measured from the light's forward direction.
+    /// Light falls off between `inner_angle` and `outer_angle` and ends at `range`.
+    Spot {
+        inner_angle: f32,
+        outer_angle: f32,
+        range: f32,
+    },
 }
 
 #[derive(Component, Clone)]
@@ -29,4 +37,20 @@ impl Light {
         self.ambient_light_amount = amount;
         self
     }
+
+    /// How far this light reaches before it's too dim to notice.
+    /// Lights are only rendered for pixels within this distance.
+    pub fn influence_range(&self) -> f32 {
+        match self.light_mode {
+            LightMode::Directional => f32::INFINITY,
+            LightMode::Point { .. } => {
+                // The distance where the light's inverse-square falloff
+                // reaches 1/256th of the light's intensity at one unit.
+                let color = self.color.to_rgb_color(color_spaces::LINEAR_SRGB);
+                let brightest = color.x.max(color.y).max(color.z) * self.intensity;
+                (brightest.max(0.0) * 256.0).sqrt()
+            }
+            LightMode::Spot { range, .. } => range,
+        }
+    }
 }
@@ -41,8 +41,18 @@ pub struct MeshData {
     pub indices: Vec<[u32; 3]>,
     pub normals: Vec<Vec3>,
     pub texture_coordinates: Vec<Vec2>,
+    /// A second set of texture coordinates, often used for lightmaps or ambient occlusion.
+    pub texture_coordinates_1: Vec<Vec2>,
+    /// The `w` component is 1.0 or -1.0 and gives the handedness of the tangent space.
+    /// See [MeshData::generate_tangents].
+    pub tangents: Vec<Vec4>,
     /// Colors are linear sRGB
     pub colors: Vec<Vec4>,
+    /// The indices of the four joints that move each vertex.
+    /// Used by shaders with the [shader_keywords::SKINNING] keyword.
+    pub joints: Vec<Vec4>,
+    /// How much each of [MeshData::joints] moves each vertex. Each vertex's weights should add up to 1.0.
+    pub weights: Vec<Vec4>,
 }
 
 impl MeshData {
@@ -52,7 +62,11 @@ impl MeshData {
             indices: Vec::new(),
             normals: Vec::new(),
             texture_coordinates: Vec::new(),
+            texture_coordinates_1: Vec::new(),
+            tangents: Vec::new(),
             colors: Vec::new(),
+            joints: Vec::new(),
+            weights: Vec::new(),
         }
     }
 
@@ -61,7 +75,11 @@ impl MeshData {
         self.indices.clear();
         self.normals.clear();
         self.texture_coordinates.clear();
+        self.texture_coordinates_1.clear();
+        self.tangents.clear();
         self.colors.clear();
+        self.joints.clear();
+        self.weights.clear();
     }
 }
 
@@ -69,10 +87,14 @@ impl MeshData {
 pub struct GPUMesh {
     pub positions: DataBuffer<Vec3>,
     pub texture_coordinates: Option<DataBuffer<Vec2>>,
+    pub texture_coordinates_1: Option<DataBuffer<Vec2>>,
     pub normals: Option<DataBuffer<Vec3>>,
+    pub tangents: Option<DataBuffer<Vec4>>,
     pub index_buffer: IndexBuffer,
     pub triangle_count: u32,
     pub colors: Option<DataBuffer<Vec4>>,
+    pub joints: Option<DataBuffer<Vec4>>,
+    pub weights: Option<DataBuffer<Vec4>>,
 }
 
 impl AssetTrait for Mesh {
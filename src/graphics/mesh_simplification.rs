@@ -0,0 +1,418 @@
+//! Mesh simplification using quadric error metrics.
+//!
+//! Based on "Surface Simplification Using Quadric Error Metrics" by Garland and Heckbert.
+//! Vertices are collapsed into a neighbouring vertex (a half-edge collapse) so the
+//! remaining vertices keep their original attributes.
+//! Vertices that share a position with another vertex (like those on a UV seam) are never moved
+//! so seams aren't torn apart.
+
+use crate::*;
+use std::collections::HashMap;
+
+/// How many triangles use an edge, one of those triangles, and the edge's vertices.
+type EdgeUse = (u32, [usize; 3], usize, usize);
+
+/// A symmetric 4x4 matrix that measures the squared distance of a point to a set of planes.
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn from_plane(normal: [f64; 3], distance: f64, weight: f64) -> Self {
+        let [a, b, c] = normal;
+        let d = distance;
+        Quadric(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|v| v * weight),
+        )
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a += b;
+        }
+    }
+
+    fn error(&self, p: [f64; 3]) -> f64 {
+        let [x, y, z] = p;
+        let q = &self.0;
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+fn to_f64(v: Vec3) -> [f64; 3] {
+    [v.x as f64, v.y as f64, v.z as f64]
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn length(a: [f64; 3]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+fn scale(a: [f64; 3], s: f64) -> [f64; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+/// How strongly open borders resist being collapsed compared to surface error.
+const BORDER_WEIGHT: f64 = 10.0;
+
+impl MeshData {
+    /// Produces a simplified copy of this [MeshData] with at most `target_triangle_count` triangles.
+    /// Simplification stops early if no further collapses are possible without flipping triangles.
+    pub fn simplify(&self, target_triangle_count: usize) -> MeshData {
+        let vertex_count = self.positions.len();
+        let positions: Vec<[f64; 3]> = self.positions.iter().map(|p| to_f64(*p)).collect();
+
+        // Vertices that share a position share a quadric.
+        let mut position_lookup = HashMap::new();
+        let mut vertex_to_position = Vec::with_capacity(vertex_count);
+        let mut vertices_per_position = Vec::new();
+        for p in &self.positions {
+            let key = [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()];
+            let next_index = vertices_per_position.len();
+            let index = *position_lookup.entry(key).or_insert(next_index);
+            if index == next_index {
+                vertices_per_position.push(0);
+            }
+            vertices_per_position[index] += 1;
+            vertex_to_position.push(index);
+        }
+
+        let mut quadrics = vec![Quadric::default(); vertices_per_position.len()];
+        let mut edge_uses: HashMap<(usize, usize), EdgeUse> = HashMap::new();
+        for triangle in &self.indices {
+            let triangle = triangle.map(|i| i as usize);
+            let [a, b, c] = triangle.map(|i| positions[i]);
+            let normal = cross(sub(b, a), sub(c, a));
+            let double_area = length(normal);
+            if double_area == 0.0 {
+                continue;
+            }
+            let normal = scale(normal, 1.0 / double_area);
+            let quadric = Quadric::from_plane(normal, -dot(normal, a), double_area * 0.5);
+            for i in triangle {
+                quadrics[vertex_to_position[i]].add(&quadric);
+            }
+
+            for j in 0..3 {
+                let (v0, v1) = (triangle[j], triangle[(j + 1) % 3]);
+                let (p0, p1) = (vertex_to_position[v0], vertex_to_position[v1]);
+                edge_uses
+                    .entry((p0.min(p1), p0.max(p1)))
+                    .or_insert((0, triangle, v0, v1))
+                    .0 += 1;
+            }
+        }
+
+        // Edges used by only one triangle are on an open border.
+        // A plane perpendicular to the triangle through the edge keeps the border in place.
+        for ((p0, p1), (use_count, triangle, v0, v1)) in &edge_uses {
+            if *use_count != 1 {
+                continue;
+            }
+            let [a, b, c] = triangle.map(|i| positions[i]);
+            let face_normal = cross(sub(b, a), sub(c, a));
+            let edge = sub(positions[*v1], positions[*v0]);
+            let edge_length = length(edge);
+            let border_normal = cross(edge, face_normal);
+            let border_normal_length = length(border_normal);
+            if border_normal_length == 0.0 {
+                continue;
+            }
+            let border_normal = scale(border_normal, 1.0 / border_normal_length);
+            let quadric = Quadric::from_plane(
+                border_normal,
+                -dot(border_normal, positions[*v0]),
+                edge_length * edge_length * BORDER_WEIGHT,
+            );
+            quadrics[*p0].add(&quadric);
+            quadrics[*p1].add(&quadric);
+        }
+
+        let mut triangles: Vec<[usize; 3]> =
+            self.indices.iter().map(|t| t.map(|i| i as usize)).collect();
+        let mut remap: Vec<usize> = (0..vertex_count).collect();
+        let mut vertex_triangles: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+        let mut edges = Vec::new();
+        let mut locked = vec![false; vertex_count];
+
+        while triangles.len() > target_triangle_count {
+            for list in &mut vertex_triangles {
+                list.clear();
+            }
+            for (i, triangle) in triangles.iter().enumerate() {
+                for v in triangle {
+                    vertex_triangles[*v].push(i);
+                }
+            }
+
+            // Consider collapsing each vertex into each of its neighbours.
+            edges.clear();
+            for triangle in &triangles {
+                for j in 0..3 {
+                    for (from, to) in [
+                        (triangle[j], triangle[(j + 1) % 3]),
+                        (triangle[(j + 1) % 3], triangle[j]),
+                    ] {
+                        let from_position = vertex_to_position[from];
+                        let to_position = vertex_to_position[to];
+                        if vertices_per_position[from_position] != 1 || from_position == to_position
+                        {
+                            continue;
+                        }
+                        let mut quadric = quadrics[from_position];
+                        quadric.add(&quadrics[to_position]);
+                        edges.push((quadric.error(positions[to]), from, to));
+                    }
+                }
+            }
+            edges.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+            // Collapse the cheapest edges whose neighbourhoods don't overlap.
+            locked.iter_mut().for_each(|l| *l = false);
+            let mut remaining_triangles = triangles.len();
+            let mut collapsed_any = false;
+            for (_, from, to) in edges.iter().copied() {
+                if remaining_triangles <= target_triangle_count {
+                    break;
+                }
+                if locked[from] || locked[to] {
+                    continue;
+                }
+                if collapse_flips_triangles(
+                    &positions,
+                    &triangles,
+                    &vertex_triangles[from],
+                    from,
+                    to,
+                ) {
+                    continue;
+                }
+
+                remap[from] = to;
+                let from_quadric = quadrics[vertex_to_position[from]];
+                quadrics[vertex_to_position[to]].add(&from_quadric);
+                for triangle in &vertex_triangles[from] {
+                    let triangle = &triangles[*triangle];
+                    if triangle.contains(&to) {
+                        remaining_triangles -= 1;
+                    }
+                    for v in triangle {
+                        locked[*v] = true;
+                    }
+                }
+                collapsed_any = true;
+            }
+
+            if !collapsed_any {
+                break;
+            }
+
+            // Apply the collapses and remove triangles that no longer have area.
+            triangles.retain_mut(|triangle| {
+                for v in triangle.iter_mut() {
+                    *v = remap[*v];
+                }
+                let [a, b, c] = triangle.map(|v| vertex_to_position[v]);
+                a != b && b != c && c != a
+            });
+        }
+
+        self.with_triangles(&triangles)
+    }
+
+    /// Generates progressively simpler copies of this [MeshData].
+    /// Each level has roughly `triangle_ratio` times the triangles of the previous level.
+    /// Levels that fail to simplify further are not included.
+    pub fn generate_lod_chain(&self, level_count: usize, triangle_ratio: f32) -> Vec<MeshData> {
+        let mut levels: Vec<MeshData> = Vec::with_capacity(level_count);
+        for _ in 0..level_count {
+            let previous = levels.last().unwrap_or(self);
+            let target = (previous.indices.len() as f32 * triangle_ratio) as usize;
+            let simplified = previous.simplify(target);
+            if simplified.indices.is_empty() || simplified.indices.len() >= previous.indices.len() {
+                break;
+            }
+            levels.push(simplified);
+        }
+        levels
+    }
+
+    /// Creates a new [MeshData] with only the vertices the triangles use.
+    fn with_triangles(&self, triangles: &[[usize; 3]]) -> MeshData {
+        let mut new_indices = vec![None; self.positions.len()];
+        let mut mesh_data = MeshData::new();
+        for triangle in triangles {
+            mesh_data.indices.push(triangle.map(|v| {
+                *new_indices[v].get_or_insert_with(|| {
+                    mesh_data.positions.push(self.positions[v]);
+                    if let Some(normal) = self.normals.get(v) {
+                        mesh_data.normals.push(*normal);
+                    }
+                    if let Some(tangent) = self.tangents.get(v) {
+                        mesh_data.tangents.push(*tangent);
+                    }
+                    if let Some(texture_coordinate) = self.texture_coordinates.get(v) {
+                        mesh_data.texture_coordinates.push(*texture_coordinate);
+                    }
+                    if let Some(texture_coordinate) = self.texture_coordinates_1.get(v) {
+                        mesh_data.texture_coordinates_1.push(*texture_coordinate);
+                    }
+                    if let Some(color) = self.colors.get(v) {
+                        mesh_data.colors.push(*color);
+                    }
+                    if let Some(joints) = self.joints.get(v) {
+                        mesh_data.joints.push(*joints);
+                    }
+                    if let Some(weights) = self.weights.get(v) {
+                        mesh_data.weights.push(*weights);
+                    }
+                    (mesh_data.positions.len() - 1) as u32
+                })
+            }));
+        }
+        mesh_data
+    }
+}
+
+/// Checks if moving `from` onto `to` would flip or flatten any of `from`'s other triangles.
+fn collapse_flips_triangles(
+    positions: &[[f64; 3]],
+    triangles: &[[usize; 3]],
+    from_triangles: &[usize],
+    from: usize,
+    to: usize,
+) -> bool {
+    from_triangles.iter().any(|triangle| {
+        let triangle = triangles[*triangle];
+        if triangle.contains(&to) {
+            // This triangle is removed by the collapse.
+            return false;
+        }
+        let [a, b, c] = triangle.map(|v| positions[v]);
+        let before = cross(sub(b, a), sub(c, a));
+        let [a, b, c] = triangle.map(|v| positions[if v == from { to } else { v }]);
+        let after = cross(sub(b, a), sub(c, a));
+        dot(before, after) <= 0.0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A flat square in the XY plane split into a `size` by `size` grid of quads.
+    fn grid(size: usize) -> MeshData {
+        let mut mesh_data = MeshData::new();
+        for y in 0..=size {
+            for x in 0..=size {
+                let uv = Vec2::new(x as f32, y as f32) / size as f32;
+                mesh_data.positions.push(uv.extend(0.0));
+                mesh_data.normals.push(Vec3::Z);
+                mesh_data.texture_coordinates.push(uv);
+            }
+        }
+        let row = size as u32 + 1;
+        for y in 0..size as u32 {
+            for x in 0..size as u32 {
+                let i = y * row + x;
+                mesh_data.indices.push([i, i + 1, i + row + 1]);
+                mesh_data.indices.push([i, i + row + 1, i + row]);
+            }
+        }
+        mesh_data
+    }
+
+    #[test]
+    fn flat_grid_simplifies_to_target() {
+        let mesh_data = grid(16);
+        let simplified = mesh_data.simplify(64);
+        assert!(simplified.indices.len() <= 64);
+        assert!(!simplified.indices.is_empty());
+        // Every vertex stays on the plane.
+        assert!(simplified.positions.iter().all(|p| p.z == 0.0));
+    }
+
+    #[test]
+    fn borders_are_preserved() {
+        let simplified = grid(16).simplify(32);
+        let bounds = Box3::from_points(simplified.positions.iter().copied());
+        assert_eq!(bounds.min, Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(bounds.max, Vec3::new(1.0, 1.0, 0.0));
+
+        // The simplified triangles still cover the whole square.
+        let area: f32 = simplified
+            .indices
+            .iter()
+            .map(|t| {
+                let [a, b, c] = t.map(|i| simplified.positions[i as usize]);
+                (b - a).cross(c - a).z * 0.5
+            })
+            .sum();
+        assert!((area - 1.0).abs() < 0.0001, "area: {}", area);
+    }
+
+    #[test]
+    fn attributes_follow_vertices() {
+        let mesh_data = grid(8);
+        let simplified = mesh_data.simplify(20);
+        assert_eq!(simplified.positions.len(), simplified.normals.len());
+        assert_eq!(
+            simplified.positions.len(),
+            simplified.texture_coordinates.len()
+        );
+        assert!(simplified.colors.is_empty());
+        for (p, uv) in simplified
+            .positions
+            .iter()
+            .zip(simplified.texture_coordinates.iter())
+        {
+            assert_eq!(p.xy(), *uv);
+        }
+    }
+
+    #[test]
+    fn lod_chain_decreases() {
+        let chain = grid(16).generate_lod_chain(3, 0.5);
+        assert_eq!(chain.len(), 3);
+        let mut previous = 16 * 16 * 2;
+        for level in &chain {
+            assert!(level.indices.len() <= previous / 2);
+            previous = level.indices.len();
+        }
+    }
+}
@@ -1,11 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub use crate::graphics::texture::Texture;
 use crate::*;
 use kgraphics::*;
 
 pub use kgraphics::{
-    BlendFactor, FacesToRender, FilterMode, Framebuffer, Pipeline, TextureSettings, WrappingMode,
+    BlendFactor, CompareFunction, FacesToRender, FilterMode, Framebuffer, Pipeline,
+    StencilOperation, StencilState, TextureSettings, WrappingMode,
 };
 
 mod camera;
@@ -14,18 +15,44 @@ pub use camera::*;
 mod camera_controls;
 pub use camera_controls::*;
 
+mod post_processing;
+pub use post_processing::*;
+
 mod render_flags;
 pub use render_flags::*;
 
 mod texture;
 pub use texture::*;
 
+mod compressed_texture;
+pub use compressed_texture::*;
+
+#[cfg(feature = "ktx2")]
+mod basis_universal;
+#[cfg(feature = "dds")]
+mod dds;
+#[cfg(feature = "ktx2")]
+mod ktx2;
+
 mod cube_map;
 pub use cube_map::*;
 
+mod procedural_sky;
+pub use procedural_sky::*;
+
 mod mesh;
 pub use mesh::*;
 
+mod mesh_simplification;
+
+mod tangent_generation;
+
+mod level_of_detail;
+pub use level_of_detail::*;
+
+mod terrain;
+pub use terrain::*;
+
 mod shader;
 pub use shader::*;
 
@@ -38,11 +65,22 @@ pub use light::*;
 mod immediate_drawer;
 pub use immediate_drawer::*;
 
+mod particles;
+pub use particles::*;
+
 mod shader_parser;
 
 mod renderer;
 pub use renderer::*;
 
+mod screenshot;
+pub use screenshot::*;
+
+#[cfg(feature = "png")]
+mod frame_recorder;
+#[cfg(feature = "png")]
+pub use frame_recorder::*;
+
 pub fn graphics_plugin() -> Plugin {
     Plugin {
         setup_systems: vec![setup_graphics.system()],
@@ -50,10 +88,16 @@ pub fn graphics_plugin() -> Plugin {
             assign_current_camera_target.system(),
             check_for_dropped_graphics_assets.system(),
         ],
+        pre_draw_systems: vec![build_terrain_chunks.system()],
         draw_systems: vec![
             load_shaders.system(),
+            load_materials.system(),
+            compile_shader_variants.system(),
+            compile_decal_shader_variants.system(),
             #[cfg(not(feature = "headless"))]
             resize_window.system(),
+            #[cfg(feature = "png")]
+            frame_recorder::record_frames.system(),
         ],
         end_of_frame_systems: vec![
             load_textures.system(),
@@ -115,6 +159,8 @@ pub struct GraphicsInner {
     /// This is used by XR devices.
     pub override_views: Vec<GraphicsViewInfo>,
     pub current_target_framebuffer: Framebuffer,
+    window_size: (u32, u32),
+    screenshot_requests: Vec<ScreenshotRequest>,
     /// Shader snippets that can be pasted into shaders.
     shader_snippets: HashMap<&'static str, &'static str>,
     #[cfg(feature = "xr")]
@@ -131,11 +177,14 @@ pub struct GraphicsViewInfo {
     pub output_rectangle: Box2,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct PipelineSettings {
     pub faces_to_render: FacesToRender,
     pub blending: Option<(BlendFactor, BlendFactor)>,
     pub depth_test: DepthTest,
+    /// `None` disables the stencil test.
+    /// Only has an effect when rendering to a target with a stencil buffer.
+    pub stencil: Option<StencilState>,
 }
 
 impl Default for PipelineSettings {
@@ -144,6 +193,7 @@ impl Default for PipelineSettings {
             faces_to_render: FacesToRender::Front,
             blending: None,
             depth_test: DepthTest::LessOrEqual,
+            stencil: None,
         }
     }
 }
@@ -209,6 +259,8 @@ fn setup_graphics(world: &mut World) {
         primary_camera_target: CameraTarget::Primary,
         override_views: Vec::new(),
         current_target_framebuffer: Framebuffer::default(),
+        window_size: (window_width, window_height),
+        screenshot_requests: Vec::new(),
         shader_snippets: HashMap::new(),
         #[cfg(feature = "xr")]
         multiview_support,
@@ -226,6 +278,12 @@ fn setup_graphics(world: &mut World) {
         include_str!("built_in_shaders/fullscreen_vertex_snippet.glsl"),
     );
 
+    graphics.register_shader_snippet("fog", include_str!("built_in_shaders/fog_snippet.glsl"));
+    graphics.register_shader_snippet(
+        "projected_decal",
+        include_str!("built_in_shaders/projected_decal_snippet.glsl"),
+    );
+
     let default_mesh = graphics.new_gpu_mesh(&MeshData::default()).unwrap();
     let mut mesh_assets = Assets::<Mesh>::new(
         Mesh {
@@ -252,7 +310,9 @@ fn setup_graphics(world: &mut World) {
             },
         )
         .unwrap();
-    let mut texture_assets = Assets::new(white_texture, TextureAssetLoader::new());
+    let texture_asset_loader = TextureAssetLoader::new()
+        .with_supported_pixel_formats(supported_compressed_pixel_formats(&graphics));
+    let mut texture_assets = Assets::new(white_texture, texture_asset_loader);
 
     let default_shader = graphics
         .new_shader(
@@ -327,10 +387,16 @@ impl GraphicsInner {
         &mut self,
         source: &str,
         prepend: &str,
+        defines: &[&str],
         pipeline_settings: PipelineSettings,
-    ) -> Result<Pipeline, PipelineError> {
+    ) -> Result<(Pipeline, HashSet<String>), PipelineError> {
         let (vertex_source, fragment_source) =
-            shader_parser::parse_shader(&self.shader_snippets, source, prepend);
+            shader_parser::parse_shader(&self.shader_snippets, source, prepend, defines);
+        let uniforms = shader_parser::uniform_names(&vertex_source)
+            .into_iter()
+            .chain(shader_parser::uniform_names(&fragment_source))
+            .map(|name| name.to_string())
+            .collect();
 
         let vertex_function = self
             .context
@@ -351,7 +417,9 @@ impl GraphicsInner {
             .blending(pipeline_settings.blending)
             .faces_to_render(pipeline_settings.faces_to_render)
             .depth_test(pipeline_settings.depth_test)
+            .stencil(pipeline_settings.stencil)
             .build()
+            .map(|pipeline| (pipeline, uniforms))
             .map_err(PipelineError::PipelineCompilationError)
     }
 
@@ -371,17 +439,56 @@ impl GraphicsInner {
         source: &str,
         pipeline_settings: PipelineSettings,
     ) -> Result<Shader, PipelineError> {
-        let pipeline = self.create_pipeline(source, "#define NUM_VIEWS 1 \n", pipeline_settings)?;
+        self.new_shader_with_keywords(name, source, pipeline_settings, &[])
+    }
+
+    /// Compiles a variant of `shader` with each of the `key`'s keywords defined and
+    /// the `key`'s [PipelineSettings], if any, replacing the `shader`'s.
+    /// [Material]s request variants with [Material::set_keyword] and [Material::set_pipeline_settings]
+    /// so this usually doesn't need to be called directly.
+    pub fn new_shader_variant(
+        &mut self,
+        shader: &Shader,
+        key: &ShaderVariantKey,
+    ) -> Result<Shader, PipelineError> {
+        let keywords: Vec<&str> = key.keywords.iter().map(|k| k.as_str()).collect();
+        self.new_shader_with_keywords(
+            shader.name,
+            &shader.source,
+            key.pipeline_settings.unwrap_or(shader.pipeline_settings),
+            &keywords,
+        )
+    }
+
+    fn new_shader_with_keywords(
+        &mut self,
+        name: &'static str,
+        source: &str,
+        pipeline_settings: PipelineSettings,
+        keywords: &[&str],
+    ) -> Result<Shader, PipelineError> {
+        let (pipeline, uniforms) = self.create_pipeline(
+            source,
+            "#define NUM_VIEWS 1 \n",
+            keywords,
+            pipeline_settings,
+        )?;
 
         #[cfg(feature = "xr")]
         let multiview_pipeline = match self.multiview_support {
             MultiviewSupport::None => None,
             MultiviewSupport::WithoutMsaa | MultiviewSupport::OculusWithMsaa => {
-                Some(self.create_pipeline(
-                    source,
-                    &"#define NUM_VIEWS 2 \n #define MULTIVIEW \n",
-                    pipeline_settings,
-                )?)
+                let mut defines = keywords.to_vec();
+                defines.push("MULTIVIEW");
+                Some(
+                    self.create_pipeline(
+                        source,
+                        "#define NUM_VIEWS 2 \n",
+                        &defines,
+                        pipeline_settings,
+                    )?
+                    .0,
+                )
             }
         };
 
@@ -390,6 +497,10 @@ impl GraphicsInner {
             pipeline,
             #[cfg(feature = "xr")]
             multiview_pipeline,
+            source: source.to_string(),
+            pipeline_settings,
+            uniforms,
+            variants: HashMap::new(),
         })
     }
 
@@ -456,12 +567,27 @@ impl GraphicsInner {
         } else {
             None
         };
+        let texture_coordinates_1 = if !mesh_data.texture_coordinates_1.is_empty() {
+            assert_eq!(mesh_data.texture_coordinates_1.len(), len);
+            Some(
+                self.context
+                    .new_data_buffer(&mesh_data.texture_coordinates_1)?,
+            )
+        } else {
+            None
+        };
         let normals = if !mesh_data.normals.is_empty() {
             assert_eq!(mesh_data.normals.len(), len);
             Some(self.context.new_data_buffer(&mesh_data.normals)?)
         } else {
             None
         };
+        let tangents = if !mesh_data.tangents.is_empty() {
+            assert_eq!(mesh_data.tangents.len(), len);
+            Some(self.context.new_data_buffer(&mesh_data.tangents)?)
+        } else {
+            None
+        };
 
         let colors = if !mesh_data.colors.is_empty() {
             assert_eq!(mesh_data.colors.len(), len);
@@ -469,14 +595,30 @@ impl GraphicsInner {
         } else {
             None
         };
+        let joints = if !mesh_data.joints.is_empty() {
+            assert_eq!(mesh_data.joints.len(), len);
+            Some(self.context.new_data_buffer(&mesh_data.joints)?)
+        } else {
+            None
+        };
+        let weights = if !mesh_data.weights.is_empty() {
+            assert_eq!(mesh_data.weights.len(), len);
+            Some(self.context.new_data_buffer(&mesh_data.weights)?)
+        } else {
+            None
+        };
 
         Ok(GPUMesh {
             positions: self.context.new_data_buffer(&mesh_data.positions)?,
             texture_coordinates,
+            texture_coordinates_1,
             normals,
+            tangents,
             index_buffer: self.context.new_index_buffer(index_buffer)?,
             triangle_count,
             colors,
+            joints,
+            weights,
         })
     }
 
@@ -484,9 +626,13 @@ impl GraphicsInner {
         let GPUMesh {
             positions,
             normals,
+            tangents,
             index_buffer,
             texture_coordinates,
+            texture_coordinates_1,
             colors,
+            joints,
+            weights,
             triangle_count: _,
         } = gpu_mesh;
         self.context.delete_data_buffer(positions);
@@ -495,12 +641,24 @@ impl GraphicsInner {
         if let Some(d) = normals {
             self.context.delete_data_buffer(d);
         }
+        if let Some(d) = tangents {
+            self.context.delete_data_buffer(d);
+        }
         if let Some(d) = texture_coordinates {
             self.context.delete_data_buffer(d);
         }
+        if let Some(d) = texture_coordinates_1 {
+            self.context.delete_data_buffer(d);
+        }
         if let Some(d) = colors {
             self.context.delete_data_buffer(d);
         }
+        if let Some(d) = joints {
+            self.context.delete_data_buffer(d);
+        }
+        if let Some(d) = weights {
+            self.context.delete_data_buffer(d);
+        }
     }
 
     pub fn register_shader_snippet(&mut self, name: &'static str, snippet: &'static str) {
@@ -518,6 +676,7 @@ pub fn resize_window(graphics: &mut Graphics, window: &NotSendSync<kapp::Window>
     graphics
         .context
         .resize(main_window, window_width, window_height);
+    graphics.window_size = (window_width, window_height);
 }
 
 fn check_for_dropped_graphics_assets(
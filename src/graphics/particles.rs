@@ -0,0 +1,333 @@
+use crate::*;
+use core::ops::Range;
+
+pub fn particles_plugin() -> Plugin {
+    Plugin {
+        fixed_update_systems: vec![update_particle_emitters.system()],
+        ..Default::default()
+    }
+}
+
+/// Where a [ParticleEmitter] spawns particles, relative to its [Transform].
+#[derive(Clone, Debug)]
+pub enum EmitterShape {
+    /// Particles start at the emitter's position and move in random directions.
+    Point,
+    /// Particles start within a sphere and move outwards.
+    Sphere { radius: f32 },
+    /// Particles start within a disc of `radius` and move upwards, spreading by up to `angle` radians.
+    Cone { angle: f32, radius: f32 },
+    /// Particles start within a box and move in random directions.
+    Box { half_extents: Vec3 },
+    /// Particles start on the surface of a [Mesh] and move away from it.
+    /// The [Mesh] must keep its [MeshData].
+    MeshSurface(Handle<Mesh>),
+}
+
+/// Particles emitted all at once.
+#[derive(Clone, Debug)]
+pub struct ParticleBurst {
+    /// Seconds after the [ParticleEmitter] is spawned.
+    pub time: f32,
+    pub count: usize,
+}
+
+/// Animates particles through frames of a sprite-sheet.
+#[derive(Clone, Debug)]
+pub struct ParticleSpriteSheet {
+    pub sprite_map: SpriteMap,
+    /// How many frames are in each row of the sprite-sheet.
+    pub columns: usize,
+    pub frame_count: usize,
+    /// If `None` the frames play once over each particle's lifetime.
+    pub frames_per_second: Option<f32>,
+}
+
+impl ParticleSpriteSheet {
+    /// The [Sprite] a particle shows at `age` seconds.
+    pub fn sprite(&self, age: f32, lifetime: f32) -> Sprite {
+        let frame = match self.frames_per_second {
+            Some(frames_per_second) => (age * frames_per_second) as usize % self.frame_count,
+            None => ((age / lifetime * self.frame_count as f32) as usize).min(self.frame_count - 1),
+        };
+        self.sprite_map
+            .get_sprite(frame % self.columns, frame / self.columns)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Particle {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    /// Seconds since the particle was emitted.
+    pub age: f32,
+    /// How many seconds the particle lives for.
+    pub lifetime: f32,
+}
+
+impl Particle {
+    /// How far through its life the particle is, from 0.0 to 1.0.
+    pub fn life(&self) -> f32 {
+        self.age / self.lifetime
+    }
+}
+
+/// Emits and simulates particles that render as camera-facing quads.
+/// Particles are simulated in world space so they're left behind when the emitter moves.
+#[derive(Component, Clone)]
+pub struct ParticleEmitter {
+    /// Particles emitted per second.
+    pub rate: f32,
+    pub bursts: Vec<ParticleBurst>,
+    pub shape: EmitterShape,
+    /// Each particle lives for a random number of seconds in this range.
+    pub lifetime: Range<f32>,
+    /// Each particle starts with a random speed in this range, directed by the [EmitterShape].
+    pub speed: Range<f32>,
+    /// Size over a particle's life, from 0.0 to 1.0.
+    pub size: Curve<f32>,
+    /// Color over a particle's life, from 0.0 to 1.0.
+    pub color: Curve<Color>,
+    /// Velocity added over a particle's life, from 0.0 to 1.0.
+    /// Unlike forces this doesn't accumulate.
+    pub velocity: Curve<Vec3>,
+    pub gravity: Vec3,
+    /// How quickly particles slow down. 0.0 is no drag.
+    pub drag: f32,
+    /// How strongly particles are pushed around by turbulent noise. 0.0 is no noise.
+    pub noise_strength: f32,
+    /// How small the swirls of the noise are.
+    pub noise_frequency: f32,
+    pub texture: Handle<Texture>,
+    /// If set particles are animated through its frames instead of using `texture`.
+    pub sprite_sheet: Option<ParticleSpriteSheet>,
+    /// How far particles fade out in front of opaque surfaces to avoid hard edges.
+    /// 0.0 disables soft particles. Soft particles require [Camera] post-processing.
+    pub soft_distance: f32,
+    /// If `true` particles brighten what's behind them instead of covering it.
+    pub additive: bool,
+    /// Particles aren't emitted past this many.
+    pub max_particles: usize,
+    /// If `false` no new particles are emitted, but existing particles still simulate.
+    pub emitting: bool,
+    particles: Vec<Particle>,
+    elapsed: f32,
+    to_emit: f32,
+    pending_burst: usize,
+    random: Random,
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParticleEmitter {
+    pub fn new() -> Self {
+        Self {
+            rate: 10.0,
+            bursts: Vec::new(),
+            shape: EmitterShape::Point,
+            lifetime: 1.0..2.0,
+            speed: 1.0..2.0,
+            size: Curve::constant(0.1),
+            color: Curve::constant(Color::WHITE),
+            velocity: Curve::constant(Vec3::ZERO),
+            gravity: Vec3::ZERO,
+            drag: 0.0,
+            noise_strength: 0.0,
+            noise_frequency: 1.0,
+            texture: Texture::WHITE,
+            sprite_sheet: None,
+            soft_distance: 0.25,
+            additive: false,
+            max_particles: 1000,
+            emitting: true,
+            particles: Vec::new(),
+            elapsed: 0.0,
+            to_emit: 0.0,
+            pending_burst: 0,
+            random: Random::new(),
+        }
+    }
+
+    /// Emits `count` particles during the next update.
+    pub fn burst(&mut self, count: usize) {
+        self.pending_burst += count;
+    }
+
+    /// Removes all live particles.
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// The [Texture] particles are drawn with.
+    pub fn texture_handle(&self) -> &Handle<Texture> {
+        self.sprite_sheet
+            .as_ref()
+            .map_or(&self.texture, |s| s.sprite_map.texture_handle())
+    }
+
+    fn update(&mut self, transform: &Transform, meshes: &Assets<Mesh>, delta_seconds: f32) {
+        let previous_elapsed = self.elapsed;
+        self.elapsed += delta_seconds;
+
+        // Age and simulate existing particles.
+        let drag = (-self.drag * delta_seconds).exp();
+        let time = self.elapsed;
+        self.particles.retain_mut(|particle| {
+            particle.age += delta_seconds;
+            if particle.age >= particle.lifetime {
+                return false;
+            }
+            particle.velocity += self.gravity * delta_seconds;
+            if self.noise_strength != 0.0 {
+                particle.velocity += turbulence(particle.position * self.noise_frequency, time)
+                    * (self.noise_strength * delta_seconds);
+            }
+            particle.velocity *= drag;
+            particle.position +=
+                (particle.velocity + self.velocity.sample(particle.life())) * delta_seconds;
+            true
+        });
+
+        // Emit new particles.
+        let mut count = std::mem::take(&mut self.pending_burst);
+        if self.emitting {
+            self.to_emit += self.rate * delta_seconds;
+            count += self.to_emit as usize;
+            self.to_emit = self.to_emit.fract();
+
+            for burst in &self.bursts {
+                if burst.time >= previous_elapsed && burst.time < self.elapsed {
+                    count += burst.count;
+                }
+            }
+        }
+        let count = count.min(self.max_particles.saturating_sub(self.particles.len()));
+        if count == 0 {
+            return;
+        }
+
+        let model = transform.model();
+        let mesh_data = match &self.shape {
+            EmitterShape::MeshSurface(mesh) => meshes.get(mesh).mesh_data.as_ref(),
+            _ => None,
+        };
+        // Triangles are picked in proportion to their area so particles spread evenly.
+        let triangle_areas: Vec<f32> = mesh_data.map_or(Vec::new(), |mesh_data| {
+            let mut total = 0.0;
+            mesh_data
+                .indices
+                .iter()
+                .map(|[a, b, c]| {
+                    let p = &mesh_data.positions;
+                    let (a, b, c) = (p[*a as usize], p[*b as usize], p[*c as usize]);
+                    total += (b - a).cross(c - a).length() * 0.5;
+                    total
+                })
+                .collect()
+        });
+
+        for _ in 0..count {
+            let (position, direction): (Vec3, Vec3) = match &self.shape {
+                EmitterShape::Point => (Vec3::ZERO, self.random.normalized_vec()),
+                EmitterShape::Sphere { radius } => {
+                    let position: Vec3 = self.random.point_in_unit_sphere() * *radius;
+                    let direction = if position.length() > 0.0 {
+                        position.normalized()
+                    } else {
+                        self.random.normalized_vec()
+                    };
+                    (position, direction)
+                }
+                EmitterShape::Cone { angle, radius } => {
+                    let disc: Vec2 = self.random.point_in_unit_sphere();
+                    let disc = Vec3::new(disc.x, 0.0, disc.y);
+                    let direction = (Vec3::Y + disc * angle.tan()).normalized();
+                    (disc * *radius, direction)
+                }
+                EmitterShape::Box { half_extents } => {
+                    let position = Vec3::new(
+                        self.random.range_f32(-half_extents.x..half_extents.x),
+                        self.random.range_f32(-half_extents.y..half_extents.y),
+                        self.random.range_f32(-half_extents.z..half_extents.z),
+                    );
+                    (position, self.random.normalized_vec())
+                }
+                EmitterShape::MeshSurface(_) => match (mesh_data, triangle_areas.last()) {
+                    (Some(mesh_data), Some(total)) if *total > 0.0 => {
+                        let target = self.random.f32() * total;
+                        let triangle = triangle_areas
+                            .partition_point(|area| *area < target)
+                            .min(triangle_areas.len() - 1);
+                        let [a, b, c] = mesh_data.indices[triangle];
+                        let p = &mesh_data.positions;
+                        let (a, b, c) = (p[a as usize], p[b as usize], p[c as usize]);
+
+                        // Fold points outside the triangle back into it.
+                        let (mut u, mut v) = (self.random.f32(), self.random.f32());
+                        if u + v > 1.0 {
+                            u = 1.0 - u;
+                            v = 1.0 - v;
+                        }
+                        (
+                            a + (b - a) * u + (c - a) * v,
+                            (b - a).cross(c - a).normalized(),
+                        )
+                    }
+                    // The mesh hasn't loaded yet or has no surface.
+                    _ => (Vec3::ZERO, self.random.normalized_vec()),
+                },
+            };
+
+            let direction = model.transform_vector(direction);
+            let direction = if direction.length() > 0.0 {
+                direction.normalized()
+            } else {
+                direction
+            };
+            let speed = random_in_range(&mut self.random, &self.speed);
+            let lifetime = random_in_range(&mut self.random, &self.lifetime);
+            self.particles.push(Particle {
+                position: model.transform_point(position),
+                velocity: direction * speed,
+                age: 0.0,
+                lifetime,
+            });
+        }
+    }
+}
+
+fn random_in_range(random: &mut Random, range: &Range<f32>) -> f32 {
+    if range.end > range.start {
+        random.range_f32(range.clone())
+    } else {
+        range.start
+    }
+}
+
+/// A cheap swirling force that varies smoothly over space and time.
+fn turbulence(p: Vec3, time: f32) -> Vec3 {
+    Vec3::new(
+        (p.y * 1.7 + time).sin() + (p.z * 2.3 - time * 0.8).sin(),
+        (p.z * 1.9 + time * 1.1).sin() + (p.x * 2.1 - time * 0.6).sin(),
+        (p.x * 1.5 + time * 0.9).sin() + (p.y * 2.7 - time * 1.2).sin(),
+    ) * 0.5
+}
+
+fn update_particle_emitters(
+    time: &Time,
+    meshes: &Assets<Mesh>,
+    mut emitters: Query<(&GlobalTransform, &mut ParticleEmitter)>,
+) {
+    let delta_seconds = time.fixed_time_step as f32;
+    for (transform, emitter) in &mut emitters {
+        emitter.update(transform, meshes, delta_seconds);
+    }
+}
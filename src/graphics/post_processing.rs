@@ -0,0 +1,283 @@
+use crate::*;
+
+/// How bright scene colors are compressed into the range a display can show.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tonemapping {
+    /// Colors brighter than the display can show are clipped.
+    None,
+    /// A simple curve that never fully reaches white.
+    Reinhard,
+    /// Stephen Hill's fit of the ACES filmic curve.
+    AcesFitted,
+    /// Bright colors desaturate towards white like film, instead of shifting hue.
+    AgX,
+    /// John Hable's filmic curve from Uncharted 2.
+    Uncharted2,
+}
+
+/// How much scene colors are scaled before they're tonemapped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exposure {
+    /// Scene colors are multiplied by 2 to the power of `stops`. 0.0 leaves them unchanged.
+    Manual { stops: f32 },
+    /// Adapts to the scene's average luminance over time, like an eye adjusting to the dark.
+    Automatic {
+        /// Stops added to the automatic exposure. Positive values brighten the scene.
+        compensation: f32,
+        /// The darkest average luminance adapted to, as a power of 2.
+        min_luminance_log2: f32,
+        /// The brightest average luminance adapted to, as a power of 2.
+        max_luminance_log2: f32,
+        /// How quickly exposure adapts to changes in brightness. Higher is faster.
+        adaptation_speed: f32,
+    },
+}
+
+impl Exposure {
+    /// [Exposure::Automatic] with reasonable defaults.
+    pub fn automatic() -> Self {
+        Exposure::Automatic {
+            compensation: 0.0,
+            min_luminance_log2: -8.0,
+            max_luminance_log2: 8.0,
+            adaptation_speed: 1.5,
+        }
+    }
+}
+
+/// Attach to an [Entity] with a [Camera] to configure its post-processing.
+/// Only used if the [Camera]'s `post_processing_enabled` is `true`.
+/// Cameras without this component use [PostProcessingSettings::default].
+#[derive(Component, Clone, Debug)]
+pub struct PostProcessingSettings {
+    pub tonemapping: Tonemapping,
+    pub exposure: Exposure,
+    /// The color temperature, in Kelvin, that appears white.
+    /// Lower values make the image cooler and higher values make it warmer.
+    /// 6500.0 leaves colors unchanged.
+    pub white_balance: f32,
+    /// How much the image darkens towards its corners. 0.0 is no vignette.
+    pub vignette_intensity: f32,
+    /// How far from the corners the vignette fades in, from 0.0 to 1.0.
+    pub vignette_smoothness: f32,
+    /// How strong the animated film grain noise is. 0.0 is no grain.
+    pub film_grain: f32,
+    /// A color-grading lookup table loaded from a `.cube` file.
+    /// It's applied to tonemapped sRGB colors.
+    pub color_grading_lut: Option<Handle<Texture>>,
+}
+
+impl Default for PostProcessingSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PostProcessingSettings {
+    pub fn new() -> Self {
+        Self {
+            tonemapping: Tonemapping::None,
+            exposure: Exposure::Manual { stops: 0.0 },
+            white_balance: NEUTRAL_TEMPERATURE,
+            vignette_intensity: 0.0,
+            vignette_smoothness: 0.5,
+            film_grain: 0.0,
+            color_grading_lut: None,
+        }
+    }
+
+    /// Per-channel scales in linear sRGB that make light of the `white_balance` temperature appear white.
+    /// The scales preserve luminance.
+    pub fn white_balance_scale(&self) -> Vec3 {
+        let to_rgb = |temperature: f32| {
+            Color::from_temperature(temperature)
+                .to_rgb_color(color_spaces::LINEAR_SRGB)
+                .xyz()
+        };
+        let scale = to_rgb(NEUTRAL_TEMPERATURE).div_by_component(to_rgb(self.white_balance));
+        scale / scale.dot(Vec3::new(0.2126, 0.7152, 0.0722))
+    }
+}
+
+/// The color temperature that white balancing leaves unchanged, roughly that of daylight.
+const NEUTRAL_TEMPERATURE: f32 = 6500.0;
+
+/// Parses a `.cube` color-grading lookup table into a texture.
+/// The 3D table is laid out as a horizontal strip of square slices, one for each blue value,
+/// because 3D textures aren't supported everywhere.
+pub fn cube_lut_data_from_bytes(bytes: &[u8]) -> Result<TextureLoadData, TextureLoadError> {
+    let source = std::str::from_utf8(bytes)
+        .map_err(|_| TextureLoadError::CouldNotDecode("A .cube file must be UTF-8 text".into()))?;
+    let (size, entries) = parse_cube_lut(source).map_err(TextureLoadError::CouldNotDecode)?;
+
+    let to_u8 = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let mut pixels = vec![[0u8; 4]; entries.len()];
+    for (i, entry) in entries.iter().enumerate() {
+        let (red, green, blue) = (i % size, (i / size) % size, i / (size * size));
+        pixels[green * size * size + blue * size + red] =
+            [to_u8(entry.x), to_u8(entry.y), to_u8(entry.z), 255];
+    }
+
+    Ok(TextureLoadData {
+        data: TextureData::Bytes(Box::new(pixels)),
+        pixel_format: kgraphics::PixelFormat::RGBA8Unorm,
+        width: (size * size) as u32,
+        height: size as u32,
+    })
+}
+
+/// Returns the table's size and its entries, with red changing fastest and blue slowest.
+/// Tables with a domain other than 0.0 to 1.0 are resampled to that domain.
+fn parse_cube_lut(source: &str) -> Result<(usize, Vec<Vec3>), String> {
+    let mut size = None;
+    let mut domain_min = Vec3::ZERO;
+    let mut domain_max = Vec3::ONE;
+    let mut entries = Vec::new();
+
+    let parse_floats = |words: &[&str]| -> Result<Vec<f32>, String> {
+        words
+            .iter()
+            .map(|w| w.parse().map_err(|_| format!("Expected a number: {:?}", w)))
+            .collect()
+    };
+    let parse_vec3 = |words: &[&str]| -> Result<Vec3, String> {
+        match parse_floats(words)?[..] {
+            [x, y, z] => Ok(Vec3::new(x, y, z)),
+            _ => Err(format!("Expected three numbers: {:?}", words.join(" "))),
+        }
+    };
+
+    for line in source.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[0] {
+            "LUT_3D_SIZE" => {
+                size = Some(
+                    words
+                        .get(1)
+                        .and_then(|s| s.parse::<usize>().ok())
+                        // The range the format allows. It also keeps `size` cubed from overflowing.
+                        .filter(|s| (2..=256).contains(s))
+                        .ok_or_else(|| format!("Invalid LUT_3D_SIZE: {:?}", line))?,
+                )
+            }
+            "LUT_1D_SIZE" => return Err("1D lookup tables are unsupported".into()),
+            "DOMAIN_MIN" => domain_min = parse_vec3(&words[1..])?,
+            "DOMAIN_MAX" => domain_max = parse_vec3(&words[1..])?,
+            "LUT_3D_INPUT_RANGE" => match parse_floats(&words[1..])?[..] {
+                [min, max] => {
+                    domain_min = Vec3::fill(min);
+                    domain_max = Vec3::fill(max);
+                }
+                _ => return Err(format!("Invalid LUT_3D_INPUT_RANGE: {:?}", line)),
+            },
+            // Skip keywords that don't affect the table, like `TITLE`.
+            word if word.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+            _ => entries.push(parse_vec3(&words)?),
+        }
+    }
+
+    let size = size.ok_or("Missing LUT_3D_SIZE")?;
+    if entries.len() != size * size * size {
+        return Err(format!(
+            "Expected {} entries but found {}",
+            size * size * size,
+            entries.len()
+        ));
+    }
+
+    if domain_min != Vec3::ZERO || domain_max != Vec3::ONE {
+        let domain_size = domain_max - domain_min;
+        let mut resampled = Vec::with_capacity(entries.len());
+        for i in 0..entries.len() {
+            let coordinate = Vec3::new(
+                (i % size) as f32,
+                ((i / size) % size) as f32,
+                (i / (size * size)) as f32,
+            ) / (size - 1) as f32;
+            let coordinate = (coordinate - domain_min).div_by_component(domain_size);
+            resampled.push(sample_lut(size, &entries, coordinate));
+        }
+        entries = resampled;
+    }
+    Ok((size, entries))
+}
+
+/// Trilinearly samples a lookup table at a coordinate from 0.0 to 1.0.
+fn sample_lut(size: usize, entries: &[Vec3], coordinate: Vec3) -> Vec3 {
+    let position = coordinate.clamp(Vec3::ZERO, Vec3::ONE) * (size - 1) as f32;
+    let start = position.as_usize().min(Vector::fill(size - 2));
+    let amount = position - start.as_f32();
+    let entry = |x: usize, y: usize, z: usize| entries[x + y * size + z * size * size];
+
+    let mut result = Vec3::ZERO;
+    for corner in 0..8 {
+        let offset = Vector::<usize, 3>::new(corner & 1, (corner >> 1) & 1, corner >> 2);
+        let weight = (0..3)
+            .map(|axis| {
+                if offset[axis] == 1 {
+                    amount[axis]
+                } else {
+                    1.0 - amount[axis]
+                }
+            })
+            .product::<f32>();
+        let p = start + offset;
+        result += entry(p.x, p.y, p.z) * weight;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY_LUT: &str = "
+# Created by hand
+TITLE \"Identity\"
+LUT_3D_SIZE 2
+
+0.0 0.0 0.0
+1.0 0.0 0.0
+0.0 1.0 0.0
+1.0 1.0 0.0
+0.0 0.0 1.0
+1.0 0.0 1.0
+0.0 1.0 1.0
+1.0 1.0 1.0
+";
+
+    #[test]
+    fn parse_cube_lut_identity() {
+        let (size, entries) = parse_cube_lut(IDENTITY_LUT).unwrap();
+        assert_eq!(size, 2);
+        assert_eq!(entries.len(), 8);
+        assert_eq!(entries[1], Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(entries[6], Vec3::new(0.0, 1.0, 1.0));
+        assert_eq!(
+            sample_lut(size, &entries, Vec3::new(0.25, 0.5, 0.75)),
+            Vec3::new(0.25, 0.5, 0.75)
+        );
+    }
+
+    #[test]
+    fn parse_cube_lut_domain() {
+        let source = IDENTITY_LUT.replace("LUT_3D_SIZE 2", "LUT_3D_SIZE 2\nDOMAIN_MAX 2.0 2.0 2.0");
+        let (_, entries) = parse_cube_lut(&source).unwrap();
+        // Inputs of 1.0 are halfway through the original domain.
+        assert_eq!(entries[7], Vec3::fill(0.5));
+        assert_eq!(entries[0], Vec3::ZERO);
+    }
+
+    #[test]
+    fn parse_cube_lut_errors() {
+        assert!(parse_cube_lut("0.0 0.0 0.0").is_err());
+        assert!(parse_cube_lut("LUT_3D_SIZE 2\n0.0 0.0 0.0").is_err());
+        assert!(parse_cube_lut("LUT_1D_SIZE 2\n0.0 0.0 0.0\n1.0 1.0 1.0").is_err());
+        assert!(parse_cube_lut("LUT_3D_SIZE 4294967296\n0.0 0.0 0.0").is_err());
+        assert!(cube_lut_data_from_bytes(&[0xff, 0xfe]).is_err());
+    }
+}
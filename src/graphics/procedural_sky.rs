@@ -0,0 +1,153 @@
+use crate::*;
+use kgraphics::*;
+
+/// A physically based sky lit by the first directional [Light] in the scene.
+/// Attach alongside a [ReflectionProbe] to render the sky into the probe's `source` [CubeMap].
+/// The probe's image based lighting is regenerated whenever the sun moves.
+///
+/// Use [spawn_procedural_sky] to spawn a visible sky that lights the scene.
+#[derive(Component, Clone, Debug)]
+pub struct ProceduralSky {
+    /// Haziness of the atmosphere. 2.0 is a clear day and 10.0 is very hazy.
+    pub turbidity: f32,
+    /// Scales the brightness of the sky.
+    pub intensity: f32,
+    /// The angular radius of the sun's disk in radians.
+    pub sun_size: f32,
+    /// The color below the horizon.
+    pub ground_color: Color,
+    /// How far, in radians, the sun must move before the sky and its lighting are regenerated.
+    /// Regenerating is expensive so small movements are ignored.
+    pub update_threshold: f32,
+    rendered_sun_direction: Option<Vec3>,
+}
+
+impl Default for ProceduralSky {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProceduralSky {
+    /// The size of each face of the sky's [CubeMap].
+    pub const FACE_SIZE: u32 = 256;
+
+    pub fn new() -> Self {
+        Self {
+            turbidity: 2.5,
+            intensity: 1.0,
+            sun_size: 0.02,
+            ground_color: Color::new(0.3, 0.3, 0.3, 1.0),
+            update_threshold: 0.02,
+            rendered_sun_direction: None,
+        }
+    }
+
+    /// Regenerate the sky next frame even if the sun hasn't moved.
+    /// Call this after changing the sky's other properties.
+    pub fn regenerate(&mut self) {
+        self.rendered_sun_direction = None;
+    }
+
+    fn needs_update(&self, sun_direction: Vec3) -> bool {
+        match self.rendered_sun_direction {
+            Some(rendered) => rendered.dot(sun_direction) < self.update_threshold.cos(),
+            None => true,
+        }
+    }
+}
+
+/// Renders each [ProceduralSky] and its [ReflectionProbe]'s irradiance maps if its sun has moved.
+pub fn update_procedural_skies(
+    graphics: &mut Graphics,
+    cube_maps: &Assets<CubeMap>,
+    meshes: &Assets<Mesh>,
+    lights: Query<(&GlobalTransform, &Light)>,
+    mut procedural_skies: Query<(&mut ProceduralSky, &ReflectionProbe)>,
+) {
+    // Lights shine along their forward direction so the sun is behind them.
+    // Without a directional light the sun is overhead.
+    let sun_direction = lights
+        .iter()
+        .find(|(_, light)| matches!(light.light_mode, LightMode::Directional))
+        .map_or(Vec3::Y, |(global_transform, _)| {
+            -global_transform.forward().normalized()
+        });
+
+    let cube_map_renderer = cube_maps.asset_loader.cube_map_renderer();
+    for (procedural_sky, reflection_probe) in &mut procedural_skies {
+        if !procedural_sky.needs_update(sun_direction) {
+            continue;
+        }
+
+        let source = cube_maps.get(&reflection_probe.source);
+        cube_map_renderer.render_procedural_sky(
+            graphics,
+            meshes,
+            procedural_sky,
+            sun_direction,
+            source,
+            ProceduralSky::FACE_SIZE as usize,
+        );
+        cube_map_renderer.render_irradiance_cube_maps(
+            graphics,
+            meshes,
+            source,
+            cube_maps.get(&reflection_probe.diffuse_irradiance_map),
+            cube_maps.get(&reflection_probe.specular_irradiance_map),
+        );
+        procedural_sky.rendered_sun_direction = Some(sun_direction);
+    }
+}
+
+/// Spawns a sky box that displays a [ProceduralSky] and a [ReflectionProbe] lit by it.
+pub fn spawn_procedural_sky(world: &mut World, procedural_sky: ProceduralSky) {
+    let (reflection_probe, skybox_material) =
+        (|graphics: &mut Graphics,
+          cube_maps: &mut Assets<CubeMap>,
+          materials: &mut Assets<Material>| {
+            let source = graphics
+                .new_cube_map(
+                    None,
+                    ProceduralSky::FACE_SIZE,
+                    ProceduralSky::FACE_SIZE,
+                    PixelFormat::RGBA16F,
+                    TextureSettings {
+                        srgb: false,
+                        wrapping_horizontal: WrappingMode::ClampToEdge,
+                        wrapping_vertical: WrappingMode::ClampToEdge,
+                        minification_filter: FilterMode::Linear,
+                        magnification_filter: FilterMode::Linear,
+                        generate_mipmaps: true,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+            let (diffuse_irradiance_map, specular_irradiance_map) =
+                new_irradiance_cube_maps(graphics);
+
+            let reflection_probe = ReflectionProbe {
+                source: cube_maps.add(source),
+                diffuse_irradiance_map: cube_maps.add(diffuse_irradiance_map),
+                specular_irradiance_map: cube_maps.add(specular_irradiance_map),
+            };
+
+            let mut material = Material::new(Shader::SKY_BOX);
+            material.set_cube_map("p_environment_map", reflection_probe.source.clone());
+            (reflection_probe, materials.add(material))
+        })
+        .run(world);
+
+    world.spawn((
+        Name("Sky box".into()),
+        Transform::new(),
+        Mesh::CUBE_MAP_CUBE,
+        Color::WHITE,
+        crate::Texture::WHITE,
+        skybox_material,
+        RenderFlags::DO_NOT_CAST_SHADOWS
+            .with_layer(RenderFlags::IGNORE_CULLING)
+            .with_layer(RenderFlags::DEFAULT),
+    ));
+    world.spawn((Transform::new(), reflection_probe, procedural_sky));
+}
@@ -9,6 +9,8 @@ impl RenderFlags {
     pub const DEFAULT: RenderFlags = RenderFlags(1 << 0);
     pub const DO_NOT_CAST_SHADOWS: RenderFlags = RenderFlags(1 << 1);
     pub const IGNORE_CULLING: RenderFlags = RenderFlags(1 << 2);
+    /// [Decal]s aren't drawn onto [Entity]s with this flag, like characters walking over footprints.
+    pub const DO_NOT_RECEIVE_DECALS: RenderFlags = RenderFlags(1 << 3);
     pub const USER_INTERFACE: RenderFlags = RenderFlags(1 << 8);
 
     pub const fn with_layer(mut self, layer: RenderFlags) -> Self {
@@ -21,4 +23,9 @@ impl RenderFlags {
     pub const fn includes_layer(&self, layer: RenderFlags) -> bool {
         self.0 & layer.0 != 0
     }
+
+    /// The layers as bits, for passing to shaders.
+    pub(crate) const fn bits(&self) -> usize {
+        self.0
+    }
 }
@@ -0,0 +1,347 @@
+use super::*;
+
+/// Attach to an [Entity] with a [Camera] to darken ambient and environment light in creases and corners.
+/// Occlusion is estimated from the depth of nearby surfaces, which requires rendering the scene's depth
+/// an extra time.
+#[derive(Component, Clone, Debug)]
+pub struct AmbientOcclusion {
+    /// How far, in world units, surfaces occlude each-other.
+    pub radius: f32,
+    /// How dark occluded areas become. 0.0 disables ambient occlusion.
+    pub intensity: f32,
+    /// Keeps surfaces from occluding themselves. Increase this if flat surfaces look blotchy.
+    pub bias: f32,
+    /// Smooths the noisy occlusion while keeping edges sharp.
+    pub blur: bool,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AmbientOcclusion {
+    pub fn new() -> Self {
+        Self {
+            radius: 0.5,
+            intensity: 1.0,
+            bias: 0.025,
+            blur: true,
+        }
+    }
+}
+
+/// Computes screen-space ambient occlusion at half resolution.
+pub struct AmbientOcclusionCalculator {
+    depth_target: OffscreenRenderTarget,
+    occlusion_target: OffscreenRenderTarget,
+    blur_target: OffscreenRenderTarget,
+    occlusion_shader: Shader,
+    blur_shader: Shader,
+    /// The occlusion texture and the scale from fragment coordinates to its texture coordinates.
+    /// `None` if the [Camera] being rendered doesn't use [AmbientOcclusion].
+    result: Option<(Handle<Texture>, Vec2)>,
+}
+
+impl AmbientOcclusionCalculator {
+    pub fn new(graphics: &mut Graphics, textures: &mut Assets<Texture>) -> Self {
+        let depth_target = OffscreenRenderTarget::new(
+            graphics,
+            textures,
+            Vec2u::ZERO,
+            None,
+            Some((
+                PixelFormat::Depth32F,
+                TextureSettings {
+                    srgb: false,
+                    generate_mipmaps: false,
+                    minification_filter: FilterMode::Nearest,
+                    magnification_filter: FilterMode::Nearest,
+                    wrapping_horizontal: WrappingMode::ClampToEdge,
+                    wrapping_vertical: WrappingMode::ClampToEdge,
+                    ..Default::default()
+                },
+            )),
+        );
+        let settings = Some((
+            PixelFormat::RGBA16F,
+            TextureSettings {
+                srgb: false,
+                generate_mipmaps: false,
+                minification_filter: FilterMode::Linear,
+                magnification_filter: FilterMode::Linear,
+                wrapping_horizontal: WrappingMode::ClampToEdge,
+                wrapping_vertical: WrappingMode::ClampToEdge,
+                ..Default::default()
+            },
+        ));
+        let occlusion_target =
+            OffscreenRenderTarget::new(graphics, textures, Vec2u::ZERO, settings, None);
+        let blur_target =
+            OffscreenRenderTarget::new(graphics, textures, Vec2u::ZERO, settings, None);
+
+        let mut new_shader = |source: &str| {
+            graphics
+                .new_shader(
+                    source,
+                    PipelineSettings {
+                        depth_test: DepthTest::AlwaysPass,
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+        };
+        Self {
+            depth_target,
+            occlusion_target,
+            blur_target,
+            occlusion_shader: new_shader(include_str!(
+                "../built_in_shaders/ambient_occlusion.glsl"
+            )),
+            blur_shader: new_shader(include_str!(
+                "../built_in_shaders/ambient_occlusion_blur.glsl"
+            )),
+            result: None,
+        }
+    }
+
+    /// The occlusion texture for the [Camera] being rendered
+    /// and the scale from fragment coordinates to its texture coordinates.
+    pub fn result(&self) -> Option<&(Handle<Texture>, Vec2)> {
+        self.result.as_ref()
+    }
+
+    /// The target holding the occlusion if it was rendered for the [Camera] being rendered.
+    pub fn target(&self) -> Option<&OffscreenRenderTarget> {
+        self.result.as_ref().map(|_| &self.occlusion_target)
+    }
+
+    /// Call for [Camera]s without [AmbientOcclusion] so that they don't use another [Camera]'s occlusion.
+    pub fn clear(&mut self) {
+        self.result = None;
+    }
+
+    /// Renders the scene's depth from the [Camera] and estimates ambient occlusion from it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        graphics: &mut Graphics,
+        textures: &mut Assets<Texture>,
+        shaders: &Assets<Shader>,
+        materials: &Assets<Material>,
+        meshes: &Assets<Mesh>,
+        command_buffer: &mut CommandBuffer,
+        camera: &Camera,
+        camera_global_transform: &GlobalTransform,
+        renderables: &Renderables,
+        ambient_occlusion: &AmbientOcclusion,
+        view_size: Vec2u,
+    ) {
+        // Half resolution is plenty for occlusion that's blurred anyways.
+        let size = (view_size + Vec2u::ONE) / 2;
+        self.depth_target.resize(graphics, textures, size);
+        self.occlusion_target.resize(graphics, textures, size);
+        self.blur_target.resize(graphics, textures, size);
+
+        let view_matrix = camera_global_transform.model().inversed();
+        let projection_matrix = camera.projection_matrix();
+        self.render_depth(
+            shaders,
+            materials,
+            meshes,
+            command_buffer,
+            camera,
+            &view_matrix,
+            &projection_matrix,
+            renderables,
+        );
+
+        let pipeline = &self.occlusion_shader.pipeline;
+        let mut render_pass = command_buffer
+            .begin_render_pass_with_framebuffer(self.occlusion_target.framebuffer(), None);
+        render_pass.set_viewport(0, 0, size.x as u32, size.y as u32);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_texture_property(
+            &pipeline.get_texture_property("p_depth_texture").unwrap(),
+            Some(textures.get(self.depth_target.depth_texture())),
+            0,
+        );
+        render_pass.set_vec2_property(
+            &pipeline
+                .get_vec2_property("p_texture_coordinate_scale")
+                .unwrap(),
+            Vec2::ONE.into(),
+        );
+        render_pass.set_vec2_property(
+            &pipeline.get_vec2_property("p_depth_texture_scale").unwrap(),
+            self.depth_target.inner_texture_scale().into(),
+        );
+        render_pass.set_vec2_property(
+            &pipeline.get_vec2_property("p_view_size").unwrap(),
+            size.as_f32().into(),
+        );
+        render_pass.set_mat4_property(
+            &pipeline.get_mat4_property("p_projection").unwrap(),
+            projection_matrix.as_array(),
+        );
+        render_pass.set_mat4_property(
+            &pipeline.get_mat4_property("p_inverse_projection").unwrap(),
+            projection_matrix.inversed().as_array(),
+        );
+        render_pass.set_float_property(
+            &pipeline.get_float_property("p_radius").unwrap(),
+            ambient_occlusion.radius,
+        );
+        render_pass.set_float_property(
+            &pipeline.get_float_property("p_intensity").unwrap(),
+            ambient_occlusion.intensity,
+        );
+        render_pass.set_float_property(
+            &pipeline.get_float_property("p_bias").unwrap(),
+            ambient_occlusion.bias,
+        );
+        render_pass.draw_triangles_without_buffer(1);
+
+        if ambient_occlusion.blur {
+            // Blur horizontally into the blur target then vertically back into the occlusion target.
+            let texel_size = self
+                .occlusion_target
+                .inner_texture_scale()
+                .div_by_component(size.as_f32());
+            let passes = [
+                (
+                    &self.occlusion_target,
+                    &self.blur_target,
+                    Vec2::new(texel_size.x, 0.0),
+                ),
+                (
+                    &self.blur_target,
+                    &self.occlusion_target,
+                    Vec2::new(0.0, texel_size.y),
+                ),
+            ];
+            let pipeline = &self.blur_shader.pipeline;
+            for (source, target, direction) in passes {
+                let mut render_pass =
+                    command_buffer.begin_render_pass_with_framebuffer(target.framebuffer(), None);
+                render_pass.set_viewport(0, 0, size.x as u32, size.y as u32);
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_texture_property(
+                    &pipeline.get_texture_property("p_texture").unwrap(),
+                    Some(textures.get(source.color_texture())),
+                    0,
+                );
+                render_pass.set_vec2_property(
+                    &pipeline
+                        .get_vec2_property("p_texture_coordinate_scale")
+                        .unwrap(),
+                    Vec2::ONE.into(),
+                );
+                render_pass.set_vec2_property(
+                    &pipeline.get_vec2_property("p_used_texture_scale").unwrap(),
+                    source.inner_texture_scale().into(),
+                );
+                render_pass.set_vec2_property(
+                    &pipeline.get_vec2_property("p_direction").unwrap(),
+                    direction.into(),
+                );
+                render_pass.draw_triangles_without_buffer(1);
+            }
+        }
+
+        // The occlusion covers the view at half resolution.
+        let scale = self
+            .occlusion_target
+            .inner_texture_scale()
+            .div_by_component(view_size.as_f32());
+        self.result = Some((self.occlusion_target.color_texture().clone(), scale));
+    }
+
+    /// Renders the depth of opaque [Renderables] that the [Camera] sees.
+    #[allow(clippy::too_many_arguments)]
+    fn render_depth(
+        &self,
+        shaders: &Assets<Shader>,
+        materials: &Assets<Material>,
+        meshes: &Assets<Mesh>,
+        command_buffer: &mut CommandBuffer,
+        camera: &Camera,
+        view_matrix: &Mat4,
+        projection_matrix: &Mat4,
+        renderables: &Renderables,
+    ) {
+        let size = self.depth_target.size();
+        let mut render_pass = command_buffer.begin_render_pass_with_framebuffer(
+            self.depth_target.framebuffer(),
+            Some((0.0, 0.0, 0.0, 0.0)),
+        );
+        render_pass.set_viewport(0, 0, size.x as u32, size.y as u32);
+        render_pass.set_depth_mask(true);
+
+        let pipeline = &shaders.get(&Shader::DEPTH_ONLY).pipeline;
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_mat4_property(
+            &pipeline.get_mat4_property("p_views[0]").unwrap(),
+            view_matrix.as_array(),
+        );
+        render_pass.set_mat4_property(
+            &pipeline.get_mat4_property("p_projections[0]").unwrap(),
+            projection_matrix.as_array(),
+        );
+        let model_property = pipeline.get_mat4_property("p_model").unwrap();
+        let position_attribute = pipeline.get_vertex_attribute::<Vec3>("a_position").unwrap();
+
+        let frustum = Frustum::from_matrix(*projection_matrix * *view_matrix);
+        for (
+            global_transform,
+            material_handle,
+            mesh_handle,
+            render_flags,
+            _,
+            _,
+            level_of_detail,
+            _,
+        ) in renderables
+        {
+            let render_flags = render_flags.cloned().unwrap_or(RenderFlags::DEFAULT);
+            if !camera.render_flags.includes_layer(render_flags) {
+                continue;
+            }
+
+            // Transparent surfaces don't occlude.
+            let shader = shaders.get(&materials.get(material_handle).shader);
+            if shader.pipeline.blending().is_some() {
+                continue;
+            }
+
+            let bounding_box = meshes.get(mesh_handle).bounding_box;
+            let should_render = render_flags.includes_layer(RenderFlags::IGNORE_CULLING)
+                || bounding_box.is_none_or(|b| {
+                    frustum_with_bounding_box(&frustum, global_transform.model(), b)
+                });
+            if !should_render {
+                continue;
+            }
+
+            // Use the same level of detail as the scene.
+            let mesh_handle = match level_of_detail_mesh(
+                mesh_handle,
+                level_of_detail,
+                global_transform,
+                bounding_box,
+                view_matrix,
+                projection_matrix,
+            ) {
+                Some(mesh_handle) => mesh_handle,
+                None => continue,
+            };
+
+            if let Some(gpu_mesh) = meshes.get(mesh_handle).gpu_mesh.as_ref() {
+                render_pass.set_mat4_property(&model_property, global_transform.model().as_array());
+                render_pass.set_vertex_attribute(&position_attribute, Some(&gpu_mesh.positions));
+                render_pass.draw_triangles(gpu_mesh.triangle_count, &gpu_mesh.index_buffer);
+            }
+        }
+    }
+}
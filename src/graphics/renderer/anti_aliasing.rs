@@ -0,0 +1,504 @@
+use super::*;
+use std::collections::HashMap;
+
+/// How many jittered frames [AntiAliasing::Temporal] cycles through.
+const JITTER_SAMPLE_COUNT: u32 = 8;
+/// How much of the accumulated history is kept each frame.
+const HISTORY_WEIGHT: f32 = 0.9;
+
+/// The sub-pixel offset [AntiAliasing::Temporal] renders a frame with, from -0.5 to 0.5 pixels.
+/// The offsets follow the Halton (2, 3) sequence which covers a pixel evenly.
+pub fn temporal_jitter(frame: u32) -> Vec2 {
+    let index = frame % JITTER_SAMPLE_COUNT + 1;
+    Vec2::new(halton(index, 2), halton(index, 3)) - Vec2::fill(0.5)
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// A [Camera]'s accumulated [AntiAliasing::Temporal] frames.
+struct TemporalHistory {
+    /// Last frame's history is read from one while this frame's is written to the other.
+    targets: [OffscreenRenderTarget; 2],
+    current_target: usize,
+    /// The unjittered view projection of the last accumulated frame.
+    /// `None` if there's no usable history.
+    previous_view_projection: Option<Mat4>,
+}
+
+/// Which target holds the anti-aliased scene.
+#[derive(Clone, Copy)]
+enum AntiAliasingResult {
+    Output,
+    History { camera_index: usize, target: usize },
+}
+
+/// Smooths the edges of a scene rendered without multisampling.
+pub struct AntiAliasingCalculator {
+    fxaa_shader: Shader,
+    temporal_shader: Shader,
+    sharpen_shader: Shader,
+    motion_vectors_shader: Shader,
+    /// Holds the result of FXAA or sharpening.
+    output_target: OffscreenRenderTarget,
+    motion_target: OffscreenRenderTarget,
+    /// One per [Camera], like the light cluster textures.
+    histories: Vec<TemporalHistory>,
+    /// Each [Entity]'s model matrix from the last frame rendered with [AntiAliasing::Temporal].
+    previous_models: HashMap<Entity, Mat4>,
+    temporal_used_this_frame: bool,
+    /// `None` if the [Camera] being rendered hasn't been anti-aliased.
+    result: Option<AntiAliasingResult>,
+}
+
+fn color_target(graphics: &mut Graphics, textures: &mut Assets<Texture>) -> OffscreenRenderTarget {
+    OffscreenRenderTarget::new(
+        graphics,
+        textures,
+        Vec2u::ZERO,
+        Some((
+            PixelFormat::RGBA16F,
+            TextureSettings {
+                srgb: false,
+                generate_mipmaps: false,
+                minification_filter: FilterMode::Linear,
+                magnification_filter: FilterMode::Linear,
+                wrapping_horizontal: WrappingMode::ClampToEdge,
+                wrapping_vertical: WrappingMode::ClampToEdge,
+                ..Default::default()
+            },
+        )),
+        None,
+    )
+}
+
+impl AntiAliasingCalculator {
+    pub fn new(graphics: &mut Graphics, textures: &mut Assets<Texture>) -> Self {
+        let nearest = TextureSettings {
+            srgb: false,
+            generate_mipmaps: false,
+            minification_filter: FilterMode::Nearest,
+            magnification_filter: FilterMode::Nearest,
+            wrapping_horizontal: WrappingMode::ClampToEdge,
+            wrapping_vertical: WrappingMode::ClampToEdge,
+            ..Default::default()
+        };
+        let motion_target = OffscreenRenderTarget::new(
+            graphics,
+            textures,
+            Vec2u::ZERO,
+            Some((PixelFormat::RGBA16F, nearest)),
+            Some((PixelFormat::Depth32F, nearest)),
+        );
+        let output_target = color_target(graphics, textures);
+
+        let mut new_shader = |source: &str| {
+            graphics
+                .new_shader(
+                    source,
+                    PipelineSettings {
+                        depth_test: DepthTest::AlwaysPass,
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+        };
+        let fxaa_shader = new_shader(include_str!("../built_in_shaders/fxaa.glsl"));
+        let temporal_shader = new_shader(include_str!(
+            "../built_in_shaders/temporal_anti_aliasing.glsl"
+        ));
+        let sharpen_shader = new_shader(include_str!("../built_in_shaders/sharpen.glsl"));
+        let motion_vectors_shader = graphics
+            .new_shader(
+                include_str!("../built_in_shaders/motion_vectors.glsl"),
+                PipelineSettings {
+                    faces_to_render: FacesToRender::FrontAndBack,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        Self {
+            fxaa_shader,
+            temporal_shader,
+            sharpen_shader,
+            motion_vectors_shader,
+            output_target,
+            motion_target,
+            histories: Vec::new(),
+            previous_models: HashMap::new(),
+            temporal_used_this_frame: false,
+            result: None,
+        }
+    }
+
+    /// The target holding the anti-aliased scene for the [Camera] being rendered.
+    pub fn result(&self) -> Option<&OffscreenRenderTarget> {
+        self.result.map(|result| match result {
+            AntiAliasingResult::Output => &self.output_target,
+            AntiAliasingResult::History {
+                camera_index,
+                target,
+            } => &self.histories[camera_index].targets[target],
+        })
+    }
+
+    /// Call before rendering each [Camera] so that it doesn't use another [Camera]'s result.
+    pub fn clear(&mut self) {
+        self.result = None;
+    }
+
+    /// Applies [AntiAliasing::Fxaa] to the scene and returns the target holding the result.
+    pub fn fxaa(
+        &mut self,
+        graphics: &mut Graphics,
+        textures: &mut Assets<Texture>,
+        command_buffer: &mut CommandBuffer,
+        scene: &OffscreenRenderTarget,
+    ) -> &OffscreenRenderTarget {
+        let size = scene.size();
+        self.output_target.resize(graphics, textures, size);
+
+        let pipeline = &self.fxaa_shader.pipeline;
+        let mut render_pass = command_buffer
+            .begin_render_pass_with_framebuffer(self.output_target.framebuffer(), None);
+        render_pass.set_viewport(0, 0, size.x as u32, size.y as u32);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_texture_property(
+            &pipeline.get_texture_property("p_texture").unwrap(),
+            Some(textures.get(scene.color_texture())),
+            0,
+        );
+        render_pass.set_vec2_property(
+            &pipeline
+                .get_vec2_property("p_texture_coordinate_scale")
+                .unwrap(),
+            Vec2::ONE.into(),
+        );
+        render_pass.set_vec2_property(
+            &pipeline.get_vec2_property("p_used_texture_scale").unwrap(),
+            scene.inner_texture_scale().into(),
+        );
+        render_pass.set_vec2_property(
+            &pipeline.get_vec2_property("p_view_size").unwrap(),
+            size.as_f32().into(),
+        );
+        render_pass.draw_triangles_without_buffer(1);
+
+        self.result = Some(AntiAliasingResult::Output);
+        &self.output_target
+    }
+
+    /// Blends the scene, which must have been rendered with [Camera::jittered_projection_matrix]
+    /// and [temporal_jitter], into the [Camera]'s history.
+    /// Returns the target holding the anti-aliased and sharpened result.
+    #[allow(clippy::too_many_arguments)]
+    pub fn temporal(
+        &mut self,
+        graphics: &mut Graphics,
+        textures: &mut Assets<Texture>,
+        shaders: &Assets<Shader>,
+        materials: &Assets<Material>,
+        meshes: &Assets<Mesh>,
+        command_buffer: &mut CommandBuffer,
+        camera_index: usize,
+        camera: &Camera,
+        camera_global_transform: &GlobalTransform,
+        renderables: &Renderables,
+        scene: &OffscreenRenderTarget,
+        sharpness: f32,
+    ) -> &OffscreenRenderTarget {
+        let size = scene.size();
+        self.temporal_used_this_frame = true;
+
+        while self.histories.len() <= camera_index {
+            self.histories.push(TemporalHistory {
+                targets: [
+                    color_target(graphics, textures),
+                    color_target(graphics, textures),
+                ],
+                current_target: 0,
+                previous_view_projection: None,
+            });
+        }
+        let history = &mut self.histories[camera_index];
+        if history.targets[0].size() != size {
+            // The old history no longer lines up with the view.
+            for target in &mut history.targets {
+                target.resize(graphics, textures, size);
+            }
+            history.previous_view_projection = None;
+        }
+
+        let view_projection =
+            camera.projection_matrix() * camera_global_transform.model().inversed();
+        let previous_view_projection = history.previous_view_projection.unwrap_or(view_projection);
+
+        self.motion_target.resize(graphics, textures, size);
+        render_motion_vectors(
+            &self.motion_vectors_shader,
+            shaders,
+            materials,
+            meshes,
+            command_buffer,
+            &self.motion_target,
+            camera,
+            camera_global_transform,
+            &view_projection,
+            &previous_view_projection,
+            renderables,
+            &self.previous_models,
+        );
+
+        // Blend this frame into the history.
+        let previous = &history.targets[history.current_target];
+        history.current_target = 1 - history.current_target;
+        let target = &history.targets[history.current_target];
+
+        let pipeline = &self.temporal_shader.pipeline;
+        let mut render_pass =
+            command_buffer.begin_render_pass_with_framebuffer(target.framebuffer(), None);
+        render_pass.set_viewport(0, 0, size.x as u32, size.y as u32);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_texture_property(
+            &pipeline.get_texture_property("p_texture").unwrap(),
+            Some(textures.get(scene.color_texture())),
+            0,
+        );
+        render_pass.set_texture_property(
+            &pipeline.get_texture_property("p_depth_texture").unwrap(),
+            Some(textures.get(scene.depth_texture())),
+            1,
+        );
+        render_pass.set_texture_property(
+            &pipeline.get_texture_property("p_motion_texture").unwrap(),
+            Some(textures.get(self.motion_target.color_texture())),
+            2,
+        );
+        render_pass.set_texture_property(
+            &pipeline.get_texture_property("p_history_texture").unwrap(),
+            Some(textures.get(previous.color_texture())),
+            3,
+        );
+        render_pass.set_vec2_property(
+            &pipeline
+                .get_vec2_property("p_texture_coordinate_scale")
+                .unwrap(),
+            Vec2::ONE.into(),
+        );
+        render_pass.set_vec2_property(
+            &pipeline.get_vec2_property("p_scene_texture_scale").unwrap(),
+            scene.inner_texture_scale().into(),
+        );
+        render_pass.set_vec2_property(
+            &pipeline
+                .get_vec2_property("p_motion_texture_scale")
+                .unwrap(),
+            self.motion_target.inner_texture_scale().into(),
+        );
+        render_pass.set_vec2_property(
+            &pipeline
+                .get_vec2_property("p_history_texture_scale")
+                .unwrap(),
+            previous.inner_texture_scale().into(),
+        );
+        render_pass.set_mat4_property(
+            &pipeline.get_mat4_property("p_reprojection").unwrap(),
+            (previous_view_projection * view_projection.inversed()).as_array(),
+        );
+        render_pass.set_float_property(
+            &pipeline.get_float_property("p_history_weight").unwrap(),
+            if history.previous_view_projection.is_some() {
+                HISTORY_WEIGHT
+            } else {
+                0.0
+            },
+        );
+        render_pass.set_vec2_property(
+            &pipeline.get_vec2_property("p_view_size").unwrap(),
+            size.as_f32().into(),
+        );
+        render_pass.draw_triangles_without_buffer(1);
+
+        history.previous_view_projection = Some(view_projection);
+
+        let current_target = history.current_target;
+        let accumulated = &self.histories[camera_index].targets[current_target];
+        if sharpness <= 0.0 {
+            self.result = Some(AntiAliasingResult::History {
+                camera_index,
+                target: current_target,
+            });
+            return accumulated;
+        }
+
+        // Sharpen into a separate target so that sharpening doesn't accumulate in the history.
+        self.output_target.resize(graphics, textures, size);
+        let pipeline = &self.sharpen_shader.pipeline;
+        let mut render_pass = command_buffer
+            .begin_render_pass_with_framebuffer(self.output_target.framebuffer(), None);
+        render_pass.set_viewport(0, 0, size.x as u32, size.y as u32);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_texture_property(
+            &pipeline.get_texture_property("p_texture").unwrap(),
+            Some(textures.get(accumulated.color_texture())),
+            0,
+        );
+        render_pass.set_vec2_property(
+            &pipeline
+                .get_vec2_property("p_texture_coordinate_scale")
+                .unwrap(),
+            Vec2::ONE.into(),
+        );
+        render_pass.set_vec2_property(
+            &pipeline.get_vec2_property("p_used_texture_scale").unwrap(),
+            accumulated.inner_texture_scale().into(),
+        );
+        render_pass.set_vec2_property(
+            &pipeline.get_vec2_property("p_view_size").unwrap(),
+            size.as_f32().into(),
+        );
+        render_pass.set_float_property(
+            &pipeline.get_float_property("p_sharpness").unwrap(),
+            sharpness,
+        );
+        render_pass.draw_triangles_without_buffer(1);
+
+        self.result = Some(AntiAliasingResult::Output);
+        &self.output_target
+    }
+
+    /// Remembers this frame's model matrices so that next frame's motion can be found.
+    /// Call once per frame after all [Camera]s are rendered.
+    pub fn end_frame(&mut self, renderables: &Renderables) {
+        self.previous_models.clear();
+        if self.temporal_used_this_frame {
+            for (global_transform, .., entity) in renderables {
+                self.previous_models
+                    .insert(entity, global_transform.model());
+            }
+        }
+        self.temporal_used_this_frame = false;
+    }
+}
+
+/// Renders how far each opaque [Renderables] moved across the screen since last frame.
+#[allow(clippy::too_many_arguments)]
+fn render_motion_vectors(
+    shader: &Shader,
+    shaders: &Assets<Shader>,
+    materials: &Assets<Material>,
+    meshes: &Assets<Mesh>,
+    command_buffer: &mut CommandBuffer,
+    target: &OffscreenRenderTarget,
+    camera: &Camera,
+    camera_global_transform: &GlobalTransform,
+    view_projection: &Mat4,
+    previous_view_projection: &Mat4,
+    renderables: &Renderables,
+    previous_models: &HashMap<Entity, Mat4>,
+) {
+    let size = target.size();
+    let mut render_pass = command_buffer
+        .begin_render_pass_with_framebuffer(target.framebuffer(), Some((0.0, 0.0, 0.0, 0.0)));
+    render_pass.set_viewport(0, 0, size.x as u32, size.y as u32);
+    render_pass.set_depth_mask(true);
+
+    let pipeline = &shader.pipeline;
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_mat4_property(
+        &pipeline.get_mat4_property("p_view_projection").unwrap(),
+        view_projection.as_array(),
+    );
+    render_pass.set_mat4_property(
+        &pipeline
+            .get_mat4_property("p_previous_view_projection")
+            .unwrap(),
+        previous_view_projection.as_array(),
+    );
+    let model_property = pipeline.get_mat4_property("p_model").unwrap();
+    let previous_model_property = pipeline.get_mat4_property("p_previous_model").unwrap();
+    let position_attribute = pipeline.get_vertex_attribute::<Vec3>("a_position").unwrap();
+
+    let view_matrix = camera_global_transform.model().inversed();
+    let projection_matrix = camera.projection_matrix();
+    let frustum = Frustum::from_matrix(*view_projection);
+    for (
+        global_transform,
+        material_handle,
+        mesh_handle,
+        render_flags,
+        _,
+        _,
+        level_of_detail,
+        entity,
+    ) in renderables
+    {
+        let render_flags = render_flags.cloned().unwrap_or(RenderFlags::DEFAULT);
+        if !camera.render_flags.includes_layer(render_flags) {
+            continue;
+        }
+
+        // Transparent surfaces are reprojected with what's behind them.
+        let shader = shaders.get(&materials.get(material_handle).shader);
+        if shader.pipeline.blending().is_some() {
+            continue;
+        }
+
+        let bounding_box = meshes.get(mesh_handle).bounding_box;
+        let should_render = render_flags.includes_layer(RenderFlags::IGNORE_CULLING)
+            || bounding_box
+                .is_none_or(|b| frustum_with_bounding_box(&frustum, global_transform.model(), b));
+        if !should_render {
+            continue;
+        }
+
+        let mesh_handle = match level_of_detail_mesh(
+            mesh_handle,
+            level_of_detail,
+            global_transform,
+            bounding_box,
+            &view_matrix,
+            &projection_matrix,
+        ) {
+            Some(mesh_handle) => mesh_handle,
+            None => continue,
+        };
+
+        if let Some(gpu_mesh) = meshes.get(mesh_handle).gpu_mesh.as_ref() {
+            let model = global_transform.model();
+            // Newly spawned entities haven't moved.
+            let previous_model = previous_models.get(&entity).unwrap_or(&model);
+            render_pass.set_mat4_property(&model_property, model.as_array());
+            render_pass.set_mat4_property(&previous_model_property, previous_model.as_array());
+            render_pass.set_vertex_attribute(&position_attribute, Some(&gpu_mesh.positions));
+            render_pass.draw_triangles(gpu_mesh.triangle_count, &gpu_mesh.index_buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temporal_jitter_covers_pixel() {
+        let offsets: Vec<Vec2> = (0..JITTER_SAMPLE_COUNT).map(temporal_jitter).collect();
+        for (i, offset) in offsets.iter().enumerate() {
+            assert!(offset.x > -0.5 && offset.x < 0.5);
+            assert!(offset.y > -0.5 && offset.y < 0.5);
+            assert!(!offsets[..i].contains(offset));
+        }
+        // The sequence repeats.
+        assert_eq!(temporal_jitter(JITTER_SAMPLE_COUNT), offsets[0]);
+        assert_eq!(temporal_jitter(0), Vec2::new(0.0, 1.0 / 3.0 - 0.5));
+    }
+}
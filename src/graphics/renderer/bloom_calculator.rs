@@ -7,6 +7,8 @@ pub struct BloomCalculator {
     upscale_targets: Vec<OffscreenRenderTarget>,
     downsample_shader: Shader,
     upscale_shader: Shader,
+    /// Whether the [Camera] being rendered has been blurred.
+    blurred: bool,
 }
 
 impl BloomCalculator {
@@ -68,6 +70,7 @@ impl BloomCalculator {
             upscale_shader,
             downscale_targets,
             upscale_targets,
+            blurred: false,
         }
     }
 
@@ -92,6 +95,16 @@ impl BloomCalculator {
         self.passes = steps;
     }
 
+    /// The target holding the blurred scene for the [Camera] being rendered.
+    pub fn result(&self) -> Option<&OffscreenRenderTarget> {
+        self.blurred.then(|| &self.upscale_targets[0])
+    }
+
+    /// Call before rendering each [Camera] so that it doesn't use another [Camera]'s bloom.
+    pub fn clear(&mut self) {
+        self.blurred = false;
+    }
+
     pub fn blur_texture(
         &mut self,
         graphics: &mut Graphics,
@@ -216,6 +229,7 @@ impl BloomCalculator {
             last_half_pixel_size = Vec2::fill(0.5).div_by_component(current_target.size().as_f32())
         }
 
+        self.blurred = true;
         self.upscale_targets[0].color_texture()
     }
 }
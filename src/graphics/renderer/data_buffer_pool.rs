@@ -0,0 +1,39 @@
+use super::*;
+
+/// [DataBuffer]s that are reused every frame instead of being created for each draw.
+///
+/// Draws only read their buffers when the command buffer is committed,
+/// so each draw in a frame gets its own buffer.
+pub(crate) struct DataBufferPool<T> {
+    buffers: Vec<DataBuffer<T>>,
+    /// How many buffers have been used this frame.
+    used: usize,
+}
+
+impl<T> Default for DataBufferPool<T> {
+    fn default() -> Self {
+        Self {
+            buffers: Vec::new(),
+            used: 0,
+        }
+    }
+}
+
+impl<T> DataBufferPool<T> {
+    /// Makes every buffer available again. Call once the previous frame's commands are committed.
+    pub fn reset(&mut self) {
+        self.used = 0;
+    }
+
+    /// Returns an unused buffer filled with `data`.
+    pub fn next(&mut self, graphics_context: &mut GraphicsContext, data: &[T]) -> &DataBuffer<T> {
+        if let Some(buffer) = self.buffers.get_mut(self.used) {
+            graphics_context.update_data_buffer(buffer, data);
+        } else {
+            self.buffers
+                .push(graphics_context.new_data_buffer(data).unwrap());
+        }
+        self.used += 1;
+        &self.buffers[self.used - 1]
+    }
+}
@@ -0,0 +1,778 @@
+use super::*;
+
+/// How a [Decal] is drawn onto the surfaces inside its box.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecalMode {
+    /// Shades the surfaces in the scene's depth each frame. Moving surfaces are decaled immediately.
+    /// Only drawn by [Camera]s with post-processing enabled, because the scene's depth is needed.
+    Projected,
+    /// Clips the [Mesh]es of the surfaces inside the box into a new [Mesh] on the CPU.
+    /// Works with every [Camera] but the [Mesh] is only rebuilt when the [Decal] changes or
+    /// [Decal::rebuild] is called.
+    Mesh,
+}
+
+/// Draws a [Material] onto the surfaces inside a box, like bullet holes, footprints, or blob shadows.
+///
+/// The box spans -0.5 to 0.5 on each axis of the [Entity]'s [Transform], so scale the [Transform]
+/// to size the [Decal]. The [Decal] projects along the [Transform]'s forward direction and its
+/// texture is laid out like a [Mesh::VERTICAL_QUAD] facing back towards the projector.
+///
+/// Use a [Material] made with [Shader::DECAL]. Its base color, normal, metallic and roughness
+/// are blended over the surfaces beneath by the base color's alpha.
+#[derive(Component, Clone)]
+pub struct Decal {
+    pub material: Handle<Material>,
+    pub mode: DecalMode,
+    /// Multiplies the [Material]'s alpha.
+    pub opacity: f32,
+    /// The angle, in radians, between a surface and the projection where the [Decal] begins to fade.
+    pub fade_start_angle: f32,
+    /// The angle, in radians, between a surface and the projection where the [Decal] is invisible.
+    pub fade_end_angle: f32,
+    /// Only surfaces on these layers receive the [Decal].
+    /// Surfaces with [RenderFlags::DO_NOT_RECEIVE_DECALS] never do.
+    pub layers: RenderFlags,
+    mesh: Option<Handle<Mesh>>,
+    built_mesh_for: Option<MeshDecalKey>,
+}
+
+impl Decal {
+    pub fn new(material: Handle<Material>) -> Self {
+        Self {
+            material,
+            mode: DecalMode::Projected,
+            opacity: 1.0,
+            fade_start_angle: 60.0_f32.to_radians(),
+            fade_end_angle: 80.0_f32.to_radians(),
+            layers: RenderFlags::DEFAULT,
+            mesh: None,
+            built_mesh_for: None,
+        }
+    }
+
+    /// Returns `true` if surfaces with `render_flags` receive this [Decal].
+    pub fn receives(&self, render_flags: RenderFlags) -> bool {
+        render_flags.includes_layer(self.layers)
+            && !render_flags.includes_layer(RenderFlags::DO_NOT_RECEIVE_DECALS)
+    }
+
+    /// Rebuilds a [DecalMode::Mesh] decal's [Mesh] next frame.
+    /// Call this when the surfaces beneath the [Decal] move or change.
+    pub fn rebuild(&mut self) {
+        self.built_mesh_for = None;
+    }
+
+    /// The cosines of [Decal::fade_start_angle] and [Decal::fade_end_angle].
+    fn angle_fade_cosines(&self) -> (f32, f32) {
+        (self.fade_start_angle.cos(), self.fade_end_angle.cos())
+    }
+}
+
+/// Everything a [DecalMode::Mesh] decal's [Mesh] was built from.
+#[derive(Clone, PartialEq)]
+struct MeshDecalKey {
+    model: Mat4,
+    opacity: f32,
+    fade_start_angle: f32,
+    fade_end_angle: f32,
+    layers: RenderFlags,
+}
+
+impl MeshDecalKey {
+    fn new(global_transform: &GlobalTransform, decal: &Decal) -> Self {
+        Self {
+            model: global_transform.model(),
+            opacity: decal.opacity,
+            fade_start_angle: decal.fade_start_angle,
+            fade_end_angle: decal.fade_end_angle,
+            layers: decal.layers,
+        }
+    }
+}
+
+pub type Decals<'a> = Query<'a, (&'static GlobalTransform, &'static Decal)>;
+
+/// The surfaces that [DecalMode::Mesh] decals are projected onto.
+pub type DecalReceivers<'a> = Query<
+    'a,
+    (
+        &'static GlobalTransform,
+        &'static Handle<Mesh>,
+        &'static Handle<Material>,
+        Option<&'static RenderFlags>,
+    ),
+>;
+
+/// How far [DecalMode::Mesh] decals are lifted off of their surfaces to avoid z-fighting.
+const MESH_DECAL_OFFSET: f32 = 0.002;
+
+/// How opaque a [Decal] is on a surface, given the cosine of the angle between them.
+/// Matches `ProjectDecal` in the projected decal shader snippet.
+fn angle_fade(facing: f32, (fade_start_cos, fade_end_cos): (f32, f32)) -> f32 {
+    ((facing - fade_end_cos) / (fade_start_cos - fade_end_cos).max(0.0001)).clamp(0.0, 1.0)
+}
+
+/// A vertex of a polygon being clipped to a [Decal]'s box.
+#[derive(Clone, Copy, Debug)]
+struct ClipVertex {
+    /// In the [Decal]'s local space.
+    position: Vec3,
+    /// In world space.
+    normal: Vec3,
+}
+
+impl ClipVertex {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            position: self.position + (other.position - self.position) * t,
+            normal: self.normal + (other.normal - self.normal) * t,
+        }
+    }
+}
+
+/// Clips a convex polygon to the box spanning -0.5 to 0.5 on each axis.
+/// `scratch` is used to avoid allocating.
+fn clip_polygon_to_unit_box(polygon: &mut Vec<ClipVertex>, scratch: &mut Vec<ClipVertex>) {
+    for axis in 0..3 {
+        for side in [-1.0, 1.0] {
+            let mut previous = match polygon.last() {
+                Some(previous) => *previous,
+                None => return,
+            };
+            // Positive distances are inside the plane.
+            let distance = |v: &ClipVertex| 0.5 - v.position[axis] * side;
+
+            scratch.clear();
+            for &current in polygon.iter() {
+                let previous_distance = distance(&previous);
+                let current_distance = distance(&current);
+                // Vertices on the plane are kept as they are rather than duplicated.
+                if (previous_distance > 0.0 && current_distance < 0.0)
+                    || (previous_distance < 0.0 && current_distance > 0.0)
+                {
+                    let t = previous_distance / (previous_distance - current_distance);
+                    scratch.push(previous.lerp(current, t));
+                }
+                if current_distance >= 0.0 {
+                    scratch.push(current);
+                }
+                previous = current;
+            }
+            std::mem::swap(polygon, scratch);
+        }
+    }
+}
+
+/// Appends the parts of `receiver`'s triangles that are inside the [Decal]'s box to `decal_mesh`.
+/// `receiver_to_decal` transforms from the receiver's space to the [Decal]'s local space.
+fn clip_mesh_to_decal(
+    decal_mesh: &mut MeshData,
+    receiver: &MeshData,
+    receiver_to_decal: &Mat4,
+    receiver_model: &Mat4,
+    decal_model: &Mat4,
+    decal: &Decal,
+) {
+    let back = decal_model.transform_vector(Vec3::Z).normalized();
+    let right = decal_model.transform_vector(Vec3::X).normalized();
+    let fade_cosines = decal.angle_fade_cosines();
+
+    let mut polygon = Vec::new();
+    let mut scratch = Vec::new();
+    for triangle in &receiver.indices {
+        let corners = triangle.map(|i| receiver.positions[i as usize]);
+        let world_corners = corners.map(|p| receiver_model.transform_point(p));
+        let face_normal = (world_corners[1] - world_corners[0])
+            .cross(world_corners[2] - world_corners[0])
+            .normalized();
+
+        // Surfaces facing away from the projection, or that are too steep, don't receive the decal.
+        let fade = angle_fade(face_normal.dot(back), fade_cosines);
+        if fade <= 0.0 || face_normal.x.is_nan() {
+            continue;
+        }
+
+        polygon.clear();
+        for (corner, index) in corners.iter().zip(triangle) {
+            let normal = match receiver.normals.get(*index as usize) {
+                Some(normal) => receiver_model.transform_vector(*normal).normalized(),
+                None => face_normal,
+            };
+            polygon.push(ClipVertex {
+                position: receiver_to_decal.transform_point(*corner),
+                normal,
+            });
+        }
+        clip_polygon_to_unit_box(&mut polygon, &mut scratch);
+        if polygon.len() < 3 {
+            continue;
+        }
+
+        let first_index = decal_mesh.positions.len() as u32;
+        for vertex in &polygon {
+            let normal = vertex.normal.normalized();
+            decal_mesh
+                .positions
+                .push(decal_model.transform_point(vertex.position) + normal * MESH_DECAL_OFFSET);
+            decal_mesh.normals.push(normal);
+            decal_mesh
+                .texture_coordinates
+                .push(Vec2::new(vertex.position.x + 0.5, 0.5 - vertex.position.y));
+            // The texture's V coordinate points down the decal.
+            decal_mesh.tangents.push(right.extend(-1.0));
+            decal_mesh
+                .colors
+                .push(Vec4::new(1.0, 1.0, 1.0, fade * decal.opacity));
+        }
+        for i in 1..polygon.len() as u32 - 1 {
+            decal_mesh
+                .indices
+                .push([first_index, first_index + i, first_index + i + 1]);
+        }
+    }
+}
+
+/// Returns `true` if `bounding_box` overlaps the box spanning -0.5 to 0.5 on each axis.
+fn overlaps_unit_box(bounding_box: Box3) -> bool {
+    bounding_box
+        .min
+        .less_than_per_component(Vec3::fill(0.5))
+        .all()
+        && bounding_box
+            .max
+            .greater_than_per_component(Vec3::fill(-0.5))
+            .all()
+}
+
+/// Rebuilds the [Mesh]es of [DecalMode::Mesh] decals that have changed.
+pub fn update_decal_meshes(
+    graphics: &mut Graphics,
+    meshes: &mut Assets<Mesh>,
+    materials: &Assets<Material>,
+    shaders: &Assets<Shader>,
+    mut decals: Query<(&GlobalTransform, &mut Decal)>,
+    receivers: DecalReceivers,
+) {
+    for (global_transform, decal) in &mut decals {
+        if decal.mode != DecalMode::Mesh {
+            decal.mesh = None;
+            decal.built_mesh_for = None;
+            continue;
+        }
+
+        let key = MeshDecalKey::new(global_transform, decal);
+        if decal.built_mesh_for.as_ref() == Some(&key) {
+            continue;
+        }
+
+        let decal_model = global_transform.model();
+        let world_to_decal = decal_model.inversed();
+        let mut mesh_data = MeshData::new();
+        for (receiver_transform, mesh_handle, material_handle, render_flags) in &receivers {
+            if !decal.receives(render_flags.cloned().unwrap_or(RenderFlags::DEFAULT)) {
+                continue;
+            }
+
+            // Transparent surfaces don't receive decals.
+            let shader = shaders.get(&materials.get(material_handle).shader);
+            if shader.pipeline.blending().is_some() {
+                continue;
+            }
+
+            let mesh = meshes.get(mesh_handle);
+            let (receiver, bounding_box) = match (mesh.mesh_data.as_ref(), mesh.bounding_box) {
+                (Some(receiver), Some(bounding_box)) => (receiver, bounding_box),
+                _ => continue,
+            };
+
+            let receiver_model = receiver_transform.model();
+            let receiver_to_decal = world_to_decal * receiver_model;
+            let bounds_in_decal = Box3::from_points(
+                bounding_box
+                    .corners()
+                    .map(|corner| receiver_to_decal.transform_point(corner)),
+            );
+            if !overlaps_unit_box(bounds_in_decal) {
+                continue;
+            }
+
+            clip_mesh_to_decal(
+                &mut mesh_data,
+                receiver,
+                &receiver_to_decal,
+                &receiver_model,
+                &decal_model,
+                decal,
+            );
+        }
+
+        match &decal.mesh {
+            Some(mesh_handle) => {
+                let mesh = meshes.get_mut(mesh_handle);
+                mesh.mesh_data = Some(mesh_data);
+                mesh.recalculate_bounding_box();
+                mesh.update_mesh_on_gpu(graphics);
+            }
+            None => decal.mesh = Some(meshes.add(Mesh::new(graphics, mesh_data))),
+        }
+        decal.built_mesh_for = Some(key);
+    }
+}
+
+/// The variant of a [Material]'s [Shader] that [DecalMode::Projected] decals draw with.
+fn projected_variant_key(material: &Material) -> ShaderVariantKey {
+    let mut key = material.shader_variant_key().clone();
+    key.set_keyword(shader_keywords::PROJECTED_DECAL, true);
+    key.pipeline_settings = Some(PipelineSettings {
+        // The back of the box is drawn so the decal still draws when the camera is inside it.
+        faces_to_render: FacesToRender::Back,
+        blending: Some((BlendFactor::SourceAlpha, BlendFactor::OneMinusSourceAlpha)),
+        depth_test: DepthTest::AlwaysPass,
+        stencil: None,
+    });
+    key
+}
+
+/// The variant of a [Material]'s [Shader] that [DecalMode::Mesh] decals draw with.
+/// Their [Mesh]es fade out through their vertex colors.
+fn mesh_variant_key(material: &Material) -> ShaderVariantKey {
+    let mut key = material.shader_variant_key().clone();
+    key.set_keyword(shader_keywords::VERTEX_COLORS, true);
+    key
+}
+
+/// Compiles the [Shader] variants that [Decal]s draw with.
+pub(crate) fn compile_decal_shader_variants(
+    graphics: &mut Graphics,
+    shaders: &mut Assets<Shader>,
+    materials: &Assets<Material>,
+    decals: Query<&Decal>,
+) {
+    for decal in &decals {
+        let material = materials.get(&decal.material);
+        let key = match decal.mode {
+            DecalMode::Projected => projected_variant_key(material),
+            DecalMode::Mesh => mesh_variant_key(material),
+        };
+        shaders
+            .get_mut(&material.shader)
+            .compile_variant(graphics, &key);
+    }
+}
+
+/// The [DecalMode::Projected] decals that a [Camera] sees.
+pub(super) fn visible_projected_decals<'a>(
+    camera: &Camera,
+    view_matrix: &Mat4,
+    projection_matrix: &Mat4,
+    decals: &'a Decals,
+) -> Vec<(&'a GlobalTransform, &'a Decal)> {
+    let frustum = Frustum::from_matrix(*projection_matrix * *view_matrix);
+    let unit_box = Box3::new(Vec3::fill(-0.5), Vec3::fill(0.5));
+    decals
+        .iter()
+        .filter(|(global_transform, decal)| {
+            decal.mode == DecalMode::Projected
+                && camera.render_flags.includes_layer(decal.layers)
+                && frustum_with_bounding_box(&frustum, global_transform.model(), unit_box)
+        })
+        .collect()
+}
+
+/// Renders the [RenderFlags] of the surfaces a [Camera] sees so that
+/// [DecalMode::Projected] decals can skip the surfaces that don't receive them.
+pub(crate) struct DecalRenderer {
+    layers_target: OffscreenRenderTarget,
+    layers_shader: Shader,
+    /// Projected decals draw the back of this box.
+    box_mesh: Handle<Mesh>,
+    /// `true` if the layers were rendered for the [Camera] being rendered.
+    has_layers: bool,
+}
+
+impl DecalRenderer {
+    pub fn new(graphics: &mut Graphics, textures: &mut Assets<Texture>) -> Self {
+        let layers_target = OffscreenRenderTarget::new(
+            graphics,
+            textures,
+            Vec2u::ZERO,
+            Some((
+                PixelFormat::RGBA8Unorm,
+                TextureSettings {
+                    srgb: false,
+                    generate_mipmaps: false,
+                    minification_filter: FilterMode::Nearest,
+                    magnification_filter: FilterMode::Nearest,
+                    wrapping_horizontal: WrappingMode::ClampToEdge,
+                    wrapping_vertical: WrappingMode::ClampToEdge,
+                    ..Default::default()
+                },
+            )),
+            Some((
+                PixelFormat::Depth32F,
+                TextureSettings {
+                    srgb: false,
+                    generate_mipmaps: false,
+                    minification_filter: FilterMode::Nearest,
+                    magnification_filter: FilterMode::Nearest,
+                    ..Default::default()
+                },
+            )),
+        );
+        let layers_shader = graphics
+            .new_shader(
+                include_str!("../built_in_shaders/decal_layers.glsl"),
+                PipelineSettings {
+                    faces_to_render: FacesToRender::FrontAndBack,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        Self {
+            layers_target,
+            layers_shader,
+            box_mesh: Mesh::CUBE,
+            has_layers: false,
+        }
+    }
+
+    /// The layers texture for the [Camera] being rendered, if one was needed.
+    fn layers(&self) -> Option<&Handle<Texture>> {
+        self.has_layers.then(|| self.layers_target.color_texture())
+    }
+
+    /// Renders the layers if some of the surfaces the [Camera] sees don't receive some of `decals`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare(
+        &mut self,
+        graphics: &mut Graphics,
+        textures: &mut Assets<Texture>,
+        shaders: &Assets<Shader>,
+        materials: &Assets<Material>,
+        meshes: &Assets<Mesh>,
+        command_buffer: &mut CommandBuffer,
+        camera: &Camera,
+        view_matrix: &Mat4,
+        projection_matrix: &Mat4,
+        renderables: &Renderables,
+        decals: &[(&GlobalTransform, &Decal)],
+        view_size: Vec2u,
+    ) {
+        let needs_layers = !decals.is_empty()
+            && renderables.iter().any(|(.., render_flags, _, _, _, _)| {
+                let render_flags = render_flags.cloned().unwrap_or(RenderFlags::DEFAULT);
+                camera.render_flags.includes_layer(render_flags)
+                    && decals
+                        .iter()
+                        .any(|(_, decal)| !decal.receives(render_flags))
+            });
+        self.has_layers = needs_layers;
+        if !needs_layers {
+            return;
+        }
+
+        self.layers_target.resize(graphics, textures, view_size);
+        let size = self.layers_target.size();
+        // Pixels without a surface are on no layers.
+        let mut render_pass = command_buffer.begin_render_pass_with_framebuffer(
+            self.layers_target.framebuffer(),
+            Some((0.0, 0.0, 0.0, 0.0)),
+        );
+        render_pass.set_viewport(0, 0, size.x as u32, size.y as u32);
+        render_pass.set_depth_mask(true);
+
+        let pipeline = &self.layers_shader.pipeline;
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_mat4_property(
+            &pipeline.get_mat4_property("p_views[0]").unwrap(),
+            view_matrix.as_array(),
+        );
+        render_pass.set_mat4_property(
+            &pipeline.get_mat4_property("p_projections[0]").unwrap(),
+            projection_matrix.as_array(),
+        );
+        let model_property = pipeline.get_mat4_property("p_model").unwrap();
+        let render_flags_property = pipeline.get_vec4_property("p_render_flags").unwrap();
+        let position_attribute = pipeline.get_vertex_attribute::<Vec3>("a_position").unwrap();
+
+        let frustum = Frustum::from_matrix(*projection_matrix * *view_matrix);
+        for (
+            global_transform,
+            material_handle,
+            mesh_handle,
+            render_flags,
+            _,
+            _,
+            level_of_detail,
+            _,
+        ) in renderables
+        {
+            let render_flags = render_flags.cloned().unwrap_or(RenderFlags::DEFAULT);
+            if !camera.render_flags.includes_layer(render_flags) {
+                continue;
+            }
+
+            // Decals draw over transparent surfaces' depth, which isn't in the scene's depth.
+            let shader = shaders.get(&materials.get(material_handle).shader);
+            if shader.pipeline.blending().is_some() {
+                continue;
+            }
+
+            let bounding_box = meshes.get(mesh_handle).bounding_box;
+            let should_render = render_flags.includes_layer(RenderFlags::IGNORE_CULLING)
+                || bounding_box.is_none_or(|b| {
+                    frustum_with_bounding_box(&frustum, global_transform.model(), b)
+                });
+            if !should_render {
+                continue;
+            }
+
+            // Use the same level of detail as the scene so the layers line up with its depth.
+            let mesh_handle = match level_of_detail_mesh(
+                mesh_handle,
+                level_of_detail,
+                global_transform,
+                bounding_box,
+                view_matrix,
+                projection_matrix,
+            ) {
+                Some(mesh_handle) => mesh_handle,
+                None => continue,
+            };
+
+            if let Some(gpu_mesh) = meshes.get(mesh_handle).gpu_mesh.as_ref() {
+                let bytes = (render_flags.bits() as u32)
+                    .to_le_bytes()
+                    .map(|byte| byte as f32 / 255.0);
+                render_pass.set_vec4_property(
+                    &render_flags_property,
+                    (bytes[0], bytes[1], bytes[2], bytes[3]),
+                );
+                render_pass.set_mat4_property(&model_property, global_transform.model().as_array());
+                render_pass.set_vertex_attribute(&position_attribute, Some(&gpu_mesh.positions));
+                render_pass.draw_triangles(gpu_mesh.triangle_count, &gpu_mesh.index_buffer);
+            }
+        }
+    }
+}
+
+impl<'a, 'b: 'a> Renderer<'a, 'b> {
+    /// Draws the [DecalMode::Mesh] decals that the [Camera] sees over the opaque scene.
+    pub(super) fn render_mesh_decals(
+        &mut self,
+        camera: &Camera,
+        frustum: &Frustum,
+        decals: &'a Decals,
+        lights: &Lights,
+        reflection_probes: &Query<(&'static GlobalTransform, &'static ReflectionProbe)>,
+    ) {
+        // The decal meshes are already in world space.
+        let transform = Transform::new();
+
+        // Decals are drawn over the surfaces beneath them and shouldn't hide each-other.
+        self.render_pass.set_depth_mask(false);
+        // The scene may have bound a decal's material with a different variant.
+        self.material_handle = None;
+        for (_, decal) in decals {
+            let mesh_handle = match &decal.mesh {
+                Some(mesh_handle) if camera.render_flags.includes_layer(decal.layers) => {
+                    mesh_handle
+                }
+                _ => continue,
+            };
+            let mesh = self.mesh_assets.get(mesh_handle);
+            let is_visible = mesh
+                .mesh_data
+                .as_ref()
+                .is_some_and(|m| !m.indices.is_empty())
+                && mesh
+                    .bounding_box
+                    .is_none_or(|b| frustum_with_bounding_box(frustum, Mat4::IDENTITY, b));
+            if is_visible {
+                let key = mesh_variant_key(self.material_assets.get(&decal.material));
+                self.change_material_variant(&decal.material, &key, lights, reflection_probes);
+                self.render_mesh(&transform, mesh_handle);
+            }
+        }
+        self.render_pass.set_depth_mask(true);
+    }
+
+    /// Draws [DecalMode::Projected] decals onto the scene whose depth is in `depth_texture`.
+    pub(super) fn render_projected_decals(
+        &mut self,
+        decals: &[(&'a GlobalTransform, &'a Decal)],
+        depth_texture: &'a Texture,
+        lights: &Lights,
+        reflection_probes: &Query<(&'static GlobalTransform, &'static ReflectionProbe)>,
+    ) {
+        let renderer_info = self.renderer_info;
+        let view_info = &self.camera_info[0];
+        let inverse_projection = view_info.projection_matrix.inversed();
+        let inverse_view = view_info.view_matrix.inversed();
+        let (layers_texture, use_layers) = match renderer_info.decal_renderer.layers() {
+            Some(layers_texture) => (layers_texture, 1),
+            None => (&Texture::WHITE, 0),
+        };
+        let layers_texture = self.texture_assets.get(layers_texture);
+
+        for (global_transform, decal) in decals {
+            let material = self.material_assets.get(&decal.material);
+            let key = projected_variant_key(material);
+            if !matches!(
+                self.shader_assets.get(&material.shader).variants.get(&key),
+                Some(Some(_))
+            ) {
+                continue;
+            }
+            self.change_material_variant(&decal.material, &key, lights, reflection_probes);
+            let pipeline = self.current_pipeline.unwrap();
+
+            // The scene's textures use the units of the material's textures that the variant doesn't sample.
+            let used_units: Vec<u8> = material
+                .texture_properties
+                .iter()
+                .filter(|(name, _)| {
+                    pipeline
+                        .get_texture_property(name)
+                        .is_ok_and(|p| p.exists())
+                })
+                .map(|(_, (_, unit))| *unit)
+                .collect();
+            let mut free_units = (0..5).filter(|unit| !used_units.contains(unit));
+            let (depth_unit, layers_unit) = match (free_units.next(), free_units.next()) {
+                (Some(depth_unit), Some(layers_unit)) => (depth_unit, layers_unit),
+                _ => continue,
+            };
+
+            self.render_pass.set_texture_property(
+                &pipeline
+                    .get_texture_property("p_decal_depth_texture")
+                    .unwrap(),
+                Some(depth_texture),
+                depth_unit,
+            );
+            self.render_pass.set_texture_property(
+                &pipeline
+                    .get_texture_property("p_decal_layers_texture")
+                    .unwrap(),
+                Some(layers_texture),
+                layers_unit,
+            );
+            self.render_pass.set_int_property(
+                &pipeline.get_int_property("p_decal_use_layers").unwrap(),
+                use_layers,
+            );
+            self.render_pass.set_int_property(
+                &pipeline.get_int_property("p_decal_layers").unwrap(),
+                decal.layers.bits() as i32,
+            );
+            self.render_pass.set_int_property(
+                &pipeline
+                    .get_int_property("p_decal_excluded_layers")
+                    .unwrap(),
+                RenderFlags::DO_NOT_RECEIVE_DECALS.bits() as i32,
+            );
+            self.render_pass.set_vec2_property(
+                &pipeline.get_vec2_property("p_decal_screen_size").unwrap(),
+                self.viewport_size.into(),
+            );
+            self.render_pass.set_mat4_property(
+                &pipeline
+                    .get_mat4_property("p_decal_inverse_projection")
+                    .unwrap(),
+                inverse_projection.as_array(),
+            );
+            self.render_pass.set_mat4_property(
+                &pipeline.get_mat4_property("p_decal_inverse_view").unwrap(),
+                inverse_view.as_array(),
+            );
+            self.render_pass.set_mat4_property(
+                &pipeline
+                    .get_mat4_property("p_decal_world_to_local")
+                    .unwrap(),
+                global_transform.model().inversed().as_array(),
+            );
+            self.render_pass.set_vec3_property(
+                &pipeline.get_vec3_property("p_decal_back").unwrap(),
+                global_transform.back().into(),
+            );
+            self.render_pass.set_vec3_property(
+                &pipeline.get_vec3_property("p_decal_right").unwrap(),
+                global_transform.right().into(),
+            );
+            let (fade_start_cos, fade_end_cos) = decal.angle_fade_cosines();
+            self.render_pass.set_vec2_property(
+                &pipeline.get_vec2_property("p_decal_angle_fade").unwrap(),
+                (fade_start_cos, fade_end_cos),
+            );
+            self.render_pass.set_float_property(
+                &pipeline.get_float_property("p_decal_opacity").unwrap(),
+                decal.opacity,
+            );
+
+            self.render_mesh(global_transform, &renderer_info.decal_renderer.box_mesh);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(positions: &[Vec3]) -> Vec<Vec3> {
+        let mut polygon = positions
+            .iter()
+            .map(|&position| ClipVertex {
+                position,
+                normal: Vec3::Z,
+            })
+            .collect();
+        clip_polygon_to_unit_box(&mut polygon, &mut Vec::new());
+        polygon.iter().map(|v| v.position).collect()
+    }
+
+    #[test]
+    fn triangle_inside_box_is_unchanged() {
+        let triangle = [
+            Vec3::new(-0.25, -0.25, 0.0),
+            Vec3::new(0.25, -0.25, 0.0),
+            Vec3::new(0.0, 0.25, 0.0),
+        ];
+        assert_eq!(clip(&triangle), triangle);
+    }
+
+    #[test]
+    fn triangle_outside_box_is_removed() {
+        let triangle = [
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(2.0, 1.0, 0.0),
+            Vec3::new(1.0, 2.0, 0.0),
+        ];
+        assert!(clip(&triangle).is_empty());
+    }
+
+    #[test]
+    fn large_triangle_is_clipped_to_box() {
+        let triangle = [
+            Vec3::new(-10.0, -10.0, 0.0),
+            Vec3::new(10.0, -10.0, 0.0),
+            Vec3::new(0.0, 10.0, 0.0),
+        ];
+        let clipped = clip(&triangle);
+        // The triangle covers the whole face of the box.
+        assert_eq!(clipped.len(), 4);
+        for position in clipped {
+            assert!(position.x.abs() <= 0.5 + 1e-5 && position.y.abs() <= 0.5 + 1e-5);
+        }
+    }
+
+    #[test]
+    fn angle_fade_between_angles() {
+        let cosines = (60.0_f32.to_radians().cos(), 80.0_f32.to_radians().cos());
+        assert_eq!(angle_fade(1.0, cosines), 1.0);
+        assert_eq!(angle_fade(-1.0, cosines), 0.0);
+        let halfway = angle_fade(70.0_f32.to_radians().cos(), cosines);
+        assert!(halfway > 0.0 && halfway < 1.0);
+    }
+}
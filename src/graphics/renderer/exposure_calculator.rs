@@ -0,0 +1,198 @@
+use crate::*;
+use kgraphics::{CommandBuffer, CommandBufferTrait, PipelineTrait, RenderPassTrait};
+
+/// The size of the first, downsampled, luminance texture.
+const LUMINANCE_SIZE: usize = 64;
+/// How many texels along each axis each reduction pass averages into one.
+/// This must match `REDUCTION` in `auto_exposure_reduce.glsl`.
+const REDUCTION: usize = 8;
+
+/// Measures a scene's average luminance and adapts to it over time for [Exposure::Automatic].
+/// Adaptation is shared by all cameras.
+pub struct ExposureCalculator {
+    luminance_shader: Shader,
+    reduce_shader: Shader,
+    adapt_shader: Shader,
+    /// Log luminance is averaged from 64x64, to 8x8, to 1x1.
+    reduction_targets: Vec<OffscreenRenderTarget>,
+    /// Adapted log luminance alternates between these so last frame's value can be read.
+    adapted_targets: [OffscreenRenderTarget; 2],
+    current_adapted_target: usize,
+    last_adaptation: Option<Instant>,
+}
+
+impl ExposureCalculator {
+    pub fn new(graphics: &mut Graphics, textures: &mut Assets<Texture>) -> Self {
+        let settings = Some((
+            kgraphics::PixelFormat::RGBA16F,
+            TextureSettings {
+                srgb: false,
+                generate_mipmaps: false,
+                wrapping_horizontal: WrappingMode::ClampToEdge,
+                wrapping_vertical: WrappingMode::ClampToEdge,
+                minification_filter: FilterMode::Nearest,
+                magnification_filter: FilterMode::Nearest,
+                ..Default::default()
+            },
+        ));
+        let mut new_shader = |source: &str| {
+            graphics
+                .new_shader(
+                    source,
+                    PipelineSettings {
+                        depth_test: kgraphics::DepthTest::AlwaysPass,
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+        };
+        let luminance_shader = new_shader(include_str!(
+            "../built_in_shaders/auto_exposure_luminance.glsl"
+        ));
+        let reduce_shader = new_shader(include_str!(
+            "../built_in_shaders/auto_exposure_reduce.glsl"
+        ));
+        let adapt_shader = new_shader(include_str!("../built_in_shaders/auto_exposure_adapt.glsl"));
+
+        let mut reduction_targets = Vec::new();
+        let mut size = LUMINANCE_SIZE;
+        while size >= 1 {
+            reduction_targets.push(OffscreenRenderTarget::new(
+                graphics,
+                textures,
+                Vec2u::fill(size),
+                settings,
+                None,
+            ));
+            size /= REDUCTION;
+        }
+        let adapted_targets = [
+            OffscreenRenderTarget::new(graphics, textures, Vec2u::ONE, settings, None),
+            OffscreenRenderTarget::new(graphics, textures, Vec2u::ONE, settings, None),
+        ];
+
+        Self {
+            luminance_shader,
+            reduce_shader,
+            adapt_shader,
+            reduction_targets,
+            adapted_targets,
+            current_adapted_target: 0,
+            last_adaptation: None,
+        }
+    }
+
+    /// Returns a 1x1 texture that holds the adapted log2 luminance in its red channel.
+    #[allow(clippy::too_many_arguments)]
+    pub fn adapt(
+        &mut self,
+        textures: &Assets<Texture>,
+        command_buffer: &mut CommandBuffer,
+        scene_texture: &Handle<Texture>,
+        scene_texture_scale: Vec2,
+        min_luminance_log2: f32,
+        max_luminance_log2: f32,
+        adaptation_speed: f32,
+    ) -> &Handle<Texture> {
+        // Measure log luminance.
+        let pipeline = &self.luminance_shader.pipeline;
+        let target = &self.reduction_targets[0];
+        let mut render_pass =
+            command_buffer.begin_render_pass_with_framebuffer(target.framebuffer(), None);
+        render_pass.set_viewport(0, 0, LUMINANCE_SIZE as u32, LUMINANCE_SIZE as u32);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_texture_property(
+            &pipeline.get_texture_property("p_texture").unwrap(),
+            Some(textures.get(scene_texture)),
+            0,
+        );
+        render_pass.set_vec2_property(
+            &pipeline
+                .get_vec2_property("p_texture_coordinate_scale")
+                .unwrap(),
+            scene_texture_scale.into(),
+        );
+        render_pass.set_vec2_property(
+            &pipeline.get_vec2_property("p_pixel_footprint").unwrap(),
+            (scene_texture_scale / LUMINANCE_SIZE as f32).into(),
+        );
+        render_pass.draw_triangles_without_buffer(1);
+
+        // Average it down to a single pixel.
+        let pipeline = &self.reduce_shader.pipeline;
+        let p_texture = pipeline.get_texture_property("p_texture").unwrap();
+        for targets in self.reduction_targets.windows(2) {
+            let size = targets[1].size();
+            let mut render_pass =
+                command_buffer.begin_render_pass_with_framebuffer(targets[1].framebuffer(), None);
+            render_pass.set_viewport(0, 0, size.x as u32, size.y as u32);
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_texture_property(
+                &p_texture,
+                Some(textures.get(targets[0].color_texture())),
+                0,
+            );
+            render_pass.set_vec2_property(
+                &pipeline
+                    .get_vec2_property("p_texture_coordinate_scale")
+                    .unwrap(),
+                Vec2::ONE.into(),
+            );
+            render_pass.draw_triangles_without_buffer(1);
+        }
+
+        // Move towards the average from last frame's adapted luminance.
+        let adaptation = match self.last_adaptation {
+            Some(last_adaptation) => {
+                1.0 - (-last_adaptation.elapsed().as_secs_f32() * adaptation_speed).exp()
+            }
+            None => 1.0,
+        };
+        self.last_adaptation = Some(Instant::now());
+
+        let previous = &self.adapted_targets[self.current_adapted_target];
+        self.current_adapted_target = 1 - self.current_adapted_target;
+        let target = &self.adapted_targets[self.current_adapted_target];
+
+        let pipeline = &self.adapt_shader.pipeline;
+        let mut render_pass =
+            command_buffer.begin_render_pass_with_framebuffer(target.framebuffer(), None);
+        render_pass.set_viewport(0, 0, 1, 1);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_texture_property(
+            &pipeline
+                .get_texture_property("p_average_luminance")
+                .unwrap(),
+            Some(textures.get(self.reduction_targets.last().unwrap().color_texture())),
+            0,
+        );
+        render_pass.set_texture_property(
+            &pipeline
+                .get_texture_property("p_previous_luminance")
+                .unwrap(),
+            Some(textures.get(previous.color_texture())),
+            1,
+        );
+        render_pass.set_float_property(
+            &pipeline.get_float_property("p_min_luminance_log2").unwrap(),
+            min_luminance_log2,
+        );
+        render_pass.set_float_property(
+            &pipeline.get_float_property("p_max_luminance_log2").unwrap(),
+            max_luminance_log2,
+        );
+        render_pass.set_float_property(
+            &pipeline.get_float_property("p_adaptation").unwrap(),
+            adaptation,
+        );
+        render_pass.set_vec2_property(
+            &pipeline
+                .get_vec2_property("p_texture_coordinate_scale")
+                .unwrap(),
+            Vec2::ONE.into(),
+        );
+        render_pass.draw_triangles_without_buffer(1);
+
+        target.color_texture()
+    }
+}
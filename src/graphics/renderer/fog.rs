@@ -0,0 +1,135 @@
+use super::*;
+
+/// How a [Fog] thickens with distance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FogMode {
+    /// Fog begins at `start` world units from the camera and fully covers surfaces at `end`.
+    Linear { start: f32, end: f32 },
+    /// Fog thickens quickly near the camera and slowly further away.
+    /// A `density` of 0.01 covers about two thirds of a surface 100 world units away.
+    Exponential { density: f32 },
+    /// Exponential fog that thins out with height, like mist settling in a valley.
+    /// `density` is the density at `base_height` and it halves every `0.69 / height_falloff` world units up.
+    ExponentialHeight {
+        density: f32,
+        height_falloff: f32,
+        base_height: f32,
+    },
+}
+
+/// Attach to an [Entity] with a [Camera] to fade distant surfaces into a fog color.
+/// Fog is applied by the built-in physically based and unlit shaders.
+#[derive(Component, Clone, Debug)]
+pub struct Fog {
+    pub mode: FogMode,
+    pub color: Color,
+    /// If true `color` is multiplied by a blurred sample of the environment map behind each surface,
+    /// which blends distant surfaces into the sky. This requires a [ReflectionProbe].
+    pub use_environment_color: bool,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fog {
+    pub fn new() -> Self {
+        Self {
+            mode: FogMode::Exponential { density: 0.01 },
+            color: Color::WHITE,
+            use_environment_color: false,
+        }
+    }
+
+    pub fn linear(start: f32, end: f32) -> Self {
+        Self {
+            mode: FogMode::Linear { start, end },
+            ..Self::new()
+        }
+    }
+
+    pub fn exponential(density: f32) -> Self {
+        Self {
+            mode: FogMode::Exponential { density },
+            ..Self::new()
+        }
+    }
+
+    pub fn exponential_height(density: f32, height_falloff: f32, base_height: f32) -> Self {
+        Self {
+            mode: FogMode::ExponentialHeight {
+                density,
+                height_falloff,
+                base_height,
+            },
+            ..Self::new()
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_environment_color(mut self) -> Self {
+        self.use_environment_color = true;
+        self
+    }
+}
+
+/// The values of the fog uniforms declared by the `fog` shader snippet.
+pub(super) struct FogUniforms {
+    /// 0 for no fog, then 1, 2 and 3 for each [FogMode] in order.
+    pub mode: i32,
+    pub color: Vec4,
+    pub start: f32,
+    pub end: f32,
+    pub density: f32,
+    pub height_falloff: f32,
+    pub base_height: f32,
+    pub use_environment_color: bool,
+}
+
+impl FogUniforms {
+    pub fn new(fog: Option<&Fog>) -> Self {
+        let mut uniforms = Self {
+            mode: 0,
+            color: Vec4::ONE,
+            start: 0.0,
+            end: 1.0,
+            density: 0.0,
+            height_falloff: 0.0,
+            base_height: 0.0,
+            use_environment_color: false,
+        };
+        if let Some(fog) = fog {
+            uniforms.color = fog.color.to_rgb_color(color_spaces::LINEAR_SRGB);
+            uniforms.use_environment_color = fog.use_environment_color;
+            match fog.mode {
+                FogMode::Linear { start, end } => {
+                    uniforms.mode = 1;
+                    uniforms.start = start;
+                    // Avoid dividing by zero in the shader.
+                    uniforms.end = end.max(start + 0.0001);
+                }
+                FogMode::Exponential { density } => {
+                    uniforms.mode = 2;
+                    uniforms.density = density;
+                }
+                FogMode::ExponentialHeight {
+                    density,
+                    height_falloff,
+                    base_height,
+                } => {
+                    uniforms.mode = 3;
+                    uniforms.density = density;
+                    uniforms.height_falloff = height_falloff;
+                    uniforms.base_height = base_height;
+                }
+            }
+        }
+        uniforms
+    }
+}
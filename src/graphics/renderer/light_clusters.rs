@@ -0,0 +1,373 @@
+//! Clustered forward lighting.
+//!
+//! The camera's view is split into a grid of clusters: screen-space tiles that are
+//! further split into depth slices which grow exponentially with distance.
+//! Each light is assigned to the clusters its sphere of influence overlaps so that
+//! shaders only loop over the lights of the cluster a pixel is in.
+//!
+//! Nothing here touches the GPU. The renderer uploads the results to a data texture.
+use crate::*;
+
+/// How many clusters the view is split into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClusterGrid {
+    pub tiles_x: u32,
+    pub tiles_y: u32,
+    pub depth_slices: u32,
+}
+
+impl Default for ClusterGrid {
+    fn default() -> Self {
+        Self {
+            tiles_x: 16,
+            tiles_y: 9,
+            depth_slices: 24,
+        }
+    }
+}
+
+impl ClusterGrid {
+    pub fn cluster_count(&self) -> usize {
+        (self.tiles_x * self.tiles_y * self.depth_slices) as usize
+    }
+
+    pub fn cluster_index(&self, x: u32, y: u32, z: u32) -> usize {
+        (x + y * self.tiles_x + z * self.tiles_x * self.tiles_y) as usize
+    }
+
+    /// The view-space depth where a depth slice begins.
+    pub fn slice_depth(&self, slice: u32, z_near: f32, z_far: f32) -> f32 {
+        z_near * (z_far / z_near).powf(slice as f32 / self.depth_slices as f32)
+    }
+
+    /// The depth slice a view-space depth is within.
+    /// This must match the calculation in `physically_based.glsl`.
+    pub fn depth_slice(&self, depth: f32, z_near: f32, z_far: f32) -> u32 {
+        if depth <= z_near {
+            return 0;
+        }
+        let slice = ((depth / z_near).ln() / (z_far / z_near).ln() * self.depth_slices as f32)
+            .floor() as u32;
+        slice.min(self.depth_slices - 1)
+    }
+}
+
+/// A light's sphere of influence.
+#[derive(Clone, Copy, Debug)]
+pub struct ClusterLight {
+    /// The light's world-space position.
+    pub position: Vec3,
+    /// Lights with an infinite range, like directional lights, are in every cluster.
+    pub range: f32,
+}
+
+pub struct LightClusters {
+    pub grid: ClusterGrid,
+    pub z_near: f32,
+    pub z_far: f32,
+    /// For each cluster the offset into `light_indices` and the number of lights.
+    clusters: Vec<(u32, u32)>,
+    light_indices: Vec<u32>,
+    max_light_indices: usize,
+    // Kept to avoid allocating each frame.
+    cluster_bounds: Vec<Box3>,
+    cluster_lights: Vec<Vec<u32>>,
+}
+
+impl LightClusters {
+    /// Lights past `max_light_indices` total assignments are ignored.
+    pub fn new(grid: ClusterGrid, max_light_indices: usize) -> Self {
+        Self {
+            grid,
+            z_near: 0.0,
+            z_far: 0.0,
+            clusters: vec![(0, 0); grid.cluster_count()],
+            light_indices: Vec::new(),
+            max_light_indices,
+            cluster_bounds: Vec::new(),
+            cluster_lights: Vec::new(),
+        }
+    }
+
+    /// Assigns `lights` to clusters for a view.
+    /// The cluster's light indices are indices into `lights`.
+    pub fn assign_lights(
+        &mut self,
+        view_matrix: &Mat4,
+        projection_matrix: &Mat4,
+        z_near: f32,
+        z_far: f32,
+        lights: &[ClusterLight],
+    ) {
+        // Avoid `ln(0.0)` for views with a near plane at 0.0.
+        self.z_near = z_near.max(0.001);
+        self.z_far = z_far.max(self.z_near * 2.0);
+        self.calculate_cluster_bounds(projection_matrix);
+
+        let cluster_count = self.grid.cluster_count();
+        self.cluster_lights.resize_with(cluster_count, Vec::new);
+        for cluster_lights in self.cluster_lights.iter_mut() {
+            cluster_lights.clear();
+        }
+
+        for (light_index, light) in lights.iter().enumerate() {
+            if light.range.is_infinite() {
+                for cluster_lights in self.cluster_lights.iter_mut() {
+                    cluster_lights.push(light_index as u32);
+                }
+                continue;
+            }
+
+            // View-space looks down negative z.
+            let position = view_matrix.transform_point(light.position);
+            let depth = -position.z;
+            if depth + light.range < self.z_near {
+                continue;
+            }
+
+            // Only the slices the sphere overlaps need to be checked.
+            let first_slice = self
+                .grid
+                .depth_slice(depth - light.range, self.z_near, self.z_far);
+            let last_slice = self
+                .grid
+                .depth_slice(depth + light.range, self.z_near, self.z_far);
+
+            for z in first_slice..=last_slice {
+                for y in 0..self.grid.tiles_y {
+                    for x in 0..self.grid.tiles_x {
+                        let cluster_index = self.grid.cluster_index(x, y, z);
+                        if sphere_overlaps_box(
+                            position,
+                            light.range,
+                            &self.cluster_bounds[cluster_index],
+                        ) {
+                            self.cluster_lights[cluster_index].push(light_index as u32);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.light_indices.clear();
+        for (cluster, cluster_lights) in self.clusters.iter_mut().zip(self.cluster_lights.iter()) {
+            let offset = self.light_indices.len();
+            let count = cluster_lights
+                .len()
+                .min(self.max_light_indices.saturating_sub(offset));
+            self.light_indices
+                .extend_from_slice(&cluster_lights[..count]);
+            *cluster = (offset as u32, count as u32);
+        }
+    }
+
+    /// The view-space bounds of each cluster.
+    fn calculate_cluster_bounds(&mut self, projection_matrix: &Mat4) {
+        let inverse_projection = projection_matrix.inversed();
+        let unproject = |x: f32, y: f32, z: f32| {
+            let p = inverse_projection * Vec4::new(x, y, z, 1.0);
+            p.xyz() / p.w
+        };
+
+        self.cluster_bounds.clear();
+        for z in 0..self.grid.depth_slices {
+            let near_depth = self.grid.slice_depth(z, self.z_near, self.z_far);
+            let far_depth = self.grid.slice_depth(z + 1, self.z_near, self.z_far);
+            for y in 0..self.grid.tiles_y {
+                for x in 0..self.grid.tiles_x {
+                    let mut points = [Vec3::ZERO; 8];
+                    let mut i = 0;
+                    for (corner_x, corner_y) in [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)] {
+                        let ndc_x = -1.0 + 2.0 * corner_x as f32 / self.grid.tiles_x as f32;
+                        let ndc_y = -1.0 + 2.0 * corner_y as f32 / self.grid.tiles_y as f32;
+
+                        // A ray through the tile's corner.
+                        // NDC z of 0.0 is used instead of 1.0 because it's finite for infinite projections.
+                        let start = unproject(ndc_x, ndc_y, -1.0);
+                        let end = unproject(ndc_x, ndc_y, 0.0);
+                        let direction = end - start;
+
+                        for depth in [near_depth, far_depth] {
+                            let t = (-depth - start.z) / direction.z;
+                            points[i] = start + direction * t;
+                            i += 1;
+                        }
+                    }
+                    self.cluster_bounds.push(Box3::from_points(points));
+                }
+            }
+        }
+    }
+
+    pub fn clusters(&self) -> &[(u32, u32)] {
+        &self.clusters
+    }
+
+    pub fn light_indices(&self) -> &[u32] {
+        &self.light_indices
+    }
+
+    pub fn lights_in_cluster(&self, x: u32, y: u32, z: u32) -> &[u32] {
+        let (offset, count) = self.clusters[self.grid.cluster_index(x, y, z)];
+        &self.light_indices[offset as usize..(offset + count) as usize]
+    }
+
+    /// Packs the clusters and light indices into RGBA texels for a texture `texture_width` wide.
+    ///
+    /// Each cluster is one texel, (offset, count, 0, 0), ordered by cluster index.
+    /// The light indices follow on the next row, four per texel.
+    /// Offsets count indices from the start of the texture so the shader doesn't need to know
+    /// where the clusters end.
+    pub fn texture_data(&self, texture_width: usize) -> Vec<f32> {
+        let row_length = texture_width * 4;
+        let cluster_rows = self.clusters.len().div_ceil(texture_width);
+        let index_start = cluster_rows * row_length;
+
+        let mut data = Vec::with_capacity(index_start + self.light_indices.len());
+        for (offset, count) in &self.clusters {
+            data.extend_from_slice(&[
+                (index_start + *offset as usize) as f32,
+                *count as f32,
+                0.0,
+                0.0,
+            ]);
+        }
+        data.resize(index_start, 0.0);
+        data.extend(self.light_indices.iter().map(|i| *i as f32));
+
+        // Pad to full rows.
+        let rows = data.len().div_ceil(row_length);
+        data.resize(rows * row_length, 0.0);
+        data
+    }
+}
+
+fn sphere_overlaps_box(center: Vec3, radius: f32, bounds: &Box3) -> bool {
+    let closest = center.max(bounds.min).min(bounds.max);
+    (closest - center).length_squared() <= radius * radius
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assign(lights: &[ClusterLight]) -> LightClusters {
+        let mut clusters = LightClusters::new(ClusterGrid::default(), 100_000);
+        let projection = kmath::projection_matrices::perspective_infinite_gl(1.2, 16.0 / 9.0, 0.3);
+        clusters.assign_lights(&Mat4::IDENTITY, &projection, 0.3, 300.0, lights);
+        clusters
+    }
+
+    #[test]
+    fn depth_slices() {
+        let grid = ClusterGrid::default();
+        assert_eq!(grid.depth_slice(0.1, 0.3, 300.0), 0);
+        assert_eq!(grid.depth_slice(1000.0, 0.3, 300.0), grid.depth_slices - 1);
+        for slice in 0..grid.depth_slices {
+            let depth = grid.slice_depth(slice, 0.3, 300.0) * 1.001;
+            assert_eq!(grid.depth_slice(depth, 0.3, 300.0), slice);
+        }
+    }
+
+    #[test]
+    fn light_in_front_of_camera() {
+        let clusters = assign(&[ClusterLight {
+            position: Vec3::new(0.0, 0.0, -10.0),
+            range: 1.0,
+        }]);
+        let grid = clusters.grid;
+        let slice = grid.depth_slice(10.0, 0.3, 300.0);
+
+        assert_eq!(clusters.lights_in_cluster(8, 4, slice), &[0]);
+        assert_eq!(clusters.lights_in_cluster(7, 4, slice), &[0]);
+        assert!(clusters.lights_in_cluster(0, 0, slice).is_empty());
+        assert!(clusters.lights_in_cluster(8, 4, 0).is_empty());
+        assert!(clusters
+            .lights_in_cluster(8, 4, grid.depth_slices - 1)
+            .is_empty());
+    }
+
+    #[test]
+    fn light_behind_camera() {
+        let clusters = assign(&[ClusterLight {
+            position: Vec3::new(0.0, 0.0, 10.0),
+            range: 1.0,
+        }]);
+        assert!(clusters.light_indices().is_empty());
+    }
+
+    #[test]
+    fn light_to_the_side() {
+        // Right and above the view so only the top right tiles should contain it.
+        let clusters = assign(&[ClusterLight {
+            position: Vec3::new(8.0, 4.0, -10.0),
+            range: 0.5,
+        }]);
+        let grid = clusters.grid;
+        for (cluster_index, (_, count)) in clusters.clusters().iter().enumerate() {
+            if *count > 0 {
+                let x = cluster_index as u32 % grid.tiles_x;
+                let y = cluster_index as u32 / grid.tiles_x % grid.tiles_y;
+                assert!(x >= grid.tiles_x / 2 && y >= grid.tiles_y / 2);
+            }
+        }
+        assert!(!clusters.light_indices().is_empty());
+    }
+
+    #[test]
+    fn infinite_lights_are_in_every_cluster() {
+        let clusters = assign(&[
+            ClusterLight {
+                position: Vec3::new(0.0, 0.0, -10.0),
+                range: 1.0,
+            },
+            ClusterLight {
+                position: Vec3::ZERO,
+                range: f32::INFINITY,
+            },
+        ]);
+        for (offset, count) in clusters.clusters() {
+            let lights = &clusters.light_indices()[*offset as usize..(*offset + *count) as usize];
+            assert!(lights.contains(&1));
+        }
+    }
+
+    #[test]
+    fn max_light_indices() {
+        let mut clusters = LightClusters::new(ClusterGrid::default(), 10);
+        let projection = kmath::projection_matrices::perspective_infinite_gl(1.2, 16.0 / 9.0, 0.3);
+        clusters.assign_lights(
+            &Mat4::IDENTITY,
+            &projection,
+            0.3,
+            300.0,
+            &[ClusterLight {
+                position: Vec3::ZERO,
+                range: f32::INFINITY,
+            }],
+        );
+        assert_eq!(clusters.light_indices().len(), 10);
+        assert_eq!(clusters.lights_in_cluster(0, 0, 0), &[0]);
+        assert!(clusters.lights_in_cluster(15, 8, 23).is_empty());
+    }
+
+    #[test]
+    fn texture_data() {
+        let clusters = assign(&[ClusterLight {
+            position: Vec3::new(0.0, 0.0, -10.0),
+            range: 1.0,
+        }]);
+        let width = 256;
+        let data = clusters.texture_data(width);
+        assert_eq!(data.len() % (width * 4), 0);
+
+        let grid = clusters.grid;
+        let slice = grid.depth_slice(10.0, 0.3, 300.0);
+        let cluster_index = grid.cluster_index(8, 4, slice);
+        let offset = data[cluster_index * 4] as usize;
+        let count = data[cluster_index * 4 + 1] as usize;
+        assert_eq!(count, 1);
+        assert!(offset >= grid.cluster_count() * 4);
+        assert_eq!(data[offset], 0.0);
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::sync::mpsc;
 // use std::ops::{Deref, DerefMut};
 
 use crate::graphics::texture::Texture;
@@ -16,6 +17,7 @@ pub struct Material {
     pub(crate) texture_properties: HashMap<String, (Handle<Texture>, u8)>,
     pub(crate) cube_map_properties: HashMap<String, (Handle<CubeMap>, u8)>,
     pub(crate) max_texture_unit: u8,
+    shader_variant_key: ShaderVariantKey,
 }
 
 impl Material {
@@ -79,9 +81,49 @@ impl Material {
             texture_properties: HashMap::new(),
             cube_map_properties: HashMap::new(),
             max_texture_unit: 0,
+            shader_variant_key: ShaderVariantKey::default(),
         }
     }
 
+    /// Enables or disables a keyword that the [Shader] checks with `#ifdef`.
+    /// A variant of the [Shader] is compiled for each set of keywords used.
+    /// See [shader_keywords] for the keywords the built-in shaders support.
+    pub fn set_keyword(&mut self, keyword: &str, enabled: bool) {
+        self.shader_variant_key.set_keyword(keyword, enabled);
+    }
+
+    pub fn keywords(&self) -> &[String] {
+        &self.shader_variant_key.keywords
+    }
+
+    /// Overrides the [Shader]'s face culling, blending, and depth test for this [Material].
+    /// `None` uses the [Shader]'s own [PipelineSettings].
+    pub fn set_pipeline_settings(&mut self, pipeline_settings: Option<PipelineSettings>) {
+        self.shader_variant_key.pipeline_settings = pipeline_settings;
+    }
+
+    pub fn pipeline_settings(&self) -> Option<PipelineSettings> {
+        self.shader_variant_key.pipeline_settings
+    }
+
+    /// The variant of the [Shader] this [Material] renders with.
+    pub fn shader_variant_key(&self) -> &ShaderVariantKey {
+        &self.shader_variant_key
+    }
+
+    /// The names of the properties set on this [Material].
+    pub fn property_names(&self) -> impl Iterator<Item = &str> {
+        self.float_properties
+            .keys()
+            .chain(self.vec2_properties.keys())
+            .chain(self.vec3_properties.keys())
+            .chain(self.vec4_properties.keys())
+            .chain(self.mat4_properties.keys())
+            .chain(self.texture_properties.keys())
+            .chain(self.cube_map_properties.keys())
+            .map(|name| name.as_str())
+    }
+
     pub fn set_float(&mut self, name: &str, value: f32) {
         self.float_properties.insert(name.to_string(), value);
     }
@@ -203,21 +245,180 @@ impl Material {
     }
 }
 
-pub struct MaterialAssetLoader;
+struct MaterialLoadMessage {
+    handle: Handle<Material>,
+    path: String,
+    description: MaterialDescription,
+}
+
+/// A [Material] waiting for its [Shader] to load.
+struct PendingMaterial {
+    handle: Handle<Material>,
+    path: String,
+    material: Material,
+    render_state: RenderState,
+}
+
+/// Loads [Material]s from `.material.json` files, whose format is described in `material_file.rs`.
+/// Properties the [Shader] doesn't declare are reported when the [Material] loads.
+pub struct MaterialAssetLoader {
+    sender: SyncGuard<mpsc::Sender<MaterialLoadMessage>>,
+    receiver: SyncGuard<mpsc::Receiver<MaterialLoadMessage>>,
+    pending: Vec<PendingMaterial>,
+}
 
 impl Default for MaterialAssetLoader {
     fn default() -> Self {
         Self::new()
     }
 }
+
 impl MaterialAssetLoader {
     pub fn new() -> Self {
-        Self
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender: SyncGuard::new(sender),
+            receiver: SyncGuard::new(receiver),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl AssetLoaderTrait<Material> for MaterialAssetLoader {
+    type Options = ();
+    fn load_with_options(&mut self, path: &str, handle: Handle<Material>, _options: Self::Options) {
+        let path = path.to_owned();
+        let sender = self.sender.inner().clone();
+
+        ktasks::spawn(async move {
+            let description = crate::fetch_bytes(&path)
+                .await
+                .map_err(|_| MaterialLoadError::CouldNotLoadFile)
+                .and_then(|bytes| {
+                    String::from_utf8(bytes).map_err(|_| MaterialLoadError::CouldNotDecode)
+                })
+                .and_then(|json| MaterialDescription::from_json(&json));
+            match description {
+                Ok(description) => {
+                    let _ = sender.send(MaterialLoadMessage {
+                        handle,
+                        path,
+                        description,
+                    });
+                }
+                Err(error) => {
+                    klog::log!("MATERIAL ERROR: Could not load {:?}: {:?}", path, error);
+                }
+            }
+        })
+        .run();
+    }
+
+    fn reload(&mut self, path: &str, handle: Handle<Material>) {
+        self.load_with_options(path, handle, ());
     }
 }
 
 impl AssetTrait for Material {
-    type AssetLoader = ();
+    type AssetLoader = MaterialAssetLoader;
+}
+
+/// `path` relative to the directory of the file at `relative_to`.
+fn relative_path(relative_to: &str, path: &str) -> String {
+    let directory = std::path::Path::new(relative_to)
+        .parent()
+        .unwrap_or_else(|| std::path::Path::new(""));
+    directory.join(path).to_string_lossy().into_owned()
+}
+
+/// A system that creates [Material]s loaded from files once their [Shader] has loaded.
+pub(crate) fn load_materials(
+    graphics: &mut Graphics,
+    materials: &mut Assets<Material>,
+    shaders: &mut Assets<Shader>,
+    textures: &mut Assets<Texture>,
+) {
+    let asset_loader = &mut materials.asset_loader;
+    while let Ok(message) = asset_loader.receiver.inner().try_recv() {
+        let MaterialDescription {
+            shader,
+            keywords,
+            properties,
+            textures: texture_properties,
+            render_state,
+        } = message.description;
+
+        let shader = Shader::built_in(&shader)
+            .unwrap_or_else(|| shaders.load(&relative_path(&message.path, &shader)));
+        let mut material = Material::new(shader);
+        for keyword in &keywords {
+            material.set_keyword(keyword, true);
+        }
+        for (name, property) in properties {
+            match property {
+                MaterialProperty::Float(v) => material.set_float(&name, v),
+                MaterialProperty::Vec2(v) => material.set_vec2(&name, v),
+                MaterialProperty::Vec3(v) => material.set_vec3(&name, v),
+                MaterialProperty::Vec4(v) => material.set_vec4(&name, v),
+                MaterialProperty::Color(v) => material.set_color(&name, v),
+            }
+        }
+        for (name, texture) in texture_properties {
+            let path = relative_path(&message.path, &texture.path);
+            let texture = textures.load_with_options(&path, texture.texture_settings);
+            material.set_texture(&name, texture);
+        }
+
+        asset_loader.pending.push(PendingMaterial {
+            handle: message.handle,
+            path: message.path,
+            material,
+            render_state,
+        });
+    }
+
+    // Properties are checked against the shader so materials wait for it to load.
+    let pending = std::mem::take(&mut materials.asset_loader.pending);
+    for mut pending in pending {
+        if shaders.is_placeholder(&pending.material.shader) {
+            match shaders.handle_to_path(&pending.material.shader) {
+                // Reloading the material file tries again.
+                Some(shader) if shaders.asset_loader.failed(shader) => {
+                    let error = MaterialLoadError::ShaderFailed {
+                        shader: shader.to_string(),
+                    };
+                    klog::log!("MATERIAL ERROR: In {:?}: {:?}", pending.path, error);
+                }
+                _ => materials.asset_loader.pending.push(pending),
+            }
+            continue;
+        }
+
+        let material = &mut pending.material;
+        let shader_name = shaders.handle_to_path(&material.shader).map(String::from);
+        let shader = shaders.get_mut(&material.shader);
+        if !pending.render_state.is_empty() {
+            material
+                .set_pipeline_settings(Some(pending.render_state.apply(shader.pipeline_settings)));
+        }
+
+        // Variants can declare uniforms their shader doesn't so properties are checked against the variant.
+        shader.compile_variant(graphics, material.shader_variant_key());
+        let variant = shader.variant(material.shader_variant_key());
+        for name in material.property_names() {
+            if !variant.has_uniform(name) {
+                let error = MaterialLoadError::UnknownProperty {
+                    name: name.to_string(),
+                    shader: shader_name
+                        .clone()
+                        .unwrap_or_else(|| variant.name.to_string()),
+                };
+                klog::log!("MATERIAL ERROR: In {:?}: {:?}", pending.path, error);
+            }
+        }
+
+        materials.replace(&pending.handle, pending.material);
+    }
 }
 
 /// Some built in properties for materials
@@ -0,0 +1,461 @@
+//! Parses `.material.json` files so [Material]s can be authored without recompiling.
+//!
+//! ```json
+//! {
+//!     "shader": "shaders/water.glsl",
+//!     "keywords": ["NORMAL_MAP"],
+//!     "properties": {
+//!         "p_roughness": { "float": 0.4 },
+//!         "p_texture_coordinate_scale": { "vec2": [2.0, 2.0] },
+//!         "p_base_color": { "color": [1.0, 0.5, 0.2, 1.0], "color_space": "srgb" }
+//!     },
+//!     "textures": {
+//!         "p_normal_texture": { "path": "water_normal.png", "srgb": false, "wrap": "mirror_repeat" }
+//!     },
+//!     "render_state": { "faces": "front_and_back", "blending": "alpha" }
+//! }
+//! ```
+//!
+//! `shader` is either the name of a built-in [Shader], like `"PHYSICALLY_BASED"`, or a path.
+//! Shader and texture paths are relative to the material file.
+//!
+//! Properties are `float`, `vec2`, `vec3`, `vec4`, or `color`.
+//! A `color`'s `color_space` is `srgb` (the default), `linear_srgb`, `display_p3`, or `linear_display_p3`.
+//!
+//! Textures accept `srgb`, `generate_mipmaps`, `filter`, `mipmap_filter`,
+//! `wrap`, `wrap_horizontal`, and `wrap_vertical` to change their [TextureSettings].
+//! Filters are `nearest` or `linear` and wrapping is `repeat`, `mirror_repeat`, or `clamp_to_edge`.
+//!
+//! `render_state` overrides the shader's `faces` (`front`, `back`, `front_and_back`, or `none`),
+//! `blending` (`none`, `alpha`, or `additive`), and `depth_test`
+//! (`always_pass`, `less`, `greater`, `less_or_equal`, or `greater_or_equal`).
+
+use crate::*;
+use kgraphics::*;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub enum MaterialLoadError {
+    CouldNotLoadFile,
+    CouldNotDecode,
+    MissingShader,
+    /// The value at `name` isn't what the format expects.
+    InvalidValue {
+        name: String,
+        expected: &'static str,
+    },
+    /// The shader doesn't declare a uniform for a property or texture.
+    UnknownProperty {
+        name: String,
+        shader: String,
+    },
+    /// The shader couldn't be read or compiled so the material can't be created.
+    ShaderFailed {
+        shader: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum MaterialProperty {
+    Float(f32),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4(Vec4),
+    Color(Color),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct MaterialTexture {
+    pub path: String,
+    pub texture_settings: TextureSettings,
+}
+
+/// Overrides for the shader's [PipelineSettings]. `None` keeps the shader's setting.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RenderState {
+    pub faces_to_render: Option<FacesToRender>,
+    pub blending: Option<Option<(BlendFactor, BlendFactor)>>,
+    pub depth_test: Option<DepthTest>,
+}
+
+impl RenderState {
+    pub fn is_empty(&self) -> bool {
+        self.faces_to_render.is_none() && self.blending.is_none() && self.depth_test.is_none()
+    }
+
+    pub fn apply(&self, pipeline_settings: PipelineSettings) -> PipelineSettings {
+        PipelineSettings {
+            faces_to_render: self
+                .faces_to_render
+                .unwrap_or(pipeline_settings.faces_to_render),
+            blending: self.blending.unwrap_or(pipeline_settings.blending),
+            depth_test: self.depth_test.unwrap_or(pipeline_settings.depth_test),
+            stencil: pipeline_settings.stencil,
+        }
+    }
+}
+
+/// The contents of a `.material.json` file.
+#[derive(Debug, Clone)]
+pub(crate) struct MaterialDescription {
+    pub shader: String,
+    pub keywords: Vec<String>,
+    pub properties: Vec<(String, MaterialProperty)>,
+    pub textures: Vec<(String, MaterialTexture)>,
+    pub render_state: RenderState,
+}
+
+impl MaterialDescription {
+    pub fn from_json(json: &str) -> Result<Self, MaterialLoadError> {
+        let thing = Thing::from_json(json).ok_or(MaterialLoadError::CouldNotDecode)?;
+        let mut shader = None;
+        let mut description = Self {
+            shader: String::new(),
+            keywords: Vec::new(),
+            properties: Vec::new(),
+            textures: Vec::new(),
+            render_state: RenderState::default(),
+        };
+
+        for (key, value) in object_entries("material", &thing)? {
+            match key {
+                "shader" => shader = Some(string(key, value)?.to_string()),
+                "keywords" => {
+                    for keyword in value.array().ok_or_else(|| invalid(key, "an array"))? {
+                        description.keywords.push(string(key, keyword)?.to_string());
+                    }
+                }
+                "properties" => {
+                    for (name, value) in object_entries(key, value)? {
+                        let property = parse_property(name, value)?;
+                        description.properties.push((name.to_string(), property));
+                    }
+                }
+                "textures" => {
+                    for (name, value) in object_entries(key, value)? {
+                        let texture = parse_texture(name, value)?;
+                        description.textures.push((name.to_string(), texture));
+                    }
+                }
+                "render_state" => description.render_state = parse_render_state(value)?,
+                _ => {
+                    return Err(invalid(
+                        key,
+                        "one of `shader`, `keywords`, `properties`, `textures`, or `render_state`",
+                    ))
+                }
+            }
+        }
+
+        description.shader = shader.ok_or(MaterialLoadError::MissingShader)?;
+        Ok(description)
+    }
+}
+
+fn invalid(name: &str, expected: &'static str) -> MaterialLoadError {
+    MaterialLoadError::InvalidValue {
+        name: name.to_string(),
+        expected,
+    }
+}
+
+/// An object's entries in the order they appear in the file.
+fn object_entries<'a, 'b>(
+    name: &str,
+    thing: &'b Thing<'a>,
+) -> Result<Vec<(&'b str, &'b Thing<'a>)>, MaterialLoadError> {
+    let object: &HashMap<Cow<'a, str>, ObjectProperty<'a>> =
+        thing.object().ok_or_else(|| invalid(name, "an object"))?;
+    let mut entries: Vec<_> = object.iter().collect();
+    entries.sort_by_key(|(_, property)| property.index);
+    Ok(entries
+        .into_iter()
+        .map(|(key, property)| (&**key, &property.item))
+        .collect())
+}
+
+fn string<'b>(name: &str, thing: &'b Thing) -> Result<&'b str, MaterialLoadError> {
+    thing
+        .string()
+        .map(|s| &**s)
+        .ok_or_else(|| invalid(name, "a string"))
+}
+
+fn bool(name: &str, thing: &Thing) -> Result<bool, MaterialLoadError> {
+    thing.bool().ok_or_else(|| invalid(name, "true or false"))
+}
+
+fn numbers<const N: usize>(
+    name: &str,
+    thing: &Thing,
+    expected: &'static str,
+) -> Result<[f32; N], MaterialLoadError> {
+    let mut numbers = [0.0; N];
+    match thing.array() {
+        Some(array) if array.len() == N => {
+            for (number, thing) in numbers.iter_mut().zip(array) {
+                *number = thing.number().ok_or_else(|| invalid(name, expected))? as f32;
+            }
+            Ok(numbers)
+        }
+        _ => Err(invalid(name, expected)),
+    }
+}
+
+fn parse_property(name: &str, thing: &Thing) -> Result<MaterialProperty, MaterialLoadError> {
+    let mut property = None;
+    // Colors are converted once their color space is known.
+    let mut color = None;
+    let mut color_space = color_spaces::ENCODED_SRGB;
+    for (key, value) in object_entries(name, thing)? {
+        match key {
+            "float" => {
+                let value = value.number().ok_or_else(|| invalid(name, "a number"))?;
+                property = Some(MaterialProperty::Float(value as f32));
+            }
+            "vec2" => {
+                let value = numbers::<2>(name, value, "an array of 2 numbers")?;
+                property = Some(MaterialProperty::Vec2(value.into()));
+            }
+            "vec3" => {
+                let value = numbers::<3>(name, value, "an array of 3 numbers")?;
+                property = Some(MaterialProperty::Vec3(value.into()));
+            }
+            "vec4" => {
+                let value = numbers::<4>(name, value, "an array of 4 numbers")?;
+                property = Some(MaterialProperty::Vec4(value.into()));
+            }
+            "color" => color = Some(numbers::<4>(name, value, "an array of 4 numbers")?),
+            "color_space" => {
+                color_space = match string(name, value)? {
+                    "srgb" => color_spaces::ENCODED_SRGB,
+                    "linear_srgb" => color_spaces::LINEAR_SRGB,
+                    "display_p3" => color_spaces::ENCODED_DISPLAY_P3,
+                    "linear_display_p3" => color_spaces::DISPLAY_P3,
+                    _ => {
+                        return Err(invalid(
+                            name,
+                            "a `color_space` of `srgb`, `linear_srgb`, `display_p3`, or `linear_display_p3`",
+                        ))
+                    }
+                }
+            }
+            _ => {
+                return Err(invalid(
+                    name,
+                    "a `float`, `vec2`, `vec3`, `vec4`, or `color` property",
+                ))
+            }
+        }
+    }
+
+    if let Some([red, green, blue, alpha]) = color {
+        property = Some(MaterialProperty::Color(Color::new_with_colorspace(
+            red,
+            green,
+            blue,
+            alpha,
+            color_space,
+        )));
+    }
+    property.ok_or_else(|| {
+        invalid(
+            name,
+            "a `float`, `vec2`, `vec3`, `vec4`, or `color` property",
+        )
+    })
+}
+
+fn parse_texture(name: &str, thing: &Thing) -> Result<MaterialTexture, MaterialLoadError> {
+    let mut path = None;
+    let mut texture_settings = TextureSettings::default();
+    for (key, value) in object_entries(name, thing)? {
+        match key {
+            "path" => path = Some(string(name, value)?.to_string()),
+            "srgb" => texture_settings.srgb = bool(name, value)?,
+            "generate_mipmaps" => texture_settings.generate_mipmaps = bool(name, value)?,
+            "filter" => {
+                let filter = parse_filter(name, value)?;
+                texture_settings.minification_filter = filter;
+                texture_settings.magnification_filter = filter;
+            }
+            "mipmap_filter" => texture_settings.mipmap_filter = parse_filter(name, value)?,
+            "wrap" => {
+                let wrap = parse_wrap(name, value)?;
+                texture_settings.wrapping_horizontal = wrap;
+                texture_settings.wrapping_vertical = wrap;
+            }
+            "wrap_horizontal" => texture_settings.wrapping_horizontal = parse_wrap(name, value)?,
+            "wrap_vertical" => texture_settings.wrapping_vertical = parse_wrap(name, value)?,
+            _ => return Err(invalid(
+                name,
+                "a texture with `path`, `srgb`, `generate_mipmaps`, `filter`, `mipmap_filter`, `wrap`, `wrap_horizontal`, or `wrap_vertical`",
+            )),
+        }
+    }
+
+    Ok(MaterialTexture {
+        path: path.ok_or_else(|| invalid(name, "a texture with a `path`"))?,
+        texture_settings,
+    })
+}
+
+fn parse_filter(name: &str, thing: &Thing) -> Result<FilterMode, MaterialLoadError> {
+    match string(name, thing)? {
+        "nearest" => Ok(FilterMode::Nearest),
+        "linear" => Ok(FilterMode::Linear),
+        _ => Err(invalid(name, "a filter of `nearest` or `linear`")),
+    }
+}
+
+fn parse_wrap(name: &str, thing: &Thing) -> Result<WrappingMode, MaterialLoadError> {
+    match string(name, thing)? {
+        "repeat" => Ok(WrappingMode::Repeat),
+        "mirror_repeat" => Ok(WrappingMode::MirrorRepeat),
+        "clamp_to_edge" => Ok(WrappingMode::ClampToEdge),
+        _ => Err(invalid(
+            name,
+            "wrapping of `repeat`, `mirror_repeat`, or `clamp_to_edge`",
+        )),
+    }
+}
+
+fn parse_render_state(thing: &Thing) -> Result<RenderState, MaterialLoadError> {
+    let mut render_state = RenderState::default();
+    for (key, value) in object_entries("render_state", thing)? {
+        match key {
+            "faces" => {
+                render_state.faces_to_render = Some(match string(key, value)? {
+                    "front" => FacesToRender::Front,
+                    "back" => FacesToRender::Back,
+                    "front_and_back" => FacesToRender::FrontAndBack,
+                    "none" => FacesToRender::None,
+                    _ => return Err(invalid(key, "`front`, `back`, `front_and_back`, or `none`")),
+                })
+            }
+            "blending" => {
+                render_state.blending = Some(match string(key, value)? {
+                    "none" => None,
+                    // Textures are premultiplied when loaded.
+                    "alpha" => Some((BlendFactor::One, BlendFactor::OneMinusSourceAlpha)),
+                    "additive" => Some((BlendFactor::One, BlendFactor::One)),
+                    _ => return Err(invalid(key, "`none`, `alpha`, or `additive`")),
+                })
+            }
+            "depth_test" => {
+                render_state.depth_test = Some(match string(key, value)? {
+                    "always_pass" => DepthTest::AlwaysPass,
+                    "less" => DepthTest::Less,
+                    "greater" => DepthTest::Greater,
+                    "less_or_equal" => DepthTest::LessOrEqual,
+                    "greater_or_equal" => DepthTest::GreaterOrEqual,
+                    _ => return Err(invalid(
+                        key,
+                        "`always_pass`, `less`, `greater`, `less_or_equal`, or `greater_or_equal`",
+                    )),
+                })
+            }
+            _ => return Err(invalid(key, "one of `faces`, `blending`, or `depth_test`")),
+        }
+    }
+    Ok(render_state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_material() {
+        let description = MaterialDescription::from_json(
+            r#"{
+                "shader": "PHYSICALLY_BASED",
+                "keywords": ["NORMAL_MAP"],
+                "properties": {
+                    "p_roughness": { "float": 0.5 },
+                    "p_texture_coordinate_scale": { "vec2": [2.0, 3.0] },
+                    "p_base_color": { "color": [1.0, 1.0, 1.0, 0.5], "color_space": "linear_srgb" }
+                },
+                "textures": {
+                    "p_normal_texture": { "path": "normal.png", "srgb": false, "filter": "nearest", "wrap": "clamp_to_edge" }
+                },
+                "render_state": { "faces": "front_and_back", "blending": "none" }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(description.shader, "PHYSICALLY_BASED");
+        assert_eq!(description.keywords, ["NORMAL_MAP"]);
+
+        let names: Vec<_> = description
+            .properties
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            ["p_roughness", "p_texture_coordinate_scale", "p_base_color"]
+        );
+        assert!(matches!(
+            description.properties[0].1,
+            MaterialProperty::Float(v) if v == 0.5
+        ));
+        assert!(matches!(
+            description.properties[1].1,
+            MaterialProperty::Vec2(v) if v == Vec2::new(2.0, 3.0)
+        ));
+        assert!(matches!(
+            description.properties[2].1,
+            MaterialProperty::Color(_)
+        ));
+
+        let (name, texture) = &description.textures[0];
+        assert_eq!(name, "p_normal_texture");
+        assert_eq!(texture.path, "normal.png");
+        assert!(!texture.texture_settings.srgb);
+        assert!(matches!(
+            texture.texture_settings.magnification_filter,
+            FilterMode::Nearest
+        ));
+        assert!(matches!(
+            texture.texture_settings.wrapping_vertical,
+            WrappingMode::ClampToEdge
+        ));
+
+        let pipeline_settings = description.render_state.apply(PipelineSettings::default());
+        assert_eq!(
+            pipeline_settings.faces_to_render,
+            FacesToRender::FrontAndBack
+        );
+        assert_eq!(pipeline_settings.blending, None);
+        assert_eq!(pipeline_settings.depth_test, DepthTest::LessOrEqual);
+    }
+
+    #[test]
+    fn requires_a_shader() {
+        assert!(matches!(
+            MaterialDescription::from_json(r#"{ "properties": {} }"#),
+            Err(MaterialLoadError::MissingShader)
+        ));
+    }
+
+    #[test]
+    fn reports_invalid_values_by_name() {
+        let error = MaterialDescription::from_json(
+            r#"{ "shader": "UNLIT", "properties": { "p_scale": { "vec2": [1.0] } } }"#,
+        );
+        assert!(matches!(
+            error,
+            Err(MaterialLoadError::InvalidValue { name, .. }) if name == "p_scale"
+        ));
+
+        let error = MaterialDescription::from_json(
+            r#"{ "shader": "UNLIT", "render_state": { "faces": "sideways" } }"#,
+        );
+        assert!(matches!(
+            error,
+            Err(MaterialLoadError::InvalidValue { name, .. }) if name == "faces"
+        ));
+    }
+}
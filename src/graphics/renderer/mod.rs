@@ -1,3 +1,6 @@
+// The headless backend skips rendering, which leaves most of the renderer unused.
+#![cfg_attr(feature = "headless", allow(dead_code))]
+
 use crate::*;
 use kgraphics::*;
 
@@ -5,23 +8,64 @@ mod material;
 use kmath::intersections::frustum_with_bounding_box;
 pub use material::*;
 
+mod material_file;
+pub use material_file::*;
+
 mod pbr_material;
 pub use pbr_material::*;
 
 mod sprite;
 pub use sprite::*;
 
+mod sprite_renderer;
+pub use sprite_renderer::*;
+
 mod brdf_lookup;
 
+mod data_buffer_pool;
+use data_buffer_pool::*;
+
 mod bloom_calculator;
 pub use bloom_calculator::*;
 
+mod exposure_calculator;
+pub use exposure_calculator::*;
+
 mod shadow_caster;
 pub use shadow_caster::*;
 
 mod offscreen_render_target;
 pub use offscreen_render_target::*;
 
+mod light_clusters;
+pub use light_clusters::*;
+
+mod particle_renderer;
+pub use particle_renderer::*;
+
+mod ambient_occlusion;
+pub use ambient_occlusion::*;
+
+mod anti_aliasing;
+pub use anti_aliasing::*;
+
+mod render_graph;
+pub use render_graph::*;
+
+mod fog;
+pub use fog::*;
+
+mod decal;
+pub use decal::*;
+
+mod outline;
+pub use outline::*;
+
+/// Lights past this many are ignored.
+pub const MAX_LIGHTS: usize = 256;
+const LIGHT_CLUSTER_TEXTURE_WIDTH: usize = 256;
+const LIGHT_CLUSTER_TEXTURE_HEIGHT: usize = 256;
+
 use crate::graphics::texture::Texture;
 
 #[derive(Clone)]
@@ -70,21 +114,66 @@ impl RenderTargetTexture {
 pub struct RendererInfo {
     pub brdf_lookup_table: Handle<Texture>,
     offscreen_render_target: OffscreenRenderTarget,
+    /// Used instead of `offscreen_render_target` by [Camera]s that don't use [AntiAliasing::Msaa].
+    single_sample_render_target: OffscreenRenderTarget,
     blur_calculator: BloomCalculator,
+    exposure_calculator: ExposureCalculator,
+    ambient_occlusion: AmbientOcclusionCalculator,
+    anti_aliasing: AntiAliasingCalculator,
+    decal_renderer: DecalRenderer,
+    outline_renderer: OutlineRenderer,
     final_postprocess_shader: Shader,
     pub bloom_enabled: bool,
     /// This value should be from 0.0 to 1.0
     /// The default value is 0.1. More than 0.3 looks rather extreme. 0.0 is no bloom.
     pub bloom_strength: f32,
     pub cascade_depths: [f32; 4],
+    light_data_texture: Texture,
+    light_clusters: LightClusters,
+    /// One per [Camera] because they're updated before the frame's commands run.
+    light_cluster_textures: Vec<Texture>,
+    /// Group identical [Mesh] and [Material] pairs into instanced draws.
+    /// The default value is `true`.
+    pub batching_enabled: bool,
+    /// Draw call counts for the most recently rendered frame.
+    pub statistics: RenderStatistics,
+    instance_model_buffers: DataBufferPool<Mat4>,
+    instance_color_buffers: DataBufferPool<Vec4>,
+    particle_batches: Vec<ParticleBatch>,
+    sprites: PreparedSprites,
+    sprite_buffers: SpriteBuffers,
+    /// Counts rendered frames to animate film grain.
+    frame: u32,
+    /// The passes that render each [Camera]. Add custom passes here.
+    pub render_graph: RenderGraph,
+}
+
+/// Draw call counts for a frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStatistics {
+    /// How many draw calls would have been issued if nothing were batched.
+    pub draw_calls_before_batching: usize,
+    /// How many draw calls were actually issued.
+    pub draw_calls: usize,
+}
+
+impl std::ops::AddAssign for RenderStatistics {
+    fn add_assign(&mut self, other: Self) {
+        self.draw_calls_before_batching += other.draw_calls_before_batching;
+        self.draw_calls += other.draw_calls;
+    }
 }
 
 pub fn renderer_plugin() -> Plugin {
     Plugin {
         setup_systems: vec![setup_renderer.system()],
         end_of_frame_systems: vec![
+            update_procedural_skies.system(),
             prepare_shadow_casters.system(),
-            render_scene.system(),
+            prepare_particles.system(),
+            prepare_sprites.system(),
+            update_decal_meshes.system(),
+            render_main_world.system(),
             drop_materials.system(),
         ],
         ..Default::default()
@@ -95,10 +184,45 @@ pub fn drop_materials(materials: &mut Assets<Material>) {
     materials.drop_items(|_| {})
 }
 
+/// Creates a target for a [Camera] to render its scene into before post-processing.
+fn new_scene_render_target(
+    graphics: &mut Graphics,
+    textures: &mut Assets<Texture>,
+    initial_size: (u32, u32),
+    msaa_samples: u8,
+) -> OffscreenRenderTarget {
+    OffscreenRenderTarget::new(
+        graphics,
+        textures,
+        Vec2u::new(initial_size.0 as usize, initial_size.1 as usize),
+        Some((
+            PixelFormat::RGBA16F,
+            TextureSettings {
+                msaa_samples,
+                srgb: false,
+                generate_mipmaps: false,
+                ..Default::default()
+            },
+        )),
+        Some((
+            PixelFormat::Depth32F,
+            TextureSettings {
+                srgb: false,
+                msaa_samples,
+                generate_mipmaps: false,
+                // Depth textures can't be linearly filtered on every platform.
+                minification_filter: FilterMode::Nearest,
+                magnification_filter: FilterMode::Nearest,
+                ..Default::default()
+            },
+        )),
+    )
+}
+
 pub fn setup_renderer(world: &mut World) {
     let default_material = new_pbr_material(Shader::PHYSICALLY_BASED, PBRProperties::default());
 
-    let mut materials = Assets::<Material>::new(default_material, ());
+    let mut materials = Assets::<Material>::new(default_material, MaterialAssetLoader::new());
     Material::initialize_static_materials(&mut materials);
     world.spawn((Name("Assets<Material>".into()), materials));
 
@@ -124,6 +248,11 @@ pub fn setup_renderer(world: &mut World) {
     let initial_size = (1, 1);
 
     let blur_calculator = BloomCalculator::new.run(world);
+    let exposure_calculator = ExposureCalculator::new.run(world);
+    let ambient_occlusion = AmbientOcclusionCalculator::new.run(world);
+    let anti_aliasing = AntiAliasingCalculator::new.run(world);
+    let decal_renderer = DecalRenderer::new.run(world);
+    let outline_renderer = OutlineRenderer::new.run(world);
     let renderer_info = RendererInfo {
         bloom_enabled: false,
         bloom_strength: 0.1,
@@ -135,42 +264,156 @@ pub fn setup_renderer(world: &mut World) {
                     faces_to_render: FacesToRender::Front,
                     blending: Some((BlendFactor::One, BlendFactor::OneMinusSourceAlpha)),
                     depth_test: DepthTest::AlwaysPass,
+                    stencil: None,
                 },
             )
             .unwrap(),
         blur_calculator,
+        exposure_calculator,
+        ambient_occlusion,
+        anti_aliasing,
+        decal_renderer,
+        outline_renderer,
         brdf_lookup_table,
         offscreen_render_target: (|graphics: &mut Graphics, textures: &mut Assets<Texture>| {
-            OffscreenRenderTarget::new(
-                graphics,
-                textures,
-                Vec2u::new(initial_size.0 as usize, initial_size.1 as usize),
-                Some((
-                    PixelFormat::RGBA16F,
-                    TextureSettings {
-                        msaa_samples: 4,
-                        srgb: false,
-                        generate_mipmaps: false,
-                        ..Default::default()
-                    },
-                )),
-                Some((
-                    PixelFormat::Depth32F,
-                    TextureSettings {
-                        srgb: false,
-                        msaa_samples: 4,
-                        generate_mipmaps: false,
-                        ..Default::default()
-                    },
-                )),
-            )
+            new_scene_render_target(graphics, textures, initial_size, 4)
+        })
+        .run(world),
+        single_sample_render_target: (|graphics: &mut Graphics, textures: &mut Assets<Texture>| {
+            new_scene_render_target(graphics, textures, initial_size, 0)
         })
         .run(world),
         cascade_depths: [5., 15., 30., 60.],
+        light_data_texture: world
+            .get_singleton::<Graphics>()
+            .new_texture(
+                None,
+                4,
+                MAX_LIGHTS as u32,
+                1,
+                PixelFormat::RGBA32F,
+                light_data_texture_settings(),
+            )
+            .unwrap(),
+        light_clusters: LightClusters::new(ClusterGrid::default(), {
+            let cluster_rows = ClusterGrid::default()
+                .cluster_count()
+                .div_ceil(LIGHT_CLUSTER_TEXTURE_WIDTH);
+            (LIGHT_CLUSTER_TEXTURE_HEIGHT - cluster_rows) * LIGHT_CLUSTER_TEXTURE_WIDTH * 4
+        }),
+        light_cluster_textures: Vec::new(),
+        batching_enabled: true,
+        statistics: RenderStatistics::default(),
+        instance_model_buffers: DataBufferPool::default(),
+        instance_color_buffers: DataBufferPool::default(),
+        particle_batches: Vec::new(),
+        sprites: PreparedSprites::default(),
+        sprite_buffers: SpriteBuffers::default(),
+        frame: 0,
+        render_graph: RenderGraph::new(),
     };
     world.spawn((Name("RendererInfo".into()), renderer_info));
 }
 
+fn light_data_texture_settings() -> TextureSettings {
+    TextureSettings {
+        // Data textures are read with `texelFetch` so they must not be filtered.
+        minification_filter: FilterMode::Nearest,
+        magnification_filter: FilterMode::Nearest,
+        srgb: false,
+        generate_mipmaps: false,
+        ..Default::default()
+    }
+}
+
+/// Packs every light's properties into the light data texture.
+/// Returns each light's sphere of influence for clustering.
+fn upload_light_data(
+    graphics: &mut Graphics,
+    renderer_info: &RendererInfo,
+    lights: &Lights,
+) -> Vec<ClusterLight> {
+    let mut data: Vec<f32> = Vec::new();
+    let mut cluster_lights = Vec::new();
+    for (transform, light, _) in lights.iter().take(MAX_LIGHTS) {
+        if transform.position.is_nan() {
+            dbg!("Light position is NaN");
+            // Keep the light so that indices still line up, but make it have no effect.
+            data.extend_from_slice(&[0.0; 16]);
+            cluster_lights.push(ClusterLight {
+                position: Vec3::ZERO,
+                range: 0.0,
+            });
+            continue;
+        }
+
+        let mode = match light.light_mode {
+            LightMode::Directional => 0.0,
+            LightMode::Point { .. } => 1.0,
+            LightMode::Spot { .. } => 2.0,
+        };
+        let (inner_cos, outer_cos, radius) = match light.light_mode {
+            LightMode::Directional => (0.0, 0.0, 0.0),
+            LightMode::Point { radius } => (0.0, 0.0, radius),
+            // The shader compares cosines to avoid calling `acos` per pixel.
+            LightMode::Spot {
+                inner_angle,
+                outer_angle,
+                ..
+            } => (inner_angle.cos(), outer_angle.cos(), 0.0),
+        };
+
+        // TODO: Make a color property and convert it into the framebuffer's color space first.
+        let color_and_intensity = light
+            .color
+            .to_rgb_color(crate::color_spaces::LINEAR_SRGB)
+            .xyz()
+            * light.intensity;
+
+        let range = light.influence_range();
+        let direction = transform.forward();
+        data.extend_from_slice(&[
+            transform.position.x,
+            transform.position.y,
+            transform.position.z,
+            mode,
+            direction.x,
+            direction.y,
+            direction.z,
+            // Infinity can't be relied on in shaders.
+            range.min(f32::MAX),
+            color_and_intensity.x,
+            color_and_intensity.y,
+            color_and_intensity.z,
+            light.ambient_light_amount,
+            inner_cos,
+            outer_cos,
+            radius,
+            0.0,
+        ]);
+        cluster_lights.push(ClusterLight {
+            position: transform.position,
+            range,
+        });
+    }
+
+    if !cluster_lights.is_empty() {
+        graphics.context.update_texture(
+            &renderer_info.light_data_texture,
+            0,
+            0,
+            0,
+            4,
+            cluster_lights.len() as u32,
+            1,
+            Some(bytemuck::cast_slice(&data)),
+            PixelFormat::RGBA32F,
+            light_data_texture_settings(),
+        );
+    }
+    cluster_lights
+}
+
 pub struct ViewInfo {
     pub projection_matrix: Mat4,
     pub view_matrix: Mat4,
@@ -182,13 +425,21 @@ struct PipelineInfo {
     model_property: Mat4Property,
     position_attribute: VertexAttribute<Vec3>,
     normal_attribute: VertexAttribute<Vec3>,
+    tangent_attribute: VertexAttribute<Vec4>,
     vertex_color_attribute: VertexAttribute<Vec4>,
+    joints_attribute: VertexAttribute<Vec4>,
+    weights_attribute: VertexAttribute<Vec4>,
     texture_coordinate_attribute: VertexAttribute<Vec2>,
+    texture_coordinate_1_attribute: VertexAttribute<Vec2>,
     base_color_property: Vec4Property,
     base_color_texture_property: TextureProperty,
     texture_coordinate_offset_property: Vec2Property,
     texture_coordinate_scale_property: Vec2Property,
     sprite_texture_unit: Option<u8>,
+    instanced_property: IntProperty,
+    instance_model_attribute: VertexAttribute<Mat4>,
+    instance_color_attribute: VertexAttribute<Vec4>,
+    lod_fade_property: FloatProperty,
 }
 
 struct Renderer<'a, 'b: 'a> {
@@ -200,7 +451,7 @@ struct Renderer<'a, 'b: 'a> {
     texture_assets: &'a Assets<Texture>,
     cube_map_assets: &'a Assets<CubeMap>,
     bound_mesh: Option<&'a Handle<Mesh>>,
-    bound_shader: Option<&'a Handle<Shader>>,
+    bound_shader: Option<&'a Shader>,
     material_handle: Option<&'a Handle<Material>>,
     pipeline_info: Option<PipelineInfo>,
     #[allow(unused)]
@@ -211,6 +462,14 @@ struct Renderer<'a, 'b: 'a> {
     brdf_lookup_texture: &'a Texture,
     color_is_set: bool,
     renderer_info: &'a RendererInfo,
+    light_cluster_texture: &'a Texture,
+    viewport_size: Vec2,
+    batching_enabled: bool,
+    statistics: RenderStatistics,
+    /// Moved in from [RendererInfo] for the duration of a frame.
+    instance_model_buffers: DataBufferPool<Mat4>,
+    instance_color_buffers: DataBufferPool<Vec4>,
+    fog: FogUniforms,
 }
 
 impl<'a, 'b: 'a> Renderer<'a, 'b> {
@@ -226,6 +485,8 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
         camera_info: &'a [ViewInfo],
         viewport: kmath::geometry::BoundingBox<u32, 2>,
         multiview_enabled: bool,
+        light_cluster_texture: &'a Texture,
+        fog: Option<&Fog>,
     ) -> Self {
         let min = viewport.min;
         let size = viewport.size();
@@ -252,6 +513,13 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
             brdf_lookup_texture,
             color_is_set: false,
             renderer_info,
+            light_cluster_texture,
+            viewport_size: Vec2::new(size.x as f32, size.y as f32),
+            batching_enabled: renderer_info.batching_enabled,
+            statistics: RenderStatistics::default(),
+            instance_model_buffers: DataBufferPool::default(),
+            instance_color_buffers: DataBufferPool::default(),
+            fog: FogUniforms::new(fog),
         }
     }
 
@@ -274,10 +542,74 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
         }
     }
 
-    fn bind_light_info(&mut self, pipeline: &Pipeline, lights: &Lights, max_texture_unit: u8) {
+    fn bind_fog(&mut self, pipeline: &Pipeline) {
+        let fog = &self.fog;
+        self.render_pass
+            .set_int_property(&pipeline.get_int_property("p_fog_mode").unwrap(), fog.mode);
+        self.render_pass.set_vec4_property(
+            &pipeline.get_vec4_property("p_fog_color").unwrap(),
+            fog.color.into(),
+        );
+        self.render_pass.set_float_property(
+            &pipeline.get_float_property("p_fog_start").unwrap(),
+            fog.start,
+        );
+        self.render_pass
+            .set_float_property(&pipeline.get_float_property("p_fog_end").unwrap(), fog.end);
+        self.render_pass.set_float_property(
+            &pipeline.get_float_property("p_fog_density").unwrap(),
+            fog.density,
+        );
+        self.render_pass.set_float_property(
+            &pipeline.get_float_property("p_fog_height_falloff").unwrap(),
+            fog.height_falloff,
+        );
+        self.render_pass.set_float_property(
+            &pipeline.get_float_property("p_fog_base_height").unwrap(),
+            fog.base_height,
+        );
         self.render_pass.set_int_property(
-            &pipeline.get_int_property("p_light_count").unwrap(),
-            lights.iter().count() as i32,
+            &pipeline
+                .get_int_property("p_fog_use_environment_color")
+                .unwrap(),
+            fog.use_environment_color as i32,
+        );
+    }
+
+    fn bind_light_info(&mut self, pipeline: &Pipeline, lights: &Lights, max_texture_unit: u8) {
+        // Light properties are uploaded to textures once per frame by `upload_light_data`
+        // and lights are assigned to clusters once per camera.
+        self.render_pass.set_texture_property(
+            &pipeline.get_texture_property("p_light_data").unwrap(),
+            Some(&self.renderer_info.light_data_texture),
+            max_texture_unit + 5,
+        );
+        self.render_pass.set_texture_property(
+            &pipeline.get_texture_property("p_light_clusters").unwrap(),
+            Some(self.light_cluster_texture),
+            max_texture_unit + 6,
+        );
+
+        let light_clusters = &self.renderer_info.light_clusters;
+        self.render_pass.set_vec3_property(
+            &pipeline.get_vec3_property("p_cluster_grid").unwrap(),
+            (
+                light_clusters.grid.tiles_x as f32,
+                light_clusters.grid.tiles_y as f32,
+                light_clusters.grid.depth_slices as f32,
+            ),
+        );
+        self.render_pass.set_float_property(
+            &pipeline.get_float_property("p_cluster_z_near").unwrap(),
+            light_clusters.z_near,
+        );
+        self.render_pass.set_float_property(
+            &pipeline.get_float_property("p_cluster_z_far").unwrap(),
+            light_clusters.z_far,
+        );
+        self.render_pass.set_vec2_property(
+            &pipeline.get_vec2_property("p_cluster_screen_size").unwrap(),
+            self.viewport_size.into(),
         );
 
         self.render_pass.set_vec4_property(
@@ -290,108 +622,75 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
             ),
         );
 
-        for (i, (transform, light, shadow_caster)) in lights.iter().enumerate() {
-            if transform.position.is_nan() {
-                dbg!("Light position is NaN");
-                continue;
-            }
+        // Always bind the point shadow map so it never shares a texture unit with a `sampler2D`.
+        self.render_pass.set_cube_map_property(
+            &pipeline
+                .get_cube_map_property("p_light_point_shadow_map")
+                .unwrap(),
+            Some(self.cube_map_assets.get(&Handle::default())),
+            max_texture_unit + 4,
+        );
 
-            self.render_pass.set_vec3_property(
-                &pipeline
-                    .get_vec3_property(&format!("p_lights[{:?}].position", i))
-                    .unwrap(),
-                (transform.position).into(),
-            );
+        // For now only the first light with a shadow caster casts shadows.
+        let shadow_light = lights
+            .iter()
+            .take(MAX_LIGHTS)
+            .enumerate()
+            .find_map(|(i, (_, _, shadow_caster))| shadow_caster.map(|s| (i, s)));
 
-            self.render_pass.set_vec3_property(
-                &pipeline
-                    .get_vec3_property(&format!("p_lights[{:?}].direction", i))
-                    .unwrap(),
-                (transform.forward()).into(),
-            );
+        self.render_pass.set_int_property(
+            &pipeline.get_int_property("p_shadow_light_index").unwrap(),
+            shadow_light.map_or(-1, |(i, _)| i as i32),
+        );
 
+        // If a light casts shadows, update the shadow caster info.
+        if let Some((_, shadow_caster)) = shadow_light {
             self.render_pass.set_float_property(
-                &pipeline
-                    .get_float_property(&format!("p_lights[{:?}].ambient", i))
-                    .unwrap(),
-                light.ambient_light_amount,
-            );
-
-            // TODO: Make a color property and convert it into the framebuffer's color space first.
-            let color_and_intensity = light
-                .color
-                .to_rgb_color(crate::color_spaces::LINEAR_SRGB)
-                .xyz()
-                * light.intensity;
-
-            self.render_pass.set_vec3_property(
-                &pipeline
-                    .get_vec3_property(&format!("p_lights[{:?}].color_and_intensity", i))
-                    .unwrap(),
-                color_and_intensity.into(),
+                &pipeline.get_float_property("p_shadow_bias").unwrap(),
+                shadow_caster.bias,
             );
-
-            self.render_pass.set_int_property(
+            self.render_pass.set_float_property(
                 &pipeline
-                    .get_int_property(&format!("p_lights[{:?}].mode", i))
+                    .get_float_property("p_shadow_ibl_shadowing")
                     .unwrap(),
-                match light.light_mode {
-                    LightMode::Directional => 0,
-                    LightMode::Point { .. } => 1,
-                },
+                shadow_caster.ibl_shadowing,
             );
 
-            if let LightMode::Point { radius } = light.light_mode {
+            if let Some(point_shadow_map) = shadow_caster.point_shadow_map.as_ref() {
+                self.render_pass.set_cube_map_property(
+                    &pipeline
+                        .get_cube_map_property("p_light_point_shadow_map")
+                        .unwrap(),
+                    Some(self.cube_map_assets.get(&point_shadow_map.cube_map)),
+                    max_texture_unit + 4,
+                );
                 self.render_pass.set_float_property(
                     &pipeline
-                        .get_float_property(&format!("p_lights[{:?}].radius", i))
+                        .get_float_property("p_light_point_shadow_range")
                         .unwrap(),
-                    radius,
+                    shadow_caster.point_shadow_range,
                 );
             }
 
-            // Set if the light has shadows enabled.
-            self.render_pass.set_int_property(
-                &pipeline
-                    .get_int_property(&format!("p_lights[{:?}].shadows_enabled", i))
-                    .unwrap(),
-                if shadow_caster.is_some() { 1 } else { 0 },
-            );
-            self.render_pass.set_float_property(
-                &pipeline
-                    .get_float_property(&format!("p_lights[{:?}].ibl_shadowing", i))
-                    .unwrap(),
-                if let Some(shadow_caster) = shadow_caster {
-                    shadow_caster.ibl_shadowing
-                } else {
-                    0.0
-                },
-            );
+            for (index, cascade) in shadow_caster.shadow_cascades.iter().enumerate() {
+                let depth_texture = self
+                    .texture_assets
+                    .get(cascade.offscreen_render_target.depth_texture());
 
-            // If this light casts shadows, update the shadow caster info.
-            if let Some(shadow_caster) = shadow_caster {
-                let mut texture_unit_offset = max_texture_unit;
-                for (index, cascade) in shadow_caster.shadow_cascades.iter().enumerate() {
-                    let depth_texture = self
-                        .texture_assets
-                        .get(cascade.offscreen_render_target.depth_texture());
-
-                    self.render_pass.set_texture_property(
-                        &pipeline
-                            .get_texture_property(&format!("p_light_shadow_maps_{:?}", index))
-                            .unwrap(),
-                        Some(depth_texture),
-                        texture_unit_offset,
-                    );
-                    texture_unit_offset += 1;
+                self.render_pass.set_texture_property(
+                    &pipeline
+                        .get_texture_property(&format!("p_light_shadow_maps_{:?}", index))
+                        .unwrap(),
+                    Some(depth_texture),
+                    max_texture_unit + index as u8,
+                );
 
-                    self.render_pass.set_mat4_property(
-                        &pipeline
-                            .get_mat4_property(&format!("p_world_to_light_space_{:?}", index))
-                            .unwrap(),
-                        cascade.world_to_light_space.as_array(),
-                    );
-                }
+                self.render_pass.set_mat4_property(
+                    &pipeline
+                        .get_mat4_property(&format!("p_world_to_light_space_{:?}", index))
+                        .unwrap(),
+                    cascade.world_to_light_space.as_array(),
+                );
             }
         }
     }
@@ -424,6 +723,21 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
         material_handle: &'a Handle<Material>,
         lights: &Lights,
         reflection_probes: &Query<(&'static GlobalTransform, &'static ReflectionProbe)>,
+    ) {
+        let variant_key = self
+            .material_assets
+            .get(material_handle)
+            .shader_variant_key();
+        self.change_material_variant(material_handle, variant_key, lights, reflection_probes);
+    }
+
+    /// Binds a [Material] with the variant of its [Shader] for `variant_key`.
+    fn change_material_variant(
+        &mut self,
+        material_handle: &'a Handle<Material>,
+        variant_key: &ShaderVariantKey,
+        lights: &Lights,
+        reflection_probes: &Query<(&'static GlobalTransform, &'static ReflectionProbe)>,
     ) {
         // Avoid unnecessary [Material] rebinds.
         // Todo: For now we rebind the entire material if a color variant has been set. This makes color variants less efficient.
@@ -435,7 +749,10 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
 
             // When a pipeline change occurs a bunch of uniforms need to be rebound.
             let material = self.material_assets.get(material_handle);
-            let shader = self.shader_assets.get(&material.shader);
+            let shader = self
+                .shader_assets
+                .get(&material.shader)
+                .variant(variant_key);
 
             #[cfg(not(feature = "xr"))]
             let pipeline = &shader.pipeline;
@@ -453,7 +770,8 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
             // Avoid unnecessary pipeline / shader changes.
             // For now this is commented out but it could be reintroduced later.
             // This check would prevent pipleline changes if the material is different but the pipeline is the same.
-            if self.bound_shader != Some(&material.shader) {
+            // Variants of the same [Shader] are different pipelines so shaders are compared by address.
+            if !matches!(self.bound_shader, Some(bound) if std::ptr::eq(bound, shader)) {
                 //println!("CHANGE SHADER");
 
                 self.current_pipeline = Some(pipeline);
@@ -473,11 +791,17 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
                 let position_attribute =
                     pipeline.get_vertex_attribute::<Vec3>("a_position").unwrap();
                 let normal_attribute = pipeline.get_vertex_attribute::<Vec3>("a_normal").unwrap();
+                let tangent_attribute = pipeline.get_vertex_attribute::<Vec4>("a_tangent").unwrap();
                 let texture_coordinate_attribute = pipeline
                     .get_vertex_attribute::<Vec2>("a_texture_coordinate")
                     .unwrap();
+                let texture_coordinate_1_attribute = pipeline
+                    .get_vertex_attribute::<Vec2>("a_texture_coordinate_1")
+                    .unwrap();
                 let vertex_color_attribute =
                     pipeline.get_vertex_attribute::<Vec4>("a_color").unwrap();
+                let joints_attribute = pipeline.get_vertex_attribute::<Vec4>("a_joints").unwrap();
+                let weights_attribute = pipeline.get_vertex_attribute::<Vec4>("a_weights").unwrap();
 
                 // Cache properties that may be changed per Sprite.
                 let base_color_texture_property = pipeline
@@ -495,18 +819,24 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
                     .map(|p| p.1);
                 let base_color_property = pipeline.get_vec4_property("p_base_color").unwrap();
 
+                // Shaders that use the standard vertex snippet support instanced draws.
+                let instanced_property = pipeline.get_int_property("p_instanced").unwrap();
+                let instance_model_attribute = pipeline
+                    .get_vertex_attribute::<Mat4>("a_instance_model")
+                    .unwrap();
+                let instance_color_attribute = pipeline
+                    .get_vertex_attribute::<Vec4>("a_instance_color")
+                    .unwrap();
+                self.render_pass.set_int_property(&instanced_property, 0);
+
+                let lod_fade_property = pipeline.get_float_property("p_lod_fade").unwrap();
+                self.render_pass.set_float_property(&lod_fade_property, 0.0);
+
                 // Bind light and shadow info.
                 self.bind_light_info(pipeline, lights, max_texture_unit + 4);
 
-                // Set fog values
-                self.render_pass
-                    .set_float_property(&pipeline.get_float_property("p_fog_start").unwrap(), 20.);
-                self.render_pass
-                    .set_float_property(&pipeline.get_float_property("p_fog_end").unwrap(), 8000.);
-                self.render_pass.set_vec4_property(
-                    &pipeline.get_vec4_property("p_fog_color").unwrap(),
-                    (1.0, 1.0, 1.0, 1.0),
-                );
+                self.bind_fog(pipeline);
+
                 // Bind the reflection probe
                 let (reflection_probe_diffuse, reflection_probe_specular) =
                     if let Some((_, reflection_probe)) = reflection_probes.iter().next() {
@@ -532,6 +862,14 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
                     Some(reflection_probe_specular),
                     max_texture_unit + 2,
                 );
+                // Fog shares the specular irradiance map's texture unit.
+                self.render_pass.set_cube_map_property(
+                    &pipeline
+                        .get_cube_map_property("p_fog_environment_map")
+                        .unwrap(),
+                    Some(reflection_probe_specular),
+                    max_texture_unit + 2,
+                );
 
                 // Bind the brdf lookup table.
 
@@ -543,18 +881,46 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
                     max_texture_unit + 3,
                 );
 
-                self.bound_shader = Some(&material.shader);
+                // Bind the camera's ambient occlusion, or white if it has none.
+                let (ambient_occlusion_texture, ambient_occlusion_scale) =
+                    match self.renderer_info.ambient_occlusion.result() {
+                        Some((texture, scale)) => (texture, *scale),
+                        None => (&Texture::WHITE, Vec2::ZERO),
+                    };
+                self.render_pass.set_texture_property(
+                    &pipeline
+                        .get_texture_property("p_ambient_occlusion_texture")
+                        .unwrap(),
+                    Some(self.texture_assets.get(ambient_occlusion_texture)),
+                    max_texture_unit,
+                );
+                self.render_pass.set_vec2_property(
+                    &pipeline
+                        .get_vec2_property("p_ambient_occlusion_scale")
+                        .unwrap(),
+                    ambient_occlusion_scale.into(),
+                );
+
+                self.bound_shader = Some(shader);
                 self.pipeline_info = Some(PipelineInfo {
                     model_property,
                     position_attribute,
                     normal_attribute,
+                    tangent_attribute,
                     texture_coordinate_attribute,
+                    texture_coordinate_1_attribute,
                     vertex_color_attribute,
+                    joints_attribute,
+                    weights_attribute,
                     base_color_property,
                     base_color_texture_property,
                     texture_coordinate_offset_property,
                     texture_coordinate_scale_property,
                     sprite_texture_unit,
+                    instanced_property,
+                    instance_model_attribute,
+                    instance_color_attribute,
+                    lod_fade_property,
                 });
             }
             self.material_handle = Some(material_handle);
@@ -602,79 +968,174 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
         }
     }
 
-    pub fn render_mesh(&mut self, transform: &Transform, mesh_handle: &'a Handle<Mesh>) {
+    /// Dithers which pixels draw to cross-fade between levels of a [LevelOfDetail].
+    /// A positive `fade` draws that fraction of pixels and a negative `fade` draws the rest.
+    fn set_lod_fade(&mut self, fade: f32) {
+        if let Some(material_info) = &self.pipeline_info {
+            self.render_pass
+                .set_float_property(&material_info.lod_fade_property, fade);
+        }
+    }
+
+    /// Binds a [Mesh]'s vertex attributes if they're not already bound.
+    fn bind_mesh(&mut self, mesh_handle: &'a Handle<Mesh>) -> Option<&'a GPUMesh> {
         // Instead of checking this here there should always be standard material properties, just
         // for a default material.
-        if let Some(material_info) = &self.pipeline_info {
-            let mesh = self.mesh_assets.get(mesh_handle);
-
-            if let Some(gpu_mesh) = &mesh.gpu_mesh {
-                // Only rebind the mesh attributes if the mesh has changed
-                // or if the material has been changed since the mesh was bound.
-                if Some(mesh_handle) != self.bound_mesh || self.just_changed_material {
-                    self.render_pass.set_vertex_attribute(
-                        &material_info.position_attribute,
-                        Some(&gpu_mesh.positions),
-                    );
-                    self.render_pass.set_vertex_attribute(
-                        &material_info.normal_attribute,
-                        gpu_mesh.normals.as_ref(),
-                    );
+        let material_info = self.pipeline_info.as_ref()?;
+        let mesh_assets = self.mesh_assets;
+        let gpu_mesh = mesh_assets.get(mesh_handle).gpu_mesh.as_ref()?;
 
-                    self.render_pass.set_vertex_attribute(
-                        &material_info.texture_coordinate_attribute,
-                        gpu_mesh.texture_coordinates.as_ref(),
-                    );
+        // Only rebind the mesh attributes if the mesh has changed
+        // or if the material has been changed since the mesh was bound.
+        if Some(mesh_handle) != self.bound_mesh || self.just_changed_material {
+            self.render_pass
+                .set_vertex_attribute(&material_info.position_attribute, Some(&gpu_mesh.positions));
+            self.render_pass
+                .set_vertex_attribute(&material_info.normal_attribute, gpu_mesh.normals.as_ref());
 
-                    if let Some(colors) = gpu_mesh.colors.as_ref() {
-                        self.render_pass.set_vertex_attribute(
-                            &material_info.vertex_color_attribute,
-                            Some(colors),
-                        );
-                    } else {
-                        self.render_pass.set_vertex_attribute_to_constant(
-                            &material_info.vertex_color_attribute,
-                            &[1.0, 1.0, 1.0, 1.0],
-                        );
-                    }
+            if let Some(tangents) = gpu_mesh.tangents.as_ref() {
+                self.render_pass
+                    .set_vertex_attribute(&material_info.tangent_attribute, Some(tangents));
+            } else {
+                // A zero tangent tells shaders to derive one from screen-space derivatives.
+                self.render_pass.set_vertex_attribute_to_constant(
+                    &material_info.tangent_attribute,
+                    &[0.0, 0.0, 0.0, 1.0],
+                );
+            }
 
-                    self.bound_mesh = Some(mesh_handle);
-                }
-                let model_matrix = transform.model();
+            self.render_pass.set_vertex_attribute(
+                &material_info.texture_coordinate_attribute,
+                gpu_mesh.texture_coordinates.as_ref(),
+            );
+            self.render_pass.set_vertex_attribute(
+                &material_info.texture_coordinate_1_attribute,
+                gpu_mesh.texture_coordinates_1.as_ref(),
+            );
+
+            if let Some(colors) = gpu_mesh.colors.as_ref() {
                 self.render_pass
-                    .set_mat4_property(&material_info.model_property, model_matrix.as_array());
+                    .set_vertex_attribute(&material_info.vertex_color_attribute, Some(colors));
+            } else {
+                self.render_pass.set_vertex_attribute_to_constant(
+                    &material_info.vertex_color_attribute,
+                    &[1.0, 1.0, 1.0, 1.0],
+                );
+            }
+            self.render_pass
+                .set_vertex_attribute(&material_info.joints_attribute, gpu_mesh.joints.as_ref());
+            self.render_pass
+                .set_vertex_attribute(&material_info.weights_attribute, gpu_mesh.weights.as_ref());
 
-                if self.camera_info.len() == 1 || self.multiview_enabled {
-                    self.render_pass
-                        .draw_triangles(gpu_mesh.triangle_count, &gpu_mesh.index_buffer);
-                } else {
-                    // Render the thing for each view if we're rendering in XR
-                    for camera_info in self.camera_info.iter() {
-                        // This needs to be scaled by the pixels in the view.
-                        let size = camera_info.viewport.size();
-                        self.render_pass.set_viewport(
-                            camera_info.viewport.min.x as u32,
-                            camera_info.viewport.min.y as u32,
-                            size.x as u32,
-                            size.y as u32,
-                        );
-                        self.bind_view(camera_info, 0);
+            self.bound_mesh = Some(mesh_handle);
+        }
+        Some(gpu_mesh)
+    }
 
-                        // Render the mesh
-                        self.render_pass
-                            .draw_triangles(gpu_mesh.triangle_count, &gpu_mesh.index_buffer);
-                    }
-                }
+    fn count_draw_call(&mut self, instances: Option<u32>) {
+        self.statistics.draw_calls += 1;
+        self.statistics.draw_calls_before_batching += instances.unwrap_or(1) as usize;
+    }
+
+    /// Draws the bound [Mesh] once per view, or `instances` times per view if instanced.
+    fn draw_mesh(&mut self, gpu_mesh: &GPUMesh, instances: Option<u32>) {
+        let draw = |render_pass: &mut RenderPass| match instances {
+            Some(instances) => render_pass.draw_triangles_instanced(
+                gpu_mesh.triangle_count,
+                &gpu_mesh.index_buffer,
+                instances,
+            ),
+            None => render_pass.draw_triangles(gpu_mesh.triangle_count, &gpu_mesh.index_buffer),
+        };
+
+        if self.camera_info.len() == 1 || self.multiview_enabled {
+            draw(self.render_pass);
+            self.count_draw_call(instances);
+        } else {
+            // Render the thing for each view if we're rendering in XR
+            for camera_info in self.camera_info.iter() {
+                // This needs to be scaled by the pixels in the view.
+                let size = camera_info.viewport.size();
+                self.render_pass.set_viewport(
+                    camera_info.viewport.min.x as u32,
+                    camera_info.viewport.min.y as u32,
+                    size.x as u32,
+                    size.y as u32,
+                );
+                self.bind_view(camera_info, 0);
+
+                // Render the mesh
+                draw(self.render_pass);
+                self.count_draw_call(instances);
             }
         }
+    }
+
+    pub fn render_mesh(&mut self, transform: &Transform, mesh_handle: &'a Handle<Mesh>) {
+        if let Some(gpu_mesh) = self.bind_mesh(mesh_handle) {
+            let material_info = self.pipeline_info.as_ref().unwrap();
+            let model_matrix = transform.model();
+            self.render_pass
+                .set_mat4_property(&material_info.model_property, model_matrix.as_array());
+
+            self.draw_mesh(gpu_mesh, None);
+        }
         self.just_changed_material = false;
     }
 
+    /// Renders a [Mesh] once per model matrix with a single instanced draw.
+    /// Each instance's color multiplies the material's base color.
+    pub fn render_mesh_instanced(
+        &mut self,
+        graphics_context: &mut GraphicsContext,
+        mesh_handle: &'a Handle<Mesh>,
+        models: &[Mat4],
+        colors: &[Vec4],
+    ) {
+        if let Some(gpu_mesh) = self.bind_mesh(mesh_handle) {
+            let material_info = self.pipeline_info.as_ref().unwrap();
+            let model_buffer = self.instance_model_buffers.next(graphics_context, models);
+            let color_buffer = self.instance_color_buffers.next(graphics_context, colors);
+
+            self.render_pass
+                .set_int_property(&material_info.instanced_property, 1);
+            self.render_pass.set_instance_attribute(
+                &material_info.instance_model_attribute,
+                Some(model_buffer),
+            );
+            self.render_pass.set_instance_attribute(
+                &material_info.instance_color_attribute,
+                Some(color_buffer),
+            );
+
+            self.draw_mesh(gpu_mesh, Some(models.len() as u32));
+
+            // Return to regular draws so later meshes don't read the instance buffers.
+            let material_info = self.pipeline_info.as_ref().unwrap();
+            self.render_pass
+                .set_int_property(&material_info.instanced_property, 0);
+            self.render_pass
+                .set_instance_attribute(&material_info.instance_model_attribute, None);
+            self.render_pass
+                .set_instance_attribute(&material_info.instance_color_attribute, None);
+        }
+        self.just_changed_material = false;
+    }
+
+    /// Whether the bound pipeline can render a [Mesh] with [Renderer::render_mesh_instanced].
+    fn supports_instancing(&self) -> bool {
+        self.pipeline_info
+            .as_ref()
+            .is_some_and(|p| p.instance_model_attribute.exists())
+    }
+
     pub fn render_scene(
         &mut self,
+        graphics_context: &mut GraphicsContext,
         camera: &Camera,
         camera_transform: &GlobalTransform,
         renderables: &'a Renderables,
+        decals: &'a Decals,
         lights: &'a Lights,
         reflection_probes: &Query<(&'static GlobalTransform, &'static ReflectionProbe)>,
     ) {
@@ -701,23 +1162,28 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
         };
 
         // These should *really* be preallocated somehow.
-        let mut transparent_renderables = Vec::new();
-        let mut non_transparent_renderables = Vec::new();
+        let mut transparent_renderables: Vec<RenderItem<'a>> = Vec::new();
+        let mut non_transparent_renderables: Vec<RenderItem<'a>> = Vec::new();
 
         for renderable in renderables.iter() {
-            let (transform, material_handle, mesh_handle, render_flags, _optional_sprite, _color) =
-                renderable;
+            let (
+                transform,
+                material_handle,
+                mesh_handle,
+                render_flags,
+                optional_sprite,
+                color,
+                level_of_detail,
+                _,
+            ) = renderable;
             let render_flags = render_flags.cloned().unwrap_or(RenderFlags::DEFAULT);
 
             if camera.render_flags.includes_layer(render_flags) {
+                let bounding_box = self.mesh_assets.get(mesh_handle).bounding_box;
                 let should_render = render_flags.includes_layer(RenderFlags::IGNORE_CULLING)
-                    || self
-                        .mesh_assets
-                        .get(mesh_handle)
-                        .bounding_box
-                        .map_or(true, |b| {
-                            intersections::frustum_with_bounding_box(&frustum, transform.model(), b)
-                        });
+                    || bounding_box.is_none_or(|b| {
+                        intersections::frustum_with_bounding_box(&frustum, transform.model(), b)
+                    });
 
                 if should_render {
                     let is_transparent = self
@@ -727,42 +1193,120 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
                         .blending()
                         .is_some();
 
-                    if is_transparent {
-                        transparent_renderables.push(renderable);
+                    let render_items = if is_transparent {
+                        &mut transparent_renderables
+                    } else {
+                        &mut non_transparent_renderables
+                    };
+                    let mut push = |mesh_handle: &'a Handle<Mesh>, lod_fade: f32| {
+                        render_items.push((
+                            transform,
+                            material_handle,
+                            mesh_handle,
+                            optional_sprite,
+                            color,
+                            lod_fade,
+                        ))
+                    };
+
+                    if let Some(level_of_detail) = level_of_detail {
+                        // Meshes without bounds always use their most detailed level.
+                        let screen_size = bounding_box.map_or(f32::INFINITY, |b| {
+                            let scale = transform.scale;
+                            let max_scale = scale.x.abs().max(scale.y.abs()).max(scale.z.abs());
+                            LevelOfDetail::screen_size(
+                                &self.camera_info[0].view_matrix,
+                                &self.camera_info[0].projection_matrix,
+                                transform.model().transform_point(b.center()),
+                                b.size().length() * 0.5 * max_scale,
+                            )
+                        });
+
+                        if let Some(selection) = level_of_detail.select(screen_size) {
+                            let mesh_handle = &level_of_detail.levels[selection.level].mesh;
+                            if let Some((fading_level, fade)) = selection.fading_level {
+                                push(&level_of_detail.levels[fading_level].mesh, fade);
+                                push(mesh_handle, -fade);
+                            } else {
+                                push(mesh_handle, 0.0);
+                            }
+                        }
                     } else {
-                        non_transparent_renderables.push(renderable);
+                        push(mesh_handle, 0.0);
                     }
                 }
             }
         }
 
         non_transparent_renderables.sort_by(
-            |(_, material_a, mesh_a, _, _, _), (_, material_b, mesh_b, _, _, _)| {
+            |(_, material_a, mesh_a, _, color_a, _), (_, material_b, mesh_b, _, color_b, _)| {
                 // Sort by material then mesh.
                 // In the future sorting could occur by pipeline as well.
-                let cmp = material_a.cmp(material_b);
-                match cmp {
-                    std::cmp::Ordering::Less | std::cmp::Ordering::Greater => cmp,
-                    _ => mesh_a.cmp(mesh_b),
-                }
+                // Entities with a [Color] are sorted after those without so they batch separately.
+                material_a
+                    .cmp(material_b)
+                    .then_with(|| mesh_a.cmp(mesh_b))
+                    .then_with(|| color_a.is_some().cmp(&color_b.is_some()))
             },
         );
 
-        for renderable in non_transparent_renderables {
-            let (transform, material_handle, mesh_handle, _render_flags, optional_sprite, color) =
-                renderable;
+        let mut models = Vec::new();
+        let mut colors = Vec::new();
+
+        let mut remaining = &non_transparent_renderables[..];
+        while let Some(&render_item) = remaining.first() {
+            let (_, material_handle, mesh_handle, optional_sprite, color, lod_fade) = render_item;
 
             self.change_material(material_handle, lights, reflection_probes);
-            if let Some(sprite) = optional_sprite {
-                self.prepare_sprite(sprite);
-            }
-            if let Some(color) = color {
-                self.set_color(*color);
-            }
 
-            self.render_mesh(transform, mesh_handle);
+            // Sprites and cross-fading levels of detail change per-entity properties
+            // so they aren't batched.
+            let can_batch = |optional_sprite: Option<&Sprite>, lod_fade: f32| {
+                optional_sprite.is_none() && lod_fade == 0.0
+            };
+            let batch_len = if self.batching_enabled
+                && can_batch(optional_sprite, lod_fade)
+                && self.supports_instancing()
+            {
+                remaining
+                    .iter()
+                    .take_while(
+                        |(_, other_material, other_mesh, other_sprite, other_color, other_fade)| {
+                            *other_material == material_handle
+                                && *other_mesh == mesh_handle
+                                && can_batch(*other_sprite, *other_fade)
+                                && other_color.is_some() == color.is_some()
+                        },
+                    )
+                    .count()
+            } else {
+                1
+            };
+
+            if batch_len > 1 {
+                // Entity colors are passed per-instance so the base color is neutral.
+                if color.is_some() {
+                    self.set_color(Color::WHITE);
+                }
+
+                models.clear();
+                colors.clear();
+                for (transform, _, _, _, color, _) in &remaining[..batch_len] {
+                    models.push(transform.model());
+                    colors.push(
+                        color.map_or(Vec4::ONE, |c| c.to_rgb_color(color_spaces::LINEAR_SRGB)),
+                    );
+                }
+                self.render_mesh_instanced(graphics_context, mesh_handle, &models, &colors);
+            } else {
+                self.render_item(render_item);
+            }
+            remaining = &remaining[batch_len..];
         }
 
+        // Decals are drawn onto opaque surfaces, beneath transparent ones.
+        self.render_mesh_decals(camera, &frustum, decals, lights, reflection_probes);
+
         transparent_renderables.sort_by(|(a, ..), (b, ..)| {
             let v0 = (a.position - camera_position).dot(camera_forward);
             let v1 = (b.position - camera_position).dot(camera_forward);
@@ -773,22 +1317,43 @@ impl<'a, 'b: 'a> Renderer<'a, 'b> {
         // This prevents transparent objects from occluding each-other.
         // self.render_pass.set_depth_mask(false);
 
-        for renderable in transparent_renderables.iter() {
-            let (transform, material_handle, mesh_handle, _render_flags, optional_sprite, color) =
-                *renderable;
-            self.change_material(material_handle, lights, reflection_probes);
-            if let Some(sprite) = optional_sprite {
-                self.prepare_sprite(sprite);
-            }
+        for render_item in transparent_renderables.iter() {
+            self.change_material(render_item.1, lights, reflection_probes);
+            self.render_item(*render_item);
+        }
+    }
 
-            if let Some(color) = color {
-                self.set_color(*color);
-            }
+    /// Renders a single [RenderItem] with its per-entity properties.
+    fn render_item(&mut self, render_item: RenderItem<'a>) {
+        let (transform, _, mesh_handle, optional_sprite, color, lod_fade) = render_item;
+        if let Some(sprite) = optional_sprite {
+            self.prepare_sprite(sprite);
+        }
+        if let Some(color) = color {
+            self.set_color(*color);
+        }
+
+        if lod_fade != 0.0 {
+            self.set_lod_fade(lod_fade);
+            self.render_mesh(transform, mesh_handle);
+            self.set_lod_fade(0.0);
+        } else {
             self.render_mesh(transform, mesh_handle);
         }
     }
 }
 
+/// A [Mesh] to draw for a renderable [Entity], after culling and level of detail selection.
+/// The last value is the [LevelOfDetail] cross-fade, or 0.0 if the [Mesh] isn't fading.
+type RenderItem<'a> = (
+    &'a GlobalTransform,
+    &'a Handle<Material>,
+    &'a Handle<Mesh>,
+    Option<&'a Sprite>,
+    Option<&'a Color>,
+    f32,
+);
+
 pub type Renderables<'a> = Query<
     'a,
     (
@@ -799,6 +1364,48 @@ pub type Renderables<'a> = Query<
         Option<&'static RenderFlags>,
         Option<&'static Sprite>,
         Option<&'static Color>,
+        Option<&'static LevelOfDetail>,
+        Entity,
+    ),
+>;
+
+/// The [Mesh] that `level_of_detail` selects, ignoring cross-fades,
+/// or `None` if it's too small on screen to draw.
+pub(crate) fn level_of_detail_mesh<'a>(
+    mesh_handle: &'a Handle<Mesh>,
+    level_of_detail: Option<&'a LevelOfDetail>,
+    global_transform: &GlobalTransform,
+    bounding_box: Option<Box3>,
+    view_matrix: &Mat4,
+    projection_matrix: &Mat4,
+) -> Option<&'a Handle<Mesh>> {
+    let level_of_detail = match level_of_detail {
+        Some(level_of_detail) => level_of_detail,
+        None => return Some(mesh_handle),
+    };
+    let screen_size = bounding_box.map_or(f32::INFINITY, |b| {
+        let scale = global_transform.scale;
+        let max_scale = scale.x.abs().max(scale.y.abs()).max(scale.z.abs());
+        LevelOfDetail::screen_size(
+            view_matrix,
+            projection_matrix,
+            global_transform.model().transform_point(b.center()),
+            b.size().length() * 0.5 * max_scale,
+        )
+    });
+    level_of_detail
+        .select(screen_size)
+        .map(|selection| &level_of_detail.levels[selection.level].mesh)
+}
+
+pub type Cameras<'a> = Query<
+    'a,
+    (
+        &'static GlobalTransform,
+        &'static Camera,
+        Option<&'static PostProcessingSettings>,
+        Option<&'static AmbientOcclusion>,
+        Option<&'static Fog>,
     ),
 >;
 
@@ -811,22 +1418,99 @@ pub type Lights<'a> = Query<
     ),
 >;
 
+pub type ReflectionProbes<'a> = Query<'a, (&'static GlobalTransform, &'static ReflectionProbe)>;
+
+/// The queries [render_scene] draws from, gathered into one system parameter.
+pub struct SceneQueries<'a> {
+    pub cameras: Cameras<'a>,
+    pub renderables: Renderables<'a>,
+    pub decals: Decals<'a>,
+    pub outlines: Outlines<'a>,
+    pub lights: Lights<'a>,
+    pub reflection_probes: ReflectionProbes<'a>,
+}
+
+impl SystemParameterTrait for SceneQueries<'_> {
+    fn get_meta_data(world: &World) -> Result<SystemParameterMetaData, KecsError> {
+        let mut meta_data = SystemParameterMetaData::empty();
+        meta_data.extend(Cameras::get_meta_data(world)?);
+        meta_data.extend(Renderables::get_meta_data(world)?);
+        meta_data.extend(Decals::get_meta_data(world)?);
+        meta_data.extend(Outlines::get_meta_data(world)?);
+        meta_data.extend(Lights::get_meta_data(world)?);
+        meta_data.extend(ReflectionProbes::get_meta_data(world)?);
+        Ok(meta_data)
+    }
+}
+
+pub struct SceneQueriesFetch<'a> {
+    cameras: Option<Cameras<'a>>,
+    renderables: Option<Renderables<'a>>,
+    decals: Option<Decals<'a>>,
+    outlines: Option<Outlines<'a>>,
+    lights: Option<Lights<'a>>,
+    reflection_probes: Option<ReflectionProbes<'a>>,
+}
+
+impl<'a> SystemParameterFetchTrait<'a> for SceneQueries<'_> {
+    type FetchResult = SceneQueriesFetch<'a>;
+
+    fn fetch(
+        world: &'a World,
+        _meta_data: &SystemParameterMetaData,
+        change_ticks: ChangeTicks,
+    ) -> Result<Self::FetchResult, KecsError> {
+        // The combined meta data is only for scheduling,
+        // so each query looks up its own archetypes.
+        fn fetch_query<'a, Q: SystemParameterTrait>(
+            world: &'a World,
+            change_ticks: ChangeTicks,
+        ) -> Result<<Q as SystemParameterFetchTrait<'a>>::FetchResult, KecsError> {
+            Q::fetch(world, &Q::get_meta_data(world)?, change_ticks)
+        }
+
+        Ok(SceneQueriesFetch {
+            cameras: fetch_query::<Cameras>(world, change_ticks)?,
+            renderables: fetch_query::<Renderables>(world, change_ticks)?,
+            decals: fetch_query::<Decals>(world, change_ticks)?,
+            outlines: fetch_query::<Outlines>(world, change_ticks)?,
+            lights: fetch_query::<Lights>(world, change_ticks)?,
+            reflection_probes: fetch_query::<ReflectionProbes>(world, change_ticks)?,
+        })
+    }
+}
+
+impl<'a, 'b> AsSystemArg<'b> for SceneQueriesFetch<'a> {
+    type Arg = SceneQueries<'a>;
+    fn as_system_arg(&'b mut self) -> Self::Arg {
+        SceneQueries {
+            cameras: self.cameras.as_system_arg(),
+            renderables: self.renderables.as_system_arg(),
+            decals: self.decals.as_system_arg(),
+            outlines: self.outlines.as_system_arg(),
+            lights: self.lights.as_system_arg(),
+            reflection_probes: self.reflection_probes.as_system_arg(),
+        }
+    }
+}
+
 pub fn prepare_shadow_casters(
     graphics: &mut Graphics,
     textures: &mut Assets<Texture>,
-    mut shadow_casters: Query<&mut ShadowCaster>,
+    cube_maps: &mut Assets<CubeMap>,
+    mut shadow_casters: Query<(&Light, &mut ShadowCaster)>,
 ) {
-    for shadow_caster in &mut shadow_casters {
-        shadow_caster.prepare_shadow_casting(graphics, textures);
+    for (light, shadow_caster) in &mut shadow_casters {
+        shadow_caster.prepare_shadow_casting(graphics, textures, cube_maps, &light.light_mode);
     }
 }
 
 pub fn render_other_world(main_world: &mut World, other_world: &mut World) {
     {
-        let commands_entity = other_world.spawn(Commands::new());
+        let commands_entity = other_world.spawn(CommandQueue::new());
         update_root_global_transforms.run(other_world);
         let mut commands = other_world
-            .remove_component::<Commands>(commands_entity)
+            .remove_component::<CommandQueue>(commands_entity)
             .unwrap();
         commands.apply(other_world);
         commands.clear();
@@ -834,20 +1518,30 @@ pub fn render_other_world(main_world: &mut World, other_world: &mut World) {
         let commands_entity = other_world.spawn(commands);
         update_global_transforms.run(other_world);
         let mut commands = other_world
-            .remove_component::<Commands>(commands_entity)
+            .remove_component::<CommandQueue>(commands_entity)
             .unwrap();
         commands.apply(other_world);
         commands.clear();
     }
 
-    (|graphics: &mut Graphics, textures: &mut Assets<Texture>| {
-        (|shadow_casters: Query<&mut ShadowCaster>| {
-            prepare_shadow_casters(graphics, textures, shadow_casters)
+    (|graphics: &mut Graphics, textures: &mut Assets<Texture>, cube_maps: &mut Assets<CubeMap>| {
+        (|shadow_casters: Query<(&Light, &mut ShadowCaster)>| {
+            prepare_shadow_casters(graphics, textures, cube_maps, shadow_casters)
         })
         .run(other_world);
     })
     .run(main_world);
 
+    (|renderer_info: &mut RendererInfo| {
+        (|particle_emitters: Query<(&ParticleEmitter, Option<&RenderFlags>)>| {
+            prepare_particles(renderer_info, particle_emitters)
+        })
+        .run(other_world);
+        (|sprites: Sprites| prepare_sprites(renderer_info, sprites)).run(other_world);
+    })
+    .run(main_world);
+
+    // The assets and renderer live in the main world, while the scene is in `other_world`.
     (|graphics: &mut Graphics,
       shader_assets: &Assets<Shader>,
       material_assets: &Assets<Material>,
@@ -856,33 +1550,63 @@ pub fn render_other_world(main_world: &mut World, other_world: &mut World) {
       offscreen_render_targets: &mut Assets<OffscreenRenderTarget>,
       cube_map_assets: &Assets<CubeMap>,
       renderer_info: &mut RendererInfo| {
-        (|cameras: Query<(&GlobalTransform, &Camera)>,
-          renderables: Renderables,
-          lights: Lights,
-          reflection_probes: Query<(&'static GlobalTransform, &'static ReflectionProbe)>| {
-              render_scene(
-                  graphics,
-                  shader_assets,
-                  material_assets,
-                  mesh_assets,
-                  texture_assets,
-                  cube_map_assets,
-                  renderer_info,
-                  cameras,
-                  renderables,
-                  lights,
-                  reflection_probes,
-                  offscreen_render_targets
-              )
-          }).run(other_world);
+        (|scene: SceneQueries| {
+            render_scene(
+                graphics,
+                shader_assets,
+                material_assets,
+                mesh_assets,
+                texture_assets,
+                cube_map_assets,
+                renderer_info,
+                scene,
+                offscreen_render_targets,
+            )
+        })
+        .run(other_world);
     })
     .run(main_world)
 }
 
-// In the future there should be a way to use structs
-// as arguments to systems to avoid this mess of queries.
+/// Renders the main [World]'s [Camera]s.
+pub fn render_main_world(world: &mut World) {
+    render_scene.run(world)
+}
+
+/// The built-in [RenderGraph] resources available to custom passes so far in the current view.
+fn built_in_graph_targets<'a>(
+    renderer_info: &'a RendererInfo,
+    camera: &Camera,
+    scene_render_target: &'a OffscreenRenderTarget,
+    anti_aliased: bool,
+    camera_target: Option<&'a OffscreenRenderTarget>,
+) -> Vec<(&'static str, &'a OffscreenRenderTarget)> {
+    let mut targets = Vec::new();
+    if let Some(ambient_occlusion) = renderer_info.ambient_occlusion.target() {
+        targets.push((RenderGraph::AMBIENT_OCCLUSION, ambient_occlusion));
+    }
+    if camera.post_processing_enabled {
+        targets.push((RenderGraph::SCENE, scene_render_target));
+        if anti_aliased {
+            let anti_aliased_scene = renderer_info
+                .anti_aliasing
+                .result()
+                .unwrap_or(scene_render_target);
+            targets.push((RenderGraph::ANTI_ALIASED_SCENE, anti_aliased_scene));
+        }
+    }
+    if let Some(bloom) = renderer_info.blur_calculator.result() {
+        targets.push((RenderGraph::BLOOM, bloom));
+    }
+    if let Some(camera_target) = camera_target {
+        targets.push((RenderGraph::CAMERA_TARGET, camera_target));
+    }
+    targets
+}
+
 #[allow(clippy::too_many_arguments)]
-pub fn render_scene<'a, 'b>(
+#[cfg_attr(feature = "headless", allow(unused_variables))]
+pub fn render_scene(
     graphics: &mut Graphics,
     shader_assets: &Assets<Shader>,
     material_assets: &Assets<Material>,
@@ -890,241 +1614,690 @@ pub fn render_scene<'a, 'b>(
     texture_assets: &mut Assets<Texture>,
     cube_map_assets: &Assets<CubeMap>,
     renderer_info: &mut RendererInfo,
-    cameras: Query<(&GlobalTransform, &Camera)>,
-    renderables: Renderables<'a>,
-    mut lights: Lights<'b>,
-    reflection_probes: Query<(&'static GlobalTransform, &'static ReflectionProbe)>,
+    scene: SceneQueries,
     offscreen_render_targets: &Assets<OffscreenRenderTarget>,
 ) {
-    #[cfg(feature = "headless")]
-    return;
-
-    let mut command_buffer = graphics.context.new_command_buffer();
-
-    let mut cameras: Vec<(&GlobalTransform, &Camera)> = cameras.iter().collect();
-    cameras.sort_by_key(|v| v.1.render_flags);
-
-    for (camera_global_transform, camera) in &cameras {
-        if !camera.enabled {
-            continue;
-        }
+    // The headless backend has nothing to render to.
+    #[cfg(not(feature = "headless"))]
+    {
+        let SceneQueries {
+            cameras,
+            renderables,
+            decals,
+            outlines,
+            mut lights,
+            reflection_probes,
+        } = scene;
+
+        let mut command_buffer = graphics.context.new_command_buffer();
+
+        let mut cameras: Vec<_> = cameras.iter().collect();
+        cameras.sort_by_key(|v| v.1.render_flags);
+
+        let cluster_lights = upload_light_data(graphics, renderer_info, &lights);
+
+        renderer_info.statistics = RenderStatistics::default();
+        // The instance buffers are lent to each [Renderer], which only borrows `renderer_info`.
+        let mut instance_model_buffers = std::mem::take(&mut renderer_info.instance_model_buffers);
+        let mut instance_color_buffers = std::mem::take(&mut renderer_info.instance_color_buffers);
+        instance_model_buffers.reset();
+        instance_color_buffers.reset();
+        let mut sprite_buffers = std::mem::take(&mut renderer_info.sprite_buffers);
+        sprite_buffers.reset(&mut graphics.context, renderer_info.sprites.quad_count());
+        let mut particle_buffers = Vec::new();
+        renderer_info.frame = renderer_info.frame.wrapping_add(1);
+        // The render graph is taken out of `renderer_info` so that its passes
+        // can borrow the renderer's targets.
+        let mut render_graph = std::mem::take(&mut renderer_info.render_graph);
+
+        for (
+            camera_index,
+            (camera_global_transform, camera, post_processing_settings, ambient_occlusion, fog),
+        ) in cameras.iter().enumerate()
+        {
+            if !camera.enabled {
+                continue;
+            }
 
-        // Render shadows if this camera renders the default scene.
-        if camera.render_flags.includes_layer(RenderFlags::DEFAULT) {
-            render_shadow_pass(
-                shader_assets,
-                mesh_assets,
-                &mut command_buffer,
-                camera,
-                camera_global_transform,
-                &mut lights,
-                &renderables,
-                &renderer_info.cascade_depths,
+            // Assign lights to clusters of this camera's view.
+            renderer_info.light_clusters.assign_lights(
+                &camera_global_transform.model().inversed(),
+                &camera.projection_matrix(),
+                camera.get_near_plane(),
+                camera.get_far_plane(),
+                &cluster_lights,
+            );
+            if renderer_info.light_cluster_textures.len() <= camera_index {
+                renderer_info.light_cluster_textures.push(
+                    graphics
+                        .new_texture(
+                            None,
+                            LIGHT_CLUSTER_TEXTURE_WIDTH as u32,
+                            LIGHT_CLUSTER_TEXTURE_HEIGHT as u32,
+                            1,
+                            PixelFormat::RGBA32F,
+                            light_data_texture_settings(),
+                        )
+                        .unwrap(),
+                );
+            }
+            let cluster_data = renderer_info
+                .light_clusters
+                .texture_data(LIGHT_CLUSTER_TEXTURE_WIDTH);
+            let cluster_rows = (cluster_data.len() / (LIGHT_CLUSTER_TEXTURE_WIDTH * 4))
+                .min(LIGHT_CLUSTER_TEXTURE_HEIGHT);
+            graphics.context.update_texture(
+                &renderer_info.light_cluster_textures[camera_index],
+                0,
+                0,
+                0,
+                LIGHT_CLUSTER_TEXTURE_WIDTH as u32,
+                cluster_rows as u32,
+                1,
+                Some(bytemuck::cast_slice(
+                    &cluster_data[..cluster_rows * LIGHT_CLUSTER_TEXTURE_WIDTH * 4],
+                )),
+                PixelFormat::RGBA32F,
+                light_data_texture_settings(),
             );
-        }
-
-        let clear_color = camera.clear_color;
-
-        // Check that this camera targets the target currently being rendered.
-        let clear_color = clear_color.map(|c| {
-            // Presently the output needs to be in non-linear sRGB.
-            // However that means that blending with the clear-color will be incorrect.
-            // A post-processing pass is needed to convert into the appropriate output space.
-            let c = c.to_rgb_color(color_spaces::LINEAR_SRGB);
-            c.into()
-        });
-
-        let initial_framebuffer = if camera.post_processing_enabled {
-            renderer_info.offscreen_render_target.framebuffer()
-        } else if let Some(CameraTarget::OffscreenRenderTarget(c)) = &camera.camera_target {
-            offscreen_render_targets.get(c).framebuffer()
-        } else {
-            &graphics.current_target_framebuffer
-        };
-
-        let mut view_size = camera.get_view_size();
-        view_size.0 = (view_size.0 as f32 / camera.resolution_scale) as u32;
-        view_size.1 = (view_size.1 as f32 / camera.resolution_scale) as u32;
 
-        let resolution_scale = camera.resolution_scale;
+            let clear_color = camera.clear_color;
+
+            // Check that this camera targets the target currently being rendered.
+            let clear_color = clear_color.map(|c| {
+                // Presently the output needs to be in non-linear sRGB.
+                // However that means that blending with the clear-color will be incorrect.
+                // A post-processing pass is needed to convert into the appropriate output space.
+                let c = c.to_rgb_color(color_spaces::LINEAR_SRGB);
+                c.into()
+            });
+
+            let mut view_size = camera.get_view_size();
+            view_size.0 = (view_size.0 as f32 / camera.resolution_scale) as u32;
+            view_size.1 = (view_size.1 as f32 / camera.resolution_scale) as u32;
+            let graph_view_size = Vec2u::new(view_size.0 as usize, view_size.1 as usize);
+            let camera_target = match &camera.camera_target {
+                Some(CameraTarget::OffscreenRenderTarget(c)) => {
+                    Some(offscreen_render_targets.get(c))
+                }
+                _ => None,
+            };
 
-        let mut render_pass =
-            command_buffer.begin_render_pass_with_framebuffer(initial_framebuffer, clear_color);
+            // Temporal anti-aliasing renders each frame from a slightly different sub-pixel position.
+            let projection_matrix = match camera.anti_aliasing {
+                AntiAliasing::Temporal { .. } if camera.post_processing_enabled => {
+                    camera.jittered_projection_matrix(temporal_jitter(renderer_info.frame))
+                }
+                _ => camera.projection_matrix(),
+            };
 
-        let camera_info = vec![Renderer::get_view_info(
-            camera_global_transform,
-            Mat4::IDENTITY,
-            camera.projection_matrix(),
-            Box2 {
-                min: Vec2::ZERO,
-                max: Vec2::ONE,
-            },
-        )];
-        /* if graphics.override_views.is_empty() {
-            camera_info.push(Renderer::get_view_info(
-                camera_global_transform,
-                Mat4::IDENTITY,
-                camera.projection_matrix(),
-                Box2 {
-                    min: Vec2::ZERO,
-                    max: Vec2::ONE,
-                },
-            ));
-        } else {
-            for view in &graphics.override_views {
-                camera_info.push(Renderer::get_view_info(
-                    camera_global_transform,
-                    view.offset_transform,
-                    view.projection_matrix,
-                    view.output_rectangle,
-                ))
+            // Only MSAA needs the multisampled scene target, which is expensive on WebGL.
+            let multisampled = camera.anti_aliasing == AntiAliasing::Msaa;
+            if camera.post_processing_enabled {
+                let scene_render_target = if multisampled {
+                    &mut renderer_info.offscreen_render_target
+                } else {
+                    &mut renderer_info.single_sample_render_target
+                };
+                scene_render_target.resize(
+                    graphics,
+                    texture_assets,
+                    Vec2u::new(view_size.0 as usize, view_size.1 as usize),
+                );
             }
-        }*/
-
-        /*
-        #[cfg(not(feature = "xr"))]
-        let multiview_enabled = false;
-        #[cfg(feature = "xr")]
-        let multiview_enabled = camera_info.len() > 1;
-        */
-        let multiview_enabled = false;
-
-        let mut renderer = Renderer::new(
-            renderer_info,
-            &mut render_pass,
-            shader_assets,
-            material_assets,
-            mesh_assets,
-            texture_assets,
-            cube_map_assets,
-            &camera_info,
-            kmath::geometry::BoundingBox::<u32, 2> {
-                min: Vector::ZERO,
-                max: Vector::<u32, 2>::new(view_size.0, view_size.1),
-            },
-            multiview_enabled,
-        );
+            let scene_render_target = if multisampled {
+                &renderer_info.offscreen_render_target
+            } else {
+                &renderer_info.single_sample_render_target
+            };
 
-        renderer.render_scene(
-            camera,
-            camera_global_transform,
-            &renderables,
-            &lights,
-            &reflection_probes,
-        );
+            // Results from the previous view shouldn't be used by this one.
+            renderer_info.ambient_occlusion.clear();
+            renderer_info.anti_aliasing.clear();
+            renderer_info.blur_calculator.clear();
+            let mut anti_aliased = false;
+
+            // Passes run in the order the render graph puts them in.
+            render_graph.begin_view(graphics, texture_assets, graph_view_size);
+            while let Some(pass) = render_graph.next_pass() {
+                match pass {
+                    ViewPass::Custom(index) => {
+                        let targets = built_in_graph_targets(
+                            renderer_info,
+                            camera,
+                            scene_render_target,
+                            anti_aliased,
+                            camera_target,
+                        );
+                        render_graph.run_custom_pass(
+                            index,
+                            &mut RenderGraphContext::new(
+                                graphics,
+                                &mut command_buffer,
+                                texture_assets,
+                                shader_assets,
+                                mesh_assets,
+                                camera,
+                                camera_global_transform,
+                                graph_view_size,
+                                camera_target,
+                                targets,
+                            ),
+                        );
+                    }
+                    ViewPass::BuiltIn(BuiltInPass::Shadows) => {
+                        // Render shadows if this camera renders the default scene.
+                        if camera.render_flags.includes_layer(RenderFlags::DEFAULT) {
+                            render_shadow_pass(
+                                shader_assets,
+                                mesh_assets,
+                                &mut command_buffer,
+                                camera,
+                                camera_global_transform,
+                                &mut lights,
+                                &renderables,
+                                &renderer_info.cascade_depths,
+                            );
+                        }
+                    }
+                    ViewPass::BuiltIn(BuiltInPass::AmbientOcclusion) => {
+                        // Ambient occlusion needs the scene's depth before the scene is shaded.
+                        if let Some(ambient_occlusion) = ambient_occlusion {
+                            if ambient_occlusion.intensity > 0.0 {
+                                renderer_info.ambient_occlusion.render(
+                                    graphics,
+                                    texture_assets,
+                                    shader_assets,
+                                    material_assets,
+                                    mesh_assets,
+                                    &mut command_buffer,
+                                    camera,
+                                    camera_global_transform,
+                                    &renderables,
+                                    ambient_occlusion,
+                                    Vec2u::new(view_size.0 as usize, view_size.1 as usize),
+                                );
+                            }
+                        }
+                    }
+                    ViewPass::BuiltIn(BuiltInPass::Scene) => {
+                        // Projected decals need the scene's depth,
+                        // which only post-processed cameras keep.
+                        let projected_decals = if camera.post_processing_enabled {
+                            visible_projected_decals(
+                                camera,
+                                &camera_global_transform.model().inversed(),
+                                &projection_matrix,
+                                &decals,
+                            )
+                        } else {
+                            Vec::new()
+                        };
+                        renderer_info.decal_renderer.prepare(
+                            graphics,
+                            texture_assets,
+                            shader_assets,
+                            material_assets,
+                            mesh_assets,
+                            &mut command_buffer,
+                            camera,
+                            &camera_global_transform.model().inversed(),
+                            &projection_matrix,
+                            &renderables,
+                            &projected_decals,
+                            Vec2u::new(view_size.0 as usize, view_size.1 as usize),
+                        );
 
-        if camera.post_processing_enabled {
-            renderer_info
-                .offscreen_render_target
-                .resize(graphics, texture_assets, {
-                    Vec2u::new(view_size.0 as usize, view_size.1 as usize)
-                });
+                        let initial_framebuffer = if camera.post_processing_enabled {
+                            scene_render_target.framebuffer()
+                        } else if let Some(camera_target) = camera_target {
+                            camera_target.framebuffer()
+                        } else {
+                            &graphics.current_target_framebuffer
+                        };
+
+                        let mut render_pass = command_buffer
+                            .begin_render_pass_with_framebuffer(initial_framebuffer, clear_color);
+
+                        let camera_info = vec![Renderer::get_view_info(
+                            camera_global_transform,
+                            Mat4::IDENTITY,
+                            projection_matrix,
+                            Box2 {
+                                min: Vec2::ZERO,
+                                max: Vec2::ONE,
+                            },
+                        )];
+                        /* if graphics.override_views.is_empty() {
+                            camera_info.push(Renderer::get_view_info(
+                                camera_global_transform,
+                                Mat4::IDENTITY,
+                                camera.projection_matrix(),
+                                Box2 {
+                                    min: Vec2::ZERO,
+                                    max: Vec2::ONE,
+                                },
+                            ));
+                        } else {
+                            for view in &graphics.override_views {
+                                camera_info.push(Renderer::get_view_info(
+                                    camera_global_transform,
+                                    view.offset_transform,
+                                    view.projection_matrix,
+                                    view.output_rectangle,
+                                ))
+                            }
+                        }*/
+
+                        /*
+                        #[cfg(not(feature = "xr"))]
+                        let multiview_enabled = false;
+                        #[cfg(feature = "xr")]
+                        let multiview_enabled = camera_info.len() > 1;
+                        */
+                        let multiview_enabled = false;
+
+                        let mut renderer = Renderer::new(
+                            renderer_info,
+                            &mut render_pass,
+                            shader_assets,
+                            material_assets,
+                            mesh_assets,
+                            texture_assets,
+                            cube_map_assets,
+                            &camera_info,
+                            kmath::geometry::BoundingBox::<u32, 2> {
+                                min: Vector::ZERO,
+                                max: Vector::<u32, 2>::new(view_size.0, view_size.1),
+                            },
+                            multiview_enabled,
+                            &renderer_info.light_cluster_textures[camera_index],
+                            *fog,
+                        );
+                        renderer.instance_model_buffers =
+                            std::mem::take(&mut instance_model_buffers);
+                        renderer.instance_color_buffers =
+                            std::mem::take(&mut instance_color_buffers);
+
+                        renderer.render_scene(
+                            &mut graphics.context,
+                            camera,
+                            camera_global_transform,
+                            &renderables,
+                            &decals,
+                            &lights,
+                            &reflection_probes,
+                        );
+                        let statistics = renderer.statistics;
+                        instance_model_buffers =
+                            std::mem::take(&mut renderer.instance_model_buffers);
+                        instance_color_buffers =
+                            std::mem::take(&mut renderer.instance_color_buffers);
+                        renderer_info.statistics += statistics;
+
+                        // Sprites draw over the scene's meshes in their own sorted order.
+                        let sprite_statistics = render_sprites(
+                            &mut graphics.context,
+                            &mut render_pass,
+                            shader_assets.get(&Shader::SPRITE),
+                            texture_assets,
+                            camera,
+                            &camera_info,
+                            multiview_enabled,
+                            &renderer_info.sprites,
+                            &mut sprite_buffers,
+                        );
+                        renderer_info.statistics += sprite_statistics;
+
+                        let particle_shader = shader_assets.get(&Shader::PARTICLE);
+                        if !camera.post_processing_enabled {
+                            render_particles(
+                                &mut graphics.context,
+                                &mut render_pass,
+                                particle_shader,
+                                mesh_assets,
+                                texture_assets,
+                                camera,
+                                &camera_info[0],
+                                &renderer_info.particle_batches,
+                                None,
+                                &mut particle_buffers,
+                            );
+
+                            // Resolve into textures
+                            if let Some(camera_target) = camera_target {
+                                camera_target.resolve(render_pass)
+                            }
+                            continue;
+                        }
+
+                        scene_render_target.resolve(render_pass);
+
+                        if !projected_decals.is_empty() {
+                            let mut render_pass = command_buffer
+                                .begin_render_pass_with_framebuffer(
+                                    scene_render_target.color_only_framebuffer(),
+                                    None,
+                                );
+                            let mut renderer = Renderer::new(
+                                renderer_info,
+                                &mut render_pass,
+                                shader_assets,
+                                material_assets,
+                                mesh_assets,
+                                texture_assets,
+                                cube_map_assets,
+                                &camera_info,
+                                kmath::geometry::BoundingBox::<u32, 2> {
+                                    min: Vector::ZERO,
+                                    max: Vector::<u32, 2>::new(view_size.0, view_size.1),
+                                },
+                                multiview_enabled,
+                                &renderer_info.light_cluster_textures[camera_index],
+                                *fog,
+                            );
+                            renderer.render_projected_decals(
+                                &projected_decals,
+                                texture_assets.get(scene_render_target.depth_texture()),
+                                &lights,
+                                &reflection_probes,
+                            );
+                            let statistics = renderer.statistics;
+                            renderer_info.statistics += statistics;
+                        }
+
+                        // Particles draw over the resolved scene so that they can sample its depth.
+                        if !renderer_info.particle_batches.is_empty() {
+                            let mut render_pass = command_buffer
+                                .begin_render_pass_with_framebuffer(
+                                    scene_render_target.color_only_framebuffer(),
+                                    None,
+                                );
+                            render_pass.set_viewport(0, 0, view_size.0, view_size.1);
+                            render_particles(
+                                &mut graphics.context,
+                                &mut render_pass,
+                                particle_shader,
+                                mesh_assets,
+                                texture_assets,
+                                camera,
+                                &camera_info[0],
+                                &renderer_info.particle_batches,
+                                Some(texture_assets.get(scene_render_target.depth_texture())),
+                                &mut particle_buffers,
+                            );
+                        }
+
+                        // Outlines draw over everything else in the scene, including particles.
+                        renderer_info.outline_renderer.render(
+                            graphics,
+                            texture_assets,
+                            mesh_assets,
+                            &mut command_buffer,
+                            camera,
+                            &camera_global_transform.model().inversed(),
+                            &projection_matrix,
+                            &outlines,
+                            scene_render_target,
+                        );
+                    }
+                    ViewPass::BuiltIn(BuiltInPass::AntiAliasing) => {
+                        if !camera.post_processing_enabled {
+                            continue;
+                        }
+                        match camera.anti_aliasing {
+                            AntiAliasing::Fxaa => {
+                                renderer_info.anti_aliasing.fxaa(
+                                    graphics,
+                                    texture_assets,
+                                    &mut command_buffer,
+                                    scene_render_target,
+                                );
+                            }
+                            AntiAliasing::Temporal { sharpness } => {
+                                renderer_info.anti_aliasing.temporal(
+                                    graphics,
+                                    texture_assets,
+                                    shader_assets,
+                                    material_assets,
+                                    mesh_assets,
+                                    &mut command_buffer,
+                                    camera_index,
+                                    camera,
+                                    camera_global_transform,
+                                    &renderables,
+                                    scene_render_target,
+                                    sharpness,
+                                );
+                            }
+                            AntiAliasing::None | AntiAliasing::Msaa => {}
+                        }
+                        anti_aliased = true;
+                    }
+                    ViewPass::BuiltIn(BuiltInPass::Bloom) => {
+                        if camera.post_processing_enabled && renderer_info.bloom_enabled {
+                            renderer_info.blur_calculator.blur_texture(
+                                graphics,
+                                texture_assets,
+                                &mut command_buffer,
+                                scene_render_target.color_texture(),
+                                Vec2u::new(view_size.0 as _, view_size.1 as _),
+                            );
+                        }
+                    }
+                    ViewPass::BuiltIn(BuiltInPass::PostProcess) => {
+                        if !camera.post_processing_enabled {
+                            continue;
+                        }
+
+                        // Anti-aliasing and bloom may have been removed from the graph.
+                        let anti_aliased_render_target = renderer_info
+                            .anti_aliasing
+                            .result()
+                            .unwrap_or(scene_render_target);
+                        let bloom = renderer_info.blur_calculator.result();
+                        let mut blurred_texture = &Texture::WHITE;
+                        if let Some(bloom) = bloom {
+                            blurred_texture = bloom.color_texture();
+                        }
+
+                        let default_post_processing_settings = PostProcessingSettings::default();
+                        let post_processing_settings =
+                            post_processing_settings.unwrap_or(&default_post_processing_settings);
+                        let texture_scale = scene_render_target.inner_texture_scale();
+
+                        let (exposure, exposure_texture) = match post_processing_settings.exposure {
+                            Exposure::Manual { stops } => (stops.exp2(), None),
+                            Exposure::Automatic {
+                                compensation,
+                                min_luminance_log2,
+                                max_luminance_log2,
+                                adaptation_speed,
+                            } => (
+                                compensation.exp2(),
+                                Some(renderer_info.exposure_calculator.adapt(
+                                    texture_assets,
+                                    &mut command_buffer,
+                                    scene_render_target.color_texture(),
+                                    texture_scale,
+                                    min_luminance_log2,
+                                    max_luminance_log2,
+                                    adaptation_speed,
+                                )),
+                            ),
+                        };
+                        let lut_texture = post_processing_settings
+                            .color_grading_lut
+                            .as_ref()
+                            .and_then(|lut| texture_assets.get_if_loaded(lut));
+
+                        let final_framebuffer = match camera_target {
+                            Some(camera_target) => camera_target.framebuffer(),
+                            None => &graphics.current_target_framebuffer,
+                        };
+
+                        // Draw to the screen
+                        let mut render_pass = command_buffer
+                            .begin_render_pass_with_framebuffer(final_framebuffer, clear_color);
+
+                        let shader = &renderer_info.final_postprocess_shader;
+                        let texture = anti_aliased_render_target.color_texture();
+                        let pipeline = &shader.pipeline;
+                        let resolution_scale = camera.resolution_scale;
+                        let output_viewport = Box2::new(
+                            Vec2::ZERO,
+                            Vec2::new(
+                                view_size.0 as f32 * resolution_scale,
+                                view_size.1 as f32 * resolution_scale,
+                            ),
+                        );
+                        let min = output_viewport.min.as_u32();
+                        let size = output_viewport.size().as_u32();
 
-            renderer_info.offscreen_render_target.resolve(render_pass);
+                        render_pass.set_pipeline(&shader.pipeline);
+                        render_pass.set_viewport(min.x, min.y, size.x, size.y);
 
-            // Bloom, linear -> sRGB, and Dither
-            let mut blurred_texture = &Texture::WHITE;
-            if renderer_info.bloom_enabled {
-                blurred_texture = renderer_info.blur_calculator.blur_texture(
-                    graphics,
-                    texture_assets,
-                    &mut command_buffer,
-                    renderer_info.offscreen_render_target.color_texture(),
-                    Vec2u::new(view_size.0 as _, view_size.1 as _),
-                )
-            };
+                        render_pass.set_texture_property(
+                            &shader.pipeline.get_texture_property("p_texture").unwrap(),
+                            Some(texture_assets.get(texture)),
+                            0,
+                        );
+                        render_pass.set_texture_property(
+                            &shader
+                                .pipeline
+                                .get_texture_property("p_blurred_texture")
+                                .unwrap(),
+                            Some(texture_assets.get(blurred_texture)),
+                            1,
+                        );
+                        render_pass.set_vec2_property(
+                            &shader
+                                .pipeline
+                                .get_vec2_property("p_texture_coordinate_scale")
+                                .unwrap(),
+                            texture_scale.into(),
+                        );
+                        render_pass.set_vec2_property(
+                            &pipeline.get_vec2_property("p_scene_texture_scale").unwrap(),
+                            anti_aliased_render_target.inner_texture_scale().into(),
+                        );
+                        render_pass.set_float_property(
+                            &shader
+                                .pipeline
+                                .get_float_property("p_bloom_strength")
+                                .unwrap(),
+                            if bloom.is_some() {
+                                renderer_info.bloom_strength
+                            } else {
+                                0.0
+                            },
+                        );
 
-            let final_framebuffer =
-                if let Some(CameraTarget::OffscreenRenderTarget(c)) = &camera.camera_target {
-                    offscreen_render_targets.get(c).framebuffer()
-                } else {
-                    &graphics.current_target_framebuffer
-                };
+                        render_pass.set_float_property(
+                            &pipeline.get_float_property("p_exposure").unwrap(),
+                            exposure,
+                        );
+                        render_pass.set_int_property(
+                            &pipeline.get_int_property("p_automatic_exposure").unwrap(),
+                            exposure_texture.is_some() as i32,
+                        );
+                        render_pass.set_texture_property(
+                            &pipeline.get_texture_property("p_exposure_texture").unwrap(),
+                            Some(texture_assets.get(exposure_texture.unwrap_or(&Texture::BLACK))),
+                            2,
+                        );
+                        render_pass.set_vec3_property(
+                            &pipeline.get_vec3_property("p_white_balance").unwrap(),
+                            post_processing_settings.white_balance_scale().into(),
+                        );
+                        render_pass.set_int_property(
+                            &pipeline.get_int_property("p_tonemapping").unwrap(),
+                            post_processing_settings.tonemapping as i32,
+                        );
+                        render_pass.set_int_property(
+                            &pipeline.get_int_property("p_use_lut").unwrap(),
+                            lut_texture.is_some() as i32,
+                        );
+                        render_pass.set_texture_property(
+                            &pipeline.get_texture_property("p_lut").unwrap(),
+                            Some(
+                                lut_texture.unwrap_or_else(|| texture_assets.get(&Texture::WHITE)),
+                            ),
+                            3,
+                        );
+                        render_pass.set_vec2_property(
+                            &pipeline.get_vec2_property("p_viewport_size").unwrap(),
+                            size.as_f32().into(),
+                        );
+                        render_pass.set_float_property(
+                            &pipeline.get_float_property("p_vignette_intensity").unwrap(),
+                            post_processing_settings.vignette_intensity,
+                        );
+                        render_pass.set_float_property(
+                            &pipeline
+                                .get_float_property("p_vignette_smoothness")
+                                .unwrap(),
+                            // A smoothness of 0.0 would make the vignette's edges undefined.
+                            post_processing_settings.vignette_smoothness.max(0.001),
+                        );
+                        render_pass.set_float_property(
+                            &pipeline.get_float_property("p_film_grain").unwrap(),
+                            post_processing_settings.film_grain,
+                        );
+                        render_pass.set_int_property(
+                            &pipeline.get_int_property("p_frame").unwrap(),
+                            renderer_info.frame as i32,
+                        );
 
-            // Draw to the screen
-            let mut render_pass =
-                command_buffer.begin_render_pass_with_framebuffer(final_framebuffer, clear_color);
-
-            let shader = &renderer_info.final_postprocess_shader;
-            let texture = renderer_info.offscreen_render_target.color_texture();
-            let output_viewport = Box2::new(
-                Vec2::ZERO,
-                Vec2::new(
-                    view_size.0 as f32 * resolution_scale,
-                    view_size.1 as f32 * resolution_scale,
-                ),
-            );
-            let texture_scale = renderer_info.offscreen_render_target.inner_texture_scale();
-            let min = output_viewport.min.as_u32();
-            let size = output_viewport.size().as_u32();
+                        render_pass.draw_triangles_without_buffer(1);
+
+                        // Debug render of intermediate bloom texture
+
+                        /*
+                        render_texture_to_screen(
+                            shader_assets.get(&Shader::FULLSCREEN_QUAD),
+                            texture_assets,
+                            &mut render_pass,
+                            Box2::new(
+                                Vec2::ZERO,
+                                Vec2::new(view_size.0 as f32, view_size.1 as f32) / 2.0,
+                            ),
+                            blurred_texture,
+                            texture_scale,
+                        );
+                        */
 
-            render_pass.set_pipeline(&shader.pipeline);
-            render_pass.set_viewport(min.x, min.y, size.x, size.y);
+                        // Resolve into textures
+                        if let Some(camera_target) = camera_target {
+                            camera_target.resolve(render_pass)
+                        }
+                    }
+                }
+            }
+        }
 
-            render_pass.set_texture_property(
-                &shader.pipeline.get_texture_property("p_texture").unwrap(),
-                Some(texture_assets.get(texture)),
-                0,
-            );
-            render_pass.set_texture_property(
-                &shader
-                    .pipeline
-                    .get_texture_property("p_blurred_texture")
-                    .unwrap(),
-                Some(texture_assets.get(blurred_texture)),
-                1,
-            );
-            render_pass.set_vec2_property(
-                &shader
-                    .pipeline
-                    .get_vec2_property("p_texture_coordinate_scale")
-                    .unwrap(),
-                texture_scale.into(),
-            );
-            render_pass.set_float_property(
-                &shader
-                    .pipeline
-                    .get_float_property("p_bloom_strength")
-                    .unwrap(),
-                if renderer_info.bloom_enabled {
-                    renderer_info.bloom_strength
-                } else {
-                    0.0
-                },
-            );
+        renderer_info.render_graph = render_graph;
+        renderer_info.anti_aliasing.end_frame(&renderables);
 
-            render_pass.draw_triangles_without_buffer(1);
+        // Screenshots read the finished frame before it's presented.
+        if !graphics.screenshot_requests.is_empty() {
+            graphics.context.commit_command_buffer(command_buffer);
+            take_screenshots(graphics, texture_assets, offscreen_render_targets);
+            command_buffer = graphics.context.new_command_buffer();
+        }
 
-            // Debug render of intermediate bloom texture
+        command_buffer.present();
+        graphics.context.commit_command_buffer(command_buffer);
 
-            /*
-            render_texture_to_screen(
-                shader_assets.get(&Shader::FULLSCREEN_QUAD),
-                texture_assets,
-                &mut render_pass,
-                Box2::new(
-                    Vec2::ZERO,
-                    Vec2::new(view_size.0 as f32, view_size.1 as f32) / 2.0,
-                ),
-                blurred_texture,
-                texture_scale,
-            );
-            */
+        renderer_info.instance_model_buffers = instance_model_buffers;
+        renderer_info.instance_color_buffers = instance_color_buffers;
+        renderer_info.sprite_buffers = sprite_buffers;
 
-            // Resolve into textures
-            if let Some(CameraTarget::OffscreenRenderTarget(c)) = &camera.camera_target {
-                offscreen_render_targets.get(c).resolve(render_pass)
-            }
-        } else {
-            // Resolve into textures
-            if let Some(CameraTarget::OffscreenRenderTarget(c)) = &camera.camera_target {
-                offscreen_render_targets.get(c).resolve(render_pass)
-            }
+        // The particle buffers have been used by the committed commands so they can be freed.
+        for particle_buffer in particle_buffers {
+            graphics.context.delete_data_buffer(particle_buffer);
         }
     }
-
-    command_buffer.present();
-    graphics.context.commit_command_buffer(command_buffer);
 }
 
 pub fn render_texture_to_screen(
@@ -7,6 +7,9 @@ pub struct OffscreenRenderTarget {
     color_texture: Option<RenderTargetTexture>,
     depth_texture: Option<RenderTargetTexture>,
     resolve_framebuffer: Option<NotSendSync<Framebuffer>>,
+    /// Draws to the readable color texture without depth
+    /// so that the readable depth texture can be sampled while drawing.
+    color_only_framebuffer: Option<NotSendSync<Framebuffer>>,
     inner_texture_size: Vec2u,
     used_size: Vec2u,
     needs_resolve: bool,
@@ -70,6 +73,7 @@ impl OffscreenRenderTarget {
                 }
             }),
             resolve_framebuffer: None,
+            color_only_framebuffer: None,
             inner_texture_size: Vec2u::ZERO,
             used_size: Vec2u::ZERO,
             needs_resolve: color_pixel_format_and_texture_settings
@@ -106,6 +110,12 @@ impl OffscreenRenderTarget {
                 depth_texture.resize(size, graphics, textures)
             }
 
+            // A depth texture with a stencil component is also attached as the stencil buffer.
+            let stencil_texture = self
+                .depth_texture
+                .as_ref()
+                .filter(|t| t.pixel_format == PixelFormat::Depth24Stencil8);
+
             if let Some(framebuffer) = self.framebuffer.take() {
                 graphics.context.delete_framebuffer(framebuffer.take())
             }
@@ -117,7 +127,7 @@ impl OffscreenRenderTarget {
                     self.depth_texture
                         .as_ref()
                         .map(|t| &textures.get(&t.texture).0),
-                    None,
+                    stencil_texture.map(|t| &textures.get(&t.texture).0),
                 ),
             ));
 
@@ -133,17 +143,31 @@ impl OffscreenRenderTarget {
                         self.depth_texture
                             .as_ref()
                             .map(|t| &textures.get(t.resolve_texture.as_ref().unwrap()).0),
-                        None,
+                        stencil_texture
+                            .map(|t| &textures.get(t.resolve_texture.as_ref().unwrap()).0),
                     ),
                 ));
             }
 
+            if let Some(framebuffer) = self.color_only_framebuffer.take() {
+                graphics.context.delete_framebuffer(framebuffer.take())
+            }
+            if self.color_texture.is_some() {
+                self.color_only_framebuffer =
+                    Some(NotSendSync::new(graphics.context.new_framebuffer(
+                        Some(&textures.get(self.color_texture()).0),
+                        None,
+                        None,
+                    )));
+            }
+
             self.inner_texture_size = size;
         }
 
         self.used_size = size;
     }
 
+    /// Copies color and depth into the readable textures.
     /// This assumes that the framebuffer is currently bound.
     pub fn resolve(&self, render_pass: RenderPass) {
         if let Some(resolve_framebuffer) = self.resolve_framebuffer.as_ref() {
@@ -165,6 +189,12 @@ impl OffscreenRenderTarget {
         &*self.framebuffer.as_ref().unwrap()
     }
 
+    /// A framebuffer that draws directly to the readable color texture, after [Self::resolve].
+    /// It has no depth attachment so [Self::depth_texture] can be sampled while drawing to it.
+    pub fn color_only_framebuffer(&self) -> &Framebuffer {
+        self.color_only_framebuffer.as_ref().unwrap()
+    }
+
     /// Gets the readable color texture.
     pub fn color_texture(&self) -> &Handle<Texture> {
         let color_texture = self.color_texture.as_ref().unwrap();
@@ -175,6 +205,10 @@ impl OffscreenRenderTarget {
         }
     }
 
+    pub fn color_pixel_format(&self) -> PixelFormat {
+        self.color_texture.as_ref().unwrap().pixel_format
+    }
+
     /// Gets the readable depth texture.
     pub fn depth_texture(&self) -> &Handle<Texture> {
         let depth_texture = self.depth_texture.as_ref().unwrap();
@@ -0,0 +1,224 @@
+use super::*;
+
+/// Draws a solid outline around an [Entity]'s [Mesh], like for selected objects or pickups.
+///
+/// Outlines are drawn over the rest of the scene, so they stay visible behind other objects.
+/// Overlapping outlined [Entity]s share one outline around their combined shape.
+/// Only drawn by [Camera]s with post-processing enabled.
+///
+/// The outline is made by pushing the [Mesh] outwards along its normals, so the [Mesh] needs normals.
+/// [Mesh]es with hard edges, like [Mesh::CUBE], can show gaps at their corners.
+#[derive(Component, Clone, Debug)]
+#[component(storage = "sparse")]
+pub struct Outline {
+    pub color: Color,
+    /// The width of the outline in pixels.
+    pub width: f32,
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        Self {
+            color: Color::ORANGE,
+            width: 3.0,
+        }
+    }
+}
+
+pub type Outlines<'a> = Query<
+    'a,
+    (
+        &'static GlobalTransform,
+        &'static Handle<Mesh>,
+        &'static Outline,
+        Option<&'static RenderFlags>,
+    ),
+>;
+
+/// Marks the pixels covered by outlined [Mesh]es in a stencil buffer,
+/// then draws the pushed-out [Mesh]es everywhere else.
+pub(crate) struct OutlineRenderer {
+    target: OffscreenRenderTarget,
+    mask_shader: Shader,
+    outline_shader: Shader,
+    composite_shader: Shader,
+}
+
+impl OutlineRenderer {
+    pub fn new(graphics: &mut Graphics, textures: &mut Assets<Texture>) -> Self {
+        let target = OffscreenRenderTarget::new(
+            graphics,
+            textures,
+            Vec2u::ZERO,
+            Some((
+                PixelFormat::RGBA16F,
+                TextureSettings {
+                    srgb: false,
+                    generate_mipmaps: false,
+                    minification_filter: FilterMode::Nearest,
+                    magnification_filter: FilterMode::Nearest,
+                    wrapping_horizontal: WrappingMode::ClampToEdge,
+                    wrapping_vertical: WrappingMode::ClampToEdge,
+                    ..Default::default()
+                },
+            )),
+            Some((
+                PixelFormat::Depth24Stencil8,
+                TextureSettings {
+                    srgb: false,
+                    generate_mipmaps: false,
+                    minification_filter: FilterMode::Nearest,
+                    magnification_filter: FilterMode::Nearest,
+                    ..Default::default()
+                },
+            )),
+        );
+
+        let source = include_str!("../built_in_shaders/outline.glsl");
+        let mask_shader = graphics
+            .new_shader(
+                source,
+                PipelineSettings {
+                    faces_to_render: FacesToRender::FrontAndBack,
+                    depth_test: DepthTest::AlwaysPass,
+                    stencil: Some(StencilState {
+                        reference: 1,
+                        compare: CompareFunction::Always,
+                        pass: StencilOperation::Replace,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let outline_shader = graphics
+            .new_shader(
+                source,
+                PipelineSettings {
+                    faces_to_render: FacesToRender::FrontAndBack,
+                    depth_test: DepthTest::AlwaysPass,
+                    stencil: Some(StencilState {
+                        reference: 1,
+                        compare: CompareFunction::NotEqual,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let composite_shader = graphics
+            .new_shader(
+                FULLSCREEN_QUAD_SHADER_SOURCE,
+                PipelineSettings {
+                    blending: Some((BlendFactor::One, BlendFactor::OneMinusSourceAlpha)),
+                    depth_test: DepthTest::AlwaysPass,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        Self {
+            target,
+            mask_shader,
+            outline_shader,
+            composite_shader,
+        }
+    }
+
+    /// Draws the [Outline]s the [Camera] sees over the resolved `scene`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        graphics: &mut Graphics,
+        textures: &mut Assets<Texture>,
+        meshes: &Assets<Mesh>,
+        command_buffer: &mut CommandBuffer,
+        camera: &Camera,
+        view_matrix: &Mat4,
+        projection_matrix: &Mat4,
+        outlines: &Outlines,
+        scene: &OffscreenRenderTarget,
+    ) {
+        let outlines: Vec<_> = outlines
+            .iter()
+            .filter(|(_, mesh_handle, outline, render_flags)| {
+                let render_flags = render_flags.cloned().unwrap_or(RenderFlags::DEFAULT);
+                camera.render_flags.includes_layer(render_flags)
+                    && outline.width > 0.0
+                    && meshes.get(mesh_handle).gpu_mesh.is_some()
+            })
+            .collect();
+        if outlines.is_empty() {
+            return;
+        }
+
+        let size = scene.size();
+        self.target.resize(graphics, textures, size);
+
+        let mut render_pass = command_buffer.begin_render_pass_with_framebuffer(
+            self.target.framebuffer(),
+            Some((0.0, 0.0, 0.0, 0.0)),
+        );
+        render_pass.set_viewport(0, 0, size.x as u32, size.y as u32);
+
+        // Every mask is drawn first so that outlines never cover another outlined Mesh.
+        for (pipeline, is_mask) in [
+            (&self.mask_shader.pipeline, true),
+            (&self.outline_shader.pipeline, false),
+        ] {
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_mat4_property(
+                &pipeline.get_mat4_property("p_views[0]").unwrap(),
+                view_matrix.as_array(),
+            );
+            render_pass.set_mat4_property(
+                &pipeline.get_mat4_property("p_projections[0]").unwrap(),
+                projection_matrix.as_array(),
+            );
+            render_pass.set_vec2_property(
+                &pipeline.get_vec2_property("p_view_size").unwrap(),
+                size.as_f32().into(),
+            );
+            let model_property = pipeline.get_mat4_property("p_model").unwrap();
+            let width_property = pipeline.get_float_property("p_width").unwrap();
+            let color_property = pipeline.get_vec4_property("p_color").unwrap();
+            let position_attribute = pipeline.get_vertex_attribute::<Vec3>("a_position").unwrap();
+            let normal_attribute = pipeline.get_vertex_attribute::<Vec3>("a_normal").unwrap();
+
+            for (global_transform, mesh_handle, outline, _) in &outlines {
+                let gpu_mesh = meshes.get(mesh_handle).gpu_mesh.as_ref().unwrap();
+                let (width, color) = if is_mask {
+                    (0.0, Vec4::ZERO)
+                } else {
+                    (
+                        outline.width,
+                        outline.color.to_rgb_color(color_spaces::LINEAR_SRGB),
+                    )
+                };
+                render_pass.set_float_property(&width_property, width);
+                render_pass.set_vec4_property(&color_property, color.into());
+                render_pass.set_mat4_property(&model_property, global_transform.model().as_array());
+                render_pass.set_vertex_attribute(&position_attribute, Some(&gpu_mesh.positions));
+                render_pass.set_vertex_attribute(&normal_attribute, gpu_mesh.normals.as_ref());
+                render_pass.draw_triangles(gpu_mesh.triangle_count, &gpu_mesh.index_buffer);
+            }
+        }
+
+        let pipeline = &self.composite_shader.pipeline;
+        let mut render_pass =
+            command_buffer.begin_render_pass_with_framebuffer(scene.color_only_framebuffer(), None);
+        render_pass.set_viewport(0, 0, size.x as u32, size.y as u32);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_texture_property(
+            &pipeline.get_texture_property("p_texture").unwrap(),
+            Some(textures.get(self.target.color_texture())),
+            0,
+        );
+        render_pass.set_vec2_property(
+            &pipeline
+                .get_vec2_property("p_texture_coordinate_scale")
+                .unwrap(),
+            self.target.inner_texture_scale().into(),
+        );
+        render_pass.draw_triangles_without_buffer(1);
+    }
+}
@@ -0,0 +1,176 @@
+use super::*;
+
+/// A [ParticleEmitter]'s particles prepared for drawing.
+pub(crate) struct ParticleBatch {
+    texture: Handle<Texture>,
+    additive: bool,
+    soft_distance: f32,
+    render_flags: RenderFlags,
+    /// The average particle position, used to sort batches.
+    center: Vec3,
+    /// Each particle's columns are: position and size, color, texture coordinate offset and scale.
+    instances: Vec<Mat4>,
+}
+
+pub fn prepare_particles(
+    renderer_info: &mut RendererInfo,
+    particle_emitters: Query<(&ParticleEmitter, Option<&RenderFlags>)>,
+) {
+    renderer_info.particle_batches.clear();
+    for (emitter, render_flags) in &particle_emitters {
+        let particles = emitter.particles();
+        if particles.is_empty() {
+            continue;
+        }
+
+        let mut center = Vec3::ZERO;
+        let instances = particles
+            .iter()
+            .map(|particle| {
+                center += particle.position;
+                let life = particle.life();
+                let size = emitter.size.sample(life);
+                let color = emitter
+                    .color
+                    .sample(life)
+                    .to_rgb_color(color_spaces::LINEAR_SRGB);
+                let bounds = emitter.sprite_sheet.as_ref().map_or(
+                    Box2::new(Vec2::ZERO, Vec2::ONE),
+                    |sprite_sheet| {
+                        sprite_sheet
+                            .sprite(particle.age, particle.lifetime)
+                            .sprite_source_bounds
+                    },
+                );
+                let (offset, scale) = (bounds.min, bounds.size());
+                let p = particle.position;
+                Matrix([
+                    [p.x, p.y, p.z, size],
+                    color.0[0],
+                    [offset.x, offset.y, scale.x, scale.y],
+                    [0.0; 4],
+                ])
+            })
+            .collect();
+
+        renderer_info.particle_batches.push(ParticleBatch {
+            texture: emitter.texture_handle().clone(),
+            additive: emitter.additive,
+            soft_distance: emitter.soft_distance,
+            render_flags: render_flags.cloned().unwrap_or(RenderFlags::DEFAULT),
+            center: center / particles.len() as f32,
+            instances,
+        });
+    }
+}
+
+/// Draws particles as camera-facing quads with one instanced draw per [ParticleEmitter].
+/// If `depth_texture` is provided particles are hidden behind and fade near opaque surfaces by sampling it.
+/// Otherwise the framebuffer's depth hides particles and they aren't soft.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn render_particles(
+    graphics_context: &mut GraphicsContext,
+    render_pass: &mut RenderPass,
+    shader: &Shader,
+    mesh_assets: &Assets<Mesh>,
+    texture_assets: &Assets<Texture>,
+    camera: &Camera,
+    view_info: &ViewInfo,
+    particle_batches: &[ParticleBatch],
+    depth_texture: Option<&Texture>,
+    particle_buffers: &mut Vec<DataBuffer<Mat4>>,
+) {
+    let mut particle_batches: Vec<&ParticleBatch> = particle_batches
+        .iter()
+        .filter(|b| camera.render_flags.includes_layer(b.render_flags))
+        .collect();
+    let gpu_mesh = match mesh_assets.get(&Mesh::VERTICAL_QUAD).gpu_mesh.as_ref() {
+        Some(gpu_mesh) if !particle_batches.is_empty() => gpu_mesh,
+        _ => return,
+    };
+
+    // Sort from back to front so that blending is correct.
+    let view_depth = |p: Vec3| -view_info.view_matrix.transform_point(p).z;
+    let back_to_front = |a: f32, b: f32| b.partial_cmp(&a).unwrap_or(std::cmp::Ordering::Equal);
+    particle_batches.sort_by(|a, b| back_to_front(view_depth(a.center), view_depth(b.center)));
+
+    let pipeline = &shader.pipeline;
+    render_pass.set_pipeline(pipeline);
+    // Particles are depth tested but don't hide each-other.
+    render_pass.set_depth_mask(false);
+
+    render_pass.set_mat4_property(
+        &pipeline.get_mat4_property("p_views[0]").unwrap(),
+        view_info.view_matrix.as_array(),
+    );
+    render_pass.set_mat4_property(
+        &pipeline.get_mat4_property("p_projections[0]").unwrap(),
+        view_info.projection_matrix.as_array(),
+    );
+    render_pass.set_mat4_property(
+        &pipeline.get_mat4_property("p_inverse_projection").unwrap(),
+        view_info.projection_matrix.inversed().as_array(),
+    );
+    render_pass.set_int_property(
+        &pipeline.get_int_property("p_use_depth_texture").unwrap(),
+        depth_texture.is_some() as i32,
+    );
+    render_pass.set_texture_property(
+        &pipeline.get_texture_property("p_depth_texture").unwrap(),
+        Some(depth_texture.unwrap_or_else(|| texture_assets.get(&Texture::WHITE))),
+        1,
+    );
+
+    render_pass.set_vertex_attribute(
+        &pipeline.get_vertex_attribute::<Vec3>("a_position").unwrap(),
+        Some(&gpu_mesh.positions),
+    );
+    render_pass.set_vertex_attribute(
+        &pipeline
+            .get_vertex_attribute::<Vec2>("a_texture_coordinate")
+            .unwrap(),
+        gpu_mesh.texture_coordinates.as_ref(),
+    );
+
+    let texture_property = pipeline.get_texture_property("p_texture").unwrap();
+    let soft_distance_property = pipeline.get_float_property("p_soft_distance").unwrap();
+    let additive_property = pipeline.get_int_property("p_additive").unwrap();
+    let instance_attribute = pipeline
+        .get_vertex_attribute::<Mat4>("a_instance_model")
+        .unwrap();
+
+    let mut instances = Vec::new();
+    for batch in particle_batches {
+        instances.clear();
+        instances.extend_from_slice(&batch.instances);
+        // Additive particles look the same in any order.
+        if !batch.additive {
+            instances.sort_by(|a: &Mat4, b: &Mat4| {
+                back_to_front(
+                    view_depth(Vec3::new(a.0[0][0], a.0[0][1], a.0[0][2])),
+                    view_depth(Vec3::new(b.0[0][0], b.0[0][1], b.0[0][2])),
+                )
+            });
+        }
+
+        render_pass.set_texture_property(
+            &texture_property,
+            Some(texture_assets.get(&batch.texture)),
+            0,
+        );
+        render_pass.set_float_property(&soft_distance_property, batch.soft_distance);
+        render_pass.set_int_property(&additive_property, batch.additive as i32);
+
+        let instance_buffer = graphics_context.new_data_buffer(&instances).unwrap();
+        render_pass.set_instance_attribute(&instance_attribute, Some(&instance_buffer));
+        render_pass.draw_triangles_instanced(
+            gpu_mesh.triangle_count,
+            &gpu_mesh.index_buffer,
+            instances.len() as u32,
+        );
+        particle_buffers.push(instance_buffer);
+    }
+
+    render_pass.set_instance_attribute(&instance_attribute, None);
+    render_pass.set_depth_mask(true);
+}
@@ -12,6 +12,8 @@ pub struct PBRProperties {
     pub emissive: Vec3,
     pub emissive_texture: Option<Handle<Texture>>,
     pub normal_texture: Option<Handle<Texture>>,
+    /// If set, pixels with an alpha below this are discarded.
+    pub alpha_cutoff: Option<f32>,
     pub blending: Option<(BlendFactor, BlendFactor)>,
 }
 
@@ -28,6 +30,7 @@ impl Default for PBRProperties {
             normal_texture: Some(Texture::NORMAL),
             emissive_texture: Some(Texture::WHITE),
             emissive: Vec3::ZERO,
+            alpha_cutoff: None,
             blending: None,
         }
     }
@@ -62,8 +65,18 @@ pub fn new_pbr_material(shader: Handle<Shader>, pbr_properties: PBRProperties) -
         .normal_texture
         .clone()
         .unwrap_or(Texture::NORMAL);
+    // The default flat normal map doesn't change normals so it can be skipped.
+    material.set_keyword(
+        shader_keywords::NORMAL_MAP,
+        normal_texture != Texture::NORMAL,
+    );
     material.set_texture("p_normal_texture", normal_texture);
 
+    if let Some(alpha_cutoff) = pbr_properties.alpha_cutoff {
+        material.set_keyword(shader_keywords::ALPHA_CLIP, true);
+        material.set_float("p_alpha_cutoff", alpha_cutoff);
+    }
+
     material.set_vec3("p_emissive", {
         let rgb_color = pbr_properties.emissive;
         rgb_color.xyz()
@@ -0,0 +1,1003 @@
+use super::*;
+use std::fmt::Write;
+
+/// Declares the passes that render each [Camera] and the resources they pass between each-other.
+///
+/// Passes are ordered by the resources they read and write instead of the order they're added in:
+/// a pass runs after every pass that writes a resource it reads, and passes that write the same
+/// resource run in the order they were added. Passes whose outputs are never read are culled
+/// unless they write [RenderGraph::CAMERA_TARGET].
+///
+/// Resources added with [RenderGraph::add_resource] are allocated by the graph and resized to
+/// match each view. Resources whose uses don't overlap share the same [OffscreenRenderTarget].
+///
+/// Plugins add custom passes from a setup system with `renderer_info.render_graph.add_pass`.
+/// Custom passes run around the built-in passes by reading or writing the built-in resources,
+/// like [RenderGraph::SCENE]. The built-in passes are ordered the same way and can be removed.
+pub struct RenderGraph {
+    passes: Vec<RenderGraphPass>,
+    resources: Vec<(String, RenderGraphResource)>,
+    /// `None` if the graph changed since it was last compiled.
+    compiled: Option<Result<CompiledRenderGraph, RenderGraphError>>,
+    /// One per target in the compiled graph.
+    targets: Vec<OffscreenRenderTarget>,
+    /// The next pass to run for the current view.
+    cursor: usize,
+}
+
+/// A pass that's part of the renderer. Custom passes can be ordered around these.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuiltInPass {
+    Shadows,
+    AmbientOcclusion,
+    /// Draws the scene and its particles.
+    Scene,
+    AntiAliasing,
+    Bloom,
+    /// Exposure, tonemapping and color grading into the [Camera]'s target.
+    PostProcess,
+}
+
+/// A pass returned by [RenderGraph::next_pass].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ViewPass {
+    BuiltIn(BuiltInPass),
+    /// Index of a custom pass to run with [RenderGraph::run_custom_pass].
+    Custom(usize),
+}
+
+type RunPass = Box<dyn FnMut(&mut RenderGraphContext) + Send + Sync>;
+
+enum PassKind {
+    BuiltIn(BuiltInPass),
+    Custom(RunPass),
+}
+
+pub struct RenderGraphPass {
+    name: String,
+    reads: Vec<String>,
+    writes: Vec<String>,
+    never_cull: bool,
+    kind: PassKind,
+}
+
+impl RenderGraphPass {
+    pub fn new(
+        name: &str,
+        run: impl FnMut(&mut RenderGraphContext) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            never_cull: false,
+            kind: PassKind::Custom(Box::new(run)),
+        }
+    }
+
+    fn built_in(built_in_pass: BuiltInPass, reads: &[&str], writes: &[&str]) -> Self {
+        Self {
+            name: match built_in_pass {
+                BuiltInPass::Shadows => "shadows",
+                BuiltInPass::AmbientOcclusion => "ambient_occlusion",
+                BuiltInPass::Scene => "scene",
+                BuiltInPass::AntiAliasing => "anti_aliasing",
+                BuiltInPass::Bloom => "bloom",
+                BuiltInPass::PostProcess => "post_process",
+            }
+            .into(),
+            reads: reads.iter().map(|r| r.to_string()).collect(),
+            writes: writes.iter().map(|w| w.to_string()).collect(),
+            // Built-in passes decide for themselves whether they have anything to do.
+            never_cull: true,
+            kind: PassKind::BuiltIn(built_in_pass),
+        }
+    }
+
+    pub fn reads(mut self, resource: &str) -> Self {
+        self.reads.push(resource.into());
+        self
+    }
+
+    pub fn writes(mut self, resource: &str) -> Self {
+        self.writes.push(resource.into());
+        self
+    }
+
+    /// Run this pass even if nothing reads what it writes.
+    pub fn never_cull(mut self) -> Self {
+        self.never_cull = true;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// How large a [RenderGraphResource] is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderGraphSize {
+    /// The size of the view being rendered.
+    View,
+    /// The size of the view being rendered multiplied by a scale.
+    ViewScaled(f32),
+    Fixed(Vec2u),
+}
+
+/// Describes an [OffscreenRenderTarget] allocated by a [RenderGraph].
+/// Color textures are filtered linearly and depth textures use nearest filtering.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderGraphResource {
+    pub size: RenderGraphSize,
+    pub color_format: Option<PixelFormat>,
+    pub depth_format: Option<PixelFormat>,
+}
+
+impl RenderGraphResource {
+    pub fn color(pixel_format: PixelFormat) -> Self {
+        Self {
+            size: RenderGraphSize::View,
+            color_format: Some(pixel_format),
+            depth_format: None,
+        }
+    }
+
+    pub fn depth(pixel_format: PixelFormat) -> Self {
+        Self {
+            size: RenderGraphSize::View,
+            color_format: None,
+            depth_format: Some(pixel_format),
+        }
+    }
+
+    pub fn with_depth(mut self, pixel_format: PixelFormat) -> Self {
+        self.depth_format = Some(pixel_format);
+        self
+    }
+
+    pub fn with_size(mut self, size: RenderGraphSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    fn size_for_view(&self, view_size: Vec2u) -> Vec2u {
+        match self.size {
+            RenderGraphSize::View => view_size,
+            RenderGraphSize::ViewScaled(scale) => (view_size.as_f32() * scale).as_usize(),
+            RenderGraphSize::Fixed(size) => size,
+        }
+    }
+
+    fn new_target(
+        &self,
+        graphics: &mut Graphics,
+        textures: &mut Assets<Texture>,
+        size: Vec2u,
+    ) -> OffscreenRenderTarget {
+        let texture_settings = |filter_mode| TextureSettings {
+            srgb: false,
+            minification_filter: filter_mode,
+            magnification_filter: filter_mode,
+            wrapping_horizontal: WrappingMode::ClampToEdge,
+            wrapping_vertical: WrappingMode::ClampToEdge,
+            generate_mipmaps: false,
+            ..Default::default()
+        };
+        OffscreenRenderTarget::new(
+            graphics,
+            textures,
+            size,
+            self.color_format
+                .map(|format| (format, texture_settings(FilterMode::Linear))),
+            self.depth_format
+                .map(|format| (format, texture_settings(FilterMode::Nearest))),
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RenderGraphError {
+    /// Two passes have the same name.
+    DuplicatePass(String),
+    /// A pass uses a resource that wasn't added to the graph and isn't a built-in resource.
+    UnknownResource { pass: String, resource: String },
+    /// A pass reads a resource that no pass writes.
+    NeverWritten { pass: String, resource: String },
+    /// A custom pass reads a built-in resource that's only used to order passes,
+    /// like [RenderGraph::SHADOW_MAPS].
+    NotReadable { pass: String, resource: String },
+    /// These passes depend on each-other.
+    Cycle(Vec<String>),
+}
+
+/// Where a resource allocated by the graph lives and which passes use it.
+#[derive(Clone, Debug, PartialEq)]
+struct ResourceAllocation {
+    name: String,
+    /// Index into [CompiledRenderGraph::targets]
+    target: usize,
+    /// The range of [CompiledRenderGraph::order] that uses the resource.
+    first_use: usize,
+    last_use: usize,
+}
+
+#[derive(Clone, Debug)]
+struct CompiledRenderGraph {
+    /// Indices of the passes that run, in the order they run.
+    order: Vec<usize>,
+    culled: Vec<usize>,
+    allocations: Vec<ResourceAllocation>,
+    targets: Vec<RenderGraphResource>,
+}
+
+impl Default for RenderGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderGraph {
+    /// Written by [BuiltInPass::Shadows].
+    /// Each light has its own shadow maps so this can only be written, to run passes before
+    /// the scene is drawn.
+    pub const SHADOW_MAPS: &'static str = "shadow_maps";
+    /// Written by [BuiltInPass::AmbientOcclusion] at half the view's resolution.
+    /// Only available if the [Camera] has [AmbientOcclusion].
+    pub const AMBIENT_OCCLUSION: &'static str = "ambient_occlusion";
+    /// The scene's linear color and depth, before anti-aliasing and post-processing.
+    /// Only available if the [Camera] has `post_processing_enabled`.
+    pub const SCENE: &'static str = "scene";
+    /// The scene after anti-aliasing.
+    /// Only available once [BuiltInPass::AntiAliasing] has run
+    /// if the [Camera] has `post_processing_enabled`.
+    pub const ANTI_ALIASED_SCENE: &'static str = "anti_aliased_scene";
+    /// Written by [BuiltInPass::Bloom].
+    /// Only available once it has run if bloom is enabled.
+    pub const BLOOM: &'static str = "bloom";
+    /// Where the [Camera]'s final image goes. Passes that write this are never culled.
+    /// Only available as a target if the [Camera] renders to an [OffscreenRenderTarget].
+    /// Use [RenderGraphContext::begin_camera_render_pass] to draw to any [Camera]'s target.
+    pub const CAMERA_TARGET: &'static str = "camera_target";
+
+    const BUILT_IN_RESOURCES: [&'static str; 6] = [
+        Self::SHADOW_MAPS,
+        Self::AMBIENT_OCCLUSION,
+        Self::SCENE,
+        Self::ANTI_ALIASED_SCENE,
+        Self::BLOOM,
+        Self::CAMERA_TARGET,
+    ];
+
+    /// A graph with only the renderer's built-in passes.
+    pub fn new() -> Self {
+        Self {
+            passes: vec![
+                RenderGraphPass::built_in(BuiltInPass::Shadows, &[], &[Self::SHADOW_MAPS]),
+                RenderGraphPass::built_in(
+                    BuiltInPass::AmbientOcclusion,
+                    &[],
+                    &[Self::AMBIENT_OCCLUSION],
+                ),
+                RenderGraphPass::built_in(
+                    BuiltInPass::Scene,
+                    &[Self::SHADOW_MAPS, Self::AMBIENT_OCCLUSION],
+                    &[Self::SCENE],
+                ),
+                RenderGraphPass::built_in(
+                    BuiltInPass::AntiAliasing,
+                    &[Self::SCENE],
+                    &[Self::ANTI_ALIASED_SCENE],
+                ),
+                RenderGraphPass::built_in(BuiltInPass::Bloom, &[Self::SCENE], &[Self::BLOOM]),
+                RenderGraphPass::built_in(
+                    BuiltInPass::PostProcess,
+                    &[Self::ANTI_ALIASED_SCENE, Self::BLOOM],
+                    &[Self::CAMERA_TARGET],
+                ),
+            ],
+            resources: Vec::new(),
+            compiled: None,
+            targets: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    /// Adds a resource that the graph allocates for each view.
+    /// Adding a resource with an existing name replaces it.
+    pub fn add_resource(&mut self, name: &str, resource: RenderGraphResource) {
+        self.compiled = None;
+        if let Some(existing) = self.resources.iter_mut().find(|(n, _)| n == name) {
+            existing.1 = resource;
+        } else {
+            self.resources.push((name.into(), resource));
+        }
+    }
+
+    pub fn add_pass(&mut self, pass: RenderGraphPass) {
+        self.compiled = None;
+        self.passes.push(pass);
+    }
+
+    /// Removes a pass. Returns `false` if there's no pass with that name.
+    /// Built-in passes can be removed too, like [BuiltInPass::Bloom] to skip bloom entirely.
+    /// Built-in passes that read a resource nothing writes use a default instead.
+    pub fn remove_pass(&mut self, name: &str) -> bool {
+        let index = self.passes.iter().position(|p| p.name == name);
+        if let Some(index) = index {
+            self.compiled = None;
+            self.passes.remove(index);
+        }
+        index.is_some()
+    }
+
+    /// Checks the graph for errors. The graph is also checked before it's used to render.
+    pub fn validate(&mut self) -> Result<(), RenderGraphError> {
+        self.compiled().map(|_| ())
+    }
+
+    fn compiled(&mut self) -> Result<&CompiledRenderGraph, RenderGraphError> {
+        if self.compiled.is_none() {
+            let compiled = self.compile();
+            if let Err(error) = &compiled {
+                crate::log!(
+                    "Render graph error: {:?}. Only the built-in passes run.",
+                    error
+                );
+            }
+            self.compiled = Some(compiled);
+            // The targets may not match the new graph's resources.
+            self.targets.clear();
+        }
+        self.compiled
+            .as_ref()
+            .unwrap()
+            .as_ref()
+            .map_err(|e| e.clone())
+    }
+
+    fn compile(&self) -> Result<CompiledRenderGraph, RenderGraphError> {
+        let passes = &self.passes;
+        for (i, pass) in passes.iter().enumerate() {
+            if passes[..i].iter().any(|p| p.name == pass.name) {
+                return Err(RenderGraphError::DuplicatePass(pass.name.clone()));
+            }
+            for resource in pass.reads.iter().chain(pass.writes.iter()) {
+                if !Self::BUILT_IN_RESOURCES.contains(&resource.as_str())
+                    && !self.resources.iter().any(|(name, _)| name == resource)
+                {
+                    return Err(RenderGraphError::UnknownResource {
+                        pass: pass.name.clone(),
+                        resource: resource.clone(),
+                    });
+                }
+            }
+            if let PassKind::Custom(_) = pass.kind {
+                if let Some(resource) = pass.reads.iter().find(|r| *r == Self::SHADOW_MAPS) {
+                    return Err(RenderGraphError::NotReadable {
+                        pass: pass.name.clone(),
+                        resource: resource.clone(),
+                    });
+                }
+            }
+        }
+
+        // Find each pass's dependencies.
+        let mut dependencies: Vec<Vec<usize>> = vec![Vec::new(); passes.len()];
+        for (i, pass) in passes.iter().enumerate() {
+            for resource in &pass.writes {
+                // Passes that write the same resource run in the order they were added.
+                if let Some(previous_writer) = passes[..i]
+                    .iter()
+                    .rposition(|p| p.writes.contains(resource))
+                {
+                    dependencies[i].push(previous_writer);
+                }
+            }
+            for resource in &pass.reads {
+                if pass.writes.contains(resource) {
+                    continue;
+                }
+                // Readers wait for every writer, including writers added after them.
+                let mut writers = passes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, p)| p.writes.contains(resource))
+                    .peekable();
+                if writers.peek().is_none() {
+                    if let PassKind::BuiltIn(_) = pass.kind {
+                        continue;
+                    }
+                    return Err(RenderGraphError::NeverWritten {
+                        pass: pass.name.clone(),
+                        resource: resource.clone(),
+                    });
+                }
+                dependencies[i].extend(writers.map(|(j, _)| j));
+            }
+        }
+
+        // Cull passes that nothing needs by walking backwards from the passes that must run.
+        let mut needed: Vec<bool> = passes
+            .iter()
+            .map(|p| p.never_cull || p.writes.iter().any(|w| w == Self::CAMERA_TARGET))
+            .collect();
+        let mut to_visit: Vec<usize> = (0..passes.len()).filter(|i| needed[*i]).collect();
+        while let Some(i) = to_visit.pop() {
+            for &dependency in &dependencies[i] {
+                if !needed[dependency] {
+                    needed[dependency] = true;
+                    to_visit.push(dependency);
+                }
+            }
+        }
+
+        // Sort topologically, preferring the order passes were added in.
+        let mut order = Vec::new();
+        let mut ordered = vec![false; passes.len()];
+        loop {
+            let next = (0..passes.len())
+                .find(|&i| needed[i] && !ordered[i] && dependencies[i].iter().all(|&d| ordered[d]));
+            match next {
+                Some(i) => {
+                    ordered[i] = true;
+                    order.push(i);
+                }
+                None => break,
+            }
+        }
+        let unordered: Vec<String> = (0..passes.len())
+            .filter(|&i| needed[i] && !ordered[i])
+            .map(|i| passes[i].name.clone())
+            .collect();
+        if !unordered.is_empty() {
+            return Err(RenderGraphError::Cycle(unordered));
+        }
+        let culled = (0..passes.len()).filter(|&i| !needed[i]).collect();
+
+        // Find when each allocated resource is used and share targets between resources
+        // with matching descriptions whose uses don't overlap.
+        let mut allocations: Vec<ResourceAllocation> = Vec::new();
+        for (name, _) in &self.resources {
+            let mut uses = order.iter().enumerate().filter(|&(_, &pass)| {
+                passes[pass].reads.contains(name) || passes[pass].writes.contains(name)
+            });
+            if let Some((first_use, _)) = uses.next() {
+                let last_use = uses.next_back().map_or(first_use, |(last_use, _)| last_use);
+                allocations.push(ResourceAllocation {
+                    name: name.clone(),
+                    target: 0,
+                    first_use,
+                    last_use,
+                });
+            }
+        }
+        allocations.sort_by_key(|a| a.first_use);
+
+        let mut targets: Vec<RenderGraphResource> = Vec::new();
+        let mut target_last_use: Vec<usize> = Vec::new();
+        for allocation in &mut allocations {
+            let resource = self
+                .resources
+                .iter()
+                .find(|(name, _)| *name == allocation.name)
+                .unwrap()
+                .1;
+            let reusable = (0..targets.len())
+                .find(|&t| targets[t] == resource && target_last_use[t] < allocation.first_use);
+            allocation.target = match reusable {
+                Some(target) => target,
+                None => {
+                    targets.push(resource);
+                    target_last_use.push(0);
+                    targets.len() - 1
+                }
+            };
+            target_last_use[allocation.target] = allocation.last_use;
+        }
+
+        Ok(CompiledRenderGraph {
+            order,
+            culled,
+            allocations,
+            targets,
+        })
+    }
+
+    /// Describes the compiled graph: the order passes run in, the passes that were culled,
+    /// and which target each allocated resource uses.
+    pub fn debug_dump(&mut self) -> String {
+        let compiled = match self.compiled() {
+            Ok(compiled) => compiled.clone(),
+            Err(error) => return format!("Render graph error: {:?}\n", error),
+        };
+
+        let mut dump = String::new();
+        writeln!(dump, "Passes:").unwrap();
+        for (i, &pass) in compiled.order.iter().enumerate() {
+            let pass = &self.passes[pass];
+            let kind = match pass.kind {
+                PassKind::BuiltIn(_) => " (built-in)",
+                PassKind::Custom(_) => "",
+            };
+            writeln!(
+                dump,
+                "  {}: {}{} reads {:?} writes {:?}",
+                i, pass.name, kind, pass.reads, pass.writes
+            )
+            .unwrap();
+        }
+        if !compiled.culled.is_empty() {
+            writeln!(dump, "Culled:").unwrap();
+            for &pass in &compiled.culled {
+                writeln!(dump, "  {}", self.passes[pass].name).unwrap();
+            }
+        }
+        if !compiled.allocations.is_empty() {
+            writeln!(dump, "Resources:").unwrap();
+            for allocation in &compiled.allocations {
+                writeln!(
+                    dump,
+                    "  {}: target {} used by passes {}..={}",
+                    allocation.name, allocation.target, allocation.first_use, allocation.last_use
+                )
+                .unwrap();
+            }
+            writeln!(dump, "Targets:").unwrap();
+            for (i, target) in compiled.targets.iter().enumerate() {
+                writeln!(
+                    dump,
+                    "  {}: {:?} color {:?} depth {:?}",
+                    i, target.size, target.color_format, target.depth_format
+                )
+                .unwrap();
+            }
+        }
+        dump
+    }
+
+    /// Allocates and resizes the graph's targets for a view and starts running the graph from its first pass.
+    pub(super) fn begin_view(
+        &mut self,
+        graphics: &mut Graphics,
+        textures: &mut Assets<Texture>,
+        view_size: Vec2u,
+    ) {
+        self.cursor = 0;
+        let target_descriptions = match self.compiled() {
+            Ok(compiled) => compiled.targets.clone(),
+            Err(_) => return,
+        };
+
+        for (i, description) in target_descriptions.iter().enumerate() {
+            let size = description.size_for_view(view_size);
+            if i < self.targets.len() {
+                self.targets[i].resize(graphics, textures, size);
+            } else {
+                self.targets
+                    .push(description.new_target(graphics, textures, size));
+            }
+        }
+    }
+
+    /// The next pass to run for the current view, in the compiled order.
+    /// If the graph has errors only the built-in passes run, in the order they were added.
+    pub(super) fn next_pass(&mut self) -> Option<ViewPass> {
+        loop {
+            let (index, compiled) = match &self.compiled {
+                Some(Ok(compiled)) => (*compiled.order.get(self.cursor)?, true),
+                _ => (self.cursor, false),
+            };
+            self.cursor += 1;
+            match self.passes.get(index)?.kind {
+                PassKind::BuiltIn(built_in_pass) => return Some(ViewPass::BuiltIn(built_in_pass)),
+                PassKind::Custom(_) if compiled => return Some(ViewPass::Custom(index)),
+                PassKind::Custom(_) => {}
+            }
+        }
+    }
+
+    /// Runs a custom pass returned by [RenderGraph::next_pass].
+    /// The context's targets are the built-in resources available so far.
+    pub(super) fn run_custom_pass(&mut self, index: usize, context: &mut RenderGraphContext) {
+        let RenderGraph {
+            passes,
+            compiled,
+            targets,
+            ..
+        } = self;
+        let (compiled, run) = match (compiled, &mut passes[index].kind) {
+            (Some(Ok(compiled)), PassKind::Custom(run)) => (compiled, run),
+            _ => return,
+        };
+
+        let mut pass_targets = Vec::new();
+        for &(name, target) in &context.targets {
+            pass_targets.push((name, target));
+        }
+        for allocation in &compiled.allocations {
+            pass_targets.push((allocation.name.as_str(), &targets[allocation.target]));
+        }
+        let mut pass_context = RenderGraphContext {
+            graphics: &mut *context.graphics,
+            command_buffer: &mut *context.command_buffer,
+            textures: context.textures,
+            shaders: context.shaders,
+            meshes: context.meshes,
+            camera: context.camera,
+            camera_global_transform: context.camera_global_transform,
+            view_size: context.view_size,
+            camera_target: context.camera_target,
+            targets: pass_targets,
+        };
+        run(&mut pass_context);
+    }
+}
+
+/// Passed to custom [RenderGraphPass]es when they run.
+pub struct RenderGraphContext<'a> {
+    pub graphics: &'a mut Graphics,
+    pub command_buffer: &'a mut CommandBuffer,
+    pub textures: &'a Assets<Texture>,
+    pub shaders: &'a Assets<Shader>,
+    pub meshes: &'a Assets<Mesh>,
+    pub camera: &'a Camera,
+    pub camera_global_transform: &'a GlobalTransform,
+    /// The size in pixels of the view being rendered.
+    pub view_size: Vec2u,
+    /// `None` if the [Camera] renders to the window.
+    pub camera_target: Option<&'a OffscreenRenderTarget>,
+    targets: Vec<(&'a str, &'a OffscreenRenderTarget)>,
+}
+
+impl<'a> RenderGraphContext<'a> {
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn new(
+        graphics: &'a mut Graphics,
+        command_buffer: &'a mut CommandBuffer,
+        textures: &'a Assets<Texture>,
+        shaders: &'a Assets<Shader>,
+        meshes: &'a Assets<Mesh>,
+        camera: &'a Camera,
+        camera_global_transform: &'a GlobalTransform,
+        view_size: Vec2u,
+        camera_target: Option<&'a OffscreenRenderTarget>,
+        targets: Vec<(&'a str, &'a OffscreenRenderTarget)>,
+    ) -> Self {
+        Self {
+            graphics,
+            command_buffer,
+            textures,
+            shaders,
+            meshes,
+            camera,
+            camera_global_transform,
+            view_size,
+            camera_target,
+            targets,
+        }
+    }
+
+    /// A resource allocated by the graph or a built-in resource, like [RenderGraph::SCENE].
+    /// Returns `None` if the resource isn't available for this view.
+    pub fn target(&self, name: &str) -> Option<&'a OffscreenRenderTarget> {
+        self.targets
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, target)| *target)
+    }
+
+    /// Begins a render pass that draws to the [Camera]'s target.
+    pub fn begin_camera_render_pass(&mut self) -> RenderPass<'_> {
+        let framebuffer = match self.camera_target {
+            Some(target) => target.framebuffer(),
+            None => &self.graphics.current_target_framebuffer,
+        };
+        let mut render_pass = self
+            .command_buffer
+            .begin_render_pass_with_framebuffer(framebuffer, None);
+        render_pass.set_viewport(0, 0, self.view_size.x as u32, self.view_size.y as u32);
+        render_pass
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom_pass(name: &str) -> RenderGraphPass {
+        RenderGraphPass::new(name, |_| {})
+    }
+
+    fn pass_order(render_graph: &mut RenderGraph) -> Vec<String> {
+        let compiled = render_graph.compiled().unwrap().clone();
+        compiled
+            .order
+            .iter()
+            .map(|&i| render_graph.passes[i].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn built_in_passes_keep_their_order() {
+        let mut render_graph = RenderGraph::new();
+        assert_eq!(
+            pass_order(&mut render_graph),
+            [
+                "shadows",
+                "ambient_occlusion",
+                "scene",
+                "anti_aliasing",
+                "bloom",
+                "post_process"
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_passes_are_ordered_by_resources() {
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_resource(
+            "outline_mask",
+            RenderGraphResource::color(PixelFormat::RGBA8Unorm),
+        );
+        // Added before the mask is written, but must run after.
+        render_graph.add_pass(
+            custom_pass("outline")
+                .reads("outline_mask")
+                .reads(RenderGraph::SCENE)
+                .writes(RenderGraph::SCENE),
+        );
+        render_graph.add_pass(custom_pass("outline_mask").writes("outline_mask"));
+        render_graph.add_pass(
+            custom_pass("ui")
+                .reads(RenderGraph::CAMERA_TARGET)
+                .writes(RenderGraph::CAMERA_TARGET),
+        );
+
+        assert_eq!(
+            pass_order(&mut render_graph),
+            [
+                "shadows",
+                "ambient_occlusion",
+                "scene",
+                "outline_mask",
+                "outline",
+                "anti_aliasing",
+                "bloom",
+                "post_process",
+                "ui"
+            ]
+        );
+    }
+
+    #[test]
+    fn built_in_passes_can_be_removed() {
+        let mut render_graph = RenderGraph::new();
+        assert!(render_graph.remove_pass("bloom"));
+        // Post-processing still reads bloom but falls back to no bloom.
+        assert_eq!(render_graph.validate(), Ok(()));
+        assert!(!pass_order(&mut render_graph).contains(&"bloom".to_string()));
+
+        // A custom pass can't read what nothing writes.
+        render_graph.add_pass(custom_pass("reads_bloom").reads(RenderGraph::BLOOM));
+        assert_eq!(
+            render_graph.validate(),
+            Err(RenderGraphError::NeverWritten {
+                pass: "reads_bloom".into(),
+                resource: RenderGraph::BLOOM.into()
+            })
+        );
+    }
+
+    #[test]
+    fn unused_passes_are_culled() {
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_resource(
+            "unused",
+            RenderGraphResource::color(PixelFormat::RGBA8Unorm),
+        );
+        render_graph.add_pass(custom_pass("culled").writes("unused"));
+        render_graph.add_pass(custom_pass("kept").never_cull());
+
+        assert!(!pass_order(&mut render_graph).contains(&"culled".to_string()));
+        assert!(pass_order(&mut render_graph).contains(&"kept".to_string()));
+        assert!(render_graph.debug_dump().contains("Culled:\n  culled\n"));
+    }
+
+    #[test]
+    fn resources_with_separate_lifetimes_share_targets() {
+        let mut render_graph = RenderGraph::new();
+        let half_size = RenderGraphResource::color(PixelFormat::RGBA16F)
+            .with_size(RenderGraphSize::ViewScaled(0.5));
+        render_graph.add_resource("blur_horizontal", half_size);
+        render_graph.add_resource("blur_vertical", half_size);
+        render_graph.add_resource("blur_result", half_size);
+        render_graph.add_pass(
+            custom_pass("horizontal")
+                .reads(RenderGraph::SCENE)
+                .writes("blur_horizontal"),
+        );
+        render_graph.add_pass(
+            custom_pass("vertical")
+                .reads("blur_horizontal")
+                .writes("blur_vertical"),
+        );
+        render_graph.add_pass(
+            custom_pass("result")
+                .reads("blur_vertical")
+                .writes("blur_result"),
+        );
+        render_graph.add_pass(
+            custom_pass("composite")
+                .reads("blur_result")
+                .writes(RenderGraph::CAMERA_TARGET),
+        );
+
+        let compiled = render_graph.compiled().unwrap();
+        let target = |name: &str| {
+            compiled
+                .allocations
+                .iter()
+                .find(|a| a.name == name)
+                .unwrap()
+                .target
+        };
+        // `blur_horizontal` is done once `blur_vertical` is written so `blur_result` can reuse it.
+        assert_ne!(target("blur_horizontal"), target("blur_vertical"));
+        assert_eq!(target("blur_horizontal"), target("blur_result"));
+        assert_eq!(compiled.targets.len(), 2);
+        assert_eq!(
+            compiled.targets[0].size_for_view(Vec2u::new(100, 50)),
+            Vec2u::new(50, 25)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(custom_pass("typo").reads("scnee"));
+        assert_eq!(
+            render_graph.validate(),
+            Err(RenderGraphError::UnknownResource {
+                pass: "typo".into(),
+                resource: "scnee".into()
+            })
+        );
+        assert!(render_graph.remove_pass("typo"));
+        assert!(!render_graph.remove_pass("typo"));
+
+        render_graph.add_pass(custom_pass("reads_shadows").reads(RenderGraph::SHADOW_MAPS));
+        assert_eq!(
+            render_graph.validate(),
+            Err(RenderGraphError::NotReadable {
+                pass: "reads_shadows".into(),
+                resource: RenderGraph::SHADOW_MAPS.into()
+            })
+        );
+        assert!(render_graph.remove_pass("reads_shadows"));
+
+        render_graph.add_resource("a", RenderGraphResource::color(PixelFormat::RGBA8Unorm));
+        render_graph.add_resource("b", RenderGraphResource::color(PixelFormat::RGBA8Unorm));
+        render_graph.add_pass(custom_pass("reads_a").reads("a").never_cull());
+        assert_eq!(
+            render_graph.validate(),
+            Err(RenderGraphError::NeverWritten {
+                pass: "reads_a".into(),
+                resource: "a".into()
+            })
+        );
+        render_graph.add_pass(custom_pass("a_to_b").reads("a").writes("b"));
+        render_graph.add_pass(custom_pass("b_to_a").reads("b").writes("a"));
+        assert_eq!(
+            render_graph.validate(),
+            Err(RenderGraphError::Cycle(vec![
+                "reads_a".into(),
+                "a_to_b".into(),
+                "b_to_a".into()
+            ]))
+        );
+    }
+
+    /// Steps through a graph the way the renderer does, with the backend that draws nothing.
+    #[cfg(feature = "headless")]
+    #[test]
+    fn runs_on_do_nothing_backend() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let mut world = World::new();
+        world.spawn(CommandQueue::new());
+        crate::graphics::setup_graphics(&mut world);
+        world.spawn((Transform::new(), Camera::new()));
+        update_root_global_transforms.run(&mut world);
+        apply_commands(&mut world);
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_resource(
+            "mask",
+            RenderGraphResource::color(PixelFormat::RGBA8Unorm)
+                .with_size(RenderGraphSize::ViewScaled(0.5)),
+        );
+        let mask_runs = runs.clone();
+        render_graph.add_pass(
+            RenderGraphPass::new("mask", move |context| {
+                assert!(context.target("mask").is_some());
+                assert!(context.target(RenderGraph::BLOOM).is_none());
+                context.begin_camera_render_pass();
+                mask_runs.fetch_add(1, Ordering::Relaxed);
+            })
+            .writes("mask"),
+        );
+        let composite_runs = runs.clone();
+        render_graph.add_pass(
+            RenderGraphPass::new("composite", move |context| {
+                assert!(context.target("mask").is_some());
+                assert!(context.target(RenderGraph::SCENE).is_some());
+                composite_runs.fetch_add(1, Ordering::Relaxed);
+            })
+            .reads("mask")
+            .reads(RenderGraph::SCENE)
+            .writes(RenderGraph::SCENE),
+        );
+        assert!(render_graph.remove_pass("bloom"));
+
+        (|graphics: &mut Graphics,
+          textures: &mut Assets<Texture>,
+          shaders: &Assets<Shader>,
+          meshes: &Assets<Mesh>,
+          cameras: Query<(&GlobalTransform, &Camera)>| {
+            let (camera_global_transform, camera) = cameras.iter().next().unwrap();
+            let view_size = Vec2u::new(64, 32);
+            let scene = RenderGraphResource::color(PixelFormat::RGBA16F)
+                .with_depth(PixelFormat::Depth32F)
+                .new_target(graphics, textures, view_size);
+            let mut command_buffer = graphics.context.new_command_buffer();
+
+            let mut order = Vec::new();
+            render_graph.begin_view(graphics, textures, view_size);
+            while let Some(pass) = render_graph.next_pass() {
+                match pass {
+                    ViewPass::BuiltIn(built_in_pass) => order.push(format!("{:?}", built_in_pass)),
+                    ViewPass::Custom(index) => {
+                        order.push(render_graph.passes[index].name.clone());
+                        render_graph.run_custom_pass(
+                            index,
+                            &mut RenderGraphContext::new(
+                                graphics,
+                                &mut command_buffer,
+                                textures,
+                                shaders,
+                                meshes,
+                                camera,
+                                camera_global_transform,
+                                view_size,
+                                None,
+                                vec![(RenderGraph::SCENE, &scene)],
+                            ),
+                        );
+                    }
+                }
+            }
+            graphics.context.commit_command_buffer(command_buffer);
+
+            assert_eq!(
+                order,
+                [
+                    "Shadows",
+                    "AmbientOcclusion",
+                    "Scene",
+                    "mask",
+                    "composite",
+                    "AntiAliasing",
+                    "PostProcess"
+                ]
+            );
+            assert_eq!(render_graph.targets.len(), 1);
+        })
+        .run(&mut world);
+
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+    }
+}
@@ -3,9 +3,17 @@ use super::*;
 // A shadow caster for a light.
 #[derive(NotCloneComponent)]
 pub struct ShadowCaster {
+    /// The cascades of a [LightMode::Directional] light, or the single shadow map of a [LightMode::Spot] light.
     pub shadow_cascades: Vec<ShadowCascadeInfo>,
+    /// The shadow cube map of a [LightMode::Point] light.
+    pub point_shadow_map: Option<PointShadowMap>,
     pub(crate) texture_size: u32,
     pub ibl_shadowing: f32,
+    /// How far a surface must be behind the closest surface to be shadowed.
+    /// Increase this if shadows have acne, decrease it if shadows detach from their casters.
+    pub bias: f32,
+    /// How far from a [LightMode::Point] light shadows are cast.
+    pub point_shadow_range: f32,
 }
 
 impl Default for ShadowCaster {
@@ -18,8 +26,11 @@ impl ShadowCaster {
     pub fn new() -> Self {
         Self {
             shadow_cascades: Vec::new(),
+            point_shadow_map: None,
             texture_size: 2048,
             ibl_shadowing: 0.0,
+            bias: 0.0002,
+            point_shadow_range: 50.0,
         }
     }
 
@@ -27,6 +38,22 @@ impl ShadowCaster {
         self.ibl_shadowing = ibl_shadowing;
         self
     }
+
+    pub fn with_bias(mut self, bias: f32) -> Self {
+        self.bias = bias;
+        self
+    }
+
+    /// Sets the width and height of each shadow map.
+    pub fn with_resolution(mut self, resolution: u32) -> Self {
+        self.texture_size = resolution;
+        self
+    }
+
+    pub fn with_point_shadow_range(mut self, range: f32) -> Self {
+        self.point_shadow_range = range;
+        self
+    }
 }
 
 pub struct ShadowCascadeInfo {
@@ -34,15 +61,53 @@ pub struct ShadowCascadeInfo {
     pub(crate) world_to_light_space: Mat4,
 }
 
+/// Stores the distance from a point light to the closest surface in every direction.
+pub struct PointShadowMap {
+    pub cube_map: Handle<CubeMap>,
+    // Shared by every face.
+    depth_texture: Handle<Texture>,
+    face_framebuffers: Vec<NotSendSync<Framebuffer>>,
+}
+
+impl PointShadowMap {
+    /// Deletes the face framebuffers and releases the cube map and depth texture,
+    /// which are deleted by their [Assets] once nothing else holds their [Handle]s.
+    pub fn delete(self, graphics: &mut Graphics) {
+        for framebuffer in self.face_framebuffers {
+            graphics.context.delete_framebuffer(framebuffer.take());
+        }
+        drop(self.cube_map);
+        drop(self.depth_texture);
+    }
+}
+
 impl ShadowCaster {
     pub fn prepare_shadow_casting(
         &mut self,
         graphics: &mut Graphics,
         textures: &mut Assets<Texture>,
+        cube_maps: &mut Assets<CubeMap>,
+        light_mode: &LightMode,
     ) {
+        let shadow_map_count = match light_mode {
+            LightMode::Directional => 4,
+            LightMode::Spot { .. } => 1,
+            LightMode::Point { .. } => 0,
+        };
+
+        // The light's mode may have changed since the shadow maps were created.
+        if self.shadow_cascades.len() != shadow_map_count {
+            self.shadow_cascades.clear();
+        }
+        if !matches!(light_mode, LightMode::Point { .. }) {
+            if let Some(point_shadow_map) = self.point_shadow_map.take() {
+                point_shadow_map.delete(graphics);
+            }
+        }
+
         if self.shadow_cascades.is_empty() {
             // Setup shadow textures
-            for _ in 0..4 {
+            for _ in 0..shadow_map_count {
                 let offscreen_render_target = OffscreenRenderTarget::new(
                     graphics,
                     textures,
@@ -69,6 +134,55 @@ impl ShadowCaster {
                 });
             }
         }
+
+        if matches!(light_mode, LightMode::Point { .. }) && self.point_shadow_map.is_none() {
+            let texture_settings = TextureSettings {
+                minification_filter: kgraphics::FilterMode::Nearest,
+                magnification_filter: kgraphics::FilterMode::Nearest,
+                wrapping_horizontal: kgraphics::WrappingMode::ClampToEdge,
+                wrapping_vertical: kgraphics::WrappingMode::ClampToEdge,
+                srgb: false,
+                generate_mipmaps: false,
+                ..Default::default()
+            };
+
+            // Distances are stored in a color cube map because depth cube maps aren't supported everywhere.
+            let cube_map = graphics
+                .new_cube_map(
+                    None,
+                    self.texture_size,
+                    self.texture_size,
+                    kgraphics::PixelFormat::RGBA16F,
+                    texture_settings,
+                )
+                .unwrap();
+            let depth_texture = graphics
+                .new_texture(
+                    None,
+                    self.texture_size,
+                    self.texture_size,
+                    1,
+                    kgraphics::PixelFormat::Depth32F,
+                    texture_settings,
+                )
+                .unwrap();
+
+            let face_framebuffers = (0..6)
+                .map(|i| {
+                    NotSendSync::new(graphics.context.new_framebuffer(
+                        Some(&cube_map.get_face_texture(i)),
+                        Some(&depth_texture),
+                        None,
+                    ))
+                })
+                .collect();
+
+            self.point_shadow_map = Some(PointShadowMap {
+                cube_map: cube_maps.add(cube_map),
+                depth_texture: textures.add(depth_texture),
+                face_framebuffers,
+            });
+        }
     }
 }
 
@@ -95,8 +209,53 @@ pub fn render_shadow_pass(
     }
 
     // In the future this could be reduced to light's that area of influence overlaps the camera's frustum.
-    for (light_global_transform, _light, shadow_caster) in lights {
+    for (light_global_transform, light, shadow_caster) in lights {
         if let Some(shadow_caster) = shadow_caster {
+            match light.light_mode {
+                LightMode::Directional => {}
+                LightMode::Spot {
+                    outer_angle, range, ..
+                } => {
+                    if let Some(shadow_map) = shadow_caster.shadow_cascades.first_mut() {
+                        let view_matrix = light_global_transform.model().inversed();
+                        let projection_matrix = kmath::projection_matrices::perspective_gl(
+                            outer_angle * 2.0,
+                            1.0,
+                            0.05,
+                            range,
+                        );
+                        shadow_map.world_to_light_space = projection_matrix * view_matrix;
+
+                        render_depth_only(
+                            shaders,
+                            meshes,
+                            command_buffer,
+                            shadow_map.offscreen_render_target.framebuffer(),
+                            &view_matrix,
+                            &projection_matrix,
+                            shadow_caster.texture_size,
+                            renderables,
+                        );
+                    }
+                    continue;
+                }
+                LightMode::Point { .. } => {
+                    if let Some(point_shadow_map) = shadow_caster.point_shadow_map.as_ref() {
+                        render_point_shadow(
+                            shaders,
+                            meshes,
+                            command_buffer,
+                            point_shadow_map,
+                            light_global_transform.position,
+                            shadow_caster.point_shadow_range,
+                            shadow_caster.texture_size,
+                            renderables,
+                        );
+                    }
+                    continue;
+                }
+            }
+
             // Render shadow map cascades
             for (i, cascade) in shadow_caster.shadow_cascades.iter_mut().enumerate() {
                 let view_matrix = light_global_transform.model().inversed();
@@ -210,9 +369,88 @@ pub fn render_depth_only(
         .get_vertex_attribute::<Vec3>("a_position")
         .unwrap();
 
-    let culling_frustum = Frustum::from_matrix(*projection_matrix * *view_matrix);
+    draw_shadow_casters(
+        &mut render_pass,
+        meshes,
+        &model_property,
+        &position_attribute,
+        &Frustum::from_matrix(*projection_matrix * *view_matrix),
+        renderables,
+    );
+}
 
-    for (global_transform, _, mesh_handle, render_flags, _, _) in renderables {
+/// Renders the distance to a point light into each face of its [PointShadowMap].
+#[allow(clippy::too_many_arguments)]
+pub fn render_point_shadow(
+    shaders: &Assets<Shader>,
+    meshes: &Assets<Mesh>,
+    command_buffer: &mut CommandBuffer,
+    point_shadow_map: &PointShadowMap,
+    light_position: Vec3,
+    range: f32,
+    viewport_size: u32,
+    renderables: &Renderables,
+) {
+    let projection_matrix =
+        kmath::projection_matrices::perspective_gl(90.0_f32.to_radians(), 1.0, 0.05, range);
+
+    // The same face orientations used to render other cube maps.
+    let directions = [
+        (Vec3::X, -Vec3::Y),
+        (-Vec3::X, -Vec3::Y),
+        (Vec3::Y, Vec3::Z),
+        (-Vec3::Y, -Vec3::Z),
+        (Vec3::Z, -Vec3::Y),
+        (-Vec3::Z, -Vec3::Y),
+    ];
+
+    let point_shadow_shader = shaders.get(&Shader::POINT_SHADOW);
+    let pipeline = &point_shadow_shader.pipeline;
+    let view_property = pipeline.get_mat4_property("p_views[0]").unwrap();
+    let projection_property = pipeline.get_mat4_property("p_projections[0]").unwrap();
+    let light_position_property = pipeline.get_vec3_property("p_light_position").unwrap();
+    let range_property = pipeline.get_float_property("p_range").unwrap();
+    let model_property = pipeline.get_mat4_property("p_model").unwrap();
+    let position_attribute = pipeline.get_vertex_attribute::<Vec3>("a_position").unwrap();
+
+    for ((direction, up), framebuffer) in directions
+        .iter()
+        .zip(point_shadow_map.face_framebuffers.iter())
+    {
+        let view_matrix = Mat4::looking_at(light_position, light_position + *direction, *up);
+
+        // Clear to the furthest distance so empty directions are never shadowed.
+        let mut render_pass = command_buffer
+            .begin_render_pass_with_framebuffer(framebuffer, Some((1.0, 1.0, 1.0, 1.0)));
+        render_pass.set_viewport(0, 0, viewport_size, viewport_size);
+        render_pass.set_depth_mask(true);
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_mat4_property(&view_property, view_matrix.as_array());
+        render_pass.set_mat4_property(&projection_property, projection_matrix.as_array());
+        render_pass.set_vec3_property(&light_position_property, light_position.into());
+        render_pass.set_float_property(&range_property, range);
+
+        draw_shadow_casters(
+            &mut render_pass,
+            meshes,
+            &model_property,
+            &position_attribute,
+            &Frustum::from_matrix(projection_matrix * view_matrix),
+            renderables,
+        );
+    }
+}
+
+fn draw_shadow_casters(
+    render_pass: &mut RenderPass,
+    meshes: &Assets<Mesh>,
+    model_property: &Mat4Property,
+    position_attribute: &VertexAttribute<Vec3>,
+    culling_frustum: &Frustum,
+    renderables: &Renderables,
+) {
+    for (global_transform, _, mesh_handle, render_flags, ..) in renderables {
         let render_flags = render_flags.cloned().unwrap_or(RenderFlags::DEFAULT);
         if render_flags.includes_layer(RenderFlags::DEFAULT)
             && !render_flags.includes_layer(RenderFlags::DO_NOT_CAST_SHADOWS)
@@ -220,15 +458,14 @@ pub fn render_depth_only(
             let mesh = meshes.get(mesh_handle);
             let should_render = render_flags.includes_layer(RenderFlags::IGNORE_CULLING)
                 || meshes.get(mesh_handle).bounding_box.map_or(true, |b| {
-                    frustum_with_bounding_box(&culling_frustum, global_transform.model(), b)
+                    frustum_with_bounding_box(culling_frustum, global_transform.model(), b)
                 });
 
             if should_render {
                 if let Some(gpu_mesh) = mesh.gpu_mesh.as_ref() {
                     render_pass
-                        .set_mat4_property(&model_property, global_transform.model().as_array());
-                    render_pass
-                        .set_vertex_attribute(&position_attribute, Some(&gpu_mesh.positions));
+                        .set_mat4_property(model_property, global_transform.model().as_array());
+                    render_pass.set_vertex_attribute(position_attribute, Some(&gpu_mesh.positions));
                     render_pass.draw_triangles(gpu_mesh.triangle_count, &gpu_mesh.index_buffer);
                 }
             }
@@ -1,12 +1,83 @@
 use crate::*;
 
+pub fn sprite_plugin() -> Plugin {
+    Plugin {
+        fixed_update_systems: vec![animate_sprites.system()],
+        ..Default::default()
+    }
+}
+
 /// [Sprite]s draw as a subset of a [Texture].
 /// Perfect for sprite-sheets, tilemaps, animated images.
+///
+/// [Sprite]s face along their [Transform]'s +Z and are drawn after the scene's [Mesh]es,
+/// batched together with the [Sprite]s around them that share their [Texture].
+/// Put [Sprite]s that share a [Texture] in a [SpriteMap] so they can be batched together.
+///
+/// If the [Entity] also has a [Mesh] the [Sprite] instead textures the [Mesh]'s [Material],
+/// and only [Sprite::texture_handle] and [Sprite::sprite_source_bounds] are used.
 #[derive(Component, Clone, Debug)]
 pub struct Sprite {
     pub texture_handle: Handle<Texture>,
     /// The a rectangle specified in percentage of the texture, not pixels!
     pub sprite_source_bounds: Box2,
+    /// The size of the [Sprite] before its [Transform] is applied.
+    /// The default value is 1.0 by 1.0.
+    pub size: Vec2,
+    /// The point of the [Sprite] placed at its [Transform]'s position.
+    /// (0.0, 0.0) is the bottom left corner and (1.0, 1.0) is the top right corner.
+    /// The default value is the center.
+    pub pivot: Vec2,
+    /// Mirrors the [Sprite] horizontally within its rectangle.
+    pub flip_x: bool,
+    /// Mirrors the [Sprite] vertically within its rectangle.
+    pub flip_y: bool,
+    /// Multiplies the [Sprite]'s [Texture].
+    pub tint: Color,
+    /// [Sprite]s on higher layers are drawn over [Sprite]s on lower layers.
+    pub sorting_layer: i32,
+    /// Within a layer [Sprite]s with a higher order are drawn over [Sprite]s with a lower order.
+    /// [Sprite]s with the same layer and order are drawn from back to front.
+    pub order_in_layer: i32,
+    /// Keeps the [Sprite]'s borders from stretching when [Sprite::size] changes.
+    pub nine_slice: Option<NineSlice>,
+}
+
+/// Splits a [Sprite] into a 3 by 3 grid so that its corners keep their size
+/// and its edges only stretch along their length. Useful for panels and buttons.
+#[derive(Clone, Copy, Debug)]
+pub struct NineSlice {
+    /// The width of the left and height of the bottom border in the [Texture],
+    /// specified in percentage of the texture like [Sprite::sprite_source_bounds].
+    pub source_min_borders: Vec2,
+    /// The width of the right and height of the top border in the [Texture],
+    /// specified in percentage of the texture like [Sprite::sprite_source_bounds].
+    pub source_max_borders: Vec2,
+    /// The drawn width of the left and height of the bottom border, in the same units as [Sprite::size].
+    pub min_borders: Vec2,
+    /// The drawn width of the right and height of the top border, in the same units as [Sprite::size].
+    pub max_borders: Vec2,
+}
+
+impl NineSlice {
+    /// Borders that are the same on every side.
+    /// `source_border` is the border's width and height in percentage of the texture.
+    pub fn uniform(source_border: Vec2, border: f32) -> Self {
+        Self {
+            source_min_borders: source_border,
+            source_max_borders: source_border,
+            min_borders: Vec2::fill(border),
+            max_borders: Vec2::fill(border),
+        }
+    }
+}
+
+/// A corner of one of a [Sprite]'s quads.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct SpriteVertex {
+    /// Relative to the [Sprite]'s pivot, before its [Transform] is applied.
+    pub position: Vec2,
+    pub texture_coordinate: Vec2,
 }
 
 impl Sprite {
@@ -15,6 +86,113 @@ impl Sprite {
         Self {
             texture_handle,
             sprite_source_bounds,
+            size: Vec2::ONE,
+            pivot: Vec2::fill(0.5),
+            flip_x: false,
+            flip_y: false,
+            tint: Color::WHITE,
+            sorting_layer: 0,
+            order_in_layer: 0,
+            nine_slice: None,
+        }
+    }
+
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_pivot(mut self, pivot: Vec2) -> Self {
+        self.pivot = pivot;
+        self
+    }
+
+    pub fn with_tint(mut self, tint: Color) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn with_sorting(mut self, sorting_layer: i32, order_in_layer: i32) -> Self {
+        self.sorting_layer = sorting_layer;
+        self.order_in_layer = order_in_layer;
+        self
+    }
+
+    pub fn with_nine_slice(mut self, nine_slice: NineSlice) -> Self {
+        self.nine_slice = Some(nine_slice);
+        self
+    }
+
+    /// Appends the quads that draw this [Sprite]. Each quad's corners are the bottom left, bottom right,
+    /// top right then top left before flipping. Nine-sliced [Sprite]s have nine quads and others have one.
+    pub(crate) fn quads(&self, quads: &mut Vec<[SpriteVertex; 4]>) {
+        let min = -self.pivot.mul_by_component(self.size);
+        let max = min + self.size;
+        let source = self.sprite_source_bounds;
+
+        // Texture rows go down the image, so the bottom of the sprite uses the largest texture coordinate.
+        let (cells, xs, us, ys, vs) = match &self.nine_slice {
+            Some(nine_slice) => {
+                // Borders shrink to fit [Sprite]s smaller than their borders.
+                let fit = |min_border: f32, max_border: f32, size: f32| {
+                    let scale = (size.abs() / (min_border + max_border)).min(1.0);
+                    (min_border * scale, max_border * scale)
+                };
+                let (left, right) = fit(
+                    nine_slice.min_borders.x,
+                    nine_slice.max_borders.x,
+                    self.size.x,
+                );
+                let (bottom, top) = fit(
+                    nine_slice.min_borders.y,
+                    nine_slice.max_borders.y,
+                    self.size.y,
+                );
+                (
+                    3,
+                    [min.x, min.x + left, max.x - right, max.x],
+                    [
+                        source.min.x,
+                        source.min.x + nine_slice.source_min_borders.x,
+                        source.max.x - nine_slice.source_max_borders.x,
+                        source.max.x,
+                    ],
+                    [min.y, min.y + bottom, max.y - top, max.y],
+                    [
+                        source.max.y,
+                        source.max.y - nine_slice.source_min_borders.y,
+                        source.min.y + nine_slice.source_max_borders.y,
+                        source.min.y,
+                    ],
+                )
+            }
+            None => (
+                1,
+                [min.x, max.x, 0.0, 0.0],
+                [source.min.x, source.max.x, 0.0, 0.0],
+                [min.y, max.y, 0.0, 0.0],
+                [source.max.y, source.min.y, 0.0, 0.0],
+            ),
+        };
+
+        // Flipping mirrors the quads within the sprite's rectangle so that uneven borders flip too.
+        let mirror = |flip: bool, p: f32, min: f32, max: f32| if flip { min + max - p } else { p };
+        let vertex = |column: usize, row: usize| SpriteVertex {
+            position: Vec2::new(
+                mirror(self.flip_x, xs[column], min.x, max.x),
+                mirror(self.flip_y, ys[row], min.y, max.y),
+            ),
+            texture_coordinate: Vec2::new(us[column], vs[row]),
+        };
+        for row in 0..cells {
+            for column in 0..cells {
+                quads.push([
+                    vertex(column, row),
+                    vertex(column + 1, row),
+                    vertex(column + 1, row + 1),
+                    vertex(column, row + 1),
+                ]);
+            }
         }
     }
 }
@@ -47,14 +225,341 @@ impl SpriteMap {
         }
     }
 
-    pub fn get_sprite(&self, x: usize, y: usize) -> Sprite {
+    pub fn texture_handle(&self) -> &Handle<Texture> {
+        &self.texture_handle
+    }
+
+    /// The bounds of a tile, specified in percentage of the texture like [Sprite::sprite_source_bounds].
+    pub fn sprite_source_bounds(&self, x: usize, y: usize) -> Box2 {
         let xy = Vec2::new(x as f32, y as f32);
-        Sprite {
-            texture_handle: self.texture_handle.clone(),
-            sprite_source_bounds: Box2::new_with_min_corner_and_size(
-                xy.mul_by_component(self.scale),
-                self.scale - self.padding_scale,
-            ),
+        Box2::new_with_min_corner_and_size(
+            xy.mul_by_component(self.scale),
+            self.scale - self.padding_scale,
+        )
+    }
+
+    pub fn get_sprite(&self, x: usize, y: usize) -> Sprite {
+        Sprite::new(self.texture_handle.clone(), self.sprite_source_bounds(x, y))
+    }
+}
+
+/// A frame of a [SpriteAnimation].
+#[derive(Clone, Debug)]
+pub struct SpriteAnimationFrame {
+    /// The column of the frame's tile in the [SpriteMap].
+    pub x: usize,
+    /// The row of the frame's tile in the [SpriteMap].
+    pub y: usize,
+    /// How many seconds the frame is shown for.
+    pub duration: f32,
+    /// Reported by [SpriteAnimation::events] when the frame begins.
+    pub event: Option<String>,
+}
+
+impl SpriteAnimationFrame {
+    pub fn new(x: usize, y: usize, duration: f32) -> Self {
+        Self {
+            x,
+            y,
+            duration,
+            event: None,
+        }
+    }
+
+    pub fn with_event(mut self, event: &str) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+}
+
+/// Steps an [Entity]'s [Sprite] through frames of a [SpriteMap].
+/// The [Sprite]'s other settings, like its size and tint, are left alone.
+#[derive(Component, Clone, Debug)]
+pub struct SpriteAnimation {
+    pub sprite_map: SpriteMap,
+    pub frames: Vec<SpriteAnimationFrame>,
+    /// If `false` the animation stops on its last frame.
+    /// The default value is `true`.
+    pub looping: bool,
+    /// Multiplies how quickly frames advance.
+    /// The default value is 1.0.
+    pub speed: f32,
+    /// Set to `false` to pause on the current frame.
+    pub playing: bool,
+    frame: usize,
+    /// Seconds since the current frame began.
+    frame_time: f32,
+    /// If the current frame's event has been reported.
+    frame_started: bool,
+    events: Vec<String>,
+}
+
+impl SpriteAnimation {
+    pub fn new(sprite_map: SpriteMap, frames: Vec<SpriteAnimationFrame>) -> Self {
+        Self {
+            sprite_map,
+            frames,
+            looping: true,
+            speed: 1.0,
+            playing: true,
+            frame: 0,
+            frame_time: 0.0,
+            frame_started: false,
+            events: Vec::new(),
+        }
+    }
+
+    /// Every frame shows for the same number of seconds.
+    pub fn from_tiles(
+        sprite_map: SpriteMap,
+        tiles: &[(usize, usize)],
+        frames_per_second: f32,
+    ) -> Self {
+        let frames = tiles
+            .iter()
+            .map(|&(x, y)| SpriteAnimationFrame::new(x, y, 1.0 / frames_per_second))
+            .collect();
+        Self::new(sprite_map, frames)
+    }
+
+    /// The index of the frame currently shown.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Jumps to a frame. Its event is reported when the animation next advances.
+    pub fn set_frame(&mut self, frame: usize) {
+        self.frame = frame.min(self.frames.len().saturating_sub(1));
+        self.frame_time = 0.0;
+        self.frame_started = false;
+    }
+
+    /// Starts the animation again from its first frame.
+    pub fn restart(&mut self) {
+        self.set_frame(0);
+        self.playing = true;
+    }
+
+    /// Returns `true` if a non-looping animation has shown its last frame for its full duration.
+    pub fn finished(&self) -> bool {
+        !self.looping
+            && self.frame + 1 >= self.frames.len()
+            && self
+                .frames
+                .get(self.frame)
+                .is_none_or(|frame| self.frame_time >= frame.duration)
+    }
+
+    /// The events of the frames that began during the animation's most recent update.
+    pub fn events(&self) -> &[String] {
+        &self.events
+    }
+
+    /// Advances the animation by `delta_seconds`.
+    pub fn advance(&mut self, delta_seconds: f32) {
+        self.events.clear();
+        if !self.playing || self.frames.is_empty() {
+            return;
         }
+        self.frame = self.frame.min(self.frames.len() - 1);
+
+        if !self.frame_started {
+            self.begin_frame();
+        }
+        self.frame_time += delta_seconds * self.speed;
+
+        // Each frame is shown at least once per update so that very short frames can't stall the app.
+        for _ in 0..self.frames.len() {
+            let duration = self.frames[self.frame].duration;
+            if self.frame_time < duration {
+                return;
+            }
+            if !self.looping && self.frame + 1 == self.frames.len() {
+                self.frame_time = duration;
+                return;
+            }
+            self.frame_time -= duration;
+            self.frame = (self.frame + 1) % self.frames.len();
+            self.begin_frame();
+        }
+        self.frame_time = self.frame_time.min(self.frames[self.frame].duration);
+    }
+
+    fn begin_frame(&mut self) {
+        self.frame_started = true;
+        if let Some(event) = &self.frames[self.frame].event {
+            self.events.push(event.clone());
+        }
+    }
+
+    /// Shows the current frame on `sprite`.
+    pub fn apply(&self, sprite: &mut Sprite) {
+        if let Some(frame) = self.frames.get(self.frame) {
+            sprite.texture_handle = self.sprite_map.texture_handle().clone();
+            sprite.sprite_source_bounds = self.sprite_map.sprite_source_bounds(frame.x, frame.y);
+        }
+    }
+}
+
+fn animate_sprites(time: &Time, mut animations: Query<(&mut SpriteAnimation, &mut Sprite)>) {
+    let delta_seconds = time.fixed_time_step as f32;
+    for (animation, sprite) in &mut animations {
+        animation.advance(delta_seconds);
+        animation.apply(sprite);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprite() -> Sprite {
+        Sprite::new(
+            Texture::WHITE,
+            Box2::new(Vec2::new(0.5, 0.0), Vec2::new(1.0, 0.5)),
+        )
+    }
+
+    fn quads(sprite: &Sprite) -> Vec<[SpriteVertex; 4]> {
+        let mut quads = Vec::new();
+        sprite.quads(&mut quads);
+        quads
+    }
+
+    #[test]
+    fn sprite_quad_around_pivot() {
+        let sprite = sprite().with_size(Vec2::new(2.0, 4.0));
+        assert_eq!(quads(&sprite).len(), 1);
+        let quad = quads(&sprite)[0];
+        let positions: Vec<Vec2> = quad.iter().map(|v| v.position).collect();
+        assert_eq!(
+            positions,
+            [
+                Vec2::new(-1.0, -2.0),
+                Vec2::new(1.0, -2.0),
+                Vec2::new(1.0, 2.0),
+                Vec2::new(-1.0, 2.0)
+            ]
+        );
+
+        // The bottom of the sprite shows the bottom of its source bounds.
+        let texture_coordinates: Vec<Vec2> = quad.iter().map(|v| v.texture_coordinate).collect();
+        assert_eq!(
+            texture_coordinates,
+            [
+                Vec2::new(0.5, 0.5),
+                Vec2::new(1.0, 0.5),
+                Vec2::new(1.0, 0.0),
+                Vec2::new(0.5, 0.0)
+            ]
+        );
+
+        let quad = quads(&sprite.with_pivot(Vec2::ZERO))[0];
+        assert_eq!(quad[0].position, Vec2::ZERO);
+        assert_eq!(quad[2].position, Vec2::new(2.0, 4.0));
+    }
+
+    #[test]
+    fn flipped_sprite_mirrors_texture() {
+        let mut flipped = sprite();
+        flipped.flip_x = true;
+        let quad = quads(&flipped)[0];
+        // The left corner now shows the right of the source bounds.
+        let left = quad.iter().find(|v| v.position == Vec2::new(-0.5, -0.5));
+        assert_eq!(left.unwrap().texture_coordinate, Vec2::new(1.0, 0.5));
+    }
+
+    #[test]
+    fn nine_slice_keeps_border_size() {
+        let nine_slice = NineSlice::uniform(Vec2::fill(0.1), 0.25);
+        let quads = quads(
+            &sprite()
+                .with_size(Vec2::new(4.0, 2.0))
+                .with_pivot(Vec2::ZERO)
+                .with_nine_slice(nine_slice),
+        );
+        assert_eq!(quads.len(), 9);
+
+        // The bottom left corner is drawn at the border size.
+        assert_eq!(quads[0][0].position, Vec2::ZERO);
+        assert_eq!(quads[0][2].position, Vec2::fill(0.25));
+        assert_eq!(quads[0][2].texture_coordinate, Vec2::new(0.6, 0.4));
+
+        // The center stretches to fill the rest.
+        assert_eq!(quads[4][0].position, Vec2::fill(0.25));
+        assert_eq!(quads[4][2].position, Vec2::new(3.75, 1.75));
+    }
+
+    #[test]
+    fn nine_slice_borders_shrink_to_fit() {
+        let quads = quads(
+            &sprite()
+                .with_size(Vec2::new(0.25, 1.0))
+                .with_pivot(Vec2::ZERO)
+                .with_nine_slice(NineSlice::uniform(Vec2::fill(0.1), 0.25)),
+        );
+        assert_eq!(quads[0][2].position, Vec2::new(0.125, 0.25));
+        assert_eq!(quads[8][2].position, Vec2::new(0.25, 1.0));
+    }
+
+    fn sprite_map() -> SpriteMap {
+        SpriteMap::new(Texture::WHITE, 16, 0, 64, 64)
+    }
+
+    #[test]
+    fn animation_frame_durations() {
+        let mut animation = SpriteAnimation::new(
+            sprite_map(),
+            vec![
+                SpriteAnimationFrame::new(0, 0, 0.5),
+                SpriteAnimationFrame::new(1, 0, 0.25),
+            ],
+        );
+        animation.advance(0.4);
+        assert_eq!(animation.frame(), 0);
+        animation.advance(0.2);
+        assert_eq!(animation.frame(), 1);
+        animation.advance(0.2);
+        assert_eq!(animation.frame(), 0);
+
+        let mut sprite = sprite();
+        animation.apply(&mut sprite);
+        assert_eq!(
+            sprite.sprite_source_bounds,
+            sprite_map().sprite_source_bounds(0, 0)
+        );
+    }
+
+    #[test]
+    fn animation_events() {
+        let mut animation = SpriteAnimation::new(
+            sprite_map(),
+            vec![
+                SpriteAnimationFrame::new(0, 0, 0.1).with_event("start"),
+                SpriteAnimationFrame::new(1, 0, 0.1),
+                SpriteAnimationFrame::new(2, 0, 0.1).with_event("step"),
+            ],
+        );
+        animation.advance(0.05);
+        assert_eq!(animation.events(), ["start"]);
+        animation.advance(0.05);
+        assert!(animation.events().is_empty());
+        animation.advance(0.15);
+        assert_eq!(animation.events(), ["step"]);
+    }
+
+    #[test]
+    fn animation_stops_when_not_looping() {
+        let mut animation = SpriteAnimation::from_tiles(sprite_map(), &[(0, 0), (1, 0)], 10.0);
+        animation.looping = false;
+        animation.advance(0.15);
+        assert!(!animation.finished());
+        animation.advance(1.0);
+        assert_eq!(animation.frame(), 1);
+        assert!(animation.finished());
+
+        animation.restart();
+        assert_eq!(animation.frame(), 0);
+        assert!(!animation.finished());
     }
 }
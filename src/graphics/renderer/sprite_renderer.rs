@@ -0,0 +1,312 @@
+use super::*;
+use core::ops::Range;
+
+/// A [Sprite] prepared for drawing.
+struct PreparedSprite {
+    texture: Handle<Texture>,
+    sorting_layer: i32,
+    order_in_layer: i32,
+    render_flags: RenderFlags,
+    /// Used to sort [Sprite]s with the same layer and order.
+    position: Vec3,
+    /// The [Sprite]'s quads in [PreparedSprites::quads].
+    quads: Range<usize>,
+}
+
+/// Every [Sprite] without a [Mesh], with their quads transformed into world space.
+#[derive(Default)]
+pub(crate) struct PreparedSprites {
+    sprites: Vec<PreparedSprite>,
+    /// Each quad's corner positions, texture coordinates and color.
+    quads: Vec<([Vec3; 4], [Vec2; 4], Vec4)>,
+    /// Reused to avoid allocating.
+    local_quads: Vec<[SpriteVertex; 4]>,
+}
+
+/// Buffers reused every frame to draw batches of [Sprite]s.
+#[derive(Default)]
+pub(crate) struct SpriteBuffers {
+    positions: DataBufferPool<Vec3>,
+    texture_coordinates: DataBufferPool<Vec2>,
+    colors: DataBufferPool<Vec4>,
+    /// Every batch's quads start at vertex 0 so they share these indices.
+    index_buffer: Option<IndexBuffer>,
+    /// How many quads `index_buffer` has indices for.
+    index_buffer_quads: usize,
+}
+
+impl SpriteBuffers {
+    /// Makes the pooled buffers available again and makes sure there are indices for `quad_count` quads.
+    /// Call once per frame, after the previous frame's commands are committed.
+    pub fn reset(&mut self, graphics_context: &mut GraphicsContext, quad_count: usize) {
+        self.positions.reset();
+        self.texture_coordinates.reset();
+        self.colors.reset();
+
+        if quad_count > self.index_buffer_quads {
+            if let Some(index_buffer) = self.index_buffer.take() {
+                graphics_context.delete_index_buffer(index_buffer);
+            }
+            let quad_count = quad_count.next_power_of_two();
+            self.index_buffer = Some(
+                graphics_context
+                    .new_index_buffer(&quad_indices(quad_count))
+                    .unwrap(),
+            );
+            self.index_buffer_quads = quad_count;
+        }
+    }
+}
+
+/// Two triangles for each quad of 4 vertices.
+fn quad_indices(quad_count: usize) -> Vec<u32> {
+    (0..quad_count as u32)
+        .flat_map(|quad| [0, 1, 2, 0, 2, 3].map(|i| quad * 4 + i))
+        .collect()
+}
+
+impl PreparedSprites {
+    pub fn quad_count(&self) -> usize {
+        self.quads.len()
+    }
+}
+
+/// [Sprite]s without a [Mesh], which are drawn in batches by the sprite renderer.
+pub type Sprites<'a> = Query<
+    'a,
+    (
+        &'static GlobalTransform,
+        &'static Sprite,
+        Option<&'static RenderFlags>,
+    ),
+    Without<Handle<Mesh>>,
+>;
+
+pub fn prepare_sprites(renderer_info: &mut RendererInfo, sprites: Sprites) {
+    let prepared = &mut renderer_info.sprites;
+    prepared.sprites.clear();
+    prepared.quads.clear();
+    for (global_transform, sprite, render_flags) in &sprites {
+        let model = global_transform.model();
+        let to_world = |p: Vec2| model.transform_point(Vec3::new(p.x, p.y, 0.0));
+        let color = sprite.tint.to_rgb_color(color_spaces::LINEAR_SRGB);
+
+        prepared.local_quads.clear();
+        sprite.quads(&mut prepared.local_quads);
+
+        let start = prepared.quads.len();
+        prepared
+            .quads
+            .extend(prepared.local_quads.iter().map(|quad| {
+                (
+                    quad.map(|v| to_world(v.position)),
+                    quad.map(|v| v.texture_coordinate),
+                    color,
+                )
+            }));
+
+        prepared.sprites.push(PreparedSprite {
+            texture: sprite.texture_handle.clone(),
+            sorting_layer: sprite.sorting_layer,
+            order_in_layer: sprite.order_in_layer,
+            render_flags: render_flags.cloned().unwrap_or(RenderFlags::DEFAULT),
+            position: global_transform.position,
+            quads: start..prepared.quads.len(),
+        });
+    }
+}
+
+/// Orders [Sprite]s by layer, then by order in layer, then from back to front.
+/// [Sprite]s that are drawn next to each-other and share a [Texture] are batched into one draw.
+fn sort_sprites(sprites: &mut [&PreparedSprite], view_matrix: &Mat4) {
+    let view_depth = |p: Vec3| -view_matrix.transform_point(p).z;
+    sprites.sort_by(|a, b| {
+        a.sorting_layer
+            .cmp(&b.sorting_layer)
+            .then_with(|| a.order_in_layer.cmp(&b.order_in_layer))
+            .then_with(|| {
+                view_depth(b.position)
+                    .partial_cmp(&view_depth(a.position))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+    });
+}
+
+/// Draws [Sprite]s over the scene with one draw per batch of [Sprite]s that share a [Texture].
+/// [Sprite]s are depth tested but don't hide each-other.
+///
+/// Without multiview each batch is drawn once per view, like [Renderer] meshes.
+/// [Sprite]s are sorted from the first view's point of view.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn render_sprites(
+    graphics_context: &mut GraphicsContext,
+    render_pass: &mut RenderPass,
+    shader: &Shader,
+    texture_assets: &Assets<Texture>,
+    camera: &Camera,
+    camera_info: &[ViewInfo],
+    multiview_enabled: bool,
+    prepared: &PreparedSprites,
+    sprite_buffers: &mut SpriteBuffers,
+) -> RenderStatistics {
+    let mut statistics = RenderStatistics::default();
+    let mut sprites: Vec<&PreparedSprite> = prepared
+        .sprites
+        .iter()
+        .filter(|s| camera.render_flags.includes_layer(s.render_flags))
+        .collect();
+    if sprites.is_empty() {
+        return statistics;
+    }
+    sort_sprites(&mut sprites, &camera_info[0].view_matrix);
+
+    let pipeline = &shader.pipeline;
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_depth_mask(false);
+
+    let bind_view = |render_pass: &mut RenderPass, view_info: &ViewInfo, i: usize| {
+        render_pass.set_mat4_property(
+            &pipeline
+                .get_mat4_property(&format!("p_views[{:?}]", i))
+                .unwrap(),
+            view_info.view_matrix.as_array(),
+        );
+        render_pass.set_mat4_property(
+            &pipeline
+                .get_mat4_property(&format!("p_projections[{:?}]", i))
+                .unwrap(),
+            view_info.projection_matrix.as_array(),
+        );
+    };
+    // Each view is drawn separately unless there's one view or multiview draws them all at once.
+    let draw_each_view = camera_info.len() > 1 && !multiview_enabled;
+    if !draw_each_view {
+        for (i, view_info) in camera_info.iter().enumerate() {
+            bind_view(render_pass, view_info, i);
+        }
+    }
+
+    let texture_property = pipeline.get_texture_property("p_texture").unwrap();
+    let position_attribute = pipeline.get_vertex_attribute::<Vec3>("a_position").unwrap();
+    let texture_coordinate_attribute = pipeline
+        .get_vertex_attribute::<Vec2>("a_texture_coordinate")
+        .unwrap();
+    let color_attribute = pipeline.get_vertex_attribute::<Vec4>("a_color").unwrap();
+
+    let mut positions = Vec::new();
+    let mut texture_coordinates = Vec::new();
+    let mut colors = Vec::new();
+
+    let mut remaining = &sprites[..];
+    while let Some(first) = remaining.first() {
+        let batch_len = remaining
+            .iter()
+            .take_while(|s| s.texture == first.texture)
+            .count();
+
+        positions.clear();
+        texture_coordinates.clear();
+        colors.clear();
+        for sprite in &remaining[..batch_len] {
+            for (quad_positions, quad_texture_coordinates, color) in
+                &prepared.quads[sprite.quads.clone()]
+            {
+                positions.extend_from_slice(quad_positions);
+                texture_coordinates.extend_from_slice(quad_texture_coordinates);
+                colors.extend_from_slice(&[*color; 4]);
+            }
+        }
+        remaining = &remaining[batch_len..];
+
+        render_pass.set_texture_property(
+            &texture_property,
+            Some(texture_assets.get(&first.texture)),
+            0,
+        );
+        render_pass.set_vertex_attribute(
+            &position_attribute,
+            Some(sprite_buffers.positions.next(graphics_context, &positions)),
+        );
+        render_pass.set_vertex_attribute(
+            &texture_coordinate_attribute,
+            Some(
+                sprite_buffers
+                    .texture_coordinates
+                    .next(graphics_context, &texture_coordinates),
+            ),
+        );
+        render_pass.set_vertex_attribute(
+            &color_attribute,
+            Some(sprite_buffers.colors.next(graphics_context, &colors)),
+        );
+
+        let index_buffer = sprite_buffers
+            .index_buffer
+            .as_ref()
+            .expect("SpriteBuffers::reset wasn't called for this frame's sprites");
+        // Each quad has 4 vertices and 2 triangles.
+        let triangle_count = positions.len() as u32 / 2;
+        if draw_each_view {
+            for view_info in camera_info {
+                let size = view_info.viewport.size();
+                render_pass.set_viewport(
+                    view_info.viewport.min.x as u32,
+                    view_info.viewport.min.y as u32,
+                    size.x as u32,
+                    size.y as u32,
+                );
+                bind_view(render_pass, view_info, 0);
+                render_pass.draw_triangles(triangle_count, index_buffer);
+                statistics.draw_calls_before_batching += batch_len;
+                statistics.draw_calls += 1;
+            }
+        } else {
+            render_pass.draw_triangles(triangle_count, index_buffer);
+            statistics.draw_calls_before_batching += batch_len;
+            statistics.draw_calls += 1;
+        }
+    }
+
+    render_pass.set_depth_mask(true);
+    statistics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepared_sprite(sorting_layer: i32, order_in_layer: i32, z: f32) -> PreparedSprite {
+        PreparedSprite {
+            texture: Texture::WHITE,
+            sorting_layer,
+            order_in_layer,
+            render_flags: RenderFlags::DEFAULT,
+            position: Vec3::new(0.0, 0.0, z),
+            quads: 0..1,
+        }
+    }
+
+    #[test]
+    fn sprites_sort_by_layer_then_order_then_depth() {
+        let sprites = [
+            prepared_sprite(1, 0, 0.0),
+            prepared_sprite(0, 5, 0.0),
+            prepared_sprite(0, 0, 1.0),
+            prepared_sprite(0, 0, -1.0),
+        ];
+        let mut sorted: Vec<&PreparedSprite> = sprites.iter().collect();
+        // The camera looks down -Z from the origin.
+        sort_sprites(&mut sorted, &Mat4::IDENTITY);
+
+        let order: Vec<(i32, i32, f32)> = sorted
+            .iter()
+            .map(|s| (s.sorting_layer, s.order_in_layer, s.position.z))
+            .collect();
+        assert_eq!(order, [(0, 0, -1.0), (0, 0, 1.0), (0, 5, 0.0), (1, 0, 0.0)]);
+    }
+
+    #[test]
+    fn quads_share_indices() {
+        assert_eq!(quad_indices(2), [0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7]);
+    }
+}
@@ -0,0 +1,201 @@
+#![cfg_attr(feature = "headless", allow(dead_code))]
+
+use crate::*;
+use kgraphics::{GraphicsContextTrait, PixelFormat, RenderTargetTrait};
+
+/// The pixels of a rendered frame.
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    /// 8-bit RGBA pixels, starting with the top row.
+    pub pixels: Vec<u8>,
+}
+
+#[cfg(feature = "png")]
+impl Screenshot {
+    /// Encodes the pixels as a PNG file.
+    pub fn to_png(&self) -> Vec<u8> {
+        encode_png(self.width, self.height, &self.pixels)
+    }
+
+    pub fn save_png(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_png())
+    }
+}
+
+/// Where a requested [Screenshot] is delivered.
+pub enum ScreenshotDestination {
+    /// Saved as a PNG file at this path. Requires the `png` feature.
+    #[cfg(feature = "png")]
+    Path(String),
+    Callback(Box<dyn FnOnce(Screenshot)>),
+}
+
+impl ScreenshotDestination {
+    pub fn callback(callback: impl FnOnce(Screenshot) + 'static) -> Self {
+        Self::Callback(Box::new(callback))
+    }
+}
+
+#[cfg(feature = "png")]
+impl From<&str> for ScreenshotDestination {
+    fn from(path: &str) -> Self {
+        Self::Path(path.to_owned())
+    }
+}
+
+#[cfg(feature = "png")]
+impl From<String> for ScreenshotDestination {
+    fn from(path: String) -> Self {
+        Self::Path(path)
+    }
+}
+
+pub(crate) struct ScreenshotRequest {
+    /// `None` captures the window.
+    source: Option<Handle<OffscreenRenderTarget>>,
+    destination: ScreenshotDestination,
+}
+
+impl GraphicsInner {
+    /// Captures the window once the current frame has been rendered and post-processed.
+    /// Pass a [ScreenshotDestination::callback] to receive the pixels,
+    /// or a path to save a PNG with the `png` feature.
+    pub fn request_screenshot(&mut self, destination: impl Into<ScreenshotDestination>) {
+        self.screenshot_requests.push(ScreenshotRequest {
+            source: None,
+            destination: destination.into(),
+        });
+    }
+
+    /// Captures an [OffscreenRenderTarget], like one a [Camera] renders to,
+    /// once the current frame has been rendered.
+    pub fn request_screenshot_of_target(
+        &mut self,
+        target: &Handle<OffscreenRenderTarget>,
+        destination: impl Into<ScreenshotDestination>,
+    ) {
+        self.screenshot_requests.push(ScreenshotRequest {
+            source: Some(target.clone()),
+            destination: destination.into(),
+        });
+    }
+}
+
+/// Reads back the requested screenshots.
+/// This must run after the frame's commands are committed but before the frame is presented.
+pub(crate) fn take_screenshots(
+    graphics: &mut Graphics,
+    textures: &Assets<Texture>,
+    offscreen_render_targets: &Assets<OffscreenRenderTarget>,
+) {
+    for request in std::mem::take(&mut graphics.screenshot_requests) {
+        let (width, height, pixels) = match &request.source {
+            None => {
+                let (width, height) = graphics.window_size;
+                let frame = graphics.render_target.current_frame();
+                let pixels = read_rgba8(graphics, &frame, PixelFormat::RGBA8Unorm, width, height);
+                (width, height, pixels)
+            }
+            Some(target) => {
+                let target = offscreen_render_targets.get(target);
+                let size = target.size();
+                let pixels = read_rgba8(
+                    graphics,
+                    textures.get(target.color_texture()),
+                    target.color_pixel_format(),
+                    size.x as u32,
+                    size.y as u32,
+                );
+                (size.x as u32, size.y as u32, pixels)
+            }
+        };
+
+        // Textures are read bottom row first.
+        let row_length = width as usize * 4;
+        let pixels = pixels
+            .chunks_exact(row_length.max(1))
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        let screenshot = Screenshot {
+            width,
+            height,
+            pixels,
+        };
+
+        match request.destination {
+            #[cfg(feature = "png")]
+            ScreenshotDestination::Path(path) => {
+                // Encoding is slow so it's done off the main thread.
+                ktasks::spawn(async move {
+                    if let Err(error) = screenshot.save_png(&path) {
+                        klog::log!("SCREENSHOT ERROR: Could not save {}: {:?}", path, error);
+                    }
+                })
+                .run();
+            }
+            ScreenshotDestination::Callback(callback) => callback(screenshot),
+        }
+    }
+}
+
+/// Reads a texture as 8-bit RGBA, clamping floating point formats.
+fn read_rgba8(
+    graphics: &mut Graphics,
+    texture: &kgraphics::Texture,
+    pixel_format: PixelFormat,
+    width: u32,
+    height: u32,
+) -> Vec<u8> {
+    match pixel_format {
+        PixelFormat::RGBA16F | PixelFormat::RGBA32F => {
+            let bytes = graphics
+                .context
+                .read_texture(texture, PixelFormat::RGBA32F, width, height);
+            bytes
+                .chunks_exact(4)
+                .map(|b| {
+                    let v = f32::from_ne_bytes([b[0], b[1], b[2], b[3]]);
+                    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+                })
+                .collect()
+        }
+        _ => graphics
+            .context
+            .read_texture(texture, PixelFormat::RGBA8Unorm, width, height),
+    }
+}
+
+/// Encodes 8-bit RGBA pixels, starting with the top row, as a PNG.
+#[cfg(feature = "png")]
+pub(crate) fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    // Writing to a `Vec` only fails if the number of pixels doesn't match the size.
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(rgba).unwrap();
+    writer.finish().unwrap();
+    png
+}
+
+#[cfg(all(test, feature = "png"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_round_trip() {
+        let pixels: Vec<u8> = (0..2 * 3 * 4).map(|i| i as u8).collect();
+        let png = encode_png(2, 3, &pixels);
+
+        let mut reader = png::Decoder::new(&png[..]).read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut decoded).unwrap();
+        assert_eq!((info.width, info.height), (2, 3));
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!(decoded, pixels);
+    }
+}
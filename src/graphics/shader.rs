@@ -1,6 +1,7 @@
 use crate::*;
 use kgraphics::*;
 
+use std::collections::{HashMap, HashSet};
 use std::sync::mpsc;
 
 #[derive(Clone)]
@@ -9,25 +10,167 @@ pub struct Shader {
     pub pipeline: Pipeline,
     #[cfg(feature = "xr")]
     pub multiview_pipeline: Option<Pipeline>, // pub transparent: bool,
+    /// Kept so variants can be compiled when a [Material] asks for them.
+    pub(crate) source: String,
+    pub(crate) pipeline_settings: PipelineSettings,
+    /// The names of the uniforms declared by the shader's source, used to validate [Material] properties.
+    pub(crate) uniforms: HashSet<String>,
+    /// Variants compiled for each [ShaderVariantKey]. `None` if the variant failed to compile.
+    pub(crate) variants: HashMap<ShaderVariantKey, Option<Shader>>,
+}
+
+/// Identifies a variant of a [Shader]: the keywords it's compiled with and
+/// the [PipelineSettings] that replace the [Shader]'s own, if any.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ShaderVariantKey {
+    /// Sorted so that materials with the same keywords share a variant.
+    pub(crate) keywords: Vec<String>,
+    pub(crate) pipeline_settings: Option<PipelineSettings>,
+}
+
+impl ShaderVariantKey {
+    pub fn is_default(&self) -> bool {
+        self.keywords.is_empty() && self.pipeline_settings.is_none()
+    }
+
+    pub(crate) fn set_keyword(&mut self, keyword: &str, enabled: bool) {
+        match (
+            self.keywords.binary_search_by(|k| k.as_str().cmp(keyword)),
+            enabled,
+        ) {
+            (Err(i), true) => self.keywords.insert(i, keyword.to_string()),
+            (Ok(i), false) => {
+                self.keywords.remove(i);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Keywords the built-in shaders check with `#ifdef`.
+/// Enable them on a [Material] with [Material::set_keyword].
+/// Custom shaders can check any keyword.
+pub mod shader_keywords {
+    /// Perturbs normals with the `p_normal_texture` normal map.
+    pub const NORMAL_MAP: &str = "NORMAL_MAP";
+    /// Discards pixels with an alpha below `p_alpha_cutoff`.
+    pub const ALPHA_CLIP: &str = "ALPHA_CLIP";
+    /// Shades the surfaces in the scene's depth texture instead of the mesh being drawn.
+    /// The renderer enables this for [Decal]s with [DecalMode::Projected].
+    pub const PROJECTED_DECAL: &str = "PROJECTED_DECAL";
+    /// Blends four layers by the weights in `p_splat_map` instead of using `p_base_color_texture`.
+    /// Enabled by [new_splat_material].
+    pub const SPLAT_MAP: &str = "SPLAT_MAP";
+    /// Multiplies the base color by the [Mesh]'s [MeshData::colors].
+    /// Meshes without colors are drawn as if their colors were white.
+    pub const VERTEX_COLORS: &str = "VERTEX_COLORS";
+    /// Moves each vertex by the `p_joint_matrices` of its [MeshData::joints], blended by its [MeshData::weights].
+    /// Set the joint matrices with [Material::set_mat4], for example `"p_joint_matrices[2]"`.
+    pub const SKINNING: &str = "SKINNING";
+}
+
+impl Shader {
+    /// The variant of this [Shader] compiled for `key`.
+    /// Returns this [Shader] if the variant hasn't been compiled yet or failed to compile.
+    pub fn variant(&self, key: &ShaderVariantKey) -> &Shader {
+        if key.is_default() {
+            return self;
+        }
+        self.variants
+            .get(key)
+            .and_then(|v| v.as_ref())
+            .unwrap_or(self)
+    }
+
+    /// Compiles the variant for `key` if it hasn't been compiled yet.
+    pub(crate) fn compile_variant(&mut self, graphics: &mut Graphics, key: &ShaderVariantKey) {
+        if key.is_default() || self.variants.contains_key(key) {
+            return;
+        }
+        let variant = match graphics.new_shader_variant(self, key) {
+            Ok(variant) => Some(variant),
+            Err(error) => {
+                klog::log!(
+                    "SHADER ERROR: Could not compile variant {:?} of shader {:?}: {:?}",
+                    key,
+                    self.name,
+                    error
+                );
+                None
+            }
+        };
+        self.variants.insert(key.clone(), variant);
+    }
+
+    /// Returns `true` if the shader's source declares a uniform named `name`.
+    /// Array elements like `"p_joint_matrices[2]"` are checked by their array's name.
+    pub fn has_uniform(&self, name: &str) -> bool {
+        let name = name.split('[').next().unwrap_or(name);
+        self.uniforms.contains(name)
+    }
 }
 
 /// A system that loads shaders onto the GPU
 pub(crate) fn load_shaders(shaders: &mut Assets<Shader>, graphics: &mut Graphics) {
     while let Ok(message) = shaders.asset_loader.receiver.inner().try_recv() {
-        let shader = graphics
-            .new_shader(&message.source, message.pipeline_settings)
-            .unwrap();
-        shaders.replace_placeholder(&message.handle, shader);
+        let path = shaders
+            .handle_to_path(&message.handle)
+            .map(String::from)
+            .unwrap_or_default();
+        let source = match message.source {
+            Ok(source) => source,
+            Err(error) => {
+                klog::log!("Could not read shader {:?}: {:?}", path, error);
+                shaders.asset_loader.failed.insert(path);
+                continue;
+            }
+        };
+        match graphics.new_shader(&source, message.pipeline_settings) {
+            // kgraphics can't delete pipelines yet so a replaced shader's pipeline is leaked.
+            Ok(shader) => {
+                shaders.asset_loader.failed.remove(&path);
+                shaders.replace(&message.handle, shader);
+            }
+            // Keep the previous shader so a typo while hot reloading doesn't crash the app.
+            Err(error) => {
+                klog::log!(
+                    "SHADER ERROR: Could not compile shader {:?}: {:?}",
+                    path,
+                    error
+                );
+                shaders.asset_loader.failed.insert(path);
+            }
+        }
+    }
+}
+
+/// Compiles the [Shader] variants requested by the keywords and render state of [Material]s in use.
+/// Variants are compiled once and then cached on their [Shader].
+pub(crate) fn compile_shader_variants(
+    graphics: &mut Graphics,
+    shaders: &mut Assets<Shader>,
+    materials: &Assets<Material>,
+    material_handles: Query<&Handle<Material>>,
+) {
+    for material_handle in &material_handles {
+        let material = materials.get(material_handle);
+        shaders
+            .get_mut(&material.shader)
+            .compile_variant(graphics, material.shader_variant_key());
     }
 }
 pub struct ShaderAssetLoader {
     sender: SyncGuard<mpsc::Sender<ShaderLoadMessage>>,
     receiver: SyncGuard<mpsc::Receiver<ShaderLoadMessage>>,
+    /// The settings each path was loaded with, used when reloading.
+    pipeline_settings: HashMap<String, PipelineSettings>,
+    /// The paths whose last load couldn't be read or compiled.
+    failed: HashSet<String>,
 }
 
 struct ShaderLoadMessage {
     handle: Handle<Shader>,
-    source: String,
+    source: std::io::Result<String>,
     pipeline_settings: PipelineSettings,
 }
 
@@ -43,8 +186,15 @@ impl ShaderAssetLoader {
         Self {
             sender: SyncGuard::new(sender),
             receiver: SyncGuard::new(receiver),
+            pipeline_settings: HashMap::new(),
+            failed: HashSet::new(),
         }
     }
+
+    /// Returns `true` if the last load of the shader at `path` couldn't be read or compiled.
+    pub fn failed(&self, path: &str) -> bool {
+        self.failed.contains(path)
+    }
 }
 
 impl AssetLoaderTrait<Shader> for ShaderAssetLoader {
@@ -55,25 +205,55 @@ impl AssetLoaderTrait<Shader> for ShaderAssetLoader {
         handle: Handle<Shader>,
         pipeline_settings: Self::Options,
     ) {
+        self.pipeline_settings
+            .insert(path.to_owned(), pipeline_settings);
         let path = path.to_owned();
         let sender = self.sender.inner().clone();
 
         ktasks::spawn(async move {
-            let source = std::fs::read_to_string(path).unwrap();
             let _ = sender.send(ShaderLoadMessage {
                 handle,
-                source,
+                source: std::fs::read_to_string(&path),
                 pipeline_settings,
             });
         })
         .run();
     }
+
+    fn reload(&mut self, path: &str, handle: Handle<Shader>) {
+        if let Some(pipeline_settings) = self.pipeline_settings.get(path).copied() {
+            self.load_with_options(path, handle, pipeline_settings);
+        }
+    }
 }
 impl AssetTrait for Shader {
     type AssetLoader = ShaderAssetLoader;
 }
 
 impl Shader {
+    /// Looks up a built-in [Shader] by the name of its constant, like `"PHYSICALLY_BASED"`.
+    pub fn built_in(name: &str) -> Option<Handle<Shader>> {
+        Some(match name {
+            "UNLIT" => Self::UNLIT,
+            "PHYSICALLY_BASED" => Self::PHYSICALLY_BASED,
+            "PHYSICALLY_BASED_TRANSPARENT" => Self::PHYSICALLY_BASED_TRANSPARENT,
+            "DEPTH_ONLY" => Self::DEPTH_ONLY,
+            "UI" => Self::UI,
+            "SKY_BOX" => Self::SKY_BOX,
+            "UNLIT_TRANSPARENT" => Self::UNLIT_TRANSPARENT,
+            "PHYSICALLY_BASED_DOUBLE_SIDED" => Self::PHYSICALLY_BASED_DOUBLE_SIDED,
+            "PHYSICALLY_BASED_TRANSPARENT_DOUBLE_SIDED" => {
+                Self::PHYSICALLY_BASED_TRANSPARENT_DOUBLE_SIDED
+            }
+            "FULLSCREEN_QUAD" => Self::FULLSCREEN_QUAD,
+            "POINT_SHADOW" => Self::POINT_SHADOW,
+            "PARTICLE" => Self::PARTICLE,
+            "DECAL" => Self::DECAL,
+            "SPRITE" => Self::SPRITE,
+            _ => return None,
+        })
+    }
+
     pub const UNLIT: Handle<Shader> = Handle::<Shader>::new_with_just_index(1);
     pub const PHYSICALLY_BASED: Handle<Shader> = Handle::<Shader>::new_with_just_index(2);
     pub const PHYSICALLY_BASED_TRANSPARENT: Handle<Shader> =
@@ -87,16 +267,25 @@ impl Shader {
     pub const PHYSICALLY_BASED_TRANSPARENT_DOUBLE_SIDED: Handle<Shader> =
         Handle::<Shader>::new_with_just_index(9);
     pub const FULLSCREEN_QUAD: Handle<Shader> = Handle::<Shader>::new_with_just_index(10);
+    pub const POINT_SHADOW: Handle<Shader> = Handle::<Shader>::new_with_just_index(11);
+    pub const PARTICLE: Handle<Shader> = Handle::<Shader>::new_with_just_index(12);
+    /// The physically based shader blended over surfaces, for [Decal] [Material]s.
+    pub const DECAL: Handle<Shader> = Handle::<Shader>::new_with_just_index(13);
+    /// Draws batches of [Sprite]s. Used by the renderer rather than by [Material]s.
+    pub const SPRITE: Handle<Shader> = Handle::<Shader>::new_with_just_index(14);
 }
 
 pub static UNLIT_SHADER_SOURCE: &str = include_str!("built_in_shaders/unlit.glsl");
 pub static PHYSICALLY_BASED_SHADER_SOURCE: &str =
     include_str!("built_in_shaders/physically_based.glsl");
 pub static DEPTH_ONLY_SHADER_SOURCE: &str = include_str!("built_in_shaders/depth_only.glsl");
+pub static POINT_SHADOW_SHADER_SOURCE: &str = include_str!("built_in_shaders/point_shadow.glsl");
 pub static FULLSCREEN_QUAD_SHADER_SOURCE: &str =
     include_str!("built_in_shaders/fullscreen_quad.glsl");
 pub static UNLIT_UI_SHADER_SOURCE: &str = include_str!("built_in_shaders/unlit_ui.glsl");
 pub static SKYBOX_SHADER_SOURCE: &str = include_str!("built_in_shaders/skybox.glsl");
+pub static PARTICLE_SHADER_SOURCE: &str = include_str!("built_in_shaders/particle.glsl");
+pub static SPRITE_SHADER_SOURCE: &str = include_str!("built_in_shaders/sprite.glsl");
 
 pub(crate) fn initialize_static_shaders(graphics: &mut Graphics, shaders: &mut Assets<Shader>) {
     shaders.add_and_leak(
@@ -189,6 +378,7 @@ pub(crate) fn initialize_static_shaders(graphics: &mut Graphics, shaders: &mut A
                     // LessOrEqual allows transparent overlays to be rendered with the same mesh
                     // as the thing being overlaid.
                     depth_test: DepthTest::AlwaysPass,
+                    stencil: None,
                 },
             )
             .unwrap(),
@@ -236,4 +426,62 @@ pub(crate) fn initialize_static_shaders(graphics: &mut Graphics, shaders: &mut A
             .unwrap(),
         &Shader::FULLSCREEN_QUAD,
     );
+
+    shaders.add_and_leak(
+        graphics
+            .new_shader(
+                POINT_SHADOW_SHADER_SOURCE,
+                PipelineSettings {
+                    faces_to_render: FacesToRender::FrontAndBack,
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+        &Shader::POINT_SHADOW,
+    );
+
+    shaders.add_and_leak(
+        graphics
+            .new_shader(
+                PARTICLE_SHADER_SOURCE,
+                PipelineSettings {
+                    faces_to_render: FacesToRender::FrontAndBack,
+                    blending: Some((BlendFactor::One, BlendFactor::OneMinusSourceAlpha)),
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+        &Shader::PARTICLE,
+    );
+
+    shaders.add_and_leak(
+        graphics
+            .new_shader_with_name(
+                "DECAL",
+                PHYSICALLY_BASED_SHADER_SOURCE,
+                PipelineSettings {
+                    faces_to_render: FacesToRender::Front,
+                    // The physically based shader's color isn't premultiplied by its alpha.
+                    blending: Some((BlendFactor::SourceAlpha, BlendFactor::OneMinusSourceAlpha)),
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+        &Shader::DECAL,
+    );
+
+    shaders.add_and_leak(
+        graphics
+            .new_shader(
+                SPRITE_SHADER_SOURCE,
+                // Flipped sprites are mirrored so both faces are rendered.
+                PipelineSettings {
+                    faces_to_render: FacesToRender::FrontAndBack,
+                    blending: Some((BlendFactor::One, BlendFactor::OneMinusSourceAlpha)),
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+        &Shader::SPRITE,
+    );
 }
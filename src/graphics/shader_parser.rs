@@ -1,5 +1,22 @@
 use std::collections::HashMap;
 
+/// An `#ifdef` or `#ifndef` block that hasn't been closed with `#endif` yet.
+struct Condition {
+    /// If the code before any `#else` is included.
+    condition: bool,
+    /// If the block containing this condition is included.
+    parent_active: bool,
+    seen_else: bool,
+}
+
+/// If code at this point should be included.
+fn is_active(conditions: &[Condition]) -> bool {
+    match conditions.last() {
+        Some(c) => c.parent_active && (c.condition != c.seen_else),
+        None => true,
+    }
+}
+
 struct ShaderParser<'a> {
     source: &'a str,
     iter: std::iter::Peekable<std::str::CharIndices<'a>>,
@@ -50,6 +67,22 @@ impl<'a> ShaderParser<'a> {
         Some(self.read_word())
     }
 
+    /// Reads until the end of the line without consuming the newline.
+    fn read_line(&mut self) -> &'a str {
+        let start = self.position;
+        loop {
+            match self.iter.peek().cloned() {
+                Some((i, c)) if c != '\n' => {
+                    self.iter.next();
+                    self.position = i + 1;
+                }
+                _ => break,
+            }
+        }
+
+        &self.source[start..self.position]
+    }
+
     fn read_stretch(&mut self) -> &'a str {
         let start = self.position;
         loop {
@@ -69,6 +102,7 @@ impl<'a> ShaderParser<'a> {
         source: &'a str,
         snippets: &'a HashMap<&'static str, &'static str>,
         prepend: &'a str,
+        defines: &'a [&'a str],
     ) -> (String, String) {
         let mut parser = Self {
             source,
@@ -76,15 +110,29 @@ impl<'a> ShaderParser<'a> {
             position: 0,
         };
 
+        // Each define's name and value, in the order they were defined.
+        let mut defined: Vec<(&'a str, &'a str)> = defines.iter().map(|d| (*d, "")).collect();
+        let mut conditions: Vec<Condition> = Vec::new();
+
         let mut vertex = String::new();
         let mut fragment = String::with_capacity(source.len());
         let mut current_string = String::with_capacity(source.len());
 
-        current_string += prepend;
+        // Defines are passed along so GLSL in snippets can check them as well.
+        let header = |defined: &[(&str, &str)]| {
+            let mut header = prepend.to_string();
+            for (name, value) in defined {
+                header += &format!("#define {} {}\n", name, value);
+            }
+            header
+        };
+
+        current_string += &header(&defined);
         // Ignore anything before the first command
         let _ = parser.read_stretch();
 
         loop {
+            let active = is_active(&conditions);
             match parser.read_command() {
                 None => {
                     break;
@@ -92,11 +140,13 @@ impl<'a> ShaderParser<'a> {
                 Some("VERTEX") => {}
                 Some("FRAGMENT") => {
                     std::mem::swap(&mut current_string, &mut vertex);
-                    current_string += prepend;
+                    current_string += &header(&defined);
                 }
                 Some("INSERT" | "INCLUDE") => {
                     let key = parser.read_word();
-                    if !key.is_empty() {
+                    if key.is_empty() {
+                        crate::log!("SHADER ERROR: Expected key after shader include");
+                    } else if active {
                         if let Some(snippet) = snippets.get(key) {
                             current_string += snippet;
                         } else {
@@ -105,8 +155,43 @@ impl<'a> ShaderParser<'a> {
                                 key
                             );
                         }
-                    } else {
-                        crate::log!("SHADER ERROR: Expected key after shader include");
+                    }
+                }
+                Some(command @ ("ifdef" | "ifndef")) => {
+                    let name = parser.read_word();
+                    if name.is_empty() {
+                        crate::log!("SHADER ERROR: Expected name after #{}", command);
+                    }
+                    let is_defined = defined.iter().any(|(d, _)| *d == name);
+                    conditions.push(Condition {
+                        condition: is_defined == (command == "ifdef"),
+                        parent_active: active,
+                        seen_else: false,
+                    });
+                }
+                Some("else") => match conditions.last_mut() {
+                    Some(condition) if !condition.seen_else => condition.seen_else = true,
+                    Some(_) => {
+                        crate::log!("SHADER ERROR: Multiple #else for one #ifdef");
+                    }
+                    None => {
+                        crate::log!("SHADER ERROR: #else without #ifdef");
+                    }
+                },
+                Some("endif") => {
+                    if conditions.pop().is_none() {
+                        crate::log!("SHADER ERROR: #endif without #ifdef");
+                    }
+                }
+                Some("define") => {
+                    let name = parser.read_word();
+                    let value = parser.read_line().trim();
+                    if name.is_empty() {
+                        crate::log!("SHADER ERROR: Expected name after #define");
+                    } else if active {
+                        defined.retain(|(d, _)| *d != name);
+                        defined.push((name, value));
+                        current_string += &format!("#define {} {}", name, value);
                     }
                 }
                 Some("") => {
@@ -118,7 +203,13 @@ impl<'a> ShaderParser<'a> {
             }
 
             let next_stretch = parser.read_stretch();
-            current_string += next_stretch;
+            if is_active(&conditions) {
+                current_string += next_stretch;
+            }
+        }
+
+        if !conditions.is_empty() {
+            crate::log!("SHADER ERROR: #ifdef without #endif");
         }
 
         std::mem::swap(&mut fragment, &mut current_string);
@@ -127,10 +218,130 @@ impl<'a> ShaderParser<'a> {
     }
 }
 
+/// Splits a shader into its vertex and fragment sources and expands its commands.
+/// `defines` are checked by `#ifdef` and `#ifndef` and are also defined for GLSL in both stages.
 pub fn parse_shader(
     snippets: &HashMap<&'static str, &'static str>,
     source: &str,
     prepend: &str,
+    defines: &[&str],
 ) -> (String, String) {
-    ShaderParser::parse(source, snippets, prepend)
+    ShaderParser::parse(source, snippets, prepend, defines)
+}
+
+/// The names of the uniforms declared in parsed GLSL source.
+/// Uniform blocks and declarations split across lines aren't recognized.
+pub fn uniform_names(source: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    for line in source.lines() {
+        let declaration = match line.trim().strip_prefix("uniform ") {
+            Some(declaration) => declaration.split(';').next().unwrap_or(""),
+            None => continue,
+        };
+        // The first declarator follows the precision and type: `uniform highp vec3 a[2], b;`
+        for (i, declarator) in declaration.split(',').enumerate() {
+            let declarator = if i == 0 {
+                declarator.split_whitespace().last().unwrap_or("")
+            } else {
+                declarator.trim()
+            };
+            let name = declarator.split('[').next().unwrap_or("").trim();
+            if !name.is_empty() {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str, defines: &[&str]) -> (String, String) {
+        let mut snippets = HashMap::new();
+        snippets.insert("snippet", "snippet_code\n");
+        parse_shader(&snippets, source, "", defines)
+    }
+
+    /// Removes whitespace so tests don't depend on exact formatting.
+    fn compact(s: &str) -> String {
+        s.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn splits_vertex_and_fragment() {
+        let (vertex, fragment) = parse("#VERTEX\nvertex_code\n#FRAGMENT\nfragment_code\n", &[]);
+        assert_eq!(compact(&vertex), "vertex_code");
+        assert_eq!(compact(&fragment), "fragment_code");
+    }
+
+    #[test]
+    fn ifdef_checks_defines() {
+        let source = "#VERTEX\n#ifdef A\na\n#else\nnot_a\n#endif\n#FRAGMENT\n";
+        assert_eq!(compact(&parse(source, &[]).0), "not_a");
+        assert_eq!(compact(&parse(source, &["A"]).0), "#define A a");
+    }
+
+    #[test]
+    fn ifndef_checks_defines() {
+        let source = "#VERTEX\n#ifndef A\nnot_a\n#endif\nafter\n#FRAGMENT\n";
+        assert_eq!(compact(&parse(source, &[]).0), "not_a after");
+        assert_eq!(compact(&parse(source, &["A"]).0), "#define A after");
+    }
+
+    #[test]
+    fn nested_conditions() {
+        let source =
+            "#VERTEX\n#ifdef A\n#ifdef B\nab\n#else\na\n#endif\n#else\n#ifdef B\nb\n#endif\nnone\n#endif\n#FRAGMENT\n";
+        assert_eq!(compact(&parse(source, &[]).0), "none");
+        assert_eq!(compact(&parse(source, &["B"]).0), "#define B b none");
+        assert_eq!(compact(&parse(source, &["A"]).0), "#define A a");
+        assert_eq!(
+            compact(&parse(source, &["A", "B"]).0),
+            "#define A #define B ab"
+        );
+    }
+
+    #[test]
+    fn define_is_visible_to_later_conditions_and_fragment() {
+        let source =
+            "#VERTEX\n#define A 2\n#ifdef A\na\n#endif\n#FRAGMENT\n#ifdef A\nfragment_a\n#endif\n";
+        let (vertex, fragment) = parse(source, &[]);
+        assert_eq!(compact(&vertex), "#define A 2 a");
+        assert_eq!(compact(&fragment), "#define A 2 fragment_a");
+    }
+
+    #[test]
+    fn define_in_inactive_block_is_ignored() {
+        let source = "#VERTEX\n#ifdef A\n#define B\n#endif\n#ifdef B\nb\n#endif\n#FRAGMENT\n";
+        assert_eq!(compact(&parse(source, &[]).0), "");
+    }
+
+    #[test]
+    fn includes_only_in_active_blocks() {
+        let source = "#VERTEX\n#ifdef A\n#INCLUDE snippet\n#endif\n#FRAGMENT\n";
+        assert_eq!(compact(&parse(source, &[]).0), "");
+        assert_eq!(compact(&parse(source, &["A"]).0), "#define A snippet_code");
+    }
+
+    #[test]
+    fn finds_uniform_names() {
+        let source = "uniform vec4 p_base_color;\n\
+            // uniform float p_commented_out;\n\
+            uniform highp sampler2D p_light_data;\n\
+            uniform vec3 p_camera_positions[NUM_VIEWS];\n\
+            uniform float p_a, p_b[2];\n\
+            vec4 not_a_uniform;\n";
+        assert_eq!(
+            uniform_names(source),
+            [
+                "p_base_color",
+                "p_light_data",
+                "p_camera_positions",
+                "p_a",
+                "p_b"
+            ]
+        );
+    }
 }
@@ -0,0 +1,178 @@
+//! Tangent generation that follows the conventions of MikkTSpace.
+//!
+//! MikkTSpace is the tangent space that Blender, Substance and most other tools bake normal maps in,
+//! so matching it keeps baked normal maps from showing seams and lighting errors.
+//! Like MikkTSpace each triangle's tangent is projected onto the vertex normal's plane,
+//! weighted by the triangle's angle at that vertex and then summed.
+//! Unlike MikkTSpace vertices are never split so a vertex shared by triangles with mirrored UVs
+//! takes the handedness of the majority of its triangles.
+
+use crate::*;
+
+impl MeshData {
+    /// Generates a tangent for each vertex from its normal and texture coordinates.
+    /// Each tangent's `w` is 1.0 or -1.0 and the bitangent is `cross(normal, tangent.xyz) * tangent.w`,
+    /// which is what glTF expects.
+    ///
+    /// Does nothing if the [MeshData] has no normals or texture coordinates.
+    pub fn generate_tangents(&mut self) {
+        let vertex_count = self.positions.len();
+        if self.normals.len() != vertex_count || self.texture_coordinates.len() != vertex_count {
+            return;
+        }
+
+        let mut tangents = vec![Vec3::ZERO; vertex_count];
+        let mut bitangents = vec![Vec3::ZERO; vertex_count];
+
+        for triangle in &self.indices {
+            let [a, b, c] = triangle.map(|i| i as usize);
+            let edge0 = self.positions[b] - self.positions[a];
+            let edge1 = self.positions[c] - self.positions[a];
+            let uv_edge0 = self.texture_coordinates[b] - self.texture_coordinates[a];
+            let uv_edge1 = self.texture_coordinates[c] - self.texture_coordinates[a];
+
+            // The sign of the UV area tells if the UVs are mirrored.
+            let uv_area = uv_edge0.x * uv_edge1.y - uv_edge1.x * uv_edge0.y;
+            if uv_area.abs() < f32::EPSILON {
+                // Triangles without UV area don't have a meaningful tangent.
+                continue;
+            }
+            let triangle_tangent = (edge0 * uv_edge1.y - edge1 * uv_edge0.y) / uv_area;
+            let triangle_bitangent = (edge1 * uv_edge0.x - edge0 * uv_edge1.x) / uv_area;
+
+            for (corner, previous, next) in [(a, c, b), (b, a, c), (c, b, a)] {
+                let normal = self.normals[corner];
+                let weight = corner_angle(
+                    self.positions[corner],
+                    self.positions[previous],
+                    self.positions[next],
+                );
+                tangents[corner] += project_onto_plane(triangle_tangent, normal) * weight;
+                bitangents[corner] += project_onto_plane(triangle_bitangent, normal) * weight;
+            }
+        }
+
+        self.tangents = tangents
+            .iter()
+            .zip(bitangents.iter())
+            .zip(self.normals.iter())
+            .map(|((tangent, bitangent), normal)| {
+                let normal = normal.normalized_or_zero();
+                let mut tangent = (*tangent - normal * normal.dot(*tangent)).normalized_or_zero();
+                if tangent == Vec3::ZERO {
+                    tangent = any_perpendicular(normal);
+                }
+                let handedness = if normal.cross(tangent).dot(*bitangent) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                tangent.extend(handedness)
+            })
+            .collect();
+    }
+}
+
+fn project_onto_plane(v: Vec3, normal: Vec3) -> Vec3 {
+    let normal = normal.normalized_or_zero();
+    (v - normal * normal.dot(v)).normalized_or_zero()
+}
+
+/// The angle of a triangle's corner at `corner`.
+fn corner_angle(corner: Vec3, previous: Vec3, next: Vec3) -> f32 {
+    let a = (previous - corner).normalized_or_zero();
+    let b = (next - corner).normalized_or_zero();
+    a.dot(b).clamp(-1.0, 1.0).acos()
+}
+
+/// Picks a tangent for vertices whose triangles have no UV area.
+fn any_perpendicular(normal: Vec3) -> Vec3 {
+    let axis = if normal.x.abs() < 0.9 {
+        Vec3::X
+    } else {
+        Vec3::Y
+    };
+    normal.cross(axis).normalized_or_zero()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A unit quad in the XY plane facing +Z with UVs that follow X and Y.
+    fn quad() -> MeshData {
+        let mut mesh_data = MeshData::new();
+        mesh_data.positions = vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        mesh_data.normals = vec![Vec3::Z; 4];
+        mesh_data.texture_coordinates = vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(0.0, 1.0),
+        ];
+        mesh_data.indices = vec![[0, 1, 2], [0, 2, 3]];
+        mesh_data
+    }
+
+    fn assert_close(a: Vec4, b: Vec4) {
+        assert!((a - b).length() < 0.0001, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn tangents_follow_u() {
+        let mut mesh_data = quad();
+        mesh_data.generate_tangents();
+        assert_eq!(mesh_data.tangents.len(), 4);
+        for tangent in &mesh_data.tangents {
+            assert_close(*tangent, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs_flip_handedness() {
+        let mut mesh_data = quad();
+        for uv in &mut mesh_data.texture_coordinates {
+            uv.y = 1.0 - uv.y;
+        }
+        mesh_data.generate_tangents();
+        for tangent in &mesh_data.tangents {
+            assert_close(*tangent, Vec4::new(1.0, 0.0, 0.0, -1.0));
+        }
+    }
+
+    #[test]
+    fn tangents_are_perpendicular_to_normals() {
+        let mut mesh_data = quad();
+        // Tilt the normals so the triangle tangents must be projected.
+        mesh_data.normals = vec![Vec3::new(0.3, 0.2, 1.0).normalized(); 4];
+        mesh_data.generate_tangents();
+        for (tangent, normal) in mesh_data.tangents.iter().zip(mesh_data.normals.iter()) {
+            assert!(tangent.xyz().dot(*normal).abs() < 0.0001);
+            assert!((tangent.xyz().length() - 1.0).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn degenerate_uvs_still_produce_tangents() {
+        let mut mesh_data = quad();
+        mesh_data.texture_coordinates = vec![Vec2::ZERO; 4];
+        mesh_data.generate_tangents();
+        for (tangent, normal) in mesh_data.tangents.iter().zip(mesh_data.normals.iter()) {
+            assert!(tangent.xyz().dot(*normal).abs() < 0.0001);
+            assert!((tangent.xyz().length() - 1.0).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn missing_texture_coordinates_do_nothing() {
+        let mut mesh_data = quad();
+        mesh_data.texture_coordinates.clear();
+        mesh_data.generate_tangents();
+        assert!(mesh_data.tangents.is_empty());
+    }
+}
@@ -0,0 +1,618 @@
+use crate::*;
+
+/// Heights on a grid, from 0.0 at a [Terrain]'s lowest to 1.0 at its highest.
+///
+/// Row 0 is the first row of a heightmap image and lies along the [Terrain]'s -Z edge.
+#[derive(Clone, Debug)]
+pub struct Heightmap {
+    columns: usize,
+    rows: usize,
+    /// Row-major with `columns` heights per row.
+    heights: Vec<f32>,
+}
+
+#[derive(Debug)]
+pub enum HeightmapLoadError {
+    CouldNotLoadFile,
+    UnsupportedExtension(String),
+    /// The file's contents are invalid or use a feature that can't be decoded.
+    CouldNotDecode(String),
+    /// A [Heightmap] needs at least 2 columns and 2 rows.
+    TooSmall,
+    /// `.r16` and `.raw` files have no header, so their size is only known if they're square.
+    NotSquare,
+}
+
+impl Heightmap {
+    /// `heights` are row-major with `columns` heights per row.
+    pub fn new(columns: usize, rows: usize, heights: Vec<f32>) -> Self {
+        assert!(
+            columns >= 2 && rows >= 2,
+            "A Heightmap needs at least 2 columns and 2 rows"
+        );
+        assert_eq!(heights.len(), columns * rows);
+        Self {
+            columns,
+            rows,
+            heights,
+        }
+    }
+
+    /// Procedurally generates heights.
+    /// `f` is passed each height's position across the [Heightmap], from (0.0, 0.0) to (1.0, 1.0).
+    pub fn from_fn(columns: usize, rows: usize, mut f: impl FnMut(f32, f32) -> f32) -> Self {
+        let mut heights = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                heights.push(f(
+                    column as f32 / columns.saturating_sub(1).max(1) as f32,
+                    row as f32 / rows.saturating_sub(1).max(1) as f32,
+                ));
+            }
+        }
+        Self::new(columns, rows, heights)
+    }
+
+    /// Reads 16-bit little-endian heights without a header,
+    /// like the `.r16` and `.raw` files terrain tools export.
+    /// Returns an error if there are fewer than 2 columns or 2 rows.
+    pub fn from_r16_bytes(bytes: &[u8], columns: usize) -> Result<Self, HeightmapLoadError> {
+        let mut heights: Vec<f32> = bytes
+            .chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as f32 / u16::MAX as f32)
+            .collect();
+        let rows = heights.len() / columns.max(1);
+        if columns < 2 || rows < 2 {
+            return Err(HeightmapLoadError::TooSmall);
+        }
+        heights.truncate(columns * rows);
+        Ok(Self::new(columns, rows, heights))
+    }
+
+    /// Reads the first channel of a PNG. 16-bit PNGs keep their full precision.
+    #[cfg(feature = "png")]
+    pub fn from_png_bytes(bytes: &[u8]) -> Result<Self, HeightmapLoadError> {
+        let decoding_error =
+            |error: png::DecodingError| HeightmapLoadError::CouldNotDecode(error.to_string());
+        let mut decoder = png::Decoder::new(std::io::BufReader::new(bytes));
+        // Expands grayscale images with fewer than 8 bits.
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info().map_err(decoding_error)?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let metadata = reader.next_frame(&mut pixels).map_err(decoding_error)?;
+
+        if metadata.width < 2 || metadata.height < 2 {
+            return Err(HeightmapLoadError::TooSmall);
+        }
+
+        let channels = metadata.color_type.samples();
+        let heights = match metadata.bit_depth {
+            png::BitDepth::Sixteen => pixels[..metadata.buffer_size()]
+                .chunks_exact(channels * 2)
+                .map(|p| u16::from_be_bytes([p[0], p[1]]) as f32 / u16::MAX as f32)
+                .collect(),
+            _ => pixels[..metadata.buffer_size()]
+                .chunks_exact(channels)
+                .map(|p| p[0] as f32 / 255.0)
+                .collect(),
+        };
+        Ok(Self::new(
+            metadata.width as usize,
+            metadata.height as usize,
+            heights,
+        ))
+    }
+
+    /// Loads a square `.r16` or `.raw` heightmap,
+    /// or a `.png` heightmap if the `png` feature is enabled.
+    /// Returns an error for heightmaps smaller than 2x2 and for raw files that aren't square.
+    pub async fn load(path: &str) -> Result<Self, HeightmapLoadError> {
+        let extension = std::path::Path::new(path)
+            .extension()
+            .and_then(std::ffi::OsStr::to_str)
+            .unwrap_or("");
+        let bytes = crate::fetch_bytes(path)
+            .await
+            .map_err(|_| HeightmapLoadError::CouldNotLoadFile)?;
+        match extension {
+            "r16" | "raw" => {
+                let columns = ((bytes.len() / 2) as f64).sqrt() as usize;
+                if columns * columns * 2 != bytes.len() {
+                    return Err(HeightmapLoadError::NotSquare);
+                }
+                Self::from_r16_bytes(&bytes, columns)
+            }
+            #[cfg(feature = "png")]
+            "png" => Self::from_png_bytes(&bytes),
+            _ => Err(HeightmapLoadError::UnsupportedExtension(
+                extension.to_owned(),
+            )),
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn get(&self, column: usize, row: usize) -> f32 {
+        self.heights[row * self.columns + column]
+    }
+
+    /// Call [Terrain::rebuild] after changing the heights of a [Terrain]'s [Heightmap].
+    pub fn set(&mut self, column: usize, row: usize, height: f32) {
+        self.heights[row * self.columns + column] = height;
+    }
+
+    /// Row-major heights with [Heightmap::columns] heights per row.
+    pub fn heights(&self) -> &[f32] {
+        &self.heights
+    }
+
+    /// Finds the cell containing a position on the grid and how far across the cell it is.
+    /// Positions are clamped to the [Heightmap]'s edges.
+    fn cell(&self, column: f32, row: f32) -> (usize, usize, f32, f32) {
+        let column = column.clamp(0.0, (self.columns - 1) as f32);
+        let row = row.clamp(0.0, (self.rows - 1) as f32);
+        // The last column and row belong to the cells before them.
+        let cell_column = (column as usize).min(self.columns - 2);
+        let cell_row = (row as usize).min(self.rows - 2);
+        (
+            cell_column,
+            cell_row,
+            column - cell_column as f32,
+            row - cell_row as f32,
+        )
+    }
+
+    /// The height at a position on the grid, interpolated across the triangles
+    /// of a [Terrain]'s most detailed level.
+    /// Each cell is split along the diagonal from its lowest column and row to its highest.
+    pub fn sample(&self, column: f32, row: f32) -> f32 {
+        let (column, row, x, z) = self.cell(column, row);
+        let h00 = self.get(column, row);
+        let h10 = self.get(column + 1, row);
+        let h01 = self.get(column, row + 1);
+        let h11 = self.get(column + 1, row + 1);
+        if z >= x {
+            h00 + (h01 - h00) * z + (h11 - h01) * x
+        } else {
+            h00 + (h10 - h00) * x + (h11 - h10) * z
+        }
+    }
+}
+
+/// One of the four textures a [new_splat_material] blends.
+#[derive(Clone, Debug)]
+pub struct TerrainLayer {
+    pub texture: Handle<Texture>,
+    /// How many times the texture repeats across the [Terrain].
+    pub tiling: f32,
+    pub roughness: f32,
+    pub metallic: f32,
+}
+
+impl Default for TerrainLayer {
+    fn default() -> Self {
+        Self {
+            texture: Texture::WHITE,
+            tiling: 1.0,
+            roughness: 0.7,
+            metallic: 0.0,
+        }
+    }
+}
+
+/// A physically based [Material] that blends four [TerrainLayer]s.
+/// The red, green and blue channels of `splat_map` weight the first three layers
+/// and the last layer covers whatever weight is left.
+///
+/// The splat map is stretched across the whole [Terrain].
+/// Splat maps should have linear color so load them with `srgb: false`.
+pub fn new_splat_material(splat_map: Handle<Texture>, layers: [TerrainLayer; 4]) -> Material {
+    let mut material = Material::new(Shader::PHYSICALLY_BASED);
+    material.set_keyword(shader_keywords::SPLAT_MAP, true);
+    material.set_color("p_base_color", Color::WHITE);
+    // The layers' properties are multiplied by these.
+    material.set_float("p_metallic", 1.0);
+    material.set_float("p_roughness", 1.0);
+    material.set_float("p_ambient", 1.0);
+    material.set_vec3("p_emissive", Vec3::ZERO);
+
+    material.set_texture("p_splat_map", splat_map);
+    for (i, layer) in layers.iter().enumerate() {
+        material.set_texture(&format!("p_layer_{}_texture", i), layer.texture.clone());
+    }
+    let [a, b, c, d] = &layers;
+    material.set_vec4(
+        "p_layer_tiling",
+        Vec4::new(a.tiling, b.tiling, c.tiling, d.tiling),
+    );
+    material.set_vec4(
+        "p_layer_roughness",
+        Vec4::new(a.roughness, b.roughness, c.roughness, d.roughness),
+    );
+    material.set_vec4(
+        "p_layer_metallic",
+        Vec4::new(a.metallic, b.metallic, c.metallic, d.metallic),
+    );
+    material
+}
+
+/// Terrain made from a [Heightmap].
+///
+/// The [Terrain] is centered on its [Entity] along X and Z, like [plane], and rises along +Y.
+/// It's split into square chunks that are spawned as children of the [Entity].
+/// Each chunk has a [LevelOfDetail] that halves its resolution as it gets smaller on screen.
+/// Chunks hang skirts down from their edges to hide the cracks between neighbors at different levels.
+///
+/// Use [new_splat_material] to blend textures across the [Terrain].
+/// With the `physics` feature a `TerrainCollider` makes the [Terrain] solid.
+#[derive(Component, Clone)]
+pub struct Terrain {
+    pub heightmap: Heightmap,
+    /// The width and depth of the [Terrain] along X and Z,
+    /// and the height of a [Heightmap] value of 1.0 along Y.
+    pub size: Vec3,
+    /// How many [Heightmap] cells are along each side of a chunk.
+    /// Powers of two let every level of detail line up with the edges of its chunk.
+    pub chunk_cells: usize,
+    /// How many levels of detail each chunk has, including the most detailed level.
+    pub detail_levels: usize,
+    /// How far skirts hang below the edges of chunks.
+    pub skirt_depth: f32,
+    pub material: Handle<Material>,
+    chunks: Vec<Entity>,
+    needs_rebuild: bool,
+    generation: u64,
+}
+
+impl Terrain {
+    pub fn new(heightmap: Heightmap, size: Vec3, material: Handle<Material>) -> Self {
+        Self {
+            heightmap,
+            size,
+            chunk_cells: 32,
+            detail_levels: 4,
+            skirt_depth: size.y * 0.05,
+            material,
+            chunks: Vec::new(),
+            needs_rebuild: true,
+            generation: 0,
+        }
+    }
+
+    /// Regenerates the chunks' [Mesh]es.
+    /// Call this after changing the [Terrain] or its [Heightmap].
+    pub fn rebuild(&mut self) {
+        self.needs_rebuild = true;
+        self.generation += 1;
+    }
+
+    /// Increases each time [Terrain::rebuild] is called.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The distance between neighboring [Heightmap] values along X and Z.
+    pub fn cell_size(&self) -> Vec2 {
+        Vec2::new(
+            self.size.x / (self.heightmap.columns - 1) as f32,
+            self.size.z / (self.heightmap.rows - 1) as f32,
+        )
+    }
+
+    /// Converts a position in the [Terrain]'s local space to a position on its [Heightmap]'s grid.
+    fn local_to_grid(&self, x: f32, z: f32) -> Option<(f32, f32)> {
+        let u = x / self.size.x + 0.5;
+        let v = z / self.size.z + 0.5;
+        // Written so that NaNs are outside the terrain.
+        if !((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v)) {
+            return None;
+        }
+        Some((
+            u * (self.heightmap.columns - 1) as f32,
+            v * (self.heightmap.rows - 1) as f32,
+        ))
+    }
+
+    /// The height of the surface at `x` and `z` in the [Terrain]'s local space.
+    /// Matches the most detailed level of the chunks.
+    /// Returns `None` outside the [Terrain].
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let (column, row) = self.local_to_grid(x, z)?;
+        Some(self.heightmap.sample(column, row) * self.size.y)
+    }
+
+    /// The normal of the surface at `x` and `z` in the [Terrain]'s local space.
+    /// Returns `None` outside the [Terrain].
+    pub fn normal_at(&self, x: f32, z: f32) -> Option<Vec3> {
+        let (column, row) = self.local_to_grid(x, z)?;
+        let (column, row, x, z) = self.heightmap.cell(column, row);
+        let heightmap = &self.heightmap;
+        let h00 = heightmap.get(column, row);
+        let h10 = heightmap.get(column + 1, row);
+        let h01 = heightmap.get(column, row + 1);
+        let h11 = heightmap.get(column + 1, row + 1);
+
+        // How much the height changes across one cell of the triangle.
+        let (x_change, z_change) = if z >= x {
+            (h11 - h01, h01 - h00)
+        } else {
+            (h10 - h00, h11 - h10)
+        };
+        let cell_size = self.cell_size();
+        Some(
+            Vec3::new(
+                -x_change * self.size.y * cell_size.y,
+                cell_size.x * cell_size.y,
+                -z_change * self.size.y * cell_size.x,
+            )
+            .normalized(),
+        )
+    }
+
+    /// The point on the surface directly above or below `position` along the [Terrain]'s up axis,
+    /// and the surface's normal there. Everything is in world space.
+    /// Returns `None` if `position` isn't over the [Terrain].
+    pub fn surface_at(
+        &self,
+        global_transform: &GlobalTransform,
+        position: Vec3,
+    ) -> Option<(Vec3, Vec3)> {
+        let model = global_transform.model();
+        let local_position = model.inversed().transform_point(position);
+        let height = self.height_at(local_position.x, local_position.z)?;
+        let normal = self.normal_at(local_position.x, local_position.z)?;
+        Some((
+            model.transform_point(Vec3::new(local_position.x, height, local_position.z)),
+            (global_transform.rotation * normal.div_by_component(global_transform.scale))
+                .normalized(),
+        ))
+    }
+
+    /// The chunk counts along X and Z.
+    fn chunk_counts(&self) -> (usize, usize) {
+        let chunk_cells = self.chunk_cells.max(1);
+        (
+            (self.heightmap.columns - 1).div_ceil(chunk_cells),
+            (self.heightmap.rows - 1).div_ceil(chunk_cells),
+        )
+    }
+
+    /// The smooth normal at a point on the [Heightmap]'s grid in the [Terrain]'s local space.
+    fn vertex_normal(&self, column: usize, row: usize) -> Vec3 {
+        let heightmap = &self.heightmap;
+        let cell_size = self.cell_size();
+        // Central differences, or one-sided differences at the edges.
+        let left = column.saturating_sub(1);
+        let right = (column + 1).min(heightmap.columns - 1);
+        let back = row.saturating_sub(1);
+        let front = (row + 1).min(heightmap.rows - 1);
+        let x_slope = (heightmap.get(right, row) - heightmap.get(left, row)) * self.size.y
+            / ((right - left) as f32 * cell_size.x);
+        let z_slope = (heightmap.get(column, front) - heightmap.get(column, back)) * self.size.y
+            / ((front - back) as f32 * cell_size.y);
+        Vec3::new(-x_slope, 1.0, -z_slope).normalized()
+    }
+
+    /// The [MeshData] for a chunk that uses every `step`th [Heightmap] value.
+    fn chunk_mesh_data(&self, chunk_x: usize, chunk_z: usize, step: usize) -> MeshData {
+        // Every `step`th line of the grid, plus the chunk's far edge.
+        let lines = |chunk: usize, count: usize| -> Vec<usize> {
+            let start = chunk * self.chunk_cells;
+            let end = (start + self.chunk_cells).min(count - 1);
+            let mut lines: Vec<usize> = (start..end).step_by(step).collect();
+            lines.push(end);
+            lines
+        };
+        let columns = lines(chunk_x, self.heightmap.columns);
+        let rows = lines(chunk_z, self.heightmap.rows);
+
+        let mut mesh_data = MeshData::new();
+        let last_column = (self.heightmap.columns - 1) as f32;
+        let last_row = (self.heightmap.rows - 1) as f32;
+        for &row in &rows {
+            for &column in &columns {
+                let u = column as f32 / last_column;
+                let v = row as f32 / last_row;
+                mesh_data.positions.push(Vec3::new(
+                    (u - 0.5) * self.size.x,
+                    self.heightmap.get(column, row) * self.size.y,
+                    (v - 0.5) * self.size.z,
+                ));
+                mesh_data.normals.push(self.vertex_normal(column, row));
+                mesh_data.texture_coordinates.push(Vec2::new(u, v));
+            }
+        }
+
+        let width = columns.len();
+        let index = |i: usize, j: usize| (j * width + i) as u32;
+        for j in 0..rows.len() - 1 {
+            for i in 0..width - 1 {
+                // Split along the same diagonal as `Heightmap::sample`.
+                mesh_data
+                    .indices
+                    .push([index(i, j), index(i, j + 1), index(i + 1, j + 1)]);
+                mesh_data
+                    .indices
+                    .push([index(i, j), index(i + 1, j + 1), index(i + 1, j)]);
+            }
+        }
+
+        // Walk the chunk's edges counter-clockwise when seen from above
+        // and hang a skirt facing outwards below each edge.
+        let (last_i, last_j) = (width - 1, rows.len() - 1);
+        let edge: Vec<u32> = (0..last_i)
+            .map(|i| index(i, 0))
+            .chain((0..last_j).map(|j| index(last_i, j)))
+            .chain((1..=last_i).rev().map(|i| index(i, last_j)))
+            .chain((1..=last_j).rev().map(|j| index(0, j)))
+            .collect();
+        let first_skirt_vertex = mesh_data.positions.len() as u32;
+        for &top in &edge {
+            let top = top as usize;
+            let position = mesh_data.positions[top] - Vec3::Y * self.skirt_depth;
+            let normal = mesh_data.normals[top];
+            let texture_coordinate = mesh_data.texture_coordinates[top];
+            mesh_data.positions.push(position);
+            mesh_data.normals.push(normal);
+            mesh_data.texture_coordinates.push(texture_coordinate);
+        }
+        for k in 0..edge.len() {
+            let next = (k + 1) % edge.len();
+            let (top, next_top) = (edge[k], edge[next]);
+            let (bottom, next_bottom) = (
+                first_skirt_vertex + k as u32,
+                first_skirt_vertex + next as u32,
+            );
+            mesh_data.indices.push([top, next_top, bottom]);
+            mesh_data.indices.push([next_top, next_bottom, bottom]);
+        }
+
+        mesh_data
+    }
+
+    /// The [MeshData] for each level of detail of a chunk, from most to least detailed.
+    fn chunk_detail_levels(&self, chunk_x: usize, chunk_z: usize) -> Vec<MeshData> {
+        (0..self.detail_levels.max(1))
+            .map(|level| 1 << level)
+            // Stop once a level would have less than one cell per chunk.
+            .take_while(|&step| step == 1 || step <= self.chunk_cells)
+            .map(|step| self.chunk_mesh_data(chunk_x, chunk_z, step))
+            .collect()
+    }
+}
+
+/// Spawns the chunks of new [Terrain]s and respawns the chunks of rebuilt [Terrain]s.
+pub(crate) fn build_terrain_chunks(
+    mut commands: Commands,
+    graphics: &mut Graphics,
+    meshes: &mut Assets<Mesh>,
+    mut terrains: Query<(Entity, &mut Terrain)>,
+) {
+    for (entity, terrain) in &mut terrains {
+        if !terrain.needs_rebuild {
+            continue;
+        }
+        terrain.needs_rebuild = false;
+
+        for chunk in terrain.chunks.drain(..) {
+            commands.entity(chunk).despawn_recursive();
+        }
+
+        let (chunks_x, chunks_z) = terrain.chunk_counts();
+        let mut chunks = Vec::with_capacity(chunks_x * chunks_z);
+        commands.entity(entity).with_children(|children| {
+            for chunk_z in 0..chunks_z {
+                for chunk_x in 0..chunks_x {
+                    let detail_levels: Vec<Handle<Mesh>> = terrain
+                        .chunk_detail_levels(chunk_x, chunk_z)
+                        .into_iter()
+                        .map(|mesh_data| meshes.add(Mesh::new(graphics, mesh_data)))
+                        .collect();
+                    let chunk = children.spawn((
+                        Transform::new(),
+                        detail_levels[0].clone(),
+                        terrain.material.clone(),
+                        LevelOfDetail::from_meshes(detail_levels),
+                    ));
+                    chunks.push(chunk.id());
+                }
+            }
+        });
+        terrain.chunks = chunks;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terrain() -> Terrain {
+        let heightmap = Heightmap::from_fn(9, 5, |x, z| (x * 3.0).sin() * 0.5 + z * z);
+        let mut terrain = Terrain::new(heightmap, Vec3::new(16.0, 4.0, 8.0), Handle::default());
+        terrain.chunk_cells = 4;
+        terrain
+    }
+
+    fn triangle_normal(mesh_data: &MeshData, triangle: [u32; 3]) -> Vec3 {
+        let [a, b, c] = triangle.map(|i| mesh_data.positions[i as usize]);
+        (b - a).cross(c - a).normalized()
+    }
+
+    #[test]
+    fn height_at_matches_most_detailed_level() {
+        let terrain = terrain();
+        let mesh_data = terrain.chunk_mesh_data(1, 0, 1);
+        // The chunk covers 5 columns and 5 rows and the rest of the vertices are its skirts.
+        let grid_vertices = 25;
+        for &triangle in &mesh_data.indices {
+            if triangle.iter().any(|&i| i >= grid_vertices) {
+                continue;
+            }
+            let [a, b, c] = triangle.map(|i| mesh_data.positions[i as usize]);
+            let center = (a + b + c) / 3.0;
+            let height = terrain.height_at(center.x, center.z).unwrap();
+            assert!((height - center.y).abs() < 0.0001);
+
+            let normal = terrain.normal_at(center.x, center.z).unwrap();
+            assert!((normal - triangle_normal(&mesh_data, triangle)).length() < 0.0001);
+        }
+        assert_eq!(terrain.height_at(8.5, 0.0), None);
+    }
+
+    #[test]
+    fn skirts_face_outwards() {
+        let terrain = terrain();
+        for step in [1, 2, 4] {
+            let mesh_data = terrain.chunk_mesh_data(0, 0, step);
+            let center = Box3::from_points(mesh_data.positions.iter().copied()).center();
+            for &triangle in &mesh_data.indices {
+                let normal = triangle_normal(&mesh_data, triangle);
+                let [a, b, c] = triangle.map(|i| mesh_data.positions[i as usize]);
+                let outwards = ((a + b + c) / 3.0 - center).mul_by_component(Vec3::XZ);
+                assert!(normal.y > 0.0 || normal.dot(outwards) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn detail_levels_halve_resolution() {
+        let mut terrain = terrain();
+        terrain.detail_levels = 4;
+        let vertex_counts: Vec<usize> = terrain
+            .chunk_detail_levels(0, 0)
+            .iter()
+            .map(|mesh_data| mesh_data.positions.len())
+            .collect();
+        // Each grid plus a skirt vertex for each vertex around its edge.
+        assert_eq!(vertex_counts, [25 + 16, 9 + 8, 4 + 4]);
+        assert_eq!(terrain.chunk_counts(), (2, 1));
+    }
+
+    #[test]
+    fn heightmap_from_r16_bytes() {
+        let heightmap = Heightmap::from_r16_bytes(&[0, 0, 255, 255, 0, 128, 0, 0, 1], 2).unwrap();
+        assert_eq!((heightmap.columns(), heightmap.rows()), (2, 2));
+        assert_eq!(heightmap.get(1, 0), 1.0);
+        assert!((heightmap.get(0, 1) - 0.5).abs() < 0.0001);
+        assert_eq!(heightmap.sample(0.5, 0.25), 0.25);
+
+        assert!(matches!(
+            Heightmap::from_r16_bytes(&[0; 8], 1),
+            Err(HeightmapLoadError::TooSmall)
+        ));
+        assert!(matches!(
+            Heightmap::from_r16_bytes(&[0; 6], 2),
+            Err(HeightmapLoadError::TooSmall)
+        ));
+        assert!(matches!(
+            Heightmap::from_r16_bytes(&[], 0),
+            Err(HeightmapLoadError::TooSmall)
+        ));
+    }
+}
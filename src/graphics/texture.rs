@@ -3,6 +3,7 @@ use bytemuck::Pod;
 use core::ops::Deref;
 use kgraphics::*;
 
+use std::collections::HashMap;
 use std::sync::mpsc;
 
 pub struct Texture(pub kgraphics::Texture);
@@ -14,6 +15,15 @@ impl Deref for Texture {
     }
 }
 
+#[derive(Debug)]
+pub enum TextureLoadError {
+    CouldNotLoadFile,
+    UnsupportedExtension(String),
+    /// The file's contents are invalid or use a feature that can't be decoded.
+    CouldNotDecode(String),
+    Compressed(CompressedTextureError),
+}
+
 struct TextureLoadMessage {
     handle: Handle<Texture>,
     texture_load_data: TextureLoadData,
@@ -44,6 +54,12 @@ pub enum TextureData {
     Bytes(Box<dyn AsU8Array>),
     #[cfg(target_arch = "wasm32")]
     JSObject(kwasm::JSObjectDynamic),
+    /// Pre-baked mip levels, starting with the full size level.
+    /// Each level stores `faces` images one after another.
+    MipLevels {
+        levels: Vec<Vec<u8>>,
+        faces: u32,
+    },
 }
 pub struct TextureLoadData {
     pub data: TextureData,
@@ -55,6 +71,10 @@ pub struct TextureLoadData {
 pub struct TextureAssetLoader {
     sender: SyncGuard<mpsc::Sender<TextureLoadMessage>>,
     receiver: SyncGuard<mpsc::Receiver<TextureLoadMessage>>,
+    /// The settings each path was loaded with, used when reloading.
+    texture_settings: HashMap<String, TextureSettings>,
+    /// Compressed formats that are uploaded as is instead of decoded on the CPU.
+    supported_pixel_formats: Vec<PixelFormat>,
 }
 
 pub fn new_texture_from_texture_load_data(
@@ -86,6 +106,21 @@ pub fn new_texture_from_texture_load_data(
                 )
                 .unwrap(),
         ),
+        TextureData::MipLevels { levels, .. } => {
+            let levels: Vec<&[u8]> = levels.iter().map(|level| &level[..]).collect();
+            Texture(
+                graphics
+                    .context
+                    .new_texture_with_mipmaps(
+                        texture_load_data.width,
+                        texture_load_data.height,
+                        &levels,
+                        texture_load_data.pixel_format,
+                        texture_settings,
+                    )
+                    .unwrap(),
+            )
+        }
     }
 }
 /// A system that loads textures onto the GPU
@@ -96,7 +131,9 @@ pub(crate) fn load_textures(textures: &mut Assets<Texture>, graphics: &mut Graph
             message.texture_load_data,
             message.texture_settings,
         );
-        textures.replace_placeholder(&message.handle, texture);
+        if let Some(previous_texture) = textures.replace(&message.handle, texture) {
+            graphics.context.delete_texture(previous_texture.0);
+        }
     }
 }
 
@@ -114,15 +151,17 @@ fn extend_pixels_3_with_alpha(pixels: Vec<u8>) -> Vec<u8> {
 }
 
 #[cfg(feature = "png")]
-pub fn png_data_from_bytes(bytes: &[u8], srgb: bool) -> TextureLoadData {
+pub fn png_data_from_bytes(bytes: &[u8], srgb: bool) -> Result<TextureLoadData, TextureLoadError> {
     let reader = std::io::BufReader::new(bytes);
     let mut decoder = png::Decoder::new(reader);
 
     // This line reduces 16-bit or greater images to 8 bit.
     decoder.set_transformations(png::Transformations::normalize_to_color8());
-    let mut reader = decoder.read_info().unwrap();
+    let decode_error =
+        |error: png::DecodingError| TextureLoadError::CouldNotDecode(error.to_string());
+    let mut reader = decoder.read_info().map_err(decode_error)?;
     let mut pixels = vec![0; reader.output_buffer_size()];
-    let metadata = reader.next_frame(&mut pixels).unwrap();
+    let metadata = reader.next_frame(&mut pixels).map_err(decode_error)?;
 
     let pixel_format = match metadata.color_type {
         // png::ColorType::Rgb => PixelFormat::RGB8Unorm,
@@ -156,24 +195,30 @@ pub fn png_data_from_bytes(bytes: &[u8], srgb: bool) -> TextureLoadData {
             }
         }
         //  png::ColorType::GrayscaleAlpha => PixelFormat::RG8Unorm, // Is this correct?
-        _ => unimplemented!("Unsupported PNG pixel format: {:?}", metadata.color_type,),
+        _ => {
+            return Err(TextureLoadError::CouldNotDecode(format!(
+                "Unsupported PNG pixel format: {:?}",
+                metadata.color_type
+            )))
+        }
     };
 
-    TextureLoadData {
+    Ok(TextureLoadData {
         data: TextureData::Bytes(Box::new(pixels)),
         pixel_format,
         width: metadata.width,
         height: metadata.height,
-    }
+    })
 }
 
 #[cfg(feature = "imagine_png")]
-fn png_data_from_bytes(bytes: &[u8], _srgb: bool) -> TextureLoadData {
+fn png_data_from_bytes(bytes: &[u8], _srgb: bool) -> Result<TextureLoadData, TextureLoadError> {
     let imagine::image::Bitmap::<imagine::pixel_formats::RGBA8888> {
         width,
         height,
         mut pixels,
-    } = imagine::image::Bitmap::try_from_png_bytes(&bytes).unwrap();
+    } = imagine::image::Bitmap::try_from_png_bytes(&bytes)
+        .map_err(|error| TextureLoadError::CouldNotDecode(format!("{:?}", error)))?;
     //  let (mut data, width, height) = imagine_integration::parse_me_a_png_yo(bytes).unwrap();
 
     // Premultiply texture
@@ -184,21 +229,25 @@ fn png_data_from_bytes(bytes: &[u8], _srgb: bool) -> TextureLoadData {
         v.b = (v.b as f32 * a) as u8;
     }
 
-    TextureLoadData {
+    Ok(TextureLoadData {
         data: TextureData::Bytes(Box::new(pixels)),
         pixel_format: PixelFormat::RGBA8Unorm,
         width,
         height,
-    }
+    })
 }
 
 #[cfg(feature = "jpeg")]
-pub fn jpeg_data_from_bytes(bytes: &[u8], srgb: bool) -> TextureLoadData {
+pub fn jpeg_data_from_bytes(bytes: &[u8], srgb: bool) -> Result<TextureLoadData, TextureLoadError> {
     let reader = std::io::BufReader::new(bytes);
 
     let mut decoder = jpeg_decoder::Decoder::new(reader);
-    let mut pixels = decoder.decode().expect("failed to decode image");
-    let metadata = decoder.info().unwrap();
+    let mut pixels = decoder
+        .decode()
+        .map_err(|error| TextureLoadError::CouldNotDecode(error.to_string()))?;
+    let metadata = decoder
+        .info()
+        .ok_or_else(|| TextureLoadError::CouldNotDecode("Missing JPEG metadata".into()))?;
 
     let pixel_format = match metadata.pixel_format {
         jpeg_decoder::PixelFormat::RGB24 => {
@@ -222,21 +271,24 @@ pub fn jpeg_data_from_bytes(bytes: &[u8], srgb: bool) -> TextureLoadData {
             }
         }
         jpeg_decoder::PixelFormat::CMYK32 => {
-            panic!("CMYK is currently unsupported")
+            return Err(TextureLoadError::CouldNotDecode(
+                "CMYK is currently unsupported".into(),
+            ))
         } // _ => unimplemented!("Unsupported Jpeg pixel format: {:?}", metadata.pixel_format,),
     };
-    TextureLoadData {
+    Ok(TextureLoadData {
         data: TextureData::Bytes(Box::new(pixels)),
         pixel_format,
         width: metadata.width as u32,
         height: metadata.height as u32,
-    }
+    })
 }
 
 #[cfg(feature = "hdri")]
-fn hdri_data_from_bytes(bytes: &[u8]) -> TextureLoadData {
+fn hdri_data_from_bytes(bytes: &[u8]) -> Result<TextureLoadData, TextureLoadError> {
     // This data is always assumed to be linear sRGB
-    let image = hdrldr::load(bytes).expect("Failed to decode HDRI image data");
+    let image = hdrldr::load(bytes)
+        .map_err(|error| TextureLoadError::CouldNotDecode(format!("{:?}", error)))?;
 
     // Pad with alpha.
     // Some platforms (Firefox on web) don't support RGB32F well.
@@ -245,19 +297,20 @@ fn hdri_data_from_bytes(bytes: &[u8]) -> TextureLoadData {
         texture.push([r, g, b, 0.0]);
     }
 
-    TextureLoadData {
+    Ok(TextureLoadData {
         data: TextureData::Bytes(Box::new(texture)),
         width: image.width as u32,
         height: image.height as u32,
         pixel_format: PixelFormat::RGBA32F,
-    }
+    })
 }
 
 pub fn texture_load_data_from_bytes(
     extension: &str,
     bytes: &[u8],
     options: &mut TextureSettings,
-) -> TextureLoadData {
+    #[allow(unused_variables)] supported_pixel_formats: &[PixelFormat],
+) -> Result<TextureLoadData, TextureLoadError> {
     match extension {
         #[cfg(any(feature = "png", feature = "imagine_png"))]
         "png" => png_data_from_bytes(bytes, options.srgb),
@@ -268,24 +321,48 @@ pub fn texture_load_data_from_bytes(
             options.srgb = false;
             hdri_data_from_bytes(bytes)
         }
-        _ => panic!("Unsupported texture extension: {:?}", extension),
+        "cube" => {
+            // Color-grading lookup tables are data that's interpolated between entries.
+            *options = TextureSettings {
+                srgb: false,
+                generate_mipmaps: false,
+                minification_filter: FilterMode::Linear,
+                magnification_filter: FilterMode::Linear,
+                wrapping_horizontal: WrappingMode::ClampToEdge,
+                wrapping_vertical: WrappingMode::ClampToEdge,
+                ..Default::default()
+            };
+            cube_lut_data_from_bytes(bytes)
+        }
+        #[cfg(feature = "ktx2")]
+        "ktx2" => super::ktx2::ktx2_image_from_bytes(bytes, supported_pixel_formats)
+            .and_then(|image| image.into_texture_load_data(options, supported_pixel_formats))
+            .map_err(TextureLoadError::Compressed),
+        #[cfg(feature = "dds")]
+        "dds" => super::dds::dds_image_from_bytes(bytes)
+            .and_then(|image| image.into_texture_load_data(options, supported_pixel_formats))
+            .map_err(TextureLoadError::Compressed),
+        _ => Err(TextureLoadError::UnsupportedExtension(
+            extension.to_string(),
+        )),
     }
 }
 
 pub(crate) async fn texture_data_from_path(
     path: &str,
     options: &mut TextureSettings,
-) -> TextureLoadData {
+    supported_pixel_formats: &[PixelFormat],
+) -> Result<TextureLoadData, TextureLoadError> {
     let extension = std::path::Path::new(&path)
         .extension()
         .and_then(std::ffi::OsStr::to_str)
-        .expect("Expected image file extension");
+        .unwrap_or("");
 
     let bytes = crate::fetch_bytes(path)
         .await
-        .unwrap_or_else(|_| panic!("Failed to open file: {}", path));
+        .map_err(|_| TextureLoadError::CouldNotLoadFile)?;
 
-    texture_load_data_from_bytes(extension, &bytes, options)
+    texture_load_data_from_bytes(extension, &bytes, options, supported_pixel_formats)
 }
 
 impl Default for TextureAssetLoader {
@@ -300,8 +377,20 @@ impl TextureAssetLoader {
         Self {
             sender: SyncGuard::new(sender),
             receiver: SyncGuard::new(receiver),
+            texture_settings: HashMap::new(),
+            supported_pixel_formats: Vec::new(),
         }
     }
+
+    /// Compressed textures in these formats are uploaded as is,
+    /// other compressed textures are decoded on the CPU.
+    pub fn with_supported_pixel_formats(
+        mut self,
+        supported_pixel_formats: Vec<PixelFormat>,
+    ) -> Self {
+        self.supported_pixel_formats = supported_pixel_formats;
+        self
+    }
 }
 
 impl AssetLoaderTrait<Texture> for TextureAssetLoader {
@@ -312,50 +401,67 @@ impl AssetLoaderTrait<Texture> for TextureAssetLoader {
         handle: Handle<Texture>,
         #[allow(unused_mut)] mut options: Self::Options,
     ) {
+        self.texture_settings.insert(path.to_owned(), options);
         let path = path.to_owned();
         let sender = self.sender.inner().clone();
+        let supported_pixel_formats = self.supported_pixel_formats.clone();
 
         ktasks::spawn(async move {
             #[cfg(not(target_arch = "wasm32"))]
-            let texture_load_data = {
-                let extension = std::path::Path::new(&path)
-                    .extension()
-                    .and_then(std::ffi::OsStr::to_str)
-                    .expect("Expected image file extension");
-
-                let bytes = crate::fetch_bytes(&path)
-                    .await
-                    .unwrap_or_else(|_| panic!("Failed to open file: {}", path));
-                texture_load_data_from_bytes(extension, &bytes, &mut options)
-            };
+            let texture_load_data =
+                texture_data_from_path(&path, &mut options, &supported_pixel_formats).await;
 
             // Web uses the browser-native decoders as much faster path.
             #[cfg(target_arch = "wasm32")]
-            let texture_load_data = {
-                let kwasm::libraries::ImageLoadResult {
-                    image_js_object,
-                    width,
-                    height,
-                } = kwasm::libraries::load_image(&path)
-                    .await
-                    .unwrap_or_else(|_| panic!("Failed to open file: {}", path));
-                TextureLoadData {
-                    data: TextureData::JSObject(image_js_object.to_dynamic()),
-                    width,
-                    height,
-                    pixel_format: PixelFormat::RGBA8Unorm,
+            let texture_load_data =
+                if path.ends_with(".cube") || path.ends_with(".ktx2") || path.ends_with(".dds") {
+                    // Lookup tables and compressed textures can't be decoded by the browser.
+                    texture_data_from_path(&path, &mut options, &supported_pixel_formats).await
+                } else {
+                    kwasm::libraries::load_image(&path)
+                        .await
+                        .map(
+                            |kwasm::libraries::ImageLoadResult {
+                                 image_js_object,
+                                 width,
+                                 height,
+                             }| TextureLoadData {
+                                data: TextureData::JSObject(image_js_object.to_dynamic()),
+                                width,
+                                height,
+                                pixel_format: PixelFormat::RGBA8Unorm,
+                            },
+                        )
+                        .map_err(|_| TextureLoadError::CouldNotLoadFile)
+                };
+
+            // Nothing is sent on an error so the previous texture, or the placeholder, stays in place.
+            match texture_load_data {
+                Ok(texture_load_data) => {
+                    let _ = sender.send(TextureLoadMessage {
+                        texture_load_data,
+                        handle,
+                        texture_settings: options,
+                    });
                 }
-            };
-
-            let _ = sender.send(TextureLoadMessage {
-                texture_load_data,
-                handle,
-                texture_settings: options,
-            });
+                Err(error) => {
+                    klog::log!(
+                        "TEXTURE ERROR: Could not load texture {:?}: {:?}",
+                        path,
+                        error
+                    );
+                }
+            }
         })
         .run();
     }
 
+    fn reload(&mut self, path: &str, handle: Handle<Texture>) {
+        if let Some(texture_settings) = self.texture_settings.get(path).copied() {
+            self.load_with_options(path, handle, texture_settings);
+        }
+    }
+
     fn load_with_data_and_options_and_extension(
         &mut self,
         data: Vec<u8>,
@@ -364,14 +470,26 @@ impl AssetLoaderTrait<Texture> for TextureAssetLoader {
         mut options: Self::Options,
     ) {
         let sender = self.sender.inner().clone();
+        let supported_pixel_formats = self.supported_pixel_formats.clone();
 
         ktasks::spawn(async move {
-            let texture_load_data = texture_load_data_from_bytes(&extension, &data, &mut options);
-            let _ = sender.send(TextureLoadMessage {
-                texture_load_data,
-                handle,
-                texture_settings: options,
-            });
+            match texture_load_data_from_bytes(
+                &extension,
+                &data,
+                &mut options,
+                &supported_pixel_formats,
+            ) {
+                Ok(texture_load_data) => {
+                    let _ = sender.send(TextureLoadMessage {
+                        texture_load_data,
+                        handle,
+                        texture_settings: options,
+                    });
+                }
+                Err(error) => {
+                    klog::log!("TEXTURE ERROR: Could not load texture: {:?}", error);
+                }
+            }
         })
         .run();
     }
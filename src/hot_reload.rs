@@ -0,0 +1,107 @@
+use crate::*;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+/// Reloads shaders, textures, materials and worlds when the files they were loaded from change.
+/// Reloaded assets replace the previous ones behind the same [Handle] so everything using them updates.
+/// Worlds that were already spawned are not updated.
+///
+/// This isn't a default plugin. Add it with `App::new().add_plugin(hot_reload_plugin())`.
+pub fn hot_reload_plugin() -> Plugin {
+    Plugin {
+        setup_systems: vec![setup_hot_reload.system()],
+        end_of_frame_systems: vec![reload_changed_assets.system()],
+        ..Default::default()
+    }
+}
+
+/// Tracks when files loaded by [Assets] were last modified.
+#[derive(NotCloneComponent)]
+pub struct HotReload {
+    /// How often files are checked for changes.
+    pub check_interval_seconds: f64,
+    last_check: Instant,
+    files: HashMap<String, FileState>,
+}
+
+struct FileState {
+    modified: SystemTime,
+    len: u64,
+    /// Set when the file changes and cleared once it's reloaded.
+    changed: bool,
+}
+
+impl HotReload {
+    fn new() -> Self {
+        Self {
+            check_interval_seconds: 0.5,
+            last_check: Instant::now(),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Reloads the assets whose files have changed and then stayed the same for a check.
+    /// Waiting for a file to settle avoids reloading it while an editor is still writing it.
+    /// Files seen for the first time are only recorded.
+    fn reload_changed<T: AssetTrait>(&mut self, assets: &mut Assets<T>)
+    where
+        T::AssetLoader: AssetLoaderTrait<T>,
+    {
+        for (path, handle) in assets.loaded_paths() {
+            // Paths that aren't files, like those of embedded assets, are ignored.
+            let (modified, len) =
+                match std::fs::metadata(&path).and_then(|m| Ok((m.modified()?, m.len()))) {
+                    Ok(metadata) => metadata,
+                    Err(_) => continue,
+                };
+            let file = match self.files.get_mut(&path) {
+                Some(file) => file,
+                None => {
+                    self.files.insert(
+                        path,
+                        FileState {
+                            modified,
+                            len,
+                            changed: false,
+                        },
+                    );
+                    continue;
+                }
+            };
+            if file.modified != modified || file.len != len {
+                file.modified = modified;
+                file.len = len;
+                file.changed = true;
+            } else if file.changed {
+                file.changed = false;
+                klog::log!("Reloading: {}", path);
+                assets.reload(&handle);
+            }
+        }
+    }
+}
+
+fn setup_hot_reload(world: &mut World) {
+    world.spawn((Name("HotReload".into()), HotReload::new()));
+}
+
+fn reload_changed_assets(
+    hot_reload: &mut HotReload,
+    worlds: &mut Assets<World>,
+    #[cfg(feature = "graphics")] shaders: &mut Assets<Shader>,
+    #[cfg(feature = "graphics")] textures: &mut Assets<Texture>,
+    #[cfg(feature = "graphics")] materials: &mut Assets<Material>,
+) {
+    if hot_reload.last_check.elapsed().as_secs_f64() < hot_reload.check_interval_seconds {
+        return;
+    }
+    hot_reload.last_check = Instant::now();
+
+    hot_reload.reload_changed(worlds);
+    #[cfg(feature = "graphics")]
+    {
+        hot_reload.reload_changed(shaders);
+        hot_reload.reload_changed(textures);
+        hot_reload.reload_changed(materials);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::*;
+
 pub trait InterpolateTrait {
     fn interpolate(&self, other: &Self, amount: f32) -> Self;
 }
@@ -5,3 +7,86 @@ pub trait InterpolateTrait {
 pub fn smooth_step(amount: f32) -> f32 {
     amount * amount * (3.0 - 2.0 * amount)
 }
+
+impl InterpolateTrait for f32 {
+    fn interpolate(&self, other: &Self, amount: f32) -> Self {
+        self + (other - self) * amount
+    }
+}
+
+impl<const R: usize, const C: usize> InterpolateTrait for Matrix<f32, R, C> {
+    fn interpolate(&self, other: &Self, amount: f32) -> Self {
+        self.lerp(*other, amount)
+    }
+}
+
+impl InterpolateTrait for Color {
+    /// Interpolates in the OKLAB color space.
+    fn interpolate(&self, other: &Self, amount: f32) -> Self {
+        Color::interpolate(*self, *other, amount)
+    }
+}
+
+/// A value that changes over time, described by keyframes.
+/// Between keyframes values are linearly interpolated.
+#[derive(Clone, Debug)]
+pub struct Curve<T> {
+    /// Pairs of times and values, sorted by time.
+    keyframes: Vec<(f32, T)>,
+}
+
+impl<T: InterpolateTrait + Clone> Curve<T> {
+    /// `keyframes` are pairs of times and values. They're sorted by time.
+    /// Panics if `keyframes` is empty.
+    pub fn new(mut keyframes: Vec<(f32, T)>) -> Self {
+        assert!(!keyframes.is_empty(), "A Curve needs at least one keyframe");
+        keyframes.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        Self { keyframes }
+    }
+
+    /// A [Curve] that's always `value`.
+    pub fn constant(value: T) -> Self {
+        Self::new(vec![(0.0, value)])
+    }
+
+    /// A [Curve] that goes from `start` at 0.0 to `end` at 1.0.
+    pub fn linear(start: T, end: T) -> Self {
+        Self::new(vec![(0.0, start), (1.0, end)])
+    }
+
+    /// Times before the first keyframe or after the last keyframe use that keyframe's value.
+    pub fn sample(&self, time: f32) -> T {
+        let next = self.keyframes.partition_point(|(t, _)| *t <= time);
+        if next == 0 {
+            return self.keyframes[0].1.clone();
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].1.clone();
+        }
+        let (start_time, start) = &self.keyframes[next - 1];
+        let (end_time, end) = &self.keyframes[next];
+        start.interpolate(end, (time - start_time) / (end_time - start_time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curve_sample() {
+        let curve = Curve::new(vec![(1.0, 4.0), (0.0, 2.0), (2.0, 0.0)]);
+        assert_eq!(curve.sample(-1.0), 2.0);
+        assert_eq!(curve.sample(0.5), 3.0);
+        assert_eq!(curve.sample(1.0), 4.0);
+        assert_eq!(curve.sample(1.25), 3.0);
+        assert_eq!(curve.sample(3.0), 0.0);
+    }
+
+    #[test]
+    fn curve_constant() {
+        let curve = Curve::constant(Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(curve.sample(0.0), Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(curve.sample(0.7), Vec3::new(1.0, 2.0, 3.0));
+    }
+}
@@ -41,6 +41,11 @@ pub use ecs_components::*;
 mod interpolate;
 pub use interpolate::*;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod hot_reload;
+#[cfg(not(target_arch = "wasm32"))]
+pub use hot_reload::*;
+
 pub use kinstant::Instant;
 
 #[cfg(feature = "graphics")]
@@ -204,6 +209,10 @@ impl App {
         let app = app.add_plugin(camera_controls_plugin());
         #[cfg(feature = "graphics")]
         let app = app.add_plugin(immediate_drawer_plugin());
+        #[cfg(feature = "graphics")]
+        let app = app.add_plugin(particles_plugin());
+        #[cfg(feature = "graphics")]
+        let app = app.add_plugin(sprite_plugin());
 
         // #[cfg(feature = "ui")]
         // let app = app.add_plugin(ui_plugin());
@@ -228,7 +237,7 @@ impl App {
         ktasks::create_workers();
 
         let mut world = World::new();
-        world.spawn((Name("Commands".into()), Commands::new()));
+        world.spawn((Name("Commands".into()), CommandQueue::new()));
 
         // Setup input
         let input_entity = world.spawn((Name("Input".into()), Input::new()));
@@ -253,6 +262,7 @@ impl App {
                 fixed_time_step,
                 discontinuity: false,
                 last_frame_time_ms: 0.0,
+                simulated_frame_seconds: None,
             },
         ));
 
@@ -284,7 +294,7 @@ impl App {
         ktasks::create_workers();
 
         let mut world = World::new();
-        world.spawn((Name("Commands".into()), Commands::new()));
+        world.spawn((Name("Commands".into()), CommandQueue::new()));
         // Setup input
         let input_entity = world.spawn((Name("Input".into()), Input::new()));
 
@@ -335,6 +345,7 @@ impl App {
                 fixed_time_step,
                 discontinuity: false,
                 last_frame_time_ms: 0.0,
+                simulated_frame_seconds: None,
             },
         ));
 
@@ -441,17 +452,25 @@ impl KoiState {
             (time_elapsed_seconds * 1000.0) as f32;
 
         self.start = Instant::now();
-        self.time_acumulator += time_elapsed_seconds;
 
-        // Check that there aren't a huge number of fixed time steps to process.
-        // This can happen if a computer goes to sleep and then exits sleep.
-        if self.time_acumulator / self.fixed_time_step > 30. {
-            self.time_acumulator = self.fixed_time_step;
-        }
+        if let Some(simulated_frame_seconds) =
+            self.world.get_singleton::<Time>().simulated_frame_seconds
+        {
+            // Simulated time ignores how long frames actually take.
+            self.time_acumulator += simulated_frame_seconds;
+        } else {
+            self.time_acumulator += time_elapsed_seconds;
+
+            // Check that there aren't a huge number of fixed time steps to process.
+            // This can happen if a computer goes to sleep and then exits sleep.
+            if self.time_acumulator / self.fixed_time_step > 30. {
+                self.time_acumulator = self.fixed_time_step;
+            }
 
-        // If the engine isn't updating continuously there can be discontinuities that shouldn't produce multiple fixed updates.
-        if self.world.get_singleton::<Time>().discontinuity {
-            self.time_acumulator = self.fixed_time_step;
+            // If the engine isn't updating continuously there can be discontinuities that shouldn't produce multiple fixed updates.
+            if self.world.get_singleton::<Time>().discontinuity {
+                self.time_acumulator = self.fixed_time_step;
+            }
         }
 
         while self.time_acumulator >= self.fixed_time_step {
@@ -8,6 +8,7 @@ pub fn physics_plugin() -> Plugin {
         fixed_update_systems: vec![
             update_physics_0.system(),
             update_physics_1.system(),
+            update_physics_terrain.system(),
             update_physics_2.system(),
         ],
         ..Default::default()
@@ -95,6 +96,26 @@ impl Collider {
     }
 }
 
+/// Makes a [Terrain] on the same `Entity` solid by adding a heightfield that matches it to the [PhysicsWorld].
+///
+/// The heightfield follows the [Terrain] as it moves but its heights only change when the [Terrain] is rebuilt.
+/// Heightfields can't be rotated so the [Terrain]'s rotation is ignored.
+#[derive(Component, Clone)]
+pub struct TerrainCollider {
+    heightfield_handle: Option<kphysics::HeightfieldHandle>,
+    /// The [Terrain::generation] the heightfield was built from.
+    terrain_generation: u64,
+}
+
+impl TerrainCollider {
+    pub fn new() -> Self {
+        Self {
+            heightfield_handle: None,
+            terrain_generation: 0,
+        }
+    }
+}
+
 #[derive(Component, Clone)]
 pub struct PhysicsWorld {
     world: kphysics::PhysicsWorld<FloatType>,
@@ -117,14 +138,14 @@ impl DerefMut for PhysicsWorld {
 /// These systems are split apart because their borrows overlap.
 /// This first system updates the physics simulation's `RigidBody` data.
 pub fn update_physics_0(
-    mut rigid_bodies: Query<(&mut RigidBody, &mut Transform)>,
+    mut rigid_bodies: Query<(Entity, &mut RigidBody, &mut Transform)>,
     physics_world: &mut PhysicsWorld,
 ) {
     if physics_world.paused {
         return;
     }
     // Synchronize all `RigidBody` values with the `PhysicsWorld`.
-    for (entity, (rigid_body, rigid_body_transform)) in rigid_bodies.entities_and_components_mut() {
+    for (entity, rigid_body, rigid_body_transform) in &mut rigid_bodies {
         let associated_entity = kphysics::AssociatedEntity {
             index: entity.index(),
             generation: entity.generation(),
@@ -170,7 +191,7 @@ pub fn update_physics_0(
 /// Update the physic simulation's `Collider` data.
 pub fn update_physics_1(
     rigid_bodies: Query<&RigidBody>,
-    mut colliders: Query<(&mut Collider, &mut Transform, &Handle<Mesh>)>,
+    mut colliders: Query<(Entity, &mut Collider, &mut Transform, &Handle<Mesh>)>,
     meshes: &Assets<Mesh>,
     physics_world: &mut PhysicsWorld,
 ) {
@@ -179,16 +200,14 @@ pub fn update_physics_1(
     }
     // Synchronize all `Collider` values with the `PhysicsWorld`.
     // Connect `Collider`s to `RigidBody`s.
-    for (entity, (collider, collider_transform, mesh_handle)) in
-        colliders.entities_and_components_mut()
-    {
+    for (entity, collider, collider_transform, mesh_handle) in &mut colliders {
         let associated_entity = kphysics::AssociatedEntity {
             index: entity.index(),
             generation: entity.generation(),
         };
 
         // Default to checking self for the `RigidBody` if the `rigid_body_entity` is `None`
-        let target_entity = collider.rigid_body_entity.as_ref().unwrap_or(entity);
+        let target_entity = collider.rigid_body_entity.as_ref().unwrap_or(&entity);
         let attached_rigid_body = rigid_bodies
             .get_entity_components(*target_entity)
             .map(|c| c.rigid_body_handle.unwrap());
@@ -224,6 +243,51 @@ pub fn update_physics_1(
     }
 }
 
+/// Update the physics simulation's heightfields to match [Terrain]s with a [TerrainCollider].
+pub fn update_physics_terrain(
+    mut terrains: Query<(&mut TerrainCollider, &Terrain, &GlobalTransform)>,
+    physics_world: &mut PhysicsWorld,
+) {
+    if physics_world.paused {
+        return;
+    }
+    for (terrain_collider, terrain, global_transform) in &mut terrains {
+        let heightmap = &terrain.heightmap;
+        let size = terrain.size.mul_by_component(global_transform.scale);
+        let origin = global_transform.position - Vec3::new(size.x, 0.0, size.z) / 2.0;
+        let cell_size = terrain
+            .cell_size()
+            .mul_by_component(global_transform.scale.xz());
+
+        match terrain_collider.heightfield_handle {
+            Some(heightfield_handle)
+                if terrain_collider.terrain_generation == terrain.generation() =>
+            {
+                // The `Terrain` may have moved.
+                let heightfield = physics_world.get_heightfield_mut(heightfield_handle);
+                heightfield.origin = origin;
+                heightfield.cell_size = cell_size;
+            }
+            _ => {
+                let heightfield = kphysics::Heightfield::new(
+                    heightmap.columns(),
+                    heightmap.rows(),
+                    heightmap.heights().iter().map(|h| h * size.y).collect(),
+                    origin,
+                    cell_size,
+                );
+                if let Some(heightfield_handle) = terrain_collider.heightfield_handle {
+                    *physics_world.get_heightfield_mut(heightfield_handle) = heightfield;
+                } else {
+                    terrain_collider.heightfield_handle =
+                        Some(physics_world.add_heightfield(heightfield));
+                }
+                terrain_collider.terrain_generation = terrain.generation();
+            }
+        }
+    }
+}
+
 /// Run the physics simulation and update the ECS world with the results.
 pub fn update_physics_2(
     mut rigid_bodies: Query<(&mut RigidBody, &mut Transform)>,
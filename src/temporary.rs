@@ -2,6 +2,7 @@ use crate::*;
 
 /// Despawned during `pre_fixed_update_systems` at the start of the next frame.
 #[derive(Component, Clone)]
+#[component(storage = "sparse")]
 pub struct Temporary(pub usize);
 
 pub fn temporary_despawn_plugin() -> Plugin {
@@ -11,10 +12,10 @@ pub fn temporary_despawn_plugin() -> Plugin {
     }
 }
 
-fn despawn_temporaries(commands: &mut Commands, mut temporaries: Query<&mut Temporary>) {
-    for (entity, temporary) in temporaries.entities_and_components_mut() {
+fn despawn_temporaries(mut commands: Commands, mut temporaries: Query<(Entity, &mut Temporary)>) {
+    for (entity, temporary) in &mut temporaries {
         if temporary.0 == 0 {
-            commands.despawn(*entity)
+            commands.despawn(entity)
         } else {
             temporary.0 -= 1;
         }
@@ -7,4 +7,7 @@ pub struct Time {
     pub fixed_time_step: f64,
     pub discontinuity: bool,
     pub last_frame_time_ms: f32,
+    /// If set each frame advances time by this many seconds instead of the real elapsed time.
+    /// This makes updates deterministic, which is used by [FrameRecorder].
+    pub simulated_frame_seconds: Option<f64>,
 }
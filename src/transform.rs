@@ -169,86 +169,71 @@ impl Mul<Transform> for Transform {
 
 /// Add [GlobalTransform]s to all root nodes without them and update their [GlobalTransform] to be equal to their [Transform]
 pub fn update_root_global_transforms(
-    commands: &mut Commands,
+    mut commands: Commands,
     mut query: Query<(
+        Entity,
         &Transform,
         Option<&mut GlobalTransform>,
         Option<&mut HierarchyNode>,
     )>,
 ) {
-    for (entity, (local_transform, global_transform, hierarchy_node)) in
-        query.entities_and_components_mut()
-    {
+    for (entity, local_transform, global_transform, hierarchy_node) in &mut query {
         if hierarchy_node.map_or(true, |h| h.parent().is_none()) {
             let new_global_transform = GlobalTransform(*local_transform);
             if let Some(global_transform) = global_transform {
                 *global_transform = new_global_transform;
             } else {
-                commands.add_component(*entity, new_global_transform)
+                commands.add_component(entity, new_global_transform)
             }
         }
     }
 }
 
 pub fn update_global_transforms(
-    commands: &mut Commands,
-    mut query: Query<(
-        &HierarchyNode,
-        Option<&Transform>,
-        Option<&mut GlobalTransform>,
-    )>,
+    mut commands: Commands,
+    hierarchy: Query<(&HierarchyNode, Option<&Transform>)>,
+    mut global_transforms: Query<(&HierarchyNode, Option<&Transform>, &mut GlobalTransform)>,
+    missing_global_transforms: Query<
+        (Entity, &HierarchyNode, Option<&Transform>),
+        Without<GlobalTransform>,
+    >,
 ) {
-    // TODO: It'd be nice to find a way to avoid this allocation
-    let mut parents = Vec::new();
-
     // This is a bit inefficient in that all hierarchies are updated, regardless of if they changed.
-    for (e, (node, _local_transform, _global_transform)) in query.entities_and_components() {
-        if node.parent().is_none() {
-            parents.push(*e)
-        }
-    }
-
-    for parent_entity in &parents {
-        update_descendent_transforms(commands, &mut query, *parent_entity, &Mat4::IDENTITY);
+    // Each Entity walks up its own ancestors so that Entities can be updated in parallel.
+    global_transforms.par_iter_mut(64, |(hierarchy_node, local_transform, global_transform)| {
+        *global_transform = GlobalTransform(Transform::from_mat4(global_matrix(
+            &hierarchy,
+            hierarchy_node,
+            local_transform,
+        )));
+    });
+
+    // It would be simpler to just always add a GlobalTransform component here, as adding a component replaces an existing component.
+    // But adding a component involves a bit of complex lookup logic in the ECS. Profiling a massive scene revealed that the calls
+    // to `add_component` were significant.
+    for (entity, hierarchy_node, local_transform) in &missing_global_transforms {
+        let global_matrix = global_matrix(&hierarchy, hierarchy_node, local_transform);
+        commands.add_component(entity, GlobalTransform(Transform::from_mat4(global_matrix)));
     }
 }
 
-fn update_descendent_transforms(
-    commands: &mut Commands,
-    query: &mut Query<(
-        &HierarchyNode,
-        Option<&Transform>,
-        Option<&mut GlobalTransform>,
-    )>,
-    parent_entity: Entity,
-    parent_matrix: &Mat4,
-) {
-    if let Some((hierarchy_node, local_transform, global_transform)) =
-        query.get_entity_components_mut(parent_entity)
+/// Multiplies an Entity's [Transform] by the [Transform]s of all of its ancestors.
+fn global_matrix(
+    hierarchy: &Query<(&HierarchyNode, Option<&Transform>)>,
+    hierarchy_node: &HierarchyNode,
+    local_transform: Option<&Transform>,
+) -> Mat4 {
+    let mut matrix = local_transform.map_or(Mat4::IDENTITY, |t| t.model());
+    let mut parent = *hierarchy_node.parent();
+    while let Some((parent_node, parent_transform)) =
+        parent.and_then(|parent| hierarchy.get_entity_components(parent))
     {
-        let my_global_matrix = if let Some(local_transform) = local_transform {
-            *parent_matrix * local_transform.model()
-        } else {
-            *parent_matrix
-        };
-
-        // It would be simpler to just always add a GlobalTransform here component here, as adding a component replaces an existing component.
-        // But adding a component involves a bit of complex lookup logic in the ECS. Profiling a massive scene revealed that the calls
-        // to `add_component` were significant.
-        let new_global_transform = GlobalTransform(Transform::from_mat4(my_global_matrix));
-        if let Some(global_transform) = global_transform {
-            *global_transform = new_global_transform;
-        } else {
-            commands.add_component(parent_entity, new_global_transform);
-        }
-
-        let mut child = *hierarchy_node.last_child();
-        while let Some(child_entity) = child {
-            let hierachy_node = query.get_entity_components_mut(child_entity).unwrap();
-            child = *hierachy_node.0.previous_sibling();
-            update_descendent_transforms(commands, query, child_entity, &my_global_matrix);
+        if let Some(parent_transform) = parent_transform {
+            matrix = parent_transform.model() * matrix;
         }
+        parent = *parent_node.parent();
     }
+    matrix
 }
 
 /// Parents to the parent and preserves the child's world-space transform.
@@ -282,8 +267,8 @@ pub fn set_parent(world: &mut World, parent: Option<Entity>, child: Entity) {
 }
 
 pub fn raycast_scene(world: &mut World, ray: Ray3) -> Option<Entity> {
-    (|meshes: &Assets<Mesh>, entities: Query<(&GlobalTransform, &Handle<Mesh>)>| {
-        raycast_entities(ray, meshes, entities.entities_and_components())
+    (|meshes: &Assets<Mesh>, entities: Query<(Entity, &GlobalTransform, &Handle<Mesh>)>| {
+        raycast_entities(ray, meshes, entities.iter())
     })
     .run(world)
 }
@@ -291,11 +276,11 @@ pub fn raycast_scene(world: &mut World, ray: Ray3) -> Option<Entity> {
 pub fn raycast_entities<'a>(
     ray: Ray3,
     meshes: &Assets<Mesh>,
-    entities: impl Iterator<Item = (&'a Entity, (&'a GlobalTransform, &'a Handle<Mesh>))>,
+    entities: impl Iterator<Item = (Entity, &'a GlobalTransform, &'a Handle<Mesh>)>,
 ) -> Option<Entity> {
     let mut closest_value = f32::MAX;
     let mut intersected_entity = None;
-    for (entity, (transform, mesh_handle)) in entities {
+    for (entity, transform, mesh_handle) in entities {
         let mesh = meshes.get(mesh_handle);
         if let Some(bounding_box) = mesh.bounding_box {
             if let Some(mesh_data) = mesh.mesh_data.as_ref() {
@@ -313,7 +298,7 @@ pub fn raycast_entities<'a>(
                         {
                             if v < closest_value {
                                 closest_value = v;
-                                intersected_entity = Some(*entity)
+                                intersected_entity = Some(entity)
                             }
                         }
                     }
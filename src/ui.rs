@@ -207,7 +207,7 @@ impl UIManager {
     }
 
     pub fn render_ui(&mut self, world: &mut World) {
-        let mut commands = Commands::new();
+        let mut commands = CommandQueue::new();
 
         (|graphics: &mut Graphics,
           meshes: &mut Assets<Mesh>,
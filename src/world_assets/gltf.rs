@@ -1,7 +1,7 @@
 use kgltf::AccessorComponentType;
 
 use crate::*;
-use std::{convert::TryInto, path::Path};
+use std::{collections::HashSet, convert::TryInto, path::Path};
 
 #[allow(clippy::too_many_arguments)]
 pub(super) fn load_gltf_as_world(
@@ -21,12 +21,14 @@ pub(super) fn load_gltf_as_world(
     for extension in &gltf.extensions_required {
         match extension.as_str() {
             "KHR_materials_unlit" => {}
-            _ => panic!("Unsupported Gltf extension: {}", extension),
+            _ => {
+                klog::log!("Unsupported Gltf extension: {}", extension);
+                return None;
+            }
         }
     }
 
-    let scene = gltf.scene.unwrap();
-    let scene = &gltf.scenes[scene];
+    let scene = gltf.scenes.get(gltf.scene.unwrap_or(0))?;
 
     let mut texture_load_states = vec![
         TextureLoadState {
@@ -36,10 +38,20 @@ pub(super) fn load_gltf_as_world(
         gltf.textures.len()
     ];
 
+    // Vertex colors are only applied by materials with the `VERTEX_COLORS` keyword.
+    let vertex_colored_materials: HashSet<usize> = gltf
+        .meshes
+        .iter()
+        .flat_map(|mesh| &mesh.primitives)
+        .filter(|primitive| primitive.attributes.contains_key("COLOR_0"))
+        .filter_map(|primitive| primitive.material)
+        .collect();
+
     let gltf_materials: Vec<_> = gltf
         .materials
         .iter()
-        .map(|material| {
+        .enumerate()
+        .map(|(i, material)| {
             let mut pbr_properties = PBRProperties::default();
             if let Some(pbr_metallic_roughness) = &material.pbr_metallic_roughness {
                 let base_color = pbr_metallic_roughness.base_color_factor;
@@ -113,12 +125,12 @@ pub(super) fn load_gltf_as_world(
                 kgltf::MaterialAlphaMode::Blend => true,
                 kgltf::MaterialAlphaMode::Opaque => false,
                 kgltf::MaterialAlphaMode::Mask => {
-                    klog::log!("NOT YET HANDLED GLTF MASK MATERIAL");
-                    true
+                    pbr_properties.alpha_cutoff = Some(material.alpha_cutoff);
+                    false
                 }
             };
 
-            let material = if unlit {
+            let mut material = if unlit {
                 let mut material = new_pbr_material(Shader::UNLIT, pbr_properties);
                 material.set_vec2("p_texture_coordinate_offset", Vec2::ZERO);
                 material.set_vec2("p_texture_coordinate_scale", Vec2::ONE);
@@ -132,6 +144,10 @@ pub(super) fn load_gltf_as_world(
                 };
                 new_pbr_material(shader, pbr_properties)
             };
+            material.set_keyword(
+                shader_keywords::VERTEX_COLORS,
+                vertex_colored_materials.contains(&i),
+            );
 
             materials.add(material)
         })
@@ -141,9 +157,20 @@ pub(super) fn load_gltf_as_world(
 
     for mesh_primitive_data in &mesh_primitive_data {
         let mut primitives = Vec::with_capacity(mesh_primitive_data.primitives.len());
-        for (mesh_data, material_index) in &mesh_primitive_data.primitives {
+        for (mesh_data, lod_mesh_data, material_index) in &mesh_primitive_data.primitives {
             let new_mesh = meshes.add(Mesh::new(graphics, mesh_data.clone()));
-            primitives.push((new_mesh, *material_index));
+            let level_of_detail = if lod_mesh_data.is_empty() {
+                None
+            } else {
+                let lod_meshes: Vec<_> = lod_mesh_data
+                    .iter()
+                    .map(|mesh_data| meshes.add(Mesh::new(graphics, mesh_data.clone())))
+                    .collect();
+                Some(LevelOfDetail::from_meshes(
+                    std::iter::once(new_mesh.clone()).chain(lod_meshes),
+                ))
+            };
+            primitives.push((new_mesh, level_of_detail, *material_index));
         }
         mesh_primitives.push(primitives);
     }
@@ -160,10 +187,10 @@ pub(super) fn load_gltf_as_world(
         )
     }
 
-    let commands_entity = gltf_world.spawn(Commands::new());
+    let commands_entity = gltf_world.spawn(CommandQueue::new());
     crate::transform::update_root_global_transforms.run(&gltf_world);
     let mut commands = gltf_world
-        .remove_component::<Commands>(commands_entity)
+        .remove_component::<CommandQueue>(commands_entity)
         .unwrap();
     commands.apply(&mut gltf_world);
     commands.clear();
@@ -171,7 +198,7 @@ pub(super) fn load_gltf_as_world(
     let commands_entity = gltf_world.spawn(commands);
     crate::transform::update_global_transforms.run(&gltf_world);
     let mut commands = gltf_world
-        .remove_component::<Commands>(commands_entity)
+        .remove_component::<CommandQueue>(commands_entity)
         .unwrap();
     commands.apply(&mut gltf_world);
     commands.clear();
@@ -182,9 +209,9 @@ pub(super) fn load_gltf_as_world(
     // Doing this makes sense for static geometry like level objects, but doesn't make sense for GlTfs which would have
     // individual animated components.
     /*
-    let commands_entity = gltf_world.spawn(Commands::new());
+    let commands_entity = gltf_world.spawn(CommandQueue::new());
     let mut commands = gltf_world
-        .remove_component::<Commands>(commands_entity)
+        .remove_component::<CommandQueue>(commands_entity)
         .unwrap();
     commands.apply(&mut gltf_world);
     commands.clear();
@@ -197,9 +224,9 @@ pub(super) fn load_gltf_as_world(
         }
     })
     .run(&gltf_world);
-    (|entities_with_hierarchy: Query<&HierarchyNode>| {
-        for (entity, _) in entities_with_hierarchy.entities_and_components() {
-            commands.remove_component::<HierarchyNode>(entity.clone());
+    (|entities_with_hierarchy: Query<Entity, With<HierarchyNode>>| {
+        for entity in &entities_with_hierarchy {
+            commands.remove_component::<HierarchyNode>(entity);
         }
     })
     .run(&gltf_world);
@@ -210,15 +237,16 @@ pub(super) fn load_gltf_as_world(
 }
 
 pub(super) struct MeshPrimitiveData {
-    /// The data for this mesh and its material attributes
+    /// The data for this mesh, its simplified levels of detail, and its material attributes
     // The way this is structured means that multiple things that share attributes will duplicate the attribute data.
-    primitives: Vec<(MeshData, Option<usize>)>,
+    primitives: Vec<(MeshData, Vec<MeshData>, Option<usize>)>,
 }
 
 pub(super) async fn load_mesh_primitive_data(
     path: &str,
     gltf: &kgltf::GlTf,
     data: Option<&[u8]>,
+    level_of_detail: Option<LevelOfDetailGeneration>,
 ) -> Vec<MeshPrimitiveData> {
     let mut buffers = Vec::with_capacity(gltf.buffers.len());
     for buffer in &gltf.buffers {
@@ -239,7 +267,11 @@ pub(super) async fn load_mesh_primitive_data(
             let mut positions = None;
             let mut normals = None;
             let mut texture_coordinates = None;
+            let mut texture_coordinates_1 = None;
+            let mut tangents = None;
             let mut colors = None;
+            let mut joints = None;
+            let mut weights = None;
 
             for (attribute, accessor_index) in &primitive.attributes {
                 // https://github.com/KhronosGroup/glTF/tree/master/specification/2.0#meshes
@@ -257,39 +289,28 @@ pub(super) async fn load_mesh_primitive_data(
                         );
                     }
                     "TEXCOORD_0" => {
-                        texture_coordinates = Some(match accessor_component_type {
-                            AccessorComponentType::UnsignedByte => {
-                                get_buffer::<Vector<u8, 2>, _, _>(
-                                    gltf,
-                                    &data,
-                                    &buffers,
-                                    *accessor_index,
-                                    |b| b.map(|v| *v as f32 / (u8::MAX as f32)),
-                                )
-                                .await
-                            }
-                            AccessorComponentType::UnsignedShort => {
-                                get_buffer::<Vector<u16, 2>, _, _>(
-                                    gltf,
-                                    &data,
-                                    &buffers,
-                                    *accessor_index,
-                                    |b| b.map(|v| *v as f32 / (u16::MAX as f32)),
-                                )
-                                .await
-                            }
-                            AccessorComponentType::Float => {
-                                get_buffer::<Vec2, _, _>(
-                                    gltf,
-                                    &data,
-                                    &buffers,
-                                    *accessor_index,
-                                    |v| v,
-                                )
-                                .await
-                            }
-                            _ => unimplemented!(),
-                        });
+                        texture_coordinates = Some(
+                            get_texture_coordinates(
+                                gltf,
+                                &data,
+                                &buffers,
+                                *accessor_index,
+                                accessor_component_type,
+                            )
+                            .await,
+                        );
+                    }
+                    "TEXCOORD_1" => {
+                        texture_coordinates_1 = Some(
+                            get_texture_coordinates(
+                                gltf,
+                                &data,
+                                &buffers,
+                                *accessor_index,
+                                accessor_component_type,
+                            )
+                            .await,
+                        );
                     }
                     "NORMAL" => {
                         normals = Some(
@@ -375,10 +396,72 @@ pub(super) async fn load_mesh_primitive_data(
                             _ => unimplemented!(),
                         }
                     }
-                    "TANGENT" => {}
-                    "TEXCOORD_1" => {}
-                    "JOINTS_0" => {}
-                    "WEIGHTS_0" => {}
+                    "TANGENT" => {
+                        tangents = Some(
+                            get_buffer::<Vec4, _, _>(gltf, &data, &buffers, *accessor_index, |v| v)
+                                .await,
+                        );
+                    }
+                    "JOINTS_0" => {
+                        joints = Some(match accessor_component_type {
+                            AccessorComponentType::UnsignedByte => {
+                                get_buffer::<Vector<u8, 4>, _, _>(
+                                    gltf,
+                                    &data,
+                                    &buffers,
+                                    *accessor_index,
+                                    |b| b.map(|v| *v as f32),
+                                )
+                                .await
+                            }
+                            AccessorComponentType::UnsignedShort => {
+                                get_buffer::<Vector<u16, 4>, _, _>(
+                                    gltf,
+                                    &data,
+                                    &buffers,
+                                    *accessor_index,
+                                    |b| b.map(|v| *v as f32),
+                                )
+                                .await
+                            }
+                            _ => unimplemented!(),
+                        });
+                    }
+                    "WEIGHTS_0" => {
+                        weights = Some(match accessor_component_type {
+                            AccessorComponentType::Float => {
+                                get_buffer::<Vec4, _, _>(
+                                    gltf,
+                                    &data,
+                                    &buffers,
+                                    *accessor_index,
+                                    |v| v,
+                                )
+                                .await
+                            }
+                            AccessorComponentType::UnsignedByte => {
+                                get_buffer::<Vector<u8, 4>, _, _>(
+                                    gltf,
+                                    &data,
+                                    &buffers,
+                                    *accessor_index,
+                                    |b| b.map(|v| *v as f32 / (u8::MAX as f32)),
+                                )
+                                .await
+                            }
+                            AccessorComponentType::UnsignedShort => {
+                                get_buffer::<Vector<u16, 4>, _, _>(
+                                    gltf,
+                                    &data,
+                                    &buffers,
+                                    *accessor_index,
+                                    |b| b.map(|v| *v as f32 / (u16::MAX as f32)),
+                                )
+                                .await
+                            }
+                            _ => unimplemented!(),
+                        });
+                    }
                     _ => {} // Unimplemented
                 }
             }
@@ -386,15 +469,29 @@ pub(super) async fn load_mesh_primitive_data(
             if let Some(indices) = primitive.indices {
                 let indices = get_indices(gltf, &data, &buffers, indices).await;
 
-                let mesh_data = MeshData {
+                let mut mesh_data = MeshData {
                     positions: positions.unwrap(),
                     normals: normals.unwrap_or_else(Vec::new),
                     texture_coordinates: texture_coordinates.unwrap_or_else(Vec::new),
+                    texture_coordinates_1: texture_coordinates_1.unwrap_or_else(Vec::new),
+                    tangents: tangents.unwrap_or_else(Vec::new),
                     colors: colors.unwrap_or_else(Vec::new),
+                    joints: joints.unwrap_or_else(Vec::new),
+                    weights: weights.unwrap_or_else(Vec::new),
                     indices,
                 };
 
-                primitives.push((mesh_data, primitive.material))
+                // The glTF spec asks for MikkTSpace tangents when a primitive doesn't provide them.
+                if mesh_data.tangents.is_empty() {
+                    mesh_data.generate_tangents();
+                }
+
+                // Simplifying here keeps the work off the main thread.
+                let lod_mesh_data = level_of_detail.map_or_else(Vec::new, |l| {
+                    mesh_data.generate_lod_chain(l.levels, l.triangle_ratio)
+                });
+
+                primitives.push((mesh_data, lod_mesh_data, primitive.material))
             } else {
                 klog::log!("Warning: GLTF primitive does not have indices.");
             }
@@ -404,6 +501,9 @@ pub(super) async fn load_mesh_primitive_data(
     meshes
 }
 
+/// A primitive's [Mesh], its [LevelOfDetail] if it has one, and the index of its material.
+type MeshPrimitive = (Handle<Mesh>, Option<LevelOfDetail>, Option<usize>);
+
 #[derive(Clone)]
 struct TextureLoadState {
     linear: Option<Handle<Texture>>,
@@ -475,7 +575,7 @@ fn initialize_nodes(
     gltf_world: &mut World,
     materials: &Assets<Material>,
     gltf_materials: &[Handle<Material>],
-    mesh_primitives: &[Vec<(Handle<Mesh>, Option<usize>)>],
+    mesh_primitives: &[Vec<MeshPrimitive>],
     nodes: &[kgltf::Node],
     node: usize,
     parent: Option<Entity>,
@@ -509,7 +609,7 @@ fn initialize_nodes(
         } else */
         {
             let entity_root = gltf_world.spawn((transform,));
-            for (mesh, material_index) in mesh_primitives {
+            for (mesh, level_of_detail, material_index) in mesh_primitives {
                 let material_handle =
                     material_index.map_or_else(Handle::default, |i| gltf_materials[i].clone());
                 let primitive_entity = gltf_world.spawn((
@@ -518,6 +618,11 @@ fn initialize_nodes(
                     RenderFlags::DEFAULT,
                     Transform::new(),
                 ));
+                if let Some(level_of_detail) = level_of_detail {
+                    gltf_world
+                        .add_component(primitive_entity, level_of_detail.clone())
+                        .unwrap();
+                }
                 HierarchyNode::set_parent(gltf_world, Some(entity_root), primitive_entity).unwrap();
             }
             entity_root
@@ -601,6 +706,34 @@ async fn get_indices(
     }
 }
 
+/// Texture coordinates can be stored as normalized integers or floats.
+async fn get_texture_coordinates(
+    gltf: &kgltf::GlTf,
+    data: &Option<&[u8]>,
+    buffers: &[Option<Vec<u8>>],
+    accessor: usize,
+    component_type: AccessorComponentType,
+) -> Vec<Vec2> {
+    match component_type {
+        AccessorComponentType::UnsignedByte => {
+            get_buffer::<Vector<u8, 2>, _, _>(gltf, data, buffers, accessor, |b| {
+                b.map(|v| *v as f32 / (u8::MAX as f32))
+            })
+            .await
+        }
+        AccessorComponentType::UnsignedShort => {
+            get_buffer::<Vector<u16, 2>, _, _>(gltf, data, buffers, accessor, |b| {
+                b.map(|v| *v as f32 / (u16::MAX as f32))
+            })
+            .await
+        }
+        AccessorComponentType::Float => {
+            get_buffer::<Vec2, _, _>(gltf, data, buffers, accessor, |v| v).await
+        }
+        _ => unimplemented!(),
+    }
+}
+
 async fn get_buffer<T: Copy, TOut, F: FnMut(T) -> TOut>(
     gltf: &kgltf::GlTf,
     data: &Option<&[u8]>,
@@ -1,5 +1,6 @@
 use crate::*;
-use std::sync::mpsc;
+use std::collections::HashMap;
+use std::sync::{mpsc, Arc};
 
 #[cfg(feature = "gltf")]
 mod gltf;
@@ -40,7 +41,7 @@ fn load_prefabs_system(
     while let Ok(PrefabLoadMessage {
         world_load_message_data,
         handle,
-        options,
+        run_on_world,
     }) = worlds.asset_loader.receiver.inner().try_recv()
     {
         let world: Option<World> = match world_load_message_data {
@@ -62,11 +63,19 @@ fn load_prefabs_system(
             ),
         };
 
-        let mut world = world.unwrap();
-        if let Some(run_on_world) = options.run_on_world {
+        // A world that fails to load leaves the previous one, or the placeholder, in place.
+        let mut world = match world {
+            Some(world) => world,
+            None => {
+                klog::log!("FAILED TO LOAD GLTF");
+                continue;
+            }
+        };
+        if let Some(run_on_world) = run_on_world {
             run_on_world(&mut world);
         }
-        worlds.replace_placeholder(&handle, world);
+        // Copies of a reloaded world that were already spawned are not updated.
+        worlds.replace(&handle, world);
     }
 }
 
@@ -76,23 +85,23 @@ fn load_prefabs_system(
 fn delayed_spawn_system(world: &mut World) {
     let mut worlds_to_add = Vec::new();
 
-    (|spawn_when_loaded: Query<(&Handle<World>,)>, worlds: &mut Assets<World>| {
-        for (entity, world) in spawn_when_loaded.entities_and_components() {
+    (|spawn_when_loaded: Query<(Entity, &Handle<World>)>, worlds: &mut Assets<World>| {
+        for (entity, world) in &spawn_when_loaded {
             if !worlds.is_placeholder(world) {
                 let new_world = worlds.get_mut(world).clone_world();
-                worlds_to_add.push((*entity, new_world));
+                worlds_to_add.push((entity, new_world));
             }
         }
     })
     .run(world);
 
     for (parent_entity, mut new_world) in worlds_to_add {
-        let top_level_nodes: Vec<Entity> = (|transform_nodes: Query<&HierarchyNode>| {
+        let top_level_nodes: Vec<Entity> = (|transform_nodes: Query<(Entity, &HierarchyNode)>| {
             transform_nodes
-                .entities_and_components()
+                .iter()
                 .filter_map(|(entity, hierarchy_node)| {
                     if hierarchy_node.parent().is_none() {
-                        Some(*entity)
+                        Some(entity)
                     } else {
                         None
                     }
@@ -116,16 +125,38 @@ fn delayed_spawn_system(world: &mut World) {
 #[derive(Default)]
 pub struct LoadWorldOptions {
     pub run_on_world: Option<Box<dyn Fn(&mut World) + Send + Sync>>,
+    /// If set simplified versions of each mesh are generated and used with a `LevelOfDetail`.
+    pub level_of_detail: Option<LevelOfDetailGeneration>,
+}
+
+/// How simplified meshes are generated for a loaded world.
+#[derive(Clone, Copy, Debug)]
+pub struct LevelOfDetailGeneration {
+    /// How many simplified levels are generated in addition to the original mesh.
+    pub levels: usize,
+    /// Each level has roughly this fraction of the previous level's triangles.
+    pub triangle_ratio: f32,
+}
+
+impl Default for LevelOfDetailGeneration {
+    fn default() -> Self {
+        Self {
+            levels: 3,
+            triangle_ratio: 0.5,
+        }
+    }
 }
 
 impl AssetTrait for World {
     type AssetLoader = WorldLoader;
 }
 
+type RunOnWorld = Arc<dyn Fn(&mut World) + Send + Sync>;
+
 struct PrefabLoadMessage {
     world_load_message_data: PrefabLoadMessageData,
     handle: Handle<World>,
-    options: LoadWorldOptions,
+    run_on_world: Option<RunOnWorld>,
 }
 
 enum PrefabLoadMessageData {
@@ -141,6 +172,8 @@ enum PrefabLoadMessageData {
 pub struct WorldLoader {
     sender: SyncGuard<mpsc::Sender<PrefabLoadMessage>>,
     receiver: SyncGuard<mpsc::Receiver<PrefabLoadMessage>>,
+    /// The options each path was loaded with, used when reloading.
+    options: HashMap<String, (Option<RunOnWorld>, Option<LevelOfDetailGeneration>)>,
 }
 
 impl WorldLoader {
@@ -149,35 +182,30 @@ impl WorldLoader {
         Self {
             sender: SyncGuard::new(sender),
             receiver: SyncGuard::new(receiver),
+            options: HashMap::new(),
         }
     }
-}
 
-impl AssetLoaderTrait<World> for WorldLoader {
-    type Options = LoadWorldOptions;
-    #[allow(unused_variables)]
-    fn load_with_options(
+    fn load(
         &mut self,
         path: &str,
         handle: crate::Handle<World>,
-        options: Self::Options,
+        run_on_world: Option<RunOnWorld>,
+        level_of_detail: Option<LevelOfDetailGeneration>,
     ) {
+        self.options
+            .insert(path.to_owned(), (run_on_world.clone(), level_of_detail));
         let path = path.to_owned();
         let sender = self.sender.inner().clone();
 
-        let extension = std::path::Path::new(&path)
-            .extension()
-            .and_then(std::ffi::OsStr::to_str)
-            .unwrap();
-
         ktasks::spawn(async move {
-            let result = load_world(&path).await;
+            let result = load_world(&path, level_of_detail).await;
             match result {
                 Ok(world_load_message_data) => {
                     let _ = sender.send(PrefabLoadMessage {
                         handle,
                         world_load_message_data,
-                        options,
+                        run_on_world,
                     });
                 }
                 Err(e) => {
@@ -187,6 +215,30 @@ impl AssetLoaderTrait<World> for WorldLoader {
         })
         .run();
     }
+}
+
+impl AssetLoaderTrait<World> for WorldLoader {
+    type Options = LoadWorldOptions;
+    fn load_with_options(
+        &mut self,
+        path: &str,
+        handle: crate::Handle<World>,
+        options: Self::Options,
+    ) {
+        self.load(
+            path,
+            handle,
+            options.run_on_world.map(Arc::from),
+            options.level_of_detail,
+        );
+    }
+
+    fn reload(&mut self, path: &str, handle: crate::Handle<World>) {
+        if let Some((run_on_world, level_of_detail)) = self.options.get(path).cloned() {
+            self.load(path, handle, run_on_world, level_of_detail);
+        }
+    }
+
     fn load_with_data_and_options_and_extension(
         &mut self,
         data: Vec<u8>,
@@ -195,14 +247,17 @@ impl AssetLoaderTrait<World> for WorldLoader {
         options: Self::Options,
     ) {
         let sender = self.sender.inner().clone();
+        let level_of_detail = options.level_of_detail;
+        let run_on_world: Option<RunOnWorld> = options.run_on_world.map(Arc::from);
         ktasks::spawn(async move {
-            let result = load_world_from_bytes_and_extension(&data, "", &extension).await;
+            let result =
+                load_world_from_bytes_and_extension(&data, "", &extension, level_of_detail).await;
             match result {
                 Ok(world_load_message_data) => {
                     let _ = sender.send(PrefabLoadMessage {
                         handle,
                         world_load_message_data,
-                        options,
+                        run_on_world,
                     });
                 }
                 Err(e) => {
@@ -219,6 +274,7 @@ async fn load_world_from_bytes_and_extension(
     bytes: &[u8],
     path: &str,
     extension: &str,
+    level_of_detail: Option<LevelOfDetailGeneration>,
 ) -> Result<PrefabLoadMessageData, WorldLoadError> {
     #[allow(unreachable_code)]
     Ok(match extension {
@@ -227,7 +283,7 @@ async fn load_world_from_bytes_and_extension(
             let glb = kgltf::GLB::from_bytes(bytes).map_err(|_| WorldLoadError::CouldNotDecode)?;
             let data = glb.binary_data.map(|d| d.into_owned());
             let mesh_primitive_data =
-                load_mesh_primitive_data(path, &glb.gltf, data.as_deref()).await;
+                load_mesh_primitive_data(path, &glb.gltf, data.as_deref(), level_of_detail).await;
 
             PrefabLoadMessageData::GlTf {
                 path: path.to_string(),
@@ -245,7 +301,8 @@ async fn load_world_from_bytes_and_extension(
             let gltf = kgltf::GlTf::from_json(s).ok_or(WorldLoadError::CouldNotDecode)?;
             //  klog::log!("ABOUT TO DECODE GLTF1");
 
-            let mesh_primitive_data = load_mesh_primitive_data(path, &gltf, None).await;
+            let mesh_primitive_data =
+                load_mesh_primitive_data(path, &gltf, None, level_of_detail).await;
 
             //   klog::log!("DECODED GLTF, SENDING RETURN MESSAGE");
             PrefabLoadMessageData::GlTf {
@@ -262,7 +319,10 @@ async fn load_world_from_bytes_and_extension(
     })
 }
 #[allow(dead_code, unused_variables)]
-async fn load_world(path: &str) -> Result<PrefabLoadMessageData, WorldLoadError> {
+async fn load_world(
+    path: &str,
+    level_of_detail: Option<LevelOfDetailGeneration>,
+) -> Result<PrefabLoadMessageData, WorldLoadError> {
     let extension = std::path::Path::new(&path)
         .extension()
         .and_then(std::ffi::OsStr::to_str)
@@ -271,11 +331,11 @@ async fn load_world(path: &str) -> Result<PrefabLoadMessageData, WorldLoadError>
     let bytes = crate::fetch_bytes(path)
         .await
         .map_err(|_| WorldLoadError::CouldNotLoadFile)?;
-    load_world_from_bytes_and_extension(&bytes, path, extension).await
+    load_world_from_bytes_and_extension(&bytes, path, extension, level_of_detail).await
 }
 
 pub fn flatten_world(world: &mut World) {
-    let mut commands = Commands::new();
+    let mut commands = CommandQueue::new();
 
     (|mut transforms: Query<(&mut Transform, &GlobalTransform)>| {
         for (local_transform, global_transform) in transforms.iter_mut() {
@@ -283,9 +343,9 @@ pub fn flatten_world(world: &mut World) {
         }
     })
     .run(world);
-    (|entities_with_hierarchy: Query<&HierarchyNode>| {
-        for (entity, _) in entities_with_hierarchy.entities_and_components() {
-            commands.remove_component::<HierarchyNode>(*entity);
+    (|entities_with_hierarchy: Query<Entity, With<HierarchyNode>>| {
+        for entity in &entities_with_hierarchy {
+            commands.remove_component::<HierarchyNode>(entity);
         }
     })
     .run(world);